@@ -0,0 +1,667 @@
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use flate2::read::GzDecoder;
+use quick_xml::Reader;
+use quick_xml::events::{BytesRef, BytesStart, Event};
+use reqwest::Client;
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::tmdb::Episode;
+
+const ANIDB_API_URL: &str = "http://api.anidb.net:9001/httpapi";
+const ANIDB_TITLES_URL: &str = "https://anidb.net/api/anime-titles.xml.gz";
+// AniDB bans clients that fetch the titles dump more than once a day
+const TITLES_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Jikan is an unofficial, keyless API over MyAnimeList data
+const JIKAN_API_URL: &str = "https://api.jikan.moe/v4";
+// Jikan allows 3 requests per second
+const JIKAN_PAGE_DELAY: Duration = Duration::from_millis(400);
+const JIKAN_MAX_PAGES: u32 = 30;
+
+const MAX_SEARCH_RESULTS: usize = 10;
+
+#[derive(Error, Debug)]
+pub enum AnimeError {
+    #[error("request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("xml parse error: {0}")]
+    XmlError(#[from] quick_xml::Error),
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("AniDB error: {0}")]
+    ApiError(String),
+    #[error("no results found")]
+    NotFound,
+}
+
+/// Anime found by a title search
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimeResult {
+    pub id: u64,
+    pub title: String,
+    pub year: Option<u16>,
+    pub episodes: Option<u32>,
+}
+
+/// Anime episode source selected by `anime.source`
+pub enum AnimeClient {
+    Anidb(AnidbClient),
+    Mal(MalClient),
+}
+
+impl AnimeClient {
+    pub async fn search_anime(&self, query: &str) -> Result<Vec<AnimeResult>, AnimeError> {
+        match self {
+            AnimeClient::Anidb(client) => client.search_anime(query).await,
+            AnimeClient::Mal(client) => client.search_anime(query).await,
+        }
+    }
+
+    pub async fn get_episodes(&self, anime_id: u64) -> Result<Vec<Episode>, AnimeError> {
+        match self {
+            AnimeClient::Anidb(client) => client.get_episodes(anime_id).await,
+            AnimeClient::Mal(client) => client.get_episodes(anime_id).await,
+        }
+    }
+
+    /// Find the anime matching a TMDB show and fetch its (absolutely numbered) episodes
+    pub async fn find_episodes(
+        &self,
+        title: &str,
+        year: Option<u16>,
+    ) -> Result<Vec<Episode>, AnimeError> {
+        let results = self.search_anime(title).await?;
+        let best = results
+            .iter()
+            .find(|r| year.is_some() && r.year == year)
+            .or(results.first())
+            .ok_or(AnimeError::NotFound)?;
+
+        debug!(title, anime_id = best.id, matched = %best.title, "matched anime");
+
+        let episodes = self.get_episodes(best.id).await?;
+        if episodes.is_empty() {
+            return Err(AnimeError::NotFound);
+        }
+        Ok(episodes)
+    }
+}
+
+/// AniDB HTTP API client. Requires a client registered at anidb.net.
+pub struct AnidbClient {
+    client: Client,
+    client_name: String,
+    client_version: u32,
+    api_url: String,
+    titles_url: String,
+    titles_cache: PathBuf,
+}
+
+impl AnidbClient {
+    pub fn new(client_name: &str, client_version: u32, titles_cache: PathBuf) -> Self {
+        Self::with_base_urls(
+            client_name,
+            client_version,
+            titles_cache,
+            ANIDB_API_URL,
+            ANIDB_TITLES_URL,
+        )
+    }
+
+    /// Create a client with custom URLs (for testing)
+    pub fn with_base_urls(
+        client_name: &str,
+        client_version: u32,
+        titles_cache: PathBuf,
+        api_url: &str,
+        titles_url: &str,
+    ) -> Self {
+        Self {
+            client: crate::http::client(),
+            client_name: client_name.to_string(),
+            client_version,
+            api_url: api_url.to_string(),
+            titles_url: titles_url.to_string(),
+            titles_cache,
+        }
+    }
+
+    /// Search the AniDB titles dump (AniDB has no search API)
+    pub async fn search_anime(&self, query: &str) -> Result<Vec<AnimeResult>, AnimeError> {
+        let titles = self.load_titles().await?;
+        search_anidb_titles(&titles, query)
+    }
+
+    /// Get the regular episodes of an anime
+    pub async fn get_episodes(&self, anime_id: u64) -> Result<Vec<Episode>, AnimeError> {
+        let url = format!(
+            "{}?request=anime&client={}&clientver={}&protover=1&aid={}",
+            self.api_url,
+            urlencoding::encode(&self.client_name),
+            self.client_version,
+            anime_id
+        );
+
+        debug!(anime_id, "fetching AniDB episodes");
+
+        let xml = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        parse_anidb_episodes(&xml)
+    }
+
+    /// Read the titles dump from cache, downloading it at most once a day
+    async fn load_titles(&self) -> Result<String, AnimeError> {
+        let is_fresh = std::fs::metadata(&self.titles_cache)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age < TITLES_MAX_AGE);
+
+        if is_fresh {
+            return Ok(std::fs::read_to_string(&self.titles_cache)?);
+        }
+
+        debug!(url = %self.titles_url, "downloading AniDB titles dump");
+
+        let bytes = self
+            .client
+            .get(&self.titles_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        // The dump is a .gz file, but may already be decoded if served with Content-Encoding
+        let xml = if bytes.starts_with(&[0x1f, 0x8b]) {
+            let mut xml = String::new();
+            GzDecoder::new(&bytes[..]).read_to_string(&mut xml)?;
+            xml
+        } else {
+            String::from_utf8_lossy(&bytes).into_owned()
+        };
+
+        if let Some(parent) = self.titles_cache.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.titles_cache, &xml)?;
+
+        Ok(xml)
+    }
+}
+
+/// MyAnimeList client (through the Jikan API, no key needed)
+pub struct MalClient {
+    client: Client,
+    base_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanSearchResponse {
+    data: Vec<JikanAnime>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanAnime {
+    mal_id: u64,
+    title: String,
+    episodes: Option<u32>,
+    year: Option<u16>,
+    aired: Option<JikanAired>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanAired {
+    from: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanEpisodesResponse {
+    data: Vec<JikanEpisode>,
+    pagination: JikanPagination,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanEpisode {
+    mal_id: u32, // Episode number
+    title: Option<String>,
+    aired: Option<String>,
+    score: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanPagination {
+    has_next_page: bool,
+}
+
+impl MalClient {
+    pub fn new() -> Self {
+        Self::with_base_url(JIKAN_API_URL)
+    }
+
+    /// Create a client with a custom base URL (for testing)
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self {
+            client: crate::http::client(),
+            base_url: base_url.to_string(),
+        }
+    }
+
+    pub async fn search_anime(&self, query: &str) -> Result<Vec<AnimeResult>, AnimeError> {
+        let url = format!(
+            "{}/anime?q={}&limit={}",
+            self.base_url,
+            urlencoding::encode(query),
+            MAX_SEARCH_RESULTS
+        );
+
+        debug!(query, "searching MyAnimeList");
+
+        let response: JikanSearchResponse = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(response
+            .data
+            .into_iter()
+            .map(|a| {
+                let year = a.year.or_else(|| {
+                    a.aired
+                        .and_then(|aired| aired.from)
+                        .and_then(|from| from.get(..4)?.parse().ok())
+                });
+                AnimeResult {
+                    id: a.mal_id,
+                    title: a.title,
+                    year,
+                    episodes: a.episodes,
+                }
+            })
+            .collect())
+    }
+
+    /// Get all episodes of an anime (Jikan pages them 100 at a time)
+    pub async fn get_episodes(&self, anime_id: u64) -> Result<Vec<Episode>, AnimeError> {
+        let mut episodes = Vec::new();
+
+        for page in 1..=JIKAN_MAX_PAGES {
+            if page > 1 {
+                tokio::time::sleep(JIKAN_PAGE_DELAY).await;
+            }
+
+            let url = format!(
+                "{}/anime/{}/episodes?page={}",
+                self.base_url, anime_id, page
+            );
+
+            debug!(anime_id, page, "fetching MyAnimeList episodes");
+
+            let response: JikanEpisodesResponse = self
+                .client
+                .get(&url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            episodes.extend(response.data.into_iter().map(|e| Episode {
+                id: e.mal_id as u64,
+                name: e.title.unwrap_or_else(|| format!("Episode {}", e.mal_id)),
+                episode_number: e.mal_id,
+                season_number: 1,
+                air_date: e.aired.and_then(|d| d.get(..10).map(String::from)),
+                overview: None,
+                still_path: None,
+                runtime: None,
+                vote_average: e.score,
+            }));
+
+            if !response.pagination.has_next_page {
+                break;
+            }
+        }
+
+        Ok(episodes)
+    }
+}
+
+impl Default for MalClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Search an AniDB titles dump, best matches first (exact, then prefix, then substring)
+pub fn search_anidb_titles(xml: &str, query: &str) -> Result<Vec<AnimeResult>, AnimeError> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut reader = Reader::from_str(xml);
+    // (rank, result) - lower rank is a better match
+    let mut matches: Vec<(u8, AnimeResult)> = Vec::new();
+
+    let mut current_id: Option<u64> = None;
+    let mut main_title = String::new();
+    let mut best_rank: Option<u8> = None;
+    let mut is_main = false;
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(ref e)) => match e.name().as_ref() {
+                b"anime" => {
+                    current_id = attr(e, b"aid").and_then(|a| a.parse().ok());
+                    main_title.clear();
+                    best_rank = None;
+                }
+                b"title" => {
+                    is_main = attr(e, b"type").as_deref() == Some("main");
+                    text.clear();
+                }
+                _ => {}
+            },
+            Ok(Event::Text(ref e)) => {
+                text.push_str(&e.decode().unwrap_or_default());
+            }
+            Ok(Event::GeneralRef(ref e)) => push_entity(&mut text, e),
+            Ok(Event::End(ref e)) => match e.name().as_ref() {
+                b"title" => {
+                    let title = text.trim();
+                    if is_main {
+                        main_title = title.to_string();
+                    }
+                    let lower = title.to_lowercase();
+                    let rank = if lower == query {
+                        Some(0)
+                    } else if lower.starts_with(&query) {
+                        Some(1)
+                    } else if lower.contains(&query) {
+                        Some(2)
+                    } else {
+                        None
+                    };
+                    if let Some(rank) = rank {
+                        best_rank = Some(best_rank.map_or(rank, |best| best.min(rank)));
+                    }
+                }
+                b"anime" => {
+                    if let (Some(id), Some(rank)) = (current_id, best_rank) {
+                        matches.push((
+                            rank,
+                            AnimeResult {
+                                id,
+                                title: main_title.clone(),
+                                year: None,
+                                episodes: None,
+                            },
+                        ));
+                    }
+                    current_id = None;
+                }
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(AnimeError::XmlError(e)),
+            _ => {}
+        }
+    }
+
+    matches.sort_by_key(|(rank, result)| (*rank, result.title.len()));
+    Ok(matches
+        .into_iter()
+        .map(|(_, result)| result)
+        .take(MAX_SEARCH_RESULTS)
+        .collect())
+}
+
+/// Parse the regular episodes out of an AniDB `request=anime` response
+pub fn parse_anidb_episodes(xml: &str) -> Result<Vec<Episode>, AnimeError> {
+    let mut reader = Reader::from_str(xml);
+    let mut episodes = Vec::new();
+
+    let mut in_episode = false;
+    let mut text = String::new();
+    let mut title_lang = String::new();
+
+    // Fields of the episode being parsed
+    let mut id: u64 = 0;
+    let mut number: Option<u32> = None;
+    let mut is_regular = false;
+    let mut titles: Vec<(String, String)> = Vec::new(); // (lang, title)
+    let mut air_date: Option<String> = None;
+    let mut runtime: Option<u32> = None;
+    let mut rating: Option<f64> = None;
+    let mut summary: Option<String> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(ref e)) => {
+                text.clear();
+
+                match e.name().as_ref() {
+                    b"episode" => {
+                        in_episode = true;
+                        id = attr(e, b"id").and_then(|a| a.parse().ok()).unwrap_or(0);
+                        number = None;
+                        is_regular = false;
+                        titles.clear();
+                        air_date = None;
+                        runtime = None;
+                        rating = None;
+                        summary = None;
+                    }
+                    // type 1 = regular episode (2 = special, 3 = credits, 4 = trailer, ...)
+                    b"epno" => is_regular = attr(e, b"type").as_deref() == Some("1"),
+                    b"title" => title_lang = attr(e, b"xml:lang").unwrap_or_default(),
+                    _ => {}
+                }
+            }
+            Ok(Event::Text(ref e)) => {
+                text.push_str(&e.decode().unwrap_or_default());
+            }
+            Ok(Event::CData(ref e)) => {
+                text.push_str(&e.decode().unwrap_or_default());
+            }
+            Ok(Event::GeneralRef(ref e)) => push_entity(&mut text, e),
+            Ok(Event::End(ref e)) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                let value = text.trim().to_string();
+                text.clear();
+
+                if name == "error" {
+                    return Err(AnimeError::ApiError(value));
+                }
+
+                if in_episode {
+                    match name.as_str() {
+                        "epno" => number = value.parse().ok(),
+                        "title" => titles.push((title_lang.clone(), value)),
+                        "airdate" if !value.is_empty() => air_date = Some(value),
+                        "length" => runtime = value.parse().ok(),
+                        "rating" => rating = value.parse().ok(),
+                        "summary" if !value.is_empty() => summary = Some(value),
+                        "episode" => {
+                            in_episode = false;
+                            if is_regular && let Some(number) = number {
+                                episodes.push(Episode {
+                                    id,
+                                    name: episode_title(&titles)
+                                        .unwrap_or_else(|| format!("Episode {}", number)),
+                                    episode_number: number,
+                                    season_number: 1,
+                                    air_date: air_date.take(),
+                                    overview: summary.take(),
+                                    still_path: None,
+                                    runtime,
+                                    vote_average: rating,
+                                });
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(AnimeError::XmlError(e)),
+            _ => {}
+        }
+    }
+
+    episodes.sort_by_key(|e| e.episode_number);
+    Ok(episodes)
+}
+
+/// Prefer the English title, then romaji, then whatever comes first
+fn episode_title(titles: &[(String, String)]) -> Option<String> {
+    ["en", "x-jat"]
+        .iter()
+        .find_map(|lang| titles.iter().find(|(l, _)| l == lang))
+        .or(titles.first())
+        .map(|(_, title)| title.clone())
+        .filter(|title| !title.is_empty())
+}
+
+fn attr(e: &BytesStart, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name)
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.into_owned())
+}
+
+/// Entities come through as separate events: "Fool&apos;s Errand"
+fn push_entity(text: &mut String, e: &BytesRef) {
+    if let Ok(Some(c)) = e.resolve_char_ref() {
+        text.push(c);
+    } else {
+        match e.decode().unwrap_or_default().as_ref() {
+            "amp" => text.push('&'),
+            "lt" => text.push('<'),
+            "gt" => text.push('>'),
+            "quot" => text.push('"'),
+            "apos" => text.push('\''),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TITLES_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<animetitles>
+    <anime aid="239">
+        <title type="main" xml:lang="x-jat">Naruto</title>
+        <title type="official" xml:lang="ja">ナルト</title>
+    </anime>
+    <anime aid="4880">
+        <title type="main" xml:lang="x-jat">Naruto: Shippuuden</title>
+        <title type="official" xml:lang="en">Naruto Shippuden</title>
+    </anime>
+    <anime aid="1">
+        <title type="main" xml:lang="x-jat">Seikai no Monshou</title>
+        <title type="official" xml:lang="en">Crest of the Stars</title>
+    </anime>
+</animetitles>"#;
+
+    #[test]
+    fn test_search_anidb_titles_ranking() {
+        let results = search_anidb_titles(TITLES_XML, "naruto").unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, 239);
+        assert_eq!(results[1].id, 4880);
+        assert_eq!(results[1].title, "Naruto: Shippuuden");
+
+        // Matches on non-main titles but reports the main title
+        let results = search_anidb_titles(TITLES_XML, "Crest of the Stars").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "Seikai no Monshou");
+
+        assert!(
+            search_anidb_titles(TITLES_XML, "bleach")
+                .unwrap()
+                .is_empty()
+        );
+        assert!(search_anidb_titles(TITLES_XML, "  ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_anidb_episodes() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<anime id="1" restricted="false">
+    <type>TV Series</type>
+    <episodecount>13</episodecount>
+    <titles>
+        <title xml:lang="x-jat" type="main">Seikai no Monshou</title>
+    </titles>
+    <episodes>
+        <episode id="3" update="2011-07-01">
+            <epno type="1">2</epno>
+            <length>25</length>
+            <airdate>1999-01-10</airdate>
+            <rating votes="10">4.02</rating>
+            <title xml:lang="ja">愚か者</title>
+            <title xml:lang="en">Fool&apos;s Errand</title>
+        </episode>
+        <episode id="2" update="2011-07-01">
+            <epno type="1">1</epno>
+            <length>25</length>
+            <airdate>1999-01-03</airdate>
+            <title xml:lang="x-jat">Shinryaku</title>
+            <summary>Jinto meets Lafiel.</summary>
+        </episode>
+        <episode id="29" update="2011-07-01">
+            <epno type="2">S1</epno>
+            <length>50</length>
+            <title xml:lang="en">Special</title>
+        </episode>
+    </episodes>
+</anime>"#;
+
+        let episodes = parse_anidb_episodes(xml).unwrap();
+        assert_eq!(episodes.len(), 2);
+
+        assert_eq!(episodes[0].episode_number, 1);
+        assert_eq!(episodes[0].id, 2);
+        assert_eq!(episodes[0].name, "Shinryaku");
+        assert_eq!(episodes[0].air_date.as_deref(), Some("1999-01-03"));
+        assert_eq!(episodes[0].overview.as_deref(), Some("Jinto meets Lafiel."));
+
+        assert_eq!(episodes[1].episode_number, 2);
+        assert_eq!(episodes[1].name, "Fool's Errand");
+        assert_eq!(episodes[1].runtime, Some(25));
+        assert_eq!(episodes[1].vote_average, Some(4.02));
+        assert_eq!(episodes[1].season_number, 1);
+    }
+
+    #[test]
+    fn test_parse_anidb_error() {
+        let xml = r#"<error code="302">client version missing or invalid</error>"#;
+        match parse_anidb_episodes(xml) {
+            Err(AnimeError::ApiError(msg)) => {
+                assert_eq!(msg, "client version missing or invalid")
+            }
+            other => panic!("expected ApiError, got {:?}", other),
+        }
+    }
+}
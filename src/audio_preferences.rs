@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::error;
+
+use crate::mpv::{self, AudioTrack};
+
+/// Audio track picked for a title, matched against the tracks of later streams
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioChoice {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl From<&AudioTrack> for AudioChoice {
+    fn from(track: &AudioTrack) -> Self {
+        Self {
+            lang: track.lang.clone(),
+            title: track.title.clone(),
+        }
+    }
+}
+
+/// Audio track picked per TMDB title, persisted next to the config
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AudioPreferences {
+    titles: BTreeMap<u64, AudioChoice>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl AudioPreferences {
+    /// Load preferences from disk, starting fresh if the file is missing or unreadable
+    pub fn load(path: PathBuf) -> Self {
+        let mut preferences = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                error!("failed to parse audio preferences: {}", e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        preferences.path = Some(path);
+        preferences
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(self)
+            .map_err(std::io::Error::from)
+            .and_then(|contents| std::fs::write(path, contents));
+        if let Err(e) = result {
+            error!("failed to save audio preferences: {}", e);
+        }
+    }
+
+    pub fn get(&self, tmdb_id: u64) -> Option<&AudioChoice> {
+        self.titles.get(&tmdb_id)
+    }
+
+    pub fn set(&mut self, tmdb_id: u64, choice: AudioChoice) {
+        self.titles.insert(tmdb_id, choice);
+    }
+
+    /// The track to switch to for a title: the same track picked before if it's
+    /// there, otherwise one in its language. None if it's already selected.
+    pub fn track_for<'a>(&self, tmdb_id: u64, tracks: &'a [AudioTrack]) -> Option<&'a AudioTrack> {
+        let choice = self.get(tmdb_id)?;
+        let same = tracks
+            .iter()
+            .find(|t| t.lang == choice.lang && t.title == choice.title);
+        match same {
+            Some(track) => (!track.selected).then_some(track),
+            None => {
+                let lang = choice.lang.clone()?;
+                mpv::preferred_audio_track(tracks, &[lang])
+            }
+        }
+    }
+}
+
+/// The track after the selected one, wrapping around
+pub fn next_track(tracks: &[AudioTrack]) -> Option<&AudioTrack> {
+    let selected = tracks.iter().position(|t| t.selected);
+    let next = selected.map_or(0, |i| (i + 1) % tracks.len().max(1));
+    tracks.get(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: i64, lang: &str, title: Option<&str>, selected: bool) -> AudioTrack {
+        AudioTrack {
+            id,
+            lang: Some(lang.to_string()),
+            title: title.map(str::to_string),
+            selected,
+        }
+    }
+
+    #[test]
+    fn test_track_for_prefers_same_track() {
+        let tracks = vec![
+            track(1, "eng", None, true),
+            track(2, "jpn", Some("Stereo"), false),
+            track(3, "jpn", Some("Commentary"), false),
+        ];
+        let mut preferences = AudioPreferences::default();
+        assert!(preferences.track_for(42, &tracks).is_none());
+
+        preferences.set(42, AudioChoice::from(&tracks[2]));
+        assert_eq!(preferences.track_for(42, &tracks).map(|t| t.id), Some(3));
+
+        // Another release without that exact track falls back to the language
+        let other = vec![track(1, "en", None, true), track(2, "ja", None, false)];
+        assert_eq!(preferences.track_for(42, &other).map(|t| t.id), Some(2));
+
+        // Nothing to do once it's selected
+        let selected = vec![track(1, "eng", None, false), track(3, "jpn", None, true)];
+        assert!(preferences.track_for(42, &selected).is_none());
+    }
+
+    #[test]
+    fn test_next_track_wraps() {
+        let tracks = vec![track(1, "eng", None, false), track(2, "jpn", None, true)];
+        assert_eq!(next_track(&tracks).map(|t| t.id), Some(1));
+        assert!(next_track(&[]).is_none());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!(
+            "ferristream-audio-test-{}.json",
+            std::process::id()
+        ));
+        let mut preferences = AudioPreferences::load(path.clone());
+        preferences.set(
+            7,
+            AudioChoice {
+                lang: Some("jpn".to_string()),
+                title: None,
+            },
+        );
+        preferences.save();
+
+        let loaded = AudioPreferences::load(path.clone());
+        assert_eq!(loaded.get(7).and_then(|c| c.lang.as_deref()), Some("jpn"));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,688 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use reqwest::Client;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// How long to listen for devices answering discovery
+const DISCOVERY_TIME: Duration = Duration::from_secs(3);
+/// mDNS service Chromecasts announce themselves with
+const CHROMECAST_SERVICE: &str = "_googlecast._tcp.local.";
+/// SSDP search target of DLNA TVs and other renderers
+const MEDIA_RENDERER: &str = "urn:schemas-upnp-org:device:MediaRenderer:1";
+/// UPnP service a renderer is told what to play through
+const AV_TRANSPORT: &str = "urn:schemas-upnp-org:service:AVTransport:1";
+/// Largest request head the relay reads
+const MAX_REQUEST_HEAD: usize = 8192;
+
+#[derive(Error, Debug)]
+pub enum CastError {
+    #[error("Chromecast error: {0}")]
+    Chromecast(String),
+    #[error("DLNA error: {0}")]
+    Dlna(String),
+    #[error("request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("stream relay failed: {0}")]
+    RelayError(#[from] std::io::Error),
+}
+
+/// How a device is cast to
+#[derive(Debug, Clone, PartialEq)]
+pub enum CastProtocol {
+    Chromecast,
+    /// AVTransport control URL of the renderer
+    Dlna {
+        control_url: String,
+    },
+}
+
+/// A device on the local network the stream can be cast to
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastDevice {
+    pub name: String,
+    pub host: IpAddr,
+    pub port: u16,
+    pub protocol: CastProtocol,
+}
+
+impl CastDevice {
+    pub fn kind(&self) -> &'static str {
+        match self.protocol {
+            CastProtocol::Chromecast => "Chromecast",
+            CastProtocol::Dlna { .. } => "DLNA",
+        }
+    }
+}
+
+/// Find Chromecasts (mDNS) and DLNA renderers (SSDP) on the local network,
+/// sorted by name
+pub async fn discover() -> Vec<CastDevice> {
+    let (chromecasts, renderers) = tokio::join!(
+        discover_chromecasts(DISCOVERY_TIME),
+        discover_dlna(DISCOVERY_TIME)
+    );
+    let mut devices: Vec<CastDevice> = chromecasts.into_iter().chain(renderers).collect();
+    devices.sort_by_key(|device| device.name.to_lowercase());
+    devices.dedup_by(|a, b| a.host == b.host && a.port == b.port && a.kind() == b.kind());
+    devices
+}
+
+async fn discover_chromecasts(wait: Duration) -> Vec<CastDevice> {
+    use mdns_sd::{ServiceDaemon, ServiceEvent};
+
+    let browse = move || {
+        let daemon = match ServiceDaemon::new() {
+            Ok(daemon) => daemon,
+            Err(e) => {
+                warn!(error = %e, "mDNS unavailable, not looking for Chromecasts");
+                return Vec::new();
+            }
+        };
+        let Ok(events) = daemon.browse(CHROMECAST_SERVICE) else {
+            return Vec::new();
+        };
+
+        let deadline = Instant::now() + wait;
+        let mut devices = Vec::new();
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            match events.recv_timeout(left) {
+                Ok(ServiceEvent::ServiceResolved(service)) => {
+                    let Some(host) = service.get_addresses_v4().into_iter().next() else {
+                        continue;
+                    };
+                    // "fn" is the name given to the device in the Google Home app
+                    let name = service
+                        .txt_properties
+                        .get_property_val_str("fn")
+                        .unwrap_or(service.get_hostname())
+                        .to_string();
+                    devices.push(CastDevice {
+                        name,
+                        host: IpAddr::V4(host),
+                        port: service.get_port(),
+                        protocol: CastProtocol::Chromecast,
+                    });
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        let _ = daemon.shutdown();
+        devices
+    };
+    tokio::task::spawn_blocking(browse)
+        .await
+        .unwrap_or_default()
+}
+
+async fn discover_dlna(wait: Duration) -> Vec<CastDevice> {
+    let Ok(target) = MEDIA_RENDERER.parse::<ssdp_client::SearchTarget>() else {
+        return Vec::new();
+    };
+    let responses = match ssdp_client::search(&target, wait, 2, None).await {
+        Ok(responses) => responses,
+        Err(e) => {
+            warn!(error = %e, "SSDP search failed, not looking for DLNA renderers");
+            return Vec::new();
+        }
+    };
+    let locations: HashSet<String> = responses
+        .filter_map(|response| async move { Some(response.ok()?.location().to_string()) })
+        .collect()
+        .await;
+
+    let client = crate::http::client();
+    let mut devices = Vec::new();
+    for location in locations {
+        match describe_renderer(&client, &location).await {
+            Some(device) => devices.push(device),
+            None => debug!(location, "not a usable DLNA renderer"),
+        }
+    }
+    devices
+}
+
+/// Fetch a renderer's device description from its SSDP location
+async fn describe_renderer(client: &Client, location: &str) -> Option<CastDevice> {
+    let url = url::Url::parse(location).ok()?;
+    let host = url.host_str()?.parse().ok()?;
+    let port = url.port_or_known_default()?;
+    let xml = client
+        .get(location)
+        .timeout(DISCOVERY_TIME)
+        .send()
+        .await
+        .ok()?
+        .text()
+        .await
+        .ok()?;
+    let (name, control_url) = parse_device_description(&xml, location)?;
+    Some(CastDevice {
+        name,
+        host,
+        port,
+        protocol: CastProtocol::Dlna { control_url },
+    })
+}
+
+/// Friendly name and absolute AVTransport control URL from a UPnP device
+/// description. None if the device can't be told what to play.
+pub fn parse_device_description(xml: &str, location: &str) -> Option<(String, String)> {
+    let mut reader = Reader::from_str(xml);
+    let mut element = String::new();
+    let mut name: Option<String> = None;
+    let mut url_base: Option<String> = None;
+    let mut service_type = String::new();
+    let mut control_url = String::new();
+    let mut av_transport: Option<String> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                element = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                if element == "service" {
+                    service_type.clear();
+                    control_url.clear();
+                }
+            }
+            Ok(Event::Text(e)) => {
+                let text = e.decode().unwrap_or_default().trim().to_string();
+                match element.as_str() {
+                    // The root device comes first, embedded ones after it
+                    "friendlyName" if name.is_none() => name = Some(text),
+                    "URLBase" => url_base = Some(text),
+                    "serviceType" => service_type = text,
+                    "controlURL" => control_url = text,
+                    _ => {}
+                }
+            }
+            Ok(Event::End(e)) => {
+                if e.local_name().as_ref() == b"service"
+                    && service_type.starts_with("urn:schemas-upnp-org:service:AVTransport:")
+                    && av_transport.is_none()
+                {
+                    av_transport = Some(control_url.clone());
+                }
+                element.clear();
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    let base = url::Url::parse(url_base.as_deref().unwrap_or(location)).ok()?;
+    let control_url = base.join(&av_transport?).ok()?;
+    Some((name?, control_url.to_string()))
+}
+
+/// MIME type a device is told the stream has, from the file name
+pub fn content_type(file_name: &str) -> &'static str {
+    let lower = file_name.to_lowercase();
+    match lower.rsplit('.').next() {
+        Some("mkv") => "video/x-matroska",
+        Some("webm") => "video/webm",
+        Some("avi") => "video/x-msvideo",
+        Some("mov") => "video/quicktime",
+        Some("ts") | Some("m2ts") => "video/mp2t",
+        _ => "video/mp4",
+    }
+}
+
+/// SOAP envelope for an AVTransport action
+pub fn soap_envelope(action: &str, arguments: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+<s:Body><u:{action} xmlns:u="{AV_TRANSPORT}"><InstanceID>0</InstanceID>{arguments}</u:{action}></s:Body>
+</s:Envelope>"#
+    )
+}
+
+/// Arguments of `SetAVTransportURI`, with DIDL-Lite metadata so renderers
+/// show the title
+pub fn set_uri_arguments(stream_url: &str, title: &str, content_type: &str) -> String {
+    let metadata = format!(
+        r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/"><item id="0" parentID="-1" restricted="1"><dc:title>{}</dc:title><upnp:class>object.item.videoItem</upnp:class><res protocolInfo="http-get:*:{}:*">{}</res></item></DIDL-Lite>"#,
+        escape(title),
+        content_type,
+        escape(stream_url)
+    );
+    format!(
+        "<CurrentURI>{}</CurrentURI><CurrentURIMetaData>{}</CurrentURIMetaData>",
+        escape(stream_url),
+        escape(&metadata)
+    )
+}
+
+/// Start playing `stream_url` on a device. The returned relay makes the
+/// stream reachable from the network and has to be kept while casting.
+pub async fn cast(
+    device: &CastDevice,
+    stream_url: &str,
+    title: &str,
+    file_name: &str,
+) -> Result<StreamRelay, CastError> {
+    let relay = StreamRelay::start(stream_url, device.host).await?;
+    let content_type = content_type(file_name);
+    info!(device = %device.name, url = %relay.url(), "casting stream");
+
+    match &device.protocol {
+        CastProtocol::Chromecast => {
+            let host = device.host.to_string();
+            let port = device.port;
+            let url = relay.url().to_string();
+            tokio::task::spawn_blocking(move || cast_chromecast(host, port, url, content_type))
+                .await
+                .map_err(|e| CastError::Chromecast(e.to_string()))??;
+        }
+        CastProtocol::Dlna { control_url } => {
+            let client = crate::http::client();
+            let arguments = set_uri_arguments(relay.url(), title, content_type);
+            soap_action(&client, control_url, "SetAVTransportURI", &arguments).await?;
+            soap_action(&client, control_url, "Play", "<Speed>1</Speed>").await?;
+        }
+    }
+    Ok(relay)
+}
+
+/// Load the stream in the Default Media Receiver app
+fn cast_chromecast(
+    host: String,
+    port: u16,
+    url: String,
+    content_type: &str,
+) -> Result<(), CastError> {
+    use rust_cast::CastDevice as Chromecast;
+    use rust_cast::channels::media::{Media, StreamType};
+    use rust_cast::channels::receiver::CastDeviceApp;
+
+    let error = |e: rust_cast::errors::Error| CastError::Chromecast(e.to_string());
+    // Chromecasts use self-signed certificates
+    let device = Chromecast::connect_without_host_verification(host, port).map_err(error)?;
+    device.connection.connect("receiver-0").map_err(error)?;
+    let app = device
+        .receiver
+        .launch_app(&CastDeviceApp::DefaultMediaReceiver)
+        .map_err(error)?;
+    device
+        .connection
+        .connect(app.transport_id.as_str())
+        .map_err(error)?;
+    device
+        .media
+        .load(
+            app.transport_id.as_str(),
+            app.session_id.as_str(),
+            &Media {
+                content_id: url,
+                stream_type: StreamType::Buffered,
+                content_type: content_type.to_string(),
+                metadata: None,
+                duration: None,
+            },
+        )
+        .map_err(error)?;
+    Ok(())
+}
+
+async fn soap_action(
+    client: &Client,
+    control_url: &str,
+    action: &str,
+    arguments: &str,
+) -> Result<(), CastError> {
+    let response = client
+        .post(control_url)
+        .header("Content-Type", r#"text/xml; charset="utf-8""#)
+        .header("SOAPAction", format!(r#""{}#{}""#, AV_TRANSPORT, action))
+        .body(soap_envelope(action, arguments))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(CastError::Dlna(format!(
+            "{} failed with status {}",
+            action,
+            response.status()
+        )));
+    }
+    Ok(())
+}
+
+/// Serves one stream URL to the local network. The torrent HTTP API only
+/// listens on localhost and also controls torrents, so devices get this
+/// instead: it only passes through requests for that one stream.
+pub struct StreamRelay {
+    url: String,
+    task: JoinHandle<()>,
+}
+
+impl StreamRelay {
+    /// Listen on the address the device reaches this machine on
+    pub async fn start(stream_url: &str, device: IpAddr) -> Result<Self, CastError> {
+        let local = local_address_for(device)?;
+        Self::listen(stream_url, local, local, None).await
+    }
+
+    /// Listen on `bind` for anything on the network to play the stream, e.g.
+    /// VLC on a TV. With a `token` the stream is only served under
+    /// `/<token>/...`, which keeps out whoever doesn't have the URL.
+    pub async fn share(
+        stream_url: &str,
+        bind: IpAddr,
+        token: Option<&str>,
+    ) -> Result<Self, CastError> {
+        let host = if bind.is_unspecified() {
+            lan_address()?
+        } else {
+            bind
+        };
+        Self::listen(stream_url, bind, host, token).await
+    }
+
+    /// Relay requests to `bind` for the stream's path, offering it as `host`
+    async fn listen(
+        stream_url: &str,
+        bind: IpAddr,
+        host: IpAddr,
+        token: Option<&str>,
+    ) -> Result<Self, CastError> {
+        let upstream = url::Url::parse(stream_url)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidInput, "bad stream URL");
+        let upstream_addr = format!(
+            "{}:{}",
+            upstream.host_str().ok_or_else(invalid)?,
+            upstream.port_or_known_default().ok_or_else(invalid)?
+        );
+        let path = match upstream.query() {
+            Some(query) => format!("{}?{}", upstream.path(), query),
+            None => upstream.path().to_string(),
+        };
+        let prefix = token.map(|token| format!("/{}", token)).unwrap_or_default();
+
+        let listener = TcpListener::bind((bind, 0)).await?;
+        let port = listener.local_addr()?.port();
+        let url = format!("http://{}{}{}", SocketAddr::new(host, port), prefix, path);
+        let task = tokio::spawn(async move {
+            while let Ok((client, peer)) = listener.accept().await {
+                debug!(%peer, "device connected to stream relay");
+                let upstream_addr = upstream_addr.clone();
+                let prefix = prefix.clone();
+                let path = path.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = relay_request(client, &upstream_addr, &prefix, &path).await {
+                        debug!(error = %e, "stream relay request failed");
+                    }
+                });
+            }
+        });
+        Ok(Self { url, task })
+    }
+
+    /// Stream URL for the device, or to hand out when shared
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for StreamRelay {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Local address packets to `device` leave from. Connecting a UDP socket
+/// sends nothing, it only picks the route.
+fn local_address_for(device: IpAddr) -> std::io::Result<IpAddr> {
+    let bind: SocketAddr = if device.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect((device, 9))?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Address this machine has on the local network, the one its default
+/// route leaves from
+pub fn lan_address() -> std::io::Result<IpAddr> {
+    local_address_for(IpAddr::from([192, 0, 2, 1]))
+}
+
+/// Forward one GET/HEAD of the stream path upstream and the response back.
+/// The upstream is asked to close afterwards, so nothing else gets through
+/// on the same connection.
+async fn relay_request(
+    mut client: TcpStream,
+    upstream: &str,
+    prefix: &str,
+    path: &str,
+) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = client.read(&mut buf).await?;
+        if n == 0 || head.len() + n > MAX_REQUEST_HEAD {
+            return Ok(());
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&head).to_string();
+    let Some(request) = relay_head(&head, prefix, path, upstream) else {
+        client
+            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .await?;
+        return Ok(());
+    };
+
+    let mut server = TcpStream::connect(upstream).await?;
+    server.write_all(request.as_bytes()).await?;
+    tokio::io::copy(&mut server, &mut client).await?;
+    Ok(())
+}
+
+/// Request head to send upstream, None unless it's a GET or HEAD of `path`
+/// behind `prefix`, which is left out upstream
+pub fn relay_head(head: &str, prefix: &str, path: &str, upstream: &str) -> Option<String> {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?;
+    let target = request_line.next()?;
+    let version = request_line.next()?;
+    if !matches!(method, "GET" | "HEAD") || target.strip_prefix(prefix) != Some(path) {
+        return None;
+    }
+
+    let mut request = format!("{} {} {}\r\nHost: {}\r\n", method, path, version, upstream);
+    for line in lines.take_while(|line| !line.is_empty()) {
+        let name = line.split(':').next().unwrap_or_default().to_lowercase();
+        if !matches!(
+            name.as_str(),
+            "host" | "connection" | "keep-alive" | "upgrade"
+        ) {
+            request.push_str(line);
+            request.push_str("\r\n");
+        }
+    }
+    request.push_str("Connection: close\r\n\r\n");
+    Some(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:MediaRenderer:1</deviceType>
+    <friendlyName>Living Room TV</friendlyName>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:RenderingControl:1</serviceType>
+        <controlURL>/upnp/control/RenderingControl1</controlURL>
+      </service>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:AVTransport:1</serviceType>
+        <controlURL>/upnp/control/AVTransport1</controlURL>
+      </service>
+    </serviceList>
+  </device>
+</root>"#;
+
+    #[test]
+    fn test_parse_device_description() {
+        let (name, control_url) =
+            parse_device_description(DESCRIPTION, "http://192.168.1.20:9197/dmr").unwrap();
+        assert_eq!(name, "Living Room TV");
+        assert_eq!(
+            control_url,
+            "http://192.168.1.20:9197/upnp/control/AVTransport1"
+        );
+
+        // URLBase wins over the location
+        let with_base = DESCRIPTION.replace(
+            "<specVersion>",
+            "<URLBase>http://192.168.1.20:8080/</URLBase><specVersion>",
+        );
+        let (_, control_url) =
+            parse_device_description(&with_base, "http://192.168.1.20:9197/dmr").unwrap();
+        assert_eq!(
+            control_url,
+            "http://192.168.1.20:8080/upnp/control/AVTransport1"
+        );
+
+        // Renderers without AVTransport can't be cast to
+        let no_transport = DESCRIPTION.replace("AVTransport:1", "ConnectionManager:1");
+        assert!(parse_device_description(&no_transport, "http://192.168.1.20:9197/").is_none());
+    }
+
+    #[test]
+    fn test_set_uri_arguments_are_escaped() {
+        let arguments = set_uri_arguments(
+            "http://192.168.1.5:4000/torrents/0/stream/1?a=1&b=2",
+            "Tom & Jerry <1940>",
+            "video/mp4",
+        );
+        assert!(arguments.contains(
+            "<CurrentURI>http://192.168.1.5:4000/torrents/0/stream/1?a=1&amp;b=2</CurrentURI>"
+        ));
+        // Metadata is XML inside XML, escaped twice
+        assert!(arguments.contains("Tom &amp;amp; Jerry &amp;lt;1940&amp;gt;"));
+        assert!(!arguments.contains("<DIDL-Lite"));
+
+        let envelope = soap_envelope("Play", "<Speed>1</Speed>");
+        assert!(envelope.contains(
+            r#"<u:Play xmlns:u="urn:schemas-upnp-org:service:AVTransport:1"><InstanceID>0</InstanceID><Speed>1</Speed></u:Play>"#
+        ));
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type("Movie.2023.1080p.MKV"), "video/x-matroska");
+        assert_eq!(content_type("movie.mp4"), "video/mp4");
+        assert_eq!(content_type("no extension"), "video/mp4");
+    }
+
+    #[test]
+    fn test_relay_head() {
+        let head = "GET /torrents/0/stream/1 HTTP/1.1\r\nHost: 192.168.1.5:4000\r\nRange: bytes=0-\r\nConnection: keep-alive\r\n\r\n";
+        let request = relay_head(head, "", "/torrents/0/stream/1", "127.0.0.1:3030").unwrap();
+        assert_eq!(
+            request,
+            "GET /torrents/0/stream/1 HTTP/1.1\r\nHost: 127.0.0.1:3030\r\nRange: bytes=0-\r\nConnection: close\r\n\r\n"
+        );
+
+        // Nothing but the stream itself
+        let other = head.replace("/torrents/0/stream/1", "/torrents/0/delete");
+        assert!(relay_head(&other, "", "/torrents/0/stream/1", "127.0.0.1:3030").is_none());
+        let post = head.replace("GET", "POST");
+        assert!(relay_head(&post, "", "/torrents/0/stream/1", "127.0.0.1:3030").is_none());
+
+        // Shared with a token, it's only served behind it
+        let path = "/torrents/0/stream/1";
+        assert!(relay_head(head, "/s3cret", path, "127.0.0.1:3030").is_none());
+        let tokened = head.replace(path, "/s3cret/torrents/0/stream/1");
+        let request = relay_head(&tokened, "/s3cret", path, "127.0.0.1:3030").unwrap();
+        assert!(request.starts_with("GET /torrents/0/stream/1 HTTP/1.1\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_relay_forwards_stream() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = upstream.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = upstream.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]).to_string();
+            assert!(request.to_lowercase().contains("range: bytes=5-"));
+            socket
+                .write_all(b"HTTP/1.1 206 Partial Content\r\nContent-Length: 5\r\n\r\nvideo")
+                .await
+                .unwrap();
+        });
+
+        let stream_url = format!("http://{}/torrents/0/stream/1", upstream_addr);
+        let relay = StreamRelay::start(&stream_url, "127.0.0.1".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(relay.url().ends_with("/torrents/0/stream/1"));
+
+        let client = Client::new();
+        let response = client
+            .get(relay.url())
+            .header("Range", "bytes=5-")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 206);
+        assert_eq!(response.text().await.unwrap(), "video");
+
+        let other = relay.url().replace("/stream/1", "/stream/2");
+        assert_eq!(client.get(other).send().await.unwrap().status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_shared_relay_needs_token() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = upstream.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = upstream.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]).to_string();
+            assert!(request.starts_with("GET /torrents/0/stream/1 "));
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nvideo")
+                .await
+                .unwrap();
+        });
+
+        let stream_url = format!("http://{}/torrents/0/stream/1", upstream_addr);
+        let relay = StreamRelay::share(&stream_url, "127.0.0.1".parse().unwrap(), Some("s3cret"))
+            .await
+            .unwrap();
+        assert!(relay.url().starts_with("http://127.0.0.1:"));
+        assert!(relay.url().ends_with("/s3cret/torrents/0/stream/1"));
+
+        let client = Client::new();
+        let bare = relay.url().replace("/s3cret", "");
+        assert_eq!(client.get(bare).send().await.unwrap().status(), 404);
+        let response = client.get(relay.url()).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "video");
+    }
+}
@@ -1,7 +1,18 @@
 use directories::ProjectDirs;
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
+use tracing::warn;
+
+use crate::anidb::{AnidbClient, AnimeClient, MalClient};
+use crate::redact;
+use crate::rss::RssRule;
+use crate::secrets::{self, KEYCHAIN_SENTINEL, KeychainBackend, OsKeychainBackend};
+use crate::streaming::{MediaMode, SeedPolicy};
+use crate::torznab::{RaceOrder, ResultFilter};
 
 #[derive(Error, Debug)]
 pub enum ConfigError {
@@ -21,6 +32,8 @@ pub enum ConfigError {
 pub struct Config {
     pub prowlarr: ProwlarrConfig,
     pub tmdb: Option<TmdbConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usenet: Option<UsenetConfig>,
     #[serde(default)]
     pub player: PlayerConfig,
     #[serde(default)]
@@ -31,6 +44,24 @@ pub struct Config {
     pub subtitles: SubtitlesConfig,
     #[serde(default)]
     pub streaming: StreamingConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub display: DisplayConfig,
+    #[serde(default)]
+    pub filter: FilterConfig,
+    #[serde(default)]
+    pub anime: AnimeConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub rss: RssConfig,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rss_rules: Vec<RssRule>,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -39,8 +70,78 @@ pub struct ExtensionsConfig {
     pub discord: DiscordConfig,
     #[serde(default)]
     pub trakt: TraktConfig,
+    #[serde(default)]
+    pub simkl: SimklConfig,
+    /// External programs run on playback events
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<CommandExtensionConfig>,
 }
 
+/// `[[extensions.command]]`: a program that gets each playback event as a
+/// line of JSON on stdin
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CommandExtensionConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SecretsConfig {
+    /// Keep API keys in the OS keychain, the config file only says "keychain"
+    #[serde(default)]
+    pub keychain: bool,
+}
+
+/// An API key that can live in the keychain instead of the config file
+struct KeychainField {
+    /// Dotted config path, the keychain account it's stored under (see
+    /// [`ConfigLocation::keychain_account`])
+    name: &'static str,
+    /// None when its section isn't configured
+    get: fn(&Config) -> Option<&str>,
+    set: fn(&mut Config, String),
+}
+
+static KEYCHAIN_FIELDS: [KeychainField; 4] = [
+    KeychainField {
+        name: "prowlarr.apikey",
+        get: |c| Some(&c.prowlarr.apikey),
+        set: |c, v| c.prowlarr.apikey = v,
+    },
+    KeychainField {
+        name: "tmdb.apikey",
+        get: |c| c.tmdb.as_ref().map(|t| t.apikey.as_str()),
+        set: |c, v| {
+            if let Some(tmdb) = c.tmdb.as_mut() {
+                tmdb.apikey = v;
+            }
+        },
+    },
+    KeychainField {
+        name: "usenet.apikey",
+        get: |c| c.usenet.as_ref().map(|u| u.apikey.as_str()),
+        set: |c, v| {
+            if let Some(usenet) = c.usenet.as_mut() {
+                usenet.apikey = v;
+            }
+        },
+    },
+    KeychainField {
+        name: "subtitles.opensubtitles_api_key",
+        get: |c| {
+            Some(
+                c.subtitles
+                    .opensubtitles_api_key
+                    .as_deref()
+                    .unwrap_or_default(),
+            )
+        },
+        set: |c, v| c.subtitles.opensubtitles_api_key = Some(v).filter(|v| !v.is_empty()),
+    },
+];
+
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct DiscordConfig {
     #[serde(default)]
@@ -49,7 +150,7 @@ pub struct DiscordConfig {
     pub app_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TraktConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -57,9 +158,44 @@ pub struct TraktConfig {
     pub client_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub access_token: Option<String>,
+    /// Needed with `refresh_token` to renew an expired access token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// Percent watched above which stopping marks it as watched
+    #[serde(default = "default_scrobble_threshold")]
+    pub scrobble_threshold: f64,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl Default for TraktConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            client_id: None,
+            access_token: None,
+            client_secret: None,
+            refresh_token: None,
+            scrobble_threshold: default_scrobble_threshold(),
+        }
+    }
+}
+
+fn default_scrobble_threshold() -> f64 {
+    80.0
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SimklConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct ProwlarrConfig {
     pub url: String,
     pub apikey: String,
@@ -76,9 +212,18 @@ pub struct SubtitlesConfig {
     pub enabled: bool,
     #[serde(default = "default_subtitle_language")]
     pub language: String,
+    /// Languages to try in order, e.g. ["fr", "en"] (overrides `language` when set)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtitle_languages: Vec<String>,
     /// OpenSubtitles API key for fetching subtitles when not included in torrent
     #[serde(skip_serializing_if = "Option::is_none")]
     pub opensubtitles_api_key: Option<String>,
+    /// Longest an OpenSubtitles request waits for the rate limit, in seconds
+    #[serde(default = "default_opensubtitles_max_wait_secs")]
+    pub opensubtitles_max_wait_secs: u64,
+    /// Turn the subtitle off in mpv when the audio is already in its language
+    #[serde(default = "default_hide_when_audio_matches")]
+    pub hide_when_audio_matches: bool,
 }
 
 impl Default for SubtitlesConfig {
@@ -86,25 +231,399 @@ impl Default for SubtitlesConfig {
         Self {
             enabled: default_subtitles_enabled(),
             language: default_subtitle_language(),
+            subtitle_languages: Vec::new(),
             opensubtitles_api_key: None,
+            opensubtitles_max_wait_secs: default_opensubtitles_max_wait_secs(),
+            hide_when_audio_matches: default_hide_when_audio_matches(),
         }
     }
 }
 
+impl SubtitlesConfig {
+    pub fn opensubtitles_max_wait(&self) -> Duration {
+        Duration::from_secs(self.opensubtitles_max_wait_secs)
+    }
+
+    /// Subtitle languages in fallback order
+    pub fn languages(&self) -> Vec<String> {
+        if self.subtitle_languages.is_empty() {
+            vec![self.language.clone()]
+        } else {
+            self.subtitle_languages.clone()
+        }
+    }
+
+    /// Preferred subtitle/audio language to pass to the player
+    pub fn player_language(&self) -> Option<String> {
+        self.languages()
+            .into_iter()
+            .next()
+            .filter(|lang| self.enabled && !lang.is_empty())
+    }
+}
+
 fn default_subtitles_enabled() -> bool {
     true
 }
 
+fn default_opensubtitles_max_wait_secs() -> u64 {
+    5
+}
+
+fn default_hide_when_audio_matches() -> bool {
+    true
+}
+
 fn default_subtitle_language() -> String {
     "en".to_string()
 }
 
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DisplayConfig {
+    /// One-line help hints and shorter stats, also turned on when the
+    /// terminal starts out narrower than 80 columns
+    #[serde(default)]
+    pub compact_mode: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NotificationConfig {
+    /// Show OS desktop notifications (opt-in)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Notify when a stream is ready to play
+    #[serde(default = "default_notify_event")]
+    pub on_stream_ready: bool,
+    /// Notify when the player exits
+    #[serde(default = "default_notify_event")]
+    pub on_player_exit: bool,
+    /// Notify when a torrent search finishes
+    #[serde(default = "default_notify_event")]
+    pub on_search_complete: bool,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            on_stream_ready: default_notify_event(),
+            on_player_exit: default_notify_event(),
+            on_search_complete: default_notify_event(),
+        }
+    }
+}
+
+fn default_notify_event() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FilterConfig {
+    /// Hide results whose title contains one of these words (case-insensitive)
+    #[serde(default = "default_blocked_terms")]
+    pub blocked_terms: Vec<String>,
+    /// Hide results smaller than this (in MB, 0 = no limit)
+    #[serde(default = "default_min_size_mb")]
+    pub min_size_mb: u64,
+    /// Hide results larger than this (in GB, 0 = no limit)
+    #[serde(default = "default_max_size_gb")]
+    pub max_size_gb: u64,
+    /// Hide results that have no magnet, infohash or download link
+    #[serde(default = "default_require_streamable")]
+    pub require_streamable: bool,
+    /// Drop adult releases entirely, by category and title words
+    #[serde(default)]
+    pub safe_mode: bool,
+    /// Keep safe mode from being turned off in the settings screen, only an
+    /// edit to the config file can
+    #[serde(default)]
+    pub safe_mode_locked: bool,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            blocked_terms: default_blocked_terms(),
+            min_size_mb: default_min_size_mb(),
+            max_size_gb: default_max_size_gb(),
+            require_streamable: default_require_streamable(),
+            safe_mode: false,
+            safe_mode_locked: false,
+        }
+    }
+}
+
+/// Default blocked terms that describe the audio releases an audio search is after
+const AUDIO_RELEASE_TERMS: &[&str] = &["audiobook", "soundtrack", "ost", "discography"];
+
+impl FilterConfig {
+    /// Filter for results of a search in `mode`. Outside video mode audio
+    /// release terms aren't blocked and the minimum size (meant to catch
+    /// fake video releases) doesn't apply.
+    pub fn result_filter(&self, mode: MediaMode) -> ResultFilter {
+        const MB: u64 = 1024 * 1024;
+        const GB: u64 = 1024 * MB;
+        let video = mode == MediaMode::Video;
+        ResultFilter {
+            blocked_terms: self
+                .blocked_terms
+                .iter()
+                .map(|t| t.to_lowercase())
+                .filter(|t| video || !AUDIO_RELEASE_TERMS.contains(&t.as_str()))
+                .collect(),
+            min_size: (video && self.min_size_mb > 0).then(|| self.min_size_mb * MB),
+            max_size: (self.max_size_gb > 0).then(|| self.max_size_gb * GB),
+            require_streamable: self.require_streamable,
+            categories: mode.torznab_categories().to_vec(),
+            safe_mode: self.safe_mode,
+        }
+    }
+}
+
+fn default_blocked_terms() -> Vec<String> {
+    [
+        "xxx",
+        "ebook",
+        "epub",
+        "pdf",
+        "mobi",
+        "audiobook",
+        "soundtrack",
+        "ost",
+        "discography",
+    ]
+    .iter()
+    .map(|t| t.to_string())
+    .collect()
+}
+
+fn default_min_size_mb() -> u64 {
+    50
+}
+
+fn default_max_size_gb() -> u64 {
+    200
+}
+
+fn default_require_streamable() -> bool {
+    true
+}
+
+/// Usenet downloader that fetches NZBs found through Prowlarr. Usenet
+/// indexers are only searched when it's set, their releases are streamed
+/// from disk once downloaded.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UsenetConfig {
+    pub backend: UsenetBackend,
+    pub url: String,
+    /// SABnzbd API key, or "username:password" for NZBGet
+    pub apikey: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsenetBackend {
+    Nzbget,
+    Sabnzbd,
+}
+
+/// Where episode lists for anime come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimeSource {
+    /// TMDB seasons (S01E05 numbering)
+    #[default]
+    Tmdb,
+    /// AniDB absolute episode numbering
+    Anidb,
+    /// MyAnimeList absolute episode numbering
+    Mal,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SearchConfig {
+    /// Releases to search for: "video", "audio" (music, audiobooks) or "all"
+    #[serde(default)]
+    pub categories: MediaMode,
+    /// Search episodes that haven't aired yet instead of refusing to
+    #[serde(default)]
+    pub allow_unaired: bool,
+    /// Reload the Discovery view every this many minutes while it's open,
+    /// 0 disables
+    #[serde(default)]
+    pub discovery_auto_refresh_minutes: u32,
+    /// Draw the focused title's backdrop behind the Discovery rows, needs a
+    /// wide terminal with true color
+    #[serde(default)]
+    pub show_backdrops: bool,
+}
+
+impl SearchConfig {
+    pub fn discovery_refresh_interval(&self) -> Option<Duration> {
+        (self.discovery_auto_refresh_minutes > 0)
+            .then(|| Duration::from_secs(self.discovery_auto_refresh_minutes as u64 * 60))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AnimeConfig {
+    /// Episode data source for anime: "tmdb", "anidb" or "mal"
+    #[serde(default)]
+    pub source: AnimeSource,
+    /// Client name registered at anidb.net (required for the anidb source)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anidb_client: Option<String>,
+    #[serde(default = "default_anidb_client_version")]
+    pub anidb_client_version: u32,
+}
+
+impl Default for AnimeConfig {
+    fn default() -> Self {
+        Self {
+            source: AnimeSource::default(),
+            anidb_client: None,
+            anidb_client_version: default_anidb_client_version(),
+        }
+    }
+}
+
+fn default_anidb_client_version() -> u32 {
+    1
+}
+
+impl AnimeConfig {
+    /// Client for the configured source, None when TMDB episode data is used
+    pub fn client(&self) -> Option<AnimeClient> {
+        match self.source {
+            AnimeSource::Tmdb => None,
+            AnimeSource::Mal => Some(AnimeClient::Mal(MalClient::new())),
+            AnimeSource::Anidb => {
+                let titles_cache = ProjectDirs::from("", "", "ferristream")
+                    .map(|dirs| dirs.cache_dir().to_path_buf())
+                    .unwrap_or_else(|| std::env::temp_dir().join("ferristream"))
+                    .join("anime-titles.xml");
+                Some(AnimeClient::Anidb(AnidbClient::new(
+                    self.anidb_client.as_deref()?,
+                    self.anidb_client_version,
+                    titles_cache,
+                )))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NetworkConfig {
+    /// HTTP(S) proxy for all outgoing requests, e.g. "http://127.0.0.1:8080"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_proxy: Option<String>,
+    /// SOCKS5 proxy, e.g. "socks5://127.0.0.1:1080" (takes precedence over http_proxy)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub socks5_proxy: Option<String>,
+    /// Local IP address to bind outgoing requests to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind_address: Option<String>,
+    /// Request timeout in seconds (0 = no timeout)
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Maximum number of redirects to follow (0 = don't follow)
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize,
+    /// Attempts for TMDB/Prowlarr requests failing with a timeout, connection
+    /// error or 5xx, with backoff in between (1 = no retries)
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Override the User-Agent header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            http_proxy: None,
+            socks5_proxy: None,
+            bind_address: None,
+            timeout_secs: default_timeout_secs(),
+            max_redirects: default_max_redirects(),
+            max_attempts: default_max_attempts(),
+            user_agent: None,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// reqwest client builder with these settings applied
+    pub fn client_builder(&self) -> Result<reqwest::ClientBuilder, ConfigError> {
+        let mut builder = reqwest::Client::builder().redirect(match self.max_redirects {
+            0 => reqwest::redirect::Policy::none(),
+            n => reqwest::redirect::Policy::limited(n),
+        });
+
+        if self.timeout_secs > 0 {
+            builder = builder.timeout(Duration::from_secs(self.timeout_secs));
+        }
+
+        // The first matching proxy wins, so SOCKS5 goes first
+        for (key, url) in [
+            ("network.socks5_proxy", &self.socks5_proxy),
+            ("network.http_proxy", &self.http_proxy),
+        ] {
+            if let Some(url) = url.as_deref().filter(|u| !u.is_empty()) {
+                let proxy = reqwest::Proxy::all(url)
+                    .map_err(|e| ConfigError::ValidationError(format!("{}: {}", key, e)))?
+                    // Never proxy the local librqbit API
+                    .no_proxy(reqwest::NoProxy::from_string("localhost,127.0.0.1,::1"));
+                builder = builder.proxy(proxy);
+            }
+        }
+
+        if let Some(addr) = self.bind_address.as_deref().filter(|a| !a.is_empty()) {
+            let addr: IpAddr = addr.parse().map_err(|_| {
+                ConfigError::ValidationError(format!(
+                    "network.bind_address is not an IP address: {}",
+                    addr
+                ))
+            })?;
+            builder = builder.local_address(addr);
+        }
+
+        if let Some(user_agent) = self.user_agent.as_deref().filter(|ua| !ua.is_empty()) {
+            builder = builder.user_agent(user_agent);
+        }
+
+        Ok(builder)
+    }
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_max_redirects() -> usize {
+    10
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PlayerConfig {
     #[serde(default = "default_player_command")]
     pub command: String,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub args: Vec<String>,
+    /// Preferred audio languages as ISO codes, most preferred first (e.g. ["ja", "en"])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audio_language: Vec<String>,
+    /// Keep the current player open when opening the stream in another one
+    #[serde(default)]
+    pub multi_player: bool,
+    /// Shell command run after each file finishes playing, with FERRISTREAM_*
+    /// environment variables describing it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_play_command: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -112,12 +631,111 @@ pub struct StreamingConfig {
     /// Automatically race top N torrents and use first to connect (0 = disabled, manual selection)
     #[serde(default = "default_auto_race")]
     pub auto_race: u8,
+    /// Skip torrents with fewer seeders when auto-racing
+    #[serde(default = "default_min_seeders")]
+    pub min_seeders: u32,
+    /// Race results with this quality in the title first (e.g. "1080p")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_quality: Option<String>,
+    /// HTTP API of a running rqbit daemon to stream through instead of an embedded session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rqbit_url: Option<String>,
+    /// Keep seeding after playback: "off", "minutes(N)" or "ratio(X)"
+    #[serde(default)]
+    pub seed_after_watch: SeedPolicy,
+    /// Percent of the torrent to download before launching the player (0 = launch right away)
+    #[serde(default = "default_pre_roll_buffer_percent")]
+    pub pre_roll_buffer_percent: f32,
+    /// Longest wait for a torrent's metadata while it has peers, in seconds.
+    /// Magnets nobody has are given up on after 30s regardless.
+    #[serde(default = "default_metadata_timeout_secs")]
+    pub metadata_timeout_secs: u64,
+    /// Warn when the file about to play is too small for the TMDB runtime
+    #[serde(default)]
+    pub verify_duration: bool,
+    /// Count the peers of a picked result and ask before streaming it, seeder
+    /// counts from indexers are often stale
+    #[serde(default = "default_probe_peers")]
+    pub probe_peers: bool,
+    /// Address to share streams on for other devices on the network, e.g.
+    /// "0.0.0.0" for every interface. Unset keeps streams on localhost.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind_lan: Option<String>,
+    /// Path prefix shared streams are only served under
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lan_token: Option<String>,
+    /// Rewrites applied to every torrent search query, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query_transforms: Vec<QueryTransform>,
+}
+
+/// Find and replace on search queries, e.g. "^office$" -> "The Office US"
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct QueryTransform {
+    /// Case-insensitive regex matched against the query
+    pub pattern: String,
+    /// Replaces every match, `$1` and `${name}` refer to capture groups
+    pub replacement: String,
+}
+
+impl QueryTransform {
+    pub fn pattern(&self) -> Result<Regex, regex::Error> {
+        RegexBuilder::new(&self.pattern)
+            .case_insensitive(true)
+            .build()
+    }
 }
 
 impl Default for StreamingConfig {
     fn default() -> Self {
         Self {
             auto_race: default_auto_race(),
+            min_seeders: default_min_seeders(),
+            preferred_quality: None,
+            rqbit_url: None,
+            seed_after_watch: SeedPolicy::Off,
+            pre_roll_buffer_percent: default_pre_roll_buffer_percent(),
+            metadata_timeout_secs: default_metadata_timeout_secs(),
+            verify_duration: false,
+            probe_peers: default_probe_peers(),
+            bind_lan: None,
+            lan_token: None,
+            query_transforms: Vec::new(),
+        }
+    }
+}
+
+impl StreamingConfig {
+    pub fn metadata_timeout(&self) -> Duration {
+        Duration::from_secs(self.metadata_timeout_secs)
+    }
+
+    /// Address to share streams on, None when they stay on localhost
+    pub fn lan_address(&self) -> Option<IpAddr> {
+        self.bind_lan.as_deref()?.trim().parse().ok()
+    }
+
+    pub fn lan_token(&self) -> Option<&str> {
+        self.lan_token.as_deref().filter(|token| !token.is_empty())
+    }
+
+    /// `query` with every transform applied, invalid patterns are skipped
+    pub fn transform_query(&self, query: &str) -> String {
+        self.query_transforms
+            .iter()
+            .filter_map(|transform| Some((transform.pattern().ok()?, &transform.replacement)))
+            .fold(query.to_string(), |query, (pattern, replacement)| {
+                pattern
+                    .replace_all(&query, replacement.as_str())
+                    .trim()
+                    .to_string()
+            })
+    }
+
+    pub fn race_order(&self) -> RaceOrder {
+        RaceOrder {
+            min_seeders: self.min_seeders,
+            preferred_quality: self.preferred_quality.clone(),
         }
     }
 }
@@ -126,11 +744,30 @@ fn default_auto_race() -> u8 {
     10 // Race top 10 torrents by default
 }
 
+fn default_min_seeders() -> u32 {
+    1
+}
+
+fn default_pre_roll_buffer_percent() -> f32 {
+    2.0
+}
+
+fn default_probe_peers() -> bool {
+    true
+}
+
+fn default_metadata_timeout_secs() -> u64 {
+    crate::streaming::DEFAULT_METADATA_TIMEOUT.as_secs()
+}
+
 impl Default for PlayerConfig {
     fn default() -> Self {
         Self {
             command: default_player_command(),
             args: Vec::new(),
+            audio_language: Vec::new(),
+            multi_player: false,
+            post_play_command: None,
         }
     }
 }
@@ -139,89 +776,621 @@ fn default_player_command() -> String {
     "mpv".to_string()
 }
 
-#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct StorageConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temp_dir: Option<PathBuf>,
+    /// Leftover session directories are removed on startup, oldest first, until the temp dir is below this
+    #[serde(default = "default_max_cache_gb")]
+    pub max_cache_gb: f32,
+    /// GeoLite2 Country database for peer flags, defaults to the data directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geoip_db: Option<PathBuf>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            temp_dir: None,
+            max_cache_gb: default_max_cache_gb(),
+            geoip_db: None,
+        }
+    }
+}
+
+fn default_max_cache_gb() -> f32 {
+    10.0
 }
 
 impl StorageConfig {
+    /// Cache limit in bytes
+    pub fn max_cache_bytes(&self) -> u64 {
+        (self.max_cache_gb.max(0.0) as f64 * 1024.0 * 1024.0 * 1024.0) as u64
+    }
+
     pub fn temp_dir(&self) -> PathBuf {
         self.temp_dir
             .clone()
             .unwrap_or_else(|| std::env::temp_dir().join("ferristream"))
     }
+
+    /// Whether `cache_size` bytes is more than `max_cache_gb` allows
+    pub fn is_over_cache_limit(&self, cache_size: u64) -> bool {
+        cache_size > self.max_cache_bytes()
+    }
+
+    pub fn geoip_db(&self) -> Option<PathBuf> {
+        self.geoip_db.clone().or_else(|| {
+            ProjectDirs::from("", "", "ferristream")
+                .map(|dirs| dirs.data_dir().join("GeoLite2-Country.mmdb"))
+        })
+    }
+}
+
+/// Which config file is in use, picked with --config or --profile
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigLocation {
+    pub path: PathBuf,
+    /// None for the default config.toml
+    pub profile: Option<String>,
+}
+
+impl ConfigLocation {
+    /// The default config.toml in the XDG config dir
+    pub fn default_location() -> Result<Self, ConfigError> {
+        Ok(Self {
+            path: Config::config_path()?,
+            profile: None,
+        })
+    }
+
+    /// A named profile, stored as ferristream/<name>.toml in the XDG config dir
+    pub fn profile(name: &str) -> Result<Self, ConfigError> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(ConfigError::ValidationError(format!(
+                "invalid profile name '{}'",
+                name
+            )));
+        }
+        let dir = ProjectDirs::from("", "", "ferristream").ok_or(ConfigError::NoConfigDir)?;
+        Ok(Self {
+            path: dir.config_dir().join(format!("{}.toml", name)),
+            profile: Some(name.to_string()),
+        })
+    }
+
+    /// An explicit config file, named after its file stem
+    pub fn file(path: PathBuf) -> Self {
+        let profile = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned());
+        Self { path, profile }
+    }
+
+    /// Name shown in the UI and used to keep log files apart
+    pub fn profile_name(&self) -> &str {
+        self.profile.as_deref().unwrap_or("default")
+    }
+
+    /// Keychain account of an API key: its config field, under the profile
+    /// name for profiles ("prowlarr.apikey", "seedbox/prowlarr.apikey")
+    pub fn keychain_account(&self, field: &str) -> String {
+        match &self.profile {
+            Some(profile) => format!("{}/{}", profile, field),
+            None => field.to_string(),
+        }
+    }
+
+    /// Indexer health stats, kept next to the config file ("config.indexers.json")
+    pub fn indexer_stats_path(&self) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "config".to_string());
+        self.path.with_file_name(format!("{}.indexers.json", stem))
+    }
+
+    /// Audio track picked per title, kept next to the config file ("config.audio.json")
+    pub fn audio_preferences_path(&self) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "config".to_string());
+        self.path.with_file_name(format!("{}.audio.json", stem))
+    }
+
+    /// What was playing, kept next to the config file ("config.session.json")
+    /// until playback stops, so a killed session can be resumed
+    pub fn session_snapshot_path(&self) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "config".to_string());
+        self.path.with_file_name(format!("{}.session.json", stem))
+    }
+
+    /// Session directories cleanup couldn't remove, kept next to the config
+    /// file ("config.cleanup.json") for the next launch to retry
+    pub fn pending_cleanup_path(&self) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "config".to_string());
+        self.path.with_file_name(format!("{}.cleanup.json", stem))
+    }
+
+    /// Default log file in the XDG cache dir, one per profile so running two
+    /// doesn't clobber it ("ferristream.log", "ferristream-<profile>.log")
+    pub fn log_path(&self) -> PathBuf {
+        let name = match &self.profile {
+            Some(profile) => format!("ferristream-{}.log", profile),
+            None => "ferristream.log".to_string(),
+        };
+        ProjectDirs::from("", "", "ferristream")
+            .map(|dirs| dirs.cache_dir().to_path_buf())
+            .unwrap_or_else(|| std::env::temp_dir().join("ferristream"))
+            .join(name)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RssConfig {
+    /// How often each feed in `rss_rules` is checked
+    #[serde(default = "default_poll_interval_minutes")]
+    pub poll_interval_minutes: u64,
+}
+
+impl Default for RssConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_minutes: default_poll_interval_minutes(),
+        }
+    }
+}
+
+fn default_poll_interval_minutes() -> u64 {
+    15
+}
+
+impl RssConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_minutes.max(1) * 60)
+    }
 }
 
 impl Config {
-    pub fn load() -> Result<Self, ConfigError> {
-        let path = Self::config_path()?;
-        Self::load_from(&path)
+    pub fn load(location: &ConfigLocation) -> Result<Self, ConfigError> {
+        Self::load_from(location)
     }
 
     /// Load config, creating a default one if it doesn't exist
-    pub fn load_or_create() -> Result<Self, ConfigError> {
-        let path = Self::config_path()?;
-        if !path.exists() {
+    pub fn load_or_create(location: &ConfigLocation) -> Result<Self, ConfigError> {
+        if !location.path.exists() {
             let config = Self::default();
-            config.save()?;
+            config.save(location)?;
             return Ok(config);
         }
-        Self::load_from(&path)
+        Self::load_from(location)
     }
 
-    pub fn load_from(path: &PathBuf) -> Result<Self, ConfigError> {
+    pub fn load_from(location: &ConfigLocation) -> Result<Self, ConfigError> {
+        let config = Self::parse_file(location)?;
+        let errors = config.validate();
+        if !errors.is_empty() {
+            let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            return Err(ConfigError::ValidationError(messages.join("; ")));
+        }
+        Ok(config)
+    }
+
+    /// Read and parse a config file without validating it
+    pub fn parse_file(location: &ConfigLocation) -> Result<Self, ConfigError> {
+        let path = &location.path;
         if !path.exists() {
             return Err(ConfigError::NotFound(path.clone()));
         }
 
         let contents = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&contents)?;
-        config.validate()?;
+        let mut config: Self = toml::from_str(&contents)?;
+        config.resolve_secrets(&OsKeychainBackend, location);
         Ok(config)
     }
 
+    /// Fill in API keys kept in the keychain: the ones saying "keychain", and
+    /// empty ones when the keychain is on
+    pub fn resolve_secrets(&mut self, keychain: &dyn KeychainBackend, location: &ConfigLocation) {
+        for field in &KEYCHAIN_FIELDS {
+            let Some(value) = (field.get)(self) else {
+                continue;
+            };
+            let sentinel = value == KEYCHAIN_SENTINEL;
+            let wanted = sentinel || (value.is_empty() && self.secrets.keychain);
+            if !wanted {
+                continue;
+            }
+            match keychain.get(secrets::SERVICE, &location.keychain_account(field.name)) {
+                Some(secret) => (field.set)(self, secret),
+                None if sentinel => {
+                    warn!(field = field.name, "API key is not in the keychain");
+                    (field.set)(self, String::new());
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// The config as written to disk: with the keychain on, API keys are
+    /// stored there and replaced by "keychain". Keys the keychain won't take
+    /// stay in plain text, returned with how many of them there are.
+    fn with_secrets_stored(
+        &self,
+        keychain: &dyn KeychainBackend,
+        location: &ConfigLocation,
+    ) -> (Config, usize) {
+        let mut on_disk = self.clone();
+        let mut plain_text = 0;
+        if !self.secrets.keychain {
+            return (on_disk, plain_text);
+        }
+        for field in &KEYCHAIN_FIELDS {
+            let Some(value) = (field.get)(self).filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            if value == KEYCHAIN_SENTINEL {
+                continue;
+            }
+            let account = location.keychain_account(field.name);
+            match keychain.set(secrets::SERVICE, &account, value) {
+                Ok(()) => (field.set)(&mut on_disk, KEYCHAIN_SENTINEL.to_string()),
+                Err(e) => {
+                    warn!(field = field.name, error = %e, "keeping API key in the config file");
+                    plain_text += 1;
+                }
+            }
+        }
+        (on_disk, plain_text)
+    }
+
     pub fn config_path() -> Result<PathBuf, ConfigError> {
         ProjectDirs::from("", "", "ferristream")
             .map(|dirs| dirs.config_dir().join("config.toml"))
             .ok_or(ConfigError::NoConfigDir)
     }
 
-    pub fn save(&self) -> Result<(), ConfigError> {
-        let path = Self::config_path()?;
+    pub fn save(&self, location: &ConfigLocation) -> Result<(), ConfigError> {
+        self.save_with(location, &OsKeychainBackend).map(|_| ())
+    }
+
+    /// Save with API keys going into `keychain` if it's on, returns how many
+    /// keys it didn't take and were written in plain text
+    pub fn save_with(
+        &self,
+        location: &ConfigLocation,
+        keychain: &dyn KeychainBackend,
+    ) -> Result<usize, ConfigError> {
+        let path = &location.path;
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
-        let contents = toml::to_string_pretty(self)
+        let (on_disk, plain_text) = self.with_secrets_stored(keychain, location);
+        let contents = toml::to_string_pretty(&on_disk)
             .map_err(|e| ConfigError::ValidationError(format!("failed to serialize: {}", e)))?;
-        std::fs::write(&path, contents)?;
-        Ok(())
+        std::fs::write(path, contents)?;
+        Ok(plain_text)
     }
 
-    fn validate(&self) -> Result<(), ConfigError> {
-        // Validate Prowlarr URL
-        if self.prowlarr.url.is_empty() {
-            return Err(ConfigError::ValidationError(
-                "prowlarr.url cannot be empty".to_string(),
-            ));
-        }
+    /// Check every field, collecting all problems instead of stopping at the first
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        let mut error = |field: &str, message: String| {
+            errors.push(ValidationError {
+                field: field.to_string(),
+                message,
+            })
+        };
 
         // Strip trailing slash for consistency
         let url = self.prowlarr.url.trim_end_matches('/');
-        if !url.starts_with("http://") && !url.starts_with("https://") {
-            return Err(ConfigError::ValidationError(
-                "prowlarr.url must start with http:// or https://".to_string(),
-            ));
+        if url.is_empty() {
+            error("prowlarr.url", "cannot be empty".to_string());
+        } else if !is_http_url(url) {
+            error(
+                "prowlarr.url",
+                "must start with http:// or https://".to_string(),
+            );
         }
 
         if self.prowlarr.apikey.is_empty() {
-            return Err(ConfigError::ValidationError(
-                "prowlarr.apikey cannot be empty".to_string(),
-            ));
+            error("prowlarr.apikey", "cannot be empty".to_string());
+        }
+
+        if let Some(usenet) = &self.usenet {
+            if !is_http_url(&usenet.url) {
+                error(
+                    "usenet.url",
+                    "must start with http:// or https://".to_string(),
+                );
+            }
+            if usenet.backend == UsenetBackend::Nzbget && !usenet.apikey.contains(':') {
+                error(
+                    "usenet.apikey",
+                    "must be \"username:password\" for NZBGet".to_string(),
+                );
+            }
+        }
+
+        if self.anime.source == AnimeSource::Anidb && self.anime.anidb_client.is_none() {
+            error(
+                "anime.anidb_client",
+                "is required when anime.source is \"anidb\"".to_string(),
+            );
+        }
+
+        if let Some(url) = &self.streaming.rqbit_url
+            && !is_http_url(url)
+        {
+            error(
+                "streaming.rqbit_url",
+                "must start with http:// or https://".to_string(),
+            );
+        }
+
+        for (idx, rule) in self.rss_rules.iter().enumerate() {
+            if !is_http_url(&rule.feed_url) {
+                error(
+                    &format!("rss_rules[{}].feed_url", idx),
+                    format!("must start with http:// or https:// ({})", rule.feed_url),
+                );
+            }
+            if let Err(e) = rule.pattern() {
+                error(
+                    &format!("rss_rules[{}].title_pattern", idx),
+                    format!("invalid pattern \"{}\": {}", rule.title_pattern, e),
+                );
+            }
+        }
+
+        for (idx, command) in self.extensions.command.iter().enumerate() {
+            if command.command.trim().is_empty() {
+                error(
+                    &format!("extensions.command[{}].command", idx),
+                    "cannot be empty".to_string(),
+                );
+            }
         }
 
-        Ok(())
+        if !(0.0..=100.0).contains(&self.streaming.pre_roll_buffer_percent) {
+            error(
+                "streaming.pre_roll_buffer_percent",
+                "must be between 0 and 100".to_string(),
+            );
+        }
+
+        if !(0.0..).contains(&self.storage.max_cache_gb) {
+            error("storage.max_cache_gb", "must be 0 or more".to_string());
+        }
+
+        if self.streaming.metadata_timeout() < crate::streaming::NO_PEERS_TIMEOUT {
+            error(
+                "streaming.metadata_timeout_secs",
+                format!(
+                    "must be at least {}",
+                    crate::streaming::NO_PEERS_TIMEOUT.as_secs()
+                ),
+            );
+        }
+
+        if let Some(bind) = self.streaming.bind_lan.as_deref()
+            && bind.trim().parse::<IpAddr>().is_err()
+        {
+            error("streaming.bind_lan", format!("not an IP address: {}", bind));
+        }
+
+        if let Some(token) = self.streaming.lan_token()
+            && !token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            error(
+                "streaming.lan_token",
+                "may only contain letters, digits, - and _".to_string(),
+            );
+        }
+
+        for (idx, transform) in self.streaming.query_transforms.iter().enumerate() {
+            if let Err(e) = transform.pattern() {
+                error(
+                    &format!("streaming.query_transforms[{}].pattern", idx),
+                    format!("invalid pattern \"{}\": {}", transform.pattern, e),
+                );
+            }
+        }
+
+        if !(0.0..=100.0).contains(&self.extensions.trakt.scrobble_threshold) {
+            error(
+                "extensions.trakt.scrobble_threshold",
+                "must be between 0 and 100".to_string(),
+            );
+        }
+
+        // Catch bad proxy URLs or bind address at load time
+        match self.network.client_builder() {
+            Ok(_) => {}
+            Err(ConfigError::ValidationError(message)) => error("network", message),
+            Err(e) => error("network", e.to_string()),
+        }
+
+        errors
+    }
+
+    /// Problems that don't stop the config from loading, like an API key that
+    /// doesn't look like one. It may still work, so doctor and the settings
+    /// view only point them out.
+    pub fn warnings(&self) -> Vec<ValidationError> {
+        let keys = [
+            ("prowlarr.apikey", Some(self.prowlarr.apikey.as_str())),
+            ("tmdb.apikey", self.tmdb.as_ref().map(|t| t.apikey.as_str())),
+        ];
+        keys.into_iter()
+            .filter_map(|(field, key)| {
+                let key = key.filter(|key| !key.is_empty())?;
+                Some(ValidationError {
+                    field: field.to_string(),
+                    message: check_api_key(key)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Settings that differ between two configs, sorted by path
+    pub fn diff(old: &Config, new: &Config) -> Vec<ConfigDiff> {
+        let mut diffs = Vec::new();
+        let (Ok(old), Ok(new)) = (toml::Value::try_from(old), toml::Value::try_from(new)) else {
+            return diffs;
+        };
+        diff_values(String::new(), Some(&old), Some(&new), &mut diffs);
+        diffs
+    }
+}
+
+/// Value of a `ConfigDiff` side where the setting isn't in the config
+pub const NOT_SET: &str = "(not set)";
+
+/// A setting changed between two configs, found by `Config::diff`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigDiff {
+    /// Top-level table, e.g. "extensions"
+    pub section: String,
+    /// Path within the section, e.g. "discord.enabled" or "[0].title_pattern"
+    pub field: String,
+    /// Value as written in the config file, `NOT_SET` when missing
+    pub old_value: String,
+    pub new_value: String,
+}
+
+impl ConfigDiff {
+    /// Dotted path of the setting, e.g. "prowlarr.apikey"
+    pub fn path(&self) -> String {
+        if self.field.is_empty() || self.field.starts_with('[') {
+            format!("{}{}", self.section, self.field)
+        } else {
+            format!("{}.{}", self.section, self.field)
+        }
+    }
+
+    /// API keys, tokens and passwords, masked when shown
+    pub fn is_secret(&self) -> bool {
+        redact::is_secret_field(self.field.rsplit('.').next().unwrap_or_default())
+    }
+}
+
+impl std::fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path(),
+            self.old_value,
+            self.new_value
+        )
+    }
+}
+
+/// Collect the leaves under `path` that differ, descending into tables and arrays
+fn diff_values(
+    path: String,
+    old: Option<&toml::Value>,
+    new: Option<&toml::Value>,
+    diffs: &mut Vec<ConfigDiff>,
+) {
+    use toml::Value;
+
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match (old, new) {
+        (Some(Value::Table(old)), Some(Value::Table(new))) => {
+            let mut keys: Vec<&String> = old.keys().collect();
+            keys.extend(new.keys().filter(|k| !old.contains_key(*k)));
+            for key in keys {
+                diff_values(join(key), old.get(key), new.get(key), diffs);
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for i in 0..old.len().max(new.len()) {
+                diff_values(format!("{}[{}]", path, i), old.get(i), new.get(i), diffs);
+            }
+        }
+        (Some(Value::Table(old)), None) => {
+            for (key, value) in old {
+                diff_values(join(key), Some(value), None, diffs);
+            }
+        }
+        (None, Some(Value::Table(new))) => {
+            for (key, value) in new {
+                diff_values(join(key), None, Some(value), diffs);
+            }
+        }
+        _ if old != new => {
+            let split = path.find(['.', '[']).unwrap_or(path.len());
+            let (section, field) = path.split_at(split);
+            let display = |value: Option<&Value>| match value {
+                None => NOT_SET.to_string(),
+                Some(Value::String(s)) => s.clone(),
+                Some(value) => value.to_string(),
+            };
+            diffs.push(ConfigDiff {
+                section: section.to_string(),
+                field: field.trim_start_matches('.').to_string(),
+                old_value: display(old),
+                new_value: display(new),
+            });
+        }
+        _ => {}
+    }
+}
+
+/// A problem found by `Config::validate`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// Dotted path of the offending field, e.g. "prowlarr.url"
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Prowlarr and TMDB (v3) API keys are 32 hex characters
+pub fn check_api_key(key: &str) -> Option<String> {
+    const API_KEY_LENGTH: usize = 32;
+
+    if key.len() != API_KEY_LENGTH {
+        Some(format!(
+            "should be {} characters, got {}",
+            API_KEY_LENGTH,
+            key.len()
+        ))
+    } else if !key.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some("should only contain hex characters (0-9, a-f)".to_string())
+    } else {
+        None
     }
 }
 
@@ -233,11 +1402,325 @@ impl Default for Config {
                 apikey: String::new(),
             },
             tmdb: None,
+            usenet: None,
             player: PlayerConfig::default(),
             storage: StorageConfig::default(),
             extensions: ExtensionsConfig::default(),
             subtitles: SubtitlesConfig::default(),
             streaming: StreamingConfig::default(),
+            search: SearchConfig::default(),
+            notifications: NotificationConfig::default(),
+            display: DisplayConfig::default(),
+            filter: FilterConfig::default(),
+            anime: AnimeConfig::default(),
+            network: NetworkConfig::default(),
+            rss: RssConfig::default(),
+            rss_rules: Vec::new(),
+            secrets: SecretsConfig::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_location() {
+        let location = ConfigLocation::file(PathBuf::from("/srv/seedbox.toml"));
+        assert_eq!(location.profile_name(), "seedbox");
+
+        if let Ok(location) = ConfigLocation::profile("home") {
+            assert!(location.path.ends_with("ferristream/home.toml"));
+            assert_eq!(location.profile_name(), "home");
         }
+        assert!(ConfigLocation::profile("../escape").is_err());
+        assert!(ConfigLocation::profile("").is_err());
+
+        assert_eq!(ConfigLocation::default().profile_name(), "default");
+
+        let location = ConfigLocation::file(PathBuf::from("/srv/seedbox.toml"));
+        assert_eq!(
+            location.indexer_stats_path(),
+            PathBuf::from("/srv/seedbox.indexers.json")
+        );
+        assert!(location.log_path().ends_with("ferristream-seedbox.log"));
+        assert!(
+            ConfigLocation::default()
+                .log_path()
+                .ends_with("ferristream.log")
+        );
+    }
+
+    #[test]
+    fn test_subtitle_languages() {
+        let mut subtitles = SubtitlesConfig::default();
+        assert_eq!(subtitles.languages(), vec!["en".to_string()]);
+
+        subtitles.subtitle_languages = vec!["fr".to_string(), "en".to_string()];
+        assert_eq!(
+            subtitles.languages(),
+            vec!["fr".to_string(), "en".to_string()]
+        );
+        assert_eq!(subtitles.player_language(), Some("fr".to_string()));
+    }
+
+    #[test]
+    fn test_opensubtitles_max_wait() {
+        let subtitles: SubtitlesConfig = toml::from_str("").unwrap();
+        assert_eq!(subtitles.opensubtitles_max_wait(), Duration::from_secs(5));
+
+        let subtitles: SubtitlesConfig = toml::from_str("opensubtitles_max_wait_secs = 2").unwrap();
+        assert_eq!(subtitles.opensubtitles_max_wait(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_api_keys_in_keychain() {
+        use crate::secrets::MemoryKeychain;
+
+        let path = std::env::temp_dir().join(format!(
+            "ferristream-keychain-test-{}.toml",
+            std::process::id()
+        ));
+        let location = ConfigLocation {
+            path: path.clone(),
+            profile: None,
+        };
+        let mut config = Config::default();
+        config.prowlarr.apikey = "a".repeat(32);
+        config.tmdb = Some(TmdbConfig {
+            apikey: "b".repeat(32),
+        });
+
+        // Off by default, keys stay in the file
+        let keychain = MemoryKeychain::default();
+        assert_eq!(config.save_with(&location, &keychain).unwrap(), 0);
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .contains(&"a".repeat(32))
+        );
+
+        config.secrets.keychain = true;
+        assert_eq!(config.save_with(&location, &keychain).unwrap(), 0);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains(&"a".repeat(32)));
+        assert!(!contents.contains("opensubtitles_api_key"));
+        let mut loaded: Config = toml::from_str(&contents).unwrap();
+        assert_eq!(loaded.prowlarr.apikey, "keychain");
+
+        loaded.resolve_secrets(&keychain, &location);
+        assert_eq!(loaded.prowlarr.apikey, "a".repeat(32));
+        assert_eq!(loaded.tmdb.unwrap().apikey, "b".repeat(32));
+        assert_eq!(loaded.subtitles.opensubtitles_api_key, None);
+
+        // An empty key is looked up too once the keychain is on
+        let mut loaded: Config = toml::from_str(&contents).unwrap();
+        loaded.prowlarr.apikey.clear();
+        loaded.resolve_secrets(&keychain, &location);
+        assert_eq!(loaded.prowlarr.apikey, "a".repeat(32));
+
+        // Missing from the keychain
+        let mut loaded: Config = toml::from_str(&contents).unwrap();
+        loaded.resolve_secrets(&MemoryKeychain::default(), &location);
+        assert_eq!(loaded.prowlarr.apikey, "");
+
+        // Profiles keep their keys apart
+        let seedbox = ConfigLocation {
+            path: path.clone(),
+            profile: Some("seedbox".to_string()),
+        };
+        let mut loaded: Config = toml::from_str(&contents).unwrap();
+        loaded.resolve_secrets(&keychain, &seedbox);
+        assert_eq!(loaded.prowlarr.apikey, "");
+        let mut other = config.clone();
+        other.prowlarr.apikey = "c".repeat(32);
+        assert_eq!(other.save_with(&seedbox, &keychain).unwrap(), 0);
+        let entries = keychain.entries.lock().unwrap().clone();
+        assert_eq!(entries["ferristream/prowlarr.apikey"], "a".repeat(32));
+        assert_eq!(
+            entries["ferristream/seedbox/prowlarr.apikey"],
+            "c".repeat(32)
+        );
+
+        // A keychain that won't take them leaves them in the file
+        let locked = MemoryKeychain {
+            read_only: true,
+            ..Default::default()
+        };
+        assert_eq!(config.save_with(&location, &locked).unwrap(), 2);
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .contains(&"a".repeat(32))
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_rss_rules_round_trip() {
+        let mut config = Config::default();
+        config.rss_rules.push(RssRule {
+            feed_url: "https://showrss.info/user/1234.rss".to_string(),
+            title_pattern: "severance".to_string(),
+            min_quality: Some("1080p".to_string()),
+            auto_stream: true,
+        });
+
+        let toml = toml::to_string_pretty(&config).unwrap();
+        assert!(toml.contains("[[rss_rules]]"));
+        let parsed: Config = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed.rss_rules, config.rss_rules);
+        assert_eq!(parsed.rss.poll_interval_minutes, 15);
+    }
+
+    #[test]
+    fn test_query_transforms() {
+        let toml = r#"
+            [prowlarr]
+            url = "http://localhost:9696"
+            apikey = "0123456789abcdef0123456789abcdef"
+
+            [[streaming.query_transforms]]
+            pattern = "^office( \\d{4})?$"
+            replacement = "The Office US$1"
+
+            [[streaming.query_transforms]]
+            pattern = "$"
+            replacement = " 1080p"
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let streaming = &config.streaming;
+
+        assert_eq!(
+            streaming.transform_query("Office 2005"),
+            "The Office US 2005 1080p"
+        );
+        // Only whole queries are rewritten, later transforms still apply
+        assert_eq!(streaming.transform_query("The Office"), "The Office 1080p");
+
+        // Without transforms the query is left alone
+        assert_eq!(
+            StreamingConfig::default().transform_query("Office"),
+            "Office"
+        );
+    }
+
+    #[test]
+    fn test_config_diff() {
+        let old = Config::default();
+        assert!(Config::diff(&old, &old.clone()).is_empty());
+
+        let mut new = old.clone();
+        new.prowlarr.apikey = "new-key".to_string();
+        new.extensions.discord.enabled = true;
+        new.tmdb = Some(TmdbConfig {
+            apikey: "tmdb-key".to_string(),
+        });
+
+        let diffs = Config::diff(&old, &new);
+        let paths: Vec<String> = diffs.iter().map(ConfigDiff::path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "extensions.discord.enabled",
+                "prowlarr.apikey",
+                "tmdb.apikey"
+            ]
+        );
+        assert_eq!(diffs[0].section, "extensions");
+        assert_eq!(diffs[0].field, "discord.enabled");
+        assert_eq!(
+            diffs[0].to_string(),
+            "extensions.discord.enabled: false -> true"
+        );
+        assert!(!diffs[0].is_secret());
+        assert_eq!(diffs[1].new_value, "new-key");
+        assert!(diffs[1].is_secret());
+        assert_eq!(diffs[2].old_value, "(not set)");
+    }
+
+    #[test]
+    fn test_validate_collects_all_errors() {
+        let mut config = Config::default();
+        config.prowlarr.apikey = "0123456789abcdef0123456789abcdef".to_string();
+        assert!(config.validate().is_empty());
+
+        config.prowlarr.url = "localhost:9696".to_string();
+        config.prowlarr.apikey = String::new();
+        config.usenet = Some(UsenetConfig {
+            backend: UsenetBackend::Nzbget,
+            url: "http://localhost:6789".to_string(),
+            apikey: "nopassword".to_string(),
+        });
+        config.rss_rules.push(RssRule {
+            feed_url: "https://showrss.info/user/1234.rss".to_string(),
+            title_pattern: "(unclosed".to_string(),
+            min_quality: None,
+            auto_stream: false,
+        });
+        config.extensions.command.push(CommandExtensionConfig {
+            name: "movie-mode".to_string(),
+            command: " ".to_string(),
+            args: Vec::new(),
+        });
+        config.streaming.pre_roll_buffer_percent = 150.0;
+        config.storage.max_cache_gb = -1.0;
+        config.streaming.metadata_timeout_secs = 10;
+        config.streaming.bind_lan = Some("lan".to_string());
+        config.streaming.lan_token = Some("a/b".to_string());
+        config.streaming.query_transforms.push(QueryTransform {
+            pattern: "office[".to_string(),
+            replacement: "The Office US".to_string(),
+        });
+        config.extensions.trakt.scrobble_threshold = -5.0;
+
+        let fields: Vec<String> = config.validate().into_iter().map(|e| e.field).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "prowlarr.url",
+                "prowlarr.apikey",
+                "usenet.apikey",
+                "rss_rules[0].title_pattern",
+                "extensions.command[0].command",
+                "streaming.pre_roll_buffer_percent",
+                "storage.max_cache_gb",
+                "streaming.metadata_timeout_secs",
+                "streaming.bind_lan",
+                "streaming.lan_token",
+                "streaming.query_transforms[0].pattern",
+                "extensions.trakt.scrobble_threshold"
+            ]
+        );
+        assert_eq!(
+            config.validate()[1].to_string(),
+            "prowlarr.apikey: cannot be empty"
+        );
+    }
+
+    #[test]
+    fn test_api_key_format_warnings() {
+        let mut config = Config::default();
+        config.prowlarr.apikey = "short".to_string();
+        config.tmdb = Some(TmdbConfig {
+            apikey: "zz23456789abcdef0123456789abcdef".to_string(),
+        });
+
+        // Odd keys still load
+        assert!(config.validate().is_empty());
+        let warnings: Vec<String> = config.warnings().iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "prowlarr.apikey: should be 32 characters, got 5",
+                "tmdb.apikey: should only contain hex characters (0-9, a-f)"
+            ]
+        );
+
+        config.prowlarr.apikey = "0123456789abcdef0123456789abcdef".to_string();
+        config.tmdb = None;
+        assert!(config.warnings().is_empty());
     }
 }
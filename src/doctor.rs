@@ -1,6 +1,8 @@
 use crate::config::Config;
 use crate::prowlarr::ProwlarrClient;
+use crate::streaming::{self, RemoteRqbitBackend};
 use crate::tmdb::TmdbClient;
+use std::time::SystemTime;
 
 pub struct CheckResult {
     pub name: String,
@@ -65,6 +67,11 @@ pub async fn run_checks(config: &Config) -> Vec<CheckResult> {
     // Check TMDB
     results.push(check_tmdb(config).await);
 
+    // Settings that load but look wrong
+    for warning in config.warnings() {
+        results.push(CheckResult::warning("Config", &warning.to_string()));
+    }
+
     // Check extensions
     if config.extensions.discord.enabled {
         results.push(check_discord(config));
@@ -75,10 +82,20 @@ pub async fn run_checks(config: &Config) -> Vec<CheckResult> {
     }
 
     // Check player
-    results.push(check_player(config));
+    results.push(check_player(config).await);
 
     // Check storage
     results.push(check_storage(config));
+    results.push(check_stale_sessions(config).await);
+
+    if let Some(bind) = &config.streaming.bind_lan {
+        results.push(check_lan_sharing(bind, config.streaming.lan_token()));
+    }
+
+    // Check external rqbit daemon
+    if let Some(url) = &config.streaming.rqbit_url {
+        results.push(check_rqbit(url).await);
+    }
 
     results
 }
@@ -86,7 +103,7 @@ pub async fn run_checks(config: &Config) -> Vec<CheckResult> {
 async fn check_prowlarr(config: &Config) -> CheckResult {
     let client = ProwlarrClient::new(&config.prowlarr);
 
-    match client.get_usable_indexers().await {
+    match client.get_usable_indexers(config.usenet.is_some()).await {
         Ok(indexers) => {
             if indexers.is_empty() {
                 CheckResult::warning(
@@ -148,13 +165,63 @@ async fn check_trakt(config: &Config) -> CheckResult {
     CheckResult::ok("Trakt", "Configured with access token")
 }
 
-fn check_player(config: &Config) -> CheckResult {
+async fn check_player(config: &Config) -> CheckResult {
     let player = &config.player.command;
+    let installed = streaming::find_players().await;
+
+    if let Some(found) = installed.iter().find(|p| &p.command == player) {
+        let version = found
+            .version
+            .as_deref()
+            .map(|v| format!(" ({})", v))
+            .unwrap_or_default();
+        return CheckResult::ok(
+            "Player",
+            &format!("{} found at {}{}", player, found.path.display(), version),
+        );
+    }
+    // A player we don't know, on PATH or given by path
+    if let Ok(path) = which::which(player) {
+        return CheckResult::ok("Player", &format!("{} found at {}", player, path.display()));
+    }
+
+    let message = if installed.is_empty() {
+        format!("'{}' not found in PATH", player)
+    } else {
+        let names: Vec<_> = installed.iter().map(|p| p.command.as_str()).collect();
+        format!(
+            "'{}' not found in PATH, installed: {}",
+            player,
+            names.join(", ")
+        )
+    };
+    CheckResult::error("Player", &message)
+}
 
-    // Check if player exists in PATH
-    match which::which(player) {
-        Ok(path) => CheckResult::ok("Player", &format!("{} found at {}", player, path.display())),
-        Err(_) => CheckResult::error("Player", &format!("'{}' not found in PATH", player)),
+async fn check_rqbit(url: &str) -> CheckResult {
+    match RemoteRqbitBackend::new(url).version().await {
+        Ok(version) => CheckResult::ok(
+            "rqbit",
+            &format!("Daemon v{} reachable at {}", version, url),
+        ),
+        Err(e) => CheckResult::error("rqbit", &format!("Daemon at {} not reachable: {}", url, e)),
+    }
+}
+
+fn check_lan_sharing(bind: &str, token: Option<&str>) -> CheckResult {
+    if token.is_some() {
+        CheckResult::ok(
+            "LAN sharing",
+            &format!("Streams shared on {} with a token", bind),
+        )
+    } else {
+        CheckResult::warning(
+            "LAN sharing",
+            &format!(
+                "Streams shared on {} without lan_token, anyone on the network can watch",
+                bind
+            ),
+        )
     }
 }
 
@@ -183,6 +250,46 @@ fn check_storage(config: &Config) -> CheckResult {
     }
 }
 
+async fn check_stale_sessions(config: &Config) -> CheckResult {
+    let (stale, total) =
+        streaming::find_stale_sessions(&config.storage.temp_dir(), SystemTime::now()).await;
+    let gb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0 / 1024.0;
+    let stale_size: u64 = stale.iter().map(|s| s.size).sum();
+
+    if stale.is_empty() && config.storage.is_over_cache_limit(total) {
+        CheckResult::warning(
+            "Cache",
+            &format!(
+                "{:.1} GB, over the {} GB max_cache_gb",
+                gb(total),
+                config.storage.max_cache_gb
+            ),
+        )
+    } else if stale.is_empty() {
+        CheckResult::ok("Cache", &format!("{:.1} GB, no stale sessions", gb(total)))
+    } else if total > config.storage.max_cache_bytes() {
+        CheckResult::warning(
+            "Cache",
+            &format!(
+                "{} stale session(s) using {:.1} GB, over the {} GB limit - removed on next start",
+                stale.len(),
+                gb(stale_size),
+                config.storage.max_cache_gb
+            ),
+        )
+    } else {
+        CheckResult::ok(
+            "Cache",
+            &format!(
+                "{} stale session(s) using {:.1} GB, within the {} GB limit",
+                stale.len(),
+                gb(stale_size),
+                config.storage.max_cache_gb
+            ),
+        )
+    }
+}
+
 pub fn print_results(results: &[CheckResult]) {
     let reset = "\x1b[0m";
 
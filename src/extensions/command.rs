@@ -0,0 +1,358 @@
+use super::{ErrorReporter, Extension, MediaInfo, PlaybackEvent};
+use crate::config::CommandExtensionConfig;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Version of the event JSON, bumped on incompatible changes
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Failed runs in a row before a command is disabled
+const MAX_FAILURES: u32 = 3;
+
+/// How long a command may take to handle an event
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One event as written to a command's stdin, a single line of JSON:
+///
+/// ```json
+/// {"version":1,"event":"stopped","media":{...},"watched_percent":92.5}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventMessage {
+    pub version: u32,
+    #[serde(flatten)]
+    pub event: EventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    Started {
+        media: MediaMessage,
+    },
+    Progress {
+        media: MediaMessage,
+        downloaded_bytes: u64,
+        position_percent: f64,
+    },
+    Paused {
+        media: MediaMessage,
+        position_percent: f64,
+    },
+    Resumed {
+        media: MediaMessage,
+        position_percent: f64,
+    },
+    Stopped {
+        media: MediaMessage,
+        watched_percent: f64,
+    },
+}
+
+/// `MediaInfo` in the event JSON, unknown values are null
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaMessage {
+    pub title: String,
+    pub file_name: String,
+    pub total_bytes: u64,
+    pub tmdb_id: Option<u64>,
+    pub year: Option<u32>,
+    /// "movie", "tv" or "anime"
+    pub media_type: Option<String>,
+    pub poster_url: Option<String>,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+}
+
+impl From<&MediaInfo> for MediaMessage {
+    fn from(media: &MediaInfo) -> Self {
+        Self {
+            title: media.title.clone(),
+            file_name: media.file_name.clone(),
+            total_bytes: media.total_bytes,
+            tmdb_id: media.tmdb_id,
+            year: media.year,
+            media_type: media.media_type.clone(),
+            poster_url: media.poster_url.clone(),
+            season: media.season,
+            episode: media.episode,
+        }
+    }
+}
+
+impl From<&PlaybackEvent> for EventMessage {
+    fn from(event: &PlaybackEvent) -> Self {
+        let event = match event {
+            PlaybackEvent::Started(media) => EventKind::Started {
+                media: media.into(),
+            },
+            PlaybackEvent::Progress {
+                media,
+                downloaded_bytes,
+                position_percent,
+            } => EventKind::Progress {
+                media: media.into(),
+                downloaded_bytes: *downloaded_bytes,
+                position_percent: *position_percent,
+            },
+            PlaybackEvent::Paused {
+                media,
+                position_percent,
+            } => EventKind::Paused {
+                media: media.into(),
+                position_percent: *position_percent,
+            },
+            PlaybackEvent::Resumed {
+                media,
+                position_percent,
+            } => EventKind::Resumed {
+                media: media.into(),
+                position_percent: *position_percent,
+            },
+            PlaybackEvent::Stopped {
+                media,
+                watched_percent,
+            } => EventKind::Stopped {
+                media: media.into(),
+                watched_percent: *watched_percent,
+            },
+        };
+        Self {
+            version: SCHEMA_VERSION,
+            event,
+        }
+    }
+}
+
+/// Runs a user command for every playback event, with the event as JSON on
+/// stdin. The command's output is discarded so it can't draw over the TUI.
+pub struct CommandExtension {
+    name: String,
+    command: String,
+    args: Vec<String>,
+    enabled: Arc<AtomicBool>,
+    failures: Arc<AtomicU32>,
+    errors: Option<ErrorReporter>,
+}
+
+impl CommandExtension {
+    pub fn new(config: &CommandExtensionConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            command: config.command.clone(),
+            args: config.args.clone(),
+            enabled: Arc::new(AtomicBool::new(false)),
+            failures: Arc::new(AtomicU32::new(0)),
+            errors: None,
+        }
+    }
+}
+
+/// Run the command once, feeding it `line`
+async fn run_command(command: &str, args: &[String], line: &[u8]) -> Result<(), String> {
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that doesn't read its input is fine
+        let _ = stdin.write_all(line).await;
+    }
+
+    match tokio::time::timeout(COMMAND_TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) if status.success() => Ok(()),
+        Ok(Ok(status)) => Err(format!("exited with {}", status)),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("timed out after {}s", COMMAND_TIMEOUT.as_secs())),
+    }
+}
+
+impl Extension for CommandExtension {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_error_reporter(&mut self, reporter: ErrorReporter) {
+        self.errors = Some(reporter);
+    }
+
+    fn on_init(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.command.trim().is_empty() {
+            return Err("command extension requires a command".into());
+        }
+        self.enabled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn on_event(&self, event: &PlaybackEvent) {
+        if !self.enabled.load(Ordering::SeqCst) {
+            return;
+        }
+        let mut line = match serde_json::to_vec(&EventMessage::from(event)) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::error!(name = %self.name, error = %e, "failed to serialize event");
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let name = self.name.clone();
+        let command = self.command.clone();
+        let args = self.args.clone();
+        let enabled = self.enabled.clone();
+        let failures = self.failures.clone();
+        let errors = self.errors.clone();
+
+        // Never wait on the command, playback goes on regardless
+        tokio::spawn(async move {
+            match run_command(&command, &args, &line).await {
+                Ok(()) => {
+                    tracing::debug!(name = %name, "command extension ran");
+                    failures.store(0, Ordering::SeqCst);
+                }
+                Err(e) => {
+                    let count = failures.fetch_add(1, Ordering::SeqCst) + 1;
+                    tracing::warn!(
+                        name = %name,
+                        error = %e,
+                        failures = count,
+                        "command extension failed"
+                    );
+                    if count >= MAX_FAILURES && enabled.swap(false, Ordering::SeqCst) {
+                        tracing::error!(name = %name, "command extension disabled");
+                        if let Some(errors) = errors {
+                            errors.report(format!(
+                                "{} disabled after {} failures: {}",
+                                name, count, e
+                            ));
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+
+    fn media() -> MediaInfo {
+        MediaInfo {
+            title: "Frieren".to_string(),
+            file_name: "Frieren.S01E12.1080p.mkv".to_string(),
+            total_bytes: 1_500_000_000,
+            tmdb_id: Some(209867),
+            year: Some(2023),
+            media_type: Some("anime".to_string()),
+            poster_url: None,
+            season: Some(1),
+            episode: Some(12),
+        }
+    }
+
+    fn extension(command: &str, args: &[&str]) -> (CommandExtension, Receiver<String>) {
+        let (errors_tx, errors_rx) = std::sync::mpsc::channel();
+        let mut ext = CommandExtension::new(&CommandExtensionConfig {
+            name: "test".to_string(),
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        });
+        ext.set_error_reporter(ErrorReporter(errors_tx));
+        ext.on_init().unwrap();
+        (ext, errors_rx)
+    }
+
+    #[test]
+    fn test_event_schema() {
+        let event = PlaybackEvent::Stopped {
+            media: media(),
+            watched_percent: 92.5,
+        };
+        let json = serde_json::to_value(EventMessage::from(&event)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": 1,
+                "event": "stopped",
+                "media": {
+                    "title": "Frieren",
+                    "file_name": "Frieren.S01E12.1080p.mkv",
+                    "total_bytes": 1_500_000_000u64,
+                    "tmdb_id": 209867,
+                    "year": 2023,
+                    "media_type": "anime",
+                    "poster_url": null,
+                    "season": 1,
+                    "episode": 12
+                },
+                "watched_percent": 92.5
+            })
+        );
+
+        let paused = EventMessage::from(&PlaybackEvent::Paused {
+            media: media(),
+            position_percent: 40.0,
+        });
+        let line = serde_json::to_string(&paused).unwrap();
+        assert!(line.starts_with(r#"{"version":1,"event":"paused""#));
+        assert!(line.ends_with(r#""position_percent":40.0}"#));
+
+        // Scripts written against the schema can read it back
+        let started = EventMessage::from(&PlaybackEvent::Started(media()));
+        let line = serde_json::to_string(&started).unwrap();
+        assert!(line.starts_with(r#"{"version":1,"event":"started""#));
+        assert_eq!(
+            serde_json::from_str::<EventMessage>(&line).unwrap(),
+            started
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_gets_event_on_stdin() {
+        let out = std::env::temp_dir().join(format!("ferristream-cmd-test-{}", std::process::id()));
+        let script = format!("cat > '{}'", out.display());
+        let (ext, _errors) = extension("sh", &["-c", &script]);
+
+        ext.on_event(&PlaybackEvent::Started(media()));
+        let mut written = String::new();
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            written = std::fs::read_to_string(&out).unwrap_or_default();
+            if written.ends_with('\n') {
+                break;
+            }
+        }
+        std::fs::remove_file(&out).ok();
+
+        let message: EventMessage = serde_json::from_str(written.trim()).unwrap();
+        assert!(matches!(message.event, EventKind::Started { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_failing_command_gets_disabled() {
+        let (ext, errors) = extension("ferristream-no-such-command", &[]);
+
+        for _ in 0..MAX_FAILURES {
+            ext.on_event(&PlaybackEvent::Started(media()));
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(!ext.enabled.load(Ordering::SeqCst));
+        let errors: Vec<String> = errors.try_iter().collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("test disabled after 3 failures"));
+    }
+}
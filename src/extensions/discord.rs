@@ -81,6 +81,7 @@ impl Extension for DiscordExtension {
                 // Determine state based on media type
                 let state = match media.media_type.as_deref() {
                     Some("tv") | Some("show") => "Watching TV Show",
+                    Some("anime") => "Watching Anime",
                     Some("movie") => "Watching Movie",
                     _ => "Streaming",
                 };
@@ -103,7 +104,9 @@ impl Extension for DiscordExtension {
                     tracing::debug!(error = %e, "discord: failed to set activity");
                 }
             }
-            PlaybackEvent::Progress { .. } => {
+            PlaybackEvent::Progress { .. }
+            | PlaybackEvent::Paused { .. }
+            | PlaybackEvent::Resumed { .. } => {
                 // Don't update on every progress tick - too noisy
             }
             PlaybackEvent::Stopped { media, .. } => {
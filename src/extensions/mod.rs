@@ -1,9 +1,16 @@
+pub mod command;
 pub mod discord;
+pub mod simkl;
 pub mod trakt;
 
+pub use command::CommandExtension;
 pub use discord::DiscordExtension;
+pub use simkl::SimklExtension;
 pub use trakt::TraktExtension;
 
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+
 /// Information about the currently playing media
 #[derive(Debug, Clone)]
 pub struct MediaInfo {
@@ -14,7 +21,7 @@ pub struct MediaInfo {
     pub tmdb_id: Option<u64>,
     /// Year of release
     pub year: Option<u32>,
-    /// Media type (movie or tv)
+    /// Media type (movie, tv or anime)
     pub media_type: Option<String>,
     /// Poster URL from TMDB (for Discord RPC)
     pub poster_url: Option<String>,
@@ -77,7 +84,8 @@ pub fn parse_episode_info(filename: &str) -> (Option<u32>, Option<u32>) {
     (None, None)
 }
 
-/// Playback event sent to extensions
+/// Playback event sent to extensions, see `command::EventMessage` for the
+/// JSON form external commands get
 #[derive(Debug, Clone)]
 pub enum PlaybackEvent {
     Started(MediaInfo),
@@ -86,12 +94,31 @@ pub enum PlaybackEvent {
         downloaded_bytes: u64,
         position_percent: f64,
     },
+    /// The player was paused, sent by players that report it (mpv)
+    Paused {
+        media: MediaInfo,
+        position_percent: f64,
+    },
+    Resumed {
+        media: MediaInfo,
+        position_percent: f64,
+    },
     Stopped {
         media: MediaInfo,
         watched_percent: f64,
     },
 }
 
+/// Handed to extensions so failures in their background tasks reach the UI
+#[derive(Debug, Clone)]
+pub struct ErrorReporter(Sender<String>);
+
+impl ErrorReporter {
+    pub fn report(&self, message: impl Into<String>) {
+        let _ = self.0.send(message.into());
+    }
+}
+
 /// Trait for ferristream extensions
 ///
 /// Implement this trait to create a new extension.
@@ -101,6 +128,9 @@ pub trait Extension: Send + Sync {
     /// Unique name for this extension
     fn name(&self) -> &str;
 
+    /// Called before `on_init` with a reporter for user-facing errors
+    fn set_error_reporter(&mut self, _reporter: ErrorReporter) {}
+
     /// Called when extension is loaded
     fn on_init(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         Ok(())
@@ -116,17 +146,23 @@ pub trait Extension: Send + Sync {
 /// Manages all loaded extensions
 pub struct ExtensionManager {
     extensions: Vec<Box<dyn Extension>>,
+    errors_tx: Sender<String>,
+    errors_rx: Mutex<Receiver<String>>,
 }
 
 impl ExtensionManager {
     pub fn new() -> Self {
+        let (errors_tx, errors_rx) = mpsc::channel();
         Self {
             extensions: Vec::new(),
+            errors_tx,
+            errors_rx: Mutex::new(errors_rx),
         }
     }
 
     /// Register an extension
     pub fn register(&mut self, mut ext: Box<dyn Extension>) {
+        ext.set_error_reporter(ErrorReporter(self.errors_tx.clone()));
         match ext.on_init() {
             Ok(()) => {
                 tracing::info!(name = ext.name(), "extension loaded");
@@ -134,10 +170,18 @@ impl ExtensionManager {
             }
             Err(e) => {
                 tracing::error!(name = ext.name(), error = %e, "failed to load extension");
+                let _ =
+                    self.errors_tx
+                        .send(format!("{} extension failed to load: {}", ext.name(), e));
             }
         }
     }
 
+    /// Errors reported since the last call, for showing in the UI
+    pub fn take_errors(&self) -> Vec<String> {
+        self.errors_rx.lock().unwrap().try_iter().collect()
+    }
+
     /// Broadcast an event to all extensions
     pub fn broadcast(&self, event: PlaybackEvent) {
         for ext in &self.extensions {
@@ -0,0 +1,290 @@
+use super::{ErrorReporter, Extension, MediaInfo, PlaybackEvent};
+use crate::config::SimklConfig;
+use reqwest::Client;
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const SIMKL_API_URL: &str = "https://api.simkl.com";
+
+/// Simkl tracking extension
+///
+/// Marks movies and episodes as watched on Simkl and moves shows to the
+/// "watching" list when they start.
+/// Requires `client_id` and `access_token` in config.
+pub struct SimklExtension {
+    enabled: Arc<AtomicBool>,
+    client: Client,
+    client_id: Option<String>,
+    access_token: Option<String>,
+    watched_threshold: f64,
+    errors: Option<ErrorReporter>,
+}
+
+/// Body for `/sync/history` and `/sync/add-to-list`
+#[derive(Debug, Default, Serialize)]
+struct SyncRequest {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    movies: Vec<SyncItem>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    shows: Vec<SyncItem>,
+}
+
+#[derive(Debug, Serialize)]
+struct SyncItem {
+    /// Target list for `/sync/add-to-list`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<&'static str>,
+    ids: SyncIds,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    seasons: Vec<SyncSeason>,
+}
+
+#[derive(Debug, Serialize)]
+struct SyncIds {
+    tmdb: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct SyncSeason {
+    number: u32,
+    episodes: Vec<SyncEpisode>,
+}
+
+#[derive(Debug, Serialize)]
+struct SyncEpisode {
+    number: u32,
+}
+
+impl SimklExtension {
+    pub fn new(config: &SimklConfig) -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(false)),
+            client: crate::http::client(),
+            client_id: config.client_id.clone(),
+            access_token: config.access_token.clone(),
+            watched_threshold: 80.0,
+            errors: None,
+        }
+    }
+
+    fn is_show(media: &MediaInfo) -> bool {
+        media
+            .media_type
+            .as_ref()
+            .is_some_and(|t| t == "tv" || t == "show" || t == "anime")
+    }
+
+    /// Mark a movie, or the episode parsed from the filename, as watched
+    fn history_request(media: &MediaInfo) -> Option<SyncRequest> {
+        let ids = SyncIds {
+            tmdb: media.tmdb_id?,
+        };
+
+        if !Self::is_show(media) {
+            return Some(SyncRequest {
+                movies: vec![SyncItem {
+                    to: None,
+                    ids,
+                    seasons: Vec::new(),
+                }],
+                ..Default::default()
+            });
+        }
+
+        let (Some(season), Some(episode)) = (media.season, media.episode) else {
+            tracing::debug!(
+                title = %media.title,
+                filename = %media.file_name,
+                "simkl: no episode info found in filename"
+            );
+            return None;
+        };
+        Some(SyncRequest {
+            shows: vec![SyncItem {
+                to: None,
+                ids,
+                seasons: vec![SyncSeason {
+                    number: season,
+                    episodes: vec![SyncEpisode { number: episode }],
+                }],
+            }],
+            ..Default::default()
+        })
+    }
+
+    /// Move a show to the "watching" list - Simkl has no such list for movies
+    fn watching_request(media: &MediaInfo) -> Option<SyncRequest> {
+        if !Self::is_show(media) {
+            return None;
+        }
+        Some(SyncRequest {
+            shows: vec![SyncItem {
+                to: Some("watching"),
+                ids: SyncIds {
+                    tmdb: media.tmdb_id?,
+                },
+                seasons: Vec::new(),
+            }],
+            ..Default::default()
+        })
+    }
+
+    fn sync(&self, path: &str, title: &str, request: SyncRequest) {
+        let (Some(client_id), Some(access_token)) =
+            (self.client_id.clone(), self.access_token.clone())
+        else {
+            return;
+        };
+
+        let url = format!("{}{}", SIMKL_API_URL, path);
+        let client = self.client.clone();
+        let title = title.to_string();
+        let path = path.to_string();
+        let errors = self.errors.clone();
+
+        // Spawn async task for the HTTP request
+        tokio::spawn(async move {
+            let result = client
+                .post(&url)
+                .header("simkl-api-key", &client_id)
+                .header("Authorization", format!("Bearer {}", access_token))
+                .json(&request)
+                .send()
+                .await;
+
+            match result {
+                Ok(resp) if resp.status().is_success() => {
+                    tracing::info!(title = %title, path = %path, "simkl: sync successful");
+                }
+                Ok(resp) => {
+                    tracing::warn!(title = %title, status = %resp.status(), "simkl: sync failed");
+                    if let Some(errors) = errors {
+                        errors.report(format!("Simkl sync failed ({})", resp.status()));
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(title = %title, error = %e, "simkl: request failed");
+                    if let Some(errors) = errors {
+                        errors.report(format!("Simkl request failed: {}", e));
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl Extension for SimklExtension {
+    fn name(&self) -> &str {
+        "simkl"
+    }
+
+    fn set_error_reporter(&mut self, reporter: ErrorReporter) {
+        self.errors = Some(reporter);
+    }
+
+    fn on_init(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.client_id.is_none() || self.access_token.is_none() {
+            return Err("simkl extension requires client_id and access_token in config".into());
+        }
+
+        tracing::info!("simkl: extension initialized");
+        self.enabled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn on_event(&self, event: &PlaybackEvent) {
+        if !self.enabled.load(Ordering::SeqCst) {
+            return;
+        }
+
+        match event {
+            PlaybackEvent::Started(media) => {
+                if let Some(request) = Self::watching_request(media) {
+                    tracing::debug!(title = %media.title, "simkl: marking show as watching");
+                    self.sync("/sync/add-to-list", &media.title, request);
+                }
+            }
+            PlaybackEvent::Progress { .. }
+            | PlaybackEvent::Paused { .. }
+            | PlaybackEvent::Resumed { .. } => {}
+            PlaybackEvent::Stopped {
+                media,
+                watched_percent,
+            } => {
+                if *watched_percent <= self.watched_threshold {
+                    tracing::debug!(
+                        title = %media.title,
+                        watched = watched_percent,
+                        "simkl: stopped early, not marking watched"
+                    );
+                    return;
+                }
+                match Self::history_request(media) {
+                    Some(request) => self.sync("/sync/history", &media.title, request),
+                    None => tracing::debug!(title = %media.title, "simkl: no TMDB ID, skipping"),
+                }
+            }
+        }
+    }
+
+    fn on_shutdown(&self) {
+        tracing::debug!("simkl: extension shutdown");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media(media_type: &str, season: Option<u32>, episode: Option<u32>) -> MediaInfo {
+        MediaInfo {
+            title: "Frieren".to_string(),
+            file_name: "Frieren.S01E12.1080p.mkv".to_string(),
+            total_bytes: 0,
+            tmdb_id: Some(209867),
+            year: Some(2023),
+            media_type: Some(media_type.to_string()),
+            poster_url: None,
+            season,
+            episode,
+        }
+    }
+
+    #[test]
+    fn test_history_request() {
+        let request = SimklExtension::history_request(&media("anime", Some(1), Some(12))).unwrap();
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "shows": [{
+                    "ids": {"tmdb": 209867},
+                    "seasons": [{"number": 1, "episodes": [{"number": 12}]}]
+                }]
+            })
+        );
+
+        let request = SimklExtension::history_request(&media("movie", None, None)).unwrap();
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"movies": [{"ids": {"tmdb": 209867}}]})
+        );
+
+        // Shows need the episode to be marked watched
+        assert!(SimklExtension::history_request(&media("tv", None, None)).is_none());
+    }
+
+    #[test]
+    fn test_watching_request() {
+        let request = SimklExtension::watching_request(&media("tv", None, None)).unwrap();
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"shows": [{"to": "watching", "ids": {"tmdb": 209867}}]})
+        );
+
+        assert!(SimklExtension::watching_request(&media("movie", None, None)).is_none());
+    }
+}
@@ -1,24 +1,158 @@
-use super::{Extension, MediaInfo, PlaybackEvent};
-use reqwest::Client;
-use serde::Serialize;
-use std::sync::Arc;
+use super::{ErrorReporter, Extension, MediaInfo, PlaybackEvent};
+use crate::config::TraktConfig;
+use directories::ProjectDirs;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 const TRAKT_API_URL: &str = "https://api.trakt.tv";
+/// Progress above which trakt itself counts a stop scrobble as watched
+const TRAKT_WATCHED_PERCENT: f64 = 80.0;
+/// Redirect URI for apps without a callback, as registered on trakt.tv
+const OOB_REDIRECT_URI: &str = "urn:ietf:wg:oauth:2.0:oob";
 
 /// Trakt.tv scrobbling extension
 ///
 /// Syncs watch history to Trakt.tv.
-/// Requires `client_id` and `access_token` in config.
+/// Requires `client_id` and `access_token` in config, plus `client_secret`
+/// and `refresh_token` to renew the access token when it expires.
+/// Pause and stop scrobbles that can't be sent are queued and retried on the
+/// next launch.
 pub struct TraktExtension {
     enabled: Arc<AtomicBool>,
     client: Client,
     client_id: Option<String>,
-    access_token: Option<String>,
+    client_secret: Option<String>,
+    tokens: Arc<Mutex<TraktTokens>>,
     scrobble_threshold: f64,
+    errors: Option<ErrorReporter>,
+}
+
+/// OAuth tokens - refreshed ones are saved to the data dir, since trakt
+/// invalidates the old refresh token and config.toml is the user's file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct TraktTokens {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    /// Refresh token from config.toml these were refreshed from, so new
+    /// credentials pasted into the config win over saved ones
+    #[serde(default)]
+    config_refresh_token: Option<String>,
+}
+
+impl TraktTokens {
+    fn from_config(config: &TraktConfig) -> Self {
+        let from_config = Self {
+            access_token: config.access_token.clone(),
+            refresh_token: config.refresh_token.clone(),
+            config_refresh_token: config.refresh_token.clone(),
+        };
+        match Self::load() {
+            Some(saved) if saved.config_refresh_token == from_config.config_refresh_token => saved,
+            _ => from_config,
+        }
+    }
+
+    fn load() -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path()?).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        match serde_json::to_string_pretty(self) {
+            Ok(json) => {
+                if let Err(e) = std::fs::write(&path, json) {
+                    tracing::warn!(error = %e, "trakt: failed to save refreshed tokens");
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "trakt: failed to serialize tokens"),
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "ferristream").map(|dirs| dirs.data_dir().join("trakt.json"))
+    }
+}
+
+/// A scrobble that couldn't be sent, saved to retry on the next launch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QueuedScrobble {
+    endpoint: String,
+    title: String,
+    request: ScrobbleRequest,
+}
+
+/// Serializes queue file updates from concurrent scrobble tasks
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+fn queue_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "ferristream").map(|dirs| dirs.data_dir().join("trakt_queue.json"))
+}
+
+fn load_queue(path: &Path) -> Vec<QueuedScrobble> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_queue(path: &Path, queue: &[QueuedScrobble]) {
+    if queue.is_empty() {
+        let _ = std::fs::remove_file(path);
+        return;
+    }
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    match serde_json::to_string_pretty(queue) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(path, json) {
+                tracing::warn!(error = %e, "trakt: failed to save scrobble queue");
+            }
+        }
+        Err(e) => tracing::warn!(error = %e, "trakt: failed to serialize scrobble queue"),
+    }
+}
+
+fn enqueue(path: &Path, scrobble: QueuedScrobble) {
+    let _guard = QUEUE_LOCK.lock().unwrap();
+    let mut queue = load_queue(path);
+    queue.push(scrobble);
+    save_queue(path, &queue);
+}
+
+/// Take every queued scrobble, leaving the queue empty
+fn take_queue(path: &Path) -> Vec<QueuedScrobble> {
+    let _guard = QUEUE_LOCK.lock().unwrap();
+    let queue = load_queue(path);
+    save_queue(path, &[]);
+    queue
 }
 
 #[derive(Serialize)]
+struct RefreshRequest<'a> {
+    refresh_token: &'a str,
+    client_id: &'a str,
+    client_secret: &'a str,
+    redirect_uri: &'a str,
+    grant_type: &'a str,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ScrobbleRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     movie: Option<ScrobbleMovie>,
@@ -29,7 +163,7 @@ struct ScrobbleRequest {
     progress: f64,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ScrobbleMovie {
     title: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -37,7 +171,7 @@ struct ScrobbleMovie {
     ids: ScrobbleIds,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ScrobbleShow {
     title: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -45,26 +179,41 @@ struct ScrobbleShow {
     ids: ScrobbleIds,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ScrobbleEpisode {
     season: u32,
     number: u32,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ScrobbleIds {
     #[serde(skip_serializing_if = "Option::is_none")]
     tmdb: Option<u64>,
 }
 
 impl TraktExtension {
-    pub fn new(client_id: Option<String>, access_token: Option<String>) -> Self {
+    pub fn new(config: &TraktConfig) -> Self {
         Self {
             enabled: Arc::new(AtomicBool::new(false)),
-            client: Client::new(),
-            client_id,
-            access_token,
-            scrobble_threshold: 80.0,
+            client: crate::http::client(),
+            client_id: config.client_id.clone(),
+            client_secret: config.client_secret.clone(),
+            tokens: Arc::new(Mutex::new(TraktTokens::from_config(config))),
+            scrobble_threshold: config.scrobble_threshold,
+            errors: None,
+        }
+    }
+
+    /// Endpoint and progress for a stopped playback. Stopping early pauses so
+    /// trakt keeps the progress without marking it watched. Trakt only counts
+    /// a stop above 80% as watched, so with a lower threshold it's sent as done.
+    fn stop_scrobble(&self, watched_percent: f64) -> (&'static str, f64) {
+        if watched_percent <= self.scrobble_threshold {
+            ("pause", watched_percent)
+        } else if watched_percent <= TRAKT_WATCHED_PERCENT {
+            ("stop", 100.0)
+        } else {
+            ("stop", watched_percent)
         }
     }
 
@@ -74,10 +223,10 @@ impl TraktExtension {
         let is_tv = media
             .media_type
             .as_ref()
-            .is_some_and(|t| t == "tv" || t == "show");
+            .is_some_and(|t| t == "tv" || t == "show" || t == "anime");
 
         if is_tv {
-            // Build episode info if we have season/episode from filename
+            // Trakt needs the episode to scrobble a show, from the filename
             let episode_info = match (media.season, media.episode) {
                 (Some(s), Some(e)) => {
                     tracing::debug!(
@@ -86,10 +235,10 @@ impl TraktExtension {
                         episode = e,
                         "trakt: parsed episode info from filename"
                     );
-                    Some(ScrobbleEpisode {
+                    ScrobbleEpisode {
                         season: s,
                         number: e,
-                    })
+                    }
                 }
                 _ => {
                     tracing::debug!(
@@ -97,7 +246,7 @@ impl TraktExtension {
                         filename = %media.file_name,
                         "trakt: no episode info found in filename"
                     );
-                    None
+                    return None;
                 }
             };
 
@@ -110,7 +259,7 @@ impl TraktExtension {
                         tmdb: Some(tmdb_id),
                     },
                 }),
-                episode: episode_info,
+                episode: Some(episode_info),
                 progress,
             })
         } else {
@@ -129,56 +278,214 @@ impl TraktExtension {
         }
     }
 
-    fn scrobble(&self, endpoint: &str, media: &MediaInfo, progress: f64) {
+    fn scrobble(&self, endpoint: &'static str, media: &MediaInfo, progress: f64) {
         let Some(request) = self.build_request(media, progress) else {
-            tracing::debug!(title = %media.title, "trakt: no TMDB ID, skipping scrobble");
+            tracing::debug!(title = %media.title, "trakt: no TMDB ID or episode, skipping scrobble");
+            return;
+        };
+        let Some(sender) = self.sender() else {
             return;
         };
+        let scrobble = QueuedScrobble {
+            endpoint: endpoint.to_string(),
+            title: media.title.clone(),
+            request,
+        };
+        let errors = self.errors.clone();
+
+        // Spawn async task for the HTTP request
+        tokio::spawn(async move {
+            match sender.send(&scrobble).await {
+                ScrobbleOutcome::Done | ScrobbleOutcome::Conflict => {}
+                // A missed start only loses the "watching now" status, but a
+                // missed pause or stop loses the progress
+                ScrobbleOutcome::Retry(e) if scrobble.endpoint != "start" => {
+                    if let Some(errors) = errors {
+                        errors.report(format!("Trakt scrobble failed ({}), will retry", e));
+                    }
+                    if let Some(path) = queue_path() {
+                        enqueue(&path, scrobble);
+                    }
+                }
+                ScrobbleOutcome::Retry(e) | ScrobbleOutcome::Failed(e) => {
+                    if let Some(errors) = errors {
+                        errors.report(format!("Trakt scrobble failed ({})", e));
+                    }
+                }
+            }
+        });
+    }
+
+    fn sender(&self) -> Option<ScrobbleSender> {
+        Some(ScrobbleSender {
+            client: self.client.clone(),
+            client_id: self.client_id.clone()?,
+            client_secret: self.client_secret.clone(),
+            tokens: self.tokens.clone(),
+        })
+    }
 
-        let Some(client_id) = &self.client_id else {
+    /// Send the scrobbles queued by earlier sessions, keeping the ones that fail again
+    fn retry_queued(&self) {
+        let Some(path) = queue_path() else {
             return;
         };
-        let Some(access_token) = &self.access_token else {
+        let queue = take_queue(&path);
+        if queue.is_empty() {
+            return;
+        }
+        let Some(sender) = self.sender() else {
+            save_queue(&path, &queue);
             return;
         };
+        tracing::info!(count = queue.len(), "trakt: retrying queued scrobbles");
 
-        let url = format!("{}/scrobble/{}", TRAKT_API_URL, endpoint);
-        let client = self.client.clone();
-        let client_id = client_id.clone();
-        let access_token = access_token.clone();
-        let title = media.title.clone();
-        let endpoint = endpoint.to_string();
-
-        // Spawn async task for the HTTP request
         tokio::spawn(async move {
-            let result = client
+            for scrobble in queue {
+                match sender.send(&scrobble).await {
+                    ScrobbleOutcome::Retry(_) => enqueue(&path, scrobble),
+                    ScrobbleOutcome::Done
+                    | ScrobbleOutcome::Conflict
+                    | ScrobbleOutcome::Failed(_) => {}
+                }
+            }
+        });
+    }
+}
+
+/// What came of sending a scrobble
+#[derive(Debug, PartialEq)]
+enum ScrobbleOutcome {
+    Done,
+    /// Trakt already has it, e.g. a check-in made elsewhere or the same
+    /// scrobble sent twice
+    Conflict,
+    /// Worth sending again later: no connection, rate limited or trakt is down
+    Retry(String),
+    /// Sending it again won't help
+    Failed(String),
+}
+
+impl ScrobbleOutcome {
+    fn from_status(status: StatusCode) -> Self {
+        if status.is_success() {
+            Self::Done
+        } else if status == StatusCode::CONFLICT {
+            Self::Conflict
+        } else if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            Self::Retry(status.to_string())
+        } else {
+            Self::Failed(status.to_string())
+        }
+    }
+}
+
+/// Everything a background task needs to send scrobbles
+struct ScrobbleSender {
+    client: Client,
+    client_id: String,
+    client_secret: Option<String>,
+    tokens: Arc<Mutex<TraktTokens>>,
+}
+
+impl ScrobbleSender {
+    async fn send(&self, scrobble: &QueuedScrobble) -> ScrobbleOutcome {
+        let url = format!("{}/scrobble/{}", TRAKT_API_URL, scrobble.endpoint);
+        let title = &scrobble.title;
+        let send = |access_token: Option<String>| {
+            self.client
                 .post(&url)
                 .header("Content-Type", "application/json")
                 .header("trakt-api-version", "2")
-                .header("trakt-api-key", &client_id)
-                .header("Authorization", format!("Bearer {}", access_token))
-                .json(&request)
+                .header("trakt-api-key", &self.client_id)
+                .header(
+                    "Authorization",
+                    format!("Bearer {}", access_token.unwrap_or_default()),
+                )
+                .json(&scrobble.request)
                 .send()
-                .await;
-
-            match result {
-                Ok(resp) => {
-                    if resp.status().is_success() {
-                        tracing::info!(title = %title, endpoint = %endpoint, "trakt: scrobble successful");
-                    } else {
-                        tracing::warn!(
-                            title = %title,
-                            status = %resp.status(),
-                            "trakt: scrobble failed"
-                        );
-                    }
-                }
+        };
+
+        let access_token = self.tokens.lock().unwrap().access_token.clone();
+        let mut result = send(access_token).await;
+
+        // Expired access token: refresh once and retry
+        if let Ok(resp) = &result
+            && resp.status() == StatusCode::UNAUTHORIZED
+        {
+            tracing::warn!(title = %title, "trakt: access token rejected (401)");
+            match refresh_tokens(
+                &self.client,
+                &self.client_id,
+                self.client_secret.as_deref(),
+                &self.tokens,
+            )
+            .await
+            {
+                Ok(access_token) => result = send(Some(access_token)).await,
                 Err(e) => {
-                    tracing::warn!(title = %title, error = %e, "trakt: request failed");
+                    tracing::warn!(error = %e, "trakt: token refresh failed");
+                    // Sent once the login is fixed
+                    return ScrobbleOutcome::Retry(format!("login expired: {}", e));
                 }
             }
-        });
+        }
+
+        let outcome = match result {
+            Ok(resp) => ScrobbleOutcome::from_status(resp.status()),
+            Err(e) => ScrobbleOutcome::Retry(e.to_string()),
+        };
+        match &outcome {
+            ScrobbleOutcome::Done => {
+                tracing::info!(title = %title, endpoint = %scrobble.endpoint, "trakt: scrobble successful")
+            }
+            ScrobbleOutcome::Conflict => {
+                tracing::info!(title = %title, endpoint = %scrobble.endpoint, "trakt: already scrobbled (409)")
+            }
+            ScrobbleOutcome::Retry(e) | ScrobbleOutcome::Failed(e) => {
+                tracing::warn!(title = %title, endpoint = %scrobble.endpoint, error = %e, "trakt: scrobble failed")
+            }
+        }
+        outcome
+    }
+}
+
+/// Exchange the refresh token for new tokens, returns the new access token
+async fn refresh_tokens(
+    client: &Client,
+    client_id: &str,
+    client_secret: Option<&str>,
+    tokens: &Mutex<TraktTokens>,
+) -> Result<String, String> {
+    let refresh_token = tokens.lock().unwrap().refresh_token.clone();
+    let (Some(refresh_token), Some(client_secret)) = (refresh_token, client_secret) else {
+        return Err("set client_secret and refresh_token to renew it".to_string());
+    };
+
+    let resp = client
+        .post(format!("{}/oauth/token", TRAKT_API_URL))
+        .json(&RefreshRequest {
+            refresh_token: &refresh_token,
+            client_id,
+            client_secret,
+            redirect_uri: OOB_REDIRECT_URI,
+            grant_type: "refresh_token",
+        })
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
     }
+    let new_tokens: TokenResponse = resp.json().await.map_err(|e| e.to_string())?;
+
+    let mut tokens = tokens.lock().unwrap();
+    tokens.access_token = Some(new_tokens.access_token.clone());
+    tokens.refresh_token = Some(new_tokens.refresh_token);
+    tokens.save();
+    tracing::info!("trakt: access token refreshed");
+
+    Ok(new_tokens.access_token)
 }
 
 impl Extension for TraktExtension {
@@ -186,13 +493,18 @@ impl Extension for TraktExtension {
         "trakt"
     }
 
+    fn set_error_reporter(&mut self, reporter: ErrorReporter) {
+        self.errors = Some(reporter);
+    }
+
     fn on_init(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if self.client_id.is_none() || self.access_token.is_none() {
+        if self.client_id.is_none() || self.tokens.lock().unwrap().access_token.is_none() {
             return Err("trakt extension requires client_id and access_token in config".into());
         }
 
         tracing::info!("trakt: extension initialized");
         self.enabled.store(true, Ordering::SeqCst);
+        self.retry_queued();
         Ok(())
     }
 
@@ -209,6 +521,20 @@ impl Extension for TraktExtension {
             PlaybackEvent::Progress { .. } => {
                 // Don't send progress updates - too noisy
             }
+            PlaybackEvent::Paused {
+                media,
+                position_percent,
+            } => {
+                tracing::debug!(title = %media.title, "trakt: paused");
+                self.scrobble("pause", media, *position_percent);
+            }
+            PlaybackEvent::Resumed {
+                media,
+                position_percent,
+            } => {
+                tracing::debug!(title = %media.title, "trakt: resumed");
+                self.scrobble("start", media, *position_percent);
+            }
             PlaybackEvent::Stopped {
                 media,
                 watched_percent,
@@ -220,8 +546,8 @@ impl Extension for TraktExtension {
                     "trakt: stopped watching"
                 );
 
-                // Trakt auto-scrobbles if progress > 80%, but we send the accurate progress
-                self.scrobble("stop", media, *watched_percent);
+                let (endpoint, progress) = self.stop_scrobble(*watched_percent);
+                self.scrobble(endpoint, media, progress);
             }
         }
     }
@@ -230,3 +556,117 @@ impl Extension for TraktExtension {
         tracing::debug!("trakt: extension shutdown");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media(media_type: &str, season: Option<u32>, episode: Option<u32>) -> MediaInfo {
+        MediaInfo {
+            title: "Severance".to_string(),
+            file_name: "Severance.S02E03.1080p.mkv".to_string(),
+            total_bytes: 0,
+            tmdb_id: Some(95396),
+            year: Some(2022),
+            media_type: Some(media_type.to_string()),
+            poster_url: None,
+            season,
+            episode,
+        }
+    }
+
+    #[test]
+    fn test_stop_scrobble() {
+        let trakt = TraktExtension::new(&TraktConfig::default());
+        assert_eq!(trakt.stop_scrobble(95.0), ("stop", 95.0));
+        assert_eq!(trakt.stop_scrobble(80.0), ("pause", 80.0));
+        assert_eq!(trakt.stop_scrobble(10.0), ("pause", 10.0));
+
+        // Below trakt's own cutoff it has to be told it's done
+        let trakt = TraktExtension::new(&TraktConfig {
+            scrobble_threshold: 60.0,
+            ..TraktConfig::default()
+        });
+        assert_eq!(trakt.stop_scrobble(70.0), ("stop", 100.0));
+        assert_eq!(trakt.stop_scrobble(90.0), ("stop", 90.0));
+        assert_eq!(trakt.stop_scrobble(50.0), ("pause", 50.0));
+    }
+
+    #[test]
+    fn test_outcome_from_status() {
+        assert_eq!(
+            ScrobbleOutcome::from_status(StatusCode::CREATED),
+            ScrobbleOutcome::Done
+        );
+        assert_eq!(
+            ScrobbleOutcome::from_status(StatusCode::CONFLICT),
+            ScrobbleOutcome::Conflict
+        );
+        assert!(matches!(
+            ScrobbleOutcome::from_status(StatusCode::BAD_GATEWAY),
+            ScrobbleOutcome::Retry(_)
+        ));
+        assert!(matches!(
+            ScrobbleOutcome::from_status(StatusCode::TOO_MANY_REQUESTS),
+            ScrobbleOutcome::Retry(_)
+        ));
+        assert!(matches!(
+            ScrobbleOutcome::from_status(StatusCode::NOT_FOUND),
+            ScrobbleOutcome::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn test_queue_round_trip() {
+        let dir =
+            std::env::temp_dir().join(format!("ferristream-trakt-queue-{}", std::process::id()));
+        let path = dir.join("trakt_queue.json");
+        let trakt = TraktExtension::new(&TraktConfig::default());
+
+        for endpoint in ["pause", "stop"] {
+            enqueue(
+                &path,
+                QueuedScrobble {
+                    endpoint: endpoint.to_string(),
+                    title: "Severance".to_string(),
+                    request: trakt
+                        .build_request(&media("tv", Some(2), Some(3)), 92.0)
+                        .unwrap(),
+                },
+            );
+        }
+
+        let queue = take_queue(&path);
+        let endpoints: Vec<&str> = queue.iter().map(|q| q.endpoint.as_str()).collect();
+        assert_eq!(endpoints, ["pause", "stop"]);
+        let episode = queue[1].request.episode.as_ref().unwrap();
+        assert_eq!((episode.season, episode.number), (2, 3));
+        assert_eq!(queue[1].request.progress, 92.0);
+
+        // Taking empties it
+        assert!(!path.exists());
+        assert!(take_queue(&path).is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_build_request() {
+        let trakt = TraktExtension::new(&TraktConfig::default());
+
+        let request = trakt.build_request(&media("tv", Some(2), Some(3)), 50.0);
+        let episode = request.and_then(|r| r.episode).unwrap();
+        assert_eq!((episode.season, episode.number), (2, 3));
+
+        // Shows can't be scrobbled without an episode
+        assert!(
+            trakt
+                .build_request(&media("tv", None, None), 50.0)
+                .is_none()
+        );
+
+        let request = trakt
+            .build_request(&media("movie", None, None), 50.0)
+            .unwrap();
+        assert!(request.movie.is_some() && request.episode.is_none());
+    }
+}
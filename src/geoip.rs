@@ -0,0 +1,62 @@
+use std::net::IpAddr;
+use std::path::Path;
+
+use maxminddb::{Reader, geoip2};
+use tracing::{debug, info};
+
+/// Country lookups against a local GeoLite2 Country database
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    /// Load the database, `None` if it's missing or unreadable
+    pub fn open(path: &Path) -> Option<Self> {
+        match Reader::open_readfile(path) {
+            Ok(reader) => {
+                info!(path = %path.display(), "loaded GeoIP database");
+                Some(Self { reader })
+            }
+            Err(e) => {
+                debug!(path = %path.display(), error = %e, "no GeoIP database");
+                None
+            }
+        }
+    }
+
+    /// ISO 3166-1 alpha-2 code of the country an IP is in
+    pub fn country_code(&self, ip: IpAddr) -> Option<String> {
+        let country: geoip2::Country = self.reader.lookup(ip).ok()?.decode().ok()??;
+        country.country.iso_code.map(str::to_string)
+    }
+}
+
+/// Flag emoji for a two-letter country code, built from regional indicator symbols
+pub fn flag_emoji(country_code: &str) -> Option<String> {
+    if country_code.len() != 2 || !country_code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    country_code
+        .to_ascii_uppercase()
+        .chars()
+        .map(|c| char::from_u32(0x1F1E6 + (c as u32 - 'A' as u32)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_emoji() {
+        assert_eq!(flag_emoji("NL").as_deref(), Some("🇳🇱"));
+        assert_eq!(flag_emoji("us").as_deref(), Some("🇺🇸"));
+        assert_eq!(flag_emoji("USA"), None);
+        assert_eq!(flag_emoji("1A"), None);
+    }
+
+    #[test]
+    fn test_open_missing_database() {
+        assert!(GeoIp::open(Path::new("/nonexistent/GeoLite2-Country.mmdb")).is_none());
+    }
+}
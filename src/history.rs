@@ -1,9 +1,82 @@
-use directories::ProjectDirs;
+use directories::{BaseDirs, ProjectDirs};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 use tracing::{debug, error};
 
+/// Progress from which content counts as watched
+pub const FINISHED_PERCENT: f64 = 90.0;
+
+/// What a history entry is about, stored as a string key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HistoryKey {
+    /// A movie, or a show streamed without episode info ("tmdb:603")
+    Title(u64),
+    /// One episode of a show ("tmdb:1396:s1e2")
+    Episode {
+        tmdb_id: u64,
+        season: u32,
+        episode: u32,
+    },
+    /// Content without a TMDB match ("file:Some.Show.S01E01.mkv")
+    File(String),
+}
+
+impl HistoryKey {
+    /// Key for a streamed file. `episode` is (season, episode) when known.
+    pub fn new(tmdb_id: Option<u64>, file_name: &str, episode: Option<(u32, u32)>) -> Self {
+        match (tmdb_id, episode) {
+            (Some(tmdb_id), Some((season, episode))) => HistoryKey::Episode {
+                tmdb_id,
+                season,
+                episode,
+            },
+            (Some(tmdb_id), None) => HistoryKey::Title(tmdb_id),
+            (None, _) => HistoryKey::File(file_name.replace(['/', '\\', ':'], "_")),
+        }
+    }
+
+    /// Parse a stored key, None for keys in an unknown format
+    pub fn parse(key: &str) -> Option<Self> {
+        if let Some(name) = key.strip_prefix("file:") {
+            return Some(HistoryKey::File(name.to_string()));
+        }
+        let rest = key.strip_prefix("tmdb:")?;
+        let Some((id, episode)) = rest.split_once(':') else {
+            return rest.parse().ok().map(HistoryKey::Title);
+        };
+        let (season, episode) = episode.strip_prefix('s')?.split_once('e')?;
+        Some(HistoryKey::Episode {
+            tmdb_id: id.parse().ok()?,
+            season: season.parse().ok()?,
+            episode: episode.parse().ok()?,
+        })
+    }
+
+    pub fn tmdb_id(&self) -> Option<u64> {
+        match self {
+            HistoryKey::Title(tmdb_id) | HistoryKey::Episode { tmdb_id, .. } => Some(*tmdb_id),
+            HistoryKey::File(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for HistoryKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryKey::Title(tmdb_id) => write!(f, "tmdb:{}", tmdb_id),
+            HistoryKey::Episode {
+                tmdb_id,
+                season,
+                episode,
+            } => write!(f, "tmdb:{}:s{}e{}", tmdb_id, season, episode),
+            HistoryKey::File(name) => write!(f, "file:{}", name),
+        }
+    }
+}
+
 /// Watch history entry for a file
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WatchEntry {
@@ -18,7 +91,7 @@ pub struct WatchEntry {
 /// Watch history stored on disk
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WatchHistory {
-    /// Map from content key (e.g., "tmdb:12345" or "file:hash") to watch entry
+    /// Map from [`HistoryKey`] (e.g., "tmdb:12345" or "file:name") to watch entry
     entries: HashMap<String, WatchEntry>,
 }
 
@@ -54,29 +127,23 @@ impl WatchHistory {
 
     /// Save history to disk
     pub fn save(&self) {
-        let path = match Self::history_path() {
-            Ok(p) => p,
-            Err(_) => return,
-        };
+        if let Err(e) = self.try_save() {
+            error!("failed to save history: {}", e);
+        }
+    }
+
+    /// Save history to disk, returning any error to the caller
+    pub fn try_save(&self) -> Result<(), io::Error> {
+        let path = Self::history_path()
+            .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
 
         // Ensure parent directory exists
-        if let Some(parent) = path.parent()
-            && let Err(e) = std::fs::create_dir_all(parent)
-        {
-            error!("failed to create history directory: {}", e);
-            return;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
 
-        match serde_json::to_string_pretty(self) {
-            Ok(contents) => {
-                if let Err(e) = std::fs::write(&path, contents) {
-                    error!("failed to write history: {}", e);
-                }
-            }
-            Err(e) => {
-                error!("failed to serialize history: {}", e);
-            }
-        }
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, contents)
     }
 
     fn history_path() -> Result<PathBuf, ()> {
@@ -85,30 +152,20 @@ impl WatchHistory {
             .ok_or(())
     }
 
-    /// Generate a key for content
-    pub fn make_key(tmdb_id: Option<u64>, file_name: &str) -> String {
-        if let Some(id) = tmdb_id {
-            format!("tmdb:{}", id)
-        } else {
-            // Hash the filename for non-TMDB content
-            format!("file:{}", file_name.replace(['/', '\\', ':'], "_"))
-        }
-    }
-
     /// Get watch entry for a key
-    pub fn get(&self, key: &str) -> Option<&WatchEntry> {
-        self.entries.get(key)
+    pub fn get(&self, key: &HistoryKey) -> Option<&WatchEntry> {
+        self.entries.get(&key.to_string())
     }
 
     /// Update watch progress
-    pub fn update(&mut self, key: String, title: String, progress_percent: f64) {
+    pub fn update(&mut self, key: &HistoryKey, title: String, progress_percent: f64) {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .map(|d| d.as_secs())
             .unwrap_or(0);
 
         self.entries.insert(
-            key,
+            key.to_string(),
             WatchEntry {
                 progress_percent,
                 last_watched: now,
@@ -118,17 +175,16 @@ impl WatchHistory {
     }
 
     /// Check if content was watched past a threshold (e.g., 90% = finished)
-    pub fn is_finished(&self, key: &str, threshold: f64) -> bool {
-        self.entries
-            .get(key)
+    pub fn is_finished(&self, key: &HistoryKey, threshold: f64) -> bool {
+        self.get(key)
             .map(|e| e.progress_percent >= threshold)
             .unwrap_or(false)
     }
 
     /// Check if content has resumable progress (between 5% and 90%)
-    pub fn has_resume_point(&self, key: &str) -> Option<f64> {
-        self.entries.get(key).and_then(|e| {
-            if e.progress_percent >= 5.0 && e.progress_percent < 90.0 {
+    pub fn has_resume_point(&self, key: &HistoryKey) -> Option<f64> {
+        self.get(key).and_then(|e| {
+            if e.progress_percent >= 5.0 && e.progress_percent < FINISHED_PERCENT {
                 Some(e.progress_percent)
             } else {
                 None
@@ -137,8 +193,64 @@ impl WatchHistory {
     }
 
     /// Clear entry for a key
-    pub fn clear(&mut self, key: &str) {
-        self.entries.remove(key);
+    pub fn clear(&mut self, key: &HistoryKey) {
+        self.entries.remove(&key.to_string());
+    }
+
+    /// Number of entries in the history
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Default CSV export location (~/ferristream_history.csv)
+    pub fn default_export_path() -> PathBuf {
+        BaseDirs::new()
+            .map(|dirs| dirs.home_dir().to_path_buf())
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ferristream_history.csv")
+    }
+
+    /// Export history as CSV (title, tmdb_id, watched_percent, last_watched), newest first
+    pub fn export_csv(&self, path: &Path) -> Result<(), io::Error> {
+        let mut entries: Vec<(&String, &WatchEntry)> = self.entries.iter().collect();
+        entries.sort_by(|a, b| b.1.last_watched.cmp(&a.1.last_watched).then(a.0.cmp(b.0)));
+
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(["title", "tmdb_id", "watched_percent", "last_watched"])?;
+        for (key, entry) in entries {
+            let tmdb_id = HistoryKey::parse(key)
+                .and_then(|key| key.tmdb_id())
+                .map(|id| id.to_string())
+                .unwrap_or_default();
+            writer.write_record([
+                entry.title.as_str(),
+                &tmdb_id,
+                &format!("{:.1}", entry.progress_percent),
+                &format_timestamp(entry.last_watched),
+            ])?;
+        }
+        writer.flush()
+    }
+
+    /// Merge entries from another machine, keeping the most recently watched
+    /// entry per key. Returns how many entries were added or updated.
+    pub fn merge(&mut self, other: WatchHistory) -> usize {
+        let mut changed = 0;
+        for (key, entry) in other.entries {
+            let newer = self
+                .entries
+                .get(&key)
+                .is_none_or(|local| entry.last_watched > local.last_watched);
+            if newer {
+                self.entries.insert(key, entry);
+                changed += 1;
+            }
+        }
+        changed
     }
 
     /// Clear entries older than given days
@@ -152,3 +264,170 @@ impl WatchHistory {
         self.entries.retain(|_, e| e.last_watched >= cutoff);
     }
 }
+
+/// Format a unix timestamp as an ISO 8601 UTC date-time ("2024-03-09T14:05:00Z")
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_710_000_000), "2024-03-09T16:00:00Z");
+    }
+
+    fn entry(title: &str, progress_percent: f64, last_watched: u64) -> WatchEntry {
+        WatchEntry {
+            progress_percent,
+            last_watched,
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_history_key() {
+        let episode = HistoryKey::new(Some(1396), "Show.S01E02.mkv", Some((1, 2)));
+        assert_eq!(episode.to_string(), "tmdb:1396:s1e2");
+        assert_eq!(HistoryKey::parse("tmdb:1396:s1e2"), Some(episode));
+
+        let movie = HistoryKey::new(Some(603), "The.Matrix.1999.mkv", None);
+        assert_eq!(movie.to_string(), "tmdb:603");
+        assert_eq!(HistoryKey::parse("tmdb:603"), Some(movie));
+
+        let file = HistoryKey::new(None, "dir/Some:File.mkv", Some((1, 1)));
+        assert_eq!(file.to_string(), "file:dir_Some_File.mkv");
+        assert_eq!(HistoryKey::parse("file:dir_Some_File.mkv"), Some(file));
+        assert_eq!(HistoryKey::parse("tmdb:1:x"), None);
+
+        // Episodes of one show are tracked separately
+        let mut history = WatchHistory::default();
+        history.update(
+            &HistoryKey::new(Some(1), "", Some((1, 1))),
+            "E1".into(),
+            95.0,
+        );
+        history.update(
+            &HistoryKey::new(Some(1), "", Some((1, 2))),
+            "E2".into(),
+            40.0,
+        );
+        assert!(history.is_finished(
+            &HistoryKey::new(Some(1), "", Some((1, 1))),
+            FINISHED_PERCENT
+        ));
+        assert_eq!(
+            history.has_resume_point(&HistoryKey::new(Some(1), "", Some((1, 2)))),
+            Some(40.0)
+        );
+    }
+
+    #[test]
+    fn test_merge_keeps_newest() {
+        let mut local = WatchHistory::default();
+        local
+            .entries
+            .insert("tmdb:1".to_string(), entry("Old Here", 40.0, 100));
+        local
+            .entries
+            .insert("tmdb:2".to_string(), entry("New Here", 80.0, 300));
+        local
+            .entries
+            .insert("tmdb:3".to_string(), entry("Tie", 10.0, 200));
+
+        let mut remote = WatchHistory::default();
+        remote
+            .entries
+            .insert("tmdb:1".to_string(), entry("Old Here", 70.0, 200));
+        remote
+            .entries
+            .insert("tmdb:2".to_string(), entry("New Here", 20.0, 100));
+        remote
+            .entries
+            .insert("tmdb:3".to_string(), entry("Tie", 90.0, 200));
+        remote
+            .entries
+            .insert("tmdb:4".to_string(), entry("Remote Only", 5.0, 50));
+
+        assert_eq!(local.merge(remote), 2);
+        assert_eq!(
+            local.get(&HistoryKey::Title(1)).unwrap().progress_percent,
+            70.0
+        );
+        assert_eq!(
+            local.get(&HistoryKey::Title(2)).unwrap().progress_percent,
+            80.0
+        );
+        // Equal timestamps keep the local entry
+        assert_eq!(
+            local.get(&HistoryKey::Title(3)).unwrap().progress_percent,
+            10.0
+        );
+        assert_eq!(
+            local.get(&HistoryKey::Title(4)).unwrap().title,
+            "Remote Only"
+        );
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let mut history = WatchHistory::default();
+        history.entries.insert(
+            "tmdb:335984".to_string(),
+            WatchEntry {
+                progress_percent: 95.4,
+                last_watched: 1_710_000_000,
+                title: "Blade Runner 2049".to_string(),
+            },
+        );
+        history.entries.insert(
+            "file:Some.Show.S01E01.mkv".to_string(),
+            WatchEntry {
+                progress_percent: 12.0,
+                last_watched: 1_700_000_000,
+                title: "Some Show, \"Pilot\"".to_string(),
+            },
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "ferristream_history_test_{}.csv",
+            std::process::id()
+        ));
+        history.export_csv(&path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            csv,
+            "title,tmdb_id,watched_percent,last_watched\n\
+             Blade Runner 2049,335984,95.4,2024-03-09T16:00:00Z\n\
+             \"Some Show, \"\"Pilot\"\"\",,12.0,2023-11-14T22:13:20Z\n"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// How long the post-play command may run before it's killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the post-play command is told about the file that finished, as
+/// `FERRISTREAM_*` environment variables
+#[derive(Debug, Clone)]
+pub struct PostPlay {
+    pub title: String,
+    pub file: String,
+    pub progress_percent: f64,
+    pub tmdb_id: Option<u64>,
+}
+
+impl PostPlay {
+    fn env(&self) -> [(&'static str, String); 4] {
+        [
+            ("FERRISTREAM_TITLE", self.title.clone()),
+            ("FERRISTREAM_FILE", self.file.clone()),
+            (
+                "FERRISTREAM_PROGRESS_PERCENT",
+                format!("{:.1}", self.progress_percent),
+            ),
+            (
+                "FERRISTREAM_TMDB_ID",
+                self.tmdb_id.map(|id| id.to_string()).unwrap_or_default(),
+            ),
+        ]
+    }
+}
+
+/// Run the user's post-play shell command in the background, logging its output
+pub fn spawn_post_play(command: String, played: PostPlay) {
+    tokio::spawn(async move {
+        match run_post_play(&command, &played).await {
+            Ok(output) => {
+                info!(command = %command, "post-play command ran");
+                for line in output.lines() {
+                    info!(command = %command, "post-play: {}", line);
+                }
+            }
+            Err(e) => warn!(command = %command, error = %e, "post-play command failed"),
+        }
+    });
+}
+
+/// Run the command through the shell, returns its stdout and stderr
+async fn run_post_play(command: &str, played: &PostPlay) -> Result<String, String> {
+    #[cfg(windows)]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+    #[cfg(not(windows))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    let child = shell
+        .arg(command)
+        .envs(played.env())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+
+    // Dropping the timed out future kills the process
+    let output = match tokio::time::timeout(HOOK_TIMEOUT, child.wait_with_output()).await {
+        Ok(output) => output.map_err(|e| e.to_string())?,
+        Err(_) => return Err(format!("killed after {}s", HOOK_TIMEOUT.as_secs())),
+    };
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    if output.status.success() {
+        Ok(text)
+    } else {
+        Err(format!("exited with {}: {}", output.status, text.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn played() -> PostPlay {
+        PostPlay {
+            title: "Severance".to_string(),
+            file: "Severance.S02E03.1080p.mkv".to_string(),
+            progress_percent: 92.46,
+            tmdb_id: Some(95396),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_post_play_gets_env() {
+        let output = run_post_play(
+            "echo \"$FERRISTREAM_TITLE|$FERRISTREAM_FILE|$FERRISTREAM_PROGRESS_PERCENT|$FERRISTREAM_TMDB_ID\"",
+            &played(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            output.trim(),
+            "Severance|Severance.S02E03.1080p.mkv|92.5|95396"
+        );
+
+        let error = run_post_play("echo nope >&2; exit 3", &played())
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("exited with"), "{}", error);
+        assert!(error.ends_with("nope"), "{}", error);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_post_play_times_out() {
+        let started = std::time::Instant::now();
+        let error = run_post_play("sleep 30", &played()).await.unwrap_err();
+        assert_eq!(error, "killed after 5s");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,149 @@
+use reqwest::{Client, ClientBuilder, RequestBuilder, Response};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+use crate::config::{ConfigError, NetworkConfig};
+
+/// Delay before the first retry, doubled for each one after
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// Network settings and the client built from them, shared by all API clients
+static SHARED: RwLock<Option<(NetworkConfig, Client)>> = RwLock::new(None);
+
+/// Apply network settings to every client created from now on
+pub fn configure(network: &NetworkConfig) -> Result<(), ConfigError> {
+    let client = network
+        .client_builder()?
+        .build()
+        .map_err(|e| ConfigError::ValidationError(format!("failed to build HTTP client: {}", e)))?;
+    *SHARED.write().unwrap() = Some((network.clone(), client));
+    Ok(())
+}
+
+/// The shared HTTP client (a plain client until `configure` is called)
+pub fn client() -> Client {
+    match SHARED.read().unwrap().as_ref() {
+        Some((_, client)) => client.clone(),
+        None => Client::new(),
+    }
+}
+
+/// Builder with the network settings applied, for clients that need extra options
+pub fn builder() -> ClientBuilder {
+    SHARED
+        .read()
+        .unwrap()
+        .as_ref()
+        .and_then(|(network, _)| network.client_builder().ok())
+        .unwrap_or_else(Client::builder)
+}
+
+/// Send a request, retrying timeouts, connection errors and 5xx responses
+/// up to `network.max_attempts` times with jittered exponential backoff.
+/// Anything else, 4xx included, is returned as is. The last 5xx response is
+/// returned once attempts run out, for the caller's usual status handling.
+pub async fn send_with_retry(request: RequestBuilder) -> reqwest::Result<Response> {
+    let max_attempts = max_attempts();
+    let mut attempt = 1;
+    loop {
+        // Requests with a streamed body can't be sent again
+        let retry = request.try_clone().filter(|_| attempt < max_attempts);
+        let Some(next) = retry else {
+            return request.send().await;
+        };
+
+        let reason = match next.send().await {
+            Ok(response) if response.status().is_server_error() => {
+                format!("status {}", response.status())
+            }
+            Err(e) if e.is_timeout() || e.is_connect() => e.to_string(),
+            result => return result,
+        };
+        let delay = backoff(attempt);
+        debug!(
+            attempt,
+            max_attempts,
+            delay_ms = delay.as_millis() as u64,
+            reason,
+            "retrying request"
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+fn max_attempts() -> u32 {
+    SHARED
+        .read()
+        .unwrap()
+        .as_ref()
+        .map_or(3, |(network, _)| network.max_attempts)
+        .max(1)
+}
+
+/// Exponential backoff for a retry, with up to 50% jitter either way so
+/// parallel searches don't all retry at once
+fn backoff(attempt: u32) -> Duration {
+    let base = RETRY_BASE_DELAY * 2u32.saturating_pow(attempt - 1);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let factor = 0.5 + (nanos % 1000) as f64 / 1000.0;
+    base.mul_f64(factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_backoff_grows() {
+        for attempt in 1..4 {
+            let base = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+            let delay = backoff(attempt);
+            assert!(delay >= base / 2 && delay <= base * 3 / 2, "{:?}", delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_server_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/flaky"))
+            .respond_with(ResponseTemplate::new(502))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flaky"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let response = send_with_retry(client().get(format!("{}/flaky", server.uri())))
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let response = send_with_retry(client().get(format!("{}/missing", server.uri())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
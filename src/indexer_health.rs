@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, error};
+
+/// Consecutive failed searches before an indexer is skipped
+const DEMOTE_AFTER_FAILURES: u32 = 3;
+/// First demotion lasts this long, doubling each time it fails again afterwards
+const BASE_DEMOTION_SECS: u64 = 30 * 60;
+const MAX_DEMOTION_SECS: u64 = 8 * 60 * 60;
+/// Result counts kept per indexer for the history column
+const RESULT_HISTORY_LEN: usize = 10;
+
+/// Health stats shared between the UI and search tasks
+pub type SharedIndexerHealth = Arc<Mutex<IndexerHealth>>;
+
+/// Search stats of one indexer
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexerStats {
+    pub successes: u32,
+    pub failures: u32,
+    pub consecutive_failures: u32,
+    pub total_latency_ms: u64,
+    pub total_results: u64,
+    /// Unix timestamp until which the indexer is skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub demoted_until: Option<u64>,
+    /// Demotions since the last successful search, drives the backoff
+    #[serde(default)]
+    pub demotions: u32,
+    #[serde(default)]
+    pub last_latency_ms: u64,
+    /// Result counts of the latest successful searches, oldest first
+    #[serde(default)]
+    pub recent_results: VecDeque<u32>,
+}
+
+impl IndexerStats {
+    pub fn searches(&self) -> u32 {
+        self.successes + self.failures
+    }
+
+    pub fn average_latency(&self) -> Option<Duration> {
+        let searches = self.searches();
+        (searches > 0).then(|| Duration::from_millis(self.total_latency_ms / searches as u64))
+    }
+
+    /// Share of searches that didn't fail, 0.0 to 1.0
+    pub fn success_rate(&self) -> Option<f64> {
+        let searches = self.searches();
+        (searches > 0).then(|| self.successes as f64 / searches as f64)
+    }
+
+    /// Average result count of successful searches
+    pub fn average_results(&self) -> Option<f64> {
+        (self.successes > 0).then(|| self.total_results as f64 / self.successes as f64)
+    }
+
+    /// Time left on the demotion, None if the indexer is in use
+    pub fn demoted_for(&self, now: u64) -> Option<Duration> {
+        self.demoted_until
+            .filter(|until| *until > now)
+            .map(|until| Duration::from_secs(until - now))
+    }
+}
+
+/// How an indexer did in a search
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchOutcome {
+    Results(usize),
+    Failed(String),
+    /// Demoted, so not searched
+    Skipped,
+}
+
+/// Indexer-by-indexer outcome of the most recent search, for diagnostics
+#[derive(Debug, Clone, Default)]
+pub struct LastSearch {
+    pub query: String,
+    pub indexers: Vec<(String, SearchOutcome, Duration)>,
+}
+
+/// Per-indexer search stats, persisted next to the config
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IndexerHealth {
+    indexers: BTreeMap<String, IndexerStats>,
+    #[serde(skip)]
+    last_search: Option<LastSearch>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl IndexerHealth {
+    /// Load stats from disk, starting fresh if the file is missing or unreadable
+    pub fn load(path: PathBuf) -> Self {
+        let mut health = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                error!("failed to parse indexer stats: {}", e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        health.path = Some(path);
+        health
+    }
+
+    pub fn shared(self) -> SharedIndexerHealth {
+        Arc::new(Mutex::new(self))
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(self)
+            .map_err(std::io::Error::from)
+            .and_then(|contents| std::fs::write(path, contents));
+        if let Err(e) = result {
+            error!("failed to save indexer stats: {}", e);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&IndexerStats> {
+        self.indexers.get(name)
+    }
+
+    pub fn last_search(&self) -> Option<&LastSearch> {
+        self.last_search.as_ref()
+    }
+
+    pub fn set_last_search(&mut self, search: LastSearch) {
+        self.last_search = Some(search);
+    }
+
+    /// Indexers by name
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &IndexerStats)> {
+        self.indexers.iter()
+    }
+
+    pub fn record_success(&mut self, name: &str, latency: Duration, results: usize) {
+        let stats = self.indexers.entry(name.to_string()).or_default();
+        stats.successes += 1;
+        stats.consecutive_failures = 0;
+        stats.total_latency_ms += latency.as_millis() as u64;
+        stats.total_results += results as u64;
+        stats.demoted_until = None;
+        stats.demotions = 0;
+        stats.last_latency_ms = latency.as_millis() as u64;
+        if stats.recent_results.len() == RESULT_HISTORY_LEN {
+            stats.recent_results.pop_front();
+        }
+        stats.recent_results.push_back(results as u32);
+    }
+
+    /// Count a failed search, returns true if the indexer got demoted by it
+    pub fn record_failure(&mut self, name: &str, latency: Duration, now: u64) -> bool {
+        let stats = self.indexers.entry(name.to_string()).or_default();
+        stats.failures += 1;
+        stats.consecutive_failures += 1;
+        stats.total_latency_ms += latency.as_millis() as u64;
+        stats.last_latency_ms = latency.as_millis() as u64;
+
+        if stats.consecutive_failures < DEMOTE_AFTER_FAILURES {
+            return false;
+        }
+        let backoff = BASE_DEMOTION_SECS
+            .saturating_mul(1 << stats.demotions.min(16))
+            .min(MAX_DEMOTION_SECS);
+        stats.demoted_until = Some(now + backoff);
+        stats.demotions += 1;
+        stats.consecutive_failures = 0;
+        debug!(indexer = name, backoff, "demoted indexer");
+        true
+    }
+
+    /// Put a demoted indexer back into use, returns false if it wasn't demoted
+    pub fn reenable(&mut self, name: &str) -> bool {
+        match self.indexers.get_mut(name) {
+            Some(stats) if stats.demoted_until.is_some() => {
+                stats.demoted_until = None;
+                stats.demotions = 0;
+                stats.consecutive_failures = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Split indexers into the ones to search and the demoted ones to skip.
+    /// If every indexer is demoted they are all searched, so a search never
+    /// comes up empty-handed just because of demotions.
+    pub fn partition<T>(
+        &self,
+        indexers: Vec<T>,
+        name: impl Fn(&T) -> &str,
+        now: u64,
+    ) -> (Vec<T>, Vec<T>) {
+        let (usable, skipped): (Vec<T>, Vec<T>) = indexers.into_iter().partition(|indexer| {
+            self.get(name(indexer))
+                .and_then(|stats| stats.demoted_for(now))
+                .is_none()
+        });
+        if usable.is_empty() {
+            (skipped, Vec::new())
+        } else {
+            (usable, skipped)
+        }
+    }
+}
+
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn fail(health: &mut IndexerHealth, name: &str, times: u32) -> bool {
+        (0..times).fold(false, |_, _| {
+            health.record_failure(name, Duration::from_millis(100), NOW)
+        })
+    }
+
+    #[test]
+    fn test_demotion_backoff() {
+        let mut health = IndexerHealth::default();
+        assert!(!fail(&mut health, "slow", 2));
+        assert!(fail(&mut health, "slow", 1));
+        assert_eq!(
+            health.get("slow").unwrap().demoted_for(NOW),
+            Some(Duration::from_secs(30 * 60))
+        );
+
+        // Failing again after the demotion doubles it
+        assert!(fail(&mut health, "slow", 3));
+        assert_eq!(
+            health.get("slow").unwrap().demoted_for(NOW),
+            Some(Duration::from_secs(60 * 60))
+        );
+
+        // A success clears the backoff
+        health.record_success("slow", Duration::from_millis(300), 10);
+        let stats = health.get("slow").unwrap();
+        assert_eq!(stats.demoted_for(NOW), None);
+        assert_eq!(stats.demotions, 0);
+        assert_eq!(stats.average_latency(), Some(Duration::from_millis(128)));
+        assert_eq!(stats.average_results(), Some(10.0));
+        assert_eq!(stats.last_latency_ms, 300);
+    }
+
+    #[test]
+    fn test_success_rate_and_history() {
+        let mut health = IndexerHealth::default();
+        for results in 0..12 {
+            health.record_success("busy", Duration::from_millis(50), results);
+        }
+        fail(&mut health, "busy", 4);
+
+        let stats = health.get("busy").unwrap();
+        assert_eq!(stats.success_rate(), Some(0.75));
+        assert_eq!(stats.last_latency_ms, 100);
+        assert_eq!(stats.recent_results.len(), RESULT_HISTORY_LEN);
+        assert_eq!(stats.recent_results.front(), Some(&2));
+        assert_eq!(stats.recent_results.back(), Some(&11));
+        assert_eq!(IndexerStats::default().success_rate(), None);
+    }
+
+    #[test]
+    fn test_partition_keeps_last_indexer() {
+        let mut health = IndexerHealth::default();
+        fail(&mut health, "broken", 3);
+
+        let (usable, skipped) = health.partition(vec!["good", "broken"], |n| n, NOW);
+        assert_eq!(usable, vec!["good"]);
+        assert_eq!(skipped, vec!["broken"]);
+
+        // Never skip everything
+        let (usable, skipped) = health.partition(vec!["broken"], |n| n, NOW);
+        assert_eq!(usable, vec!["broken"]);
+        assert!(skipped.is_empty());
+
+        // Demotion expires
+        let later = NOW + 31 * 60;
+        let (usable, _) = health.partition(vec!["good", "broken"], |n| n, later);
+        assert_eq!(usable.len(), 2);
+
+        assert!(health.reenable("broken"));
+        assert!(!health.reenable("good"));
+        let (usable, _) = health.partition(vec!["good", "broken"], |n| n, NOW);
+        assert_eq!(usable.len(), 2);
+    }
+}
@@ -1,4 +1,12 @@
 // Library exports for integration tests
+pub mod anidb;
+pub mod config;
+pub mod http;
+pub mod mpv;
+pub mod redact;
+pub mod rss;
+pub mod secrets;
 pub mod streaming;
 pub mod tmdb;
 pub mod torznab;
+pub mod usenet;
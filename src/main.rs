@@ -1,25 +1,232 @@
 #![allow(unused)]
 
+mod anidb;
+mod audio_preferences;
+mod cast;
 mod config;
+mod diagnostics;
 mod doctor;
 mod extensions;
+mod geoip;
 mod history;
+mod hooks;
+mod http;
+mod indexer_health;
+mod mpv;
+mod notifications;
 mod opensubtitles;
 mod prowlarr;
+mod recovery;
+mod redact;
+mod rss;
+mod search_service;
+mod secrets;
+mod state;
 mod streaming;
 mod tmdb;
 mod torznab;
 mod tui;
+mod usenet;
 
-use config::Config;
-use extensions::{DiscordExtension, ExtensionManager, TraktExtension};
-use std::fs::File;
+use clap::{Parser, Subcommand};
+use config::{Config, ConfigLocation};
+use extensions::{
+    CommandExtension, DiscordExtension, ExtensionManager, SimklExtension, TraktExtension,
+};
+use history::WatchHistory;
+use state::{StateBundle, StateError};
+use std::path::{Path, PathBuf};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::EnvFilter;
 
+/// Days of rotated log files to keep
+const LOG_FILES_KEPT: usize = 7;
+
+/// Shown after the options in --help, with this platform's config paths
+fn after_help() -> String {
+    let Ok(location) = ConfigLocation::default_location() else {
+        return "--config and --profile pair well with shell aliases, one per setup".to_string();
+    };
+    let work = location.path.with_file_name("work.toml");
+    format!(
+        "The default config is {}.\n\
+         --config and --profile pair well with shell aliases, one per setup:\n\n  \
+         alias ferristream-work='ferristream --config {}'",
+        location.path.display(),
+        work.display()
+    )
+}
+
+/// Stream torrents from Prowlarr straight to your media player
+#[derive(Parser)]
+#[command(version, after_help = after_help())]
+struct Cli {
+    /// Config file to use instead of the default config.toml, saved back to the same file
+    #[arg(long, value_name = "PATH", conflicts_with = "profile")]
+    config: Option<PathBuf>,
+    /// Named profile, stored as ferristream/<NAME>.toml in the config dir
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+    /// Log file, rotated daily keeping 7 days [default: ferristream.log in the cache dir]
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+    /// Move the API keys in the config file to the OS keychain and exit
+    #[arg(long)]
+    migrate_to_keychain: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Export watch history to a file for another machine
+    Export {
+        #[arg(long, value_name = "PATH")]
+        out: PathBuf,
+    },
+    /// Merge an exported file into the local watch history, newest entries win
+    Import {
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+    },
+    /// Check the config file and report every problem, without starting the TUI
+    Validate,
+}
+
+impl Command {
+    fn run(&self, cli: &Cli) -> Result<String, String> {
+        match self {
+            Command::Export { out } => export_state(out).map_err(|e| e.to_string()),
+            Command::Import { path } => import_state(path).map_err(|e| e.to_string()),
+            Command::Validate => {
+                let location = cli.config_location().map_err(|e| e.to_string())?;
+                validate_config(&location)
+            }
+        }
+    }
+}
+
+fn export_state(out: &Path) -> Result<String, StateError> {
+    let history = WatchHistory::load();
+    let count = history.len();
+    StateBundle::new(history).write(out)?;
+    Ok(format!("Exported {} entries to {}", count, out.display()))
+}
+
+fn import_state(path: &Path) -> Result<String, StateError> {
+    let bundle = StateBundle::read(path)?;
+    let mut history = WatchHistory::load();
+    let summary = bundle.merge_into(&mut history);
+    history.try_save()?;
+    Ok(format!(
+        "Imported {} entries ({} total)",
+        summary.updated, summary.total
+    ))
+}
+
+fn migrate_to_keychain(location: &ConfigLocation) -> Result<String, String> {
+    let mut config = Config::parse_file(location).map_err(|e| e.to_string())?;
+    config.secrets.keychain = true;
+    let plain_text = config
+        .save_with(location, &secrets::OsKeychainBackend)
+        .map_err(|e| e.to_string())?;
+    if plain_text > 0 {
+        return Err(format!(
+            "the keychain didn't take {} API key(s), they're still in {}",
+            plain_text,
+            location.path.display()
+        ));
+    }
+    Ok(format!(
+        "API keys moved to the keychain, {} now says \"keychain\" for them",
+        location.path.display()
+    ))
+}
+
+fn validate_config(location: &ConfigLocation) -> Result<String, String> {
+    let path = &location.path;
+    let config = Config::parse_file(location).map_err(|e| e.to_string())?;
+    let errors = config.validate();
+    if errors.is_empty() {
+        return Ok(format!("{} is valid", path.display()));
+    }
+
+    let lines: Vec<String> = errors.iter().map(|e| format!("  {}", e)).collect();
+    Err(format!(
+        "{} has {} problem(s):\n{}",
+        path.display(),
+        errors.len(),
+        lines.join("\n")
+    ))
+}
+
+/// Daily rotated appender writing `<path>.YYYY-MM-DD` files next to `path`
+fn log_appender(path: &Path) -> Option<RollingFileAppender> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let dir = dir.unwrap_or(Path::new("."));
+    let name = path.file_name()?.to_string_lossy().into_owned();
+    std::fs::create_dir_all(dir).ok()?;
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(name)
+        .max_log_files(LOG_FILES_KEPT)
+        .build(dir)
+        .ok()
+}
+
+impl Cli {
+    fn config_location(&self) -> Result<ConfigLocation, config::ConfigError> {
+        match (&self.config, &self.profile) {
+            (Some(path), _) => Ok(ConfigLocation::file(path.clone())),
+            (None, Some(name)) => ConfigLocation::profile(name),
+            (None, None) => ConfigLocation::default_location(),
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
+    // Both rustls backends get compiled in (reqwest uses ring, the Chromecast
+    // client aws-lc-rs), so rustls can't pick one by itself
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let cli = Cli::parse();
+    if let Some(command) = &cli.command {
+        match command.run(&cli) {
+            Ok(message) => println!("{}", message),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if cli.migrate_to_keychain {
+        let result = cli
+            .config_location()
+            .map_err(|e| e.to_string())
+            .and_then(|location| migrate_to_keychain(&location));
+        match result {
+            Ok(message) => println!("{}", message),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let location = match cli.config_location() {
+        Ok(location) => location,
+        Err(e) => {
+            eprintln!("Failed to locate config: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     // Initialize tracing - log to file to not interfere with TUI
-    let log_file = File::create("/tmp/ferristream.log").ok();
+    let log_path = cli.log_file.clone().unwrap_or_else(|| location.log_path());
+    let log_file = log_appender(&log_path);
 
     if let Some(file) = log_file {
         tracing_subscriber::fmt()
@@ -43,11 +250,11 @@ async fn main() {
             .init();
     }
 
-    let (config, is_new) = match Config::load() {
+    let (config, is_new) = match Config::load(&location) {
         Ok(config) => (config, false),
         Err(config::ConfigError::NotFound(_)) => {
             // Config doesn't exist - create default and open settings
-            match Config::load_or_create() {
+            match Config::load_or_create(&location) {
                 Ok(config) => {
                     eprintln!("Created default config. Opening settings to configure...");
                     std::thread::sleep(std::time::Duration::from_secs(1));
@@ -65,6 +272,12 @@ async fn main() {
         }
     };
 
+    // Apply proxy/timeout settings before any API client is created
+    if let Err(e) = http::configure(&config.network) {
+        eprintln!("Invalid network settings: {}", e);
+        std::process::exit(1);
+    }
+
     // Initialize extensions
     let mut ext_manager = ExtensionManager::new();
 
@@ -75,13 +288,18 @@ async fn main() {
     }
 
     if config.extensions.trakt.enabled {
-        ext_manager.register(Box::new(TraktExtension::new(
-            config.extensions.trakt.client_id.clone(),
-            config.extensions.trakt.access_token.clone(),
-        )));
+        ext_manager.register(Box::new(TraktExtension::new(&config.extensions.trakt)));
+    }
+
+    if config.extensions.simkl.enabled {
+        ext_manager.register(Box::new(SimklExtension::new(&config.extensions.simkl)));
+    }
+
+    for command in &config.extensions.command {
+        ext_manager.register(Box::new(CommandExtension::new(command)));
     }
 
-    let result = tui::run(config, ext_manager, is_new).await;
+    let result = tui::run(config, location, log_path, ext_manager, is_new).await;
 
     if let Err(e) = result {
         eprintln!("Error: {}", e);
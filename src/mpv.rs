@@ -0,0 +1,399 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::streaming::IpcEndpoint;
+
+/// ISO 639-1 codes with their ISO 639-2 equivalents (bibliographic and terminology)
+const LANGUAGE_CODES: &[(&str, &[&str])] = &[
+    ("en", &["eng"]),
+    ("es", &["spa"]),
+    ("fr", &["fre", "fra"]),
+    ("de", &["ger", "deu"]),
+    ("it", &["ita"]),
+    ("pt", &["por"]),
+    ("ru", &["rus"]),
+    ("ja", &["jpn"]),
+    ("ko", &["kor"]),
+    ("zh", &["chi", "zho"]),
+    ("nl", &["dut", "nld"]),
+    ("sv", &["swe"]),
+    ("ar", &["ara"]),
+    ("pl", &["pol"]),
+    ("hi", &["hin"]),
+];
+
+/// An audio track from mpv's `track-list` property
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AudioTrack {
+    pub id: i64,
+    #[serde(default)]
+    pub lang: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub selected: bool,
+}
+
+impl AudioTrack {
+    /// Short description for toasts, e.g. "jpn (Japanese 5.1)"
+    pub fn label(&self) -> String {
+        match (&self.lang, &self.title) {
+            (Some(lang), Some(title)) => format!("{} ({})", lang, title),
+            (Some(lang), None) => lang.clone(),
+            (None, Some(title)) => title.clone(),
+            (None, None) => format!("track {}", self.id),
+        }
+    }
+}
+
+/// A chapter from mpv's `chapter-list` property
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Chapter {
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Start, in seconds
+    pub time: f64,
+}
+
+impl Chapter {
+    /// Title, or "Chapter N" for untitled ones (`index` counts from 0)
+    pub fn label(&self, index: usize) -> String {
+        match self.title.as_deref().filter(|t| !t.trim().is_empty()) {
+            Some(title) => title.to_string(),
+            None => format!("Chapter {}", index + 1),
+        }
+    }
+}
+
+/// One JSON IPC request line, e.g. `{"command":["get_property","time-pos"]}\n`
+fn command_line(command: &Value) -> String {
+    let mut line = json!({ "command": command }).to_string();
+    line.push('\n');
+    line
+}
+
+/// Pick the `data` out of a response line. Events mpv interleaves give
+/// Some(None) so the caller keeps reading.
+fn parse_response(line: &str) -> Option<Option<Value>> {
+    let value: Value = serde_json::from_str(line).ok()?;
+    if value.get("event").is_some() {
+        return Some(None);
+    }
+    if value.get("error").and_then(Value::as_str) != Some("success") {
+        return None;
+    }
+    Some(Some(value.get("data").cloned().unwrap_or(Value::Null)))
+}
+
+/// Send one command over a connected stream and wait for its response
+async fn exchange<S>(stream: S, command: Value) -> Option<Value>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite,
+{
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    writer
+        .write_all(command_line(&command).as_bytes())
+        .await
+        .ok()?;
+
+    let mut response = String::new();
+    loop {
+        response.clear();
+        if reader.read_line(&mut response).await.ok()? == 0 {
+            return None;
+        }
+        if let Some(data) = parse_response(&response)? {
+            return Some(data);
+        }
+    }
+}
+
+/// Send one command and return its `data`
+async fn request(endpoint: &IpcEndpoint, command: Value) -> Option<Value> {
+    match endpoint {
+        #[cfg(unix)]
+        IpcEndpoint::UnixSocket(path) => {
+            let stream = tokio::net::UnixStream::connect(path).await.ok()?;
+            exchange(stream, command).await
+        }
+        #[cfg(windows)]
+        IpcEndpoint::NamedPipe(name) => {
+            let pipe = open_pipe(name).await?;
+            exchange(pipe, command).await
+        }
+        _ => None,
+    }
+}
+
+/// Connect to mpv's named pipe. Every instance of it is busy while other
+/// requests (progress polling, audio checks) are connected, so wait a little
+/// for one to free up.
+#[cfg(windows)]
+async fn open_pipe(name: &str) -> Option<tokio::net::windows::named_pipe::NamedPipeClient> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    const ERROR_PIPE_BUSY: i32 = 231;
+    const ATTEMPTS: usize = 10;
+
+    for _ in 0..ATTEMPTS {
+        match ClientOptions::new().open(name) {
+            Ok(pipe) => return Some(pipe),
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// Read an mpv property
+pub async fn get_property(endpoint: &IpcEndpoint, name: &str) -> Option<Value> {
+    request(endpoint, json!(["get_property", name])).await
+}
+
+/// Set an mpv property, returns false if mpv rejected it or isn't reachable
+pub async fn set_property(endpoint: &IpcEndpoint, name: &str, value: Value) -> bool {
+    request(endpoint, json!(["set_property", name, value]))
+        .await
+        .is_some()
+}
+
+/// Get current playback position from mpv via IPC
+/// Returns (position_seconds, duration_seconds) if successful
+pub async fn get_mpv_position(endpoint: &IpcEndpoint) -> Option<(f64, f64)> {
+    let pos = get_property(endpoint, "time-pos").await?.as_f64()?;
+    let dur = get_property(endpoint, "duration").await?.as_f64()?;
+    Some((pos, dur))
+}
+
+/// Audio tracks of the loaded file, empty until mpv has opened it
+pub async fn get_audio_tracks(endpoint: &IpcEndpoint) -> Option<Vec<AudioTrack>> {
+    let tracks = get_property(endpoint, "track-list").await?;
+    Some(audio_tracks(&tracks))
+}
+
+/// Chapters of the loaded file, empty if it has none
+pub async fn get_mpv_chapters(endpoint: &IpcEndpoint) -> Option<Vec<Chapter>> {
+    let list = get_property(endpoint, "chapter-list").await?;
+    Some(chapters(&list))
+}
+
+fn chapters(chapter_list: &Value) -> Vec<Chapter> {
+    chapter_list
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|c| serde_json::from_value(c.clone()).ok())
+        .collect()
+}
+
+/// Index of the chapter playing at `position` seconds, None before the first
+pub fn chapter_at(chapters: &[Chapter], position: f64) -> Option<usize> {
+    chapters.iter().rposition(|c| c.time <= position)
+}
+
+fn audio_tracks(track_list: &Value) -> Vec<AudioTrack> {
+    track_list
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|t| t.get("type").and_then(Value::as_str) == Some("audio"))
+        .filter_map(|t| serde_json::from_value(t.clone()).ok())
+        .collect()
+}
+
+/// True if two language codes name the same language ("ja", "jpn", "JPN")
+fn same_language(a: &str, b: &str) -> bool {
+    let (a, b) = (a.to_lowercase(), b.to_lowercase());
+    if a == b {
+        return true;
+    }
+    LANGUAGE_CODES.iter().any(|(two, three)| {
+        let known = |code: &str| code == *two || three.contains(&code);
+        known(&a) && known(&b)
+    })
+}
+
+/// ISO 639-2/B code ("ger", not "deu") for an ISO 639-1 or 639-2 code,
+/// None for languages not in the table
+pub fn three_letter_code(code: &str) -> Option<&'static str> {
+    let code = code.to_lowercase();
+    LANGUAGE_CODES
+        .iter()
+        .find(|(two, three)| *two == code || three.contains(&code.as_str()))
+        .map(|(_, three)| three[0])
+}
+
+/// The track to switch to, or None if the selected track already has the
+/// most preferred language available (or no track matches any preference)
+pub fn preferred_audio_track<'a>(
+    tracks: &'a [AudioTrack],
+    languages: &[String],
+) -> Option<&'a AudioTrack> {
+    let best = languages.iter().find_map(|lang| {
+        tracks
+            .iter()
+            .find(|t| t.lang.as_deref().is_some_and(|l| same_language(l, lang)))
+    })?;
+    let selected = tracks.iter().find(|t| t.selected);
+    let already_preferred = selected.is_some_and(|s| {
+        s.id == best.id
+            || s.lang
+                .as_deref()
+                .zip(best.lang.as_deref())
+                .is_some_and(|(a, b)| same_language(a, b))
+    });
+    (!already_preferred).then_some(best)
+}
+
+/// The playing audio track when it's already in the subtitle's language,
+/// or in the most preferred subtitle language when the subtitle's own is
+/// unknown. Subtitles are only in the way then.
+pub fn audio_matching_subtitles<'a>(
+    tracks: &'a [AudioTrack],
+    subtitle_language: Option<&str>,
+    preferred: &[String],
+) -> Option<&'a AudioTrack> {
+    let language = subtitle_language.or(preferred.first().map(String::as_str))?;
+    tracks.iter().find(|t| t.selected).filter(|t| {
+        t.lang
+            .as_deref()
+            .is_some_and(|l| same_language(l, language))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_three_letter_code() {
+        assert_eq!(three_letter_code("en"), Some("eng"));
+        assert_eq!(three_letter_code("DE"), Some("ger"));
+        assert_eq!(three_letter_code("deu"), Some("ger"));
+        assert_eq!(three_letter_code("xx"), None);
+    }
+
+    fn track(id: i64, lang: &str, selected: bool) -> AudioTrack {
+        AudioTrack {
+            id,
+            lang: Some(lang.to_string()),
+            title: None,
+            selected,
+        }
+    }
+
+    #[test]
+    fn test_preferred_audio_track() {
+        let tracks = vec![track(1, "eng", true), track(2, "jpn", false)];
+        let prefs = |langs: &[&str]| langs.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            preferred_audio_track(&tracks, &prefs(&["ja", "en"])).map(|t| t.id),
+            Some(2)
+        );
+        // Selected track already has the first available preference
+        assert!(preferred_audio_track(&tracks, &prefs(&["en", "ja"])).is_none());
+        assert!(preferred_audio_track(&tracks, &prefs(&["de", "eng"])).is_none());
+        // Nothing matches, leave mpv's choice alone
+        assert!(preferred_audio_track(&tracks, &prefs(&["fr"])).is_none());
+        assert!(preferred_audio_track(&tracks, &[]).is_none());
+    }
+
+    #[test]
+    fn test_audio_matching_subtitles() {
+        let prefs = |langs: &[&str]| langs.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        let english = vec![track(1, "eng", true), track(2, "jpn", false)];
+        let japanese = vec![track(1, "eng", false), track(2, "jpn", true)];
+
+        assert_eq!(
+            audio_matching_subtitles(&english, Some("en"), &prefs(&["en"])).map(|t| t.id),
+            Some(1)
+        );
+        assert!(audio_matching_subtitles(&japanese, Some("en"), &prefs(&["en"])).is_none());
+        // French subtitles weren't found, the English ones match the audio
+        assert!(audio_matching_subtitles(&english, Some("en"), &prefs(&["fr", "en"])).is_some());
+        // A French subtitle is still wanted over English audio
+        assert!(audio_matching_subtitles(&english, Some("fr"), &prefs(&["fr", "en"])).is_none());
+        // Unknown subtitle language, the first preference decides
+        assert!(audio_matching_subtitles(&english, None, &prefs(&["en"])).is_some());
+        assert!(audio_matching_subtitles(&english, None, &[]).is_none());
+        // No language tag on the audio, keep the subtitle
+        let untagged = vec![AudioTrack {
+            id: 1,
+            lang: None,
+            title: None,
+            selected: true,
+        }];
+        assert!(audio_matching_subtitles(&untagged, Some("en"), &prefs(&["en"])).is_none());
+    }
+
+    #[test]
+    fn test_chapters() {
+        let list = json!([
+            {"title": "Opening", "time": 0.0},
+            {"time": 312.5},
+            {"title": "Encore", "time": 4980.0},
+            {"title": "broken"}
+        ]);
+        let chapters = chapters(&list);
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[0].label(0), "Opening");
+        assert_eq!(chapters[1].label(1), "Chapter 2");
+
+        assert_eq!(chapter_at(&chapters, 0.0), Some(0));
+        assert_eq!(chapter_at(&chapters, 400.0), Some(1));
+        assert_eq!(chapter_at(&chapters, 6000.0), Some(2));
+        assert_eq!(chapter_at(&chapters[1..], 10.0), None);
+        assert!(super::chapters(&Value::Null).is_empty());
+    }
+
+    #[test]
+    fn test_command_serialization() {
+        assert_eq!(
+            command_line(&json!(["get_property", "time-pos"])),
+            "{\"command\":[\"get_property\",\"time-pos\"]}\n"
+        );
+        assert_eq!(
+            command_line(&json!(["set_property", "aid", 2])),
+            "{\"command\":[\"set_property\",\"aid\",2]}\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(
+            parse_response(r#"{"data":42.5,"error":"success","request_id":0}"#),
+            Some(Some(json!(42.5)))
+        );
+        assert_eq!(
+            parse_response(r#"{"error":"success"}"#),
+            Some(Some(Value::Null))
+        );
+        assert_eq!(
+            parse_response(r#"{"event":"playback-restart"}"#),
+            Some(None)
+        );
+        assert_eq!(parse_response(r#"{"error":"property unavailable"}"#), None);
+        assert_eq!(parse_response("not json"), None);
+    }
+
+    #[test]
+    fn test_audio_tracks_from_track_list() {
+        let list = json!([
+            {"id": 1, "type": "video", "selected": true},
+            {"id": 1, "type": "audio", "lang": "jpn", "title": "Japanese 5.1", "selected": true},
+            {"id": 2, "type": "audio", "lang": "eng"},
+            {"id": 1, "type": "sub", "lang": "eng"}
+        ]);
+        let tracks = audio_tracks(&list);
+
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].label(), "jpn (Japanese 5.1)");
+        assert!(!tracks[1].selected);
+    }
+}
@@ -0,0 +1,28 @@
+use notify_rust::{Notification, Urgency};
+use tracing::debug;
+
+/// Show an OS desktop notification.
+///
+/// Sending happens on a blocking thread so a slow or missing notification
+/// daemon never stalls the UI. Failures are logged and otherwise ignored.
+pub fn notify(title: &str, body: &str, urgency: Urgency) {
+    let title = title.to_string();
+    let body = body.to_string();
+
+    std::thread::spawn(move || {
+        let mut notification = Notification::new();
+        notification
+            .appname("ferristream")
+            .summary(&title)
+            .body(&body);
+
+        #[cfg(not(target_os = "macos"))]
+        notification.urgency(urgency);
+        #[cfg(target_os = "macos")]
+        let _ = urgency;
+
+        if let Err(e) = notification.show() {
+            debug!(error = %e, "failed to show desktop notification");
+        }
+    });
+}
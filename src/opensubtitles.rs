@@ -1,7 +1,43 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use flate2::read::GzDecoder;
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesRef, Event};
 use reqwest::Client;
+use reqwest::StatusCode;
+use reqwest::header::RANGE;
 use serde::Deserialize;
 use thiserror::Error;
-use tracing::{debug, info};
+use tokio::time::timeout;
+use tracing::{debug, info, warn};
+
+/// Bytes hashed from each end of a file for its moviehash
+const HASH_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// How long to wait for both ends of a streamed file before giving up on hashing
+const HASH_FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Searches allowed per second, the REST API's documented limit
+const SEARCH_RATE: (usize, Duration) = (5, Duration::from_secs(1));
+
+/// Download links requested per minute, well under what the quota allows
+const DOWNLOAD_RATE: (usize, Duration) = (10, Duration::from_secs(60));
+
+/// Shared by all clients, since each subtitle lookup makes its own
+static SEARCH_LIMITER: LazyLock<Arc<Mutex<RateLimiter>>> =
+    LazyLock::new(|| Arc::new(Mutex::new(RateLimiter::new(SEARCH_RATE))));
+static DOWNLOAD_LIMITER: LazyLock<Arc<Mutex<RateLimiter>>> =
+    LazyLock::new(|| Arc::new(Mutex::new(RateLimiter::new(DOWNLOAD_RATE))));
+
+const XMLRPC_URL: &str = "https://api.opensubtitles.org/xml-rpc";
+
+/// User agent the XML-RPC API knows this client by
+const XMLRPC_USER_AGENT: &str = concat!("ferristream v", env!("CARGO_PKG_VERSION"));
 
 #[derive(Error, Debug)]
 pub enum OpenSubtitlesError {
@@ -11,6 +47,16 @@ pub enum OpenSubtitlesError {
     NotFound,
     #[error("API error: {0}")]
     ApiError(String),
+    #[error("API key rejected")]
+    Unauthorized,
+    #[error("daily quota used up")]
+    QuotaExceeded,
+    #[error("rate limited, try again shortly")]
+    RateLimited,
+    #[error("invalid XML-RPC response: {0}")]
+    XmlError(String),
+    #[error("failed to save subtitle: {0}")]
+    IoError(#[from] std::io::Error),
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,9 +86,76 @@ struct DownloadResponse {
     link: String,
 }
 
+/// Sliding window limit of `max_requests` per `window`
+#[derive(Debug)]
+pub struct RateLimiter {
+    max_requests: usize,
+    window: Duration,
+    /// When the requests still in the window were made, oldest first
+    recent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new((max_requests, window): (usize, Duration)) -> Self {
+        Self {
+            max_requests,
+            window,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Take a slot for a request at `now`, or say how long until one frees up
+    fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        while self
+            .recent
+            .front()
+            .is_some_and(|&at| now.duration_since(at) >= self.window)
+        {
+            self.recent.pop_front();
+        }
+        match self.recent.front() {
+            Some(&oldest) if self.recent.len() >= self.max_requests => {
+                Err(self.window - now.duration_since(oldest))
+            }
+            _ => {
+                self.recent.push_back(now);
+                Ok(())
+            }
+        }
+    }
+
+    /// Wait for a slot, up to `max_wait`
+    pub async fn acquire(
+        limiter: &Mutex<RateLimiter>,
+        max_wait: Duration,
+    ) -> Result<(), OpenSubtitlesError> {
+        let start = Instant::now();
+        loop {
+            let now = Instant::now();
+            let wait = match limiter.lock().unwrap().try_acquire(now) {
+                Ok(()) => return Ok(()),
+                Err(wait) => wait,
+            };
+            if now.duration_since(start) + wait > max_wait {
+                warn!(
+                    wait_ms = wait.as_millis() as u64,
+                    "OpenSubtitles rate limit hit"
+                );
+                return Err(OpenSubtitlesError::RateLimited);
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct OpenSubtitlesClient {
     client: Client,
     api_key: String,
+    search_limiter: Arc<Mutex<RateLimiter>>,
+    download_limiter: Arc<Mutex<RateLimiter>>,
+    /// Longest a request waits for the rate limit before giving up
+    max_wait: Duration,
 }
 
 #[derive(Debug, Clone)]
@@ -53,10 +166,13 @@ pub struct SubtitleDownload {
 }
 
 impl OpenSubtitlesClient {
-    pub fn new(api_key: &str) -> Self {
+    pub fn new(api_key: &str, max_wait: Duration) -> Self {
         Self {
-            client: Client::new(),
+            client: crate::http::client(),
             api_key: api_key.to_string(),
+            search_limiter: SEARCH_LIMITER.clone(),
+            download_limiter: DOWNLOAD_LIMITER.clone(),
+            max_wait,
         }
     }
 
@@ -69,12 +185,41 @@ impl OpenSubtitlesClient {
         // Clean IMDB ID (remove 'tt' prefix if present)
         let imdb_clean = imdb_id.trim_start_matches("tt");
 
-        let url = format!(
-            "https://api.opensubtitles.com/api/v1/subtitles?imdb_id={}&languages={}",
-            imdb_clean, language
-        );
-
         debug!(imdb = imdb_clean, language, "searching OpenSubtitles");
+        self.search(&format!("imdb_id={}&languages={}", imdb_clean, language))
+            .await
+    }
+
+    /// Search for subtitles by TMDB ID
+    pub async fn search_by_tmdb(
+        &self,
+        tmdb_id: u64,
+        language: &str,
+    ) -> Result<Vec<SubtitleDownload>, OpenSubtitlesError> {
+        debug!(tmdb_id, language, "searching OpenSubtitles by TMDB");
+        self.search(&format!("tmdb_id={}&languages={}", tmdb_id, language))
+            .await
+    }
+
+    /// Search for subtitles made for this exact file, by its moviehash
+    pub async fn search_by_hash(
+        &self,
+        hash: &str,
+        size: u64,
+        language: &str,
+    ) -> Result<Vec<SubtitleDownload>, OpenSubtitlesError> {
+        debug!(hash, size, language, "searching OpenSubtitles by moviehash");
+        self.search(&format!(
+            "moviehash={}&moviehash_match=only&languages={}",
+            hash, language
+        ))
+        .await
+    }
+
+    /// Run a subtitle search and get download links for the top results
+    async fn search(&self, query: &str) -> Result<Vec<SubtitleDownload>, OpenSubtitlesError> {
+        let url = format!("https://api.opensubtitles.com/api/v1/subtitles?{}", query);
+        RateLimiter::acquire(&self.search_limiter, self.max_wait).await?;
 
         let response = self
             .client
@@ -84,6 +229,12 @@ impl OpenSubtitlesClient {
             .send()
             .await?;
 
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(OpenSubtitlesError::QuotaExceeded);
+        }
+        if is_auth_error(response.status()) {
+            return Err(OpenSubtitlesError::Unauthorized);
+        }
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
@@ -101,10 +252,8 @@ impl OpenSubtitlesClient {
 
         info!(count = search.data.len(), "found subtitles");
 
-        // Get download links for each subtitle
         let mut results = Vec::new();
         for sub in search.data.into_iter().take(3) {
-            // Limit to top 3
             if let Some(file) = sub.attributes.files.first() {
                 match self.get_download_link(file.file_id).await {
                     Ok(link) => {
@@ -114,6 +263,11 @@ impl OpenSubtitlesClient {
                             download_url: link,
                         });
                     }
+                    Err(
+                        e @ (OpenSubtitlesError::QuotaExceeded | OpenSubtitlesError::RateLimited),
+                    ) => {
+                        return Err(e);
+                    }
                     Err(e) => {
                         debug!(error = %e, "failed to get download link");
                     }
@@ -128,27 +282,29 @@ impl OpenSubtitlesClient {
         Ok(results)
     }
 
-    /// Search for subtitles by TMDB ID
-    pub async fn search_by_tmdb(
-        &self,
-        tmdb_id: u64,
-        language: &str,
-    ) -> Result<Vec<SubtitleDownload>, OpenSubtitlesError> {
-        let url = format!(
-            "https://api.opensubtitles.com/api/v1/subtitles?tmdb_id={}&languages={}",
-            tmdb_id, language
-        );
-
-        debug!(tmdb_id, language, "searching OpenSubtitles by TMDB");
+    async fn get_download_link(&self, file_id: u64) -> Result<String, OpenSubtitlesError> {
+        let url = "https://api.opensubtitles.com/api/v1/download";
+        RateLimiter::acquire(&self.download_limiter, self.max_wait).await?;
 
         let response = self
             .client
-            .get(&url)
+            .post(url)
             .header("Api-Key", &self.api_key)
             .header("Content-Type", "application/json")
+            .json(&serde_json::json!({ "file_id": file_id }))
             .send()
             .await?;
 
+        // 406 is the download quota, 429 the request rate
+        if matches!(
+            response.status(),
+            StatusCode::NOT_ACCEPTABLE | StatusCode::TOO_MANY_REQUESTS
+        ) {
+            return Err(OpenSubtitlesError::QuotaExceeded);
+        }
+        if is_auth_error(response.status()) {
+            return Err(OpenSubtitlesError::Unauthorized);
+        }
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
@@ -158,61 +314,752 @@ impl OpenSubtitlesClient {
             )));
         }
 
-        let search: SearchResponse = response.json().await?;
+        let download: DownloadResponse = response.json().await?;
+        Ok(download.link)
+    }
+}
 
-        if search.data.is_empty() {
-            return Err(OpenSubtitlesError::NotFound);
+/// A missing, wrong or blocked API key
+fn is_auth_error(status: StatusCode) -> bool {
+    matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+}
+
+/// A value in an XML-RPC call or response
+#[derive(Debug, Clone, PartialEq)]
+enum XmlRpcValue {
+    String(String),
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+    Array(Vec<XmlRpcValue>),
+    Struct(Vec<(String, XmlRpcValue)>),
+}
+
+impl XmlRpcValue {
+    fn member(&self, name: &str) -> Option<&XmlRpcValue> {
+        match self {
+            Self::Struct(members) => members.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
         }
+    }
 
-        info!(count = search.data.len(), "found subtitles");
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
 
-        let mut results = Vec::new();
-        for sub in search.data.into_iter().take(3) {
-            if let Some(file) = sub.attributes.files.first() {
-                match self.get_download_link(file.file_id).await {
-                    Ok(link) => {
-                        results.push(SubtitleDownload {
-                            language: sub.attributes.language.clone(),
-                            file_name: file.file_name.clone(),
-                            download_url: link,
-                        });
-                    }
-                    Err(e) => {
-                        debug!(error = %e, "failed to get download link");
+    fn write(&self, out: &mut String) {
+        out.push_str("<value>");
+        match self {
+            Self::String(s) => out.push_str(&format!("<string>{}</string>", escape(s.as_str()))),
+            Self::Int(i) => out.push_str(&format!("<int>{}</int>", i)),
+            Self::Double(d) => out.push_str(&format!("<double>{}</double>", d)),
+            Self::Bool(b) => out.push_str(&format!("<boolean>{}</boolean>", *b as u8)),
+            Self::Array(items) => {
+                out.push_str("<array><data>");
+                items.iter().for_each(|item| item.write(out));
+                out.push_str("</data></array>");
+            }
+            Self::Struct(members) => {
+                out.push_str("<struct>");
+                for (name, value) in members {
+                    out.push_str(&format!("<member><name>{}</name>", escape(name.as_str())));
+                    value.write(out);
+                    out.push_str("</member>");
+                }
+                out.push_str("</struct>");
+            }
+        }
+        out.push_str("</value>");
+    }
+}
+
+/// Request body for an XML-RPC call
+fn method_call(method: &str, params: &[XmlRpcValue]) -> String {
+    let mut body = format!(
+        "<?xml version=\"1.0\"?><methodCall><methodName>{}</methodName><params>",
+        method
+    );
+    for param in params {
+        body.push_str("<param>");
+        param.write(&mut body);
+        body.push_str("</param>");
+    }
+    body.push_str("</params></methodCall>");
+    body
+}
+
+/// Containers being filled while parsing a response
+enum Frame {
+    Array(Vec<XmlRpcValue>),
+    Struct(Vec<(String, XmlRpcValue)>, String),
+}
+
+/// The value returned by a method call, faults become errors
+fn parse_method_response(xml: &str) -> Result<XmlRpcValue, OpenSubtitlesError> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut scalar_type: Option<String> = None;
+    // Array or struct just closed, the value of the enclosing <value>
+    let mut closed: Option<XmlRpcValue> = None;
+    let mut text = String::new();
+    let mut result = None;
+    let mut is_fault = false;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| OpenSubtitlesError::XmlError(e.to_string()))?;
+        match event {
+            Event::Start(e) => {
+                match e.local_name().as_ref() {
+                    b"fault" => is_fault = true,
+                    b"value" => {
+                        scalar_type = None;
+                        closed = None;
                     }
+                    b"array" => stack.push(Frame::Array(Vec::new())),
+                    b"struct" => stack.push(Frame::Struct(Vec::new(), String::new())),
+                    b"data" | b"member" | b"name" | b"params" | b"param" | b"methodResponse" => {}
+                    other => scalar_type = Some(String::from_utf8_lossy(other).to_string()),
                 }
+                text.clear();
             }
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"value" => attach(&mut stack, &mut result, XmlRpcValue::String(String::new())),
+                b"array" => closed = Some(XmlRpcValue::Array(Vec::new())),
+                b"struct" => closed = Some(XmlRpcValue::Struct(Vec::new())),
+                b"data" | b"params" | b"param" => {}
+                other => {
+                    scalar_type = Some(String::from_utf8_lossy(other).to_string());
+                    text.clear();
+                }
+            },
+            Event::Text(e) => text.push_str(&e.decode().unwrap_or_default()),
+            Event::GeneralRef(e) => push_entity(&mut text, &e),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"name" => {
+                    if let Some(Frame::Struct(_, name)) = stack.last_mut() {
+                        *name = text.trim().to_string();
+                    }
+                }
+                b"array" | b"struct" => {
+                    closed = stack.pop().map(|frame| match frame {
+                        Frame::Array(items) => XmlRpcValue::Array(items),
+                        Frame::Struct(members, _) => XmlRpcValue::Struct(members),
+                    });
+                }
+                b"value" => {
+                    let value = closed
+                        .take()
+                        .unwrap_or_else(|| scalar(scalar_type.take().as_deref(), &text));
+                    text.clear();
+                    attach(&mut stack, &mut result, value);
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
         }
+    }
 
-        if results.is_empty() {
-            return Err(OpenSubtitlesError::NotFound);
+    let value =
+        result.ok_or_else(|| OpenSubtitlesError::XmlError("no value in response".to_string()))?;
+    if is_fault {
+        let message = value
+            .member("faultString")
+            .and_then(XmlRpcValue::as_str)
+            .unwrap_or("unknown fault");
+        return Err(OpenSubtitlesError::ApiError(message.to_string()));
+    }
+    Ok(value)
+}
+
+/// Add a finished value to the array or struct it's in, or make it the result
+fn attach(stack: &mut [Frame], result: &mut Option<XmlRpcValue>, value: XmlRpcValue) {
+    match stack.last_mut() {
+        Some(Frame::Array(items)) => items.push(value),
+        Some(Frame::Struct(members, name)) => members.push((std::mem::take(name), value)),
+        None => *result = Some(value),
+    }
+}
+
+/// A scalar from its type element and text, untyped values are strings
+fn scalar(kind: Option<&str>, text: &str) -> XmlRpcValue {
+    match kind {
+        Some("int" | "i4" | "i8") => XmlRpcValue::Int(text.trim().parse().unwrap_or(0)),
+        Some("double") => XmlRpcValue::Double(text.trim().parse().unwrap_or(0.0)),
+        Some("boolean") => XmlRpcValue::Bool(text.trim() == "1"),
+        _ => XmlRpcValue::String(text.trim().to_string()),
+    }
+}
+
+fn push_entity(text: &mut String, e: &BytesRef) {
+    if let Ok(Some(c)) = e.resolve_char_ref() {
+        text.push(c);
+    } else {
+        match e.decode().unwrap_or_default().as_ref() {
+            "amp" => text.push('&'),
+            "lt" => text.push('<'),
+            "gt" => text.push('>'),
+            "quot" => text.push('"'),
+            "apos" => text.push('\''),
+            _ => {}
         }
+    }
+}
 
-        Ok(results)
+/// What an XML-RPC subtitle search looks for. There is no TMDB lookup on
+/// this API, so without a hash or IMDB id the title is searched as text.
+#[derive(Debug, Clone, Copy)]
+pub enum SubtitleQuery<'a> {
+    /// Subtitles made for this exact file, by its moviehash
+    Hash { hash: &'a str, size: u64 },
+    /// IMDB id like "tt0111161"
+    Imdb(&'a str),
+    /// Free text, e.g. a title
+    Text(&'a str),
+}
+
+/// Client for the legacy XML-RPC API at opensubtitles.org, used once the
+/// REST API's daily quota is gone. It logs in anonymously on the first
+/// search; call `logout` when done to end the session.
+pub struct OpenSubtitlesXmlRpcClient {
+    client: Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl OpenSubtitlesXmlRpcClient {
+    pub fn new() -> Self {
+        Self::with_url(XMLRPC_URL)
     }
 
-    async fn get_download_link(&self, file_id: u64) -> Result<String, OpenSubtitlesError> {
-        let url = "https://api.opensubtitles.com/api/v1/download";
+    pub fn with_url(url: &str) -> Self {
+        Self {
+            client: crate::http::client(),
+            url: url.to_string(),
+            token: None,
+        }
+    }
 
+    async fn call(
+        &self,
+        method: &str,
+        params: &[XmlRpcValue],
+    ) -> Result<XmlRpcValue, OpenSubtitlesError> {
         let response = self
             .client
-            .post(url)
-            .header("Api-Key", &self.api_key)
-            .header("Content-Type", "application/json")
-            .json(&serde_json::json!({ "file_id": file_id }))
+            .post(&self.url)
+            .header("Content-Type", "text/xml")
+            .body(method_call(method, params))
             .send()
             .await?;
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(OpenSubtitlesError::QuotaExceeded);
+        }
+        if !response.status().is_success() {
+            return Err(OpenSubtitlesError::ApiError(format!(
+                "HTTP {}",
+                response.status()
+            )));
+        }
+        let value = parse_method_response(&response.text().await?)?;
+
+        // Errors come back as a "status" like "401 Unauthorized"
+        let status = value
+            .member("status")
+            .and_then(XmlRpcValue::as_str)
+            .unwrap_or("200 OK");
+        match status.split_whitespace().next() {
+            Some("200") => Ok(value),
+            Some("407" | "429") => Err(OpenSubtitlesError::QuotaExceeded),
+            Some("401" | "403") => Err(OpenSubtitlesError::Unauthorized),
+            _ => Err(OpenSubtitlesError::ApiError(format!(
+                "{}: {}",
+                method, status
+            ))),
+        }
+    }
+
+    /// Start an anonymous session, if there isn't one yet
+    async fn login(&mut self) -> Result<String, OpenSubtitlesError> {
+        if let Some(token) = &self.token {
+            return Ok(token.clone());
+        }
+        let params = [
+            XmlRpcValue::String(String::new()),
+            XmlRpcValue::String(String::new()),
+            XmlRpcValue::String("en".to_string()),
+            XmlRpcValue::String(XMLRPC_USER_AGENT.to_string()),
+        ];
+        let response = self.call("LogIn", &params).await?;
+        let token = response
+            .member("token")
+            .and_then(XmlRpcValue::as_str)
+            .ok_or_else(|| OpenSubtitlesError::XmlError("no token in LogIn".to_string()))?
+            .to_string();
+        debug!("logged in to OpenSubtitles XML-RPC");
+        self.token = Some(token.clone());
+        Ok(token)
+    }
+
+    /// End the session, if one was started
+    pub async fn logout(&mut self) {
+        let Some(token) = self.token.take() else {
+            return;
+        };
+        if let Err(e) = self.call("LogOut", &[XmlRpcValue::String(token)]).await {
+            debug!(error = %e, "OpenSubtitles XML-RPC logout failed");
+        }
+    }
+
+    /// Search for subtitles in `language` and download the best one
+    pub async fn search_subtitles(
+        &mut self,
+        query: SubtitleQuery<'_>,
+        language: &str,
+    ) -> Result<Vec<SubtitleDownload>, OpenSubtitlesError> {
+        // This API wants three letter codes
+        let language = crate::mpv::three_letter_code(language).unwrap_or(language);
+        debug!(?query, language, "searching OpenSubtitles XML-RPC");
+        let mut criteria = vec![(
+            "sublanguageid".to_string(),
+            XmlRpcValue::String(language.to_string()),
+        )];
+        match query {
+            SubtitleQuery::Hash { hash, size } => {
+                criteria.push((
+                    "moviehash".to_string(),
+                    XmlRpcValue::String(hash.to_string()),
+                ));
+                criteria.push((
+                    "moviebytesize".to_string(),
+                    XmlRpcValue::String(size.to_string()),
+                ));
+            }
+            SubtitleQuery::Imdb(imdb_id) => criteria.push((
+                "imdbid".to_string(),
+                XmlRpcValue::String(imdb_id.trim_start_matches("tt").to_string()),
+            )),
+            SubtitleQuery::Text(text) => {
+                criteria.push(("query".to_string(), XmlRpcValue::String(text.to_string())))
+            }
+        }
+
+        let token = self.login().await?;
+        let params = [
+            XmlRpcValue::String(token),
+            XmlRpcValue::Array(vec![XmlRpcValue::Struct(criteria)]),
+            XmlRpcValue::Struct(vec![("limit".to_string(), XmlRpcValue::Int(3))]),
+        ];
+        let response = self.call("SearchSubtitles", &params).await?;
+
+        // "data" is false rather than an empty array when nothing matched
+        let Some(XmlRpcValue::Array(found)) = response.member("data") else {
+            return Err(OpenSubtitlesError::NotFound);
+        };
+        info!(count = found.len(), "found subtitles over XML-RPC");
 
+        // Downloads count against the quota, so only fetch the best match
+        for sub in found {
+            let field = |name: &str| sub.member(name).and_then(XmlRpcValue::as_str);
+            let (Some(link), Some(id)) = (field("SubDownloadLink"), field("IDSubtitleFile")) else {
+                continue;
+            };
+            match self
+                .download(link, id, field("SubFormat").unwrap_or("srt"))
+                .await
+            {
+                Ok(path) => {
+                    return Ok(vec![SubtitleDownload {
+                        language: field("ISO639").unwrap_or_default().to_string(),
+                        file_name: field("SubFileName").unwrap_or_default().to_string(),
+                        download_url: path.to_string_lossy().into_owned(),
+                    }]);
+                }
+                Err(e) => debug!(error = %e, "failed to download subtitle"),
+            }
+        }
+        Err(OpenSubtitlesError::NotFound)
+    }
+
+    /// Fetch a gzipped subtitle and unpack it to a temp file mpv can load
+    async fn download(
+        &self,
+        link: &str,
+        id: &str,
+        format: &str,
+    ) -> Result<PathBuf, OpenSubtitlesError> {
+        let response = self.client.get(link).send().await?;
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
             return Err(OpenSubtitlesError::ApiError(format!(
-                "HTTP {}: {}",
-                status, body
+                "HTTP {}",
+                response.status()
             )));
         }
+        let bytes = response.bytes().await?;
+        let mut subtitle = Vec::new();
+        GzDecoder::new(&bytes[..]).read_to_end(&mut subtitle)?;
 
-        let download: DownloadResponse = response.json().await?;
-        Ok(download.link)
+        let dir = std::env::temp_dir().join("ferristream-subtitles");
+        std::fs::create_dir_all(&dir)?;
+        let id: String = id.chars().filter(char::is_ascii_alphanumeric).collect();
+        let format: String = format.chars().filter(char::is_ascii_alphanumeric).collect();
+        let path = dir.join(format!("{}.{}", id, format));
+        std::fs::write(&path, subtitle)?;
+        Ok(path)
+    }
+}
+
+/// OpenSubtitles moviehash: the file size plus every little-endian u64 in the
+/// first and last 64 KiB, wrapping on overflow
+pub fn moviehash(head: &[u8], tail: &[u8], size: u64) -> String {
+    let hash = head
+        .chunks_exact(8)
+        .chain(tail.chunks_exact(8))
+        .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+        .fold(size, u64::wrapping_add);
+    format!("{:016x}", hash)
+}
+
+/// Moviehash of a file being streamed, reading both ends with range requests.
+/// None if the file is too small or those pieces aren't downloaded in time.
+pub async fn stream_moviehash(stream_url: &str, size: u64) -> Option<String> {
+    if size < HASH_CHUNK_SIZE {
+        return None;
+    }
+    let client = crate::http::client();
+    let fetch_range = |start: u64| {
+        let request = client.get(stream_url).header(
+            RANGE,
+            format!("bytes={}-{}", start, start + HASH_CHUNK_SIZE - 1),
+        );
+        async move {
+            let response = request.send().await.ok()?;
+            if !response.status().is_success() {
+                return None;
+            }
+            let bytes = response.bytes().await.ok()?;
+            (bytes.len() as u64 == HASH_CHUNK_SIZE).then_some(bytes)
+        }
+    };
+
+    let ranges = futures::future::join(fetch_range(0), fetch_range(size - HASH_CHUNK_SIZE));
+    match timeout(HASH_FETCH_TIMEOUT, ranges).await {
+        Ok((Some(head), Some(tail))) => Some(moviehash(&head, &tail, size)),
+        _ => {
+            debug!("file ends not downloaded yet, skipping moviehash");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn response(members: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\"?><methodResponse><params><param><value><struct>{}</struct></value></param></params></methodResponse>",
+            members
+        )
+    }
+
+    fn member(name: &str, value: &str) -> String {
+        format!(
+            "<member><name>{}</name><value>{}</value></member>",
+            name, value
+        )
+    }
+
+    #[test]
+    fn test_method_call() {
+        let body = method_call(
+            "SearchSubtitles",
+            &[
+                XmlRpcValue::String("a&b".to_string()),
+                XmlRpcValue::Array(vec![XmlRpcValue::Struct(vec![(
+                    "limit".to_string(),
+                    XmlRpcValue::Int(3),
+                )])]),
+            ],
+        );
+        assert_eq!(
+            body,
+            "<?xml version=\"1.0\"?><methodCall><methodName>SearchSubtitles</methodName><params>\
+             <param><value><string>a&amp;b</string></value></param>\
+             <param><value><array><data><value><struct><member><name>limit</name>\
+             <value><int>3</int></value></member></struct></value></data></array></value></param>\
+             </params></methodCall>"
+        );
+    }
+
+    #[test]
+    fn test_parse_method_response() {
+        let xml = response(&format!(
+            "{}{}{}",
+            member("status", "<string>200 OK</string>"),
+            member(
+                "data",
+                "<array><data><value><struct>\
+                 <member><name>SubFileName</name><value>Heat &amp; Dust.srt</value></member>\
+                 <member><name>Score</name><value><double>12.5</double></value></member>\
+                 </struct></value><value><boolean>1</boolean></value></data></array>"
+            ),
+            member("empty", "<string/>"),
+        ));
+        let value = parse_method_response(&xml).unwrap();
+        assert_eq!(
+            value.member("status").and_then(XmlRpcValue::as_str),
+            Some("200 OK")
+        );
+        let Some(XmlRpcValue::Array(data)) = value.member("data") else {
+            panic!("no data array: {:?}", value);
+        };
+        assert_eq!(
+            data[0].member("SubFileName").and_then(XmlRpcValue::as_str),
+            Some("Heat & Dust.srt")
+        );
+        assert_eq!(data[0].member("Score"), Some(&XmlRpcValue::Double(12.5)));
+        assert_eq!(data[1], XmlRpcValue::Bool(true));
+        assert_eq!(
+            value.member("empty").and_then(XmlRpcValue::as_str),
+            Some("")
+        );
+
+        let fault = "<?xml version=\"1.0\"?><methodResponse><fault><value><struct>\
+                     <member><name>faultCode</name><value><int>4</int></value></member>\
+                     <member><name>faultString</name><value><string>Too many parameters</string></value></member>\
+                     </struct></value></fault></methodResponse>";
+        assert!(matches!(
+            parse_method_response(fault),
+            Err(OpenSubtitlesError::ApiError(message)) if message == "Too many parameters"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_xmlrpc_session() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("<methodName>LogIn</methodName>"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(response(&format!(
+                    "{}{}",
+                    member("token", "<string>tok123</string>"),
+                    member("status", "<string>200 OK</string>")
+                ))),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let link = format!("{}/download/1954081240.gz", server.uri());
+        Mock::given(method("POST"))
+            .and(body_string_contains("<methodName>SearchSubtitles</methodName>"))
+            .and(body_string_contains("<string>tok123</string>"))
+            .and(body_string_contains("<string>ger</string>"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(response(&format!(
+                "{}{}",
+                member("status", "<string>200 OK</string>"),
+                member(
+                    "data",
+                    &format!(
+                        "<array><data><value><struct>{}{}{}{}{}</struct></value></data></array>",
+                        member("IDSubtitleFile", "<string>1954081240</string>"),
+                        member("SubFileName", "<string>Heat.1995.srt</string>"),
+                        member("SubFormat", "<string>srt</string>"),
+                        member("ISO639", "<string>de</string>"),
+                        member("SubDownloadLink", &format!("<string>{}</string>", link)),
+                    )
+                )
+            ))))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let mut gzipped = GzEncoder::new(Vec::new(), Compression::default());
+        gzipped
+            .write_all(b"1\n00:00:01,000 --> 00:00:02,000\nHallo\n")
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path("/download/1954081240.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(gzipped.finish().unwrap()))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("<methodName>LogOut</methodName>"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(response(&member("status", "<string>200 OK</string>"))),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut client = OpenSubtitlesXmlRpcClient::with_url(&server.uri());
+        let subs = client
+            .search_subtitles(
+                SubtitleQuery::Hash {
+                    hash: "8e245d9679d31e12",
+                    size: 12_909_756,
+                },
+                "de",
+            )
+            .await
+            .unwrap();
+        assert_eq!(subs[0].language, "de");
+        let contents = std::fs::read_to_string(&subs[0].download_url).unwrap();
+        assert!(contents.ends_with("Hallo\n"));
+        std::fs::remove_file(&subs[0].download_url).ok();
+
+        // The session is reused until logged out
+        client
+            .search_subtitles(
+                SubtitleQuery::Hash {
+                    hash: "8e245d9679d31e12",
+                    size: 12_909_756,
+                },
+                "de",
+            )
+            .await
+            .unwrap();
+        client.logout().await;
+        client.logout().await;
+    }
+
+    #[tokio::test]
+    async fn test_xmlrpc_status_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(response(&member(
+                "status",
+                "<string>407 Download limit reached</string>",
+            ))))
+            .mount(&server)
+            .await;
+        let mut client = OpenSubtitlesXmlRpcClient::with_url(&server.uri());
+        assert!(matches!(
+            client
+                .search_subtitles(SubtitleQuery::Imdb("tt0113277"), "en")
+                .await,
+            Err(OpenSubtitlesError::QuotaExceeded)
+        ));
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(response(&member(
+                "status",
+                "<string>401 Unauthorized</string>",
+            ))))
+            .mount(&server)
+            .await;
+        let mut client = OpenSubtitlesXmlRpcClient::with_url(&server.uri());
+        assert!(matches!(
+            client
+                .search_subtitles(SubtitleQuery::Imdb("tt0113277"), "en")
+                .await,
+            Err(OpenSubtitlesError::Unauthorized)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_xmlrpc_text_search() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("<methodName>LogIn</methodName>"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(response(&member("token", "<string>tok123</string>"))),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_string_contains(
+                "<methodName>SearchSubtitles</methodName>",
+            ))
+            .and(body_string_contains(
+                "<name>query</name><value><string>Heat</string></value>",
+            ))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(response(&format!(
+                    "{}{}",
+                    member("status", "<string>200 OK</string>"),
+                    member("data", "<boolean>0</boolean>")
+                ))),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut client = OpenSubtitlesXmlRpcClient::with_url(&server.uri());
+        assert!(matches!(
+            client
+                .search_subtitles(SubtitleQuery::Text("Heat"), "en")
+                .await,
+            Err(OpenSubtitlesError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_rate_limiter_window() {
+        let mut limiter = RateLimiter::new((2, Duration::from_secs(1)));
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire(start).is_ok());
+        assert!(
+            limiter
+                .try_acquire(start + Duration::from_millis(300))
+                .is_ok()
+        );
+        // Full until the first request leaves the window
+        assert_eq!(
+            limiter.try_acquire(start + Duration::from_millis(400)),
+            Err(Duration::from_millis(600))
+        );
+        assert!(limiter.try_acquire(start + Duration::from_secs(1)).is_ok());
+        assert_eq!(
+            limiter.try_acquire(start + Duration::from_secs(1)),
+            Err(Duration::from_millis(300))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_waits_or_gives_up() {
+        let limiter = Mutex::new(RateLimiter::new((1, Duration::from_millis(200))));
+        RateLimiter::acquire(&limiter, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        // A short wait is sat out
+        let start = Instant::now();
+        RateLimiter::acquire(&limiter, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        // A longer one than allowed fails right away
+        let start = Instant::now();
+        let result = RateLimiter::acquire(&limiter, Duration::from_millis(50)).await;
+        assert!(matches!(result, Err(OpenSubtitlesError::RateLimited)));
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_moviehash() {
+        let zeros = vec![0u8; HASH_CHUNK_SIZE as usize];
+        assert_eq!(moviehash(&zeros, &zeros, 131_072), "0000000000020000");
+
+        // Words are summed little-endian and wrap around
+        let mut head = zeros.clone();
+        head[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let mut tail = zeros.clone();
+        tail[8] = 0x02;
+        assert_eq!(moviehash(&head, &tail, 1), "0000000000000002");
     }
 }
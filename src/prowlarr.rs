@@ -3,6 +3,7 @@ use serde::Deserialize;
 use thiserror::Error;
 
 use crate::config::ProwlarrConfig;
+use crate::http::send_with_retry;
 
 #[derive(Error, Debug)]
 pub enum ProwlarrError {
@@ -12,7 +13,7 @@ pub enum ProwlarrError {
     InvalidResponse(String),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Indexer {
     pub id: i32,
@@ -26,8 +27,16 @@ pub struct Indexer {
 }
 
 impl Indexer {
-    pub fn is_usable(&self) -> bool {
-        self.enable && self.protocol == "torrent" && self.supports_search
+    /// Enabled, searchable torrent indexer, or Usenet one when `usenet` is set
+    pub fn is_usable(&self, usenet: bool) -> bool {
+        self.enable
+            && self.supports_search
+            && (self.protocol == "torrent" || usenet && self.is_usenet())
+    }
+
+    /// Newznab indexer, its results link to NZBs
+    pub fn is_usenet(&self) -> bool {
+        self.protocol == "usenet"
     }
 }
 
@@ -40,7 +49,7 @@ pub struct ProwlarrClient {
 impl ProwlarrClient {
     pub fn new(config: &ProwlarrConfig) -> Self {
         Self {
-            client: Client::new(),
+            client: crate::http::client(),
             base_url: config.url.trim_end_matches('/').to_string(),
             api_key: config.apikey.clone(),
         }
@@ -49,12 +58,8 @@ impl ProwlarrClient {
     pub async fn get_indexers(&self) -> Result<Vec<Indexer>, ProwlarrError> {
         let url = format!("{}/api/v1/indexer", self.base_url);
 
-        let response = self
-            .client
-            .get(&url)
-            .header("X-Api-Key", &self.api_key)
-            .send()
-            .await?;
+        let response =
+            send_with_retry(self.client.get(&url).header("X-Api-Key", &self.api_key)).await?;
 
         if !response.status().is_success() {
             return Err(ProwlarrError::InvalidResponse(format!(
@@ -67,9 +72,13 @@ impl ProwlarrClient {
         Ok(indexers)
     }
 
-    pub async fn get_usable_indexers(&self) -> Result<Vec<Indexer>, ProwlarrError> {
+    /// Indexers that can be searched, Usenet ones included when `usenet` is set
+    pub async fn get_usable_indexers(&self, usenet: bool) -> Result<Vec<Indexer>, ProwlarrError> {
         let indexers = self.get_indexers().await?;
-        Ok(indexers.into_iter().filter(|i| i.is_usable()).collect())
+        Ok(indexers
+            .into_iter()
+            .filter(|i| i.is_usable(usenet))
+            .collect())
     }
 
     /// Build the Torznab search URL for a specific indexer
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Snapshots older than this are left alone, the torrent is likely gone
+const MAX_AGE: Duration = Duration::from_secs(3 * 24 * 60 * 60);
+
+/// Least time between two writes of the playback position
+const SAVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// What was playing, so a stream cut short by a crash or kill can be picked
+/// up on the next launch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    /// Magnet link or torrent URL the stream was started from
+    pub source_url: String,
+    pub file_name: String,
+    /// Index of the file in the torrent, for when the name doesn't match
+    pub file_idx: usize,
+    pub progress_percent: f64,
+    pub title: String,
+    pub tmdb_id: Option<u64>,
+    pub year: Option<u16>,
+    pub media_type: Option<String>,
+    /// Unix timestamp of the last save
+    #[serde(default)]
+    pub saved_at: u64,
+}
+
+/// The snapshot file of a profile, written while playing and removed when
+/// playback ends the normal way
+pub struct SessionRecovery {
+    path: PathBuf,
+    last_save: Option<Instant>,
+}
+
+impl SessionRecovery {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            last_save: None,
+        }
+    }
+
+    /// The snapshot left by a session that didn't stop cleanly, unless it's stale
+    pub fn load(&self) -> Option<SessionSnapshot> {
+        let content = std::fs::read_to_string(&self.path).ok()?;
+        let snapshot: SessionSnapshot = serde_json::from_str(&content)
+            .inspect_err(|e| warn!(error = %e, "ignoring unreadable session snapshot"))
+            .ok()?;
+        let age = now().saturating_sub(snapshot.saved_at);
+        if age > MAX_AGE.as_secs() {
+            debug!(age, "ignoring stale session snapshot");
+            return None;
+        }
+        Some(snapshot)
+    }
+
+    /// Write `snapshot` now
+    pub fn save(&mut self, snapshot: &SessionSnapshot) {
+        let snapshot = SessionSnapshot {
+            saved_at: now(),
+            ..snapshot.clone()
+        };
+        let result = serde_json::to_string_pretty(&snapshot)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(&self.path, json));
+        if let Err(e) = result {
+            warn!(path = %self.path.display(), error = %e, "failed to save session snapshot");
+        }
+        self.last_save = Some(Instant::now());
+    }
+
+    /// Write `snapshot` unless the last write was less than `SAVE_INTERVAL` ago
+    pub fn save_throttled(&mut self, snapshot: &SessionSnapshot) {
+        if self
+            .last_save
+            .is_none_or(|saved| saved.elapsed() >= SAVE_INTERVAL)
+        {
+            self.save(snapshot);
+        }
+    }
+
+    /// Remove the snapshot, playback ended the normal way
+    pub fn clear(&mut self) {
+        self.last_save = None;
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                warn!(path = %self.path.display(), error = %e, "failed to remove session snapshot");
+            }
+            _ => {}
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> SessionSnapshot {
+        SessionSnapshot {
+            source_url: "magnet:?xt=urn:btih:0123456789abcdef0123456789abcdef01234567".to_string(),
+            file_name: "Severance.S02E03.1080p.mkv".to_string(),
+            file_idx: 2,
+            progress_percent: 42.5,
+            title: "Severance".to_string(),
+            tmdb_id: Some(95396),
+            year: Some(2022),
+            media_type: Some("tv".to_string()),
+            saved_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_save_load_clear() {
+        let path =
+            std::env::temp_dir().join(format!("ferristream-recovery-{}.json", std::process::id()));
+        let mut recovery = SessionRecovery::new(path.clone());
+        assert_eq!(recovery.load(), None);
+
+        recovery.save(&snapshot());
+        let loaded = recovery.load().unwrap();
+        assert_eq!(loaded.progress_percent, 42.5);
+        assert!(loaded.saved_at > 0);
+
+        // Right after a save the position isn't written again
+        let later = SessionSnapshot {
+            progress_percent: 50.0,
+            ..snapshot()
+        };
+        recovery.save_throttled(&later);
+        assert_eq!(recovery.load().unwrap().progress_percent, 42.5);
+
+        recovery.clear();
+        assert!(!path.exists());
+        assert_eq!(recovery.load(), None);
+        // Nothing to remove is fine
+        recovery.clear();
+    }
+
+    #[test]
+    fn test_stale_snapshot_is_ignored() {
+        let path = std::env::temp_dir().join(format!(
+            "ferristream-recovery-stale-{}.json",
+            std::process::id()
+        ));
+        let stale = SessionSnapshot {
+            saved_at: now() - MAX_AGE.as_secs() - 60,
+            ..snapshot()
+        };
+        std::fs::write(&path, serde_json::to_string(&stale).unwrap()).unwrap();
+        let recovery = SessionRecovery::new(path.clone());
+        assert_eq!(recovery.load(), None);
+
+        std::fs::write(&path, "{\"source_url\": ").unwrap();
+        assert_eq!(recovery.load(), None);
+        std::fs::remove_file(&path).ok();
+    }
+}
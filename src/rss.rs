@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+
+use regex::{Regex, RegexBuilder};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::torznab::{TorrentResult, TorznabError, parse_torznab_xml, title_words};
+
+/// Watch an RSS feed for releases matching a title pattern
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RssRule {
+    pub feed_url: String,
+    /// Case-insensitive regex matched against release titles
+    pub title_pattern: String,
+    /// Lowest resolution to accept, e.g. "720p" (releases without one are skipped)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_quality: Option<String>,
+    /// Start streaming a match right away instead of queueing it
+    #[serde(default)]
+    pub auto_stream: bool,
+}
+
+impl RssRule {
+    /// Name shown in notifications
+    pub fn name(&self) -> &str {
+        &self.title_pattern
+    }
+
+    pub fn pattern(&self) -> Result<Regex, regex::Error> {
+        RegexBuilder::new(&self.title_pattern)
+            .case_insensitive(true)
+            .build()
+    }
+}
+
+/// Tracks which feed items a rule has already seen
+pub struct RssWatcher {
+    pub rule: RssRule,
+    pattern: Regex,
+    min_quality: Option<u32>,
+    seen: HashSet<String>,
+    primed: bool,
+}
+
+impl RssWatcher {
+    pub fn new(rule: RssRule) -> Result<Self, regex::Error> {
+        Ok(Self {
+            pattern: rule.pattern()?,
+            min_quality: rule.min_quality.as_deref().and_then(quality_rank),
+            rule,
+            seen: HashSet::new(),
+            primed: false,
+        })
+    }
+
+    pub fn matches(&self, result: &TorrentResult) -> bool {
+        if !self.pattern.is_match(&result.title) || !result.is_streamable() {
+            return false;
+        }
+        match self.min_quality {
+            Some(min) => release_quality(&result.title).is_some_and(|q| q >= min),
+            None => true,
+        }
+    }
+
+    /// Matching items not seen in earlier polls.
+    /// The first poll only records what's already in the feed, so existing
+    /// releases don't all fire at startup.
+    pub fn new_matches(&mut self, items: Vec<TorrentResult>) -> Vec<TorrentResult> {
+        let primed = std::mem::replace(&mut self.primed, true);
+        let unseen: Vec<TorrentResult> = items
+            .into_iter()
+            .filter(|item| self.seen.insert(item_key(item)))
+            .collect();
+        if !primed {
+            return Vec::new();
+        }
+        unseen
+            .into_iter()
+            .filter(|item| self.matches(item))
+            .collect()
+    }
+}
+
+/// Fetch and parse an RSS feed (plain RSS or Torznab)
+pub async fn fetch_feed(client: &Client, url: &str) -> Result<Vec<TorrentResult>, TorznabError> {
+    let response = client.get(url).send().await?;
+
+    if !response.status().is_success() {
+        return Err(TorznabError::InvalidResponse(format!(
+            "status: {}",
+            response.status()
+        )));
+    }
+
+    let xml = response.text().await?;
+    let source = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(String::from))
+        .unwrap_or_else(|| "rss".to_string());
+    parse_torznab_xml(&xml, &source)
+}
+
+fn item_key(item: &TorrentResult) -> String {
+    item.infohash
+        .clone()
+        .or_else(|| item.get_torrent_url())
+        .unwrap_or_else(|| item.title.clone())
+}
+
+/// Vertical resolution of a quality tag ("1080p", "4k")
+fn quality_rank(tag: &str) -> Option<u32> {
+    match tag.to_lowercase().as_str() {
+        "4k" | "uhd" => Some(2160),
+        tag => tag.strip_suffix('p')?.parse().ok(),
+    }
+}
+
+/// Highest resolution tagged in a release title
+fn release_quality(title: &str) -> Option<u32> {
+    title_words(title)
+        .iter()
+        .filter_map(|w| quality_rank(w))
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str) -> TorrentResult {
+        TorrentResult {
+            title: title.to_string(),
+            link: Some(format!("https://example.com/{}.torrent", title)),
+            magnet_url: None,
+            infohash: None,
+            size: None,
+            seeders: None,
+            leechers: None,
+            indexer: "rss".to_string(),
+            categories: Vec::new(),
+            usenet: false,
+        }
+    }
+
+    fn watcher(min_quality: Option<&str>) -> RssWatcher {
+        RssWatcher::new(RssRule {
+            feed_url: "https://example.com/rss".to_string(),
+            title_pattern: r"severance.*s02e\d+".to_string(),
+            min_quality: min_quality.map(String::from),
+            auto_stream: false,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_matches() {
+        let hd = watcher(Some("1080p"));
+        assert!(hd.matches(&item("Severance.S02E05.1080p.WEB.h264")));
+        assert!(hd.matches(&item("Severance S02E05 2160p")));
+        assert!(!hd.matches(&item("Severance.S02E05.720p.WEB.h264")));
+        assert!(!hd.matches(&item("Severance.S02E05.WEB.h264")));
+        assert!(!hd.matches(&item("Silo.S02E05.1080p.WEB.h264")));
+
+        assert!(watcher(None).matches(&item("Severance.S02E05.WEB.h264")));
+    }
+
+    #[test]
+    fn test_new_matches() {
+        let mut watcher = watcher(None);
+        let first = vec![item("Severance.S02E04.1080p"), item("Silo.S02E04.1080p")];
+        assert!(watcher.new_matches(first.clone()).is_empty());
+
+        let mut second = first;
+        second.push(item("Severance.S02E05.1080p"));
+        let matches = watcher.new_matches(second.clone());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].title, "Severance.S02E05.1080p");
+
+        assert!(watcher.new_matches(second).is_empty());
+    }
+}
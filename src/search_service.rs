@@ -0,0 +1,238 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, warn};
+
+use crate::config::ProwlarrConfig;
+use crate::prowlarr::{Indexer, ProwlarrClient, ProwlarrError};
+use crate::torznab::TorznabClient;
+
+/// How long a fetched indexer list is used before asking Prowlarr again
+const INDEXER_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Search service shared between the UI and search tasks
+pub type SharedSearchService = Arc<SearchService>;
+
+/// Prowlarr access for torrent searches. The indexer list almost never
+/// changes, so it's cached and the last known list outlives Prowlarr hiccups.
+pub struct SearchService {
+    prowlarr: ProwlarrClient,
+    config: ProwlarrConfig,
+    cache: Mutex<Option<CachedIndexers>>,
+    /// Search Usenet indexers too, there's a downloader for their NZBs
+    usenet: bool,
+    /// Shared by all searches so indexer caps are only fetched once
+    torznab: TorznabClient,
+}
+
+struct CachedIndexers {
+    indexers: Vec<Indexer>,
+    /// None once refreshed, the list is then only a fallback
+    fetched_at: Option<Instant>,
+}
+
+/// Usable indexers for a search
+pub struct IndexerList {
+    pub indexers: Vec<Indexer>,
+    /// Why Prowlarr couldn't be asked, set when the last known list is used instead
+    pub stale_reason: Option<ProwlarrError>,
+}
+
+impl SearchService {
+    pub fn new(config: &ProwlarrConfig) -> Self {
+        Self {
+            prowlarr: ProwlarrClient::new(config),
+            config: config.clone(),
+            cache: Mutex::new(None),
+            usenet: false,
+            torznab: TorznabClient::new(),
+        }
+    }
+
+    /// Search Usenet indexers as well as torrent ones
+    pub fn with_usenet(mut self, enabled: bool) -> Self {
+        self.usenet = enabled;
+        self
+    }
+
+    pub fn shared(self) -> SharedSearchService {
+        Arc::new(self)
+    }
+
+    pub fn prowlarr_url(&self) -> &str {
+        &self.config.url
+    }
+
+    pub fn prowlarr_apikey(&self) -> &str {
+        &self.config.apikey
+    }
+
+    pub fn torznab(&self) -> &TorznabClient {
+        &self.torznab
+    }
+
+    /// Usable indexers, from the cache while it's fresh. If Prowlarr fails the
+    /// last known list is returned with the reason, erroring only without one.
+    pub async fn usable_indexers(&self) -> Result<IndexerList, ProwlarrError> {
+        if let Some(indexers) = self.cached(true) {
+            debug!(count = indexers.len(), "using cached indexer list");
+            return Ok(IndexerList {
+                indexers,
+                stale_reason: None,
+            });
+        }
+
+        match self.prowlarr.get_usable_indexers(self.usenet).await {
+            Ok(indexers) => {
+                *self.cache.lock().unwrap() = Some(CachedIndexers {
+                    indexers: indexers.clone(),
+                    fetched_at: Some(Instant::now()),
+                });
+                Ok(IndexerList {
+                    indexers,
+                    stale_reason: None,
+                })
+            }
+            Err(e) => match self.cached(false) {
+                Some(indexers) => {
+                    warn!(error = %e, "Prowlarr unavailable, using last known indexers");
+                    Ok(IndexerList {
+                        indexers,
+                        stale_reason: Some(e),
+                    })
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Ask Prowlarr for the indexer list on the next search, keeping the
+    /// current one as a fallback
+    pub fn refresh(&self) {
+        if let Some(cache) = self.cache.lock().unwrap().as_mut() {
+            cache.fetched_at = None;
+        }
+    }
+
+    fn cached(&self, fresh_only: bool) -> Option<Vec<Indexer>> {
+        let cache = self.cache.lock().unwrap();
+        let cache = cache.as_ref()?;
+        let fresh = cache
+            .fetched_at
+            .is_some_and(|fetched_at| fetched_at.elapsed() < INDEXER_TTL);
+        (fresh || !fresh_only).then(|| cache.indexers.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn indexers_json() -> serde_json::Value {
+        serde_json::json!([
+            {"id": 1, "name": "Good", "enable": true, "protocol": "torrent", "supportsSearch": true},
+            {"id": 2, "name": "Usenet", "enable": true, "protocol": "usenet", "supportsSearch": true}
+        ])
+    }
+
+    fn service(server: &MockServer) -> SearchService {
+        SearchService::new(&ProwlarrConfig {
+            url: server.uri(),
+            apikey: "test".to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_indexers_are_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/indexer"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(indexers_json()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let service = service(&server);
+
+        for _ in 0..2 {
+            let list = service.usable_indexers().await.unwrap();
+            assert_eq!(list.indexers.len(), 1);
+            assert!(list.stale_reason.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_usenet_indexers_with_downloader() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/indexer"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(indexers_json()))
+            .mount(&server)
+            .await;
+
+        let list = service(&server)
+            .with_usenet(true)
+            .usable_indexers()
+            .await
+            .unwrap();
+        let names: Vec<&str> = list.indexers.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["Good", "Usenet"]);
+    }
+
+    #[tokio::test]
+    async fn test_stale_fallback_after_refresh() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/indexer"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(indexers_json()))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/indexer"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let service = service(&server);
+
+        service.usable_indexers().await.unwrap();
+        service.refresh();
+        let list = service.usable_indexers().await.unwrap();
+        assert_eq!(list.indexers[0].name, "Good");
+        assert!(list.stale_reason.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_error_without_known_indexers() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/indexer"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        assert!(service(&server).usable_indexers().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recovers_from_transient_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/indexer"))
+            .respond_with(ResponseTemplate::new(502))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/indexer"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(indexers_json()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let list = service(&server).usable_indexers().await.unwrap();
+        assert_eq!(list.indexers.len(), 1);
+    }
+}
@@ -0,0 +1,72 @@
+use thiserror::Error;
+use tracing::debug;
+
+/// Keychain service API keys are stored under, with the config field
+/// (e.g. "prowlarr.apikey", "seedbox/prowlarr.apikey" for a profile) as the account
+pub const SERVICE: &str = "ferristream";
+
+/// Written to the config file in place of a key that's in the keychain
+pub const KEYCHAIN_SENTINEL: &str = "keychain";
+
+#[derive(Error, Debug)]
+pub enum KeychainError {
+    #[error("keychain unavailable: {0}")]
+    Unavailable(#[from] keyring::Error),
+}
+
+/// Somewhere to keep secrets outside the config file
+pub trait KeychainBackend {
+    /// The secret stored for `service`/`account`, None if there's none or
+    /// the keychain can't be reached
+    fn get(&self, service: &str, account: &str) -> Option<String>;
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<(), KeychainError>;
+}
+
+/// The OS keychain: Keychain on macOS, Credential Manager on Windows and the
+/// Secret Service (GNOME Keyring, KWallet) elsewhere
+pub struct OsKeychainBackend;
+
+impl KeychainBackend for OsKeychainBackend {
+    fn get(&self, service: &str, account: &str) -> Option<String> {
+        let entry = keyring::Entry::new(service, account).ok()?;
+        match entry.get_password() {
+            Ok(secret) => Some(secret),
+            Err(keyring::Error::NoEntry) => None,
+            Err(e) => {
+                debug!(service, account, error = %e, "keychain lookup failed");
+                None
+            }
+        }
+    }
+
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<(), KeychainError> {
+        keyring::Entry::new(service, account)?.set_password(secret)?;
+        Ok(())
+    }
+}
+
+/// Keychain kept in memory, for tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryKeychain {
+    pub entries: std::sync::Mutex<std::collections::HashMap<String, String>>,
+    /// Refuse every `set`, like a keychain that's locked or missing
+    pub read_only: bool,
+}
+
+#[cfg(test)]
+impl KeychainBackend for MemoryKeychain {
+    fn get(&self, service: &str, account: &str) -> Option<String> {
+        let key = format!("{}/{}", service, account);
+        self.entries.lock().unwrap().get(&key).cloned()
+    }
+
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<(), KeychainError> {
+        if self.read_only {
+            return Err(keyring::Error::NoStorageAccess("read only".into()).into());
+        }
+        let key = format!("{}/{}", service, account);
+        self.entries.lock().unwrap().insert(key, secret.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+
+use crate::history::WatchHistory;
+
+/// Current version of the exported state format.
+/// Bump it when the layout changes and migrate older versions in `StateBundle::from_json`.
+pub const STATE_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum StateError {
+    #[error("failed to access state file: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("invalid state file: {0}")]
+    ParseError(#[from] serde_json::Error),
+    #[error("state file version {0} is not supported (newest known is {STATE_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+/// Local state that moves between machines: `ferristream export` / `import`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StateBundle {
+    pub version: u32,
+    /// Unix timestamp of the export
+    #[serde(default)]
+    pub exported_at: u64,
+    pub watch_history: WatchHistory,
+}
+
+/// Outcome of importing a bundle
+#[derive(Debug, PartialEq)]
+pub struct ImportSummary {
+    /// History entries added or replaced by a newer one
+    pub updated: usize,
+    pub total: usize,
+}
+
+impl StateBundle {
+    pub fn new(watch_history: WatchHistory) -> Self {
+        let exported_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            version: STATE_VERSION,
+            exported_at,
+            watch_history,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, StateError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parse an exported bundle, migrating older versions to the current layout
+    pub fn from_json(json: &str) -> Result<Self, StateError> {
+        #[derive(Deserialize)]
+        struct Header {
+            version: u32,
+        }
+
+        let header: Header = serde_json::from_str(json)?;
+        match header.version {
+            STATE_VERSION => Ok(serde_json::from_str(json)?),
+            version => Err(StateError::UnsupportedVersion(version)),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), StateError> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, StateError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Merge into the local state, newest entry per key wins
+    pub fn merge_into(self, history: &mut WatchHistory) -> ImportSummary {
+        let updated = history.merge(self.watch_history);
+        ImportSummary {
+            updated,
+            total: history.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryKey;
+
+    #[test]
+    fn test_round_trip_and_merge() {
+        let mut remote = WatchHistory::default();
+        remote.update(
+            &HistoryKey::Title(335984),
+            "Blade Runner 2049".to_string(),
+            42.0,
+        );
+
+        let json = StateBundle::new(remote).to_json().unwrap();
+        let bundle = StateBundle::from_json(&json).unwrap();
+        assert_eq!(bundle.version, STATE_VERSION);
+
+        let mut local = WatchHistory::default();
+        let summary = bundle.merge_into(&mut local);
+        assert_eq!(
+            summary,
+            ImportSummary {
+                updated: 1,
+                total: 1
+            }
+        );
+        assert_eq!(
+            local
+                .get(&HistoryKey::Title(335984))
+                .unwrap()
+                .progress_percent,
+            42.0
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        assert!(matches!(
+            StateBundle::from_json("{\"version\": 1, \"watch_hist"),
+            Err(StateError::ParseError(_))
+        ));
+        assert!(matches!(
+            StateBundle::from_json("{\"exported_at\": 0}"),
+            Err(StateError::ParseError(_))
+        ));
+        assert!(matches!(
+            StateBundle::from_json("{\"version\": 1, \"watch_history\": []}"),
+            Err(StateError::ParseError(_))
+        ));
+        assert!(matches!(
+            StateBundle::from_json("{\"version\": 99, \"watch_history\": {}}"),
+            Err(StateError::UnsupportedVersion(99))
+        ));
+    }
+}
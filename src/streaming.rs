@@ -1,18 +1,27 @@
-use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use futures::future::{Either, select};
 use librqbit::api::Api;
+use librqbit::dht::{Dht, Id20};
 use librqbit::http_api::{HttpApi, HttpApiOptions};
-use librqbit::{AddTorrent, AddTorrentOptions, AddTorrentResponse, Session, SessionOptions};
+use librqbit::{
+    AddTorrent, AddTorrentOptions, AddTorrentResponse, Magnet, Session, SessionOptions,
+};
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::net::TcpListener;
 use tokio::process::Command;
+use tokio::sync::{mpsc, watch};
 use tokio::time::timeout;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+use crate::torznab::{TorrentResult, title_words};
+use crate::usenet::{UsenetClient, UsenetError, serve_file};
 
 #[derive(Error, Debug)]
 pub enum StreamError {
@@ -22,8 +31,8 @@ pub enum StreamError {
     #[error("{0}")]
     TorrentError(String),
 
-    #[error("no video files found in torrent - this might be a game, software, or audio release")]
-    NoVideoFiles,
+    #[error("{}", .0.no_files_message())]
+    NoVideoFiles(MediaMode),
 
     #[error("failed to launch player '{0}': {1}. Is the player installed and in your PATH?")]
     PlayerError(String, String),
@@ -36,9 +45,22 @@ pub enum StreamError {
 
     #[error("timeout waiting for torrent metadata - the torrent may be dead or have no seeders")]
     MetadataTimeout,
+
+    #[error("usenet: {0}")]
+    Usenet(#[from] UsenetError),
+
+    #[error(
+        "file is only {size_mb} MB where a {runtime_minutes} minute runtime needs about {expected_mb} MB - it may be a sample or cam rip"
+    )]
+    SuspiciousFile {
+        size_mb: u64,
+        expected_mb: u64,
+        runtime_minutes: u32,
+    },
 }
 
 const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "avi", "mov", "wmv", "flv", "webm", "m4v"];
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "m4a", "opus"];
 const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "sub", "vtt"];
 
 /// Try to extract language code from subtitle filename
@@ -99,16 +121,218 @@ fn extract_subtitle_language(filename: &str) -> Option<String> {
     None
 }
 
+/// What gets searched and streamed: "video", "audio" (music, audiobooks) or "all"
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaMode {
+    #[default]
+    Video,
+    Audio,
+    All,
+}
+
+impl MediaMode {
+    /// Torznab categories to search: 2000 movies, 5000 TV, 3000 audio
+    pub fn torznab_categories(self) -> &'static [u32] {
+        match self {
+            MediaMode::Video => &[2000, 5000],
+            MediaMode::Audio => &[3000],
+            MediaMode::All => &[2000, 3000, 5000],
+        }
+    }
+
+    /// Whether a file from a torrent can be picked for playback
+    pub fn is_playable(self, filename: &str) -> bool {
+        match self {
+            MediaMode::Video => is_video_file(filename),
+            MediaMode::Audio => is_audio_file(filename),
+            MediaMode::All => is_video_file(filename) || is_audio_file(filename),
+        }
+    }
+
+    /// Cycle Video -> Audio -> All
+    pub fn next(self) -> Self {
+        match self {
+            MediaMode::Video => MediaMode::Audio,
+            MediaMode::Audio => MediaMode::All,
+            MediaMode::All => MediaMode::Video,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MediaMode::Video => "Video",
+            MediaMode::Audio => "Audio",
+            MediaMode::All => "All",
+        }
+    }
+
+    fn no_files_message(self) -> &'static str {
+        match self {
+            MediaMode::Video => {
+                "no video files found in torrent - this might be a game, software, or audio release"
+            }
+            MediaMode::Audio => "no audio files found in torrent - this might be a video release",
+            MediaMode::All => "no video or audio files found in torrent",
+        }
+    }
+}
+
+/// Files of a torrent that can be played
+struct PlayableFiles {
+    files: Vec<VideoFile>,
+    /// Samples, trailers and the like, never picked by default
+    extras: Vec<VideoFile>,
+    selected: VideoFile,
+}
+
+/// Keep the files playable in `mode` and pick the default one: the largest
+/// video (usually the main content), or the first track of an audio release
+fn playable_files(
+    mode: MediaMode,
+    files: impl IntoIterator<Item = VideoFile>,
+) -> Result<PlayableFiles, StreamError> {
+    let files: Vec<VideoFile> = files
+        .into_iter()
+        .filter(|f| mode.is_playable(&f.name))
+        .collect();
+    let (mut files, extras) = split_extras(files);
+
+    let selected = if files.iter().all(|f| is_audio_file(&f.name)) {
+        sort_episodes(&mut files);
+        files.first().cloned()
+    } else {
+        files.iter().max_by_key(|f| f.size).cloned()
+    };
+    let selected = selected.ok_or(StreamError::NoVideoFiles(mode))?;
+    info!(
+        files = files.len(),
+        extras = extras.len(),
+        "found playable files"
+    );
+    Ok(PlayableFiles {
+        files,
+        extras,
+        selected,
+    })
+}
+
+/// Directory names that only hold extras ("Sample/", "Featurettes/")
+const EXTRA_DIRS: &[&str] = &[
+    "sample",
+    "samples",
+    "extras",
+    "extra",
+    "featurettes",
+    "featurette",
+    "bonus",
+    "trailers",
+    "behind the scenes",
+    "deleted scenes",
+    "interviews",
+];
+
+/// Words in a file name that mark an extra ("movie-sample.mkv", "Trailer.mp4")
+const EXTRA_WORDS: &[&str] = &["sample", "trailer", "teaser", "featurette"];
+
+/// Videos smaller than this fraction of the median video are extras
+const EXTRA_SIZE_RATIO: f64 = 0.1;
+
+/// Whether a video in a torrent is a sample, trailer or other extra rather than
+/// the content: by its directory, by a word in its name, or by being tiny next
+/// to the median video of the torrent
+pub fn is_extra_file(name: &str, size: u64, median_size: u64) -> bool {
+    let lower = name.to_lowercase();
+    let mut components: Vec<&str> = lower.split(['/', '\\']).collect();
+    let file = components.pop().unwrap_or_default();
+
+    let in_extras_dir = components.iter().any(|dir| {
+        let dir = dir.replace(['.', '_', '-'], " ");
+        EXTRA_DIRS.contains(&dir.trim())
+    });
+    let stem = file.rsplit_once('.').map_or(file, |(stem, _)| stem);
+    let has_extra_word = stem
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| EXTRA_WORDS.contains(&word));
+    let tiny = (size as f64) < median_size as f64 * EXTRA_SIZE_RATIO;
+
+    in_extras_dir || has_extra_word || tiny
+}
+
+/// Split videos into (content, extras). Audio files are all content, and if
+/// everything looks like an extra nothing is, so there's always a file to play.
+pub fn split_extras(files: Vec<VideoFile>) -> (Vec<VideoFile>, Vec<VideoFile>) {
+    let mut sizes: Vec<u64> = files
+        .iter()
+        .filter(|f| is_video_file(&f.name))
+        .map(|f| f.size)
+        .collect();
+    sizes.sort_unstable();
+    let median = sizes.get(sizes.len() / 2).copied().unwrap_or(0);
+
+    let (extras, content): (Vec<VideoFile>, Vec<VideoFile>) = files
+        .into_iter()
+        .partition(|f| is_video_file(&f.name) && is_extra_file(&f.name, f.size, median));
+    if content.is_empty() {
+        return (extras, Vec::new());
+    }
+    (content, extras)
+}
+
 #[derive(Clone)]
 pub struct StreamingSession {
-    session: Arc<Session>,
-    http_addr: SocketAddr,
+    /// Embedded librqbit session, None when using an external rqbit daemon
+    session: Option<Arc<Session>>,
+    api: RemoteRqbitBackend,
     http_client: Client,
+    /// Shared storage root, each session downloads into its own subdirectory
+    storage_dir: PathBuf,
+    session_id: u64,
+    /// `storage_dir/<session_id>-<pid>`, the only directory cleanup removes
     temp_dir: PathBuf,
+    /// Torrents we added to an external daemon - the only ones cleanup removes
+    added_ids: Arc<Mutex<Vec<usize>>>,
+    /// Torrents the daemon already had before we connected
+    existing_ids: Arc<Vec<usize>>,
+    /// URL each torrent was added from, to add it again after a restart
+    sources: Arc<Mutex<HashMap<usize, String>>>,
+    /// Length of every file of each torrent, for piece maps
+    file_lengths: Arc<Mutex<HashMap<usize, Vec<u64>>>>,
+    /// Players streaming from this session, cleanup waits for them to close
+    players: Arc<watch::Sender<usize>>,
+    /// Which files of added torrents are offered for playback
+    media_mode: MediaMode,
+    /// Longest wait for a torrent's metadata, see [`MetadataWait`]
+    metadata_timeout: Duration,
+    /// Where adding a torrent is at, for the UI
+    add_progress: Option<mpsc::UnboundedSender<AddPhase>>,
+    /// Finished Usenet downloads served over local HTTP, by the id their
+    /// [`TorrentInfo`] got
+    served: Arc<Mutex<HashMap<usize, ServedFile>>>,
+}
+
+/// A finished Usenet download, streamed from disk rather than by rqbit
+struct ServedFile {
+    size: u64,
+    server: tokio::task::JoinHandle<()>,
 }
 
 impl StreamingSession {
-    pub async fn new(temp_dir: PathBuf) -> Result<Self, StreamError> {
+    /// Use the rqbit daemon at `rqbit_url` if set, otherwise start an embedded session
+    pub async fn start(
+        rqbit_url: Option<&str>,
+        storage_dir: PathBuf,
+        session_id: u64,
+    ) -> Result<Self, StreamError> {
+        match rqbit_url {
+            Some(url) => Self::connect(url).await,
+            None => Self::new(storage_dir, session_id).await,
+        }
+    }
+
+    /// Start an embedded session downloading into `storage_dir/<session_id>-<pid>/`
+    pub async fn new(storage_dir: PathBuf, session_id: u64) -> Result<Self, StreamError> {
+        let temp_dir = storage_dir.join(session_dir_name(session_id));
         tokio::fs::create_dir_all(&temp_dir)
             .await
             .map_err(|e| StreamError::SessionError(e.to_string()))?;
@@ -153,22 +377,216 @@ impl StreamingSession {
             let _ = http_api.make_http_api_and_run(listener, None).await;
         });
 
+        // Lets a later run tell this directory apart from one left behind by a crash
+        if let Err(e) = tokio::fs::write(
+            temp_dir.join(SESSION_LOCK_FILE),
+            http_addr.port().to_string(),
+        )
+        .await
+        {
+            debug!(error = %e, "failed to write session lock file");
+        }
+
         Ok(Self {
-            session,
-            http_addr,
-            http_client: Client::builder()
-                .redirect(reqwest::redirect::Policy::none()) // we handle these redirects manually
-                .build()
-                .unwrap(),
+            session: Some(session),
+            api: RemoteRqbitBackend::new(&format!("http://{}", http_addr)),
+            http_client: torrent_fetch_client(),
+            storage_dir,
+            session_id,
             temp_dir,
+            added_ids: Arc::default(),
+            existing_ids: Arc::default(),
+            sources: Arc::default(),
+            file_lengths: Arc::default(),
+            players: Arc::new(watch::Sender::new(0)),
+            media_mode: MediaMode::default(),
+            metadata_timeout: DEFAULT_METADATA_TIMEOUT,
+            add_progress: None,
+            served: Arc::default(),
+        })
+    }
+
+    /// Offer the files of added torrents that are playable in `mode`
+    pub fn with_media_mode(mut self, mode: MediaMode) -> Self {
+        self.media_mode = mode;
+        self
+    }
+
+    pub fn media_mode(&self) -> MediaMode {
+        self.media_mode
+    }
+
+    pub fn metadata_timeout(&self) -> Duration {
+        self.metadata_timeout
+    }
+
+    /// Give up on torrents whose metadata takes longer than `max`
+    pub fn with_metadata_timeout(mut self, max: Duration) -> Self {
+        self.metadata_timeout = max;
+        self
+    }
+
+    /// Send the phases torrents go through while being added to `progress`
+    pub fn with_add_progress(mut self, progress: mpsc::UnboundedSender<AddPhase>) -> Self {
+        self.add_progress = Some(progress);
+        self
+    }
+
+    /// Bounds and reports the wait for a torrent's metadata. Magnets on the
+    /// embedded session also count the peers our DHT finds for them.
+    fn metadata_wait(&self, magnet: Option<&str>) -> MetadataWait {
+        let dht = self.session.as_ref().and_then(|session| session.get_dht());
+        let info_hash = magnet
+            .and_then(|magnet| Magnet::parse(magnet).ok())
+            .and_then(|magnet| magnet.as_id20());
+        MetadataWait {
+            max: self.metadata_timeout,
+            progress: self.add_progress.clone(),
+            magnet: magnet.is_some(),
+            peers: dht.cloned().zip(info_hash),
+        }
+    }
+
+    /// Stream through an already running rqbit daemon instead of an embedded session
+    pub async fn connect(rqbit_url: &str) -> Result<Self, StreamError> {
+        let api = RemoteRqbitBackend::new(rqbit_url);
+        let version = api.version().await?;
+        let existing_ids = api.torrent_ids().await?;
+        info!(url = %api.base_url(), version = %version, existing = existing_ids.len(), "connected to rqbit daemon");
+
+        Ok(Self {
+            session: None,
+            api,
+            http_client: torrent_fetch_client(),
+            storage_dir: PathBuf::new(),
+            session_id: 0,
+            temp_dir: PathBuf::new(),
+            added_ids: Arc::default(),
+            existing_ids: Arc::new(existing_ids),
+            sources: Arc::default(),
+            file_lengths: Arc::default(),
+            players: Arc::new(watch::Sender::new(0)),
+            media_mode: MediaMode::default(),
+            metadata_timeout: DEFAULT_METADATA_TIMEOUT,
+            add_progress: None,
+            served: Arc::default(),
         })
     }
 
-    /// Clean up temp files
-    pub async fn cleanup(&self) {
-        info!("cleaning up temp files");
-        if let Err(e) = tokio::fs::remove_dir_all(&self.temp_dir).await {
-            debug!(error = %e, "failed to remove temp dir (may not exist)");
+    /// Count a daemon torrent as ours, so cleanup removes it even if the daemon
+    /// had it before we connected, like one of ours it kept across a restart
+    pub fn claim(&self, torrent_id: usize) {
+        if self.session.is_some() {
+            return;
+        }
+        let mut added_ids = self.added_ids.lock().unwrap();
+        if !added_ids.contains(&torrent_id) {
+            added_ids.push(torrent_id);
+        }
+    }
+
+    /// Remove every torrent except `torrent_id`, e.g. the losers of a race before seeding
+    pub async fn keep_only(&self, torrent_id: usize) {
+        let ids = match self.session {
+            Some(_) => self.api.torrent_ids().await.unwrap_or_default(),
+            None => self.added_ids.lock().unwrap().clone(),
+        };
+        for id in ids.into_iter().filter(|id| *id != torrent_id) {
+            if let Err(e) = self.api.delete(id).await {
+                debug!(id, error = %e, "failed to remove torrent");
+            }
+        }
+        self.added_ids
+            .lock()
+            .unwrap()
+            .retain(|id| *id == torrent_id);
+        self.served.lock().unwrap().retain(|id, served| {
+            if *id != torrent_id {
+                served.server.abort();
+            }
+            *id == torrent_id
+        });
+    }
+
+    /// Remove one of our torrents, e.g. a race entry something better superseded
+    async fn remove(&self, torrent_id: usize) {
+        if let Err(e) = self.api.delete(torrent_id).await {
+            debug!(id = torrent_id, error = %e, "failed to remove torrent");
+        }
+        self.added_ids
+            .lock()
+            .unwrap()
+            .retain(|id| *id != torrent_id);
+    }
+
+    /// Storage root the session directory lives in
+    pub fn storage_dir(&self) -> &PathBuf {
+        &self.storage_dir
+    }
+
+    pub fn session_id(&self) -> u64 {
+        self.session_id
+    }
+
+    /// URL a torrent was added from
+    pub fn source_url(&self, torrent_id: usize) -> Option<String> {
+        self.sources.lock().unwrap().get(&torrent_id).cloned()
+    }
+
+    /// Stop the embedded session but keep its downloaded data, so a new
+    /// session on the same directory can pick up where this one left off
+    pub async fn shutdown(&self) {
+        if let Some(session) = &self.session {
+            session.stop().await;
+        }
+    }
+
+    /// Mark a player as streaming from this session until the guard is dropped
+    pub fn player_running(&self) -> PlayerGuard {
+        self.players.send_modify(|count| *count += 1);
+        PlayerGuard {
+            players: self.players.clone(),
+        }
+    }
+
+    /// Clean up temp files, or the torrents we added to an external daemon.
+    /// Waits for players to close first, then retries while they still hold
+    /// files open, see [`Cleanup`].
+    pub async fn cleanup(&self) -> Cleanup {
+        // The downloader owns Usenet downloads, only stop serving them
+        for (_, served) in self.served.lock().unwrap().drain() {
+            served.server.abort();
+        }
+
+        let Some(session) = &self.session else {
+            let ids = std::mem::take(&mut *self.added_ids.lock().unwrap());
+            info!(count = ids.len(), "removing our torrents from rqbit daemon");
+            for id in ids {
+                if let Err(e) = self.api.delete(id).await {
+                    debug!(id, error = %e, "failed to remove torrent from daemon");
+                }
+            }
+            return Cleanup::Daemon;
+        };
+
+        // Stop uploading before the files go away
+        session.stop().await;
+
+        let mut players = self.players.subscribe();
+        if timeout(PLAYER_EXIT_WAIT, players.wait_for(|count| *count == 0))
+            .await
+            .is_err()
+        {
+            warn!("player still running, cleaning up anyway");
+        }
+
+        info!(dir = %self.temp_dir.display(), "cleaning up temp files");
+        match remove_dir_with_retry(&self.temp_dir, CLEANUP_RETRY_FOR).await {
+            Ok(freed) => Cleanup::Freed(freed),
+            Err(e) => {
+                warn!(dir = %self.temp_dir.display(), error = %e, "failed to remove temp dir");
+                Cleanup::Deferred(self.temp_dir.clone())
+            }
         }
     }
 
@@ -196,6 +614,8 @@ impl StreamingSession {
 
         let mut urls_iter = urls.into_iter().enumerate();
         let mut in_flight = 0;
+        // Valid torrent without the preferred quality, used if nothing better connects
+        let mut best_so_far: Option<(u32, usize, TorrentInfo)> = None;
 
         // Start initial batch
         for _ in 0..concurrent {
@@ -225,14 +645,15 @@ impl StreamingSession {
                             Ok(info) => {
                                 // Validate the filename if validation is provided
                                 if let Some(ref v) = validation
-                                    && !v.matches(&info.selected_file.name) {
+                                    && !v.matches_torrent(&info) {
                                         info!(
                                             idx,
                                             name = %info.selected_file.name,
                                             "torrent rejected - filename doesn't match"
                                         );
                                         // Add next torrent to keep racing
-                                        if let Some((next_idx, url)) = urls_iter.next() {
+                                        if best_so_far.is_none()
+                                            && let Some((next_idx, url)) = urls_iter.next() {
                                             let session = self.clone();
                                             let tx = tx.clone();
                                             tokio::spawn(async move {
@@ -243,13 +664,34 @@ impl StreamingSession {
                                         }
                                         continue;
                                     }
+
+                                // Hold back lower quality winners until the other slots finish
+                                if let Some(ref v) = validation
+                                    && !v.meets_quality(&info) {
+                                        let score = v.torrent_quality_score(&info);
+                                        info!(
+                                            idx,
+                                            score,
+                                            name = %info.selected_file.name,
+                                            "torrent connected without preferred quality"
+                                        );
+                                        // Only the best one is kept, the rest stop downloading now
+                                        if best_so_far.as_ref().is_some_and(|(best, _, _)| score <= *best) {
+                                            self.remove(info.id).await;
+                                        } else if let Some((_, _, replaced)) = best_so_far.replace((score, idx, info)) {
+                                            self.remove(replaced.id).await;
+                                        }
+                                        continue;
+                                    }
+
                                 info!(idx, name = %info.selected_file.name, "torrent won the race");
                                 return Ok((idx, info));
                             }
                             Err(e) => {
                                 debug!(idx, error = %e, "torrent failed");
                                 // Add next torrent to keep racing
-                                if let Some((next_idx, url)) = urls_iter.next() {
+                                if best_so_far.is_none()
+                                    && let Some((next_idx, url)) = urls_iter.next() {
                                     let session = self.clone();
                                     let tx = tx.clone();
                                     tokio::spawn(async move {
@@ -266,168 +708,1151 @@ impl StreamingSession {
             }
         }
 
+        if let Some((score, idx, info)) = best_so_far {
+            info!(idx, score, name = %info.selected_file.name, "best available torrent won the race");
+            return Ok((idx, info));
+        }
+
         Err(StreamError::TorrentError(
             "no matching torrents found".to_string(),
         ))
     }
 }
 
-/// Validation criteria for racing torrents
-#[derive(Debug, Clone)]
-pub struct TorrentValidation {
-    /// Title keywords - at least one must be present in filename
-    pub title_keywords: Vec<String>,
-    /// Expected year - if set, must be present in filename
-    pub year: Option<u16>,
+fn is_magnet(input: &str) -> bool {
+    input
+        .get(..7)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("magnet:"))
 }
 
-impl TorrentValidation {
-    pub fn new(title_keywords: Vec<String>, year: Option<u16>) -> Self {
-        Self {
-            title_keywords,
-            year,
-        }
+/// A torrent to stream straight away instead of searching for: a magnet
+/// link, an http(s) link to a .torrent, or a local .torrent file. Files
+/// dropped on a terminal can arrive quoted, with escaped spaces, or as a
+/// file:// URL; the path returned is plain.
+pub fn direct_torrent_source(input: &str) -> Option<String> {
+    let input = input.trim();
+    let input = match input.as_bytes() {
+        [quote @ (b'\'' | b'"'), .., last] if last == quote => &input[1..input.len() - 1],
+        _ => input,
+    };
+
+    if is_magnet(input) {
+        return Some(input.to_string());
+    }
+    if input.starts_with("http://") || input.starts_with("https://") {
+        let url = url::Url::parse(input).ok()?;
+        return url
+            .path()
+            .to_lowercase()
+            .ends_with(".torrent")
+            .then(|| input.to_string());
     }
+    if let Some(path) = local_torrent_path(input) {
+        return Some(path.to_string_lossy().into_owned());
+    }
+    let path = input.replace("\\ ", " ");
+    path.to_lowercase().ends_with(".torrent").then_some(path)
+}
 
-    /// Check if a filename matches the validation criteria
-    pub fn matches(&self, filename: &str) -> bool {
-        let filename_lower = filename.to_lowercase();
+/// The file an input names when it's clearly a path, whatever its extension:
+/// absolute, under ~/ or a file:// URL. Escaped spaces are unescaped.
+pub fn local_torrent_path(input: &str) -> Option<PathBuf> {
+    if let Some(path) = input.strip_prefix("file://") {
+        return Some(PathBuf::from(urlencoding::decode(path).ok()?.into_owned()));
+    }
+    let path = input.replace("\\ ", " ");
+    if let Some(rest) = path.strip_prefix("~/") {
+        let home = directories::BaseDirs::new()?.home_dir().to_path_buf();
+        return Some(home.join(rest));
+    }
+    (path.starts_with('/') || Path::new(&path).is_absolute()).then(|| PathBuf::from(path))
+}
 
-        // Check title keywords - at least one must match
-        let title_matches = self.title_keywords.is_empty()
-            || self
-                .title_keywords
-                .iter()
-                .any(|kw| filename_lower.contains(kw));
+/// Whether `bytes` start like a .torrent file, a bencoded dictionary
+fn looks_like_torrent(bytes: &[u8]) -> bool {
+    matches!(bytes, [b'd', len, ..] if len.is_ascii_digit())
+}
 
-        // Check year if specified
-        let year_matches = match self.year {
-            Some(year) => filename.contains(&year.to_string()),
-            None => true,
-        };
+/// Make sure a direct source that's a local file exists and holds a torrent,
+/// so a typo shows up before leaving the search. Links pass unchecked.
+pub fn check_torrent_source(source: &str) -> Result<(), StreamError> {
+    use std::io::Read;
 
-        title_matches && year_matches
+    if is_magnet(source) || source.starts_with("http://") || source.starts_with("https://") {
+        return Ok(());
+    }
+    let path = local_torrent_path(source).unwrap_or_else(|| PathBuf::from(source));
+    let mut head = [0u8; 2];
+    std::fs::File::open(&path)
+        .and_then(|mut file| file.read_exact(&mut head))
+        .map_err(|e| StreamError::TorrentError(format!("Can't read {}: {}", path.display(), e)))?;
+    if looks_like_torrent(&head) {
+        Ok(())
+    } else {
+        Err(StreamError::TorrentError(format!(
+            "{} isn't a valid .torrent file",
+            path.display()
+        )))
     }
+}
 
-    /// Extract title keywords from a query string
-    pub fn extract_keywords(query: &str) -> Vec<String> {
-        let stop_words = [
-            "the", "a", "an", "and", "or", "of", "in", "on", "at", "to", "for",
-        ];
-        query
-            .split(|c: char| !c.is_alphanumeric())
-            .filter(|word| word.len() >= 3)
-            .map(|word| word.to_lowercase())
-            .filter(|word| !stop_words.contains(&word.as_str()))
-            // Filter out years from title keywords (they're handled separately)
-            .filter(|word| word.parse::<u16>().is_err() || word.len() != 4)
-            .collect()
+/// Read a local .torrent file, given as a path or file:// URL
+async fn read_local_torrent(source: &str) -> Result<Vec<u8>, StreamError> {
+    let path = local_torrent_path(source).unwrap_or_else(|| PathBuf::from(source));
+    debug!(path = %path.display(), "reading local torrent file");
+    let bytes = tokio::fs::read(&path).await.map_err(|e| {
+        StreamError::TorrentError(format!("failed to read {}: {}", path.display(), e))
+    })?;
+    if !looks_like_torrent(&bytes) {
+        return Err(StreamError::TorrentError(format!(
+            "{} isn't a valid .torrent file",
+            path.display()
+        )));
     }
+    Ok(bytes)
 }
 
-impl StreamingSession {
-    /// Add a torrent by URL (magnet or .torrent file URL)
-    pub fn add_torrent(
-        &self,
-        url: &str,
-    ) -> std::pin::Pin<
-        Box<dyn std::future::Future<Output = Result<TorrentInfo, StreamError>> + Send + '_>,
-    > {
-        let url = url.to_string();
-        Box::pin(async move {
-            // there are two types of urls (magnet/http).
-            // if it's an http URL fetch the .torrent file first
-            let magnet_url = if url.starts_with("http://") || url.starts_with("https://") {
-                debug!("fetching torrent from URL");
-                match self.fetch_torrent_file(&url).await {
-                    Ok(bytes) => {
-                        debug!(bytes = bytes.len(), "got .torrent file");
-                        return self.add_torrent_bytes(bytes).await;
-                    }
-                    Err(StreamError::MagnetRedirect(magnet)) => {
-                        debug!("prowlarr redirected to magnet link");
-                        magnet
-                    }
-                    Err(e) => return Err(e),
-                }
-            } else {
-                url
-            };
+/// Magnet link for a .torrent file, with its info hash and trackers
+fn torrent_magnet(bytes: &[u8]) -> Result<String, StreamError> {
+    let torrent = librqbit::torrent_from_bytes::<librqbit::ByteBuf>(bytes)
+        .map_err(|e| StreamError::TorrentError(format!("invalid .torrent file: {}", e)))?;
+    let trackers = torrent
+        .iter_announce()
+        .filter_map(|tracker| std::str::from_utf8(tracker).ok())
+        .map(str::to_string)
+        .collect();
+    Ok(Magnet::from_id20(torrent.info_hash, trackers, None).to_string())
+}
 
-            debug!(magnet = %&magnet_url[..magnet_url.len().min(60)], "using magnet link");
-            self.add_torrent_via_http_full(&magnet_url).await
-        })
+/// File in a session directory holding the port of its embedded HTTP API
+const SESSION_LOCK_FILE: &str = ".lock";
+/// How often the Usenet downloader is asked how far an NZB got
+const NZB_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Ids of served Usenet downloads start here, clear of rqbit's torrent ids
+const SERVED_ID_BASE: usize = usize::MAX / 2;
+
+/// Directory a session downloads into. Session ids are seeded from the clock
+/// and the pid tells instances apart, so they never share a directory and a
+/// later run can tell whether the owner is still around.
+pub fn session_dir_name(session_id: u64) -> String {
+    format!("{}-{}", session_id, std::process::id())
+}
+
+/// Session id and owning pid from a session directory name. Directories from
+/// before the pid was added only have the id.
+fn parse_session_dir_name(name: &str) -> Option<(u64, Option<u32>)> {
+    match name.split_once('-') {
+        Some((id, pid)) => Some((id.parse().ok()?, Some(pid.parse().ok()?))),
+        None => Some((name.parse().ok()?, None)),
     }
+}
 
-    async fn add_torrent_bytes(&self, bytes: Vec<u8>) -> Result<TorrentInfo, StreamError> {
-        self.add_torrent_inner(AddTorrent::from_bytes(bytes)).await
+/// Whether the process with `pid` is still running. Without a cheap way to
+/// ask (Windows) it's assumed gone and the lock file decides.
+fn is_process_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
     }
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else if cfg!(unix) {
+        std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    } else {
+        false
+    }
+}
 
-    async fn add_torrent_via_http_full(
-        &self,
-        magnet_or_url: &str,
-    ) -> Result<TorrentInfo, StreamError> {
-        debug!("adding torrent via HTTP API");
+/// Session directory with no running session behind it
+#[derive(Debug, Clone)]
+pub struct StaleSession {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: std::time::SystemTime,
+}
 
-        let url = format!("http://{}/torrents", self.http_addr);
-        // Add overwrite=true to allow resuming/replacing existing torrents
-        let response = timeout(
-            Duration::from_secs(30),
-            self.http_client
-                .post(&url)
-                .query(&[("overwrite", "true")])
-                .body(magnet_or_url.to_string())
-                .send(),
-        )
-        .await
-        .map_err(|_| StreamError::TorrentError("timeout posting to HTTP API".to_string()))?
-        .map_err(|e| StreamError::TorrentError(format!("HTTP request failed: {}", e)))?;
+/// Total size of the files under `path`
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
 
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
+/// Size of everything under the temp dir
+pub async fn cache_size(temp_base: &Path) -> u64 {
+    let temp_base = temp_base.to_path_buf();
+    tokio::task::spawn_blocking(move || dir_size(&temp_base))
+        .await
+        .unwrap_or(0)
+}
 
-        if !status.is_success() {
-            return Err(StreamError::TorrentError(format!(
-                "HTTP {} - {}",
-                status, body
-            )));
-        }
+/// Free space under this is worth a warning before streaming into the temp dir
+pub const LOW_FREE_SPACE: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Space left on the filesystem `temp_base` is (or would be created) on,
+/// None if it can't be read
+pub async fn free_space(temp_base: &Path) -> Option<u64> {
+    let temp_base = temp_base.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let existing = temp_base.ancestors().find(|dir| dir.exists())?;
+        fs4::available_space(existing).ok()
+    })
+    .await
+    .ok()
+    .flatten()
+}
 
-        debug!(response = %&body[..body.len().min(100)], "HTTP API response");
+/// Create `temp_base` if needed and make sure files can be written in it
+pub fn check_writable(temp_base: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(temp_base)?;
+    let probe = temp_base.join(format!(".ferristream-write-test-{}", std::process::id()));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
 
-        // Parse response to get torrent ID
-        let json: serde_json::Value = serde_json::from_str(&body)
-            .map_err(|e| StreamError::TorrentError(format!("invalid JSON: {}", e)))?;
+/// A session is alive while the HTTP API port in its lock file still answers
+async fn is_session_alive(dir: &Path) -> bool {
+    let Some(port) = tokio::fs::read_to_string(dir.join(SESSION_LOCK_FILE))
+        .await
+        .ok()
+        .and_then(|port| port.trim().parse::<u16>().ok())
+    else {
+        return false;
+    };
+    matches!(
+        timeout(
+            Duration::from_millis(500),
+            tokio::net::TcpStream::connect(("127.0.0.1", port)),
+        )
+        .await,
+        Ok(Ok(_))
+    )
+}
 
-        let id = json
-            .get("id")
-            .and_then(|v| v.as_u64())
-            .map(|id| id as usize)
-            .ok_or_else(|| StreamError::TorrentError("no id in response".to_string()))?;
+/// Whether `dir` is a session directory, named `<id>-<pid>` or holding a lock
+/// file. Anything else in the temp dir isn't ours to remove.
+fn is_session_dir(dir: &Path) -> bool {
+    let named = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(parse_session_dir_name)
+        .is_some_and(|(_, pid)| pid.is_some());
+    named || dir.join(SESSION_LOCK_FILE).is_file()
+}
 
-        info!(id, "torrent added, waiting for metadata");
+/// Session directories under `temp_base` left behind by runs before `run_started`
+/// that didn't clean up, oldest first, plus the size of everything in `temp_base`.
+/// Only directories [`is_session_dir`] recognizes are ever reported.
+pub async fn find_stale_sessions(
+    temp_base: &Path,
+    run_started: std::time::SystemTime,
+) -> (Vec<StaleSession>, u64) {
+    let Ok(mut entries) = tokio::fs::read_dir(temp_base).await else {
+        return (Vec::new(), 0);
+    };
+    let mut stale = Vec::new();
+    let mut total = 0;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let Ok(meta) = entry.metadata().await else {
+            continue;
+        };
+        if !meta.is_dir() {
+            total += meta.len();
+            continue;
+        }
+        let size = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || dir_size(&path))
+                .await
+                .unwrap_or(0)
+        };
+        total += size;
+        if !is_session_dir(&path) {
+            continue;
+        }
 
-        // Poll for torrent details until we have metadata
-        let details_url = format!("http://{}/torrents/{}", self.http_addr, id);
-        let start = std::time::Instant::now();
-        let timeout_duration = Duration::from_secs(120);
+        let modified = meta.modified().unwrap_or(std::time::UNIX_EPOCH);
+        let owner = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_session_dir_name);
+        let from_earlier_run = match owner {
+            Some((_, Some(pid))) => !is_process_alive(pid),
+            // Without a pid only the age tells
+            _ => modified < run_started,
+        };
+        if from_earlier_run && !is_session_alive(&path).await {
+            stale.push(StaleSession {
+                path,
+                size,
+                modified,
+            });
+        }
+    }
+    stale.sort_by_key(|s| s.modified);
+    (stale, total)
+}
 
-        loop {
-            if start.elapsed() > timeout_duration {
-                return Err(StreamError::MetadataTimeout);
+/// Remove the session directories left behind by crashed or killed runs,
+/// oldest first, until `temp_base` is below `max_bytes`. Returns how many were
+/// removed and the bytes freed.
+pub async fn cleanup_stale_sessions(
+    temp_base: &Path,
+    run_started: std::time::SystemTime,
+    max_bytes: u64,
+) -> (usize, u64) {
+    let (stale, mut total) = find_stale_sessions(temp_base, run_started).await;
+    let mut removed = 0;
+    let mut freed = 0;
+    for session in stale {
+        if total < max_bytes {
+            break;
+        }
+        match tokio::fs::remove_dir_all(&session.path).await {
+            Ok(()) => {
+                info!(dir = %session.path.display(), size = session.size, "removed stale session");
+                total = total.saturating_sub(session.size);
+                freed += session.size;
+                removed += 1;
+            }
+            Err(e) => {
+                debug!(dir = %session.path.display(), error = %e, "failed to remove stale session")
             }
+        }
+    }
+    (removed, freed)
+}
 
-            tokio::time::sleep(Duration::from_secs(2)).await;
+/// How long cleanup waits for a closing player to exit
+const PLAYER_EXIT_WAIT: Duration = Duration::from_secs(5);
+/// How long cleanup keeps retrying files a player (or the OS) still holds
+const CLEANUP_RETRY_FOR: Duration = Duration::from_secs(10);
+const CLEANUP_MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// What [`StreamingSession::cleanup`] did
+#[derive(Debug, Clone, PartialEq)]
+#[must_use = "a deferred directory has to be recorded for the next launch"]
+pub enum Cleanup {
+    /// Removed our torrents from the daemon, which owns the files
+    Daemon,
+    /// The session directory is gone, bytes freed
+    Freed(u64),
+    /// The directory couldn't be removed, worth another try next launch
+    Deferred(PathBuf),
+}
 
-            let resp = self
-                .http_client
-                .get(&details_url)
-                .send()
-                .await
-                .map_err(|e| StreamError::TorrentError(e.to_string()))?;
+/// Held while a player streams from a session, see [`StreamingSession::player_running`]
+pub struct PlayerGuard {
+    players: Arc<watch::Sender<usize>>,
+}
 
-            if !resp.status().is_success() {
-                continue;
+impl Drop for PlayerGuard {
+    fn drop(&mut self) {
+        self.players
+            .send_modify(|count| *count = count.saturating_sub(1));
+    }
+}
+
+/// Remove `dir`, retrying with backoff for up to `retry_for` while files in
+/// it can't be deleted yet. Returns the bytes freed once it's really gone.
+pub async fn remove_dir_with_retry(dir: &Path, retry_for: Duration) -> std::io::Result<u64> {
+    let size = {
+        let dir = dir.to_path_buf();
+        tokio::task::spawn_blocking(move || dir_size(&dir))
+            .await
+            .unwrap_or(0)
+    };
+    let deadline = tokio::time::Instant::now() + retry_for;
+    let mut backoff = Duration::from_millis(100);
+    loop {
+        let error = match tokio::fs::remove_dir_all(dir).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => e,
+            // Some filesystems report success while a deleted file is still open
+            _ if tokio::fs::try_exists(dir).await.unwrap_or(true) => {
+                std::io::Error::other("directory still exists after removal")
+            }
+            _ => return Ok(size),
+        };
+        if tokio::time::Instant::now() + backoff > deadline {
+            return Err(error);
+        }
+        debug!(dir = %dir.display(), error = %error, retry_in = ?backoff, "temp dir not removed yet");
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(CLEANUP_MAX_BACKOFF);
+    }
+}
+
+/// Session directories whose cleanup failed, for the next launch to retry
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PendingCleanup {
+    dirs: Vec<PathBuf>,
+}
+
+impl PendingCleanup {
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the list, removing the file once nothing is pending
+    pub fn save(&self, path: &Path) {
+        let result = if self.dirs.is_empty() {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            }
+        } else {
+            serde_json::to_string_pretty(self)
+                .map_err(std::io::Error::other)
+                .and_then(|json| std::fs::write(path, json))
+        };
+        if let Err(e) = result {
+            warn!(path = %path.display(), error = %e, "failed to save pending cleanup list");
+        }
+    }
+
+    /// Add `dir` to the list kept at `path`
+    pub fn defer(path: &Path, dir: &Path) {
+        let mut pending = Self::load(path);
+        if !pending.dirs.iter().any(|d| d == dir) {
+            pending.dirs.push(dir.to_path_buf());
+        }
+        pending.save(path);
+    }
+
+    /// Try the listed directories again, keeping the ones that still fail.
+    /// Returns how many were removed and the bytes freed.
+    pub async fn retry(path: &Path) -> (usize, u64) {
+        let pending = Self::load(path);
+        let mut failed = Vec::new();
+        let mut removed = 0;
+        let mut freed = 0;
+        for dir in pending.dirs {
+            match remove_dir_with_retry(&dir, Duration::ZERO).await {
+                Ok(size) => {
+                    info!(dir = %dir.display(), size, "removed deferred session");
+                    removed += 1;
+                    freed += size;
+                }
+                Err(e) => {
+                    debug!(dir = %dir.display(), error = %e, "deferred session still can't be removed");
+                    failed.push(dir);
+                }
+            }
+        }
+        Self { dirs: failed }.save(path);
+        (removed, freed)
+    }
+}
+
+/// Resolutions from lowest to highest, with their common aliases
+const RESOLUTIONS: [&[&str]; 5] = [
+    &["480p", "sd"],
+    &["576p"],
+    &["720p"],
+    &["1080p"],
+    &["2160p", "4k", "uhd"],
+];
+
+/// Quality score for a release with the preferred quality token
+const QUALITY_MATCH: u32 = 100;
+
+fn resolution_rank(words: &[String]) -> Option<usize> {
+    RESOLUTIONS
+        .iter()
+        .position(|aliases| words.iter().any(|w| aliases.contains(&w.as_str())))
+}
+
+/// Bitrate of a lean but complete encode at each of the `RESOLUTIONS`, in
+/// bits per second
+const LEAN_BITRATES: [u64; RESOLUTIONS.len()] = [500_000, 600_000, 800_000, 1_500_000, 6_000_000];
+/// How far below runtime * bitrate a file may be before it's suspicious
+const SIZE_TOLERANCE: f64 = 0.2;
+
+/// Check a video file's size against the runtime TMDB gives for it. A file
+/// much smaller than a lean encode of that runtime is likely a sample, cut
+/// short or a cam rip, flagged as [`StreamError::SuspiciousFile`].
+pub fn check_file_size(name: &str, size: u64, runtime_minutes: u32) -> Result<(), StreamError> {
+    let bitrate =
+        resolution_rank(&title_words(name)).map_or(LEAN_BITRATES[0], |rank| LEAN_BITRATES[rank]);
+    let expected = u64::from(runtime_minutes) * 60 * bitrate / 8;
+    if (size as f64) < expected as f64 * (1.0 - SIZE_TOLERANCE) {
+        Err(StreamError::SuspiciousFile {
+            size_mb: size / 1_000_000,
+            expected_mb: expected / 1_000_000,
+            runtime_minutes,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// What a torrent search is looking for - used to validate raced torrents
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchContext {
+    pub title: String,
+    pub year: Option<u16>,
+    /// Season number, None for absolutely numbered (anime) episodes
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+    /// "movie", "tv" or "anime" - None for free-text searches
+    pub media_type: Option<String>,
+    /// TMDB id of the movie or show, for indexers that search by id
+    pub tmdb_id: Option<u64>,
+    /// IMDB id of a movie ("tt1160419"), for indexers that take it but not TMDB ids
+    pub imdb_id: Option<String>,
+}
+
+impl SearchContext {
+    /// Context for a free-text search, picking up an SxxExx tag if the query has one
+    pub fn from_query(query: &str) -> Self {
+        let (season, episode) = match parse_episode_tags(query).first() {
+            Some(&(season, first, _)) => (Some(season), Some(first)),
+            None => (None, None),
+        };
+        Self {
+            title: query.to_string(),
+            season,
+            episode,
+            ..Default::default()
+        }
+    }
+}
+
+/// Validation criteria for racing torrents
+#[derive(Debug, Clone, Default)]
+pub struct TorrentValidation {
+    /// Title keywords - at least one must be present in filename
+    pub title_keywords: Vec<String>,
+    /// Expected year - if set, must be present in filename
+    pub year: Option<u16>,
+    /// Expected season - if set with an episode, the SxxExx tag must match
+    pub season: Option<u32>,
+    /// Expected episode - if set, the filename must be (or contain) this episode
+    pub episode: Option<u32>,
+    /// Preferred quality token (e.g. "1080p") - torrents without it only win
+    /// when nothing better connects
+    pub quality_preference: Option<String>,
+}
+
+impl TorrentValidation {
+    pub fn new(title_keywords: Vec<String>, year: Option<u16>) -> Self {
+        Self {
+            title_keywords,
+            year,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_context(context: &SearchContext) -> Self {
+        Self {
+            title_keywords: Self::extract_keywords(&context.title),
+            // Episode files rarely carry the show's year
+            year: context.year.filter(|_| context.episode.is_none()),
+            season: context.season,
+            episode: context.episode,
+            quality_preference: None,
+        }
+    }
+
+    /// True if there is nothing to validate against
+    pub fn is_empty(&self) -> bool {
+        self.title_keywords.is_empty()
+            && self.year.is_none()
+            && self.episode.is_none()
+            && self.quality_preference.is_none()
+    }
+
+    /// Score a release name against the preferred quality: QUALITY_MATCH when it
+    /// has the token, less the further its resolution is from the preferred one,
+    /// and 0 when it can't be compared
+    pub fn quality_score(&self, name: &str) -> u32 {
+        let Some(preferred) = self.quality_preference.as_deref() else {
+            return QUALITY_MATCH;
+        };
+        let preferred = preferred.to_lowercase();
+        let words = title_words(name);
+        if words.contains(&preferred) {
+            return QUALITY_MATCH;
+        }
+
+        match (
+            resolution_rank(std::slice::from_ref(&preferred)),
+            resolution_rank(&words),
+        ) {
+            (Some(want), Some(got)) => {
+                let distance = want.abs_diff(got) as u32;
+                // Prefer a step up over a step down at the same distance
+                let penalty = distance * 20 + u32::from(got < want) * 10;
+                (QUALITY_MATCH / 2).saturating_sub(penalty).max(1)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Best quality score of the torrent name and its selected file
+    pub fn torrent_quality_score(&self, info: &TorrentInfo) -> u32 {
+        self.quality_score(&info.name)
+            .max(self.quality_score(&info.selected_file.name))
+    }
+
+    /// True if the torrent has the preferred quality (or no quality is preferred)
+    pub fn meets_quality(&self, info: &TorrentInfo) -> bool {
+        self.torrent_quality_score(info) == QUALITY_MATCH
+    }
+
+    /// Check if a filename matches the validation criteria
+    pub fn matches(&self, filename: &str) -> bool {
+        // Check year if specified
+        let year_matches = match self.year {
+            Some(year) => filename.contains(&year.to_string()),
+            None => true,
+        };
+
+        self.title_matches(filename) && year_matches && self.episode_matches(filename)
+    }
+
+    /// Check a raced torrent: its selected file must match, or - when looking for
+    /// an episode - the torrent must be a pack containing that episode
+    pub fn matches_torrent(&self, info: &TorrentInfo) -> bool {
+        self.matches(&info.selected_file.name)
+            || (self.episode.is_some()
+                && self.pack_contains_episode(
+                    &info.name,
+                    info.video_files.iter().map(|f| f.name.as_str()),
+                ))
+    }
+
+    /// Season pack check: title from the torrent name, episode from any of its files
+    pub fn pack_contains_episode<'a>(
+        &self,
+        torrent_name: &str,
+        file_names: impl IntoIterator<Item = &'a str>,
+    ) -> bool {
+        self.title_matches(torrent_name)
+            && file_names
+                .into_iter()
+                .any(|name| self.episode_matches(name))
+    }
+
+    fn title_matches(&self, filename: &str) -> bool {
+        let filename_lower = filename.to_lowercase();
+
+        // At least one keyword must match
+        self.title_keywords.is_empty()
+            || self
+                .title_keywords
+                .iter()
+                .any(|kw| filename_lower.contains(kw))
+    }
+
+    fn episode_matches(&self, filename: &str) -> bool {
+        let Some(episode) = self.episode else {
+            return true;
+        };
+
+        match self.season {
+            // SxxExx / 1x02 tag required - season packs without one are rejected
+            Some(season) => parse_episode_tags(filename)
+                .iter()
+                .any(|&(s, first, last)| s == season && (first..=last).contains(&episode)),
+            // Absolute numbering: "[Group] Show - 148 (1080p).mkv"
+            None => {
+                let re = regex::Regex::new(&format!(
+                    r"(?i)(?:^|[\s._\-\[(#e])0*{}(?:v\d)?(?:[\s._\-\])]|$)",
+                    episode
+                ))
+                .unwrap();
+                re.is_match(filename)
+            }
+        }
+    }
+
+    /// Extract title keywords from a query string
+    pub fn extract_keywords(query: &str) -> Vec<String> {
+        let stop_words = [
+            "the", "a", "an", "and", "or", "of", "in", "on", "at", "to", "for",
+        ];
+        query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.len() >= 3)
+            .map(|word| word.to_lowercase())
+            .filter(|word| !stop_words.contains(&word.as_str()))
+            // Filter out years from title keywords (they're handled separately)
+            .filter(|word| word.parse::<u16>().is_err() || word.len() != 4)
+            .collect()
+    }
+}
+
+/// Weights of the smart score components, summing to 100
+const SEEDERS_WEIGHT: f64 = 30.0;
+const QUALITY_WEIGHT: f64 = 20.0;
+const SIZE_WEIGHT: f64 = 10.0;
+const TITLE_WEIGHT: f64 = 40.0;
+/// Subtracted from results with a blocked term, so they sink even when shown
+const BLOCKED_PENALTY: f64 = 40.0;
+
+/// Composite 0-100 score of search results, used by the Smart sort and auto-race
+#[derive(Debug, Clone, Default)]
+pub struct ResultScorer {
+    validation: TorrentValidation,
+    /// "movie", "tv" or "anime", picks the plausible size range
+    media_type: Option<String>,
+    /// Lowercase terms that penalize a result
+    blocked_terms: Vec<String>,
+}
+
+impl ResultScorer {
+    pub fn new(
+        validation: TorrentValidation,
+        media_type: Option<String>,
+        blocked_terms: Vec<String>,
+    ) -> Self {
+        Self {
+            validation,
+            media_type,
+            blocked_terms,
+        }
+    }
+
+    /// Score every result, seeders are normalized against the best-seeded one
+    pub fn score_all(&self, results: &[TorrentResult]) -> Vec<u8> {
+        let max_seeders = results.iter().filter_map(|r| r.seeders).max().unwrap_or(0);
+        results.iter().map(|r| self.score(r, max_seeders)).collect()
+    }
+
+    fn score(&self, result: &TorrentResult, max_seeders: u32) -> u8 {
+        // Log scale so 500 vs 1000 seeders matters less than 5 vs 50
+        let seeders = match result.seeders {
+            Some(seeders) if max_seeders > 0 => {
+                (seeders as f64).ln_1p() / (max_seeders as f64).ln_1p()
+            }
+            _ => 0.0,
+        };
+        let quality = self.validation.quality_score(&result.title) as f64 / QUALITY_MATCH as f64;
+
+        let words = title_words(&result.title);
+        let blocked = self.blocked_terms.iter().any(|term| words.contains(term));
+
+        let score = SEEDERS_WEIGHT * seeders
+            + QUALITY_WEIGHT * quality
+            + SIZE_WEIGHT * self.size_fit(result.size)
+            + TITLE_WEIGHT * self.title_fit(&result.title)
+            - if blocked { BLOCKED_PENALTY } else { 0.0 };
+        score.clamp(0.0, 100.0).round() as u8
+    }
+
+    /// 1.0 inside the plausible size range, falling off with the distance to it
+    fn size_fit(&self, size: Option<u64>) -> f64 {
+        const MB: u64 = 1024 * 1024;
+        const GB: u64 = 1024 * MB;
+
+        let Some(size) = size.filter(|s| *s > 0) else {
+            return 0.5;
+        };
+        let (min, max) = if self.validation.episode.is_some() {
+            (100 * MB, 8 * GB)
+        } else {
+            match self.media_type.as_deref() {
+                Some("movie") => (700 * MB, 40 * GB),
+                // Whole shows come as season packs
+                _ => (100 * MB, 100 * GB),
+            }
+        };
+
+        if size < min {
+            size as f64 / min as f64
+        } else if size > max {
+            max as f64 / size as f64
+        } else {
+            1.0
+        }
+    }
+
+    /// 1.0 for a full title/year/episode match, partial credit for the title alone
+    fn title_fit(&self, title: &str) -> f64 {
+        if self.validation.matches(title) {
+            1.0
+        } else if self.validation.title_matches(title) {
+            0.25
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Client for fetching .torrent files - redirects are followed manually
+/// so magnet redirects can be caught
+fn torrent_fetch_client() -> Client {
+    crate::http::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+/// Default for the longest wait on a torrent's metadata
+pub const DEFAULT_METADATA_TIMEOUT: Duration = Duration::from_secs(120);
+/// Magnets the DHT has found no peers for in this long are given up on
+pub const NO_PEERS_TIMEOUT: Duration = Duration::from_secs(30);
+/// DHT nodes we need to know before finding no peers means anything, a DHT
+/// still bootstrapping finds nothing either
+const DHT_READY_NODES: usize = 32;
+/// Time past the metadata wait the add request gets, so giving up on the
+/// wait is what ends it rather than the HTTP client's own timeout
+const ADD_REQUEST_SLACK: Duration = Duration::from_secs(10);
+const METADATA_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Longest a health probe spends on a torrent, metadata included
+pub const PROBE_BUDGET: Duration = Duration::from_secs(15);
+
+/// What a health probe found out about a torrent, see [`StreamingSession::probe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Probe {
+    /// Peers discovered while its metadata was fetched
+    pub peers: usize,
+    /// The budget ran out before the metadata came in, so no peers were counted
+    pub metadata_pending: bool,
+}
+
+/// Where adding a torrent is at, in the order it gets there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AddPhase {
+    /// Waiting on rqbit to fetch the magnet's metadata from the swarm
+    ResolvingMagnet,
+    /// Peers the DHT has found for the magnet so far
+    FindingPeers { found: usize },
+    /// Metadata is in, rqbit is setting up the files
+    Initializing,
+    /// The Usenet downloader is fetching an NZB, it plays once complete
+    Downloading { percent: u8 },
+}
+
+impl std::fmt::Display for AddPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddPhase::ResolvingMagnet => write!(f, "Resolving magnet"),
+            AddPhase::FindingPeers { found } => {
+                write!(f, "Connecting to peers ({} found)", found)
+            }
+            AddPhase::Initializing => write!(f, "Loading torrent"),
+            AddPhase::Downloading { percent } => {
+                write!(f, "Downloading from Usenet ({}%)", percent)
+            }
+        }
+    }
+}
+
+/// The wait for one torrent's metadata. It ends early when the DHT is
+/// bootstrapped but turns up no peers for [`NO_PEERS_TIMEOUT`], and lasts up
+/// to `max` while there are peers to get the metadata from.
+pub struct MetadataWait {
+    max: Duration,
+    progress: Option<mpsc::UnboundedSender<AddPhase>>,
+    /// Adding a magnet, which rqbit has to resolve first
+    magnet: bool,
+    /// Our DHT and the magnet's info hash, to count its peers
+    peers: Option<(Dht, Id20)>,
+}
+
+impl MetadataWait {
+    fn report(&self, phase: AddPhase) {
+        if let Some(progress) = &self.progress {
+            let _ = progress.send(phase);
+        }
+    }
+
+    /// Run `add` (rqbit resolving the torrent) until it's done or the wait is over
+    async fn watch<F: std::future::Future>(
+        &mut self,
+        started: std::time::Instant,
+        add: F,
+    ) -> Result<F::Output, StreamError> {
+        tokio::pin!(add);
+        if self.magnet {
+            self.report(AddPhase::ResolvingMagnet);
+        }
+
+        let dht = self.peers.as_ref().map(|(dht, _)| dht.clone());
+        let mut peer_stream = self
+            .peers
+            .take()
+            .map(|(dht, info_hash)| dht.get_peers(info_hash, None));
+        let mut found = HashSet::new();
+        let mut reported = 0;
+        let mut empty_since: Option<std::time::Instant> = None;
+        let mut tick = tokio::time::interval(METADATA_POLL_INTERVAL);
+
+        loop {
+            // Biased towards `add`, so finishing wins over giving up
+            let woken = {
+                let peer = std::pin::pin!(async {
+                    match peer_stream.as_mut() {
+                        Some(stream) => futures::StreamExt::next(stream).await,
+                        None => std::future::pending().await,
+                    }
+                });
+                let tick = std::pin::pin!(tick.tick());
+                match select(add.as_mut(), select(peer, tick)).await {
+                    Either::Left((output, _)) => return Ok(output),
+                    Either::Right((Either::Left((peer, _)), _)) => Some(peer),
+                    Either::Right((Either::Right(_), _)) => None,
+                }
+            };
+            match woken {
+                Some(Some(peer)) => {
+                    found.insert(peer);
+                }
+                Some(None) => peer_stream = None,
+                None => {
+                    if let Some(dht) = &dht {
+                        if !found.is_empty() {
+                            empty_since = None;
+                        } else if dht.stats().routing_table_size >= DHT_READY_NODES {
+                            empty_since.get_or_insert_with(std::time::Instant::now);
+                        }
+                    }
+                    if found.len() != reported {
+                        reported = found.len();
+                        self.report(AddPhase::FindingPeers { found: reported });
+                    }
+                    if let Some(e) = give_up_waiting(
+                        started.elapsed(),
+                        empty_since.map(|since| since.elapsed()),
+                        self.max,
+                    ) {
+                        info!(error = %e, peers = found.len(), "giving up on torrent");
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether to stop waiting for metadata after `waited`, with the DHT having
+/// found no peers for `no_peers_for` (None when it did, or can't tell)
+fn give_up_waiting(
+    waited: Duration,
+    no_peers_for: Option<Duration>,
+    max: Duration,
+) -> Option<StreamError> {
+    if no_peers_for.is_some_and(|empty| empty >= NO_PEERS_TIMEOUT) {
+        Some(StreamError::NoPeers)
+    } else if waited >= max {
+        Some(StreamError::MetadataTimeout)
+    } else {
+        None
+    }
+}
+
+/// rqbit's HTTP API - either the embedded session's or an external daemon's
+#[derive(Debug, Clone)]
+pub struct RemoteRqbitBackend {
+    base_url: String,
+    client: Client,
+}
+
+impl RemoteRqbitBackend {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: crate::http::client(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn stream_url(&self, torrent_id: usize, file_idx: usize) -> String {
+        format!(
+            "{}/torrents/{}/stream/{}",
+            self.base_url, torrent_id, file_idx
+        )
+    }
+
+    /// Version reported by the daemon, doubles as a reachability check
+    pub async fn version(&self) -> Result<String, StreamError> {
+        let json = self.get_json(&self.base_url).await?;
+        Ok(json
+            .get("version")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown")
+            .to_string())
+    }
+
+    /// IDs of all torrents the daemon manages
+    pub async fn torrent_ids(&self) -> Result<Vec<usize>, StreamError> {
+        let json = self
+            .get_json(&format!("{}/torrents", self.base_url))
+            .await?;
+        Ok(json
+            .get("torrents")
+            .and_then(|t| t.as_array())
+            .map(|torrents| {
+                torrents
+                    .iter()
+                    .filter_map(|t| t.get("id")?.as_u64())
+                    .map(|id| id as usize)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Remove a torrent and its downloaded files
+    pub async fn delete(&self, torrent_id: usize) -> Result<(), StreamError> {
+        self.torrent_action(torrent_id, "delete").await
+    }
+
+    async fn torrent_action(&self, torrent_id: usize, action: &str) -> Result<(), StreamError> {
+        let url = format!("{}/torrents/{}/{}", self.base_url, torrent_id, action);
+        let response = self
+            .client
+            .post(&url)
+            .send()
+            .await
+            .map_err(|e| StreamError::TorrentError(format!("HTTP request failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(StreamError::TorrentError(format!(
+                "HTTP {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn get_json(&self, url: &str) -> Result<serde_json::Value, StreamError> {
+        let response = timeout(Duration::from_secs(10), self.client.get(url).send())
+            .await
+            .map_err(|_| StreamError::SessionError(format!("timeout connecting to {}", url)))?
+            .map_err(|e| StreamError::SessionError(format!("rqbit not reachable: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(StreamError::SessionError(format!(
+                "rqbit returned HTTP {}",
+                response.status()
+            )));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| StreamError::SessionError(format!("invalid JSON from rqbit: {}", e)))
+    }
+
+    /// Fetch a magnet's metadata without adding the torrent (rqbit's list-only
+    /// mode), returns how many peers turned up on the way. `max` bounds the
+    /// request, rqbit only answers once it has the metadata.
+    pub async fn seen_peers(&self, magnet: String, max: Duration) -> Result<usize, StreamError> {
+        let url = format!("{}/torrents", self.base_url);
+        let response = self
+            .client
+            .post(&url)
+            .query(&[("list_only", "true")])
+            .timeout(max + ADD_REQUEST_SLACK)
+            .body(magnet)
+            .send()
+            .await
+            .map_err(|e| StreamError::TorrentError(format!("HTTP request failed: {}", e)))?;
+
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(StreamError::TorrentError(format!(
+                "HTTP {} - {}",
+                status, body
+            )));
+        }
+        let json: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|e| StreamError::TorrentError(format!("invalid JSON: {}", e)))?;
+        Ok(json
+            .get("seen_peers")
+            .and_then(|peers| peers.as_array())
+            .map_or(0, Vec::len))
+    }
+
+    /// Add a magnet link, URL or .torrent file and wait for its metadata
+    pub async fn add(
+        &self,
+        torrent: impl Into<reqwest::Body>,
+        mode: MediaMode,
+        mut wait: MetadataWait,
+    ) -> Result<TorrentInfo, StreamError> {
+        debug!("adding torrent via HTTP API");
+
+        let url = format!("{}/torrents", self.base_url);
+        let started = std::time::Instant::now();
+        // rqbit answers once it has resolved a magnet's metadata, which can
+        // take longer than network.timeout_secs allows a request.
+        // Add overwrite=true to allow resuming/replacing existing torrents
+        let response = wait
+            .watch(
+                started,
+                self.client
+                    .post(&url)
+                    .query(&[("overwrite", "true")])
+                    .timeout(wait.max + ADD_REQUEST_SLACK)
+                    .body(torrent)
+                    .send(),
+            )
+            .await?
+            .map_err(|e| StreamError::TorrentError(format!("HTTP request failed: {}", e)))?;
+
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+
+        if !status.is_success() {
+            return Err(StreamError::TorrentError(format!(
+                "HTTP {} - {}",
+                status, body
+            )));
+        }
+
+        debug!(response = %&body[..body.len().min(100)], "HTTP API response");
+
+        // Parse response to get torrent ID
+        let json: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|e| StreamError::TorrentError(format!("invalid JSON: {}", e)))?;
+
+        let id = json
+            .get("id")
+            .and_then(|v| v.as_u64())
+            .map(|id| id as usize)
+            .ok_or_else(|| StreamError::TorrentError("no id in response".to_string()))?;
+
+        info!(id, "torrent added, waiting for metadata");
+        wait.report(AddPhase::Initializing);
+
+        // Poll for torrent details until we have metadata
+        let details_url = format!("{}/torrents/{}", self.base_url, id);
+
+        loop {
+            if started.elapsed() > wait.max {
+                return Err(StreamError::MetadataTimeout);
+            }
+
+            let resp = self
+                .client
+                .get(&details_url)
+                .send()
+                .await
+                .map_err(|e| StreamError::TorrentError(e.to_string()))?;
+
+            if !resp.status().is_success() {
+                tokio::time::sleep(METADATA_POLL_INTERVAL).await;
+                continue;
             }
 
             let details: serde_json::Value = resp
@@ -441,38 +1866,18 @@ impl StreamingSession {
             {
                 info!(files = files.len(), "metadata received");
 
-                // Find all video files
-                let video_files: Vec<VideoFile> = files
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(idx, f)| {
+                let playable = playable_files(
+                    mode,
+                    files.iter().enumerate().filter_map(|(idx, f)| {
                         let name = f.get("name").and_then(|n| n.as_str())?;
-                        let name_lower = name.to_lowercase();
-                        if VIDEO_EXTENSIONS.iter().any(|ext| name_lower.ends_with(ext)) {
-                            let size = f.get("length").and_then(|l| l.as_u64()).unwrap_or(0);
-                            Some(VideoFile {
-                                name: name.to_string(),
-                                file_idx: idx,
-                                size,
-                                stream_url: format!(
-                                    "http://{}/torrents/{}/stream/{}",
-                                    self.http_addr, id, idx
-                                ),
-                            })
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
-
-                if video_files.is_empty() {
-                    return Err(StreamError::NoVideoFiles);
-                }
-
-                info!(video_files = video_files.len(), "found video files");
-
-                // Select the largest video file by default (usually the main content)
-                let selected_file = video_files.iter().max_by_key(|f| f.size).cloned().unwrap();
+                        Some(VideoFile {
+                            name: name.to_string(),
+                            file_idx: idx,
+                            size: f.get("length").and_then(|l| l.as_u64()).unwrap_or(0),
+                            stream_url: self.stream_url(id, idx),
+                        })
+                    }),
+                )?;
 
                 let torrent_name = details
                     .get("name")
@@ -496,10 +1901,7 @@ impl StreamingSession {
                                 name: name.to_string(),
                                 file_idx: idx,
                                 language,
-                                stream_url: format!(
-                                    "http://{}/torrents/{}/stream/{}",
-                                    self.http_addr, id, idx
-                                ),
+                                stream_url: self.stream_url(id, idx),
                             })
                         } else {
                             None
@@ -509,19 +1911,80 @@ impl StreamingSession {
 
                 info!(subtitles = subtitle_files.len(), "found subtitle files");
 
-                return Ok(TorrentInfo {
-                    id,
-                    name: torrent_name,
-                    video_files,
-                    selected_file,
-                    subtitle_files,
-                });
+                return Ok(TorrentInfo {
+                    id,
+                    name: torrent_name,
+                    video_files: playable.files,
+                    extra_files: playable.extras,
+                    selected_file: playable.selected,
+                    subtitle_files,
+                });
+            }
+
+            debug!(
+                elapsed_secs = started.elapsed().as_secs(),
+                "still waiting for metadata"
+            );
+            tokio::time::sleep(METADATA_POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl StreamingSession {
+    /// Add a torrent by URL (magnet or .torrent file URL)
+    pub fn add_torrent(
+        &self,
+        url: &str,
+    ) -> std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<TorrentInfo, StreamError>> + Send + '_>,
+    > {
+        let url = url.to_string();
+        Box::pin(async move {
+            let info = self.add_torrent_url(url.clone()).await?;
+            self.sources.lock().unwrap().insert(info.id, url);
+            if !self.existing_ids.contains(&info.id) {
+                self.claim(info.id);
+            }
+            Ok(info)
+        })
+    }
+
+    async fn add_torrent_url(&self, url: String) -> Result<TorrentInfo, StreamError> {
+        // there are three types of urls (magnet/http/local .torrent path).
+        // if it's an http URL fetch the .torrent file first
+        let magnet_url = if url.starts_with("http://") || url.starts_with("https://") {
+            debug!("fetching torrent from URL");
+            match self.fetch_torrent_file(&url).await {
+                Ok(bytes) => {
+                    debug!(bytes = bytes.len(), "got .torrent file");
+                    return self.add_torrent_bytes(bytes).await;
+                }
+                Err(StreamError::MagnetRedirect(magnet)) => {
+                    debug!("prowlarr redirected to magnet link");
+                    magnet
+                }
+                Err(e) => return Err(e),
             }
+        } else if !is_magnet(&url) {
+            return self
+                .add_torrent_bytes(read_local_torrent(&url).await?)
+                .await;
+        } else {
+            url
+        };
 
-            debug!(
-                elapsed_secs = start.elapsed().as_secs(),
-                "still waiting for metadata"
-            );
+        debug!(magnet = %&magnet_url[..magnet_url.len().min(60)], "using magnet link");
+        let wait = self.metadata_wait(Some(&magnet_url));
+        self.api.add(magnet_url, self.media_mode, wait).await
+    }
+
+    async fn add_torrent_bytes(&self, bytes: Vec<u8>) -> Result<TorrentInfo, StreamError> {
+        match self.session {
+            Some(_) => self.add_torrent_inner(AddTorrent::from_bytes(bytes)).await,
+            None => {
+                let wait = self.metadata_wait(None);
+                self.api.add(bytes, self.media_mode, wait).await
+            }
         }
     }
 
@@ -531,7 +1994,10 @@ impl StreamingSession {
     ) -> Result<TorrentInfo, StreamError> {
         debug!("adding torrent to session");
 
-        let add_future = self.session.add_torrent(
+        let session = self.session.as_ref().ok_or_else(|| {
+            StreamError::SessionError("no embedded session to add torrent to".to_string())
+        })?;
+        let add_future = session.add_torrent(
             add_torrent,
             Some(AddTorrentOptions {
                 overwrite: true,
@@ -560,7 +2026,7 @@ impl StreamingSession {
 
         // wait for metadata (this can take a while for magnet links)
         debug!("waiting for metadata from peers");
-        timeout(Duration::from_secs(120), handle.wait_until_initialized())
+        timeout(self.metadata_timeout, handle.wait_until_initialized())
             .await
             .map_err(|_| StreamError::MetadataTimeout)?
             .map_err(|e| StreamError::TorrentError(e.to_string()))?;
@@ -569,42 +2035,22 @@ impl StreamingSession {
 
         let torrent_name = handle.name().unwrap_or_default();
 
-        // Find all video files
-        let http_addr = self.http_addr;
-        let video_files: Vec<VideoFile> = handle
+        let api = &self.api;
+        let files: Vec<VideoFile> = handle
             .with_metadata(|meta| {
                 meta.file_infos
                     .iter()
                     .enumerate()
-                    .filter_map(|(idx, f)| {
-                        let path = f.relative_filename.to_string_lossy();
-                        let path_lower = path.to_lowercase();
-                        if VIDEO_EXTENSIONS.iter().any(|ext| path_lower.ends_with(ext)) {
-                            Some(VideoFile {
-                                name: path.to_string(),
-                                file_idx: idx,
-                                size: f.len,
-                                stream_url: format!(
-                                    "http://{}/torrents/{}/stream/{}",
-                                    http_addr, id, idx
-                                ),
-                            })
-                        } else {
-                            None
-                        }
+                    .map(|(idx, f)| VideoFile {
+                        name: f.relative_filename.to_string_lossy().into_owned(),
+                        file_idx: idx,
+                        size: f.len,
+                        stream_url: api.stream_url(id, idx),
                     })
                     .collect()
             })
             .map_err(|e| StreamError::TorrentError(e.to_string()))?;
-
-        if video_files.is_empty() {
-            return Err(StreamError::NoVideoFiles);
-        }
-
-        info!(video_files = video_files.len(), "found video files");
-
-        // Select the largest video file by default (usually the main content)
-        let selected_file = video_files.iter().max_by_key(|f| f.size).cloned().unwrap();
+        let playable = playable_files(self.media_mode, files)?;
 
         // Find subtitle files
         let subtitle_files: Vec<SubtitleFile> = handle
@@ -624,10 +2070,7 @@ impl StreamingSession {
                                 name: path.to_string(),
                                 file_idx: idx,
                                 language,
-                                stream_url: format!(
-                                    "http://{}/torrents/{}/stream/{}",
-                                    http_addr, id, idx
-                                ),
+                                stream_url: api.stream_url(id, idx),
                             })
                         } else {
                             None
@@ -642,16 +2085,13 @@ impl StreamingSession {
         Ok(TorrentInfo {
             id,
             name: torrent_name,
-            video_files,
-            selected_file,
+            video_files: playable.files,
+            extra_files: playable.extras,
+            selected_file: playable.selected,
             subtitle_files,
         })
     }
 
-    pub fn http_addr(&self) -> SocketAddr {
-        self.http_addr
-    }
-
     /// Prioritize downloading a specific file by making a range request
     /// This triggers librqbit to prioritize pieces for that file
     pub async fn prioritize_file(
@@ -659,10 +2099,10 @@ impl StreamingSession {
         torrent_id: usize,
         file_idx: usize,
     ) -> Result<(), StreamError> {
-        let url = format!(
-            "http://{}/torrents/{}/stream/{}",
-            self.http_addr, torrent_id, file_idx
-        );
+        if self.is_served(torrent_id) {
+            return Ok(());
+        }
+        let url = self.api.stream_url(torrent_id, file_idx);
 
         // Make a small range request to trigger prioritization
         let result = self
@@ -688,70 +2128,192 @@ impl StreamingSession {
         }
     }
 
-    /// Get download stats for a torrent
+    /// Have the Usenet downloader fetch an NZB and serve the largest video
+    /// in it like a torrent's file. It can only play once the download is
+    /// complete, progress is reported as [`AddPhase::Downloading`].
+    pub async fn add_nzb(
+        &self,
+        usenet: &UsenetClient,
+        nzb_url: &str,
+        name: &str,
+        cancel: &tokio_util::sync::CancellationToken,
+    ) -> Result<TorrentInfo, StreamError> {
+        let job_id = usenet.submit(nzb_url, name).await?;
+        let path = usenet
+            .wait_for_completion(&job_id, NZB_POLL_INTERVAL, cancel, |percent| {
+                if let Some(progress) = &self.add_progress {
+                    let percent = percent.clamp(0.0, 100.0) as u8;
+                    let _ = progress.send(AddPhase::Downloading { percent });
+                }
+            })
+            .await?;
+
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !self.media_mode.is_playable(&file_name) {
+            return Err(StreamError::NoVideoFiles(self.media_mode));
+        }
+        let size = tokio::fs::metadata(&path)
+            .await
+            .map_err(UsenetError::from)?
+            .len();
+        let (stream_url, server) = serve_file(path).await?;
+        info!(file = %file_name, url = %stream_url, "serving usenet download");
+
+        let id = {
+            let mut served = self.served.lock().unwrap();
+            let id = SERVED_ID_BASE + served.len();
+            served.insert(id, ServedFile { size, server });
+            id
+        };
+        let playable = playable_files(
+            self.media_mode,
+            [VideoFile {
+                name: file_name,
+                file_idx: 0,
+                size,
+                stream_url,
+            }],
+        )?;
+        Ok(TorrentInfo {
+            id,
+            name: name.to_string(),
+            video_files: playable.files,
+            extra_files: playable.extras,
+            selected_file: playable.selected,
+            subtitle_files: Vec::new(),
+        })
+    }
+
+    /// Whether `torrent_id` is a served Usenet download rather than a torrent
+    pub fn is_served(&self, torrent_id: usize) -> bool {
+        self.served.lock().unwrap().contains_key(&torrent_id)
+    }
+
+    /// Count the peers of a torrent without adding it: its metadata is
+    /// fetched in list-only mode, nothing is downloaded and there's nothing
+    /// to clean up. Spends at most `budget`, running out only means the
+    /// metadata is still pending, adding the torrent gets the full wait.
+    pub async fn probe(&self, url: &str, budget: Duration) -> Result<Probe, StreamError> {
+        let started = std::time::Instant::now();
+        let count = async {
+            let magnet = self.magnet_for(url).await?;
+            let wait = budget.saturating_sub(started.elapsed());
+            self.api.seen_peers(magnet, wait).await
+        };
+        let probe = match timeout(budget, count).await {
+            Ok(peers) => Probe {
+                peers: peers?,
+                metadata_pending: false,
+            },
+            Err(_) => Probe {
+                peers: 0,
+                metadata_pending: true,
+            },
+        };
+        info!(
+            peers = probe.peers,
+            pending = probe.metadata_pending,
+            "probed torrent"
+        );
+        Ok(probe)
+    }
+
+    /// Magnet link of a magnet, .torrent URL or path
+    async fn magnet_for(&self, url: &str) -> Result<String, StreamError> {
+        let bytes = if url.starts_with("http://") || url.starts_with("https://") {
+            match self.fetch_torrent_file(url).await {
+                Ok(bytes) => bytes,
+                Err(StreamError::MagnetRedirect(magnet)) => return Ok(magnet),
+                Err(e) => return Err(e),
+            }
+        } else if is_magnet(url) {
+            return Ok(url.to_string());
+        } else {
+            read_local_torrent(url).await?
+        };
+        torrent_magnet(&bytes)
+    }
+
+    /// Get download stats for a torrent. Served Usenet downloads are complete.
     pub async fn get_stats(&self, torrent_id: usize) -> Option<TorrentStats> {
-        let url = format!("http://{}/torrents/{}/stats/v1", self.http_addr, torrent_id);
+        if let Some(served) = self.served.lock().unwrap().get(&torrent_id) {
+            return Some(TorrentStats {
+                downloaded_bytes: served.size,
+                total_bytes: served.size,
+                ..TorrentStats::default()
+            });
+        }
+        let url = format!("{}/torrents/{}/stats/v1", self.api.base_url, torrent_id);
 
         let resp = self.http_client.get(&url).send().await.ok()?;
         if !resp.status().is_success() {
             return None;
         }
 
-        let json: serde_json::Value = resp.json().await.ok()?;
-
-        // Log the raw response once to understand the structure
-        debug!(stats_json = %json, "raw stats response");
-
-        // Parse the stats from librqbit response
-        // The structure varies - try different paths
-        let live = json.get("live");
+        // The API answered, so an unknown layout doesn't mean the backend is gone
+        let body = resp.text().await.ok()?;
+        Some(parse_stats(&body).unwrap_or_else(|| {
+            debug!(stats_json = %body, "unrecognized stats response");
+            TorrentStats::default()
+        }))
+    }
 
-        let downloaded_bytes = live
-            .and_then(|l| l.get("downloaded_bytes"))
-            .and_then(|v| v.as_u64())
-            .or_else(|| json.get("progress_bytes").and_then(|v| v.as_u64()))
-            .unwrap_or(0);
+    /// Which pieces of a file are downloaded, None until the torrent has
+    /// metadata or if the API doesn't answer
+    pub async fn get_piece_map(&self, torrent_id: usize, file_idx: usize) -> Option<PieceMap> {
+        if let Some(served) = self.served.lock().unwrap().get(&torrent_id) {
+            return PieceMap::for_file(&[true], &[served.size], file_idx);
+        }
+        let lengths = self.torrent_file_lengths(torrent_id).await?;
+        let url = format!("{}/torrents/{}/haves", self.api.base_url, torrent_id);
+        let resp = self.http_client.get(&url).send().await.ok()?;
+        if !resp.status().is_success() {
+            return None;
+        }
+        let haves = parse_haves(&resp.text().await.ok()?)?;
+        PieceMap::for_file(&haves, &lengths, file_idx)
+    }
 
-        let total_bytes = json
-            .get("total_bytes")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0);
-
-        let download_speed = live
-            .and_then(|l| l.get("download_speed"))
-            .and_then(|ds| {
-                // Could be {"mbps": 1.5} or {"human_readable": "1.5 MB/s"} or just a number
-                ds.get("mbps")
-                    .and_then(|v| v.as_f64())
-                    .map(|mbps| (mbps * 1_000_000.0 / 8.0) as u64)
-                    .or_else(|| ds.as_f64().map(|v| v as u64))
-            })
-            .unwrap_or(0);
-
-        let upload_speed = live
-            .and_then(|l| l.get("upload_speed"))
-            .and_then(|us| {
-                us.get("mbps")
-                    .and_then(|v| v.as_f64())
-                    .map(|mbps| (mbps * 1_000_000.0 / 8.0) as u64)
-                    .or_else(|| us.as_f64().map(|v| v as u64))
-            })
-            .unwrap_or(0);
+    /// Lengths of all files in a torrent, fetched once
+    async fn torrent_file_lengths(&self, torrent_id: usize) -> Option<Vec<u64>> {
+        if let Some(lengths) = self.file_lengths.lock().unwrap().get(&torrent_id) {
+            return Some(lengths.clone());
+        }
+        let json = self
+            .api
+            .get_json(&format!("{}/torrents/{}", self.api.base_url, torrent_id))
+            .await
+            .ok()?;
+        let lengths: Vec<u64> = json
+            .get("files")?
+            .as_array()?
+            .iter()
+            .map(|f| f.get("length").and_then(|l| l.as_u64()).unwrap_or(0))
+            .collect();
+        if lengths.is_empty() {
+            return None;
+        }
+        self.file_lengths
+            .lock()
+            .unwrap()
+            .insert(torrent_id, lengths.clone());
+        Some(lengths)
+    }
 
-        let peers_connected = live
-            .and_then(|l| l.get("snapshot"))
-            .and_then(|s| s.get("peers"))
-            .and_then(|v| v.as_u64())
-            .or_else(|| json.get("peers").and_then(|v| v.as_u64()))
-            .unwrap_or(0) as u32;
-
-        Some(TorrentStats {
-            downloaded_bytes,
-            total_bytes,
-            download_speed,
-            upload_speed,
-            peers_connected,
-        })
+    /// Peers of a torrent, most downloaded from first
+    pub async fn get_peers(&self, torrent_id: usize) -> Result<Vec<PeerInfo>, StreamError> {
+        if self.is_served(torrent_id) {
+            return Ok(Vec::new());
+        }
+        let url = format!(
+            "{}/torrents/{}/peer_stats?state=all",
+            self.api.base_url, torrent_id
+        );
+        let json = self.api.get_json(&url).await?;
+        Ok(parse_peer_stats(&json))
     }
 
     /// Fetch a .torrent file, manually following redirects
@@ -853,6 +2415,31 @@ pub struct VideoFile {
     pub stream_url: String,
 }
 
+/// Find (season, first episode, last episode) tags in a filename.
+/// Handles S01E05, multi-episode S01E05E06 / S01E05-E06 and 1x05.
+pub fn parse_episode_tags(filename: &str) -> Vec<(u32, u32, u32)> {
+    use regex::Regex;
+
+    let sxex_re = Regex::new(r"(?i)s(\d{1,2})\s?e(\d{1,3})(?:-?e(\d{1,3}))?").unwrap();
+    let x_re = Regex::new(r"(?i)(?:^|[^0-9a-z])(\d{1,2})x(\d{2,3})(?:[^0-9]|$)").unwrap();
+
+    let tags: Vec<(u32, u32, u32)> = sxex_re
+        .captures_iter(filename)
+        .chain(x_re.captures_iter(filename))
+        .filter_map(|caps| {
+            let season = caps.get(1)?.as_str().parse().ok()?;
+            let first: u32 = caps.get(2)?.as_str().parse().ok()?;
+            let last = caps
+                .get(3)
+                .and_then(|m| m.as_str().parse().ok())
+                .filter(|last| *last >= first)
+                .unwrap_or(first);
+            Some((season, first, last))
+        })
+        .collect();
+    tags
+}
+
 impl VideoFile {
     /// Extract season and episode numbers from filename for sorting
     pub fn episode_sort_key(&self) -> (u32, u32) {
@@ -879,19 +2466,32 @@ impl VideoFile {
         // If no episode pattern found, use large values to sort at end
         (u32::MAX, u32::MAX)
     }
+
+    /// Directory (disc), track number and name, e.g. "CD2/03 - Song.flac" -> ("CD2", 3, ..)
+    pub fn track_sort_key(&self) -> (&str, u32, &str) {
+        let (dir, file) = self.name.rsplit_once('/').unwrap_or(("", &self.name));
+        let digits: String = file.chars().take_while(|c| c.is_ascii_digit()).collect();
+        (dir, digits.parse().unwrap_or(u32::MAX), file)
+    }
 }
 
-/// Sort video files by episode number (for season packs)
+/// Sort video files by episode number (for season packs), audio releases by track
 pub fn sort_episodes(files: &mut [VideoFile]) {
-    files.sort_by_key(|f| f.episode_sort_key());
+    if files.iter().all(|f| is_audio_file(&f.name)) {
+        files.sort_by(|a, b| a.track_sort_key().cmp(&b.track_sort_key()));
+    } else {
+        files.sort_by_key(|f| f.episode_sort_key());
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct TorrentInfo {
     pub id: usize,
     pub name: String,
-    /// All video files found in the torrent
+    /// All video files found in the torrent, except extras
     pub video_files: Vec<VideoFile>,
+    /// Samples, trailers and featurettes, hidden from the file list by default
+    pub extra_files: Vec<VideoFile>,
     /// The selected video file (defaults to first/largest)
     pub selected_file: VideoFile,
     pub subtitle_files: Vec<SubtitleFile>,
@@ -908,29 +2508,662 @@ pub struct SubtitleFile {
 #[derive(Debug, Clone, Default)]
 pub struct TorrentStats {
     pub downloaded_bytes: u64,
+    pub uploaded_bytes: u64,
     pub total_bytes: u64,
     pub download_speed: u64,
     pub upload_speed: u64,
     pub peers_connected: u32,
 }
 
+impl TorrentStats {
+    /// Share of the torrent downloaded, 0 until its size is known
+    pub fn progress_percent(&self) -> f64 {
+        if self.total_bytes > 0 {
+            (self.downloaded_bytes as f64 / self.total_bytes as f64) * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Which pieces of one file are downloaded, first to last
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PieceMap {
+    pub pieces: Vec<bool>,
+}
+
+impl PieceMap {
+    /// The pieces of file `file_idx` out of a torrent's have bitfield
+    pub fn for_file(haves: &[bool], lengths: &[u64], file_idx: usize) -> Option<Self> {
+        let length = *lengths.get(file_idx)?;
+        let total: u64 = lengths.iter().sum();
+        if haves.is_empty() || length == 0 {
+            return None;
+        }
+        let piece_length = piece_length(total, haves.len());
+        let offset: u64 = lengths[..file_idx].iter().sum();
+        let first = (offset / piece_length) as usize;
+        let last = ((offset + length - 1) / piece_length) as usize;
+        let pieces = haves.get(first..=last.min(haves.len() - 1))?;
+        Some(Self {
+            pieces: pieces.to_vec(),
+        })
+    }
+
+    /// Share downloaded of each of `count` equal stretches of the file, for
+    /// drawing it in `count` cells
+    pub fn buckets(&self, count: usize) -> Vec<f64> {
+        let len = self.pieces.len();
+        if len == 0 {
+            return vec![0.0; count];
+        }
+        (0..count)
+            .map(|i| {
+                let start = i * len / count;
+                let end = ((i + 1) * len / count).max(start + 1);
+                let have = self.pieces[start..end].iter().filter(|&&p| p).count();
+                have as f64 / (end - start) as f64
+            })
+            .collect()
+    }
+}
+
+/// The API doesn't tell the piece length and the have bitfield can be padded
+/// to whole bytes, but piece lengths are powers of two, so it's the one that
+/// splits `total` into at most `bits` pieces
+fn piece_length(total: u64, bits: usize) -> u64 {
+    total.div_ceil(bits as u64).max(1).next_power_of_two()
+}
+
+/// Parse `/torrents/{id}/haves`, librqbit's debug print of the bitfield:
+/// `BitSlice<u8, bitvec::order::Msb0> { addr: 0x.., head: 000, bits: 16 } [1, 0, ...]`
+pub fn parse_haves(body: &str) -> Option<Vec<bool>> {
+    let list = body[body.find('[')?..]
+        .trim()
+        .strip_prefix('[')?
+        .strip_suffix(']')?;
+    list.split(',')
+        .map(str::trim)
+        .filter(|bit| !bit.is_empty())
+        .map(|bit| match bit {
+            "1" => Some(true),
+            "0" => Some(false),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Body of `/torrents/{id}/stats/v1`. Its layout drifts between librqbit
+/// versions, so each known one gets its own shape, newest first.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StatsPayload {
+    /// librqbit 8, uploads counted at the top level
+    Current {
+        progress_bytes: u64,
+        uploaded_bytes: u64,
+        total_bytes: u64,
+        live: Option<LiveStats>,
+    },
+    /// Older librqbit (5.x), uploads only in the live snapshot
+    Legacy {
+        progress_bytes: u64,
+        total_bytes: u64,
+        live: Option<LiveStats>,
+    },
+}
+
+#[derive(Deserialize)]
+struct LiveStats {
+    snapshot: StatsSnapshot,
+    download_speed: Speed,
+    upload_speed: Speed,
+}
+
+#[derive(Deserialize)]
+struct StatsSnapshot {
+    #[serde(default)]
+    uploaded_bytes: u64,
+    #[serde(default)]
+    peer_stats: PeerCounts,
+}
+
+#[derive(Deserialize, Default)]
+struct PeerCounts {
+    live: u64,
+}
+
+/// A transfer speed, which librqbit reports in MiB/s despite the name
+#[derive(Deserialize)]
+struct Speed {
+    mbps: f64,
+}
+
+impl Speed {
+    fn bytes_per_sec(&self) -> u64 {
+        (self.mbps * 1024.0 * 1024.0) as u64
+    }
+}
+
+/// Parse a torrent stats response, None if it matches no known librqbit version
+pub fn parse_stats(body: &str) -> Option<TorrentStats> {
+    let (downloaded_bytes, uploaded_bytes, total_bytes, live) =
+        match serde_json::from_str(body).ok()? {
+            StatsPayload::Current {
+                progress_bytes,
+                uploaded_bytes,
+                total_bytes,
+                live,
+            } => (progress_bytes, Some(uploaded_bytes), total_bytes, live),
+            StatsPayload::Legacy {
+                progress_bytes,
+                total_bytes,
+                live,
+            } => (progress_bytes, None, total_bytes, live),
+        };
+
+    let mut stats = TorrentStats {
+        downloaded_bytes,
+        uploaded_bytes: uploaded_bytes.unwrap_or(0),
+        total_bytes,
+        ..Default::default()
+    };
+    // Paused and initializing torrents have no live stats
+    if let Some(live) = live {
+        stats.uploaded_bytes = uploaded_bytes.unwrap_or(live.snapshot.uploaded_bytes);
+        stats.download_speed = live.download_speed.bytes_per_sec();
+        stats.upload_speed = live.upload_speed.bytes_per_sec();
+        stats.peers_connected = live.snapshot.peer_stats.live as u32;
+    }
+    Some(stats)
+}
+
+/// One peer from the rqbit peer stats endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerInfo {
+    pub addr: String,  // "ip:port"
+    pub state: String, // "live", "connecting", "queued", "dead", ...
+    pub fetched_bytes: u64,
+}
+
+impl PeerInfo {
+    pub fn ip(&self) -> Option<std::net::IpAddr> {
+        self.addr
+            .parse::<std::net::SocketAddr>()
+            .map(|a| a.ip())
+            .or_else(|_| self.addr.parse())
+            .ok()
+    }
+
+    /// IP with everything after the first two octets (or IPv6 groups) hidden
+    pub fn masked_ip(&self) -> String {
+        match self.ip() {
+            Some(std::net::IpAddr::V4(ip)) => {
+                let [a, b, _, _] = ip.octets();
+                format!("{}.{}.x.x", a, b)
+            }
+            Some(std::net::IpAddr::V6(ip)) => {
+                let segments = ip.segments();
+                format!("{:x}:{:x}:…", segments[0], segments[1])
+            }
+            None => "unknown".to_string(),
+        }
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.state == "live"
+    }
+}
+
+/// Parse `GET /torrents/{id}/peer_stats`, sorted by bytes fetched (most first)
+pub fn parse_peer_stats(json: &serde_json::Value) -> Vec<PeerInfo> {
+    let mut peers: Vec<PeerInfo> = json
+        .get("peers")
+        .and_then(|p| p.as_object())
+        .map(|peers| {
+            peers
+                .iter()
+                .map(|(addr, stats)| PeerInfo {
+                    addr: addr.clone(),
+                    state: stats
+                        .get("state")
+                        .and_then(|s| s.as_str())
+                        .unwrap_or("unknown")
+                        .to_string(),
+                    fetched_bytes: stats
+                        .get("counters")
+                        .and_then(|c| c.get("fetched_bytes"))
+                        .and_then(|v| v.as_u64())
+                        .unwrap_or(0),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    peers.sort_by(|a, b| {
+        b.fetched_bytes
+            .cmp(&a.fetched_bytes)
+            .then(b.is_live().cmp(&a.is_live()))
+            .then(a.addr.cmp(&b.addr))
+    });
+    peers
+}
+
+/// Share of all fetched bytes each peer contributed, in percent
+pub fn peer_contributions(peers: &[PeerInfo]) -> Vec<f64> {
+    let total: u64 = peers.iter().map(|p| p.fetched_bytes).sum();
+    peers
+        .iter()
+        .map(|p| {
+            if total == 0 {
+                0.0
+            } else {
+                p.fetched_bytes as f64 / total as f64 * 100.0
+            }
+        })
+        .collect()
+}
+
+/// When to stop seeding a torrent after playback ends
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SeedPolicy {
+    /// Remove the torrent as soon as playback ends
+    #[default]
+    Off,
+    /// Seed for this many minutes
+    Minutes(u64),
+    /// Seed until uploaded / total size reaches this ratio
+    Ratio(f64),
+}
+
+impl SeedPolicy {
+    pub fn is_enabled(&self) -> bool {
+        *self != SeedPolicy::Off
+    }
+
+    /// Whether the seeding target has been hit
+    pub fn is_met(&self, seeded_for: Duration, stats: &TorrentStats) -> bool {
+        match *self {
+            SeedPolicy::Off => true,
+            SeedPolicy::Minutes(minutes) => seeded_for >= Duration::from_secs(minutes * 60),
+            SeedPolicy::Ratio(ratio) => {
+                stats.total_bytes > 0
+                    && stats.uploaded_bytes as f64 / stats.total_bytes as f64 >= ratio
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for SeedPolicy {
+    type Err = String;
+
+    /// Parse "off", "minutes(N)" or "ratio(X)"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if s == "off" {
+            return Ok(SeedPolicy::Off);
+        }
+        let invalid = || {
+            format!(
+                "invalid seed policy '{}': use off, minutes(N) or ratio(X)",
+                s
+            )
+        };
+        let (kind, arg) = s
+            .strip_suffix(')')
+            .and_then(|s| s.split_once('('))
+            .ok_or_else(invalid)?;
+        match kind.trim() {
+            "minutes" => arg
+                .trim()
+                .parse()
+                .ok()
+                .filter(|m| *m > 0)
+                .map(SeedPolicy::Minutes),
+            "ratio" => arg
+                .trim()
+                .parse()
+                .ok()
+                .filter(|r: &f64| r.is_finite() && *r > 0.0)
+                .map(SeedPolicy::Ratio),
+            _ => None,
+        }
+        .ok_or_else(invalid)
+    }
+}
+
+impl std::fmt::Display for SeedPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeedPolicy::Off => write!(f, "off"),
+            SeedPolicy::Minutes(minutes) => write!(f, "minutes({})", minutes),
+            SeedPolicy::Ratio(ratio) => write!(f, "ratio({})", ratio),
+        }
+    }
+}
+
+impl TryFrom<String> for SeedPolicy {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<SeedPolicy> for String {
+    fn from(policy: SeedPolicy) -> Self {
+        policy.to_string()
+    }
+}
+
 /// Check if a file is a video file based on extension
 pub fn is_video_file(filename: &str) -> bool {
     let lower = filename.to_lowercase();
     VIDEO_EXTENSIONS.iter().any(|ext| lower.ends_with(ext))
 }
 
+/// Check if a file is an audio file based on extension
+pub fn is_audio_file(filename: &str) -> bool {
+    let lower = filename.to_lowercase();
+    AUDIO_EXTENSIONS.iter().any(|ext| lower.ends_with(ext))
+}
+
 /// Check if a file is a subtitle file based on extension
 pub fn is_subtitle_file(filename: &str) -> bool {
     let lower = filename.to_lowercase();
     SUBTITLE_EXTENSIONS.iter().any(|ext| lower.ends_with(ext))
 }
 
-/// Result of launching a player with IPC support
-pub struct PlayerHandle {
-    pub child: tokio::process::Child,
-    /// Path to mpv IPC socket (only for mpv)
-    pub ipc_socket: Option<PathBuf>,
+/// Where mpv listens for JSON IPC commands (`--input-ipc-server`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpcEndpoint {
+    UnixSocket(PathBuf),
+    /// Windows named pipe, e.g. `\\.\pipe\ferristream-mpv-1234`
+    NamedPipe(String),
+}
+
+impl IpcEndpoint {
+    /// A fresh endpoint for this platform, unique per launched player
+    pub fn new_for_player() -> Self {
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let name = format!("ferristream-mpv-{}-{}", std::process::id(), stamp);
+
+        if cfg!(windows) {
+            IpcEndpoint::NamedPipe(format!(r"\\.\pipe\{}", name))
+        } else {
+            IpcEndpoint::UnixSocket(std::env::temp_dir().join(format!("{}.sock", name)))
+        }
+    }
+
+    /// Value for mpv's `--input-ipc-server`
+    pub fn server_arg(&self) -> String {
+        match self {
+            IpcEndpoint::UnixSocket(path) => format!("--input-ipc-server={}", path.display()),
+            IpcEndpoint::NamedPipe(name) => format!("--input-ipc-server={}", name),
+        }
+    }
+
+    /// Remove the socket file once the player is gone. Named pipes vanish with mpv.
+    pub fn cleanup(&self) {
+        if let IpcEndpoint::UnixSocket(path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Result of launching a player with IPC support
+pub struct PlayerHandle {
+    pub child: tokio::process::Child,
+    /// mpv IPC endpoint (only for mpv)
+    pub ipc_socket: Option<IpcEndpoint>,
+}
+
+/// Media player family, used to pick player-specific arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerKind {
+    Mpv,
+    Vlc,
+    /// IINA (macOS) - mpv based, takes mpv options as --mpv-<option>
+    Iina,
+    Generic,
+}
+
+/// Detect the player family from its command (name or path)
+pub fn detect_player_kind(command: &str) -> PlayerKind {
+    let name = std::path::Path::new(command)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if name.contains("iina") {
+        PlayerKind::Iina
+    } else if name.contains("mpv") {
+        PlayerKind::Mpv
+    } else if name.contains("vlc") {
+        PlayerKind::Vlc
+    } else {
+        PlayerKind::Generic
+    }
+}
+
+impl PlayerKind {
+    /// Arguments for a subtitle file and preferred subtitle/audio language.
+    /// Each argument is passed to the player as-is (no shell), so paths with spaces are safe.
+    pub fn subtitle_args(self, subtitle_url: Option<&str>, language: Option<&str>) -> Vec<String> {
+        let mut args = Vec::new();
+
+        match self {
+            PlayerKind::Mpv => {
+                if let Some(sub_url) = subtitle_url {
+                    args.push(format!("--sub-file={}", sub_url));
+                }
+            }
+            PlayerKind::Iina => {
+                if let Some(sub_url) = subtitle_url {
+                    args.push(format!("--mpv-sub-file={}", sub_url));
+                }
+            }
+            PlayerKind::Vlc => {
+                // Pass the path as its own argument so VLC doesn't mis-parse paths with spaces
+                if let Some(sub_url) = subtitle_url {
+                    args.push("--sub-file".to_string());
+                    args.push(sub_url.to_string());
+                }
+                if let Some(lang) = language {
+                    args.push(format!("--sub-language={}", lang));
+                    args.push(format!("--audio-language={}", lang));
+                }
+            }
+            PlayerKind::Generic => {}
+        }
+
+        args
+    }
+
+    /// Arguments for preferred audio languages, most preferred first.
+    /// Passed after the subtitle arguments so they override VLC's subtitle-derived hint.
+    pub fn audio_args(self, languages: &[String]) -> Vec<String> {
+        if languages.is_empty() {
+            return Vec::new();
+        }
+        let languages = languages.join(",");
+
+        match self {
+            PlayerKind::Mpv => vec![format!("--alang={}", languages)],
+            PlayerKind::Iina => vec![format!("--mpv-alang={}", languages)],
+            PlayerKind::Vlc => vec![format!("--audio-language={}", languages)],
+            PlayerKind::Generic => Vec::new(),
+        }
+    }
+
+    /// Arguments to start playback at a percentage of the file, e.g. after a restart.
+    /// VLC only takes a start time in seconds, so it starts from the beginning.
+    pub fn start_args(self, start_percent: Option<f64>) -> Vec<String> {
+        let Some(percent) = start_percent.filter(|p| *p > 0.0) else {
+            return Vec::new();
+        };
+
+        match self {
+            PlayerKind::Mpv => vec![format!("--start={:.1}%", percent)],
+            PlayerKind::Iina => vec![format!("--mpv-start={:.1}%", percent)],
+            PlayerKind::Vlc | PlayerKind::Generic => Vec::new(),
+        }
+    }
+}
+
+/// A player ferristream knows how to find and set up
+struct KnownPlayer {
+    /// Command looked up on PATH, also the name shown
+    command: &'static str,
+    /// Where its installer puts it when that isn't on PATH
+    locations: &'static [&'static str],
+    /// Prints the version and exits, for players where that doesn't open a window
+    version_arg: Option<&'static str>,
+    /// `player.args` when it's picked. mpv and IINA get theirs at launch.
+    default_args: &'static [&'static str],
+}
+
+const KNOWN_PLAYERS: &[KnownPlayer] = &[
+    KnownPlayer {
+        command: "mpv",
+        locations: &["/Applications/mpv.app/Contents/MacOS/mpv"],
+        version_arg: Some("--version"),
+        default_args: &[],
+    },
+    KnownPlayer {
+        command: "vlc",
+        locations: &[
+            "/Applications/VLC.app/Contents/MacOS/VLC",
+            r"C:\Program Files\VideoLAN\VLC\vlc.exe",
+        ],
+        version_arg: Some("--version"),
+        // Quit when the file ends so the stream does too
+        default_args: &["--play-and-exit"],
+    },
+    KnownPlayer {
+        command: "iina-cli",
+        locations: &["/Applications/IINA.app/Contents/MacOS/iina-cli"],
+        version_arg: None,
+        default_args: &[],
+    },
+    KnownPlayer {
+        command: "mpc-hc64",
+        locations: &[r"C:\Program Files\MPC-HC\mpc-hc64.exe"],
+        version_arg: None,
+        // Start playing straight away and close at the end
+        default_args: &["/play", "/close"],
+    },
+    KnownPlayer {
+        command: "mpc-hc",
+        locations: &[r"C:\Program Files (x86)\MPC-HC\mpc-hc.exe"],
+        version_arg: None,
+        default_args: &["/play", "/close"],
+    },
+    KnownPlayer {
+        command: "mplayer",
+        locations: &[],
+        version_arg: None,
+        default_args: &[],
+    },
+];
+
+/// A known player installed on this machine
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectedPlayer {
+    pub name: &'static str,
+    /// What goes in `player.command`: the name when it's on PATH, else the full path
+    pub command: String,
+    pub path: PathBuf,
+    /// First line of its version output, None if it wasn't asked or didn't answer
+    pub version: Option<String>,
+    pub default_args: &'static [&'static str],
+}
+
+/// Known players found on PATH or where their installers put them
+fn locate_players(
+    on_path: impl Fn(&str) -> Option<PathBuf>,
+    exists: impl Fn(&Path) -> bool,
+) -> Vec<DetectedPlayer> {
+    KNOWN_PLAYERS
+        .iter()
+        .filter_map(|known| {
+            let (command, path) = match on_path(known.command) {
+                Some(path) => (known.command.to_string(), path),
+                None => {
+                    let path = known
+                        .locations
+                        .iter()
+                        .map(PathBuf::from)
+                        .find(|path| exists(path))?;
+                    (path.to_string_lossy().into_owned(), path)
+                }
+            };
+            Some(DetectedPlayer {
+                name: known.command,
+                command,
+                path,
+                version: None,
+                default_args: known.default_args,
+            })
+        })
+        .collect()
+}
+
+fn installed_players() -> Vec<DetectedPlayer> {
+    locate_players(|command| which::which(command).ok(), Path::exists)
+}
+
+/// Installed players with their versions, for picking one in the wizard,
+/// settings and doctor
+pub async fn find_players() -> Vec<DetectedPlayer> {
+    let mut players = installed_players();
+    for player in &mut players {
+        player.version = player_version(player).await;
+    }
+    players
+}
+
+async fn player_version(player: &DetectedPlayer) -> Option<String> {
+    let arg = KNOWN_PLAYERS
+        .iter()
+        .find(|known| known.command == player.name)?
+        .version_arg?;
+    let output = timeout(
+        Duration::from_secs(3),
+        Command::new(&player.path)
+            .arg(arg)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(String::from)
+}
+
+/// Players offered when switching players mid-stream, the configured one first
+pub fn detect_players(configured: &str) -> Vec<String> {
+    player_choices(configured, installed_players())
+}
+
+fn player_choices(configured: &str, installed: Vec<DetectedPlayer>) -> Vec<String> {
+    let mut players = vec![configured.to_string()];
+    players.extend(
+        installed
+            .into_iter()
+            .map(|player| player.command)
+            .filter(|command| command != configured),
+    );
+    players
 }
 
 pub async fn launch_player(
@@ -938,15 +3171,17 @@ pub async fn launch_player(
     args: &[String],
     stream_url: &str,
     subtitle_url: Option<&str>,
+    language: Option<&str>,
+    audio_languages: &[String],
+    start_percent: Option<f64>,
 ) -> Result<PlayerHandle, StreamError> {
     let mut cmd = Command::new(command);
     let mut ipc_socket = None;
+    let kind = detect_player_kind(command);
 
     // Only add mpv-specific args if using mpv
-    if command.contains("mpv") {
-        // Create IPC socket path
-        let socket_path =
-            std::env::temp_dir().join(format!("ferristream-mpv-{}.sock", std::process::id()));
+    if kind == PlayerKind::Mpv {
+        let endpoint = IpcEndpoint::new_for_player();
 
         cmd.args([
             "--force-seekable=yes",
@@ -957,22 +3192,13 @@ pub async fn launch_player(
         ]);
 
         // Enable IPC for position tracking
-        cmd.arg(format!("--input-ipc-server={}", socket_path.display()));
-        ipc_socket = Some(socket_path);
-
-        // Add subtitle file if provided
-        if let Some(sub_url) = subtitle_url {
-            cmd.arg(format!("--sub-file={}", sub_url));
-        }
-    }
-
-    // For VLC, subtitles are handled differently
-    if command.contains("vlc")
-        && let Some(sub_url) = subtitle_url
-    {
-        cmd.arg(format!("--sub-file={}", sub_url));
+        cmd.arg(endpoint.server_arg());
+        ipc_socket = Some(endpoint);
     }
 
+    cmd.args(kind.subtitle_args(subtitle_url, language));
+    cmd.args(kind.audio_args(audio_languages));
+    cmd.args(kind.start_args(start_percent));
     cmd.args(args);
     cmd.arg(stream_url);
 
@@ -988,54 +3214,6 @@ pub async fn launch_player(
     Ok(PlayerHandle { child, ipc_socket })
 }
 
-/// Get current playback position from mpv via IPC
-/// Returns (position_seconds, duration_seconds) if successful
-#[cfg(unix)]
-pub async fn get_mpv_position(socket_path: &std::path::Path) -> Option<(f64, f64)> {
-    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-    use tokio::net::UnixStream;
-
-    // Connect to mpv socket
-    let stream = UnixStream::connect(socket_path).await.ok()?;
-    let (reader, mut writer) = stream.into_split();
-    let mut reader = BufReader::new(reader);
-
-    // Request time-pos
-    writer
-        .write_all(b"{\"command\": [\"get_property\", \"time-pos\"]}\n")
-        .await
-        .ok()?;
-    let mut pos_response = String::new();
-    reader.read_line(&mut pos_response).await.ok()?;
-
-    // Request duration
-    writer
-        .write_all(b"{\"command\": [\"get_property\", \"duration\"]}\n")
-        .await
-        .ok()?;
-    let mut dur_response = String::new();
-    reader.read_line(&mut dur_response).await.ok()?;
-
-    // Parse responses
-    let pos: f64 = serde_json::from_str::<serde_json::Value>(&pos_response)
-        .ok()?
-        .get("data")?
-        .as_f64()?;
-
-    let dur: f64 = serde_json::from_str::<serde_json::Value>(&dur_response)
-        .ok()?
-        .get("data")?
-        .as_f64()?;
-
-    Some((pos, dur))
-}
-
-// Add a Windows stub that returns None
-#[cfg(not(unix))]
-pub async fn get_mpv_position(_socket_path: &std::path::Path) -> Option<(f64, f64)> {
-    None
-}
-
 /// Calculate playback progress as percentage
 pub fn calculate_progress(position: f64, duration: f64) -> f64 {
     if duration > 0.0 {
@@ -1049,6 +3227,297 @@ pub fn calculate_progress(position: f64, duration: f64) -> f64 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_direct_torrent_source() {
+        let magnet = "magnet:?xt=urn:btih:4344503b7e797ebf31582327a5baae35b11bda01&dn=Test";
+        assert_eq!(direct_torrent_source(magnet).as_deref(), Some(magnet));
+        assert_eq!(
+            direct_torrent_source(&format!("  MAGNET:{}\n", &magnet[7..])),
+            Some(format!("MAGNET:{}", &magnet[7..]))
+        );
+        assert_eq!(
+            direct_torrent_source("https://tracker.example/dl/Movie.2024.torrent?passkey=x")
+                .as_deref(),
+            Some("https://tracker.example/dl/Movie.2024.torrent?passkey=x")
+        );
+
+        // Dropped files, however the terminal quotes them
+        for dropped in [
+            "/home/me/Downloads/Some Movie.torrent",
+            "'/home/me/Downloads/Some Movie.torrent'",
+            "/home/me/Downloads/Some\\ Movie.torrent",
+            "file:///home/me/Downloads/Some%20Movie.torrent",
+        ] {
+            assert_eq!(
+                direct_torrent_source(dropped).as_deref(),
+                Some("/home/me/Downloads/Some Movie.torrent"),
+                "{}",
+                dropped
+            );
+        }
+
+        // Anything that's clearly a path, whatever it's called
+        assert_eq!(
+            direct_torrent_source("/home/me/Downloads/Some Movie").as_deref(),
+            Some("/home/me/Downloads/Some Movie")
+        );
+        let home = directories::BaseDirs::new()
+            .unwrap()
+            .home_dir()
+            .to_path_buf();
+        assert_eq!(
+            direct_torrent_source("~/Downloads/movie.torrent").map(PathBuf::from),
+            Some(home.join("Downloads/movie.torrent"))
+        );
+
+        // Everything else is a search
+        assert_eq!(direct_torrent_source("the matrix 1999"), None);
+        assert_eq!(direct_torrent_source("https://example.com/movie"), None);
+        assert_eq!(direct_torrent_source("magnet"), None);
+    }
+
+    #[test]
+    fn test_torrent_magnet() {
+        let info: &[u8] =
+            b"d6:lengthi10e4:name8:test.mkv12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+        let torrent = [
+            b"d8:announce30:udp://tracker.example:1337/ann4:info".as_slice(),
+            info,
+            b"e",
+        ]
+        .concat();
+        let magnet = torrent_magnet(&torrent).unwrap();
+        let hash = sha1_smol::Sha1::from(info).digest().to_string();
+        assert!(magnet.starts_with(&format!("magnet:?xt=urn:btih:{}", hash)));
+        assert!(magnet.contains("tracker.example"));
+
+        assert!(torrent_magnet(b"d8:announce3:url4:infode").is_err());
+    }
+
+    #[test]
+    fn test_check_torrent_source() {
+        let base =
+            std::env::temp_dir().join(format!("ferristream_torrent_test_{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        let torrent = base.join("movie.torrent");
+        std::fs::write(&torrent, b"d8:announce3:url4:infode").unwrap();
+        let video = base.join("movie.mkv.torrent");
+        std::fs::write(&video, b"\x1aE\xdf\xa3").unwrap();
+
+        assert!(check_torrent_source(torrent.to_str().unwrap()).is_ok());
+        let url = format!("file://{}", torrent.display());
+        assert!(check_torrent_source(&url).is_ok());
+        assert!(
+            check_torrent_source(video.to_str().unwrap())
+                .unwrap_err()
+                .to_string()
+                .contains("isn't a valid .torrent file")
+        );
+        assert!(
+            check_torrent_source(base.join("missing.torrent").to_str().unwrap())
+                .unwrap_err()
+                .to_string()
+                .starts_with("Can't read")
+        );
+        assert!(check_torrent_source("magnet:?xt=urn:btih:abc").is_ok());
+
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_parse_haves() {
+        let body = "BitSlice<u8, bitvec::order::Msb0> { addr: 0x55d0c1e0, head: 000, bits: 4 } [1, 0, 0, 1]";
+        assert_eq!(parse_haves(body), Some(vec![true, false, false, true]));
+        assert_eq!(
+            parse_haves("BitSlice<u8, bitvec::order::Msb0> { addr: 0x1, head: 000, bits: 0 } []"),
+            Some(vec![])
+        );
+        assert_eq!(parse_haves("not found"), None);
+    }
+
+    #[test]
+    fn test_piece_map_for_file() {
+        // 1 MiB pieces: 13 of them padded to 16 bits, the middle file spans
+        // pieces 4-10 and the last one 10-12
+        let lengths = [4_500_000, 6_000_000, 2_500_000];
+        let mut haves = [false; 16];
+        haves[..6].fill(true);
+        haves[10] = true;
+        assert_eq!(piece_length(13_000_000, haves.len()), 1 << 20);
+
+        let map = PieceMap::for_file(&haves, &lengths, 1).unwrap();
+        assert_eq!(map.pieces, [true, true, false, false, false, false, true]);
+        let map = PieceMap::for_file(&haves, &lengths, 2).unwrap();
+        assert_eq!(map.pieces, [true, false, false]);
+
+        assert!(PieceMap::for_file(&haves, &lengths, 3).is_none());
+        assert!(PieceMap::for_file(&[], &lengths, 0).is_none());
+    }
+
+    #[test]
+    fn test_piece_map_buckets() {
+        let map = PieceMap {
+            pieces: vec![true, true, true, false, true, false, false, false],
+        };
+        assert_eq!(map.buckets(4), vec![1.0, 0.5, 0.5, 0.0]);
+        assert_eq!(map.buckets(2), vec![0.75, 0.25]);
+        // More cells than pieces repeats them
+        assert_eq!(
+            PieceMap {
+                pieces: vec![true, false]
+            }
+            .buckets(4),
+            vec![1.0, 1.0, 0.0, 0.0]
+        );
+        assert_eq!(PieceMap::default().buckets(2), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_stats_progress_percent() {
+        let stats = TorrentStats {
+            downloaded_bytes: 25,
+            total_bytes: 1000,
+            ..Default::default()
+        };
+        assert_eq!(stats.progress_percent(), 2.5);
+        assert_eq!(TorrentStats::default().progress_percent(), 0.0);
+    }
+
+    #[test]
+    fn test_installed_players() {
+        let iina = "/Applications/IINA.app/Contents/MacOS/iina-cli";
+        let players = locate_players(
+            |command| {
+                ["vlc", "mplayer"]
+                    .contains(&command)
+                    .then(|| PathBuf::from("/usr/bin").join(command))
+            },
+            |path| path == Path::new(iina),
+        );
+        let commands: Vec<_> = players.iter().map(|p| p.command.as_str()).collect();
+        assert_eq!(commands, vec!["vlc", iina, "mplayer"]);
+        assert_eq!(players[0].path, PathBuf::from("/usr/bin/vlc"));
+        assert_eq!(players[0].default_args, ["--play-and-exit"]);
+        assert_eq!(players[1].name, "iina-cli");
+        assert!(players[1].default_args.is_empty());
+
+        assert_eq!(
+            player_choices("vlc", players.clone()),
+            vec!["vlc", iina, "mplayer"]
+        );
+        assert_eq!(
+            player_choices("/opt/celluloid", Vec::new()),
+            vec!["/opt/celluloid"]
+        );
+    }
+
+    #[test]
+    fn test_detect_player_kind() {
+        assert_eq!(detect_player_kind("mpv"), PlayerKind::Mpv);
+        assert_eq!(detect_player_kind("/usr/bin/mpv"), PlayerKind::Mpv);
+        assert_eq!(detect_player_kind("vlc"), PlayerKind::Vlc);
+        assert_eq!(detect_player_kind("cvlc"), PlayerKind::Vlc);
+        assert_eq!(
+            detect_player_kind("/Applications/VLC.app/Contents/MacOS/VLC"),
+            PlayerKind::Vlc
+        );
+        assert_eq!(detect_player_kind("iina"), PlayerKind::Iina);
+        assert_eq!(detect_player_kind("iina-cli"), PlayerKind::Iina);
+        assert_eq!(detect_player_kind("mplayer"), PlayerKind::Generic);
+        // Only the binary name counts, not the directory
+        assert_eq!(
+            detect_player_kind("/opt/mpv-tools/celluloid"),
+            PlayerKind::Generic
+        );
+    }
+
+    #[test]
+    fn test_player_subtitle_args() {
+        let sub = "/tmp/ferristream/My Movie.en.srt";
+
+        assert_eq!(
+            PlayerKind::Vlc.subtitle_args(Some(sub), Some("en")),
+            vec![
+                "--sub-file".to_string(),
+                sub.to_string(),
+                "--sub-language=en".to_string(),
+                "--audio-language=en".to_string(),
+            ]
+        );
+        assert_eq!(
+            PlayerKind::Vlc.subtitle_args(None, Some("nl")),
+            vec![
+                "--sub-language=nl".to_string(),
+                "--audio-language=nl".to_string()
+            ]
+        );
+        assert_eq!(
+            PlayerKind::Mpv.subtitle_args(Some(sub), Some("en")),
+            vec![format!("--sub-file={}", sub)]
+        );
+        assert_eq!(
+            PlayerKind::Iina.subtitle_args(Some(sub), None),
+            vec![format!("--mpv-sub-file={}", sub)]
+        );
+        assert!(
+            PlayerKind::Generic
+                .subtitle_args(Some(sub), Some("en"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_player_audio_args() {
+        let languages = vec!["ja".to_string(), "en".to_string()];
+
+        assert_eq!(
+            PlayerKind::Mpv.audio_args(&languages),
+            vec!["--alang=ja,en".to_string()]
+        );
+        assert_eq!(
+            PlayerKind::Iina.audio_args(&languages),
+            vec!["--mpv-alang=ja,en".to_string()]
+        );
+        assert_eq!(
+            PlayerKind::Vlc.audio_args(&languages),
+            vec!["--audio-language=ja,en".to_string()]
+        );
+        assert!(PlayerKind::Generic.audio_args(&languages).is_empty());
+        assert!(PlayerKind::Mpv.audio_args(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_ipc_endpoint() {
+        let endpoint = IpcEndpoint::new_for_player();
+        match &endpoint {
+            IpcEndpoint::NamedPipe(name) => assert!(name.starts_with(r"\\.\pipe\ferristream-mpv-")),
+            IpcEndpoint::UnixSocket(path) => assert!(path.to_string_lossy().ends_with(".sock")),
+        }
+
+        let pipe = IpcEndpoint::NamedPipe(r"\\.\pipe\ferristream-mpv-1".to_string());
+        assert_eq!(
+            pipe.server_arg(),
+            r"--input-ipc-server=\\.\pipe\ferristream-mpv-1"
+        );
+        let socket = IpcEndpoint::UnixSocket(PathBuf::from("/tmp/mpv.sock"));
+        assert_eq!(socket.server_arg(), "--input-ipc-server=/tmp/mpv.sock");
+    }
+
+    #[test]
+    fn test_player_start_args() {
+        assert_eq!(
+            PlayerKind::Mpv.start_args(Some(42.26)),
+            vec!["--start=42.3%".to_string()]
+        );
+        assert_eq!(
+            PlayerKind::Iina.start_args(Some(10.0)),
+            vec!["--mpv-start=10.0%".to_string()]
+        );
+        assert!(PlayerKind::Vlc.start_args(Some(42.0)).is_empty());
+        assert!(PlayerKind::Mpv.start_args(Some(0.0)).is_empty());
+        assert!(PlayerKind::Mpv.start_args(None).is_empty());
+    }
+
     #[test]
     fn test_extract_subtitle_language() {
         // English variations
@@ -1180,6 +3649,155 @@ mod tests {
         assert!(files[2].name.contains("E03"));
     }
 
+    fn file(name: &str, file_idx: usize, size: u64) -> VideoFile {
+        VideoFile {
+            name: name.to_string(),
+            file_idx,
+            size,
+            stream_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_playable_files_audio_release() {
+        let files = vec![
+            file("Album/CD2/01 - Third.flac", 0, 300),
+            file("Album/CD1/10 - Second.flac", 1, 100),
+            file("Album/CD1/02 - First.flac", 2, 200),
+            file("Album/cover.jpg", 3, 50),
+        ];
+
+        assert!(matches!(
+            playable_files(MediaMode::Video, files.clone()),
+            Err(StreamError::NoVideoFiles(MediaMode::Video))
+        ));
+
+        // Track order rather than the largest file
+        let playable = playable_files(MediaMode::Audio, files).unwrap();
+        let order: Vec<usize> = playable.files.iter().map(|f| f.file_idx).collect();
+        assert_eq!(order, vec![2, 1, 0]);
+        assert!(playable.extras.is_empty());
+        assert_eq!(playable.selected.file_idx, 2);
+    }
+
+    #[test]
+    fn test_playable_files_video_picks_largest() {
+        let files = vec![
+            file("Movie/sample.mkv", 0, 10),
+            file("Movie/Movie.mkv", 1, 1000),
+            file("Movie/theme.mp3", 2, 5),
+        ];
+
+        let playable = playable_files(MediaMode::Video, files.clone()).unwrap();
+        assert_eq!(playable.files.len(), 1);
+        assert_eq!(playable.extras[0].file_idx, 0);
+        assert_eq!(playable.selected.file_idx, 1);
+
+        let playable = playable_files(MediaMode::All, files).unwrap();
+        assert_eq!(playable.files.len(), 2);
+        assert_eq!(playable.selected.file_idx, 1);
+    }
+
+    #[test]
+    fn test_playable_files_skips_large_sample() {
+        // A sample bigger than the main file is still never the default
+        let files = vec![
+            file("Short.Film.2019.1080p/Short.Film.2019.1080p.mkv", 0, 400),
+            file("Short.Film.2019.1080p/Sample/short.film.sample.mkv", 1, 900),
+        ];
+        let playable = playable_files(MediaMode::Video, files).unwrap();
+        assert_eq!(playable.selected.file_idx, 0);
+        assert_eq!(playable.extras.len(), 1);
+    }
+
+    /// Names of the files `split_extras` keeps and drops for a release layout
+    fn split_names(files: &[(&str, u64)]) -> (Vec<String>, Vec<String>) {
+        let files = files
+            .iter()
+            .enumerate()
+            .map(|(i, (name, size))| file(name, i, *size))
+            .collect();
+        let (content, extras) = split_extras(files);
+        let names = |files: Vec<VideoFile>| files.into_iter().map(|f| f.name).collect();
+        (names(content), names(extras))
+    }
+
+    #[test]
+    fn test_split_extras_release_layouts() {
+        const MB: u64 = 1024 * 1024;
+
+        // Scene movie with a sample directory
+        let (content, extras) = split_names(&[
+            (
+                "Heat.1995.1080p.BluRay.x264-AMIABLE/heat.1995.1080p.bluray.x264-amiable.mkv",
+                9000 * MB,
+            ),
+            (
+                "Heat.1995.1080p.BluRay.x264-AMIABLE/Sample/heat.1995.1080p.bluray.x264-amiable.sample.mkv",
+                60 * MB,
+            ),
+        ]);
+        assert_eq!(content.len(), 1);
+        assert!(extras[0].contains("/Sample/"));
+
+        // P2P movie with extras and featurettes next to it
+        let (content, extras) = split_names(&[
+            ("Blade Runner (1982)/Blade Runner (1982).mkv", 20000 * MB),
+            ("Blade Runner (1982)/Extras/Deleted Scenes.mkv", 900 * MB),
+            (
+                "Blade Runner (1982)/Featurettes/Dangerous Days.mkv",
+                3000 * MB,
+            ),
+            (
+                "Blade Runner (1982)/Blade Runner (1982) - Trailer.mp4",
+                80 * MB,
+            ),
+        ]);
+        assert_eq!(content, vec!["Blade Runner (1982)/Blade Runner (1982).mkv"]);
+        assert_eq!(extras.len(), 3);
+
+        // Season pack: every episode is content, the tiny teaser is not
+        let (content, extras) = split_names(&[
+            ("The.Wire.S01.1080p/The.Wire.S01E01.1080p.mkv", 2000 * MB),
+            ("The.Wire.S01.1080p/The.Wire.S01E02.1080p.mkv", 2100 * MB),
+            ("The.Wire.S01.1080p/The.Wire.S01E03.1080p.mkv", 1900 * MB),
+            ("The.Wire.S01.1080p/Season.Teaser.mkv", 40 * MB),
+        ]);
+        assert_eq!(content.len(), 3);
+        assert_eq!(extras, vec!["The.Wire.S01.1080p/Season.Teaser.mkv"]);
+
+        // Anime batch with a short NCOP: too small next to the episodes
+        let (content, extras) = split_names(&[
+            ("[Group] Show (BD 1080p)/[Group] Show - 01.mkv", 1200 * MB),
+            ("[Group] Show (BD 1080p)/[Group] Show - 02.mkv", 1200 * MB),
+            ("[Group] Show (BD 1080p)/[Group] Show - NCOP.mkv", 90 * MB),
+        ]);
+        assert_eq!(content.len(), 2);
+        assert_eq!(extras.len(), 1);
+
+        // Titles containing the words aren't extras ("Extras" the show, "Samples" in a word)
+        let (content, extras) = split_names(&[
+            ("Extras.S01.720p/Extras.S01E01.720p.mkv", 700 * MB),
+            ("Extras.S01.720p/Extras.S01E02.720p.mkv", 700 * MB),
+            ("The.Sampler.2021.mkv", 700 * MB),
+        ]);
+        assert_eq!(content.len(), 3);
+        assert!(extras.is_empty());
+
+        // A lone sample is still playable
+        let (content, extras) = split_names(&[("movie-sample.mkv", 30 * MB)]);
+        assert_eq!(content, vec!["movie-sample.mkv"]);
+        assert!(extras.is_empty());
+
+        // Audio never counts as an extra
+        let (content, extras) = split_names(&[
+            ("Soundtrack/01 - Main Theme.flac", 40 * MB),
+            ("Soundtrack/02 - Trailer Music.flac", 2 * MB),
+        ]);
+        assert_eq!(content.len(), 2);
+        assert!(extras.is_empty());
+    }
+
     #[test]
     fn test_extract_keywords() {
         // Basic extraction - years are filtered out
@@ -1203,6 +3821,193 @@ mod tests {
         assert!(!kw.contains(&"2021".to_string())); // Years are filtered
     }
 
+    #[test]
+    fn test_parse_episode_tags() {
+        assert_eq!(parse_episode_tags("Show.S01E05.1080p.mkv"), vec![(1, 5, 5)]);
+        assert_eq!(
+            parse_episode_tags("Show.S01E05E06.720p.mkv"),
+            vec![(1, 5, 6)]
+        );
+        assert_eq!(
+            parse_episode_tags("Show.S01E05-E06.720p.mkv"),
+            vec![(1, 5, 6)]
+        );
+        assert_eq!(
+            parse_episode_tags("Show.1x05.HDTV.x264.avi"),
+            vec![(1, 5, 5)]
+        );
+        // Resolution after the tag isn't a second episode
+        assert_eq!(parse_episode_tags("Show.S01E05-1080p.mkv"), vec![(1, 5, 5)]);
+        assert!(parse_episode_tags("Show.S01.1080p.WEB-DL").is_empty());
+        assert!(parse_episode_tags("Movie.2024.1080p.x264.mkv").is_empty());
+    }
+
+    #[test]
+    fn test_torrent_validation_episode() {
+        let context = SearchContext {
+            title: "Breaking Bad".to_string(),
+            year: Some(2008),
+            season: Some(1),
+            episode: Some(5),
+            media_type: Some("tv".to_string()),
+            ..Default::default()
+        };
+        let v = TorrentValidation::from_context(&context);
+        assert_eq!(v.year, None); // Episodes aren't checked against the show's year
+
+        assert!(v.matches("Breaking.Bad.S01E05.720p.HDTV.mkv"));
+        assert!(v.matches("breaking bad 1x05 gray matter.avi"));
+        assert!(v.matches("Breaking.Bad.S01E04E05.720p.mkv"));
+        assert!(!v.matches("Breaking.Bad.S01E06.720p.HDTV.mkv")); // Wrong episode
+        assert!(!v.matches("Breaking.Bad.S02E05.720p.HDTV.mkv")); // Wrong season
+        assert!(!v.matches("Breaking.Bad.S01.720p.BluRay")); // Season pack name
+        assert!(!v.matches("Better.Call.Saul.S01E05.mkv")); // Other show
+
+        // Season packs only pass when they contain the episode
+        assert!(v.pack_contains_episode(
+            "Breaking.Bad.S01.720p.BluRay",
+            ["Breaking.Bad.S01E04.mkv", "Breaking.Bad.S01E05.mkv"]
+        ));
+        assert!(!v.pack_contains_episode(
+            "Breaking.Bad.S01.720p.BluRay",
+            ["Breaking.Bad.S01E06.mkv", "Breaking.Bad.S01E07.mkv"]
+        ));
+        assert!(
+            !v.pack_contains_episode("Better.Call.Saul.S01.720p", ["Better.Call.Saul.S01E05.mkv"])
+        );
+    }
+
+    #[test]
+    fn test_torrent_validation_absolute_episode() {
+        let context = SearchContext {
+            title: "Naruto".to_string(),
+            episode: Some(148),
+            media_type: Some("anime".to_string()),
+            ..Default::default()
+        };
+        let v = TorrentValidation::from_context(&context);
+
+        assert!(v.matches("[Group] Naruto - 148 (1080p).mkv"));
+        assert!(v.matches("Naruto.E148.720p.mkv"));
+        assert!(v.matches("[Group] Naruto - 148v2 [720p].mkv"));
+        assert!(!v.matches("[Group] Naruto - 149 (1080p).mkv"));
+        assert!(!v.matches("[Group] Naruto - 1148 (1080p).mkv"));
+
+        let v = TorrentValidation::from_context(&SearchContext {
+            title: "Naruto".to_string(),
+            episode: Some(264),
+            ..Default::default()
+        });
+        assert!(!v.matches("[Group] Naruto - 012 [x264].mkv"));
+    }
+
+    #[test]
+    fn test_quality_score() {
+        let v = TorrentValidation {
+            quality_preference: Some("1080p".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            v.quality_score("Movie.2024.1080p.WEB-DL.mkv"),
+            QUALITY_MATCH
+        );
+        assert_eq!(v.quality_score("Movie 2024 [1080P] x265"), QUALITY_MATCH);
+
+        // Closer resolutions beat further ones, a step up beats a step down
+        let uhd = v.quality_score("Movie.2024.2160p.HDR.mkv");
+        let hd = v.quality_score("Movie.2024.720p.WEB.mkv");
+        let sd = v.quality_score("Movie.2024.480p.DVDRip.avi");
+        assert!(uhd < QUALITY_MATCH);
+        assert!(uhd > hd);
+        assert!(hd > sd);
+        assert!(sd > 0);
+
+        // Unknown resolution can't be compared
+        assert_eq!(v.quality_score("Movie.2024.WEBRip.mkv"), 0);
+
+        // 1080 inside another word doesn't count
+        assert_eq!(v.quality_score("Movie.2024.x1080px.mkv"), 0);
+    }
+
+    #[test]
+    fn test_quality_score_without_preference() {
+        let v = TorrentValidation::new(vec!["movie".to_string()], None);
+        assert_eq!(v.quality_score("Movie.2024.480p.avi"), QUALITY_MATCH);
+        assert_eq!(v.quality_score("Movie.2024.mkv"), QUALITY_MATCH);
+
+        // A non-resolution preference is all or nothing
+        let v = TorrentValidation {
+            quality_preference: Some("HDR".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(v.quality_score("Movie.2024.2160p.HDR.mkv"), QUALITY_MATCH);
+        assert_eq!(v.quality_score("Movie.2024.2160p.mkv"), 0);
+    }
+
+    fn scored_result(title: &str, size_gb: u64, seeders: u32) -> TorrentResult {
+        TorrentResult {
+            title: title.to_string(),
+            link: Some("http://localhost/dl".to_string()),
+            magnet_url: None,
+            infohash: None,
+            size: Some(size_gb * 1024 * 1024 * 1024),
+            seeders: Some(seeders),
+            leechers: None,
+            indexer: "test".to_string(),
+            categories: vec![5040],
+            usenet: false,
+        }
+    }
+
+    #[test]
+    fn test_result_scorer() {
+        let context = SearchContext {
+            title: "Severance".to_string(),
+            season: Some(2),
+            episode: Some(3),
+            media_type: Some("tv".to_string()),
+            ..Default::default()
+        };
+        let mut validation = TorrentValidation::from_context(&context);
+        validation.quality_preference = Some("1080p".to_string());
+        let scorer = ResultScorer::new(validation, context.media_type, vec!["cam".to_string()]);
+
+        let results = vec![
+            scored_result("Severance.S02E03.1080p.WEB.h264", 2, 120),
+            scored_result("Severance.S02E03.720p.WEB.h264", 1, 400),
+            scored_result("Severance.S02E04.1080p.WEB.h264", 2, 400),
+            scored_result("Severance.S02E03.1080p.CAM", 2, 400),
+            scored_result("Severance.S02E03.1080p.REMUX", 60, 400),
+        ];
+        let scores = scorer.score_all(&results);
+
+        // Right episode in the preferred quality wins over better-seeded misses
+        let best = scores
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, s)| **s)
+            .unwrap()
+            .0;
+        assert_eq!(best, 0);
+        assert!(scores[1] > scores[2], "wrong episode ranks low");
+        assert!(scores[3] < scores[0] - 30, "blocked term is penalized");
+        assert!(scores[4] < scores[0], "implausible episode size");
+        assert!(scores.iter().all(|s| *s <= 100));
+    }
+
+    #[test]
+    fn test_search_context_from_query() {
+        let context = SearchContext::from_query("The Office S02E10");
+        assert_eq!(context.season, Some(2));
+        assert_eq!(context.episode, Some(10));
+        assert_eq!(context.media_type, None);
+
+        let context = SearchContext::from_query("Dune Part Two 2024");
+        assert_eq!(context.episode, None);
+        assert_eq!(context.year, None);
+    }
+
     #[test]
     fn test_torrent_validation() {
         // Title + year validation
@@ -1221,4 +4026,288 @@ mod tests {
         assert!(v.matches("Spider-Man.No.Way.Home.2021.mkv"));
         assert!(v.matches("The.Amazing.Spider-Man.2021.mkv")); // "spider" matches
     }
+
+    #[test]
+    fn test_seed_policy_parse() {
+        assert_eq!("off".parse(), Ok(SeedPolicy::Off));
+        assert_eq!("minutes(30)".parse(), Ok(SeedPolicy::Minutes(30)));
+        assert_eq!(" Ratio( 1.5 ) ".parse(), Ok(SeedPolicy::Ratio(1.5)));
+        assert!("minutes(0)".parse::<SeedPolicy>().is_err());
+        assert!("ratio(-1)".parse::<SeedPolicy>().is_err());
+        assert!("forever".parse::<SeedPolicy>().is_err());
+
+        for policy in [SeedPolicy::Minutes(45), SeedPolicy::Ratio(2.0)] {
+            assert_eq!(policy.to_string().parse(), Ok(policy));
+        }
+    }
+
+    #[test]
+    fn test_seed_policy_is_met() {
+        let stats = TorrentStats {
+            uploaded_bytes: 500,
+            total_bytes: 1000,
+            ..Default::default()
+        };
+        let minute = Duration::from_secs(60);
+
+        assert!(!SeedPolicy::Minutes(10).is_met(minute * 9, &stats));
+        assert!(SeedPolicy::Minutes(10).is_met(minute * 10, &stats));
+        assert!(SeedPolicy::Ratio(0.5).is_met(Duration::ZERO, &stats));
+        assert!(!SeedPolicy::Ratio(1.0).is_met(minute * 600, &stats));
+        // Unknown size never reaches a ratio
+        assert!(!SeedPolicy::Ratio(0.5).is_met(minute, &TorrentStats::default()));
+    }
+
+    #[test]
+    fn test_parse_peer_stats() {
+        let json = serde_json::json!({
+            "peers": {
+                "10.0.0.1:6881": {"counters": {"fetched_bytes": 100}, "state": "live"},
+                "192.168.1.20:51413": {"counters": {"fetched_bytes": 300}, "state": "live"},
+                "[2001:db8::1]:6881": {"counters": {"fetched_bytes": 0}, "state": "dead"}
+            }
+        });
+        let peers = parse_peer_stats(&json);
+        assert_eq!(peers.len(), 3);
+        assert_eq!(peers[0].addr, "192.168.1.20:51413");
+        assert_eq!(peers[1].fetched_bytes, 100);
+        assert_eq!(peers[2].state, "dead");
+        assert!(parse_peer_stats(&serde_json::json!({})).is_empty());
+
+        let shares = peer_contributions(&peers);
+        assert_eq!(shares, vec![75.0, 25.0, 0.0]);
+        assert_eq!(peer_contributions(&peers[2..]), vec![0.0]);
+    }
+
+    #[test]
+    fn test_peer_masked_ip() {
+        let peer = |addr: &str| PeerInfo {
+            addr: addr.to_string(),
+            state: "live".to_string(),
+            fetched_bytes: 0,
+        };
+        assert_eq!(peer("81.171.4.20:6881").masked_ip(), "81.171.x.x");
+        assert_eq!(peer("[2001:db8::1]:6881").masked_ip(), "2001:db8:…");
+        assert_eq!(peer("81.171.4.20").masked_ip(), "81.171.x.x");
+        assert_eq!(peer("garbage").masked_ip(), "unknown");
+    }
+
+    #[test]
+    fn test_session_dir_name() {
+        let name = session_dir_name(1_700_000_000_123);
+        assert_eq!(
+            parse_session_dir_name(&name),
+            Some((1_700_000_000_123, Some(std::process::id())))
+        );
+        assert_eq!(parse_session_dir_name("42"), Some((42, None)));
+        assert_eq!(parse_session_dir_name("42-abc"), None);
+        assert_eq!(parse_session_dir_name("notes"), None);
+    }
+
+    #[test]
+    fn test_check_file_size() {
+        const MB: u64 = 1_000_000;
+        // A 2h 1080p movie needs about 1350 MB
+        assert!(check_file_size("Heat.1995.1080p.BluRay.x264.mkv", 1800 * MB, 120).is_ok());
+        assert!(check_file_size("Heat.1995.1080p.BluRay.x265.mkv", 1100 * MB, 120).is_ok());
+        let error = check_file_size("Heat.1995.1080p.BluRay.x264.mkv", 300 * MB, 120).unwrap_err();
+        assert!(matches!(
+            error,
+            StreamError::SuspiciousFile {
+                size_mb: 300,
+                expected_mb: 1350,
+                runtime_minutes: 120
+            }
+        ));
+
+        // Without a resolution only the leanest encodes are expected
+        assert!(check_file_size("Severance.S02E01.mkv", 250 * MB, 50).is_ok());
+        assert!(check_file_size("Severance.S02E01.2160p.mkv", 1500 * MB, 50).is_err());
+    }
+
+    #[test]
+    fn test_give_up_waiting() {
+        let max = Duration::from_secs(120);
+        let secs = Duration::from_secs;
+
+        assert!(give_up_waiting(secs(10), None, max).is_none());
+        // Peers found, or the daemon's DHT we can't see: wait up to the max
+        assert!(give_up_waiting(secs(90), None, max).is_none());
+        assert!(matches!(
+            give_up_waiting(secs(120), None, max),
+            Some(StreamError::MetadataTimeout)
+        ));
+        // Nobody has it
+        assert!(give_up_waiting(secs(40), Some(secs(20)), max).is_none());
+        assert!(matches!(
+            give_up_waiting(secs(40), Some(secs(30)), max),
+            Some(StreamError::NoPeers)
+        ));
+    }
+
+    #[test]
+    fn test_add_phase() {
+        // The UI keeps the furthest phase of the raced torrents
+        let phases = [
+            AddPhase::FindingPeers { found: 3 },
+            AddPhase::ResolvingMagnet,
+            AddPhase::FindingPeers { found: 12 },
+        ];
+        let furthest = phases.into_iter().max().unwrap();
+        assert_eq!(furthest, AddPhase::FindingPeers { found: 12 });
+        assert_eq!(furthest.to_string(), "Connecting to peers (12 found)");
+        assert!(AddPhase::Initializing > furthest);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_stale_sessions() {
+        let base =
+            std::env::temp_dir().join(format!("ferristream_stale_test_{}", std::process::id()));
+        let ours = base.join(session_dir_name(1));
+        let crashed = base.join("2-999999999");
+        let legacy_live = base.join("3");
+        let legacy_stale = base.join("4");
+        let older_crash = base.join("5-999999998");
+        // Someone else's folders in a shared temp dir
+        let foreign = base.join("holiday-photos");
+        let foreign_numbered = base.join("2023");
+        for dir in [
+            &older_crash,
+            &ours,
+            &crashed,
+            &legacy_live,
+            &legacy_stale,
+            &foreign,
+            &foreign_numbered,
+        ] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("video.mkv"), vec![0u8; 1000]).unwrap();
+        }
+        // A live session still answers on its port, one left by a crash doesn't
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::fs::write(legacy_live.join(SESSION_LOCK_FILE), port.to_string()).unwrap();
+        std::fs::write(legacy_stale.join(SESSION_LOCK_FILE), "1").unwrap();
+
+        // Directories without a pid only go once they're older than the run
+        let (found, total) = find_stale_sessions(&base, std::time::UNIX_EPOCH).await;
+        assert_eq!(found.len(), 2);
+        assert!(
+            found
+                .iter()
+                .all(|s| s.path == crashed || s.path == older_crash)
+        );
+        assert!(total >= 7000);
+
+        let run_started = std::time::SystemTime::now() + Duration::from_secs(60);
+        // Under the limit nothing goes
+        assert_eq!(
+            cleanup_stale_sessions(&base, run_started, total + 1)
+                .await
+                .0,
+            0
+        );
+        // One leftover is enough to get below the limit
+        let (removed, freed) = cleanup_stale_sessions(&base, run_started, total - 500).await;
+        assert_eq!(removed, 1);
+        assert!(freed >= 1000);
+        assert!(crashed.exists() != older_crash.exists());
+        // With no limit every leftover goes, live sessions and foreign
+        // directories stay
+        let (removed, _) = cleanup_stale_sessions(&base, run_started, 0).await;
+        assert_eq!(removed, 2);
+        assert!(ours.exists());
+        assert!(legacy_live.exists());
+        assert!(!crashed.exists());
+        assert!(!legacy_stale.exists());
+        assert!(foreign.exists());
+        assert!(foreign_numbered.exists());
+
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_is_session_dir() {
+        let base =
+            std::env::temp_dir().join(format!("ferristream_session_dir_{}", std::process::id()));
+        let tagged = base.join("17-4242");
+        // Sessions from before the pid was added are only known by their lock file
+        let locked = base.join("17");
+        let bare = base.join("18");
+        let other = base.join("downloads");
+        for dir in [&tagged, &locked, &bare, &other] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(locked.join(SESSION_LOCK_FILE), "1").unwrap();
+
+        assert!(is_session_dir(&tagged));
+        assert!(is_session_dir(&locked));
+        assert!(!is_session_dir(&bare));
+        assert!(!is_session_dir(&other));
+
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[tokio::test]
+    async fn test_temp_dir_free_space() {
+        let base =
+            std::env::temp_dir().join(format!("ferristream_space_test_{}", std::process::id()));
+        let nested = base.join("a").join("b");
+
+        // Read from the closest existing parent before the dir is created
+        assert!(free_space(&nested).await.is_some_and(|free| free > 0));
+        check_writable(&nested).unwrap();
+        assert!(nested.is_dir());
+        assert_eq!(std::fs::read_dir(&nested).unwrap().count(), 0);
+
+        // A file where a directory should go
+        let blocked = base.join("file");
+        std::fs::write(&blocked, "x").unwrap();
+        assert!(check_writable(&blocked.join("dir")).is_err());
+
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[tokio::test]
+    async fn test_pending_cleanup() {
+        let base =
+            std::env::temp_dir().join(format!("ferristream_pending_test_{}", std::process::id()));
+        let session = base.join(session_dir_name(1));
+        std::fs::create_dir_all(&session).unwrap();
+        std::fs::write(session.join("video.mkv"), vec![0u8; 1000]).unwrap();
+        // Stands in for a directory that can't be removed
+        let stuck = base.join("not-a-dir");
+        std::fs::write(&stuck, "x").unwrap();
+        let list = base.join("config.cleanup.json");
+
+        assert_eq!(
+            remove_dir_with_retry(&base.join("gone"), Duration::ZERO)
+                .await
+                .unwrap(),
+            0
+        );
+        let started = std::time::Instant::now();
+        assert!(
+            remove_dir_with_retry(&stuck, Duration::from_millis(300))
+                .await
+                .is_err()
+        );
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        PendingCleanup::defer(&list, &session);
+        PendingCleanup::defer(&list, &session);
+        PendingCleanup::defer(&list, &stuck);
+        assert_eq!(PendingCleanup::load(&list).dirs.len(), 2);
+
+        assert_eq!(PendingCleanup::retry(&list).await, (1, 1000));
+        assert!(!session.exists());
+        assert_eq!(PendingCleanup::load(&list).dirs, vec![stuck.clone()]);
+
+        // Nothing left to retry removes the list
+        std::fs::remove_file(&stuck).unwrap();
+        assert_eq!(PendingCleanup::retry(&list).await, (1, 0));
+        assert!(!list.exists());
+
+        std::fs::remove_dir_all(&base).ok();
+    }
 }
@@ -1,14 +1,34 @@
 use itertools::Itertools;
+use regex::Regex;
 use reqwest::Client;
 use serde::Deserialize;
+use std::sync::LazyLock;
 use thiserror::Error;
 use tracing::debug;
 
+use crate::http::send_with_retry;
+use crate::torznab::title_words;
+
 // Embedded API key for ferristream - this is allowed per TMDB terms for open source projects
 // Users can override with their own key in config if needed
 // At compile time, set TMDB_API_KEY env var to embed it, otherwise users must provide in config
 const EMBEDDED_API_KEY: Option<&str> = option_env!("TMDB_API_KEY");
 
+const ANIMATION_GENRE_ID: u32 = 16;
+/// Talk and news shows, where people appear as themselves
+const TALK_GENRE_IDS: [u32; 2] = [10767, 10763];
+
+/// Votes a credit needs to make a whole filmography, which leaves out one-off
+/// guest appearances
+const FILMOGRAPHY_MIN_VOTES: u32 = 20;
+
+/// TMDB release type for DVD and Blu-ray releases
+const PHYSICAL_RELEASE_TYPE: u32 = 5;
+/// When 4K Blu-ray came out, discs released since usually have a 4K version
+const UHD_BLURAY_LAUNCH: &str = "2016-03-01";
+/// Votes a movie needs to count as popular enough to get a 4K disc
+const UHD_MIN_VOTES: u32 = 300;
+
 #[derive(Error, Debug)]
 pub enum TmdbError {
     #[error("request failed: {0}")]
@@ -26,9 +46,19 @@ pub struct SearchResult {
     pub release_date: Option<String>,   // Movies
     pub first_air_date: Option<String>, // TV shows
     pub vote_average: Option<f64>,
+    #[serde(default)]
+    pub vote_count: u32,
+    #[serde(default)]
+    pub popularity: f64,
     pub poster_path: Option<String>,
     pub backdrop_path: Option<String>,
     pub media_type: Option<String>,
+    #[serde(default)]
+    pub genre_ids: Vec<u32>,
+    #[serde(default)]
+    pub origin_country: Vec<String>, // TV shows
+    #[serde(default)]
+    pub adult: Option<bool>,
 }
 
 impl SearchResult {
@@ -47,11 +77,56 @@ impl SearchResult {
         date.split('-').next()?.parse().ok()
     }
 
+    /// Japanese animated TV show
+    pub fn is_anime(&self) -> bool {
+        let is_tv = match self.media_type.as_deref() {
+            Some(media_type) => media_type == "tv",
+            None => self.name.is_some(),
+        };
+        is_tv
+            && self.genre_ids.contains(&ANIMATION_GENRE_ID)
+            && self.origin_country.iter().any(|c| c == "JP")
+    }
+
     pub fn poster_url(&self, size: &str) -> Option<String> {
         self.poster_path
             .as_ref()
             .map(|p| format!("https://image.tmdb.org/t/p/{}{}", size, p))
     }
+
+    pub fn backdrop_url(&self, size: &str) -> Option<String> {
+        self.backdrop_path
+            .as_ref()
+            .map(|p| format!("https://image.tmdb.org/t/p/{}{}", size, p))
+    }
+}
+
+/// Votes or popularity a match needs before it's worth asking about
+const AMBIGUOUS_MIN_VOTES: u32 = 200;
+const AMBIGUOUS_MIN_POPULARITY: f64 = 10.0;
+
+/// Movies/shows sharing the top match's title that are well known enough to be
+/// what the user meant (e.g. both "Dune" films). Empty unless there's a real choice.
+pub fn ambiguous_matches(results: &[SearchResult]) -> Vec<&SearchResult> {
+    let normalize = |title: &str| title_words(title).join(" ");
+    let candidates: Vec<&SearchResult> = results
+        .iter()
+        .filter(|r| matches!(r.media_type.as_deref(), Some("movie") | Some("tv") | None))
+        .collect();
+    let Some(first) = candidates.first() else {
+        return Vec::new();
+    };
+    let title = normalize(first.display_title());
+    let matches: Vec<&SearchResult> = candidates
+        .into_iter()
+        .filter(|r| normalize(r.display_title()) == title)
+        .filter(|r| r.vote_count >= AMBIGUOUS_MIN_VOTES || r.popularity >= AMBIGUOUS_MIN_POPULARITY)
+        .collect();
+    if matches.len() > 1 {
+        matches
+    } else {
+        Vec::new()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -59,6 +134,17 @@ struct SearchResponse {
     results: Vec<SearchResult>,
 }
 
+impl SearchResponse {
+    /// The results without anything flagged adult, which `include_adult=false`
+    /// doesn't keep out of every endpoint
+    fn into_results(self) -> Vec<SearchResult> {
+        self.results
+            .into_iter()
+            .filter(|r| r.adult != Some(true))
+            .collect()
+    }
+}
+
 /// TV show details including seasons
 #[derive(Debug, Clone, Deserialize)]
 pub struct TvDetails {
@@ -126,6 +212,205 @@ impl Episode {
             show_name, self.season_number, self.episode_number
         )
     }
+
+    /// Format for Prowlarr search query with absolute (anime) numbering, e.g. "Naruto 148"
+    pub fn absolute_search_query(&self, show_name: &str) -> String {
+        format!("{} {:02}", show_name, self.episode_number)
+    }
+
+    /// Whether the episode is out by `today` (days since 1970-01-01)
+    pub fn air_status(&self, today: i64) -> AirStatus {
+        match self.air_date.as_deref().and_then(parse_date) {
+            Some(day) if day > today => AirStatus::Upcoming { days: day - today },
+            Some(_) => AirStatus::Aired,
+            None => AirStatus::Unknown,
+        }
+    }
+}
+
+/// When an episode airs, relative to today
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AirStatus {
+    /// Aired today or earlier
+    Aired,
+    /// Airs in this many days
+    Upcoming { days: i64 },
+    /// No air date listed, usually announced but not scheduled
+    Unknown,
+}
+
+/// Days since 1970-01-01 of a "YYYY-MM-DD" date. Air dates have no time
+/// zone, so neither does this.
+pub fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.get(..10)?.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Howard Hinnant's days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some(era * 146_097 + doe - 719_468)
+}
+
+/// Today in days since 1970-01-01, by the UTC date
+pub fn today() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| (d.as_secs() / 86_400) as i64)
+        .unwrap_or(0)
+}
+
+/// Image of a title or episode, as listed by the images endpoints
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImageInfo {
+    pub file_path: String,
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32,
+    #[serde(default)]
+    pub vote_average: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpisodeImages {
+    #[serde(default)]
+    stills: Vec<ImageInfo>,
+}
+
+/// Collection a movie belongs to (e.g. "The Lord of the Rings Collection")
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CollectionSummary {
+    pub id: u64,
+    pub name: String,
+}
+
+/// Movie details, only the fields we use
+#[derive(Debug, Clone, Deserialize)]
+pub struct MovieDetails {
+    pub id: u64,
+    pub title: String,
+    pub release_date: Option<String>,
+    /// Minutes
+    pub runtime: Option<u32>,
+    pub belongs_to_collection: Option<CollectionSummary>,
+}
+
+/// Ids of a movie on other sites, only the fields we use
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExternalIds {
+    /// e.g. "tt0111161", None (or empty) when TMDB doesn't know it
+    pub imdb_id: Option<String>,
+}
+
+impl ExternalIds {
+    pub fn imdb_id(&self) -> Option<&str> {
+        self.imdb_id.as_deref().filter(|id| !id.is_empty())
+    }
+}
+
+/// Movie franchise with its movies in release order
+#[derive(Debug, Clone, Deserialize)]
+pub struct Collection {
+    pub id: u64,
+    pub name: String,
+    pub overview: Option<String>,
+    #[serde(default)]
+    pub parts: Vec<SearchResult>,
+}
+
+/// Person (actor, director, ...) from a person search
+#[derive(Debug, Clone, Deserialize)]
+pub struct PersonResult {
+    pub id: u64,
+    pub name: String,
+    pub known_for_department: Option<String>, // "Acting", "Directing", ...
+    pub profile_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PersonSearchResponse {
+    results: Vec<PersonResult>,
+}
+
+/// Crew credit - a search result plus the job the person had on it
+#[derive(Debug, Clone, Deserialize)]
+pub struct CrewCredit {
+    #[serde(flatten)]
+    pub result: SearchResult,
+    pub job: Option<String>,
+}
+
+/// Movie and TV credits of a person (from combined_credits)
+#[derive(Debug, Clone, Deserialize)]
+pub struct PersonCredits {
+    #[serde(default)]
+    pub cast: Vec<SearchResult>,
+    #[serde(default)]
+    pub crew: Vec<CrewCredit>,
+}
+
+impl PersonCredits {
+    /// Credits for the given role, without duplicates (actors show up once
+    /// per character, crew once per job). Newest first for a role, most
+    /// popular first for `Any`, which also leaves out talk shows and guest spots.
+    pub fn for_role(&self, role: PersonRole) -> Vec<SearchResult> {
+        let credits: Vec<SearchResult> = match role {
+            PersonRole::Actor => self.cast.clone(),
+            PersonRole::Director => self
+                .crew
+                .iter()
+                .filter(|c| c.job.as_deref() == Some("Director"))
+                .map(|c| c.result.clone())
+                .collect(),
+            PersonRole::Any => self
+                .cast
+                .iter()
+                .chain(self.crew.iter().map(|c| &c.result))
+                .filter(|r| r.vote_count >= FILMOGRAPHY_MIN_VOTES)
+                .filter(|r| !r.genre_ids.iter().any(|g| TALK_GENRE_IDS.contains(g)))
+                .cloned()
+                .collect(),
+        };
+
+        let mut credits: Vec<SearchResult> = credits
+            .into_iter()
+            .unique_by(|r| (r.id, r.media_type.clone()))
+            .collect();
+        match role {
+            PersonRole::Any => credits.sort_by(|a, b| b.popularity.total_cmp(&a.popularity)),
+            _ => credits.sort_by_key(|r| std::cmp::Reverse(r.year())),
+        }
+        credits
+    }
+}
+
+/// Which credits to show for a person search
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PersonRole {
+    Actor,
+    Director,
+    /// Everything they're known for, when picked from a plain search
+    Any,
+}
+
+/// Split an `actor: name` / `director: name` search query into role and name
+pub fn parse_person_query(query: &str) -> Option<(PersonRole, &str)> {
+    let (prefix, name) = query.split_once(':')?;
+    let role = match prefix.trim().to_lowercase().as_str() {
+        "actor" => PersonRole::Actor,
+        "director" => PersonRole::Director,
+        _ => return None,
+    };
+    Some((role, name.trim()))
 }
 
 pub struct TmdbClient {
@@ -148,7 +433,7 @@ impl TmdbClient {
             .or_else(|| EMBEDDED_API_KEY.map(String::from))?;
 
         Some(Self {
-            client: Client::new(),
+            client: crate::http::client(),
             api_key,
             base_url: base_url.to_string(),
         })
@@ -165,9 +450,9 @@ impl TmdbClient {
 
         debug!(query, "searching TMDB");
 
-        let response: SearchResponse = self.client.get(&url).send().await?.json().await?;
+        let response: SearchResponse = send_with_retry(self.client.get(&url)).await?.json().await?;
 
-        Ok(response.results)
+        Ok(response.into_results())
     }
 
     /// Search for movies only
@@ -177,7 +462,7 @@ impl TmdbClient {
         year: Option<u16>,
     ) -> Result<Vec<SearchResult>, TmdbError> {
         let mut url = format!(
-            "{}/3/search/movie?api_key={}&query={}",
+            "{}/3/search/movie?api_key={}&query={}&include_adult=false",
             self.base_url,
             self.api_key,
             urlencoding::encode(query)
@@ -187,9 +472,9 @@ impl TmdbClient {
             url.push_str(&format!("&year={}", y));
         }
 
-        let response: SearchResponse = self.client.get(&url).send().await?.json().await?;
+        let response: SearchResponse = send_with_retry(self.client.get(&url)).await?.json().await?;
 
-        Ok(response.results)
+        Ok(response.into_results())
     }
 
     /// Search for TV shows only
@@ -199,7 +484,7 @@ impl TmdbClient {
         year: Option<u16>,
     ) -> Result<Vec<SearchResult>, TmdbError> {
         let mut url = format!(
-            "{}/3/search/tv?api_key={}&query={}",
+            "{}/3/search/tv?api_key={}&query={}&include_adult=false",
             self.base_url,
             self.api_key,
             urlencoding::encode(query)
@@ -209,18 +494,98 @@ impl TmdbClient {
             url.push_str(&format!("&first_air_date_year={}", y));
         }
 
-        let response: SearchResponse = self.client.get(&url).send().await?.json().await?;
+        let response: SearchResponse = send_with_retry(self.client.get(&url)).await?.json().await?;
+
+        Ok(response.into_results())
+    }
+
+    /// Search for people (actors, directors, ...)
+    pub async fn search_person(&self, query: &str) -> Result<Vec<PersonResult>, TmdbError> {
+        let url = format!(
+            "{}/3/search/person?api_key={}&query={}&include_adult=false",
+            self.base_url,
+            self.api_key,
+            urlencoding::encode(query)
+        );
+
+        debug!(query, "searching TMDB people");
+
+        let response: PersonSearchResponse =
+            send_with_retry(self.client.get(&url)).await?.json().await?;
 
         Ok(response.results)
     }
 
+    /// Get the movie and TV credits of a person
+    pub async fn get_person_credits(&self, person_id: u64) -> Result<PersonCredits, TmdbError> {
+        let url = format!(
+            "{}/3/person/{}/combined_credits?api_key={}",
+            self.base_url, person_id, self.api_key
+        );
+
+        debug!(person_id, "fetching person credits");
+
+        let response: PersonCredits = send_with_retry(self.client.get(&url)).await?.json().await?;
+
+        Ok(response)
+    }
+
+    /// Get movie details, including the collection it belongs to
+    pub async fn get_movie_details(&self, movie_id: u64) -> Result<MovieDetails, TmdbError> {
+        let url = format!(
+            "{}/3/movie/{}?api_key={}",
+            self.base_url, movie_id, self.api_key
+        );
+
+        debug!(movie_id, "fetching movie details");
+
+        let response: MovieDetails = send_with_retry(self.client.get(&url)).await?.json().await?;
+
+        Ok(response)
+    }
+
+    /// Get the IMDB id and other external ids of a movie
+    pub async fn get_external_ids(&self, movie_id: u64) -> Result<ExternalIds, TmdbError> {
+        let url = format!(
+            "{}/3/movie/{}/external_ids?api_key={}",
+            self.base_url, movie_id, self.api_key
+        );
+
+        debug!(movie_id, "fetching movie external ids");
+
+        let response: ExternalIds = send_with_retry(self.client.get(&url)).await?.json().await?;
+
+        Ok(response)
+    }
+
+    /// Get a collection with its movies in release order (unreleased ones last)
+    pub async fn get_collection(&self, collection_id: u64) -> Result<Collection, TmdbError> {
+        let url = format!(
+            "{}/3/collection/{}?api_key={}",
+            self.base_url, collection_id, self.api_key
+        );
+
+        debug!(collection_id, "fetching collection");
+
+        let mut response: Collection = send_with_retry(self.client.get(&url)).await?.json().await?;
+        response.parts.iter_mut().for_each(|part| {
+            part.media_type = Some("movie".to_string());
+        });
+        response.parts.sort_by_key(|part| {
+            let date = part.release_date.clone().filter(|d| !d.is_empty());
+            (date.is_none(), date)
+        });
+
+        Ok(response)
+    }
+
     /// Get TV show details including list of seasons
     pub async fn get_tv_details(&self, tv_id: u64) -> Result<TvDetails, TmdbError> {
         let url = format!("{}/3/tv/{}?api_key={}", self.base_url, tv_id, self.api_key);
 
         debug!(tv_id, "fetching TV details");
 
-        let response: TvDetails = self.client.get(&url).send().await?.json().await?;
+        let response: TvDetails = send_with_retry(self.client.get(&url)).await?.json().await?;
 
         Ok(response)
     }
@@ -238,11 +603,41 @@ impl TmdbClient {
 
         debug!(tv_id, season_number, "fetching season details");
 
-        let response: SeasonDetails = self.client.get(&url).send().await?.json().await?;
+        let response: SeasonDetails = send_with_retry(self.client.get(&url)).await?.json().await?;
 
         Ok(response)
     }
 
+    /// URL of the largest still of an episode, None if TMDB has none or the lookup fails
+    pub async fn get_episode_still(
+        &self,
+        tv_id: u64,
+        season_number: u32,
+        episode_number: u32,
+    ) -> Option<String> {
+        let url = format!(
+            "{}/3/tv/{}/season/{}/episode/{}/images?api_key={}",
+            self.base_url, tv_id, season_number, episode_number, self.api_key
+        );
+
+        debug!(
+            tv_id,
+            season_number, episode_number, "fetching episode stills"
+        );
+
+        let response = send_with_retry(self.client.get(&url)).await.ok()?;
+        let images: EpisodeImages = response.error_for_status().ok()?.json().await.ok()?;
+        images
+            .stills
+            .into_iter()
+            .max_by(|a, b| {
+                (a.width, a.height)
+                    .cmp(&(b.width, b.height))
+                    .then(a.vote_average.total_cmp(&b.vote_average))
+            })
+            .map(|still| format!("https://image.tmdb.org/t/p/original{}", still.file_path))
+    }
+
     /// Get trending content (movies + TV)
     pub async fn get_trending(
         &self,
@@ -250,15 +645,15 @@ impl TmdbClient {
         time_window: &str,
     ) -> Result<Vec<SearchResult>, TmdbError> {
         let url = format!(
-            "{}/3/trending/{}/{}?api_key={}",
+            "{}/3/trending/{}/{}?api_key={}&include_adult=false",
             self.base_url, media_type, time_window, self.api_key
         );
 
         debug!(media_type, time_window, "fetching trending content");
 
-        let response: SearchResponse = self.client.get(&url).send().await?.json().await?;
+        let response: SearchResponse = send_with_retry(self.client.get(&url)).await?.json().await?;
 
-        Ok(response.results)
+        Ok(response.into_results())
     }
 
     /// Helper function to fetch a TMDB list endpoint
@@ -267,12 +662,15 @@ impl TmdbClient {
         path: &str,
         debug_message: &str,
     ) -> Result<Vec<SearchResult>, TmdbError> {
-        let url = format!("{}{}?api_key={}", self.base_url, path, self.api_key);
+        let url = format!(
+            "{}{}?api_key={}&include_adult=false",
+            self.base_url, path, self.api_key
+        );
 
         debug!(debug_message);
 
-        let response: SearchResponse = self.client.get(&url).send().await?.json().await?;
-        Ok(response.results)
+        let response: SearchResponse = send_with_retry(self.client.get(&url)).await?.json().await?;
+        Ok(response.into_results())
     }
 
     /// Get popular movies
@@ -303,13 +701,13 @@ impl TmdbClient {
     pub async fn discover_mixed(&self) -> Result<Vec<SearchResult>, TmdbError> {
         // Get movies
         let movies_url = format!(
-            "{}/3/discover/movie?api_key={}&sort_by=popularity.desc",
+            "{}/3/discover/movie?api_key={}&sort_by=popularity.desc&include_adult=false",
             self.base_url, self.api_key
         );
 
         // Get TV shows
         let tv_url = format!(
-            "{}/3/discover/tv?api_key={}&sort_by=popularity.desc",
+            "{}/3/discover/tv?api_key={}&sort_by=popularity.desc&include_adult=false",
             self.base_url, self.api_key
         );
 
@@ -318,17 +716,13 @@ impl TmdbClient {
         // Fetch both in parallel
         let (movies_response, tv_response) = tokio::try_join!(
             async {
-                self.client
-                    .get(&movies_url)
-                    .send()
+                send_with_retry(self.client.get(&movies_url))
                     .await?
                     .json::<SearchResponse>()
                     .await
             },
             async {
-                self.client
-                    .get(&tv_url)
-                    .send()
+                send_with_retry(self.client.get(&tv_url))
                     .await?
                     .json::<SearchResponse>()
                     .await
@@ -337,13 +731,35 @@ impl TmdbClient {
 
         // Interleave results (movie, tv, movie, tv, ...)
         let results: Vec<_> = movies_response
-            .results
+            .into_results()
             .into_iter()
-            .interleave(tv_response.results.into_iter())
+            .interleave(tv_response.into_results())
             .collect();
 
         Ok(results)
     }
+
+    /// Discover movies that are likely out in 4K HDR. TMDB has no resolution
+    /// filter, so this asks for popular movies with a disc release since 4K
+    /// Blu-ray launched.
+    pub async fn discover_4k_movies(&self) -> Result<Vec<SearchResult>, TmdbError> {
+        let url = format!(
+            "{}/3/discover/movie?api_key={}&sort_by=popularity.desc&include_adult=false&with_release_type={}&release_date.gte={}&vote_count.gte={}",
+            self.base_url, self.api_key, PHYSICAL_RELEASE_TYPE, UHD_BLURAY_LAUNCH, UHD_MIN_VOTES
+        );
+
+        debug!("fetching 4K movies");
+
+        let mut results = send_with_retry(self.client.get(&url))
+            .await?
+            .json::<SearchResponse>()
+            .await?
+            .into_results();
+        results
+            .iter_mut()
+            .for_each(|r| r.media_type = Some("movie".to_string()));
+        Ok(results)
+    }
 }
 
 /// Try to extract a clean title and year from a torrent name
@@ -434,6 +850,26 @@ pub fn parse_torrent_title(torrent_name: &str) -> (String, Option<u16>) {
     (title, year)
 }
 
+/// Season or episode tag of a series release, like S02E01 or S02
+static SERIES_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(^|[ ._\-\[(])s\d{1,2}(e\d{1,3})?([ ._\-\])]|$)").unwrap());
+
+/// Title and year of a release for a TMDB lookup, and whether it's a series
+/// (tagged with an episode or season like S02E01 or S02). The title is cut
+/// at the tag, which is where show names end.
+pub fn parse_release_name(torrent_name: &str) -> (String, Option<u16>, bool) {
+    match SERIES_TAG.find(torrent_name) {
+        Some(found) => {
+            let (title, year) = parse_torrent_title(&torrent_name[..found.start()]);
+            (title, year, true)
+        }
+        None => {
+            let (title, year) = parse_torrent_title(torrent_name);
+            (title, year, false)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -451,6 +887,24 @@ mod tests {
         assert_eq!(year, Some(1999));
     }
 
+    #[test]
+    fn test_parse_release_name() {
+        assert_eq!(
+            parse_release_name("Severance.S02E01.1080p.WEB.H264-SuccessfulCrab"),
+            ("Severance".to_string(), None, true)
+        );
+        assert_eq!(
+            parse_release_name("The Office (US) S03 Complete 720p"),
+            ("The Office (us)".to_string(), None, true)
+        );
+        assert_eq!(
+            parse_release_name("Inception.2010.1080p.BluRay.x264"),
+            ("Inception".to_string(), Some(2010), false)
+        );
+        // A word starting with s and digits isn't a season
+        assert!(!parse_release_name("Se7en.1995.1080p").2);
+    }
+
     #[test]
     fn test_parse_torrent_title_underscores() {
         let (title, year) = parse_torrent_title("Inception_2010_720p_BluRay");
@@ -500,9 +954,14 @@ mod tests {
             release_date: None,
             first_air_date: None,
             vote_average: None,
+            vote_count: 0,
+            popularity: 0.0,
             poster_path: None,
             backdrop_path: None,
             media_type: Some("movie".to_string()),
+            genre_ids: Vec::new(),
+            origin_country: Vec::new(),
+            adult: None,
         };
         assert_eq!(movie.display_title(), "The Matrix");
 
@@ -514,9 +973,14 @@ mod tests {
             release_date: None,
             first_air_date: None,
             vote_average: None,
+            vote_count: 0,
+            popularity: 0.0,
             poster_path: None,
             backdrop_path: None,
             media_type: Some("tv".to_string()),
+            genre_ids: Vec::new(),
+            origin_country: Vec::new(),
+            adult: None,
         };
         assert_eq!(tv.display_title(), "Breaking Bad");
 
@@ -528,9 +992,14 @@ mod tests {
             release_date: None,
             first_air_date: None,
             vote_average: None,
+            vote_count: 0,
+            popularity: 0.0,
             poster_path: None,
             backdrop_path: None,
             media_type: None,
+            genre_ids: Vec::new(),
+            origin_country: Vec::new(),
+            adult: None,
         };
         assert_eq!(unknown.display_title(), "Unknown");
     }
@@ -545,9 +1014,14 @@ mod tests {
             release_date: Some("2023-05-15".to_string()),
             first_air_date: None,
             vote_average: None,
+            vote_count: 0,
+            popularity: 0.0,
             poster_path: None,
             backdrop_path: None,
             media_type: Some("movie".to_string()),
+            genre_ids: Vec::new(),
+            origin_country: Vec::new(),
+            adult: None,
         };
         assert_eq!(movie.year(), Some(2023));
 
@@ -559,9 +1033,14 @@ mod tests {
             release_date: None,
             first_air_date: Some("2020-01-01".to_string()),
             vote_average: None,
+            vote_count: 0,
+            popularity: 0.0,
             poster_path: None,
             backdrop_path: None,
             media_type: Some("tv".to_string()),
+            genre_ids: Vec::new(),
+            origin_country: Vec::new(),
+            adult: None,
         };
         assert_eq!(tv.year(), Some(2020));
 
@@ -573,9 +1052,14 @@ mod tests {
             release_date: None,
             first_air_date: None,
             vote_average: None,
+            vote_count: 0,
+            popularity: 0.0,
             poster_path: None,
             backdrop_path: None,
             media_type: None,
+            genre_ids: Vec::new(),
+            origin_country: Vec::new(),
+            adult: None,
         };
         assert_eq!(no_date.year(), None);
     }
@@ -590,9 +1074,14 @@ mod tests {
             release_date: None,
             first_air_date: None,
             vote_average: None,
+            vote_count: 0,
+            popularity: 0.0,
             poster_path: Some("/abc123.jpg".to_string()),
             backdrop_path: None,
             media_type: None,
+            genre_ids: Vec::new(),
+            origin_country: Vec::new(),
+            adult: None,
         };
         assert_eq!(
             with_poster.poster_url("w500"),
@@ -607,10 +1096,175 @@ mod tests {
             release_date: None,
             first_air_date: None,
             vote_average: None,
+            vote_count: 0,
+            popularity: 0.0,
             poster_path: None,
             backdrop_path: None,
             media_type: None,
+            genre_ids: Vec::new(),
+            origin_country: Vec::new(),
+            adult: None,
         };
         assert_eq!(no_poster.poster_url("w500"), None);
     }
+
+    #[test]
+    fn test_search_result_is_anime() {
+        let mut show: SearchResult = serde_json::from_str(
+            r#"{"id": 46260, "name": "Naruto", "media_type": "tv", "genre_ids": [16, 10759], "origin_country": ["JP"]}"#,
+        )
+        .unwrap();
+        assert!(show.is_anime());
+
+        show.origin_country = vec!["US".to_string()];
+        assert!(!show.is_anime());
+
+        let movie: SearchResult = serde_json::from_str(
+            r#"{"id": 129, "title": "Spirited Away", "media_type": "movie", "genre_ids": [16], "origin_country": ["JP"]}"#,
+        )
+        .unwrap();
+        assert!(!movie.is_anime());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2000-02-29"), Some(11_016));
+        assert_eq!(parse_date("2024-03-09"), Some(19_791));
+        assert_eq!(parse_date("2024-03-09T16:00:00Z"), Some(19_791));
+        assert_eq!(parse_date(""), None);
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("soon"), None);
+    }
+
+    #[test]
+    fn test_episode_air_status() {
+        let mut episode = Episode {
+            id: 1,
+            name: "Pilot".to_string(),
+            episode_number: 1,
+            season_number: 1,
+            air_date: Some("2024-03-09".to_string()),
+            overview: None,
+            still_path: None,
+            runtime: None,
+            vote_average: None,
+        };
+        let today = parse_date("2024-03-09").unwrap();
+
+        assert_eq!(episode.air_status(today), AirStatus::Aired);
+        assert_eq!(
+            episode.air_status(today - 12),
+            AirStatus::Upcoming { days: 12 }
+        );
+
+        episode.air_date = None;
+        assert_eq!(episode.air_status(today), AirStatus::Unknown);
+    }
+
+    #[test]
+    fn test_parse_person_query() {
+        assert_eq!(
+            parse_person_query("actor: Cillian Murphy"),
+            Some((PersonRole::Actor, "Cillian Murphy"))
+        );
+        assert_eq!(
+            parse_person_query("Director:Christopher Nolan"),
+            Some((PersonRole::Director, "Christopher Nolan"))
+        );
+        assert_eq!(parse_person_query("actor:"), Some((PersonRole::Actor, "")));
+        assert_eq!(parse_person_query("Star Wars: Andor"), None);
+        assert_eq!(parse_person_query("Oppenheimer"), None);
+    }
+
+    #[test]
+    fn test_person_credits_for_role() {
+        let credits: PersonCredits = serde_json::from_str(
+            r#"{
+                "cast": [
+                    {"id": 1, "title": "Old Movie", "release_date": "2005-06-15", "media_type": "movie"},
+                    {"id": 2, "name": "Show", "first_air_date": "2013-09-12", "media_type": "tv"},
+                    {"id": 2, "name": "Show", "first_air_date": "2013-09-12", "media_type": "tv"},
+                    {"id": 3, "title": "New Movie", "release_date": "2023-07-19", "media_type": "movie"}
+                ],
+                "crew": [
+                    {"id": 4, "title": "Directed", "release_date": "2010-07-16", "media_type": "movie", "job": "Director"},
+                    {"id": 3, "title": "New Movie", "release_date": "2023-07-19", "media_type": "movie", "job": "Producer"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let acting: Vec<u64> = credits
+            .for_role(PersonRole::Actor)
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(acting, vec![3, 2, 1]);
+
+        let directing = credits.for_role(PersonRole::Director);
+        assert_eq!(directing.len(), 1);
+        assert_eq!(directing[0].display_title(), "Directed");
+    }
+
+    #[test]
+    fn test_person_credits_any_role() {
+        let credits: PersonCredits = serde_json::from_str(
+            r#"{
+                "cast": [
+                    {"id": 1, "title": "Hit", "media_type": "movie", "vote_count": 9000, "popularity": 40.0},
+                    {"id": 2, "name": "Late Night", "media_type": "tv", "vote_count": 400, "popularity": 90.0, "genre_ids": [10767]},
+                    {"id": 3, "name": "Guest Spot", "media_type": "tv", "vote_count": 4, "popularity": 5.0},
+                    {"id": 4, "name": "Series", "media_type": "tv", "vote_count": 2000, "popularity": 60.0}
+                ],
+                "crew": [
+                    {"id": 1, "title": "Hit", "media_type": "movie", "vote_count": 9000, "popularity": 40.0, "job": "Producer"},
+                    {"id": 5, "title": "Debut", "media_type": "movie", "vote_count": 300, "popularity": 12.0, "job": "Director"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        // Both roles, most popular first, without talk shows or guest spots
+        let ids: Vec<u64> = credits
+            .for_role(PersonRole::Any)
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, vec![4, 1, 5]);
+    }
+
+    #[test]
+    fn test_ambiguous_matches() {
+        let result = |id: u64, title: &str, date: &str, votes: u32| SearchResult {
+            id,
+            title: Some(title.to_string()),
+            name: None,
+            overview: None,
+            release_date: Some(date.to_string()),
+            first_air_date: None,
+            vote_average: None,
+            vote_count: votes,
+            popularity: 0.0,
+            poster_path: None,
+            backdrop_path: None,
+            media_type: Some("movie".to_string()),
+            genre_ids: Vec::new(),
+            origin_country: Vec::new(),
+            adult: None,
+        };
+
+        let results = vec![
+            result(1, "Dune", "1984-12-14", 2500),
+            result(2, "Dune: Part Two", "2024-02-27", 6000),
+            result(3, "Dune", "2021-09-15", 12000),
+            result(4, "DUNE", "2019-01-01", 3), // Obscure short, not worth asking about
+        ];
+        let ids: Vec<u64> = ambiguous_matches(&results).iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+
+        // A single well-known match needs no prompt
+        assert!(ambiguous_matches(&results[..2]).is_empty());
+        assert!(ambiguous_matches(&[]).is_empty());
+    }
 }
@@ -1,7 +1,23 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
 use quick_xml::Reader;
-use quick_xml::events::Event;
+use quick_xml::events::{BytesStart, Event};
 use reqwest::Client;
 use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::http::send_with_retry;
+use crate::streaming::SearchContext;
+use crate::tmdb::parse_torrent_title;
+
+/// How long fetched caps are trusted, they rarely change
+const CAPS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long to wait before asking an indexer that failed `t=caps` again
+const CAPS_RETRY: Duration = Duration::from_secs(10 * 60);
 
 #[derive(Error, Debug)]
 pub enum TorznabError {
@@ -13,7 +29,7 @@ pub enum TorznabError {
     InvalidResponse(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TorrentResult {
     pub title: String,
     pub link: Option<String>,
@@ -23,6 +39,10 @@ pub struct TorrentResult {
     pub seeders: Option<u32>,
     pub leechers: Option<u32>,
     pub indexer: String,
+    /// Newznab category IDs reported by the indexer (e.g. 2040 = Movies/HD)
+    pub categories: Vec<u32>,
+    /// Found on a Usenet (Newznab) indexer, `link` is then the NZB
+    pub usenet: bool,
 }
 
 impl TorrentResult {
@@ -42,8 +62,12 @@ impl TorrentResult {
     }
 
     /// Get a URL that librqbit can use
-    /// magnet, infohash-based magnet, or .torrent URL
+    /// magnet, infohash-based magnet, or .torrent URL. None for Usenet results
     pub fn get_torrent_url(&self) -> Option<String> {
+        if self.usenet {
+            return None;
+        }
+
         // prefer magnet url if available
         if let Some(ref magnet) = self.magnet_url {
             return Some(magnet.clone());
@@ -70,14 +94,513 @@ impl TorrentResult {
         None
     }
 
+    /// The NZB of a Usenet result, for the configured downloader
+    pub fn nzb_url(&self) -> Option<String> {
+        self.link.clone().filter(|_| self.usenet)
+    }
+
     /// Check if this result can be streamed
     pub fn is_streamable(&self) -> bool {
         self.magnet_url.is_some() || self.infohash.is_some() || self.link.is_some()
     }
+
+    /// Whether this is a TV release: by its TV category (5xxx) or Movies
+    /// category (2xxx), or by an episode tag in the title when it has neither
+    pub fn is_tv(&self) -> bool {
+        let top_level = |top: u32| self.categories.iter().any(|c| c / 1000 * 1000 == top);
+        if top_level(5000) {
+            true
+        } else if top_level(2000) {
+            false
+        } else {
+            crate::tmdb::parse_release_name(&self.title).2
+        }
+    }
+}
+
+/// Newznab categories that contain video (Movies 2xxx, TV 5xxx)
+const VIDEO_CATEGORIES: &[u32] = &[2000, 5000];
+
+/// Categories at or above this are indexer-specific and carry no standard meaning
+const CUSTOM_CATEGORY_START: u32 = 100_000;
+
+/// Newznab XXX categories, 6000-6999
+const ADULT_CATEGORIES: std::ops::Range<u32> = 6000..7000;
+
+/// Title words of adult releases, caught in safe mode whatever their category
+const ADULT_TERMS: &[&str] = &[
+    "xxx",
+    "porn",
+    "porno",
+    "hentai",
+    "nsfw",
+    "onlyfans",
+    "brazzers",
+    "bangbros",
+    "realitykings",
+];
+
+/// Why a result was dropped by [`ResultFilter`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterReason {
+    /// Adult release in safe mode, never shown even among filtered results
+    Adult,
+    NotStreamable,
+    Category,
+    BlockedTerm,
+    Size,
+}
+
+/// Post-search filter that drops junk results indexers return despite the category filter
+#[derive(Debug, Clone)]
+pub struct ResultFilter {
+    /// Terms (lowercase) that reject a result when they appear as a word in its title
+    pub blocked_terms: Vec<String>,
+    /// Minimum plausible size for a video release in bytes
+    pub min_size: Option<u64>,
+    /// Maximum plausible size for a video release in bytes
+    pub max_size: Option<u64>,
+    /// Drop results without a magnet, infohash or download link
+    pub require_streamable: bool,
+    /// Top-level Newznab categories to keep, e.g. 2000 keeps all of 2xxx
+    pub categories: Vec<u32>,
+    /// Drop adult releases by category and title
+    pub safe_mode: bool,
+}
+
+impl Default for ResultFilter {
+    fn default() -> Self {
+        Self {
+            blocked_terms: Vec::new(),
+            min_size: None,
+            max_size: None,
+            require_streamable: true,
+            categories: VIDEO_CATEGORIES.to_vec(),
+            safe_mode: false,
+        }
+    }
 }
 
+impl ResultFilter {
+    /// Check a single result, returning the reason it should be hidden
+    pub fn check(&self, result: &TorrentResult) -> Option<FilterReason> {
+        if self.safe_mode && is_adult(result) {
+            return Some(FilterReason::Adult);
+        }
+
+        if self.require_streamable && !result.is_streamable() {
+            return Some(FilterReason::NotStreamable);
+        }
+
+        if !has_category(&result.categories, &self.categories) {
+            return Some(FilterReason::Category);
+        }
+
+        let words = title_words(&result.title);
+        if self
+            .blocked_terms
+            .iter()
+            .any(|term| words.contains(&term.to_lowercase()))
+        {
+            return Some(FilterReason::BlockedTerm);
+        }
+
+        if let Some(size) = result.size {
+            let too_small = self.min_size.is_some_and(|min| size < min);
+            let too_large = self.max_size.is_some_and(|max| size > max);
+            if too_small || too_large {
+                return Some(FilterReason::Size);
+            }
+        }
+
+        None
+    }
+
+    /// Split results into (kept, hidden)
+    pub fn apply(&self, results: Vec<TorrentResult>) -> (Vec<TorrentResult>, Vec<TorrentResult>) {
+        results.into_iter().partition(|r| self.check(r).is_none())
+    }
+}
+
+/// Order in which auto-race tries results
+#[derive(Debug, Clone, Default)]
+pub struct RaceOrder {
+    /// Skip results reporting fewer seeders than this
+    pub min_seeders: u32,
+    /// Quality tag (e.g. "1080p") whose results are raced first
+    pub preferred_quality: Option<String>,
+}
+
+impl RaceOrder {
+    /// Streamable results to race, best first: highest score (parallel to `results`,
+    /// missing scores count as 0), then preferred quality, then by seeders.
+    /// Results without a seeder count are kept, but after those with one.
+    pub fn rank<'a>(&self, results: &'a [TorrentResult], scores: &[u8]) -> Vec<&'a TorrentResult> {
+        let quality = self
+            .preferred_quality
+            .as_deref()
+            .map(str::to_lowercase)
+            .filter(|q| !q.is_empty());
+
+        let mut ranked: Vec<(u8, &TorrentResult)> = results
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.is_streamable())
+            .filter(|(_, r)| r.seeders.is_none_or(|s| s >= self.min_seeders))
+            .map(|(i, r)| (scores.get(i).copied().unwrap_or(0), r))
+            .collect();
+
+        // Stable sort keeps the incoming order for ties
+        ranked.sort_by_key(|(score, r)| {
+            let preferred = quality
+                .as_deref()
+                .is_some_and(|q| title_words(&r.title).iter().any(|w| w == q));
+            (
+                Reverse(*score),
+                !preferred,
+                r.seeders.is_none(),
+                Reverse(r.seeders),
+            )
+        });
+        ranked.into_iter().map(|(_, r)| r).collect()
+    }
+
+    /// Human readable ordering, e.g. "top 3 by score"
+    pub fn describe(&self, count: usize) -> String {
+        format!("top {} by score", count)
+    }
+}
+
+/// Key identifying the same release across indexers: its infohash, else the
+/// one in its magnet link, else a fuzzy key from the normalised title and size.
+/// Infohashes are lowercase hex, base32 ones from magnets are converted.
+pub fn canonical_id(result: &TorrentResult) -> String {
+    let hash = result
+        .infohash
+        .as_deref()
+        .or_else(|| result.magnet_url.as_deref().and_then(magnet_infohash))
+        .or_else(|| result.link.as_deref().and_then(magnet_infohash))
+        .and_then(normalize_infohash);
+
+    match hash {
+        Some(hash) => format!("btih:{}", hash),
+        None => {
+            let size = result.size.map_or("?".to_string(), |s| s.to_string());
+            format!("title:{}:{}", title_words(&result.title).join("."), size)
+        }
+    }
+}
+
+/// Drop results another indexer already returned, keeping the best seeded
+/// copy in the place the release was first seen
+pub fn dedup_results(results: Vec<TorrentResult>) -> Vec<TorrentResult> {
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut unique: Vec<TorrentResult> = Vec::with_capacity(results.len());
+    for result in results {
+        match positions.entry(canonical_id(&result)) {
+            Entry::Occupied(entry) => {
+                let kept = &mut unique[*entry.get()];
+                if result.seeders.unwrap_or(0) > kept.seeders.unwrap_or(0) {
+                    *kept = result;
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(unique.len());
+                unique.push(result);
+            }
+        }
+    }
+    unique
+}
+
+/// Where a release was ripped from, the part of its quality that sets
+/// releases apart (a 720p and a 1080p BluRay are the same release)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReleaseSource {
+    BluRay,
+    Web,
+    Hdtv,
+    Dvd,
+    Unknown,
+}
+
+impl ReleaseSource {
+    pub fn detect(title: &str) -> Self {
+        let words = title_words(title);
+        let has = |terms: &[&str]| words.iter().any(|w| terms.contains(&w.as_str()));
+        if has(&["bluray", "bdrip", "brrip", "bdremux", "remux", "bd"])
+            || title.to_lowercase().contains("blu-ray")
+        {
+            Self::BluRay
+        } else if has(&[
+            "web", "webrip", "webdl", "dl", "amzn", "nf", "dsnp", "hmax", "atvp",
+        ]) {
+            Self::Web
+        } else if has(&["hdtv", "pdtv", "tvrip"]) {
+            Self::Hdtv
+        } else if has(&["dvdrip", "dvd", "dvd5", "dvd9", "dvdscr"]) {
+            Self::Dvd
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+/// Results of one release at different qualities
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReleaseGroup {
+    /// Clean title, with the year when the name has one
+    pub title: String,
+    pub source: ReleaseSource,
+    /// Indices into the grouped results, best seeded first
+    pub members: Vec<usize>,
+}
+
+/// Group results by clean title, year and source, in the order each group is
+/// first seen
+pub fn group_releases(results: &[TorrentResult]) -> Vec<ReleaseGroup> {
+    let mut positions: HashMap<(String, Option<u16>, ReleaseSource), usize> = HashMap::new();
+    let mut groups: Vec<ReleaseGroup> = Vec::new();
+    for (idx, result) in results.iter().enumerate() {
+        let (title, year) = parse_torrent_title(&result.title);
+        let source = ReleaseSource::detect(&result.title);
+        let title = if title.is_empty() {
+            // Nothing left after cleaning, don't lump these together
+            result.title.clone()
+        } else {
+            title
+        };
+        match positions.entry((title.to_lowercase(), year, source)) {
+            Entry::Occupied(entry) => groups[*entry.get()].members.push(idx),
+            Entry::Vacant(entry) => {
+                entry.insert(groups.len());
+                groups.push(ReleaseGroup {
+                    title: match year {
+                        Some(year) => format!("{} ({})", title, year),
+                        None => title,
+                    },
+                    source,
+                    members: vec![idx],
+                });
+            }
+        }
+    }
+    for group in &mut groups {
+        group
+            .members
+            .sort_by_key(|&idx| Reverse(results[idx].seeders.unwrap_or(0)));
+    }
+    groups
+}
+
+/// The `xt=urn:btih:` hash of a magnet link
+fn magnet_infohash(magnet: &str) -> Option<&str> {
+    let query = magnet.strip_prefix("magnet:?")?;
+    query.split('&').find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        let hash = value
+            .get(..9)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("urn:btih:"));
+        (key == "xt" && hash.is_some()).then(|| &value[9..])
+    })
+}
+
+/// Lowercase hex of a 40 character hex or 32 character base32 infohash
+fn normalize_infohash(hash: &str) -> Option<String> {
+    let hash = hash.trim();
+    match hash.len() {
+        40 if hash.chars().all(|c| c.is_ascii_hexdigit()) => Some(hash.to_lowercase()),
+        32 => {
+            let mut bits: u64 = 0;
+            let mut count = 0;
+            let mut hex = String::with_capacity(40);
+            for c in hash.chars() {
+                let value = match c.to_ascii_uppercase() {
+                    c @ 'A'..='Z' => c as u64 - 'A' as u64,
+                    c @ '2'..='7' => c as u64 - '2' as u64 + 26,
+                    _ => return None,
+                };
+                bits = (bits << 5) | value;
+                count += 5;
+                while count >= 4 {
+                    count -= 4;
+                    hex.push_str(&format!("{:x}", (bits >> count) & 0xf));
+                }
+            }
+            Some(hex)
+        }
+        // Not a real infohash, still better than the title
+        _ if !hash.is_empty() => Some(hash.to_lowercase()),
+        _ => None,
+    }
+}
+
+/// Lowercase words of a release title ("Movie.2024.1080p" -> ["movie", "2024", "1080p"])
+pub(crate) fn title_words(title: &str) -> Vec<String> {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// An XXX category, or a title word only adult releases use
+fn is_adult(result: &TorrentResult) -> bool {
+    result
+        .categories
+        .iter()
+        .any(|c| ADULT_CATEGORIES.contains(c))
+        || title_words(&result.title)
+            .iter()
+            .any(|w| ADULT_TERMS.contains(&w.as_str()))
+}
+
+/// True if the categories contain video, or carry no standard category at all
+fn has_category(categories: &[u32], wanted: &[u32]) -> bool {
+    let mut standard = categories
+        .iter()
+        .filter(|c| **c < CUSTOM_CATEGORY_START)
+        .peekable();
+
+    if standard.peek().is_none() {
+        return true;
+    }
+
+    standard.any(|c| wanted.contains(&(c / 1000 * 1000)))
+}
+
+/// Torznab search function, the `t` parameter of a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchType {
+    Search,
+    TvSearch,
+    Movie,
+}
+
+impl SearchType {
+    pub fn param(self) -> &'static str {
+        match self {
+            SearchType::Search => "search",
+            SearchType::TvSearch => "tvsearch",
+            SearchType::Movie => "movie",
+        }
+    }
+}
+
+/// Search functions an indexer supports, from `t=caps`. Each holds the
+/// parameters it accepts, None when the indexer doesn't offer it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexerCaps {
+    pub search: Option<Vec<String>>,
+    pub tv_search: Option<Vec<String>>,
+    pub movie_search: Option<Vec<String>>,
+}
+
+impl IndexerCaps {
+    fn params(&self, search_type: SearchType) -> Option<&[String]> {
+        match search_type {
+            SearchType::Search => self.search.as_deref(),
+            SearchType::TvSearch => self.tv_search.as_deref(),
+            SearchType::Movie => self.movie_search.as_deref(),
+        }
+    }
+
+    pub fn supports(&self, search_type: SearchType, param: &str) -> bool {
+        self.params(search_type)
+            .is_some_and(|params| params.iter().any(|p| p == param))
+    }
+
+    /// The best search for a request and its parameters. Episodes use
+    /// `tvsearch` with season/ep and movies `movie`, by TMDB id where the
+    /// indexer takes one, else by IMDB id. Everything else is a free-text
+    /// `search`, unless the indexer only does structured searches.
+    pub fn plan(&self, request: &SearchRequest) -> (SearchType, Vec<(&'static str, String)>) {
+        let context = &request.context;
+        let tmdb_id = |search_type| {
+            context
+                .tmdb_id
+                .filter(|_| self.supports(search_type, "tmdbid"))
+                .map(|id| ("tmdbid", id.to_string()))
+        };
+
+        if let (Some(season), Some(episode)) = (context.season, context.episode)
+            && context.media_type.is_some()
+            && self.supports(SearchType::TvSearch, "season")
+            && self.supports(SearchType::TvSearch, "ep")
+        {
+            let show = tmdb_id(SearchType::TvSearch).unwrap_or(("q", context.title.clone()));
+            return (
+                SearchType::TvSearch,
+                vec![
+                    show,
+                    ("season", season.to_string()),
+                    ("ep", episode.to_string()),
+                ],
+            );
+        }
+
+        if context.media_type.as_deref() == Some("movie") && self.movie_search.is_some() {
+            let imdb_id = context
+                .imdb_id
+                .as_deref()
+                .filter(|_| self.supports(SearchType::Movie, "imdbid"))
+                .map(|id| ("imdbid", imdb_param(id)));
+            if let Some(id) = tmdb_id(SearchType::Movie).or(imdb_id) {
+                return (SearchType::Movie, vec![id]);
+            }
+        }
+
+        let text = ("q", request.query.clone());
+        if self.search.is_some() {
+            return (SearchType::Search, vec![text]);
+        }
+        let structured = if context.media_type.as_deref() == Some("movie") {
+            [SearchType::Movie, SearchType::TvSearch]
+        } else {
+            [SearchType::TvSearch, SearchType::Movie]
+        };
+        let search_type = structured
+            .into_iter()
+            .find(|&t| self.supports(t, "q"))
+            .unwrap_or(SearchType::Search);
+        (search_type, vec![text])
+    }
+}
+
+/// What to search an indexer for: the free-text query, and what it's for so
+/// a structured search can be used where the indexer supports one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchRequest {
+    pub query: String,
+    pub context: SearchContext,
+}
+
+impl SearchRequest {
+    pub fn new(query: impl Into<String>, context: SearchContext) -> Self {
+        Self {
+            query: query.into(),
+            context,
+        }
+    }
+
+    /// Free-text search, used when an indexer's caps are unknown
+    fn free_text(&self) -> (SearchType, Vec<(&'static str, String)>) {
+        (SearchType::Search, vec![("q", self.query.clone())])
+    }
+}
+
+struct CachedCaps {
+    /// None if fetching them failed
+    caps: Option<IndexerCaps>,
+    fetched_at: Instant,
+}
+
+/// Torznab client. Clones share the cache of indexer caps.
+#[derive(Clone)]
 pub struct TorznabClient {
     client: Client,
+    caps: Arc<Mutex<HashMap<String, CachedCaps>>>,
 }
 
 impl Default for TorznabClient {
@@ -89,43 +612,126 @@ impl Default for TorznabClient {
 impl TorznabClient {
     pub fn new() -> Self {
         Self {
-            client: Client::new(),
+            client: crate::http::client(),
+            caps: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    /// Search with optional category filter
-    /// Categories: 2000 = Movies, 5000 = TV
+    /// Search an indexer, using the search type that fits the request best
+    /// according to its caps. Categories: 2000 = Movies, 5000 = TV
     pub async fn search(
         &self,
         base_url: &str,
         api_key: &str,
         indexer_id: i32,
         indexer_name: &str,
-        query: &str,
+        request: &SearchRequest,
         categories: Option<&[u32]>,
     ) -> Result<Vec<TorrentResult>, TorznabError> {
-        let cat_param = categories
-            .map(|cats| {
-                format!(
-                    "&cat={}",
-                    cats.iter()
-                        .map(|c| c.to_string())
-                        .collect::<Vec<_>>()
-                        .join(",")
-                )
-            })
-            .unwrap_or_default();
+        let (search_type, params) = match self.cached_caps(base_url, api_key, indexer_id).await {
+            Some(caps) => caps.plan(request),
+            None => request.free_text(),
+        };
+        debug!(
+            indexer = indexer_name,
+            search_type = search_type.param(),
+            "searching indexer"
+        );
 
-        let url = format!(
-            "{}/{}/api?t=search&apikey={}&q={}&limit=100{}",
-            base_url.trim_end_matches('/'),
+        let url = search_url(
+            base_url,
+            api_key,
             indexer_id,
+            search_type,
+            &params,
+            categories,
+        );
+        let xml = self.get(&url).await?;
+        parse_torznab_xml(&xml, indexer_name)
+    }
+
+    /// Search an indexer for exactly one movie by its IMDB id ("tt1160419"
+    /// or just the digits), whatever its caps say
+    pub async fn search_by_imdb_id(
+        &self,
+        base_url: &str,
+        api_key: &str,
+        indexer_id: i32,
+        indexer_name: &str,
+        imdb_id: &str,
+        categories: Option<&[u32]>,
+    ) -> Result<Vec<TorrentResult>, TorznabError> {
+        debug!(
+            indexer = indexer_name,
+            imdb_id, "searching indexer by IMDB id"
+        );
+        let url = search_url(
+            base_url,
             api_key,
-            urlencoding::encode(query),
-            cat_param
+            indexer_id,
+            SearchType::Movie,
+            &[("imdbid", imdb_param(imdb_id))],
+            categories,
+        );
+        let xml = self.get(&url).await?;
+        parse_torznab_xml(&xml, indexer_name)
+    }
+
+    /// Fetch what an indexer can search for
+    pub async fn caps(
+        &self,
+        base_url: &str,
+        api_key: &str,
+        indexer_id: i32,
+    ) -> Result<IndexerCaps, TorznabError> {
+        let url = format!(
+            "{}?t=caps&apikey={}",
+            api_url(base_url, indexer_id),
+            api_key
+        );
+        let xml = self.get(&url).await?;
+        parse_caps_xml(&xml)
+    }
+
+    /// Caps from the cache, fetching them when missing or expired.
+    /// None if the indexer can't tell, searches then fall back to free text.
+    async fn cached_caps(
+        &self,
+        base_url: &str,
+        api_key: &str,
+        indexer_id: i32,
+    ) -> Option<IndexerCaps> {
+        let key = api_url(base_url, indexer_id);
+        if let Some(cached) = self.caps.lock().unwrap().get(&key) {
+            let ttl = if cached.caps.is_some() {
+                CAPS_TTL
+            } else {
+                CAPS_RETRY
+            };
+            if cached.fetched_at.elapsed() < ttl {
+                return cached.caps.clone();
+            }
+        }
+
+        let caps = match self.caps(base_url, api_key, indexer_id).await {
+            Ok(caps) => Some(caps),
+            Err(e) => {
+                warn!(indexer_id, error = %e, "indexer caps unavailable, using free-text search");
+                None
+            }
+        };
+        self.caps.lock().unwrap().insert(
+            key,
+            CachedCaps {
+                caps: caps.clone(),
+                fetched_at: Instant::now(),
+            },
         );
+        caps
+    }
 
-        let response = self.client.get(&url).send().await?;
+    async fn get(&self, url: &str) -> Result<String, TorznabError> {
+        let response = send_with_retry(self.client.get(url)).await?;
 
         if !response.status().is_success() {
             return Err(TorznabError::InvalidResponse(format!(
@@ -134,29 +740,168 @@ impl TorznabClient {
             )));
         }
 
-        let xml = response.text().await?;
-        self.parse_response(&xml, indexer_name)
+        Ok(response.text().await?)
     }
+}
 
-    fn parse_response(
-        &self,
-        xml: &str,
-        indexer_name: &str,
-    ) -> Result<Vec<TorrentResult>, TorznabError> {
-        let mut reader = Reader::from_str(xml);
-        reader.config_mut().trim_text(true);
+/// Request URL of a search, 100 results at most
+fn search_url(
+    base_url: &str,
+    api_key: &str,
+    indexer_id: i32,
+    search_type: SearchType,
+    params: &[(&'static str, String)],
+    categories: Option<&[u32]>,
+) -> String {
+    let params: String = params
+        .iter()
+        .map(|(name, value)| format!("&{}={}", name, urlencoding::encode(value)))
+        .collect();
+    let cat_param = categories
+        .map(|cats| {
+            format!(
+                "&cat={}",
+                cats.iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        "{}?t={}&apikey={}{}&limit=100{}",
+        api_url(base_url, indexer_id),
+        search_type.param(),
+        api_key,
+        params,
+        cat_param
+    )
+}
+
+/// Newznab `imdbid` values are the digits, without "tt"
+fn imdb_param(imdb_id: &str) -> String {
+    imdb_id.trim().trim_start_matches("tt").to_string()
+}
+
+/// Torznab endpoint of a Prowlarr indexer
+fn api_url(base_url: &str, indexer_id: i32) -> String {
+    format!("{}/{}/api", base_url.trim_end_matches('/'), indexer_id)
+}
+
+/// Parse a Torznab `t=caps` document into the search functions it lists.
+///
+/// A function missing `supportedParams` only takes `q`. Torznab `<error>`
+/// responses and documents that aren't caps are errors.
+pub fn parse_caps_xml(xml: &str) -> Result<IndexerCaps, TorznabError> {
+    let mut reader = Reader::from_str(xml);
+    let mut caps = IndexerCaps::default();
+    let mut saw_caps = false;
+    let mut depth: usize = 0;
+
+    loop {
+        let (e, empty) = match reader.read_event() {
+            Ok(Event::Start(e)) => (e, false),
+            Ok(Event::Empty(e)) => (e, true),
+            Ok(Event::End(_)) => {
+                depth = depth.saturating_sub(1);
+                continue;
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(TorznabError::XmlError(e)),
+            _ => continue,
+        };
+
+        let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+        if name == "error" {
+            return Err(indexer_error(&e));
+        }
+        if depth == 0 {
+            if name != "caps" {
+                return Err(TorznabError::InvalidResponse(
+                    "response is not a Torznab caps document".to_string(),
+                ));
+            }
+            saw_caps = true;
+        }
+        if !empty {
+            depth += 1;
+        }
+
+        let slot = match name.as_str() {
+            "search" => &mut caps.search,
+            "tv-search" => &mut caps.tv_search,
+            "movie-search" => &mut caps.movie_search,
+            _ => continue,
+        };
+        let mut available = false;
+        let mut params = vec!["q".to_string()];
+        for attr in e.attributes().flatten() {
+            let val = attr.unescape_value().unwrap_or_default().to_string();
+            match attr.key.as_ref() {
+                b"available" => available = val.eq_ignore_ascii_case("yes"),
+                b"supportedParams" => {
+                    params = val
+                        .split(',')
+                        .map(|p| p.trim().to_lowercase())
+                        .filter(|p| !p.is_empty())
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        *slot = available.then_some(params);
+    }
 
-        let mut results = Vec::new();
-        let mut current_item: Option<TorrentResult> = None;
-        let mut current_element = String::new();
+    if !saw_caps {
+        return Err(TorznabError::InvalidResponse(
+            "response is not a Torznab caps document".to_string(),
+        ));
+    }
+    Ok(caps)
+}
 
-        loop {
-            match reader.read_event() {
-                Ok(Event::Start(ref e)) => {
-                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                    current_element = name.clone();
+/// Parse a Torznab RSS feed into torrent results.
+///
+/// Kept separate from the HTTP call so it can be tested against recorded
+/// indexer responses. Returns an error for Torznab `<error>` responses,
+/// documents that aren't an RSS feed (e.g. HTML error pages) and truncated feeds.
+pub fn parse_torznab_xml(
+    xml: &str,
+    indexer_name: &str,
+) -> Result<Vec<TorrentResult>, TorznabError> {
+    // Text is trimmed per element rather than per event, otherwise the
+    // whitespace around entity references ("Tom &amp; Jerry") gets lost
+    let mut reader = Reader::from_str(xml);
+
+    let mut results = Vec::new();
+    let mut current_item: Option<TorrentResult> = None;
+    let mut current_element = String::new();
+    let mut text = String::new();
+    let mut depth: usize = 0;
+    let mut saw_feed = false;
+    // Prowlarr reports seeders + leechers as "peers" instead of "leechers"
+    let mut current_peers: Option<u32> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(ref e)) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                // bail out early on HTML error pages and other non-feed documents,
+                // which are often not well-formed XML either
+                if depth == 0 && name != "rss" && name != "error" {
+                    return Err(TorznabError::InvalidResponse(
+                        "response is not a Torznab feed".to_string(),
+                    ));
+                }
+                depth += 1;
+                text.clear();
 
-                    if name == "item" {
+                match name.as_str() {
+                    "error" => return Err(indexer_error(e)),
+                    "rss" => saw_feed = true,
+                    "item" => {
+                        current_peers = None;
                         current_item = Some(TorrentResult {
                             title: String::new(),
                             link: None,
@@ -166,74 +911,152 @@ impl TorznabClient {
                             seeders: None,
                             leechers: None,
                             indexer: indexer_name.to_string(),
+                            categories: Vec::new(),
+                            usenet: false,
                         });
                     }
+                    _ => {}
                 }
-                Ok(Event::Empty(ref e)) => {
-                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
 
-                    // handle <torznab:attr name="X" value="Y" /> elements
-                    if (name == "torznab:attr" || name == "attr")
-                        && let Some(ref mut item) = current_item
-                    {
-                        let mut attr_name = String::new();
-                        let mut attr_value = String::new();
+                current_element = name;
+            }
+            Ok(Event::Empty(ref e)) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
 
-                        for attr in e.attributes().flatten() {
-                            let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                            let val = String::from_utf8_lossy(&attr.value).to_string();
+                if name == "error" {
+                    return Err(indexer_error(e));
+                }
 
-                            if key == "name" {
-                                attr_name = val;
-                            } else if key == "value" {
-                                attr_value = val;
-                            }
+                // handle <torznab:attr name="X" value="Y" /> elements
+                if (name == "torznab:attr" || name == "attr")
+                    && let Some(ref mut item) = current_item
+                {
+                    let mut attr_name = String::new();
+                    let mut attr_value = String::new();
+
+                    for attr in e.attributes().flatten() {
+                        let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+                        let val = attr.unescape_value().unwrap_or_default().to_string();
+
+                        if key == "name" {
+                            attr_name = val;
+                        } else if key == "value" {
+                            attr_value = val;
                         }
+                    }
 
-                        match attr_name.as_str() {
-                            "seeders" => item.seeders = attr_value.parse().ok(),
-                            "leechers" => item.leechers = attr_value.parse().ok(),
-                            "size" => item.size = attr_value.parse().ok(),
-                            "magneturl" => item.magnet_url = Some(attr_value),
-                            "infohash" => item.infohash = Some(attr_value),
-                            _ => {}
+                    match attr_name.as_str() {
+                        "seeders" => item.seeders = attr_value.parse().ok(),
+                        "leechers" => item.leechers = attr_value.parse().ok(),
+                        "peers" => current_peers = attr_value.parse().ok(),
+                        "size" => item.size = attr_value.parse().ok(),
+                        "magneturl" => item.magnet_url = Some(attr_value),
+                        "infohash" => item.infohash = Some(attr_value),
+                        "category" => {
+                            if let Ok(cat) = attr_value.parse()
+                                && !item.categories.contains(&cat)
+                            {
+                                item.categories.push(cat);
+                            }
                         }
+                        _ => {}
                     }
                 }
-                Ok(Event::Text(ref e)) => {
-                    if let Some(ref mut item) = current_item {
-                        let text = e.decode().unwrap_or_default().to_string();
-
-                        match current_element.as_str() {
-                            "title" => item.title = text,
-                            "link" => item.link = Some(text),
-                            "size" => {
-                                if item.size.is_none() {
-                                    item.size = text.parse().ok();
-                                }
+            }
+            Ok(Event::Text(ref e)) => {
+                text.push_str(&e.decode().unwrap_or_default());
+            }
+            Ok(Event::CData(ref e)) => {
+                text.push_str(&e.decode().unwrap_or_default());
+            }
+            Ok(Event::GeneralRef(ref e)) => {
+                // Entities come through as separate events: "Tom &amp; Jerry"
+                if let Ok(Some(c)) = e.resolve_char_ref() {
+                    text.push(c);
+                } else {
+                    match e.decode().unwrap_or_default().as_ref() {
+                        "amp" => text.push('&'),
+                        "lt" => text.push('<'),
+                        "gt" => text.push('>'),
+                        "quot" => text.push('"'),
+                        "apos" => text.push('\''),
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                depth = depth.saturating_sub(1);
+
+                if name == current_element
+                    && let Some(ref mut item) = current_item
+                {
+                    let value = text.trim().to_string();
+                    match name.as_str() {
+                        "title" => item.title = value,
+                        "link" => item.link = Some(value),
+                        "size" if item.size.is_none() => {
+                            item.size = value.parse().ok();
+                        }
+                        "category" => {
+                            if let Ok(cat) = value.parse()
+                                && !item.categories.contains(&cat)
+                            {
+                                item.categories.push(cat);
                             }
-                            _ => {}
                         }
+                        _ => {}
                     }
                 }
-                Ok(Event::End(ref e)) => {
-                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                text.clear();
+                current_element.clear();
 
-                    if name == "item"
-                        && let Some(item) = current_item.take()
-                        && !item.title.is_empty()
+                if name == "item"
+                    && let Some(mut item) = current_item.take()
+                    && !item.title.is_empty()
+                {
+                    if item.leechers.is_none()
+                        && let Some(peers) = current_peers
                     {
-                        results.push(item);
+                        item.leechers = Some(peers.saturating_sub(item.seeders.unwrap_or(0)));
                     }
+                    results.push(item);
                 }
-                Ok(Event::Eof) => break,
-                Err(e) => return Err(TorznabError::XmlError(e)),
-                _ => {}
             }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(TorznabError::XmlError(e)),
+            _ => {}
         }
+    }
+
+    if !saw_feed {
+        return Err(TorznabError::InvalidResponse(
+            "response is not a Torznab feed".to_string(),
+        ));
+    }
 
-        Ok(results)
+    if depth > 0 {
+        return Err(TorznabError::InvalidResponse(
+            "truncated Torznab feed".to_string(),
+        ));
     }
+
+    Ok(results)
+}
+
+/// Build an error from a Torznab `<error code="100" description="Invalid API Key"/>` element
+fn indexer_error(e: &BytesStart) -> TorznabError {
+    let mut code = String::new();
+    let mut description = String::new();
+    for attr in e.attributes().flatten() {
+        let val = attr.unescape_value().unwrap_or_default().to_string();
+        match attr.key.as_ref() {
+            b"code" => code = val,
+            b"description" => description = val,
+            _ => {}
+        }
+    }
+    TorznabError::InvalidResponse(format!("indexer error {}: {}", code, description))
 }
 
 #[cfg(test)]
@@ -251,6 +1074,8 @@ mod tests {
             seeders: None,
             leechers: None,
             indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: false,
         };
         assert_eq!(result.size_human(), "1.00 GB");
 
@@ -285,6 +1110,8 @@ mod tests {
             seeders: None,
             leechers: None,
             indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: false,
         };
         assert_eq!(
             result.get_torrent_url(),
@@ -304,6 +1131,8 @@ mod tests {
             seeders: None,
             leechers: None,
             indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: false,
         };
         assert_eq!(
             result.get_torrent_url(),
@@ -323,6 +1152,8 @@ mod tests {
             seeders: None,
             leechers: None,
             indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: false,
         };
         let url = result.get_torrent_url().unwrap();
         assert!(url.starts_with("magnet:?xt=urn:btih:abc123hash"));
@@ -341,6 +1172,8 @@ mod tests {
             seeders: None,
             leechers: None,
             indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: false,
         };
         assert_eq!(
             result.get_torrent_url(),
@@ -359,10 +1192,38 @@ mod tests {
             seeders: None,
             leechers: None,
             indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: false,
         };
         assert_eq!(result.get_torrent_url(), None);
     }
 
+    #[test]
+    fn test_usenet_result_urls() {
+        let result = TorrentResult {
+            title: "Test".to_string(),
+            link: Some("http://prowlarr/1/download?file=Test.nzb".to_string()),
+            magnet_url: None,
+            infohash: None,
+            size: None,
+            seeders: None,
+            leechers: None,
+            indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: true,
+        };
+        // Not something librqbit can add, but still streamable through the downloader
+        assert_eq!(result.get_torrent_url(), None);
+        assert_eq!(result.nzb_url(), result.link);
+        assert!(result.is_streamable());
+
+        let torrent = TorrentResult {
+            usenet: false,
+            ..result
+        };
+        assert_eq!(torrent.nzb_url(), None);
+    }
+
     #[test]
     fn test_is_streamable() {
         let streamable = TorrentResult {
@@ -374,6 +1235,8 @@ mod tests {
             seeders: None,
             leechers: None,
             indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: false,
         };
         assert!(streamable.is_streamable());
 
@@ -386,6 +1249,8 @@ mod tests {
             seeders: None,
             leechers: None,
             indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: false,
         };
         assert!(!not_streamable.is_streamable());
     }
@@ -406,8 +1271,7 @@ mod tests {
   </channel>
 </rss>"#;
 
-        let client = TorznabClient::new();
-        let results = client.parse_response(xml, "TestIndexer").unwrap();
+        let results = parse_torznab_xml(xml, "TestIndexer").unwrap();
 
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].title, "Test Movie 2024 1080p");
@@ -445,8 +1309,7 @@ mod tests {
   </channel>
 </rss>"#;
 
-        let client = TorznabClient::new();
-        let results = client.parse_response(xml, "Test").unwrap();
+        let results = parse_torznab_xml(xml, "Test").unwrap();
 
         assert_eq!(results.len(), 3);
         assert_eq!(results[0].title, "Movie One");
@@ -467,12 +1330,386 @@ mod tests {
   </channel>
 </rss>"#;
 
-        let client = TorznabClient::new();
-        let results = client.parse_response(xml, "Test").unwrap();
+        let results = parse_torznab_xml(xml, "Test").unwrap();
 
         assert_eq!(results[0].infohash, Some("abcdef1234567890".to_string()));
     }
 
+    fn filter_result(title: &str, size: Option<u64>, categories: Vec<u32>) -> TorrentResult {
+        TorrentResult {
+            title: title.to_string(),
+            link: Some("http://example.com/download".to_string()),
+            magnet_url: None,
+            infohash: None,
+            size,
+            seeders: None,
+            leechers: None,
+            indexer: "test".to_string(),
+            categories,
+            usenet: false,
+        }
+    }
+
+    #[test]
+    fn test_is_tv() {
+        assert!(filter_result("The.Rings.of.Power.S02E01.1080p", None, vec![5040]).is_tv());
+        assert!(!filter_result("The.Lord.of.the.Rings.2001.1080p", None, vec![2040]).is_tv());
+        // The category wins over the title
+        assert!(!filter_result("Ring.S01.Making.Of", None, vec![2000, 100_001]).is_tv());
+        // Without one the title decides
+        assert!(filter_result("The.Ring.S01E03.720p", None, vec![100_001]).is_tv());
+        assert!(!filter_result("The.Ring.2002.720p", None, vec![]).is_tv());
+    }
+
+    #[test]
+    fn test_result_filter_categories() {
+        let filter = ResultFilter::default();
+        const GB: u64 = 1024 * 1024 * 1024;
+
+        assert_eq!(
+            filter.check(&filter_result("Movie", Some(GB), vec![2040])),
+            None
+        );
+        assert_eq!(
+            filter.check(&filter_result("Show", Some(GB), vec![5030, 100_123])),
+            None
+        );
+        // No category info at all is kept
+        assert_eq!(
+            filter.check(&filter_result("Movie", Some(GB), vec![])),
+            None
+        );
+        // Only indexer-specific categories is kept
+        assert_eq!(
+            filter.check(&filter_result("Movie", Some(GB), vec![100_001])),
+            None
+        );
+        // Games and XXX are dropped
+        assert_eq!(
+            filter.check(&filter_result("Game", Some(GB), vec![4050])),
+            Some(FilterReason::Category)
+        );
+        assert_eq!(
+            filter.check(&filter_result("Adult", Some(GB), vec![6000])),
+            Some(FilterReason::Category)
+        );
+
+        // Audio only passes when its categories are asked for
+        let album = filter_result("Album", Some(GB), vec![3040]);
+        assert_eq!(filter.check(&album), Some(FilterReason::Category));
+        let audio = ResultFilter {
+            categories: vec![3000],
+            ..ResultFilter::default()
+        };
+        assert_eq!(audio.check(&album), None);
+    }
+
+    #[test]
+    fn test_result_filter_terms_and_size() {
+        const MB: u64 = 1024 * 1024;
+        let filter = ResultFilter {
+            blocked_terms: vec!["ebook".to_string(), "ost".to_string()],
+            min_size: Some(50 * MB),
+            max_size: Some(100 * 1024 * MB),
+            require_streamable: true,
+            ..ResultFilter::default()
+        };
+
+        assert_eq!(
+            filter.check(&filter_result(
+                "Movie.2024.eBook.EPUB",
+                Some(500 * MB),
+                vec![]
+            )),
+            Some(FilterReason::BlockedTerm)
+        );
+        // Terms match whole words only
+        assert_eq!(
+            filter.check(&filter_result("Ghost.2024.1080p", Some(500 * MB), vec![])),
+            None
+        );
+        assert_eq!(
+            filter.check(&filter_result("Movie.2024.1080p", Some(MB), vec![])),
+            Some(FilterReason::Size)
+        );
+        assert_eq!(
+            filter.check(&filter_result("Movie.2024.1080p", None, vec![])),
+            None
+        );
+
+        let mut no_url = filter_result("Movie.2024.1080p", Some(500 * MB), vec![]);
+        no_url.link = None;
+        assert_eq!(filter.check(&no_url), Some(FilterReason::NotStreamable));
+
+        let (kept, hidden) = filter.apply(vec![
+            filter_result("Movie.2024.1080p", Some(500 * MB), vec![2040]),
+            filter_result("Movie.2024.OST", Some(500 * MB), vec![2040]),
+        ]);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(hidden.len(), 1);
+        assert_eq!(hidden[0].title, "Movie.2024.OST");
+    }
+
+    #[test]
+    fn test_result_filter_safe_mode() {
+        const GB: u64 = 1024 * 1024 * 1024;
+        let filter = ResultFilter {
+            safe_mode: true,
+            ..ResultFilter::default()
+        };
+
+        // Adult categories go even when an indexer also tags them as movies
+        assert_eq!(
+            filter.check(&filter_result(
+                "Some.Movie.2024",
+                Some(GB),
+                vec![2000, 6040]
+            )),
+            Some(FilterReason::Adult)
+        );
+        assert_eq!(
+            filter.check(&filter_result(
+                "Some.Movie.2024.XXX.1080p",
+                Some(GB),
+                vec![2040]
+            )),
+            Some(FilterReason::Adult)
+        );
+        assert_eq!(
+            filter.check(&filter_result(
+                "Dune.Part.Two.2024.1080p",
+                Some(GB),
+                vec![2040]
+            )),
+            None
+        );
+
+        // Without safe mode it is up to the blocked terms
+        assert_eq!(
+            ResultFilter::default().check(&filter_result(
+                "Some.Movie.2024.XXX.1080p",
+                Some(GB),
+                vec![2040]
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn test_canonical_id() {
+        const HASH: &str = "c12fe1c06bba254a9dc9f519b335aa7c1367a88a";
+        let mut with_hash = filter_result("Movie.2024.1080p", Some(1000), vec![]);
+        with_hash.infohash = Some(HASH.to_uppercase());
+        assert_eq!(canonical_id(&with_hash), format!("btih:{}", HASH));
+
+        // Same hash from a magnet link, also in base32
+        let mut from_magnet = filter_result("Movie 2024 1080p", None, vec![]);
+        from_magnet.magnet_url = Some(format!("magnet:?dn=Movie&xt=urn:btih:{}", HASH));
+        assert_eq!(canonical_id(&from_magnet), canonical_id(&with_hash));
+        from_magnet.magnet_url =
+            Some("magnet:?xt=urn:btih:YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKEK&dn=Movie".to_string());
+        assert_eq!(canonical_id(&from_magnet), canonical_id(&with_hash));
+
+        // Without any hash the normalised title and size are used
+        let a = filter_result("Movie.2024.1080p", Some(1000), vec![]);
+        let b = filter_result("movie 2024 [1080p]", Some(1000), vec![]);
+        let c = filter_result("Movie.2024.1080p", Some(2000), vec![]);
+        assert_eq!(canonical_id(&a), canonical_id(&b));
+        assert_ne!(canonical_id(&a), canonical_id(&c));
+    }
+
+    #[test]
+    fn test_dedup_results() {
+        let mut first = filter_result("Movie.2024.1080p", Some(1000), vec![]);
+        first.infohash = Some("abc".to_string());
+        first.seeders = Some(5);
+        let mut better = filter_result("Movie 2024 1080p", None, vec![]);
+        better.magnet_url = Some("magnet:?xt=urn:btih:ABC".to_string());
+        better.seeders = Some(50);
+        let other = filter_result("Other.2024.1080p", Some(1000), vec![]);
+
+        let results = dedup_results(vec![first, other, better]);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].seeders, Some(50));
+        assert_eq!(results[1].title, "Other.2024.1080p");
+    }
+
+    #[test]
+    fn test_release_source() {
+        assert_eq!(
+            ReleaseSource::detect("Heat.1995.1080p.BluRay.x264"),
+            ReleaseSource::BluRay
+        );
+        assert_eq!(
+            ReleaseSource::detect("Heat 1995 2160p UHD Blu-ray REMUX"),
+            ReleaseSource::BluRay
+        );
+        assert_eq!(
+            ReleaseSource::detect("Heat.1995.1080p.AMZN.WEB-DL.DDP5.1"),
+            ReleaseSource::Web
+        );
+        assert_eq!(
+            ReleaseSource::detect("Show.S01E01.720p.HDTV.x264"),
+            ReleaseSource::Hdtv
+        );
+        assert_eq!(
+            ReleaseSource::detect("Heat.1995.DVDRip"),
+            ReleaseSource::Dvd
+        );
+        assert_eq!(
+            ReleaseSource::detect("Heat.1995.1080p"),
+            ReleaseSource::Unknown
+        );
+    }
+
+    #[test]
+    fn test_group_releases() {
+        let mut results = vec![
+            filter_result("Heat.1995.720p.BluRay.x264", None, vec![]),
+            filter_result("Heat.1995.1080p.WEB-DL", None, vec![]),
+            filter_result("Heat 1995 2160p BluRay REMUX", None, vec![]),
+            filter_result("Heat.1986.1080p.BluRay", None, vec![]),
+        ];
+        results[0].seeders = Some(10);
+        results[2].seeders = Some(80);
+
+        let groups = group_releases(&results);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].title, "Heat (1995)");
+        assert_eq!(groups[0].source, ReleaseSource::BluRay);
+        // Best seeded first
+        assert_eq!(groups[0].members, vec![2, 0]);
+        assert_eq!(groups[1].source, ReleaseSource::Web);
+        assert_eq!(groups[1].members, vec![1]);
+        // Same title, another year
+        assert_eq!(groups[2].members, vec![3]);
+    }
+
+    fn race_result(title: &str, seeders: Option<u32>) -> TorrentResult {
+        TorrentResult {
+            seeders,
+            ..filter_result(title, None, vec![])
+        }
+    }
+
+    #[test]
+    fn test_race_order_by_seeders() {
+        let results = vec![
+            race_result("Unknown.1080p", None),
+            race_result("Few.720p", Some(3)),
+            race_result("Dead.1080p", Some(0)),
+            race_result("Many.2160p", Some(250)),
+            race_result("Unknown.720p", None),
+        ];
+        let order = RaceOrder {
+            min_seeders: 1,
+            preferred_quality: None,
+        };
+
+        let titles: Vec<&str> = order
+            .rank(&results, &[])
+            .iter()
+            .map(|r| r.title.as_str())
+            .collect();
+        // Dead torrent skipped, unknown seeder counts last in original order
+        assert_eq!(
+            titles,
+            vec!["Many.2160p", "Few.720p", "Unknown.1080p", "Unknown.720p"]
+        );
+        assert_eq!(order.describe(3), "top 3 by score");
+    }
+
+    #[test]
+    fn test_race_order_preferred_quality() {
+        let mut no_url = race_result("NoUrl.1080p", Some(900));
+        no_url.link = None;
+        let results = vec![
+            race_result("Movie.2160p", Some(500)),
+            race_result("Movie.1080p.x264", Some(20)),
+            race_result("Movie.1080p", None),
+            race_result("Movie.720p", Some(100)),
+            race_result("Movie.1080p.HEVC", Some(40)),
+            no_url,
+        ];
+        let order = RaceOrder {
+            min_seeders: 0,
+            preferred_quality: Some("1080P".to_string()),
+        };
+
+        let titles: Vec<&str> = order
+            .rank(&results, &[])
+            .iter()
+            .map(|r| r.title.as_str())
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                "Movie.1080p.HEVC",
+                "Movie.1080p.x264",
+                "Movie.1080p",
+                "Movie.2160p",
+                "Movie.720p"
+            ]
+        );
+
+        // Scores come first, the quality/seeders order breaks ties
+        let titles: Vec<&str> = order
+            .rank(&results, &[10, 90, 10, 90, 10, 99])
+            .iter()
+            .map(|r| r.title.as_str())
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                "Movie.1080p.x264",
+                "Movie.720p",
+                "Movie.1080p.HEVC",
+                "Movie.1080p",
+                "Movie.2160p"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_response_categories() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+  <channel>
+    <item>
+      <title>Test</title>
+      <category>2040</category>
+      <torznab:attr name="category" value="2040"/>
+      <torznab:attr name="category" value="100001"/>
+    </item>
+  </channel>
+</rss>"#;
+
+        let results = parse_torznab_xml(xml, "Test").unwrap();
+
+        assert_eq!(results[0].categories, vec![2040, 100001]);
+    }
+
+    #[test]
+    fn test_parse_response_unescapes_entities() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+  <channel>
+    <item>
+      <title>Tom &amp; Jerry &#8211; <![CDATA[The Movie]]></title>
+      <torznab:attr name="magneturl" value="magnet:?xt=urn:btih:abc&amp;dn=Tom"/>
+    </item>
+  </channel>
+</rss>"#;
+
+        let results = parse_torznab_xml(xml, "Test").unwrap();
+
+        assert_eq!(results[0].title, "Tom & Jerry – The Movie");
+        assert_eq!(
+            results[0].magnet_url,
+            Some("magnet:?xt=urn:btih:abc&dn=Tom".to_string())
+        );
+    }
+
     #[test]
     fn test_parse_response_empty() {
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -481,9 +1718,139 @@ mod tests {
   </channel>
 </rss>"#;
 
-        let client = TorznabClient::new();
-        let results = client.parse_response(xml, "Test").unwrap();
+        let results = parse_torznab_xml(xml, "Test").unwrap();
 
         assert!(results.is_empty());
     }
+
+    fn caps(tv: &[&str], movie: &[&str]) -> IndexerCaps {
+        let params = |params: &[&str]| {
+            (!params.is_empty()).then(|| params.iter().map(|p| p.to_string()).collect())
+        };
+        IndexerCaps {
+            search: Some(vec!["q".to_string()]),
+            tv_search: params(tv),
+            movie_search: params(movie),
+        }
+    }
+
+    fn request(query: &str, context: SearchContext) -> SearchRequest {
+        SearchRequest::new(query, context)
+    }
+
+    fn episode(tmdb_id: Option<u64>) -> SearchContext {
+        SearchContext {
+            title: "Severance".to_string(),
+            season: Some(2),
+            episode: Some(3),
+            media_type: Some("tv".to_string()),
+            tmdb_id,
+            ..Default::default()
+        }
+    }
+
+    fn movie(tmdb_id: Option<u64>) -> SearchContext {
+        SearchContext {
+            title: "Dune".to_string(),
+            year: Some(2021),
+            media_type: Some("movie".to_string()),
+            tmdb_id,
+            ..Default::default()
+        }
+    }
+
+    fn owned(params: &[(&'static str, &str)]) -> Vec<(&'static str, String)> {
+        params.iter().map(|(k, v)| (*k, v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_plan_episode() {
+        let full = caps(&["q", "season", "ep", "tmdbid"], &["q", "tmdbid"]);
+        assert_eq!(
+            full.plan(&request("Severance S02E03", episode(Some(95396)))),
+            (
+                SearchType::TvSearch,
+                owned(&[("tmdbid", "95396"), ("season", "2"), ("ep", "3")])
+            )
+        );
+
+        // Without an id the show title goes in q, not the SxxExx query
+        assert_eq!(
+            full.plan(&request("Severance S02E03", episode(None))).1,
+            owned(&[("q", "Severance"), ("season", "2"), ("ep", "3")])
+        );
+        let no_tmdb = caps(&["q", "season", "ep", "tvdbid"], &[]);
+        assert_eq!(
+            no_tmdb
+                .plan(&request("Severance S02E03", episode(Some(95396))))
+                .1[0],
+            ("q", "Severance".to_string())
+        );
+
+        // Season searches without episodes would pull in the whole season
+        let no_ep = caps(&["q", "season"], &[]);
+        assert_eq!(
+            no_ep.plan(&request("Severance S02E03", episode(Some(95396)))),
+            (SearchType::Search, owned(&[("q", "Severance S02E03")]))
+        );
+
+        // Absolute anime numbering has no season
+        let anime = SearchContext {
+            season: None,
+            media_type: Some("anime".to_string()),
+            ..episode(None)
+        };
+        assert_eq!(
+            full.plan(&request("Frieren 12", anime)).0,
+            SearchType::Search
+        );
+    }
+
+    #[test]
+    fn test_plan_movie() {
+        let full = caps(&[], &["q", "imdbid", "tmdbid"]);
+        assert_eq!(
+            full.plan(&request("Dune 2021", movie(Some(438631)))),
+            (SearchType::Movie, owned(&[("tmdbid", "438631")]))
+        );
+
+        // Free text keeps the year, which the movie search can't take
+        let imdb_only = caps(&[], &["q", "imdbid"]);
+        assert_eq!(
+            imdb_only.plan(&request("Dune 2021", movie(Some(438631)))),
+            (SearchType::Search, owned(&[("q", "Dune 2021")]))
+        );
+
+        // Unless the IMDB id is known
+        let with_imdb = SearchContext {
+            imdb_id: Some("tt1160419".to_string()),
+            ..movie(Some(438631))
+        };
+        assert_eq!(
+            imdb_only.plan(&request("Dune 2021", with_imdb.clone())),
+            (SearchType::Movie, owned(&[("imdbid", "1160419")]))
+        );
+        assert_eq!(
+            full.plan(&request("Dune 2021", with_imdb)),
+            (SearchType::Movie, owned(&[("tmdbid", "438631")]))
+        );
+    }
+
+    #[test]
+    fn test_plan_without_text_search() {
+        let structured_only = IndexerCaps {
+            search: None,
+            ..caps(&["q", "season", "ep"], &["q"])
+        };
+        assert_eq!(
+            structured_only.plan(&request("Dune 2021", movie(None))),
+            (SearchType::Movie, owned(&[("q", "Dune 2021")]))
+        );
+        assert_eq!(
+            structured_only
+                .plan(&SearchRequest::new("anything", SearchContext::default()))
+                .0,
+            SearchType::TvSearch
+        );
+    }
 }
@@ -1,8 +1,34 @@
-use crate::streaming::VideoFile;
-use crate::tmdb::{Episode, SearchResult as TmdbResult, SeasonSummary, TvDetails};
-use crate::torznab::TorrentResult;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{BuildHasher, DefaultHasher, Hash, Hasher, RandomState};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tokio::task::JoinSet;
+
+use super::playback::{MediaContext, Subtitle};
+use crate::cast::CastDevice;
+use crate::config::{Config, ConfigDiff, ConfigLocation};
+use crate::mpv::{AudioTrack, Chapter};
+use crate::streaming::{
+    AddPhase, DetectedPlayer, IpcEndpoint, MediaMode, PieceMap, Probe, SearchContext,
+    StreamingSession, TorrentInfo, VideoFile,
+};
+use crate::tmdb::{
+    AirStatus, CollectionSummary, Episode, SearchResult as TmdbResult, SeasonSummary, TmdbError,
+    TvDetails,
+};
+use crate::torznab::{ReleaseGroup, TorrentResult, group_releases};
 
 use crate::doctor::{CheckResult, CheckStatus};
+use crate::extensions::parse_episode_info;
+use crate::history::{FINISHED_PERCENT, HistoryKey};
+use crate::indexer_health::{IndexerHealth, SharedIndexerHealth};
+use crate::recovery::SessionSnapshot;
+use crate::search_service::{SearchService, SharedSearchService};
+
+use super::progress::ProgressSender;
+use super::thumbnail::Thumbnail;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum View {
@@ -10,6 +36,10 @@ pub enum View {
     Wizard,
     /// Discovery/browse page with content rows
     Discovery,
+    /// Movies/shows of an actor or director (from an `actor:`/`director:` search)
+    PersonFilmography,
+    /// Movies of a franchise in release order
+    Collection,
     Search,
     Results,
     /// Browse seasons of a TV show
@@ -19,7 +49,15 @@ pub enum View {
     FileSelection,
     Streaming,
     Doctor,
+    /// Per-indexer search stats, opened from Doctor
+    Indexers,
     Settings,
+    /// Confirm exporting watch history to CSV
+    HistoryExport,
+    /// Peers of the torrent being streamed, opened from Streaming
+    PeerList,
+    /// Chromecasts and DLNA renderers to cast the stream to, opened from Streaming
+    CastDevices,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
@@ -29,6 +67,8 @@ pub enum WizardStep {
     Prowlarr,
     Tmdb,
     Player,
+    Storage,
+    Integrations,
     Done,
 }
 
@@ -38,7 +78,9 @@ impl WizardStep {
             WizardStep::Welcome => WizardStep::Prowlarr,
             WizardStep::Prowlarr => WizardStep::Tmdb,
             WizardStep::Tmdb => WizardStep::Player,
-            WizardStep::Player => WizardStep::Done,
+            WizardStep::Player => WizardStep::Storage,
+            WizardStep::Storage => WizardStep::Integrations,
+            WizardStep::Integrations => WizardStep::Done,
             WizardStep::Done => WizardStep::Done,
         }
     }
@@ -49,7 +91,9 @@ impl WizardStep {
             WizardStep::Prowlarr => WizardStep::Welcome,
             WizardStep::Tmdb => WizardStep::Prowlarr,
             WizardStep::Player => WizardStep::Tmdb,
-            WizardStep::Done => WizardStep::Player,
+            WizardStep::Storage => WizardStep::Player,
+            WizardStep::Integrations => WizardStep::Storage,
+            WizardStep::Done => WizardStep::Integrations,
         }
     }
 
@@ -59,12 +103,28 @@ impl WizardStep {
             WizardStep::Prowlarr => 1,
             WizardStep::Tmdb => 2,
             WizardStep::Player => 3,
-            WizardStep::Done => 4,
+            WizardStep::Storage => 4,
+            WizardStep::Integrations => 5,
+            WizardStep::Done => 6,
         }
     }
 
     pub fn total() -> usize {
-        5
+        7
+    }
+
+    /// The step that sets up a settings section, to re-run it from Settings
+    pub fn for_section(section: SettingsSection) -> Option<Self> {
+        match section {
+            SettingsSection::Prowlarr => Some(WizardStep::Prowlarr),
+            SettingsSection::Tmdb => Some(WizardStep::Tmdb),
+            SettingsSection::Player => Some(WizardStep::Player),
+            SettingsSection::Storage => Some(WizardStep::Storage),
+            SettingsSection::Discord | SettingsSection::Trakt | SettingsSection::Simkl => {
+                Some(WizardStep::Integrations)
+            }
+            _ => None,
+        }
     }
 }
 
@@ -75,9 +135,13 @@ pub enum SettingsSection {
     Tmdb,
     Player,
     Streaming,
+    Filters,
+    Network,
+    Storage,
     Subtitles,
     Discord,
     Trakt,
+    Simkl,
 }
 
 impl SettingsSection {
@@ -86,22 +150,30 @@ impl SettingsSection {
             SettingsSection::Prowlarr => SettingsSection::Tmdb,
             SettingsSection::Tmdb => SettingsSection::Player,
             SettingsSection::Player => SettingsSection::Streaming,
-            SettingsSection::Streaming => SettingsSection::Subtitles,
+            SettingsSection::Streaming => SettingsSection::Filters,
+            SettingsSection::Filters => SettingsSection::Network,
+            SettingsSection::Network => SettingsSection::Storage,
+            SettingsSection::Storage => SettingsSection::Subtitles,
             SettingsSection::Subtitles => SettingsSection::Discord,
             SettingsSection::Discord => SettingsSection::Trakt,
-            SettingsSection::Trakt => SettingsSection::Prowlarr,
+            SettingsSection::Trakt => SettingsSection::Simkl,
+            SettingsSection::Simkl => SettingsSection::Prowlarr,
         }
     }
 
     pub fn prev(self) -> Self {
         match self {
-            SettingsSection::Prowlarr => SettingsSection::Trakt,
+            SettingsSection::Prowlarr => SettingsSection::Simkl,
             SettingsSection::Tmdb => SettingsSection::Prowlarr,
             SettingsSection::Player => SettingsSection::Tmdb,
             SettingsSection::Streaming => SettingsSection::Player,
-            SettingsSection::Subtitles => SettingsSection::Streaming,
+            SettingsSection::Filters => SettingsSection::Streaming,
+            SettingsSection::Network => SettingsSection::Filters,
+            SettingsSection::Storage => SettingsSection::Network,
+            SettingsSection::Subtitles => SettingsSection::Storage,
             SettingsSection::Discord => SettingsSection::Subtitles,
             SettingsSection::Trakt => SettingsSection::Discord,
+            SettingsSection::Simkl => SettingsSection::Trakt,
         }
     }
 
@@ -111,23 +183,19 @@ impl SettingsSection {
             SettingsSection::Tmdb => "TMDB",
             SettingsSection::Player => "Player",
             SettingsSection::Streaming => "Streaming",
+            SettingsSection::Filters => "Filters",
+            SettingsSection::Network => "Network",
+            SettingsSection::Storage => "Storage",
             SettingsSection::Subtitles => "Subtitles",
             SettingsSection::Discord => "Discord",
             SettingsSection::Trakt => "Trakt",
+            SettingsSection::Simkl => "Simkl",
         }
     }
 
     /// Number of editable fields in this section
     pub fn field_count(&self) -> usize {
-        match self {
-            SettingsSection::Prowlarr => 2,  // url, apikey
-            SettingsSection::Tmdb => 1,      // apikey
-            SettingsSection::Player => 2,    // command, args
-            SettingsSection::Streaming => 1, // auto_race
-            SettingsSection::Subtitles => 3, // enabled, language, api_key
-            SettingsSection::Discord => 2,   // enabled, app_id
-            SettingsSection::Trakt => 3,     // enabled, client_id, access_token
-        }
+        super::settings::fields(*self).len()
     }
 
     pub const ALL: &'static [SettingsSection] = &[
@@ -135,15 +203,56 @@ impl SettingsSection {
         SettingsSection::Tmdb,
         SettingsSection::Player,
         SettingsSection::Streaming,
+        SettingsSection::Filters,
+        SettingsSection::Network,
+        SettingsSection::Storage,
         SettingsSection::Subtitles,
         SettingsSection::Discord,
         SettingsSection::Trakt,
+        SettingsSection::Simkl,
     ];
 }
 
+/// What the player was started with in the way of subtitles
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum SubtitleStatus {
+    /// Still being looked up
+    #[default]
+    Unknown,
+    None,
+    Attached(Subtitle),
+    /// Attached, then turned off since the audio is in its language
+    Hidden {
+        subtitle: Subtitle,
+        audio: String,
+    },
+}
+
+impl SubtitleStatus {
+    /// "Subs: OpenSubtitles (en)", None while still looking
+    pub fn label(&self) -> Option<String> {
+        let describe = |subtitle: &Subtitle| match &subtitle.language {
+            Some(language) => format!("{} ({})", subtitle.source.label(), language),
+            None => subtitle.source.label().to_string(),
+        };
+        match self {
+            SubtitleStatus::Unknown => None,
+            SubtitleStatus::None => Some("Subs: none".to_string()),
+            SubtitleStatus::Attached(subtitle) => Some(format!("Subs: {}", describe(subtitle))),
+            SubtitleStatus::Hidden { subtitle, audio } => Some(format!(
+                "Subs: {}, off since audio is {}",
+                describe(subtitle),
+                audio
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum SortOrder {
+    /// Composite score of seeders, quality, size and title match
     #[default]
+    Smart,
     SeedersDesc,
     SeedersAsc,
     SizeDesc,
@@ -155,17 +264,32 @@ pub enum SortOrder {
 impl SortOrder {
     pub fn next(self) -> Self {
         match self {
+            SortOrder::Smart => SortOrder::SeedersDesc,
             SortOrder::SeedersDesc => SortOrder::SeedersAsc,
             SortOrder::SeedersAsc => SortOrder::SizeDesc,
             SortOrder::SizeDesc => SortOrder::SizeAsc,
             SortOrder::SizeAsc => SortOrder::NameAsc,
             SortOrder::NameAsc => SortOrder::NameDesc,
-            SortOrder::NameDesc => SortOrder::SeedersDesc,
+            SortOrder::NameDesc => SortOrder::Smart,
+        }
+    }
+
+    /// Shorter label for compact mode
+    pub fn short_label(&self) -> &'static str {
+        match self {
+            SortOrder::Smart => "Smart",
+            SortOrder::SeedersDesc => "S↓",
+            SortOrder::SeedersAsc => "S↑",
+            SortOrder::SizeDesc => "Size↓",
+            SortOrder::SizeAsc => "Size↑",
+            SortOrder::NameAsc => "A-Z",
+            SortOrder::NameDesc => "Z-A",
         }
     }
 
     pub fn label(&self) -> &'static str {
         match self {
+            SortOrder::Smart => "Smart",
             SortOrder::SeedersDesc => "Seeders ↓",
             SortOrder::SeedersAsc => "Seeders ↑",
             SortOrder::SizeDesc => "Size ↓",
@@ -176,6 +300,41 @@ impl SortOrder {
     }
 }
 
+/// Which kind of release the results list shows
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MediaFilter {
+    #[default]
+    All,
+    Movie,
+    Tv,
+}
+
+impl MediaFilter {
+    pub fn next(self) -> Self {
+        match self {
+            MediaFilter::All => MediaFilter::Movie,
+            MediaFilter::Movie => MediaFilter::Tv,
+            MediaFilter::Tv => MediaFilter::All,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MediaFilter::All => "All",
+            MediaFilter::Movie => "Movies",
+            MediaFilter::Tv => "TV",
+        }
+    }
+
+    pub fn matches(&self, result: &TorrentResult) -> bool {
+        match self {
+            MediaFilter::All => true,
+            MediaFilter::Movie => !result.is_tv(),
+            MediaFilter::Tv => result.is_tv(),
+        }
+    }
+}
+
 /// TMDB metadata for the current search
 #[derive(Debug, Clone, Default)]
 pub struct TmdbMetadata {
@@ -188,6 +347,71 @@ pub struct TmdbMetadata {
     pub poster_url: Option<String>,
 }
 
+impl From<&TmdbResult> for TmdbMetadata {
+    fn from(result: &TmdbResult) -> Self {
+        TmdbMetadata {
+            id: Some(result.id),
+            title: result.display_title().to_string(),
+            year: result.year(),
+            overview: result.overview.clone(),
+            rating: result.vote_average,
+            media_type: result.media_type.clone(),
+            poster_url: result.poster_url("w500"),
+        }
+    }
+}
+
+/// Several TMDB matches fit a search equally well, the user picks one
+#[derive(Debug, Clone)]
+pub struct TmdbPrompt {
+    pub search_id: u64,
+    pub query: String,
+    pub candidates: Vec<TmdbMetadata>,
+}
+
+/// Answer to the missing subtitles prompt
+#[derive(Debug)]
+pub enum SubtitleDecision {
+    /// Launch without, or with the torrent's subtitles in another language
+    Continue,
+    /// Look for subtitles in this language instead
+    Language(String),
+}
+
+/// No subtitle was found in the preferred languages, the player launch waits
+/// for the answer
+#[derive(Debug)]
+pub struct SubtitlePrompt {
+    pub stream_id: u64,
+    pub languages: Vec<String>,
+    /// The torrent ships subtitles in some other language
+    pub has_fallback: bool,
+    /// Language being typed, None until `l` is pressed
+    pub language_input: Option<String>,
+    pub reply: oneshot::Sender<SubtitleDecision>,
+}
+
+impl SubtitlePrompt {
+    pub fn answer(self, decision: SubtitleDecision) {
+        let _ = self.reply.send(decision);
+    }
+}
+
+/// Peers of a picked result were counted, streaming it waits for the answer
+#[derive(Debug)]
+pub struct ProbePrompt {
+    pub stream_id: u64,
+    pub probe: Probe,
+    /// true streams the torrent, false (or dropping it) backs out
+    pub reply: oneshot::Sender<bool>,
+}
+
+impl ProbePrompt {
+    pub fn answer(self, stream: bool) {
+        let _ = self.reply.send(stream);
+    }
+}
+
 /// TMDB suggestion for autocomplete
 #[derive(Debug, Clone)]
 pub struct TmdbSuggestion {
@@ -197,34 +421,144 @@ pub struct TmdbSuggestion {
     pub media_type: String,
 }
 
+impl TmdbSuggestion {
+    /// TV show or anime (browsed by season/episode)
+    pub fn is_series(&self) -> bool {
+        self.media_type == "tv" || self.media_type == "anime"
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum StreamingState {
     Connecting,
     FetchingMetadata,
-    Ready { stream_url: String },
-    Playing,
+    Ready {
+        stream_url: String,
+    },
+    /// The player is up
+    Playing {
+        stream_url: String,
+    },
+    /// The torrent HTTP API died mid-playback, the stream can be restarted
+    BackendLost,
     Error(String),
 }
 
+impl StreamingState {
+    /// URL of the file being streamed, once there is one
+    pub fn stream_url(&self) -> Option<&str> {
+        match self {
+            Self::Ready { stream_url } | Self::Playing { stream_url } => Some(stream_url),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct DownloadProgress {
     pub downloaded_bytes: u64,
     pub total_bytes: u64,
     pub download_speed: u64, // bytes/sec
     pub upload_speed: u64,   // bytes/sec
+    pub uploaded_bytes: u64,
     pub peers_connected: u32,
     pub progress_percent: f64,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToastLevel {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl ToastLevel {
+    /// How long a toast of this level stays on screen
+    pub fn ttl(self) -> Duration {
+        match self {
+            ToastLevel::Info | ToastLevel::Success => Duration::from_secs(3),
+            ToastLevel::Warning | ToastLevel::Error => Duration::from_secs(6),
+        }
+    }
+}
+
+/// Short-lived message shown in the bottom-right corner
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub message: String,
+    pub level: ToastLevel,
+    pub expires_at: Instant,
+}
+
+/// Image from TMDB: an episode still or a Discovery backdrop
+#[derive(Debug, Clone)]
+pub enum Artwork {
+    Loading,
+    Missing,
+    Loaded(Thumbnail),
+}
+
+/// How long a new Discovery backdrop takes to fade in
+const BACKDROP_FADE: Duration = Duration::from_millis(400);
+
+/// Backdrop behind the Discovery rows, fading in over the one before it
+#[derive(Debug, Clone)]
+pub struct BackdropFade {
+    pub url: String,
+    pub image: Thumbnail,
+    pub previous: Option<Thumbnail>,
+    pub started: Instant,
+}
+
+impl BackdropFade {
+    /// How far along the fade is, from 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        (self.started.elapsed().as_secs_f32() / BACKDROP_FADE.as_secs_f32()).min(1.0)
+    }
+}
+
+/// Next episode found and connected in the background while the current one plays
+pub struct PrefetchedEpisode {
+    pub episode_index: usize, // Into tv_episodes
+    pub title: String,
+    pub context: SearchContext,
+    pub torrent_info: TorrentInfo,
+    pub session: std::sync::Arc<StreamingSession>,
+}
+
+/// Peer of the streamed torrent, ready for display
+#[derive(Debug, Clone)]
+pub struct PeerRow {
+    pub masked_ip: String,
+    pub flag: Option<String>, // Country flag emoji, needs a GeoIP database
+    pub share: f64,           // Percent of all bytes fetched from peers
+    pub state: String,
+}
+
+/// Most toasts shown at once - older ones are dropped
+const MAX_TOASTS: usize = 4;
+
+/// How long a result has to stay highlighted before it's looked up on TMDB
+const ENRICH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Download speed samples kept for the speed graph (one per progress update)
+const SPEED_HISTORY_LEN: usize = 60;
+
 pub struct App {
     pub view: View,
     pub should_quit: bool,
+    pub config_location: ConfigLocation, // Where settings are saved
+    pub log_path: PathBuf,               // Log file, read into diagnostics bundles
 
     // Search
     pub search_input: String,
+    pub results_query: String, // Query the shown results were searched with
+    pub transformed_query: Option<(String, String)>, // (Sent, typed) when query_transforms changed it
     pub is_searching: bool,
     pub search_error: Option<String>,
     pub search_id: u64, // Incremented for each search to ignore stale results
+    pub media_mode: MediaMode, // Video, audio or both, cycled with Tab
 
     // Autocomplete
     pub suggestions: Vec<TmdbSuggestion>,
@@ -236,9 +570,35 @@ pub struct App {
     pub selected_index: usize,
     pub sort_order: SortOrder,
     pub tmdb_info: Option<TmdbMetadata>,
+    pub hidden_results: Vec<TorrentResult>, // Dropped by the result filter
+    pub result_scores: Vec<u8>,             // Smart score of each entry in `results`
+    pub hidden_scores: Vec<u8>,             // Smart score of each entry in `hidden_results`
+    pub show_hidden: bool,                  // Whether hidden results are merged into the list
+    pub marked_results: HashSet<String>,    // Torrent URLs picked with Space to race
+    pub result_media_filter: MediaFilter,
+    pub media_filtered: Vec<(TorrentResult, u8)>, // Results and scores the media filter hides
+    pub group_releases: bool,                     // One row per release, toggled with 'g'
+    pub release_groups: Vec<ReleaseGroup>,        // Groups of `results` while grouped
+    pub expanded_group: Option<usize>,            // Group showing its variants
+    pub selected_group: usize,
+    pub selected_variant: Option<usize>, // Row in the expanded group, None on its own row
+
+    // TMDB matches guessed from raw result titles, when the search had none
+    pub enrichment_cache: HashMap<String, Option<TmdbMetadata>>, // Torrent title -> match
+    pub enrichment_in_flight: HashSet<String>,
+    pub enrichment_highlight: Option<(String, Instant)>, // Highlighted title and since when
+    pub enrichment_started: Option<String>, // Title of a started result still being looked up
+    pub tmdb_info_guessed: bool,            // `tmdb_info` came from the highlighted result
+
+    // TMDB match disambiguation
+    pub tmdb_prompt: Option<TmdbPrompt>, // Open until a match is picked
+    pub selected_tmdb_candidate: usize,
+    pub tmdb_choices: HashMap<String, u64>, // Query -> picked TMDB id, for this session
 
     // File selection (for multi-file torrents)
     pub available_files: Vec<VideoFile>,
+    pub extra_files: Vec<VideoFile>, // Samples, trailers and featurettes of the torrent
+    pub show_extras: bool,           // Whether extras are listed after the files
     pub selected_file_index: usize,
     pub pending_torrent_id: Option<usize>,
 
@@ -246,6 +606,10 @@ pub struct App {
     pub current_episode_index: usize, // Index in available_files of currently playing
     pub next_episode_ready: bool,     // True when next episode is pre-loaded
     pub auto_play_next: bool,         // Whether to auto-advance to next episode
+    pub shuffle_episodes: bool,       // Play the pack's files in random order (`S`)
+    pub played_episode_indices: HashSet<usize>, // Started this shuffle round
+    pub loop_shuffle: bool,           // Start another round once all were played (`L`)
+    shuffle_seed: u64,
 
     // Streaming
     pub streaming_state: StreamingState,
@@ -256,37 +620,103 @@ pub struct App {
     pub current_media_type: Option<String>,
     pub current_poster_url: Option<String>,
     pub download_progress: DownloadProgress,
-    pub is_streaming: bool, // Prevents spawning multiple stream tasks
+    /// Recent download speed samples, oldest first, for the speed graph
+    pub download_speed_history: VecDeque<u64>,
+    /// Downloaded pieces of the file streaming, refreshed with the progress
+    pub piece_map: Option<PieceMap>,
+    pub piece_map_loading: bool,
+    pub add_phase: Option<AddPhase>, // Furthest any torrent of the stream got while being added
+    pub is_streaming: bool,          // Prevents spawning multiple stream tasks
+    /// Download progress while waiting for the pre-roll buffer, None once the player launches
+    pub buffering_percent: Option<f64>,
+    /// Audio tracks of the file playing in mpv, switched over `player_ipc`
+    pub audio_tracks: Vec<AudioTrack>,
+    pub player_ipc: Option<IpcEndpoint>,
+    /// Chapters of the file playing in mpv, with its length to place the playback position
+    pub chapters: Vec<Chapter>,
+    pub media_duration: f64,
+    /// Subtitle the player was started with, for the File block
+    pub subtitle_status: SubtitleStatus,
+    pub current_chapter: Option<usize>,
+    pub restarting_stream: bool, // The next PlayerExited is the stalled player closed for a restart
+    pub switching_player: bool,  // The next PlayerExited is the old player closed for another one
+    /// Id of the stream being watched, messages from earlier streams carry an older one
+    pub stream_id: u64,
+    /// Stream started from a pasted magnet or .torrent, titled by its torrent name
+    pub direct_stream_id: Option<u64>,
+    /// Id of the latest streaming session, names its storage subdirectory
+    pub session_id: u64,
+
+    // Peer list
+    pub peers: Vec<PeerRow>,
+    pub selected_peer_index: usize,
+    pub is_fetching_peers: bool,
+    pub peers_error: Option<String>,
+
+    // Casting
+    pub cast_devices: Vec<CastDevice>,
+    pub selected_cast_index: usize,
+    pub is_discovering_cast: bool,
+    pub is_starting_cast: bool,
+    pub casting_to: Option<String>, // Name of the device playing the stream
+    pub shared_stream_url: Option<String>, // Stream URL shown to open elsewhere
 
     // Doctor
     pub doctor_results: Vec<CheckResult>,
     pub is_checking: bool,
 
+    // Prowlarr access for searches, caches the indexer list
+    pub search_service: SharedSearchService,
+    // Where background polls send download and playback progress
+    pub progress: ProgressSender,
+
+    // Indexer health
+    pub indexer_health: SharedIndexerHealth,
+    pub selected_indexer_index: usize,
+
     // TV Show browsing
     pub tv_details: Option<TvDetails>,
     pub tv_seasons: Vec<SeasonSummary>,
     pub selected_season_index: usize,
     pub tv_episodes: Vec<Episode>,
     pub selected_episode_index: usize,
+    pub episode_progress: HashMap<(u32, u32), f64>, // (season, episode) -> watched percent
+    pub episode_stills: HashMap<u64, Artwork>,      // Episode id -> still, for this session
     pub is_fetching_tv_details: bool,
+    pub absolute_numbering: bool, // Anime episodes from AniDB/MAL, numbered across seasons
 
     // Settings
     pub settings_section: SettingsSection,
     pub settings_field_index: usize,
     pub settings_editing: bool,
     pub settings_edit_buffer: String,
-    pub settings_dirty: bool, // Has unsaved changes
+    pub settings_dirty: bool,                   // Has unsaved changes
+    pub settings_error: Option<String>,         // Validation error for the field being edited
+    pub settings_saved: Option<Config>, // Config as last saved, to diff against and discard to
+    pub settings_diff: Option<Vec<ConfigDiff>>, // Unsaved changes, asked about before leaving
+    pub cache_size: Option<u64>,        // Temp dir usage, None while it's measured
+    pub free_space: Option<u64>,        // Space left beside the temp dir, None if unknown
+    pub is_clearing_cache: bool,
 
     // Wizard
     pub wizard_step: WizardStep,
     pub wizard_field_index: usize, // Which field in current step
     pub wizard_editing: bool,
     pub wizard_edit_buffer: String,
+    pub wizard_return_to: Option<View>, // Where finishing goes when re-run from Settings
 
     // Resume prompt
     pub show_resume_prompt: bool,
     pub resume_progress: f64, // Progress percentage to resume from
 
+    // Stream left behind by a session that didn't stop cleanly, offered on Discovery
+    pub recovery_prompt: Option<SessionSnapshot>,
+    pub recovering_file: Option<usize>, // File index of the snapshot the next torrent picks up
+
+    // Missing subtitles prompt
+    pub subtitle_prompt: Option<SubtitlePrompt>,
+    pub probe_prompt: Option<ProbePrompt>,
+
     // Playback tracking (from mpv IPC)
     pub playback_progress: f64, // Actual playback progress from player
 
@@ -299,6 +729,66 @@ pub struct App {
     pub selected_item_index: usize,
     pub is_loading_discovery: bool,
     pub discovery_error: Option<String>,
+    pub discovery_filter: String,     // Inline "/" filter on titles
+    pub is_filtering_discovery: bool, // Typing into the filter bar
+    pub discovery_unfiltered: Vec<DiscoveryRow>, // All loaded rows, before the filter
+    pub last_discovery_load: Option<Instant>,
+    pub discovery_refresh_paused: bool, // Auto-refresh held off with 'p'
+    pub backdrops: HashMap<String, Artwork>, // Backdrop URL -> blurred image, for this session
+    pub backdrop_focus: Option<(String, Instant)>, // Backdrop of the focused title, since when
+    pub backdrop: Option<BackdropFade>,
+
+    // Person filmography
+    pub person_name: String,
+    pub person_credits: Vec<DiscoveryItem>,
+    pub selected_credit_index: usize,
+    pub is_fetching_person: bool,
+
+    // Movie collection (franchise)
+    pub results_collection: Option<(u64, CollectionSummary)>, // Search ID it was found for
+    pub collection_name: String,
+    pub collection_items: Vec<DiscoveryItem>,
+    pub selected_collection_index: usize,
+    pub is_fetching_collection: bool,
+
+    // Toasts
+    pub toasts: Vec<Toast>, // Oldest first
+
+    // History export
+    pub history_export_path: PathBuf,
+    pub history_export_count: usize,
+    pub history_export_result: Option<Result<String, String>>, // Popup after export
+
+    // Background seeding after playback
+    pub seeding_count: usize,
+    // Bytes down and up per torrent this run, keyed by streaming session and torrent id
+    pub transfers: HashMap<(u64, usize), (u64, u64)>,
+    pub show_quit_prompt: bool, // Confirm quitting while torrents still seed
+
+    // Compact mode, for narrow terminals
+    pub compact_mode: bool, // Help hints collapse to "[?]: help"
+    pub show_help: bool,    // Help popup opened with '?' in compact mode
+
+    // Releases matched by RSS rules, waiting to be streamed
+    pub rss_queue: VecDeque<TorrentResult>,
+
+    // Next episode prefetch
+    pub search_context: SearchContext, // What the last torrent search was for
+    pub prefetched_episode: Option<PrefetchedEpisode>,
+    pub show_next_episode_prompt: bool,
+    pub is_prefetching: bool, // Next episode search/race running in the background
+    pub cleanups: JoinSet<()>, // Discarded sessions still being cleaned up
+
+    // Open the stream in another player
+    pub show_player_picker: bool,
+    pub player_choices: Vec<String>,
+    pub selected_player_index: usize,
+
+    // Installed players, picked from in the wizard and settings
+    pub detected_players: Option<Vec<DetectedPlayer>>, // None until they're looked for
+    pub is_detecting_players: bool,
+    pub show_command_picker: bool, // Player settings list of detected players
+    pub selected_command_index: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -312,10 +802,19 @@ pub struct DiscoveryItem {
     pub id: u64,
     pub title: String,
     pub year: Option<u16>,
-    pub media_type: String, // "movie" or "tv"
+    pub media_type: String, // "movie", "tv" or "anime"
     pub poster_url: Option<String>,
+    pub backdrop_url: Option<String>,
     pub overview: Option<String>,
     pub rating: Option<f64>,
+    pub has_4k: bool, // Likely out in 4K HDR, shown as a badge
+}
+
+impl DiscoveryItem {
+    /// TV show or anime (browsed by season/episode)
+    pub fn is_series(&self) -> bool {
+        self.media_type == "tv" || self.media_type == "anime"
+    }
 }
 
 impl From<TmdbResult> for DiscoveryItem {
@@ -324,16 +823,22 @@ impl From<TmdbResult> for DiscoveryItem {
             id: result.id,
             title: result.display_title().to_string(),
             year: result.year(),
-            media_type: result.media_type.clone().unwrap_or_else(|| {
-                if result.name.is_some() {
-                    "tv".to_string()
-                } else {
-                    "movie".to_string()
-                }
-            }),
+            media_type: if result.is_anime() {
+                "anime".to_string()
+            } else {
+                result.media_type.clone().unwrap_or_else(|| {
+                    if result.name.is_some() {
+                        "tv".to_string()
+                    } else {
+                        "movie".to_string()
+                    }
+                })
+            },
             poster_url: result.poster_url("w300"),
+            backdrop_url: result.backdrop_url("w780"),
             overview: result.overview,
             rating: result.vote_average,
+            has_4k: false,
         }
     }
 }
@@ -343,10 +848,15 @@ impl App {
         Self {
             view: View::Discovery,
             should_quit: false,
+            config_location: ConfigLocation::default(),
+            log_path: PathBuf::new(),
             search_input: String::new(),
+            results_query: String::new(),
+            transformed_query: None,
             is_searching: false,
             search_error: None,
             search_id: 0,
+            media_mode: MediaMode::default(),
             suggestions: Vec::new(),
             selected_suggestion: 0,
             is_fetching_suggestions: false,
@@ -354,12 +864,35 @@ impl App {
             selected_index: 0,
             sort_order: SortOrder::default(),
             tmdb_info: None,
+            hidden_results: Vec::new(),
+            result_scores: Vec::new(),
+            hidden_scores: Vec::new(),
+            show_hidden: false,
+            group_releases: false,
+            release_groups: Vec::new(),
+            expanded_group: None,
+            selected_group: 0,
+            selected_variant: None,
+            marked_results: HashSet::new(),
+            result_media_filter: MediaFilter::default(),
+            media_filtered: Vec::new(),
+            enrichment_cache: HashMap::new(),
+            enrichment_in_flight: HashSet::new(),
+            enrichment_highlight: None,
+            enrichment_started: None,
+            tmdb_info_guessed: false,
             available_files: Vec::new(),
+            extra_files: Vec::new(),
+            show_extras: false,
             selected_file_index: 0,
             pending_torrent_id: None,
             current_episode_index: 0,
             next_episode_ready: false,
             auto_play_next: true, // Default to auto-play next episode
+            shuffle_episodes: false,
+            played_episode_indices: HashSet::new(),
+            loop_shuffle: false,
+            shuffle_seed: 0,
             streaming_state: StreamingState::Connecting,
             current_title: String::new(),
             current_file: String::new(),
@@ -368,26 +901,74 @@ impl App {
             current_media_type: None,
             current_poster_url: None,
             download_progress: DownloadProgress::default(),
+            download_speed_history: VecDeque::new(),
+            piece_map: None,
+            piece_map_loading: false,
+            add_phase: None,
             is_streaming: false,
+            buffering_percent: None,
+            audio_tracks: Vec::new(),
+            subtitle_status: SubtitleStatus::Unknown,
+            player_ipc: None,
+            chapters: Vec::new(),
+            media_duration: 0.0,
+            current_chapter: None,
+            restarting_stream: false,
+            switching_player: false,
+            stream_id: 0,
+            direct_stream_id: None,
+            // Seeded from the clock so ids don't repeat leftover directories of earlier runs
+            session_id: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            peers: Vec::new(),
+            selected_peer_index: 0,
+            is_fetching_peers: false,
+            peers_error: None,
+            cast_devices: Vec::new(),
+            selected_cast_index: 0,
+            is_discovering_cast: false,
+            is_starting_cast: false,
+            casting_to: None,
+            shared_stream_url: None,
             doctor_results: Vec::new(),
             is_checking: false,
+            indexer_health: IndexerHealth::default().shared(),
+            search_service: SearchService::new(&Default::default()).shared(),
+            progress: ProgressSender::new(),
+            selected_indexer_index: 0,
             tv_details: None,
             tv_seasons: Vec::new(),
             selected_season_index: 0,
             tv_episodes: Vec::new(),
             selected_episode_index: 0,
+            episode_progress: HashMap::new(),
+            episode_stills: HashMap::new(),
             is_fetching_tv_details: false,
+            absolute_numbering: false,
             settings_section: SettingsSection::default(),
             settings_field_index: 0,
             settings_editing: false,
             settings_edit_buffer: String::new(),
             settings_dirty: false,
+            settings_error: None,
+            settings_saved: None,
+            settings_diff: None,
+            cache_size: None,
+            free_space: None,
+            is_clearing_cache: false,
             wizard_step: WizardStep::default(),
             wizard_field_index: 0,
             wizard_editing: false,
             wizard_edit_buffer: String::new(),
+            wizard_return_to: None,
             show_resume_prompt: false,
             resume_progress: 0.0,
+            recovery_prompt: None,
+            recovering_file: None,
+            subtitle_prompt: None,
+            probe_prompt: None,
             playback_progress: 0.0,
             racing_message: None,
             discovery_rows: Vec::new(),
@@ -395,6 +976,53 @@ impl App {
             selected_item_index: 0,
             is_loading_discovery: false,
             discovery_error: None,
+            discovery_filter: String::new(),
+            is_filtering_discovery: false,
+            discovery_unfiltered: Vec::new(),
+            last_discovery_load: None,
+            discovery_refresh_paused: false,
+            backdrops: HashMap::new(),
+            backdrop_focus: None,
+            backdrop: None,
+            person_name: String::new(),
+            person_credits: Vec::new(),
+            selected_credit_index: 0,
+            results_collection: None,
+            tmdb_prompt: None,
+            selected_tmdb_candidate: 0,
+            tmdb_choices: HashMap::new(),
+            collection_name: String::new(),
+            collection_items: Vec::new(),
+            selected_collection_index: 0,
+            is_fetching_collection: false,
+            is_fetching_person: false,
+
+            toasts: Vec::new(),
+
+            history_export_path: PathBuf::new(),
+            history_export_count: 0,
+            history_export_result: None,
+
+            seeding_count: 0,
+            transfers: HashMap::new(),
+            rss_queue: VecDeque::new(),
+            show_quit_prompt: false,
+            compact_mode: false,
+            show_help: false,
+
+            search_context: SearchContext::default(),
+            prefetched_episode: None,
+            show_next_episode_prompt: false,
+            is_prefetching: false,
+            cleanups: JoinSet::new(),
+            show_player_picker: false,
+            player_choices: Vec::new(),
+            selected_player_index: 0,
+
+            detected_players: None,
+            is_detecting_players: false,
+            show_command_picker: false,
+            selected_command_index: 0,
         }
     }
 
@@ -403,11 +1031,28 @@ impl App {
             WizardStep::Welcome => 0,
             WizardStep::Prowlarr => 2, // url, apikey
             WizardStep::Tmdb => 1,     // apikey (optional)
-            WizardStep::Player => 1,   // command
+            WizardStep::Player => self.detected_players().len() + 1, // players, custom command
+            WizardStep::Storage => 1,  // temp dir
+            WizardStep::Integrations => 2, // simkl client id, access token (optional)
             WizardStep::Done => 0,
         }
     }
 
+    /// Installed players found so far
+    pub fn detected_players(&self) -> &[DetectedPlayer] {
+        self.detected_players.as_deref().unwrap_or_default()
+    }
+
+    /// Row of `command` among the detected players, or the custom command
+    /// row after them
+    pub fn player_option_index(&self, command: &str) -> usize {
+        let players = self.detected_players();
+        players
+            .iter()
+            .position(|player| player.command == command)
+            .unwrap_or(players.len())
+    }
+
     pub fn wizard_next_field(&mut self) {
         let max = self.wizard_field_count();
         if max > 0 {
@@ -426,6 +1071,10 @@ impl App {
         }
     }
 
+    pub fn selected_settings_field(&self) -> &'static super::settings::SettingsField {
+        &super::settings::fields(self.settings_section)[self.settings_field_index]
+    }
+
     pub fn settings_next_field(&mut self) {
         let max = self.settings_section.field_count();
         self.settings_field_index = (self.settings_field_index + 1) % max;
@@ -441,56 +1090,331 @@ impl App {
     }
 
     pub fn select_next(&mut self) {
-        if !self.results.is_empty() {
+        if self.group_releases {
+            let rows = self.result_rows();
+            let row = self.selected_row(&rows);
+            if let Some(&(group, variant)) = rows.get(row + 1) {
+                self.select_variant(group, variant);
+            }
+        } else if !self.results.is_empty() {
             self.selected_index = (self.selected_index + 1).min(self.results.len() - 1);
         }
     }
 
     pub fn select_previous(&mut self) {
-        if self.selected_index > 0 {
+        if self.group_releases {
+            let rows = self.result_rows();
+            let row = self.selected_row(&rows);
+            if let Some(&(group, variant)) = row.checked_sub(1).and_then(|row| rows.get(row)) {
+                self.select_variant(group, variant);
+            }
+        } else if self.selected_index > 0 {
             self.selected_index -= 1;
         }
     }
 
+    /// Rows of the grouped list: each group, followed by its variants when
+    /// it's expanded
+    pub fn result_rows(&self) -> Vec<(usize, Option<usize>)> {
+        let mut rows = Vec::new();
+        for (group, release) in self.release_groups.iter().enumerate() {
+            rows.push((group, None));
+            if self.expanded_group == Some(group) {
+                rows.extend((0..release.members.len()).map(|variant| (group, Some(variant))));
+            }
+        }
+        rows
+    }
+
+    fn selected_row(&self, rows: &[(usize, Option<usize>)]) -> usize {
+        rows.iter()
+            .position(|&row| row == (self.selected_group, self.selected_variant))
+            .unwrap_or(0)
+    }
+
+    /// Select a group's row (its best seeded variant) or one of its variants
+    fn select_variant(&mut self, group: usize, variant: Option<usize>) {
+        self.selected_group = group;
+        self.selected_variant = variant;
+        if let Some(release) = self.release_groups.get(group) {
+            self.selected_index = release.members[variant.unwrap_or(0)];
+        }
+    }
+
+    /// Switch between one row per result and one per release
+    pub fn toggle_release_grouping(&mut self) {
+        self.group_releases = !self.group_releases;
+        self.regroup_results();
+    }
+
+    /// Expand or collapse the selected group. False when there's nothing to
+    /// expand, a single variant or a variant row, so Enter streams it.
+    pub fn toggle_selected_group(&mut self) -> bool {
+        if !self.group_releases || self.selected_variant.is_some() {
+            return false;
+        }
+        let group = self.selected_group;
+        if self
+            .release_groups
+            .get(group)
+            .is_none_or(|release| release.members.len() < 2)
+        {
+            return false;
+        }
+        self.expanded_group = if self.expanded_group == Some(group) {
+            None
+        } else {
+            Some(group)
+        };
+        true
+    }
+
+    /// Rebuild the groups after the results changed, selecting the group of
+    /// the selected result
+    fn regroup_results(&mut self) {
+        self.expanded_group = None;
+        self.selected_variant = None;
+        if !self.group_releases {
+            self.release_groups.clear();
+            return;
+        }
+        self.release_groups = group_releases(&self.results);
+        let selected = self.selected_index;
+        self.selected_group = self
+            .release_groups
+            .iter()
+            .position(|release| release.members.contains(&selected))
+            .unwrap_or(0);
+        self.select_variant(self.selected_group, None);
+    }
+
     pub fn selected_result(&self) -> Option<&TorrentResult> {
         self.results.get(self.selected_index)
     }
 
+    /// Keep a guessed TMDB match in step with the highlighted result, and
+    /// return its title once it has been highlighted long enough to look up.
+    /// Does nothing when the search found a match itself.
+    pub fn result_to_enrich(&mut self, now: Instant) -> Option<String> {
+        if self.view != View::Results || (self.tmdb_info.is_some() && !self.tmdb_info_guessed) {
+            self.enrichment_highlight = None;
+            return None;
+        }
+        let title = self.selected_result()?.title.clone();
+        if let Some(guess) = self.enrichment_cache.get(&title) {
+            self.tmdb_info = guess.clone();
+            self.tmdb_info_guessed = guess.is_some();
+            return None;
+        }
+        if self.tmdb_info_guessed {
+            // Don't show the last result's match for this one
+            self.tmdb_info = None;
+            self.tmdb_info_guessed = false;
+        }
+        match &self.enrichment_highlight {
+            Some((highlighted, since)) if *highlighted == title => (now.duration_since(*since)
+                >= ENRICH_DEBOUNCE
+                && self.enrichment_in_flight.insert(title.clone()))
+            .then_some(title),
+            _ => {
+                self.enrichment_highlight = Some((title, now));
+                None
+            }
+        }
+    }
+
+    /// Store a looked up match, filling in the stream's ids if it belongs to
+    /// the result that was started. A failed lookup isn't cached, so it's
+    /// tried again the next time the result is highlighted.
+    pub fn finish_enrichment(
+        &mut self,
+        title: String,
+        lookup: Result<Option<TmdbMetadata>, TmdbError>,
+    ) {
+        self.enrichment_in_flight.remove(&title);
+        let Ok(info) = lookup else {
+            return;
+        };
+        if self.enrichment_started.as_ref() == Some(&title) {
+            self.enrichment_started = None;
+            if let Some(info) = &info
+                && self.is_streaming
+                && self.current_tmdb_id.is_none()
+            {
+                self.current_title = info.title.clone();
+                self.current_tmdb_id = info.id;
+                self.current_year = info.year;
+                self.current_media_type = info.media_type.clone();
+                self.current_poster_url = info.poster_url.clone();
+            }
+        }
+        self.enrichment_cache.insert(title, info);
+    }
+
     pub fn cycle_sort(&mut self) {
         self.sort_order = self.sort_order.next();
         self.sort_results();
     }
 
+    pub fn cycle_media_filter(&mut self) {
+        self.result_media_filter = self.result_media_filter.next();
+        self.sort_results();
+    }
+
+    /// Put the results the media filter hides back into the list
+    fn unfilter_media(&mut self) {
+        for (result, score) in std::mem::take(&mut self.media_filtered) {
+            self.results.push(result);
+            self.result_scores.push(score);
+        }
+    }
+
+    /// Replace the results, each list paired with its smart scores
+    pub fn set_results(
+        &mut self,
+        kept: Vec<(TorrentResult, u8)>,
+        hidden: Vec<(TorrentResult, u8)>,
+    ) {
+        (self.results, self.result_scores) = kept.into_iter().unzip();
+        (self.hidden_results, self.hidden_scores) = hidden.into_iter().unzip();
+        self.media_filtered.clear();
+        self.show_hidden = false;
+        self.marked_results.clear();
+        self.sort_results();
+    }
+
+    /// Mark the selected result for a manual race, or unmark it
+    pub fn toggle_marked_result(&mut self) {
+        let Some(url) = self.selected_result().and_then(|r| r.get_torrent_url()) else {
+            return;
+        };
+        if !self.marked_results.remove(&url) {
+            self.marked_results.insert(url);
+        }
+    }
+
+    pub fn is_marked(&self, result: &TorrentResult) -> bool {
+        result
+            .get_torrent_url()
+            .is_some_and(|url| self.marked_results.contains(&url))
+    }
+
+    /// URLs of the marked results in list order, filtered ones included
+    pub fn marked_urls(&self) -> Vec<String> {
+        let mut urls: Vec<String> = self
+            .results
+            .iter()
+            .chain(&self.hidden_results)
+            .chain(self.media_filtered.iter().map(|(r, _)| r))
+            .filter_map(|r| r.get_torrent_url())
+            .filter(|url| self.marked_results.contains(url))
+            .collect();
+        // Filtered results are in both lists while shown
+        let mut seen = HashSet::new();
+        urls.retain(|url| seen.insert(url.clone()));
+        urls
+    }
+
+    /// Recompute smart scores after the validation criteria changed
+    pub fn rescore_results(&mut self, score: impl Fn(&[TorrentResult]) -> Vec<u8>) {
+        self.unfilter_media();
+        self.result_scores = score(&self.results);
+        self.hidden_scores = score(&self.hidden_results);
+        self.sort_results();
+    }
+
+    /// Key a TMDB match choice is remembered under
+    pub fn tmdb_choice_key(query: &str) -> String {
+        query.trim().to_lowercase()
+    }
+
+    /// Show or hide results dropped by the result filter
+    pub fn toggle_hidden_results(&mut self) {
+        self.unfilter_media();
+        if self.show_hidden {
+            let hidden = &self.hidden_results;
+            (self.results, self.result_scores) = std::mem::take(&mut self.results)
+                .into_iter()
+                .zip(std::mem::take(&mut self.result_scores))
+                .filter(|(r, _)| !hidden.contains(r))
+                .unzip();
+        } else {
+            self.results.extend(self.hidden_results.iter().cloned());
+            self.result_scores
+                .extend(self.hidden_scores.iter().copied());
+        }
+        self.show_hidden = !self.show_hidden;
+        self.sort_results();
+    }
+
+    /// Sort results and their scores together, setting aside the ones the
+    /// media filter doesn't show
     pub fn sort_results(&mut self) {
+        let filter = self.result_media_filter;
+        let (mut scored, filtered): (Vec<(TorrentResult, u8)>, _) =
+            std::mem::take(&mut self.results)
+                .into_iter()
+                .zip(std::mem::take(&mut self.result_scores))
+                .chain(std::mem::take(&mut self.media_filtered))
+                .partition(|(r, _)| filter.matches(r));
+        self.media_filtered = filtered;
         match self.sort_order {
+            SortOrder::Smart => {
+                scored.sort_by_key(|(r, score)| (Reverse(*score), Reverse(r.seeders)));
+            }
             SortOrder::SeedersDesc => {
-                self.results.sort_by(|a, b| b.seeders.cmp(&a.seeders));
+                scored.sort_by_key(|(r, _)| Reverse(r.seeders));
             }
             SortOrder::SeedersAsc => {
-                self.results.sort_by(|a, b| a.seeders.cmp(&b.seeders));
+                scored.sort_by_key(|(r, _)| r.seeders);
             }
             SortOrder::SizeDesc => {
-                self.results.sort_by(|a, b| b.size.cmp(&a.size));
+                scored.sort_by_key(|(r, _)| Reverse(r.size));
             }
             SortOrder::SizeAsc => {
-                self.results.sort_by(|a, b| a.size.cmp(&b.size));
+                scored.sort_by_key(|(r, _)| r.size);
             }
             SortOrder::NameAsc => {
-                self.results
-                    .sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+                scored.sort_by_key(|(r, _)| r.title.to_lowercase());
             }
             SortOrder::NameDesc => {
-                self.results
-                    .sort_by(|a, b| b.title.to_lowercase().cmp(&a.title.to_lowercase()));
+                scored.sort_by_key(|(r, _)| Reverse(r.title.to_lowercase()));
             }
         }
+        (self.results, self.result_scores) = scored.into_iter().unzip();
         // Keep selection valid
         if self.selected_index >= self.results.len() {
             self.selected_index = self.results.len().saturating_sub(1);
         }
+        self.regroup_results();
     }
 
     // File selection helpers
+    /// Replace the files of a multi-file torrent, with its extras hidden
+    pub fn set_files(&mut self, files: Vec<VideoFile>, extras: Vec<VideoFile>) {
+        self.available_files = files;
+        self.extra_files = extras;
+        self.show_extras = false;
+        self.selected_file_index = 0;
+        self.played_episode_indices.clear();
+    }
+
+    /// Show or hide the torrent's extras at the end of the file list
+    pub fn toggle_extra_files(&mut self) {
+        if self.show_extras {
+            let extras = &self.extra_files;
+            self.available_files
+                .retain(|f| !extras.iter().any(|e| e.file_idx == f.file_idx));
+            self.selected_file_index = self
+                .selected_file_index
+                .min(self.available_files.len().saturating_sub(1));
+        } else {
+            self.available_files
+                .extend(self.extra_files.iter().cloned());
+        }
+        self.show_extras = !self.show_extras;
+    }
+
     pub fn select_next_file(&mut self) {
         if !self.available_files.is_empty() {
             self.selected_file_index =
@@ -508,26 +1432,176 @@ impl App {
         self.available_files.get(self.selected_file_index)
     }
 
+    /// Id for a new stream, bumped whenever one replaces the last
+    /// History key for what's playing: per episode for shows, so the episode
+    /// browser can mark them, per title for movies
+    pub fn history_key(&self) -> HistoryKey {
+        let context = &self.search_context;
+        let episode = match (context.season, context.episode) {
+            // Absolute (anime) numbering, the file's own numbers may not match the list
+            (None, Some(episode)) => Some((1, episode)),
+            (season, episode) => match parse_episode_info(&self.current_file) {
+                (Some(s), Some(e)) => Some((s, e)),
+                _ => season.zip(episode),
+            },
+        };
+        HistoryKey::new(self.current_tmdb_id, &self.current_file, episode)
+    }
+
+    /// What's playing, for the player launch
+    pub fn media_context(&self) -> MediaContext {
+        MediaContext {
+            tmdb_id: self.current_tmdb_id,
+            title: self.current_title.clone(),
+        }
+    }
+
+    pub fn next_stream_id(&mut self) -> u64 {
+        self.stream_id += 1;
+        self.stream_id
+    }
+
+    pub fn push_download_speed(&mut self, speed: u64) {
+        if self.download_speed_history.len() == SPEED_HISTORY_LEN {
+            self.download_speed_history.pop_front();
+        }
+        self.download_speed_history.push_back(speed);
+    }
+
+    pub fn current_audio_track(&self) -> Option<&AudioTrack> {
+        self.audio_tracks.iter().find(|t| t.selected)
+    }
+
+    /// Forget the tracks and chapters of a player that closed
+    pub fn forget_player(&mut self) {
+        self.audio_tracks.clear();
+        self.player_ipc = None;
+        self.chapters.clear();
+        self.current_chapter = None;
+        self.subtitle_status = SubtitleStatus::Unknown;
+    }
+
+    /// Follow the playback position into the chapter it's in
+    pub fn update_current_chapter(&mut self) {
+        let position = self.playback_progress / 100.0 * self.media_duration;
+        self.current_chapter = crate::mpv::chapter_at(&self.chapters, position);
+    }
+
+    /// "Chapter 3/12: Encore" while a file with chapters plays
+    pub fn current_chapter_label(&self) -> Option<String> {
+        let index = self.current_chapter?;
+        let chapter = self.chapters.get(index)?;
+        Some(format!(
+            "Chapter {}/{}: {}",
+            index + 1,
+            self.chapters.len(),
+            chapter.label(index)
+        ))
+    }
+
+    /// Mark a track as the playing one, ahead of mpv confirming the switch
+    pub fn select_audio_track(&mut self, id: i64) {
+        for track in &mut self.audio_tracks {
+            track.selected = track.id == id;
+        }
+    }
+
+    /// Clear progress and the speed graph for a new stream
+    pub fn reset_download_progress(&mut self) {
+        self.download_progress = DownloadProgress::default();
+        self.download_speed_history.clear();
+        self.piece_map = None;
+        self.buffering_percent = None;
+        self.add_phase = None;
+    }
+
+    /// Latest byte counters of a torrent, which keep growing while it seeds
+    pub fn record_transfer(&mut self, torrent: (u64, usize), downloaded: u64, uploaded: u64) {
+        self.transfers.insert(torrent, (downloaded, uploaded));
+    }
+
+    /// Bytes uploaded this run over every torrent, and the ratio to what was
+    /// downloaded (None until something was)
+    pub fn session_upload(&self) -> (u64, Option<f64>) {
+        let (downloaded, uploaded) = self
+            .transfers
+            .values()
+            .fold((0, 0), |(down, up), (d, u)| (down + d, up + u));
+        let ratio = (downloaded > 0).then(|| uploaded as f64 / downloaded as f64);
+        (uploaded, ratio)
+    }
+
+    /// Id for a new streaming session, every stream gets its own storage directory
+    pub fn next_session_id(&mut self) -> u64 {
+        self.session_id += 1;
+        self.session_id
+    }
+
     /// Check if there's a next episode available
     pub fn has_next_episode(&self) -> bool {
-        self.current_episode_index + 1 < self.available_files.len()
+        self.next_episode_index().is_some()
     }
 
     /// Get the next episode if available
     pub fn next_episode(&self) -> Option<&VideoFile> {
-        self.available_files.get(self.current_episode_index + 1)
+        self.available_files.get(self.next_episode_index()?)
     }
 
     /// Advance to next episode
     pub fn advance_to_next_episode(&mut self) -> Option<&VideoFile> {
-        if self.has_next_episode() {
-            self.current_episode_index += 1;
-            self.selected_file_index = self.current_episode_index;
-            self.next_episode_ready = false;
-            self.available_files.get(self.current_episode_index)
-        } else {
-            None
+        let next = self.next_episode_index()?;
+        if self.shuffle_episodes && self.unplayed_episodes().is_empty() {
+            // Every file had its turn, start another round
+            self.played_episode_indices.clear();
         }
+        self.current_episode_index = next;
+        self.selected_file_index = next;
+        self.played_episode_indices.insert(next);
+        self.next_episode_ready = false;
+        self.available_files.get(next)
+    }
+
+    /// Turn shuffle on or off, a new shuffle starts with nothing played
+    pub fn toggle_shuffle(&mut self) {
+        self.shuffle_episodes = !self.shuffle_episodes;
+        self.played_episode_indices.clear();
+        self.shuffle_seed = RandomState::new().hash_one(self.available_files.len());
+    }
+
+    /// Index of the file after the current one: the next in order, or with
+    /// shuffle a random one not played this round. Once all were played, any
+    /// other if looping, else none.
+    pub fn next_episode_index(&self) -> Option<usize> {
+        if !self.shuffle_episodes {
+            let next = self.current_episode_index + 1;
+            return (next < self.available_files.len()).then_some(next);
+        }
+        let mut candidates = self.unplayed_episodes();
+        if candidates.is_empty() && self.loop_shuffle {
+            candidates = (0..self.available_files.len())
+                .filter(|i| *i != self.current_episode_index)
+                .collect();
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+        // Seeded so the file pre-downloaded is the one played next
+        let mut hasher = DefaultHasher::new();
+        (
+            self.shuffle_seed,
+            self.current_episode_index,
+            self.played_episode_indices.len(),
+        )
+            .hash(&mut hasher);
+        Some(candidates[hasher.finish() as usize % candidates.len()])
+    }
+
+    fn unplayed_episodes(&self) -> Vec<usize> {
+        (0..self.available_files.len())
+            .filter(|i| {
+                *i != self.current_episode_index && !self.played_episode_indices.contains(i)
+            })
+            .collect()
     }
 
     // TV Season navigation
@@ -556,12 +1630,75 @@ impl App {
         }
     }
 
+    /// Select the first episode after the current one that hasn't aired by
+    /// `today`, wrapping around. False if none is upcoming.
+    pub fn select_next_unaired_episode(&mut self, today: i64) -> bool {
+        let count = self.tv_episodes.len();
+        let next = (1..=count)
+            .map(|offset| (self.selected_episode_index + offset) % count)
+            .find(|&idx| {
+                matches!(
+                    self.tv_episodes[idx].air_status(today),
+                    AirStatus::Upcoming { .. }
+                )
+            });
+        if let Some(idx) = next {
+            self.selected_episode_index = idx;
+        }
+        next.is_some()
+    }
+
     pub fn select_previous_episode(&mut self) {
         if self.selected_episode_index > 0 {
             self.selected_episode_index -= 1;
         }
     }
 
+    /// History key of an episode in the browser
+    pub fn episode_history_key(&self, episode: &Episode) -> Option<HistoryKey> {
+        Some(HistoryKey::Episode {
+            tmdb_id: self.tv_details.as_ref()?.id,
+            season: episode.season_number,
+            episode: episode.episode_number,
+        })
+    }
+
+    /// Watched percent of an episode in the browser
+    pub fn episode_watched_percent(&self, episode: &Episode) -> Option<f64> {
+        self.episode_progress
+            .get(&(episode.season_number, episode.episode_number))
+            .copied()
+    }
+
+    /// Select the first episode not watched to the end, or the first one
+    pub fn select_first_unwatched_episode(&mut self) {
+        self.selected_episode_index = self
+            .tv_episodes
+            .iter()
+            .position(|ep| {
+                self.episode_watched_percent(ep)
+                    .is_none_or(|p| p < FINISHED_PERCENT)
+            })
+            .unwrap_or(0);
+    }
+
+    /// Episode after the one being streamed, if it was picked in the episode browser
+    pub fn following_tv_episode(&self) -> Option<(usize, &Episode)> {
+        let context = &self.search_context;
+        let episode = context.episode?;
+        let show = self.tv_details.as_ref()?;
+        // Free-text searches and other shows don't belong to the loaded episode list
+        if context.media_type.is_none() || context.title != show.name {
+            return None;
+        }
+        let current = self.tv_episodes.iter().position(|e| {
+            e.episode_number == episode && context.season.is_none_or(|s| s == e.season_number)
+        })?;
+        self.tv_episodes
+            .get(current + 1)
+            .map(|next| (current + 1, next))
+    }
+
     pub fn selected_tv_episode(&self) -> Option<&Episode> {
         self.tv_episodes.get(self.selected_episode_index)
     }
@@ -601,4 +1738,814 @@ impl App {
             .get(self.selected_row_index)
             .and_then(|row| row.items.get(self.selected_item_index))
     }
+
+    /// Replace the discovery rows, keeping any filter applied. The selection
+    /// follows the selected title if it's still there, otherwise stays in
+    /// the same row, so a refresh doesn't throw away the scroll position.
+    pub fn set_discovery_rows(&mut self, rows: Vec<DiscoveryRow>) {
+        let selected_row = self
+            .discovery_rows
+            .get(self.selected_row_index)
+            .map(|row| row.title.clone());
+        let selected_item = self
+            .selected_discovery_item()
+            .map(|item| (item.id, item.media_type.clone()));
+        let (row_index, item_index) = (self.selected_row_index, self.selected_item_index);
+
+        self.discovery_unfiltered = rows;
+        self.apply_discovery_filter();
+
+        let Some(row_title) = selected_row else {
+            return;
+        };
+        let new_row_index = self
+            .discovery_rows
+            .iter()
+            .position(|row| row.title == row_title)
+            .unwrap_or(row_index.min(self.discovery_rows.len().saturating_sub(1)));
+        let Some(row) = self.discovery_rows.get(new_row_index) else {
+            return;
+        };
+        self.selected_row_index = new_row_index;
+        self.selected_item_index = row
+            .items
+            .iter()
+            .position(|item| selected_item.as_ref() == Some(&(item.id, item.media_type.clone())))
+            .unwrap_or(item_index.min(row.items.len().saturating_sub(1)));
+    }
+
+    /// Whether discovery should reload by itself: only while it's on screen,
+    /// not already loading, and not paused
+    pub fn discovery_refresh_due(&self, interval: Option<Duration>) -> bool {
+        let Some(interval) = interval else {
+            return false;
+        };
+        self.view == View::Discovery
+            && !self.is_loading_discovery
+            && !self.discovery_refresh_paused
+            && self
+                .last_discovery_load
+                .is_some_and(|loaded| loaded.elapsed() >= interval)
+    }
+
+    /// Show only items whose title contains the filter, case-insensitively,
+    /// dropping rows left empty
+    pub fn apply_discovery_filter(&mut self) {
+        let filter = self.discovery_filter.to_lowercase();
+        self.discovery_rows = if filter.is_empty() {
+            self.discovery_unfiltered.clone()
+        } else {
+            self.discovery_unfiltered
+                .iter()
+                .map(|row| DiscoveryRow {
+                    title: row.title.clone(),
+                    items: row
+                        .items
+                        .iter()
+                        .filter(|item| item.title.to_lowercase().contains(&filter))
+                        .cloned()
+                        .collect(),
+                })
+                .filter(|row| !row.items.is_empty())
+                .collect()
+        };
+        self.selected_row_index = 0;
+        self.selected_item_index = 0;
+    }
+
+    pub fn clear_discovery_filter(&mut self) {
+        self.discovery_filter.clear();
+        self.is_filtering_discovery = false;
+        self.apply_discovery_filter();
+    }
+
+    /// The only title left by the filter, if exactly one is (the same title
+    /// can show up in several rows)
+    pub fn single_discovery_match(&self) -> Option<&DiscoveryItem> {
+        let mut items = self.discovery_rows.iter().flat_map(|row| &row.items);
+        let first = items.next()?;
+        items
+            .all(|item| item.id == first.id && item.media_type == first.media_type)
+            .then_some(first)
+    }
+
+    // Person filmography navigation
+    pub fn select_next_credit(&mut self) {
+        if !self.person_credits.is_empty() {
+            self.selected_credit_index =
+                (self.selected_credit_index + 1).min(self.person_credits.len() - 1);
+        }
+    }
+
+    pub fn select_previous_credit(&mut self) {
+        if self.selected_credit_index > 0 {
+            self.selected_credit_index -= 1;
+        }
+    }
+
+    pub fn selected_credit(&self) -> Option<&DiscoveryItem> {
+        self.person_credits.get(self.selected_credit_index)
+    }
+
+    /// Collection of the movie the current results are for
+    pub fn current_collection(&self) -> Option<&CollectionSummary> {
+        self.results_collection
+            .as_ref()
+            .filter(|(search_id, _)| *search_id == self.search_id)
+            .map(|(_, collection)| collection)
+    }
+
+    /// Missing subtitles prompt for the current stream, if one is open
+    pub fn current_subtitle_prompt(&self) -> Option<&SubtitlePrompt> {
+        self.subtitle_prompt
+            .as_ref()
+            .filter(|prompt| prompt.stream_id == self.stream_id)
+    }
+
+    /// Peer count prompt for the current stream, if one is open
+    pub fn current_probe_prompt(&self) -> Option<&ProbePrompt> {
+        self.probe_prompt
+            .as_ref()
+            .filter(|prompt| prompt.stream_id == self.stream_id)
+    }
+
+    /// Whether keys go into a text field, so '?' can't open the help
+    pub fn is_typing(&self) -> bool {
+        self.view == View::Search
+            || self.wizard_editing
+            || self.settings_editing
+            || self.is_filtering_discovery
+            || self.is_picking_subtitle_language()
+    }
+
+    /// Whether a language is being typed into the missing subtitles prompt
+    pub fn is_picking_subtitle_language(&self) -> bool {
+        self.current_subtitle_prompt()
+            .is_some_and(|prompt| prompt.language_input.is_some())
+    }
+
+    /// Match prompt for the current search, if one is open
+    pub fn current_tmdb_prompt(&self) -> Option<&TmdbPrompt> {
+        self.tmdb_prompt
+            .as_ref()
+            .filter(|prompt| prompt.search_id == self.search_id)
+    }
+
+    pub fn select_next_tmdb_candidate(&mut self) {
+        let count = self
+            .current_tmdb_prompt()
+            .map_or(0, |prompt| prompt.candidates.len());
+        if count > 0 {
+            self.selected_tmdb_candidate = (self.selected_tmdb_candidate + 1).min(count - 1);
+        }
+    }
+
+    pub fn select_previous_tmdb_candidate(&mut self) {
+        self.selected_tmdb_candidate = self.selected_tmdb_candidate.saturating_sub(1);
+    }
+
+    // Collection navigation
+    pub fn select_next_collection_item(&mut self) {
+        if !self.collection_items.is_empty() {
+            self.selected_collection_index =
+                (self.selected_collection_index + 1).min(self.collection_items.len() - 1);
+        }
+    }
+
+    pub fn select_previous_collection_item(&mut self) {
+        if self.selected_collection_index > 0 {
+            self.selected_collection_index -= 1;
+        }
+    }
+
+    pub fn selected_collection_item(&self) -> Option<&DiscoveryItem> {
+        self.collection_items.get(self.selected_collection_index)
+    }
+
+    // Indexer navigation
+    pub fn select_next_indexer(&mut self) {
+        let count = self.indexer_health.lock().unwrap().iter().count();
+        if count > 0 {
+            self.selected_indexer_index = (self.selected_indexer_index + 1).min(count - 1);
+        }
+    }
+
+    pub fn select_previous_indexer(&mut self) {
+        if self.selected_indexer_index > 0 {
+            self.selected_indexer_index -= 1;
+        }
+    }
+
+    pub fn selected_indexer_name(&self) -> Option<String> {
+        self.indexer_health
+            .lock()
+            .unwrap()
+            .iter()
+            .nth(self.selected_indexer_index)
+            .map(|(name, _)| name.clone())
+    }
+
+    // Peer navigation
+    pub fn select_next_peer(&mut self) {
+        if !self.peers.is_empty() {
+            self.selected_peer_index = (self.selected_peer_index + 1).min(self.peers.len() - 1);
+        }
+    }
+
+    pub fn select_previous_peer(&mut self) {
+        if self.selected_peer_index > 0 {
+            self.selected_peer_index -= 1;
+        }
+    }
+
+    pub fn select_next_cast_device(&mut self) {
+        if !self.cast_devices.is_empty() {
+            self.selected_cast_index =
+                (self.selected_cast_index + 1).min(self.cast_devices.len() - 1);
+        }
+    }
+
+    pub fn select_previous_cast_device(&mut self) {
+        if self.selected_cast_index > 0 {
+            self.selected_cast_index -= 1;
+        }
+    }
+
+    pub fn selected_cast_device(&self) -> Option<&CastDevice> {
+        self.cast_devices.get(self.selected_cast_index)
+    }
+
+    pub fn push_toast(&mut self, message: impl Into<String>, level: ToastLevel, ttl: Duration) {
+        self.toasts.push(Toast {
+            message: message.into(),
+            level,
+            expires_at: Instant::now() + ttl,
+        });
+        if self.toasts.len() > MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+    }
+
+    /// Drop toasts whose time is up, called before each draw
+    pub fn expire_toasts(&mut self) {
+        let now = Instant::now();
+        self.toasts.retain(|t| t.expires_at > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episode(season: u32, number: u32) -> Episode {
+        serde_json::from_value(serde_json::json!({
+            "id": season * 100 + number,
+            "name": format!("Episode {}", number),
+            "episode_number": number,
+            "season_number": season,
+        }))
+        .unwrap()
+    }
+
+    fn browsing(show: &str) -> App {
+        let mut app = App::new();
+        app.tv_details = Some(
+            serde_json::from_value(serde_json::json!({
+                "id": 1,
+                "name": show,
+                "number_of_seasons": 1,
+                "number_of_episodes": 3,
+                "seasons": [],
+            }))
+            .unwrap(),
+        );
+        app.tv_episodes = (1..=3).map(|n| episode(1, n)).collect();
+        app
+    }
+
+    #[test]
+    fn test_subtitle_prompt_for_current_stream() {
+        let mut app = App::new();
+        let stream_id = app.next_stream_id();
+        let (reply, mut decision) = oneshot::channel();
+        app.subtitle_prompt = Some(SubtitlePrompt {
+            stream_id,
+            languages: vec!["nl".to_string()],
+            has_fallback: false,
+            language_input: None,
+            reply,
+        });
+        assert!(app.current_subtitle_prompt().is_some());
+        assert!(!app.is_picking_subtitle_language());
+
+        app.subtitle_prompt.as_mut().unwrap().language_input = Some(String::new());
+        assert!(app.is_picking_subtitle_language());
+
+        // A newer stream doesn't show the old prompt
+        app.next_stream_id();
+        assert!(app.current_subtitle_prompt().is_none());
+        assert!(!app.is_picking_subtitle_language());
+
+        app.subtitle_prompt
+            .take()
+            .unwrap()
+            .answer(SubtitleDecision::Language("de".to_string()));
+        assert!(matches!(
+            decision.try_recv(),
+            Ok(SubtitleDecision::Language(language)) if language == "de"
+        ));
+    }
+
+    #[test]
+    fn test_toggle_extra_files() {
+        let file = |name: &str, file_idx| VideoFile {
+            name: name.to_string(),
+            file_idx,
+            size: 0,
+            stream_url: String::new(),
+        };
+        let mut app = App::new();
+        app.set_files(
+            vec![file("S01E01.mkv", 0), file("S01E02.mkv", 1)],
+            vec![file("Sample/sample.mkv", 2)],
+        );
+
+        app.toggle_extra_files();
+        assert_eq!(app.available_files.len(), 3);
+        app.selected_file_index = 2;
+
+        app.toggle_extra_files();
+        assert!(!app.show_extras);
+        assert_eq!(app.available_files.len(), 2);
+        assert_eq!(app.selected_file_index, 1);
+    }
+
+    #[test]
+    fn test_shuffle_episodes() {
+        let mut app = App::new();
+        let files = (0..6)
+            .map(|i| VideoFile {
+                name: format!("S01E0{}.mkv", i + 1),
+                file_idx: i,
+                size: 0,
+                stream_url: String::new(),
+            })
+            .collect();
+        app.set_files(files, Vec::new());
+        app.current_episode_index = 2;
+        assert_eq!(app.next_episode_index(), Some(3));
+
+        app.toggle_shuffle();
+        app.played_episode_indices.insert(2);
+        // Each round plays every file once, in an order of its own
+        let mut round = vec![2];
+        for _ in 0..5 {
+            let expected = app.next_episode().unwrap().file_idx;
+            assert_eq!(app.advance_to_next_episode().unwrap().file_idx, expected);
+            round.push(app.current_episode_index);
+        }
+        let mut played = round.clone();
+        played.sort();
+        assert_eq!(played, (0..6).collect::<Vec<_>>());
+
+        // All played, shuffle stops unless it loops into another round
+        assert!(!app.has_next_episode());
+        assert!(app.advance_to_next_episode().is_none());
+        app.loop_shuffle = true;
+        assert!(app.has_next_episode());
+        let last = app.current_episode_index;
+        app.advance_to_next_episode();
+        assert_ne!(app.current_episode_index, last);
+        assert_eq!(app.played_episode_indices.len(), 1);
+
+        app.toggle_shuffle();
+        assert!(!app.shuffle_episodes);
+        assert!(app.played_episode_indices.is_empty());
+    }
+
+    #[test]
+    fn test_select_first_unwatched_episode() {
+        let mut app = browsing("Slow Horses");
+        app.episode_progress.insert((1, 1), 100.0);
+        app.episode_progress.insert((1, 2), 30.0);
+        app.select_first_unwatched_episode();
+        assert_eq!(app.selected_episode_index, 1);
+        assert_eq!(
+            app.episode_history_key(&app.tv_episodes[1]),
+            Some(HistoryKey::Episode {
+                tmdb_id: 1,
+                season: 1,
+                episode: 2
+            })
+        );
+
+        app.episode_progress.insert((1, 2), 95.0);
+        app.episode_progress.insert((1, 3), 90.0);
+        app.select_first_unwatched_episode();
+        assert_eq!(app.selected_episode_index, 0);
+    }
+
+    #[test]
+    fn test_following_tv_episode() {
+        let mut app = browsing("Slow Horses");
+        app.search_context = SearchContext {
+            title: "Slow Horses".to_string(),
+            season: Some(1),
+            episode: Some(2),
+            media_type: Some("tv".to_string()),
+            ..Default::default()
+        };
+        let (index, next) = app.following_tv_episode().unwrap();
+        assert_eq!((index, next.episode_number), (2, 3));
+
+        // Last loaded episode has nothing after it
+        app.search_context.episode = Some(3);
+        assert!(app.following_tv_episode().is_none());
+
+        // Free-text searches aren't tied to the episode browser
+        app.search_context.episode = Some(1);
+        app.search_context.media_type = None;
+        assert!(app.following_tv_episode().is_none());
+    }
+
+    #[test]
+    fn test_select_next_unaired_episode() {
+        let mut app = browsing("Severance");
+        let today = crate::tmdb::parse_date("2025-01-17").unwrap();
+        app.tv_episodes[0].air_date = Some("2025-01-17".to_string());
+        app.tv_episodes[1].air_date = Some("2025-01-24".to_string());
+
+        assert!(app.select_next_unaired_episode(today));
+        assert_eq!(app.selected_episode_index, 1);
+        // Episode 3 has no date yet, so the only upcoming one is found again
+        assert!(app.select_next_unaired_episode(today));
+        assert_eq!(app.selected_episode_index, 1);
+
+        assert!(!app.select_next_unaired_episode(today + 7));
+    }
+
+    #[test]
+    fn test_current_chapter() {
+        let mut app = App::new();
+        app.chapters = serde_json::from_value(serde_json::json!([
+            {"title": "Intro", "time": 0.0},
+            {"time": 600.0},
+        ]))
+        .unwrap();
+        app.media_duration = 1200.0;
+
+        app.playback_progress = 25.0;
+        app.update_current_chapter();
+        assert_eq!(
+            app.current_chapter_label().as_deref(),
+            Some("Chapter 1/2: Intro")
+        );
+
+        app.playback_progress = 75.0;
+        app.update_current_chapter();
+        assert_eq!(
+            app.current_chapter_label().as_deref(),
+            Some("Chapter 2/2: Chapter 2")
+        );
+
+        app.forget_player();
+        assert!(app.current_chapter_label().is_none());
+    }
+
+    fn torrent(title: &str, seeders: u32) -> TorrentResult {
+        TorrentResult {
+            title: title.to_string(),
+            link: None,
+            magnet_url: Some(format!("magnet:?xt=urn:btih:{}", title)),
+            infohash: None,
+            size: None,
+            seeders: Some(seeders),
+            leechers: None,
+            indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: false,
+        }
+    }
+
+    #[test]
+    fn test_marked_results() {
+        let mut app = App::new();
+        app.sort_order = SortOrder::SeedersDesc;
+        app.set_results(
+            vec![(torrent("a", 5), 50), (torrent("b", 50), 50)],
+            vec![(torrent("c", 500), 50)],
+        );
+        app.toggle_marked_result(); // b, sorted first
+        app.select_next();
+        app.toggle_marked_result(); // a
+        app.toggle_hidden_results();
+        app.selected_index = 0;
+        app.toggle_marked_result(); // c
+        assert!(app.is_marked(&torrent("c", 0)));
+        assert_eq!(
+            app.marked_urls(),
+            vec![
+                "magnet:?xt=urn:btih:c",
+                "magnet:?xt=urn:btih:b",
+                "magnet:?xt=urn:btih:a"
+            ]
+        );
+
+        app.toggle_marked_result();
+        assert_eq!(app.marked_urls().len(), 2);
+
+        // A new search starts over
+        app.set_results(vec![(torrent("a", 5), 50)], Vec::new());
+        assert!(app.marked_urls().is_empty());
+    }
+
+    #[test]
+    fn test_release_grouping() {
+        let mut app = App::new();
+        app.sort_order = SortOrder::SeedersDesc;
+        app.set_results(
+            vec![
+                (torrent("Heat.1995.720p.BluRay", 10), 50),
+                (torrent("Heat.1995.1080p.BluRay", 80), 50),
+                (torrent("Heat.1995.1080p.WEB-DL", 40), 50),
+                (torrent("Heat.1995.2160p.BluRay", 30), 50),
+            ],
+            Vec::new(),
+        );
+        let selected = |app: &App| app.selected_result().unwrap().title.clone();
+
+        app.toggle_release_grouping();
+        assert_eq!(app.release_groups.len(), 2);
+        assert_eq!(app.result_rows().len(), 2);
+        assert_eq!(selected(&app), "Heat.1995.1080p.BluRay");
+        app.select_next();
+        assert_eq!(selected(&app), "Heat.1995.1080p.WEB-DL");
+        // A single variant has nothing to expand, Enter streams it
+        assert!(!app.toggle_selected_group());
+
+        app.select_previous();
+        assert!(app.toggle_selected_group());
+        assert_eq!(app.result_rows().len(), 5);
+        app.select_next();
+        app.select_next();
+        assert_eq!(app.selected_variant, Some(1));
+        assert_eq!(selected(&app), "Heat.1995.2160p.BluRay");
+        assert!(!app.toggle_selected_group());
+
+        app.select_next();
+        app.select_next();
+        assert_eq!(selected(&app), "Heat.1995.1080p.WEB-DL");
+        app.select_next();
+        assert_eq!(selected(&app), "Heat.1995.1080p.WEB-DL");
+
+        app.toggle_release_grouping();
+        assert!(app.release_groups.is_empty());
+        assert_eq!(selected(&app), "Heat.1995.1080p.WEB-DL");
+
+        // Resorting regroups, collapsed
+        app.toggle_release_grouping();
+        app.select_previous();
+        assert!(app.toggle_selected_group());
+        app.cycle_sort();
+        assert_eq!(app.expanded_group, None);
+        assert_eq!(app.result_rows().len(), 2);
+    }
+
+    #[test]
+    fn test_media_filter() {
+        let mut app = App::new();
+        app.sort_order = SortOrder::SeedersDesc;
+        let mut show = torrent("Ring.S01E01", 5);
+        show.categories = vec![5040];
+        app.set_results(
+            vec![
+                (torrent("The.Ring.2002", 50), 50),
+                (show, 60),
+                (torrent("Ring.S01E02", 500), 70),
+            ],
+            Vec::new(),
+        );
+
+        app.cycle_media_filter();
+        assert_eq!(app.result_media_filter, MediaFilter::Movie);
+        assert_eq!(app.results.len(), 1);
+        assert_eq!(app.result_scores, vec![50]);
+
+        // Sorting keeps the filter, scores stay with their results
+        app.cycle_media_filter();
+        app.cycle_sort();
+        let titles: Vec<&str> = app.results.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(titles, vec!["Ring.S01E01", "Ring.S01E02"]);
+        assert_eq!(app.result_scores, vec![60, 70]);
+
+        app.cycle_media_filter();
+        assert_eq!(app.results.len(), 3);
+        assert!(app.media_filtered.is_empty());
+    }
+
+    #[test]
+    fn test_download_speed_history() {
+        let mut app = App::new();
+        for speed in 0..(SPEED_HISTORY_LEN as u64 + 5) {
+            app.push_download_speed(speed);
+        }
+        assert_eq!(app.download_speed_history.len(), SPEED_HISTORY_LEN);
+        assert_eq!(app.download_speed_history.front(), Some(&5));
+
+        app.reset_download_progress();
+        assert!(app.download_speed_history.is_empty());
+    }
+
+    #[test]
+    fn test_session_upload() {
+        let mut app = App::new();
+        assert_eq!(app.session_upload(), (0, None));
+
+        app.record_transfer((1, 0), 1000, 200);
+        // Counters only grow, a later update replaces the earlier one
+        app.record_transfer((1, 0), 2000, 500);
+        // Same torrent id in another session is another torrent
+        app.record_transfer((2, 0), 2000, 1500);
+        assert_eq!(app.session_upload(), (2000, Some(0.5)));
+    }
+
+    fn discovery_item(id: u64, title: &str) -> DiscoveryItem {
+        DiscoveryItem {
+            id,
+            title: title.to_string(),
+            year: None,
+            media_type: "movie".to_string(),
+            poster_url: None,
+            backdrop_url: None,
+            overview: None,
+            rating: None,
+            has_4k: false,
+        }
+    }
+
+    #[test]
+    fn test_discovery_filter() {
+        let mut app = App::new();
+        app.set_discovery_rows(vec![
+            DiscoveryRow {
+                title: "Trending".to_string(),
+                items: vec![
+                    discovery_item(1, "Dune: Part Two"),
+                    discovery_item(2, "Alien"),
+                ],
+            },
+            DiscoveryRow {
+                title: "Popular".to_string(),
+                items: vec![
+                    discovery_item(1, "Dune: Part Two"),
+                    discovery_item(3, "Dunkirk"),
+                ],
+            },
+        ]);
+        app.selected_row_index = 1;
+
+        app.discovery_filter = "DUN".to_string();
+        app.apply_discovery_filter();
+        assert_eq!(app.discovery_rows.len(), 2);
+        assert_eq!(app.selected_row_index, 0);
+        assert!(app.single_discovery_match().is_none());
+
+        // Same title in two rows is still one match
+        app.discovery_filter = "dune".to_string();
+        app.apply_discovery_filter();
+        assert_eq!(app.single_discovery_match().map(|i| i.id), Some(1));
+
+        // Rows with nothing left are hidden, and reloads stay filtered
+        app.discovery_filter = "alien".to_string();
+        app.set_discovery_rows(app.discovery_unfiltered.clone());
+        assert_eq!(app.discovery_rows.len(), 1);
+        assert_eq!(app.discovery_rows[0].title, "Trending");
+
+        app.clear_discovery_filter();
+        assert_eq!(app.discovery_rows[1].items.len(), 2);
+    }
+
+    #[test]
+    fn test_discovery_refresh_keeps_selection() {
+        let mut app = App::new();
+        let row = |title: &str, items: Vec<DiscoveryItem>| DiscoveryRow {
+            title: title.to_string(),
+            items,
+        };
+        app.set_discovery_rows(vec![
+            row("Trending", vec![discovery_item(1, "Dune")]),
+            row(
+                "Popular",
+                vec![
+                    discovery_item(2, "Alien"),
+                    discovery_item(3, "Heat"),
+                    discovery_item(4, "Ran"),
+                ],
+            ),
+        ]);
+        app.selected_row_index = 1;
+        app.selected_item_index = 1;
+
+        // Heat moved, and a row showed up above
+        app.set_discovery_rows(vec![
+            row("New", vec![discovery_item(9, "Up")]),
+            row("Trending", vec![discovery_item(1, "Dune")]),
+            row(
+                "Popular",
+                vec![
+                    discovery_item(5, "Jaws"),
+                    discovery_item(2, "Alien"),
+                    discovery_item(3, "Heat"),
+                ],
+            ),
+        ]);
+        assert_eq!(app.selected_row_index, 2);
+        assert_eq!(app.selected_discovery_item().map(|i| i.id), Some(3));
+
+        // Heat dropped out, stay at the same spot in the row
+        app.set_discovery_rows(vec![row(
+            "Popular",
+            vec![discovery_item(5, "Jaws"), discovery_item(2, "Alien")],
+        )]);
+        assert_eq!((app.selected_row_index, app.selected_item_index), (0, 1));
+    }
+
+    #[test]
+    fn test_result_enrichment() {
+        let mut app = App::new();
+        app.view = View::Results;
+        app.set_results(
+            vec![
+                (torrent("Heat.1995.1080p", 50), 90),
+                (torrent("Ran.1985", 10), 40),
+            ],
+            Vec::new(),
+        );
+        let start = Instant::now();
+
+        // Only after the highlight settles, and only once
+        assert_eq!(app.result_to_enrich(start), None);
+        assert_eq!(app.result_to_enrich(start + ENRICH_DEBOUNCE / 2), None);
+        assert_eq!(
+            app.result_to_enrich(start + ENRICH_DEBOUNCE).as_deref(),
+            Some("Heat.1995.1080p")
+        );
+        assert_eq!(app.result_to_enrich(start + ENRICH_DEBOUNCE * 2), None);
+
+        let heat = TmdbMetadata {
+            id: Some(949),
+            title: "Heat".to_string(),
+            ..Default::default()
+        };
+        app.finish_enrichment("Heat.1995.1080p".to_string(), Ok(Some(heat)));
+        app.result_to_enrich(start + ENRICH_DEBOUNCE * 2);
+        assert_eq!(app.tmdb_info.as_ref().and_then(|t| t.id), Some(949));
+
+        // Scrolling on drops the guess, scrolling back brings it from the cache
+        app.select_next();
+        app.result_to_enrich(start + ENRICH_DEBOUNCE * 3);
+        assert!(app.tmdb_info.is_none());
+        app.select_previous();
+        app.result_to_enrich(start + ENRICH_DEBOUNCE * 3);
+        assert!(app.tmdb_info_guessed);
+
+        // A failed lookup is tried again, not remembered as no match
+        app.select_next();
+        assert_eq!(
+            app.result_to_enrich(start + ENRICH_DEBOUNCE * 4).as_deref(),
+            Some("Ran.1985")
+        );
+        app.finish_enrichment("Ran.1985".to_string(), Err(TmdbError::NotFound));
+        assert!(!app.enrichment_cache.contains_key("Ran.1985"));
+        assert_eq!(
+            app.result_to_enrich(start + ENRICH_DEBOUNCE * 5).as_deref(),
+            Some("Ran.1985")
+        );
+        app.select_previous();
+        app.result_to_enrich(start + ENRICH_DEBOUNCE * 5);
+
+        // A match from the search itself is left alone
+        app.tmdb_info_guessed = false;
+        app.select_next();
+        assert_eq!(app.result_to_enrich(start + ENRICH_DEBOUNCE * 9), None);
+        assert_eq!(app.tmdb_info.as_ref().and_then(|t| t.id), Some(949));
+    }
+
+    #[test]
+    fn test_discovery_refresh_due() {
+        let mut app = App::new();
+        let interval = Some(Duration::ZERO);
+        assert!(!app.discovery_refresh_due(interval));
+
+        app.last_discovery_load = Some(Instant::now());
+        assert!(app.discovery_refresh_due(interval));
+        assert!(!app.discovery_refresh_due(None));
+        assert!(!app.discovery_refresh_due(Some(Duration::from_secs(1800))));
+
+        app.discovery_refresh_paused = true;
+        assert!(!app.discovery_refresh_due(interval));
+        app.discovery_refresh_paused = false;
+        app.view = View::Search;
+        assert!(!app.discovery_refresh_due(interval));
+    }
 }
@@ -0,0 +1,144 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use super::UiMessage;
+use crate::config::{Config, ConfigLocation};
+
+/// Quiet time after the last change before the file is read, editors often
+/// write a file in several steps
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the config file for edits made outside ferristream and sends the
+/// reparsed config as a `ConfigReloaded`, unvalidated since our own saves
+/// (say halfway through the wizard) come back this way too. The watch stops
+/// when this is dropped.
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    pub fn start(location: ConfigLocation, tx: mpsc::Sender<UiMessage>) -> notify::Result<Self> {
+        let (changes_tx, mut changes_rx) = mpsc::unbounded_channel();
+        let path = location.path.clone();
+        let file = path.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if event.is_ok_and(|event| touches(&event, &file)) {
+                let _ = changes_tx.send(());
+            }
+        })?;
+        // Editors save by renaming a new file over the old one, which a watch
+        // on the file itself doesn't survive
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        tokio::spawn(async move {
+            while changes_rx.recv().await.is_some() {
+                loop {
+                    match tokio::time::timeout(DEBOUNCE, changes_rx.recv()).await {
+                        Ok(Some(())) => continue,
+                        Ok(None) => return,
+                        Err(_) => break,
+                    }
+                }
+                let message = match Config::parse_file(&location) {
+                    Ok(config) => {
+                        info!(path = %path.display(), "config changed on disk");
+                        UiMessage::ConfigReloaded(Box::new(config))
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "config changed on disk but can't be parsed");
+                        UiMessage::ConfigReloadFailed(e.to_string())
+                    }
+                };
+                if tx.send(message).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+/// Whether a change in the config's directory is to the config file
+fn touches(event: &Event, file: &Path) -> bool {
+    !matches!(event.kind, EventKind::Access(_))
+        && event
+            .paths
+            .iter()
+            .any(|path| path.file_name() == file.file_name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, ModifyKind, RenameMode};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_touches() {
+        let file = Path::new("/home/me/.config/ferristream/config.toml");
+        let event = |kind, path: &str| Event::new(kind).add_path(PathBuf::from(path));
+
+        assert!(touches(
+            &event(
+                EventKind::Modify(ModifyKind::Any),
+                "/home/me/.config/ferristream/config.toml"
+            ),
+            file
+        ));
+        // Saved by renaming a temp file over it
+        let rename = event(
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            "/home/me/.config/ferristream/.config.toml.swp",
+        )
+        .add_path(PathBuf::from("/home/me/.config/ferristream/config.toml"));
+        assert!(touches(&rename, file));
+
+        // Other files in the directory and reads don't count
+        assert!(!touches(
+            &event(
+                EventKind::Create(CreateKind::File),
+                "/home/me/.config/ferristream/history.json"
+            ),
+            file
+        ));
+        assert!(!touches(
+            &event(
+                EventKind::Access(AccessKind::Any),
+                "/home/me/.config/ferristream/config.toml"
+            ),
+            file
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reloads_after_edit() {
+        let dir = std::env::temp_dir().join(format!("ferristream-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, toml::to_string(&Config::default()).unwrap()).unwrap();
+
+        let (tx, mut rx) = mpsc::channel(8);
+        let _watcher = ConfigWatcher::start(ConfigLocation::file(path.clone()), tx).unwrap();
+
+        let mut config = Config::default();
+        config.player.command = "vlc".to_string();
+        std::fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+
+        let message = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await;
+        std::fs::remove_dir_all(&dir).ok();
+        match message {
+            Ok(Some(UiMessage::ConfigReloaded(reloaded))) => {
+                assert_eq!(reloaded.player.command, "vlc")
+            }
+            _ => panic!("no reload"),
+        }
+    }
+}
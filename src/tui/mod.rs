@@ -1,66 +1,228 @@
 mod app;
+mod config_watch;
+mod playback;
+mod progress;
+mod settings;
+mod thumbnail;
 mod ui;
 
 pub use app::{
-    App, DiscoveryItem, DiscoveryRow, DownloadProgress, SettingsSection, SortOrder, StreamingState,
-    TmdbMetadata, TmdbSuggestion, View, WizardStep,
+    App, Artwork, BackdropFade, DiscoveryItem, DiscoveryRow, DownloadProgress, PeerRow,
+    PrefetchedEpisode, ProbePrompt, SettingsSection, StreamingState, SubtitleDecision,
+    SubtitlePrompt, SubtitleStatus, TmdbMetadata, TmdbPrompt, TmdbSuggestion, ToastLevel, View,
+    WizardStep,
 };
 
+use std::collections::HashSet;
 use std::io;
-use std::time::Duration;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    clipboard::CopyToClipboard,
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyModifiers, MouseButton, MouseEventKind,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
+use notify_rust::Urgency;
 use ratatui::{Terminal, backend::CrosstermBackend};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::config::Config;
+use crate::anidb::AnimeClient;
+use crate::audio_preferences::{self, AudioChoice, AudioPreferences};
+use crate::cast::{self, CastDevice, StreamRelay};
+use crate::config::{Config, ConfigLocation};
+use crate::diagnostics;
 use crate::doctor::{self, CheckResult};
 use crate::extensions::{ExtensionManager, MediaInfo, PlaybackEvent, parse_episode_info};
-use crate::history::WatchHistory;
-use crate::opensubtitles::OpenSubtitlesClient;
-use crate::prowlarr::ProwlarrClient;
-use crate::streaming::{self, StreamingSession, TorrentValidation, VideoFile, sort_episodes};
-use crate::tmdb::{TmdbClient, parse_torrent_title};
-use crate::torznab::{TorrentResult, TorznabClient};
+use crate::geoip::{self, GeoIp};
+use crate::history::{FINISHED_PERCENT, WatchHistory};
+use crate::hooks::{self, PostPlay};
+use crate::indexer_health::{self, IndexerHealth, LastSearch, SearchOutcome, SharedIndexerHealth};
+use crate::mpv::{self, AudioTrack, Chapter};
+use crate::notifications;
+use crate::opensubtitles::{
+    self, OpenSubtitlesClient, OpenSubtitlesError, OpenSubtitlesXmlRpcClient, SubtitleQuery,
+};
+use crate::recovery::{SessionRecovery, SessionSnapshot};
+use crate::rss::{self, RssWatcher};
+use crate::search_service::{IndexerList, SearchService, SharedSearchService};
+use crate::streaming::{
+    self, AddPhase, Cleanup, DetectedPlayer, MediaMode, PendingCleanup, PieceMap, Probe,
+    ResultScorer, SearchContext, SeedPolicy, StreamError, StreamingSession, SubtitleFile,
+    TorrentInfo, TorrentStats, TorrentValidation, VideoFile, sort_episodes,
+};
+use crate::tmdb::{
+    self, CollectionSummary, PersonRole, TmdbClient, TmdbError, parse_person_query,
+    parse_torrent_title,
+};
+use crate::torznab::{self, FilterReason, SearchRequest, TorrentResult};
+use crate::usenet::{UsenetClient, UsenetError};
+use config_watch::ConfigWatcher;
+use playback::{MediaContext, PlaybackController, Subtitle, SubtitleSource};
+use progress::{DownloadUpdate, ProgressSender};
+use thumbnail::Thumbnail;
 
 /// Messages sent from background tasks to the UI
 pub enum UiMessage {
     SearchComplete {
         results: Vec<TorrentResult>,
         search_id: u64,
+        /// What was sent to the indexers
+        query: String,
+        context: SearchContext,
     },
     SearchError(String),
     TmdbInfo(TmdbMetadata),
+    /// TMDB match guessed from a result's title, None when nothing matched
+    /// and an error when TMDB couldn't be asked
+    ResultEnriched {
+        title: String,
+        info: Result<Option<TmdbMetadata>, TmdbError>,
+    },
+    /// Several well-known TMDB matches share the searched title
+    TmdbAmbiguous {
+        search_id: u64,
+        query: String,
+        candidates: Vec<TmdbMetadata>,
+    },
     Suggestions(Vec<TmdbSuggestion>),
     /// TV show details with seasons
     TvDetailsLoaded(crate::tmdb::TvDetails),
     /// Season episodes loaded
     SeasonEpisodesLoaded(Vec<crate::tmdb::Episode>),
+    /// Anime episodes with absolute numbering loaded (AniDB/MAL)
+    AnimeEpisodesLoaded(Vec<crate::tmdb::Episode>),
+    /// Anime episode lookup failed - fall back to TMDB seasons
+    AnimeEpisodesError(String),
+    /// Still of an episode in the browser, None if TMDB has none
+    EpisodeStillLoaded {
+        episode_id: u64,
+        thumbnail: Option<Thumbnail>,
+    },
+    /// Backdrop of a Discovery title, None if it couldn't be loaded
+    BackdropLoaded {
+        url: String,
+        thumbnail: Option<Thumbnail>,
+    },
     /// Torrent metadata received - may have multiple video files
     TorrentMetadata {
         torrent_info: crate::streaming::TorrentInfo,
         session: std::sync::Arc<StreamingSession>,
+        stream_id: u64,
     },
-    /// Racing torrents - show status
-    RacingStatus {
-        count: usize,
-        message: String,
+    /// Where adding the stream's torrent (or the best of the raced ones) is at
+    AddProgress {
+        phase: AddPhase,
+        stream_id: u64,
     },
     StreamReady {
         file_name: String,
         stream_url: String,
+        stream_id: u64,
+    },
+    StreamError {
+        error: String,
+        stream_id: u64,
+    },
+    /// The torrent HTTP API stopped answering mid-playback
+    StreamBackendLost {
+        stream_id: u64,
+    },
+    /// A lost stream came back on a fresh session
+    StreamRestarted {
+        torrent_info: crate::streaming::TorrentInfo,
+        session: std::sync::Arc<StreamingSession>,
+    },
+    /// Latest download stats, only ever sent through a [`ProgressSender`]
+    ProgressUpdate {
+        progress: DownloadProgress,
+        stream_id: u64,
+        /// Streaming session and torrent the byte counters are for
+        torrent: (u64, usize),
     },
-    StreamError(String),
-    ProgressUpdate(DownloadProgress),
-    /// Playback position update from mpv (percent watched)
+    /// Playback position update from mpv (percent watched), only ever sent
+    /// through a [`ProgressSender`]
     PlaybackProgress(f64),
+    /// mpv was paused (true) or resumed
+    PlaybackPaused(bool),
+    /// mpv has loaded the file's chapters, with its duration in seconds
+    ChaptersLoaded {
+        chapters: Vec<Chapter>,
+        duration: f64,
+    },
+    /// mpv jumped to this chapter
+    ChapterChanged(usize),
+    /// mpv has loaded the file's audio tracks
+    AudioTracksLoaded {
+        socket: crate::streaming::IpcEndpoint,
+        tracks: Vec<AudioTrack>,
+        stream_id: u64,
+    },
+    /// Subtitle the player is about to start with, None for none
+    SubtitleChosen {
+        subtitle: Option<Subtitle>,
+        stream_id: u64,
+    },
+    /// Downloaded pieces of the file streaming, None if they couldn't be read
+    PieceMapLoaded {
+        map: Option<PieceMap>,
+        stream_id: u64,
+    },
+    /// Waiting for the pre-roll buffer before launching the player
+    Buffering {
+        percent: f64,
+        stream_id: u64,
+    },
+    /// Nothing found in the preferred subtitle languages, the launch waits for `reply`
+    SubtitlesMissing {
+        languages: Vec<String>,
+        has_fallback: bool,
+        stream_id: u64,
+        reply: oneshot::Sender<SubtitleDecision>,
+    },
+    /// Peers of a picked result were counted, streaming it waits for `reply`
+    TorrentProbed {
+        probe: Probe,
+        stream_id: u64,
+        reply: oneshot::Sender<bool>,
+    },
+    /// Pre-roll done, the player is being launched on this file
+    PlayerLaunching {
+        file_name: String,
+        total_bytes: u64,
+        stream_id: u64,
+    },
+    /// The player started, playback is underway. Cancelling `cancel` closes
+    /// just the player, the stream stays up.
+    PlayerStarted {
+        stream_id: u64,
+        cancel: CancellationToken,
+    },
     PlayerExited,
+    /// A player opened next to the main one was closed
+    ExtraPlayerExited(String),
+    /// Next episode found and connected while the current one plays
+    NextEpisodePrefetched(PrefetchedEpisode),
+    /// Background search for the next episode came up empty
+    PrefetchFailed(String),
+    /// A torrent seeding in the background hit its target and was removed
+    SeedingFinished {
+        seed_id: u64,
+        name: String,
+    },
+    /// Byte counters of a torrent seeding in the background
+    SeedingStats {
+        torrent: (u64, usize),
+        stats: TorrentStats,
+    },
     DoctorComplete(Vec<CheckResult>),
     /// Discovery data loaded
     DiscoveryLoaded {
@@ -68,11 +230,79 @@ pub enum UiMessage {
     },
     /// Discovery loading failed
     DiscoveryError(String),
+    /// Cast devices answered on the local network
+    CastDevicesFound(Vec<CastDevice>),
+    /// A device is playing the stream, through the relay
+    CastStarted {
+        name: String,
+        stream_id: u64,
+        relay: StreamRelay,
+    },
+    CastFailed(String),
+    /// The stream is shared on the local network, through the relay
+    StreamShared {
+        stream_id: u64,
+        relay: StreamRelay,
+    },
+    /// Filmography of an actor/director loaded
+    PersonCreditsLoaded {
+        name: String,
+        items: Vec<DiscoveryItem>,
+    },
+    /// Person lookup failed
+    PersonError(String),
+    /// The movie being searched for is part of a collection
+    CollectionFound {
+        search_id: u64,
+        collection: CollectionSummary,
+    },
+    /// Movies of a collection loaded
+    CollectionLoaded {
+        name: String,
+        items: Vec<DiscoveryItem>,
+    },
+    /// Collection lookup failed
+    CollectionError(String),
+    /// A new release matched one of the RSS rules
+    RssMatch {
+        rule_name: String,
+        result: TorrentResult,
+        auto_stream: bool,
+    },
+    /// Peers of the streamed torrent fetched
+    PeersLoaded(Vec<PeerRow>),
+    PeersError(String),
+    /// Show a toast for something that happened in the background
+    Notify {
+        message: String,
+        level: ToastLevel,
+    },
+    /// The config file was edited outside ferristream
+    ConfigReloaded(Box<Config>),
+    /// The edited config file doesn't load, the running config stays
+    ConfigReloadFailed(String),
+    /// Temp dir measured for the Storage settings
+    CacheSize(u64),
+    /// Sessions no longer in use removed from the temp dir
+    CacheCleared {
+        removed: usize,
+        freed: u64,
+        size: u64,
+    },
+    /// Space left on the temp dir's filesystem, None if it can't be read
+    FreeSpace(Option<u64>),
+    /// Installed players looked up for the wizard and settings
+    PlayersDetected(Vec<DetectedPlayer>),
 }
 
 fn restore_terminal() {
     let _ = disable_raw_mode();
-    let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture);
+    let _ = execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    );
 }
 
 // Discovery row item count constants
@@ -81,6 +311,31 @@ const POPULAR_MOVIES_ITEM_COUNT: usize = 10;
 const POPULAR_TV_ITEM_COUNT: usize = 10;
 const UPCOMING_ROW_ITEM_COUNT: usize = 20;
 const FOR_YOU_ROW_ITEM_COUNT: usize = 20;
+const UHD_ROW_ITEM_COUNT: usize = 20;
+
+/// Spawn a background task to pre-download a file, with a toast when it's queued
+fn spawn_prioritize_file(
+    session: std::sync::Arc<StreamingSession>,
+    torrent_id: usize,
+    file: &VideoFile,
+    tx: mpsc::Sender<UiMessage>,
+) {
+    let file_idx = file.file_idx;
+    let name = file.name.clone();
+    tokio::spawn(async move {
+        let notice = match session.prioritize_file(torrent_id, file_idx).await {
+            Ok(()) => UiMessage::Notify {
+                message: format!("Pre-downloading {}", name),
+                level: ToastLevel::Info,
+            },
+            Err(e) => UiMessage::Notify {
+                message: format!("Pre-download failed: {}", e),
+                level: ToastLevel::Warning,
+            },
+        };
+        let _ = tx.send(notice).await;
+    });
+}
 
 /// Helper function to add a discovery row from TMDB API results
 fn add_row_from_results(
@@ -109,6 +364,19 @@ fn add_row_from_results(
     }
 }
 
+/// Badge every movie in the `uhd_row` as 4K, wherever it shows up
+fn mark_4k(rows: &mut [DiscoveryRow], uhd_row: &str) {
+    let uhd_ids: HashSet<u64> = rows
+        .iter()
+        .filter(|row| row.title == uhd_row)
+        .flat_map(|row| row.items.iter().map(|item| item.id))
+        .collect();
+    rows.iter_mut()
+        .flat_map(|row| row.items.iter_mut())
+        .filter(|item| item.media_type == "movie" && uhd_ids.contains(&item.id))
+        .for_each(|item| item.has_4k = true);
+}
+
 fn load_discovery_data(tx: &mpsc::Sender<UiMessage>, config: &Config) {
     let tx = tx.clone();
     let tmdb_apikey = config.tmdb.as_ref().map(|t| t.apikey.clone());
@@ -126,12 +394,13 @@ fn load_discovery_data(tx: &mpsc::Sender<UiMessage>, config: &Config) {
         let mut rows = Vec::new();
 
         // Fetch all data in parallel for better performance
-        let (trending_res, popular_movies_res, popular_tv_res, upcoming_res, discover_res) = tokio::join!(
+        let (trending_res, popular_movies_res, popular_tv_res, upcoming_res, discover_res, uhd_res) = tokio::join!(
             client.get_trending("all", "week"),
             client.get_popular_movies(),
             client.get_popular_tv(),
             client.get_upcoming(),
-            client.discover_mixed()
+            client.discover_mixed(),
+            client.discover_4k_movies()
         );
 
         // Row 1: Trending
@@ -195,6 +464,16 @@ fn load_discovery_data(tx: &mpsc::Sender<UiMessage>, config: &Config) {
             "failed to load recommendations",
         );
 
+        // Row 5: 4K & HDR
+        add_row_from_results(
+            &mut rows,
+            "4K & HDR",
+            uhd_res,
+            UHD_ROW_ITEM_COUNT,
+            "failed to load 4K movies",
+        );
+        mark_4k(&mut rows, "4K & HDR");
+
         if rows.is_empty() {
             let _ = tx
                 .send(UiMessage::DiscoveryError(
@@ -207,111 +486,379 @@ fn load_discovery_data(tx: &mpsc::Sender<UiMessage>, config: &Config) {
     });
 }
 
+/// Run the configured query transforms, remembering the typed query for the
+/// status line when they change it
+fn transform_search_query(app: &mut App, config: &Config, query: String) -> String {
+    let transformed = config.streaming.transform_query(&query);
+    if transformed == query {
+        app.transformed_query = None;
+        return query;
+    }
+    info!(query = %transformed, from = %query, "search query transformed");
+    app.transformed_query = Some((transformed.clone(), query));
+    transformed
+}
+
 /// Spawn a background task to search for torrents across all indexers
 fn spawn_torrent_search(
     search_query: String,
+    context: SearchContext,
     search_id: u64,
+    mode: MediaMode,
     tx: mpsc::Sender<UiMessage>,
-    prowlarr_url: String,
-    prowlarr_apikey: String,
+    search_service: SharedSearchService,
+    indexer_health: SharedIndexerHealth,
+) {
+    tokio::spawn(async move {
+        let request = SearchRequest::new(search_query.clone(), context.clone());
+        match search_indexers(&request, mode, &search_service, &indexer_health, &tx).await {
+            Ok(results) => {
+                let _ = tx
+                    .send(UiMessage::SearchComplete {
+                        results,
+                        search_id,
+                        query: search_query,
+                        context,
+                    })
+                    .await;
+            }
+            Err(e) => {
+                let _ = tx.send(UiMessage::SearchError(e)).await;
+            }
+        }
+    });
+}
+
+/// Search for a movie, by its IMDB id on indexers that take one. The id is
+/// looked up on TMDB first, without it the search is by keyword.
+fn spawn_movie_search(
+    app: &App,
+    search_query: String,
+    mut context: SearchContext,
+    tx: &mpsc::Sender<UiMessage>,
+    config: &Config,
 ) {
+    let tmdb_apikey = config.tmdb.as_ref().map(|t| t.apikey.clone());
+    let search_id = app.search_id;
+    let mode = app.media_mode;
+    let tx = tx.clone();
+    let search_service = app.search_service.clone();
+    let indexer_health = app.indexer_health.clone();
+    tokio::spawn(async move {
+        if let (Some(client), Some(tmdb_id)) =
+            (TmdbClient::new(tmdb_apikey.as_deref()), context.tmdb_id)
+        {
+            match client.get_external_ids(tmdb_id).await {
+                Ok(ids) => context.imdb_id = ids.imdb_id().map(String::from),
+                Err(e) => debug!(tmdb_id, error = %e, "external ids lookup failed"),
+            }
+        }
+        spawn_torrent_search(
+            search_query,
+            context,
+            search_id,
+            mode,
+            tx,
+            search_service,
+            indexer_health,
+        );
+    });
+}
+
+/// Search every usable indexer, erroring only if nothing was found at all.
+/// Demoted indexers are skipped and every outcome is recorded in `indexer_health`.
+/// If Prowlarr can't list its indexers the last known ones are searched, with a warning.
+async fn search_indexers(
+    request: &SearchRequest,
+    mode: MediaMode,
+    search_service: &SearchService,
+    indexer_health: &SharedIndexerHealth,
+    tx: &mpsc::Sender<UiMessage>,
+) -> Result<Vec<TorrentResult>, String> {
     use futures::stream::{self, StreamExt};
 
-    const VIDEO_CATEGORIES: &[u32] = &[2000, 5000];
     const MAX_CONCURRENT_SEARCHES: usize = 5; // Limit concurrent searches to avoid overwhelming the system
 
-    tokio::spawn(async move {
-        let prowlarr_config = crate::config::ProwlarrConfig {
-            url: prowlarr_url,
-            apikey: prowlarr_apikey,
-        };
-        let prowlarr = ProwlarrClient::new(&prowlarr_config);
-        let torznab = TorznabClient::new();
-
-        match prowlarr.get_usable_indexers().await {
-            Ok(indexers) => {
-                if indexers.is_empty() {
-                    let _ = tx
-                        .send(UiMessage::SearchError(
-                            "No indexers configured in Prowlarr".to_string(),
-                        ))
-                        .await;
-                    return;
-                }
+    let IndexerList {
+        indexers,
+        stale_reason,
+    } = search_service
+        .usable_indexers()
+        .await
+        .map_err(|e| format!("Prowlarr error: {}", e))?;
+    if let Some(e) = stale_reason {
+        let _ = tx
+            .send(UiMessage::Notify {
+                message: format!("Prowlarr error: {} - using last known indexers", e),
+                level: ToastLevel::Warning,
+            })
+            .await;
+    }
+    if indexers.is_empty() {
+        return Err("No indexers configured in Prowlarr".to_string());
+    }
 
-                // Run searches in parallel with limited concurrency
-                let search_futures = indexers.into_iter().map(|indexer| {
-                    let prowlarr_url = prowlarr_config.url.clone();
-                    let prowlarr_apikey = prowlarr_config.apikey.clone();
-                    let search_query = search_query.clone();
-                    let indexer_name = indexer.name.clone();
-                    let indexer_id = indexer.id;
-
-                    async move {
-                        let torznab = TorznabClient::new();
-                        let result = torznab
-                            .search(
-                                &prowlarr_url,
-                                &prowlarr_apikey,
-                                indexer_id,
-                                &indexer_name,
-                                &search_query,
-                                Some(VIDEO_CATEGORIES),
-                            )
-                            .await;
+    let (indexers, skipped) = indexer_health.lock().unwrap().partition(
+        indexers,
+        |indexer| indexer.name.as_str(),
+        indexer_health::now_secs(),
+    );
+    let mut last_search = LastSearch {
+        query: request.query.clone(),
+        indexers: Vec::new(),
+    };
+    for indexer in &skipped {
+        debug!(indexer = indexer.name, "skipping demoted indexer");
+        last_search
+            .indexers
+            .push((indexer.name.clone(), SearchOutcome::Skipped, Duration::ZERO));
+    }
 
-                        (indexer_name, result)
+    // Run searches in parallel with limited concurrency
+    let search_futures = indexers.into_iter().map(|indexer| {
+        let prowlarr_url = search_service.prowlarr_url().to_string();
+        let prowlarr_apikey = search_service.prowlarr_apikey().to_string();
+        let torznab = search_service.torznab().clone();
+        let indexer_name = indexer.name.clone();
+        let indexer_id = indexer.id;
+        let usenet = indexer.is_usenet();
+
+        async move {
+            let started = Instant::now();
+            let result = torznab
+                .search(
+                    &prowlarr_url,
+                    &prowlarr_apikey,
+                    indexer_id,
+                    &indexer_name,
+                    request,
+                    Some(mode.torznab_categories()),
+                )
+                .await
+                .map(|mut results| {
+                    for result in &mut results {
+                        result.usenet = usenet;
                     }
+                    results
                 });
 
-                // Collect results as they complete
-                let mut all_results = Vec::new();
-                let mut last_error: Option<String> = None;
-                let mut results_stream =
-                    stream::iter(search_futures).buffer_unordered(MAX_CONCURRENT_SEARCHES);
-
-                while let Some((indexer_name, result)) = results_stream.next().await {
-                    match result {
-                        Ok(results) => {
-                            debug!(
-                                indexer = indexer_name,
-                                count = results.len(),
-                                "indexer search completed"
-                            );
-                            all_results.extend(results);
-                        }
-                        Err(e) => {
-                            error!(
-                                indexer = indexer_name,
-                                error = %e,
-                                "indexer search failed"
-                            );
-                            last_error = Some(format!("{}: {}", indexer_name, e));
-                        }
-                    }
-                }
+            (indexer_name, started.elapsed(), result)
+        }
+    });
 
-                if all_results.is_empty() {
-                    let error_msg = last_error.unwrap_or_else(|| "No results found".to_string());
-                    let _ = tx.send(UiMessage::SearchError(error_msg)).await;
-                } else {
-                    let _ = tx
-                        .send(UiMessage::SearchComplete {
-                            results: all_results,
-                            search_id,
-                        })
-                        .await;
-                }
+    // Collect results as they complete
+    let mut all_results = Vec::new();
+    let mut last_error: Option<String> = None;
+    let mut results_stream = stream::iter(search_futures).buffer_unordered(MAX_CONCURRENT_SEARCHES);
+
+    while let Some((indexer_name, latency, result)) = results_stream.next().await {
+        match result {
+            Ok(results) => {
+                debug!(
+                    indexer = indexer_name,
+                    count = results.len(),
+                    "indexer search completed"
+                );
+                indexer_health.lock().unwrap().record_success(
+                    &indexer_name,
+                    latency,
+                    results.len(),
+                );
+                last_search.indexers.push((
+                    indexer_name,
+                    SearchOutcome::Results(results.len()),
+                    latency,
+                ));
+                all_results.extend(results);
             }
             Err(e) => {
-                let _ = tx
-                    .send(UiMessage::SearchError(format!("Prowlarr error: {}", e)))
-                    .await;
+                error!(
+                    indexer = indexer_name,
+                    error = %e,
+                    "indexer search failed"
+                );
+                let demoted = indexer_health.lock().unwrap().record_failure(
+                    &indexer_name,
+                    latency,
+                    indexer_health::now_secs(),
+                );
+                if demoted {
+                    warn!(
+                        indexer = indexer_name,
+                        "indexer demoted after repeated failures"
+                    );
+                }
+                last_error = Some(format!("{}: {}", indexer_name, e));
+                last_search.indexers.push((
+                    indexer_name,
+                    SearchOutcome::Failed(e.to_string()),
+                    latency,
+                ));
             }
         }
+    }
+    {
+        let mut health = indexer_health.lock().unwrap();
+        health.set_last_search(last_search);
+        health.save();
+    }
+
+    if all_results.is_empty() {
+        return Err(last_error.unwrap_or_else(|| "No results found".to_string()));
+    }
+    Ok(torznab::dedup_results(all_results))
+}
+
+/// Load the watched percent of the listed episodes from history
+fn refresh_episode_progress(app: &mut App, watch_history: &WatchHistory) {
+    app.episode_progress = app
+        .tv_episodes
+        .iter()
+        .filter_map(|ep| {
+            let entry = watch_history.get(&app.episode_history_key(ep)?)?;
+            Some((
+                (ep.season_number, ep.episode_number),
+                entry.progress_percent,
+            ))
+        })
+        .collect();
+}
+
+/// Fetch the still of the selected episode for the side panel, once per episode
+fn spawn_episode_still_fetch(app: &mut App, tx: &mpsc::Sender<UiMessage>, config: &Config) {
+    let wide =
+        crossterm::terminal::size().is_ok_and(|(width, _)| width > ui::EPISODE_STILL_MIN_WIDTH);
+    // Absolute anime numbering doesn't map onto TMDB seasons
+    if !wide || app.absolute_numbering {
+        return;
+    }
+    let (Some(tv_id), Some(episode)) = (
+        app.tv_details.as_ref().map(|d| d.id),
+        app.selected_tv_episode(),
+    ) else {
+        return;
+    };
+    if app.episode_stills.contains_key(&episode.id) {
+        return;
+    }
+    let episode_id = episode.id;
+    let season_number = episode.season_number;
+    let episode_number = episode.episode_number;
+    app.episode_stills.insert(episode_id, Artwork::Loading);
+
+    let tx = tx.clone();
+    let tmdb_apikey = config.tmdb.as_ref().map(|t| t.apikey.clone());
+    tokio::spawn(async move {
+        let thumbnail = match TmdbClient::new(tmdb_apikey.as_deref()) {
+            Some(client) => {
+                fetch_episode_thumbnail(&client, tv_id, season_number, episode_number).await
+            }
+            None => None,
+        };
+        let _ = tx
+            .send(UiMessage::EpisodeStillLoaded {
+                episode_id,
+                thumbnail,
+            })
+            .await;
     });
 }
 
+/// Download and decode the best still of an episode
+async fn fetch_episode_thumbnail(
+    client: &TmdbClient,
+    tv_id: u64,
+    season_number: u32,
+    episode_number: u32,
+) -> Option<Thumbnail> {
+    let url = client
+        .get_episode_still(tv_id, season_number, episode_number)
+        .await?;
+    fetch_thumbnail(&url, |thumbnail| thumbnail).await
+}
+
+/// Download and decode an image, then run `prepare` on it off the UI thread
+async fn fetch_thumbnail(url: &str, prepare: fn(Thumbnail) -> Thumbnail) -> Option<Thumbnail> {
+    let response = crate::http::client().get(url).send().await.ok()?;
+    let bytes = response.error_for_status().ok()?.bytes().await.ok()?;
+    tokio::task::spawn_blocking(move || Thumbnail::decode(&bytes).map(prepare))
+        .await
+        .ok()?
+}
+
+/// How long a title has to stay focused before its backdrop is fetched, so
+/// scrolling through a row doesn't download every one
+const BACKDROP_DELAY: Duration = Duration::from_millis(300);
+
+/// Half blocks need a wide terminal to look like anything, and true color
+/// to not look like noise
+/// Compact mode is on when the config asks for it or the terminal is too
+/// narrow for the full help lines
+fn compact_mode(config: &Config, width: u16) -> bool {
+    config.display.compact_mode || width < ui::COMPACT_MODE_WIDTH
+}
+
+fn supports_backdrops() -> bool {
+    let wide = crossterm::terminal::size().is_ok_and(|(width, _)| width > ui::BACKDROP_MIN_WIDTH);
+    let true_color =
+        std::env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit");
+    wide && true_color
+}
+
+/// Keep the Discovery backdrop on the focused title: fetch it once the
+/// focus has rested, then fade it in over the last one. Titles without a
+/// backdrop leave the last one up.
+fn update_backdrop(app: &mut App, tx: &mpsc::Sender<UiMessage>, config: &Config) {
+    if !config.search.show_backdrops {
+        app.backdrop = None;
+        return;
+    }
+    if app.view != View::Discovery {
+        return;
+    }
+    let Some(url) = app
+        .selected_discovery_item()
+        .and_then(|item| item.backdrop_url.clone())
+    else {
+        return;
+    };
+    if app.backdrop.as_ref().is_some_and(|b| b.url == url) {
+        return;
+    }
+
+    match app.backdrops.get(&url) {
+        Some(Artwork::Loaded(image)) => {
+            let image = image.clone();
+            let previous = app.backdrop.take().map(|b| b.image);
+            app.backdrop = Some(BackdropFade {
+                url,
+                image,
+                previous,
+                started: Instant::now(),
+            });
+        }
+        Some(Artwork::Loading | Artwork::Missing) => {}
+        None => {
+            let rested = match &app.backdrop_focus {
+                Some((focused, since)) if *focused == url => since.elapsed() >= BACKDROP_DELAY,
+                _ => {
+                    app.backdrop_focus = Some((url.clone(), Instant::now()));
+                    false
+                }
+            };
+            if !rested || !supports_backdrops() {
+                return;
+            }
+            app.backdrops.insert(url.clone(), Artwork::Loading);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let thumbnail = fetch_thumbnail(&url, |image| image.blurred(2)).await;
+                let _ = tx.send(UiMessage::BackdropLoaded { url, thumbnail }).await;
+            });
+        }
+    }
+}
+
 /// Spawn a background task to fetch TV show details
 fn spawn_tv_details_fetch(tv_id: u64, tx: mpsc::Sender<UiMessage>, tmdb_apikey: Option<String>) {
     tokio::spawn(async move {
@@ -323,102 +870,626 @@ fn spawn_tv_details_fetch(tv_id: u64, tx: mpsc::Sender<UiMessage>, tmdb_apikey:
     });
 }
 
-pub async fn run(
-    config: Config,
-    ext_manager: ExtensionManager,
-    open_settings: bool,
-) -> io::Result<()> {
-    // Set up panic hook to restore terminal
-    let original_hook = std::panic::take_hook();
-    std::panic::set_hook(Box::new(move |panic_info| {
-        restore_terminal();
-        original_hook(panic_info);
-    }));
-
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    // Create app and channels
-    let mut app = App::new();
+/// Fetch absolutely numbered anime episodes for a TMDB show
+fn spawn_anime_episodes_fetch(
+    client: AnimeClient,
+    title: String,
+    year: Option<u16>,
+    tx: mpsc::Sender<UiMessage>,
+) {
+    tokio::spawn(async move {
+        match client.find_episodes(&title, year).await {
+            Ok(episodes) => {
+                let _ = tx.send(UiMessage::AnimeEpisodesLoaded(episodes)).await;
+            }
+            Err(e) => {
+                let _ = tx.send(UiMessage::AnimeEpisodesError(e.to_string())).await;
+            }
+        }
+    });
+}
 
-    // Open wizard if this is a new config (needs setup)
-    if open_settings {
-        app.view = View::Wizard;
+/// Fetch autocomplete suggestions for the search input.
+/// `actor:`/`director:` queries suggest only people.
+fn spawn_suggestion_fetch(app: &mut App, tx: &mpsc::Sender<UiMessage>, config: &Config) {
+    let query = app.search_input.clone();
+    let person_query = parse_person_query(&query).map(|(_, name)| name.to_string());
+    // TMDB only knows movies and TV, and has nothing on a magnet link
+    if app.media_mode == MediaMode::Audio
+        || person_query.as_deref().unwrap_or(&query).len() < 3
+        || streaming::direct_torrent_source(&query).is_some()
+    {
+        return;
     }
 
-    let (tx, mut rx) = mpsc::channel::<UiMessage>(32);
-
-    // Main loop - config is mutable for settings editing
-    let mut config = config;
-    let result = run_app(
-        &mut terminal,
-        &mut app,
-        &mut config,
-        &ext_manager,
-        tx,
-        &mut rx,
-    )
-    .await;
-
-    // Shutdown extensions
-    ext_manager.shutdown();
+    let tx = tx.clone();
+    let tmdb_apikey = config.tmdb.as_ref().map(|t| t.apikey.clone());
+    app.is_fetching_suggestions = true;
 
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    tokio::spawn(async move {
+        let Some(client) = TmdbClient::new(tmdb_apikey.as_deref()) else {
+            return;
+        };
 
-    result
+        let suggestions: Vec<TmdbSuggestion> = if let Some(name) = person_query {
+            let Ok(people) = client.search_person(&name).await else {
+                return;
+            };
+            people
+                .into_iter()
+                .take(5)
+                .map(|p| TmdbSuggestion {
+                    id: p.id,
+                    title: p.name,
+                    year: None,
+                    media_type: "person".to_string(),
+                })
+                .collect()
+        } else {
+            let Ok(results) = client.search_multi(&query).await else {
+                return;
+            };
+            results
+                .into_iter()
+                .take(5)
+                .map(|r| TmdbSuggestion {
+                    id: r.id,
+                    title: r.display_title().to_string(),
+                    year: r.year(),
+                    media_type: if r.is_anime() {
+                        "anime".to_string()
+                    } else {
+                        r.media_type.unwrap_or_default()
+                    },
+                })
+                .collect()
+        };
+        let _ = tx.send(UiMessage::Suggestions(suggestions)).await;
+    });
 }
 
-async fn run_app(
-    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    app: &mut App,
-    config: &mut Config,
-    ext_manager: &ExtensionManager,
+/// Load the filmography of an actor/director.
+/// Without a picked suggestion, the best person search match is used.
+fn spawn_person_credits_fetch(
+    person_id: Option<u64>,
+    name: String,
+    role: PersonRole,
     tx: mpsc::Sender<UiMessage>,
-    rx: &mut mpsc::Receiver<UiMessage>,
-) -> io::Result<()> {
-    let _prowlarr = ProwlarrClient::new(&config.prowlarr);
-    let _torznab = TorznabClient::new();
+    tmdb_apikey: Option<String>,
+) {
+    tokio::spawn(async move {
+        let Some(client) = TmdbClient::new(tmdb_apikey.as_deref()) else {
+            let _ = tx
+                .send(UiMessage::PersonError(
+                    "TMDB API key required for actor/director search".to_string(),
+                ))
+                .await;
+            return;
+        };
 
-    // Video categories: Movies & TV
-    const VIDEO_CATEGORIES: &[u32] = &[2000, 5000];
+        let result = async {
+            let (id, name) = match person_id {
+                Some(id) => (id, name),
+                None => {
+                    let person = client
+                        .search_person(&name)
+                        .await?
+                        .into_iter()
+                        .next()
+                        .ok_or(TmdbError::NotFound)?;
+                    (person.id, person.name)
+                }
+            };
+            let credits = client.get_person_credits(id).await?;
+            Ok::<_, TmdbError>((name, credits))
+        }
+        .await;
+
+        match result {
+            Ok((name, credits)) => {
+                let items = credits
+                    .for_role(role)
+                    .into_iter()
+                    .map(DiscoveryItem::from)
+                    .collect();
+                let _ = tx
+                    .send(UiMessage::PersonCreditsLoaded { name, items })
+                    .await;
+            }
+            Err(e) => {
+                let _ = tx
+                    .send(UiMessage::PersonError(format!("TMDB error: {}", e)))
+                    .await;
+            }
+        }
+    });
+}
 
-    // Watch history for resume functionality
-    let mut watch_history = WatchHistory::load();
-    // Clean up entries older than 30 days
-    watch_history.cleanup_old(30);
+/// Look up what a raw result is on TMDB from its release name
+fn spawn_result_enrichment(
+    title: String,
+    tx: mpsc::Sender<UiMessage>,
+    tmdb_apikey: Option<String>,
+) {
+    tokio::spawn(async move {
+        let (query, year, is_series) = tmdb::parse_release_name(&title);
+        let info = match TmdbClient::new(tmdb_apikey.as_deref()) {
+            Some(client) if !query.is_empty() => {
+                debug!(%query, ?year, is_series, "enriching result from TMDB");
+                let (results, media_type) = if is_series {
+                    (client.search_tv(&query, year).await, "tv")
+                } else {
+                    (client.search_movie(&query, year).await, "movie")
+                };
+                results
+                    .map(|results| {
+                        results.first().map(|first| TmdbMetadata {
+                            media_type: Some(media_type.to_string()),
+                            ..TmdbMetadata::from(first)
+                        })
+                    })
+                    .inspect_err(|e| debug!(%query, error = %e, "result enrichment failed"))
+            }
+            _ => Ok(None),
+        };
+        let _ = tx.send(UiMessage::ResultEnriched { title, info }).await;
+    });
+}
+
+/// Check whether a movie belongs to a collection, for "Browse Collection" in Results
+fn spawn_collection_lookup(
+    movie_id: u64,
+    search_id: u64,
+    tx: mpsc::Sender<UiMessage>,
+    tmdb_apikey: Option<String>,
+) {
+    tokio::spawn(async move {
+        let Some(client) = TmdbClient::new(tmdb_apikey.as_deref()) else {
+            return;
+        };
+        match client.get_movie_details(movie_id).await {
+            Ok(details) => {
+                if let Some(collection) = details.belongs_to_collection {
+                    let _ = tx
+                        .send(UiMessage::CollectionFound {
+                            search_id,
+                            collection,
+                        })
+                        .await;
+                }
+            }
+            Err(e) => debug!(movie_id, error = %e, "movie details lookup failed"),
+        }
+    });
+}
+
+/// Fetch the peers of a torrent and look up their countries
+fn spawn_peer_fetch(
+    session: std::sync::Arc<StreamingSession>,
+    torrent_id: usize,
+    geoip: Option<std::sync::Arc<GeoIp>>,
+    tx: mpsc::Sender<UiMessage>,
+) {
+    tokio::spawn(async move {
+        let message = match session.get_peers(torrent_id).await {
+            Ok(peers) => {
+                let shares = streaming::peer_contributions(&peers);
+                let rows = peers
+                    .iter()
+                    .zip(shares)
+                    .map(|(peer, share)| PeerRow {
+                        masked_ip: peer.masked_ip(),
+                        flag: geoip
+                            .as_ref()
+                            .zip(peer.ip())
+                            .and_then(|(geoip, ip)| geoip.country_code(ip))
+                            .and_then(|code| geoip::flag_emoji(&code)),
+                        share,
+                        state: peer.state.clone(),
+                    })
+                    .collect();
+                UiMessage::PeersLoaded(rows)
+            }
+            Err(e) => UiMessage::PeersError(e.to_string()),
+        };
+        let _ = tx.send(message).await;
+    });
+}
+
+/// Look for Chromecasts and DLNA renderers on the local network
+fn spawn_cast_discovery(tx: mpsc::Sender<UiMessage>) {
+    tokio::spawn(async move {
+        let devices = cast::discover().await;
+        info!(count = devices.len(), "found cast devices");
+        let _ = tx.send(UiMessage::CastDevicesFound(devices)).await;
+    });
+}
+
+/// Start playing the stream on a cast device
+fn spawn_cast(app: &App, device: CastDevice, stream_url: String, tx: mpsc::Sender<UiMessage>) {
+    let title = app.current_title.clone();
+    let file_name = app.current_file.clone();
+    let stream_id = app.stream_id;
+    tokio::spawn(async move {
+        let message = match cast::cast(&device, &stream_url, &title, &file_name).await {
+            Ok(relay) => UiMessage::CastStarted {
+                name: device.name,
+                stream_id,
+                relay,
+            },
+            Err(e) => {
+                warn!(device = %device.name, error = %e, "casting failed");
+                UiMessage::CastFailed(e.to_string())
+            }
+        };
+        let _ = tx.send(message).await;
+    });
+}
+
+/// Share the stream with other devices on the network
+fn spawn_stream_share(
+    app: &App,
+    stream_url: String,
+    bind: IpAddr,
+    token: Option<String>,
+    tx: mpsc::Sender<UiMessage>,
+) {
+    let stream_id = app.stream_id;
+    tokio::spawn(async move {
+        let message = match StreamRelay::share(&stream_url, bind, token.as_deref()).await {
+            Ok(relay) => {
+                info!(url = %relay.url(), "sharing stream on the network");
+                UiMessage::StreamShared { stream_id, relay }
+            }
+            Err(e) => {
+                warn!(%bind, error = %e, "sharing stream failed");
+                UiMessage::Notify {
+                    message: format!("Sharing failed: {}", e),
+                    level: ToastLevel::Error,
+                }
+            }
+        };
+        let _ = tx.send(message).await;
+    });
+}
+
+/// System clipboard, kept open since on Linux the copied text is only
+/// served while it is. None without a display to reach, e.g. over SSH.
+static CLIPBOARD: LazyLock<Mutex<Option<arboard::Clipboard>>> = LazyLock::new(|| {
+    Mutex::new(
+        arboard::Clipboard::new()
+            .inspect_err(|e| debug!(error = %e, "no system clipboard"))
+            .ok(),
+    )
+});
+
+/// Where a copy ended up
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Copied {
+    /// The system clipboard
+    Clipboard,
+    /// Sent to the terminal as OSC 52, terminals without it ignore that
+    Terminal,
+}
+
+/// Copy `text` to the system clipboard, or through the terminal's OSC 52
+/// support when there isn't one
+fn copy_to_clipboard(text: &str) -> io::Result<Copied> {
+    if let Some(clipboard) = CLIPBOARD.lock().unwrap().as_mut() {
+        match clipboard.set_text(text) {
+            Ok(()) => return Ok(Copied::Clipboard),
+            Err(e) => debug!(error = %e, "system clipboard copy failed, trying OSC 52"),
+        }
+    }
+    execute!(io::stdout(), CopyToClipboard::to_clipboard_from(text))?;
+    Ok(Copied::Terminal)
+}
+
+/// Copy `text` and say so in a toast
+fn copy_with_toast(app: &mut App, text: &str) {
+    match copy_to_clipboard(text) {
+        Ok(Copied::Clipboard) => app.push_toast(
+            "Copied to clipboard",
+            ToastLevel::Success,
+            ToastLevel::Success.ttl(),
+        ),
+        Ok(Copied::Terminal) => app.push_toast(
+            "Sent to the terminal's clipboard",
+            ToastLevel::Info,
+            ToastLevel::Info.ttl(),
+        ),
+        Err(e) => app.push_toast(
+            format!("Copy failed: {}", e),
+            ToastLevel::Error,
+            ToastLevel::Error.ttl(),
+        ),
+    }
+}
+
+/// Show a stream URL to open elsewhere, and copy it to the clipboard
+fn show_stream_url(app: &mut App, url: String) {
+    if let Err(e) = copy_to_clipboard(&url) {
+        debug!(error = %e, "failed to copy stream URL");
+    }
+    app.shared_stream_url = Some(url);
+}
+
+/// Load the movies of a collection in release order
+fn spawn_collection_fetch(
+    collection_id: u64,
+    tx: mpsc::Sender<UiMessage>,
+    tmdb_apikey: Option<String>,
+) {
+    tokio::spawn(async move {
+        let Some(client) = TmdbClient::new(tmdb_apikey.as_deref()) else {
+            return;
+        };
+        let message = match client.get_collection(collection_id).await {
+            Ok(collection) => UiMessage::CollectionLoaded {
+                name: collection.name,
+                items: collection
+                    .parts
+                    .into_iter()
+                    .map(DiscoveryItem::from)
+                    .collect(),
+            },
+            Err(e) => UiMessage::CollectionError(format!("TMDB error: {}", e)),
+        };
+        let _ = tx.send(message).await;
+    });
+}
+
+/// Open a movie/show picked from discovery or a filmography:
+/// TV shows go to the season browser, movies start a torrent search
+fn open_discovery_item(
+    app: &mut App,
+    item: DiscoveryItem,
+    tx: &mpsc::Sender<UiMessage>,
+    config: &Config,
+) {
+    // Set metadata
+    app.current_title = item.title.clone();
+    app.current_tmdb_id = Some(item.id);
+    app.current_year = item.year;
+    app.current_media_type = Some(item.media_type.clone());
+    app.current_poster_url = item.poster_url.clone();
+
+    // If TV show, go to season browser
+    if item.is_series() {
+        app.is_fetching_tv_details = true;
+        spawn_tv_details_fetch(
+            item.id,
+            tx.clone(),
+            config.tmdb.as_ref().map(|t| t.apikey.clone()),
+        );
+    } else {
+        // Movie - start torrent search
+        let search_query = if let Some(year) = item.year {
+            format!("{} {}", item.title, year)
+        } else {
+            item.title.clone()
+        };
+        let search_query = transform_search_query(app, config, search_query);
+
+        app.search_id += 1;
+        app.is_searching = true;
+        app.search_input = search_query.clone();
+        app.search_error = None;
+        spawn_collection_lookup(
+            item.id,
+            app.search_id,
+            tx.clone(),
+            config.tmdb.as_ref().map(|t| t.apikey.clone()),
+        );
+
+        let context = SearchContext {
+            title: item.title.clone(),
+            year: item.year,
+            media_type: Some(item.media_type.clone()),
+            tmdb_id: Some(item.id),
+            ..Default::default()
+        };
+        spawn_movie_search(app, search_query, context, tx, config);
+
+        // Navigate to Results view
+        app.view = View::Results;
+    }
+}
+
+pub async fn run(
+    config: Config,
+    location: ConfigLocation,
+    log_path: PathBuf,
+    ext_manager: ExtensionManager,
+    open_settings: bool,
+) -> io::Result<()> {
+    // Set up panic hook to restore terminal
+    let original_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        restore_terminal();
+        original_hook(panic_info);
+    }));
+
+    // Setup terminal
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    // Bracketed paste delivers a pasted magnet as one event instead of keys
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    // Create app and channels
+    let mut app = App::new();
+    app.indexer_health = IndexerHealth::load(location.indexer_stats_path()).shared();
+    app.search_service = SearchService::new(&config.prowlarr)
+        .with_usenet(config.usenet.is_some())
+        .shared();
+    app.media_mode = config.search.categories;
+    app.config_location = location;
+    app.log_path = log_path;
+    app.compact_mode = compact_mode(&config, terminal.size()?.width);
+
+    // Open wizard if this is a new config (needs setup)
+    if open_settings {
+        app.view = View::Wizard;
+    }
+
+    let (tx, mut rx) = mpsc::channel::<UiMessage>(32);
+
+    // Main loop - config is mutable for settings editing
+    let mut config = config;
+    let result = run_app(
+        &mut terminal,
+        &mut app,
+        &mut config,
+        &ext_manager,
+        tx,
+        &mut rx,
+    )
+    .await;
+
+    // Shutdown extensions
+    ext_manager.shutdown();
+
+    // Restore terminal
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    )?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+async fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    config: &mut Config,
+    ext_manager: &ExtensionManager,
+    tx: mpsc::Sender<UiMessage>,
+    rx: &mut mpsc::Receiver<UiMessage>,
+) -> io::Result<()> {
+    // Watch history for resume functionality
+    let mut watch_history = WatchHistory::load();
+    // Audio track picked per title with `a`
+    let mut audio_preferences =
+        AudioPreferences::load(app.config_location.audio_preferences_path());
+    // Clean up entries older than 30 days
+    watch_history.cleanup_old(30);
+    // What's playing, left behind on disk if ferristream doesn't stop cleanly
+    let mut session_recovery = SessionRecovery::new(app.config_location.session_snapshot_path());
+    app.recovery_prompt = session_recovery.load();
 
     // Streaming session (created when needed)
     let mut streaming_session: Option<std::sync::Arc<StreamingSession>> = None;
     // Cancellation token for streaming task
     let mut streaming_cancel: Option<CancellationToken> = None;
+    // Closes the main player, a child of streaming_cancel
+    let mut player_cancel: Option<CancellationToken> = None;
     // Stored torrent info for file selection
     let mut pending_torrent_info: Option<crate::streaming::TorrentInfo> = None;
+    // Torrents kept seeding after playback
+    let mut seeding: Vec<Seeding> = Vec::new();
+    let mut next_seed_id: u64 = 0;
+    // Next episode prefetch, Some once started for the current stream
+    let mut prefetch_cancel: Option<CancellationToken> = None;
+    // Relay serving the stream to a cast device, for the stream id it was started for
+    let mut cast_relay: Option<(u64, StreamRelay)> = None;
+    // Relay sharing the stream on the network, for the stream id it was started for
+    let mut share_relay: Option<(u64, StreamRelay)> = None;
+    // Country lookups for the peer list, only if a GeoLite2 database is installed
+    let geoip = config
+        .storage
+        .geoip_db()
+        .and_then(|path| GeoIp::open(&path))
+        .map(std::sync::Arc::new);
 
     // Load discovery data on startup (if not in wizard mode)
     if app.view == View::Discovery {
         app.is_loading_discovery = true;
         load_discovery_data(&tx, config);
     }
+    spawn_rss_poll(&tx, config);
+    spawn_stale_session_cleanup(&tx, config, app.config_location.pending_cleanup_path());
+    // Kept until the app exits, dropping it stops the watch
+    let _config_watcher = ConfigWatcher::start(app.config_location.clone(), tx.clone())
+        .inspect_err(|e| warn!(error = %e, "not watching the config file for changes"))
+        .ok();
+    // Progress updates, coalesced to the latest and read every frame
+    let mut progress_rx = app.progress.subscribe();
+    // Messages raised by the UI itself, handled next frame without going
+    // through the bounded channel it is the only reader of
+    let mut local_messages: Vec<UiMessage> = Vec::new();
 
     loop {
+        // Surface extension errors and drop expired toasts
+        for message in ext_manager.take_errors() {
+            app.push_toast(message, ToastLevel::Error, ToastLevel::Error.ttl());
+        }
+        app.expire_toasts();
+
+        // Guess what a highlighted result is when the search didn't say
+        if app.media_mode != MediaMode::Audio
+            && let Some(title) = app.result_to_enrich(Instant::now())
+        {
+            spawn_result_enrichment(
+                title,
+                tx.clone(),
+                config.tmdb.as_ref().map(|t| t.apikey.clone()),
+            );
+        }
+
+        // Periodic discovery reload, only while it's being looked at
+        if app.discovery_refresh_due(config.search.discovery_refresh_interval()) {
+            debug!("auto-refreshing discovery");
+            app.is_loading_discovery = true;
+            load_discovery_data(&tx, config);
+        }
+
+        // Casting ends with the stream it was started for
+        if cast_relay
+            .as_ref()
+            .is_some_and(|(stream_id, _)| !app.is_streaming || *stream_id != app.stream_id)
+        {
+            cast_relay = None;
+            app.casting_to = None;
+        }
+        if share_relay
+            .as_ref()
+            .is_some_and(|(stream_id, _)| !app.is_streaming || *stream_id != app.stream_id)
+        {
+            share_relay = None;
+        }
+        if !app.is_streaming {
+            app.shared_stream_url = None;
+        }
+
+        update_backdrop(app, &tx, config);
+
         // Draw UI
         terminal.draw(|f| ui::draw(f, app, Some(config)))?;
 
-        // Handle messages from background tasks
-        while let Ok(msg) = rx.try_recv() {
+        // Handle messages from background tasks, latest progress first
+        for msg in progress_rx
+            .take()
+            .chain(std::mem::take(&mut local_messages))
+            .chain(std::iter::from_fn(|| rx.try_recv().ok()))
+        {
             match msg {
-                UiMessage::SearchComplete { results, search_id } => {
+                UiMessage::SearchComplete {
+                    results,
+                    search_id,
+                    query,
+                    context,
+                } => {
                     // Ignore results from stale searches
                     if search_id != app.search_id {
                         debug!(
@@ -430,15 +1501,55 @@ async fn run_app(
                     }
 
                     app.is_searching = false;
-                    app.results = results;
-                    app.sort_results(); // Apply current sort order
+                    app.search_context = context.clone();
+                    app.results_query = query;
+
+                    // Score once up front, the Smart sort and auto-race both use it
+                    let validation = search_validation(&context, app.tmdb_info.as_ref(), config);
+                    let scorer = ResultScorer::new(
+                        validation.clone(),
+                        context.media_type.clone(),
+                        config.filter.result_filter(app.media_mode).blocked_terms,
+                    );
+                    let scores = scorer.score_all(&results);
+                    let result_filter = config.filter.result_filter(app.media_mode);
+                    let (kept, mut hidden): (Vec<_>, Vec<_>) = results
+                        .into_iter()
+                        .zip(scores)
+                        .partition(|(r, _)| result_filter.check(r).is_none());
+                    // Safe mode drops adult releases, revealing hidden results doesn't bring them back
+                    hidden.retain(|(r, _)| result_filter.check(r) != Some(FilterReason::Adult));
+                    if !hidden.is_empty() {
+                        debug!(hidden = hidden.len(), "filtered out junk results");
+                    }
                     app.selected_index = 0;
+                    app.set_results(kept, hidden); // Applies current sort order
 
                     if app.results.is_empty() {
-                        app.search_error = Some("No results found".to_string());
+                        app.search_error = Some(if app.hidden_results.is_empty() {
+                            "No results found".to_string()
+                        } else {
+                            format!(
+                                "No results found ({} hidden by filter)",
+                                app.hidden_results.len()
+                            )
+                        });
                     } else {
                         app.search_error = None;
 
+                        if config.notifications.enabled && config.notifications.on_search_complete {
+                            let body = if app.search_input.is_empty() {
+                                format!("{} results found", app.results.len())
+                            } else {
+                                format!(
+                                    "{} results for \"{}\"",
+                                    app.results.len(),
+                                    app.search_input
+                                )
+                            };
+                            notifications::notify("Search complete", &body, Urgency::Low);
+                        }
+
                         // Check if auto-race is enabled
                         let auto_race = config.streaming.auto_race as usize;
                         if auto_race > 0 && !app.is_streaming {
@@ -446,112 +1557,31 @@ async fn run_app(
                             if let Some(cancel) = streaming_cancel.take() {
                                 cancel.cancel();
                             }
+                            discard_prefetch(app, &mut prefetch_cancel, &tx);
                             if let Some(session) = streaming_session.take() {
-                                session.cleanup().await;
+                                spawn_cleanup(app, session, &tx);
                             }
 
-                            // Get ALL torrent URLs - we'll race through them until we find a match
-                            let urls: Vec<String> = app
-                                .results
+                            // Race the best candidates first, skipping dead torrents
+                            let race_order = config.streaming.race_order();
+                            let urls: Vec<String> = race_order
+                                .rank(&app.results, &app.result_scores)
                                 .iter()
                                 .filter_map(|r| r.get_torrent_url())
                                 .collect();
+                            let race_description = race_order.describe(urls.len().min(auto_race));
 
                             if !urls.is_empty() {
-                                // Clear previous streaming state
-                                app.current_file.clear();
-                                app.current_title.clear();
-                                app.available_files.clear();
-                                app.download_progress = DownloadProgress::default();
                                 pending_torrent_info = None;
-
-                                app.is_streaming = true;
-                                app.racing_message = Some(format!(
-                                    "Racing {} torrents...",
-                                    urls.len().min(auto_race)
-                                ));
-                                app.view = View::Streaming;
-                                app.streaming_state = StreamingState::Connecting;
-
-                                let tx = tx.clone();
-                                let temp_dir = config.storage.temp_dir();
-                                let cancel_token = CancellationToken::new();
-                                streaming_cancel = Some(cancel_token.clone());
-
-                                // Build validation criteria from search query and TMDB info
-                                let mut title_keywords =
-                                    TorrentValidation::extract_keywords(&app.search_input);
-                                let mut year: Option<u16> = None;
-
-                                // Add TMDB title keywords and year if available
-                                if let Some(ref tmdb) = app.tmdb_info {
-                                    title_keywords
-                                        .extend(TorrentValidation::extract_keywords(&tmdb.title));
-                                    year = tmdb.year;
-                                }
-                                // Deduplicate keywords
-                                title_keywords.sort();
-                                title_keywords.dedup();
-
-                                let validation = if title_keywords.is_empty() && year.is_none() {
-                                    None
-                                } else {
-                                    Some(TorrentValidation::new(title_keywords.clone(), year))
-                                };
-                                info!(keywords = ?title_keywords, year = ?year, "validation criteria");
-
-                                let concurrent = auto_race;
-                                tokio::spawn(async move {
-                                    let _ = tx
-                                        .send(UiMessage::RacingStatus {
-                                            count: concurrent.min(urls.len()),
-                                            message: "connecting...".to_string(),
-                                        })
-                                        .await;
-
-                                    let session = match StreamingSession::new(temp_dir).await {
-                                        Ok(s) => std::sync::Arc::new(s),
-                                        Err(e) => {
-                                            let _ = tx
-                                                .send(UiMessage::StreamError(e.to_string()))
-                                                .await;
-                                            return;
-                                        }
-                                    };
-
-                                    if cancel_token.is_cancelled() {
-                                        session.cleanup().await;
-                                        return;
-                                    }
-
-                                    match session
-                                        .race_torrents(
-                                            urls,
-                                            validation,
-                                            concurrent,
-                                            cancel_token.clone(),
-                                        )
-                                        .await
-                                    {
-                                        Ok((_winner_idx, torrent_info)) => {
-                                            let _ = tx
-                                                .send(UiMessage::TorrentMetadata {
-                                                    torrent_info,
-                                                    session,
-                                                })
-                                                .await;
-                                        }
-                                        Err(e) => {
-                                            // Don't report error if cancelled
-                                            if !cancel_token.is_cancelled() {
-                                                let _ = tx
-                                                    .send(UiMessage::StreamError(e.to_string()))
-                                                    .await;
-                                            }
-                                            session.cleanup().await;
-                                        }
-                                    }
-                                });
+                                start_race(
+                                    app,
+                                    urls,
+                                    auto_race,
+                                    race_description,
+                                    &tx,
+                                    config,
+                                    &mut streaming_cancel,
+                                );
                             } else {
                                 app.view = View::Results;
                             }
@@ -565,8 +1595,49 @@ async fn run_app(
                     app.search_error = Some(e);
                 }
                 UiMessage::TmdbInfo(info) => {
+                    if info.media_type.as_deref() == Some("movie")
+                        && let Some(id) = info.id
+                    {
+                        spawn_collection_lookup(
+                            id,
+                            app.search_id,
+                            tx.clone(),
+                            config.tmdb.as_ref().map(|t| t.apikey.clone()),
+                        );
+                    }
                     app.tmdb_info = Some(info);
+                    app.tmdb_info_guessed = false;
+                }
+                UiMessage::ResultEnriched { title, info } => {
+                    app.finish_enrichment(title, info);
+                }
+                UiMessage::TmdbAmbiguous {
+                    search_id,
+                    query,
+                    candidates,
+                } if search_id == app.search_id => {
+                    let remembered = app
+                        .tmdb_choices
+                        .get(&App::tmdb_choice_key(&query))
+                        .and_then(|id| candidates.iter().find(|c| c.id == Some(*id)));
+                    match remembered {
+                        Some(choice) => {
+                            let current = app.tmdb_info.as_ref().and_then(|t| t.id);
+                            if choice.id != current {
+                                choose_tmdb_match(app, choice.clone(), &tx, config);
+                            }
+                        }
+                        None => {
+                            app.selected_tmdb_candidate = 0;
+                            app.tmdb_prompt = Some(TmdbPrompt {
+                                search_id,
+                                query,
+                                candidates,
+                            });
+                        }
+                    }
                 }
+                UiMessage::TmdbAmbiguous { .. } => {}
                 UiMessage::Suggestions(suggestions) => {
                     app.suggestions = suggestions;
                     app.selected_suggestion = 0;
@@ -580,258 +1651,386 @@ async fn run_app(
                         .filter(|s| s.season_number > 0)
                         .cloned()
                         .collect();
-                    app.tv_details = Some(details);
                     app.selected_season_index = 0;
-                    app.is_fetching_tv_details = false;
-                    app.view = View::TvSeasons;
+
+                    // Anime with an AniDB/MAL source skips seasons and lists all episodes
+                    if app.current_media_type.as_deref() == Some("anime")
+                        && let Some(client) = config.anime.client()
+                    {
+                        let year = details
+                            .first_air_date
+                            .as_ref()
+                            .and_then(|d| d.split('-').next()?.parse().ok());
+                        spawn_anime_episodes_fetch(client, details.name.clone(), year, tx.clone());
+                    } else {
+                        app.is_fetching_tv_details = false;
+                        app.view = View::TvSeasons;
+                    }
+                    app.tv_details = Some(details);
                 }
                 UiMessage::SeasonEpisodesLoaded(episodes) => {
                     app.tv_episodes = episodes;
-                    app.selected_episode_index = 0;
+                    refresh_episode_progress(app, &watch_history);
+                    app.select_first_unwatched_episode();
+                    app.absolute_numbering = false;
+                    app.is_fetching_tv_details = false;
+                    app.view = View::TvEpisodes;
+                    spawn_episode_still_fetch(app, &tx, config);
+                }
+                UiMessage::AnimeEpisodesLoaded(episodes) => {
+                    app.tv_episodes = episodes;
+                    refresh_episode_progress(app, &watch_history);
+                    app.select_first_unwatched_episode();
+                    app.absolute_numbering = true;
                     app.is_fetching_tv_details = false;
                     app.view = View::TvEpisodes;
                 }
+                UiMessage::AnimeEpisodesError(e) => {
+                    warn!(error = %e, "anime episode lookup failed, using TMDB seasons");
+                    app.is_fetching_tv_details = false;
+                    app.view = View::TvSeasons;
+                }
+                UiMessage::EpisodeStillLoaded {
+                    episode_id,
+                    thumbnail,
+                } => {
+                    let still = thumbnail.map_or(Artwork::Missing, Artwork::Loaded);
+                    app.episode_stills.insert(episode_id, still);
+                }
+                UiMessage::BackdropLoaded { url, thumbnail } => {
+                    let backdrop = thumbnail.map_or(Artwork::Missing, Artwork::Loaded);
+                    app.backdrops.insert(url, backdrop);
+                }
                 UiMessage::DoctorComplete(results) => {
                     app.doctor_results = results;
                     app.is_checking = false;
                 }
                 UiMessage::DiscoveryLoaded { rows } => {
-                    app.discovery_rows = rows;
-                    app.selected_row_index = 0;
-                    app.selected_item_index = 0;
+                    app.set_discovery_rows(rows);
                     app.is_loading_discovery = false;
                     app.discovery_error = None;
+                    app.last_discovery_load = Some(Instant::now());
                 }
                 UiMessage::DiscoveryError(e) => {
                     app.is_loading_discovery = false;
                     app.discovery_error = Some(e);
+                    // Auto-refresh tries again an interval later, not right away
+                    app.last_discovery_load = Some(Instant::now());
                 }
-                UiMessage::RacingStatus { count, message } => {
-                    app.racing_message = Some(format!("Racing {} torrents: {}", count, message));
+                UiMessage::CastDevicesFound(devices) => {
+                    app.selected_cast_index =
+                        app.selected_cast_index.min(devices.len().saturating_sub(1));
+                    app.cast_devices = devices;
+                    app.is_discovering_cast = false;
                 }
-                UiMessage::TorrentMetadata {
-                    torrent_info,
-                    session,
+                UiMessage::CastStarted {
+                    name,
+                    stream_id,
+                    relay,
                 } => {
-                    app.racing_message = None; // Clear racing message
-                    app.pending_torrent_id = Some(torrent_info.id);
-                    streaming_session = Some(session.clone());
-                    pending_torrent_info = Some(torrent_info.clone());
-
-                    if torrent_info.video_files.len() > 1 {
-                        // Multiple files - show selection UI
-                        info!(
-                            files = torrent_info.video_files.len(),
-                            "multiple video files, showing selection"
+                    app.is_starting_cast = false;
+                    if stream_id == app.stream_id && app.is_streaming {
+                        app.push_toast(
+                            format!("Casting to {}", name),
+                            ToastLevel::Success,
+                            ToastLevel::Success.ttl(),
                         );
-                        // Sort by episode number for season packs
-                        let mut sorted_files = torrent_info.video_files.clone();
-                        sort_episodes(&mut sorted_files);
-                        app.available_files = sorted_files;
-                        app.selected_file_index = 0;
-                        app.current_episode_index = 0;
-                        app.next_episode_ready = false;
-                        app.view = View::FileSelection;
-                        app.streaming_state = StreamingState::FetchingMetadata;
-                    } else if let Some(file) = torrent_info.video_files.first().cloned() {
-                        // Single file - proceed directly to streaming
-                        info!(file = %file.name, "single video file, starting stream");
-                        app.current_file = file.name.clone();
-                        app.streaming_state = StreamingState::Ready {
-                            stream_url: file.stream_url.clone(),
-                        };
+                        app.casting_to = Some(name);
                         app.view = View::Streaming;
+                        cast_relay = Some((stream_id, relay));
+                    }
+                }
+                UiMessage::StreamShared { stream_id, relay } => {
+                    if stream_id == app.stream_id && app.is_streaming {
+                        show_stream_url(app, relay.url().to_string());
+                        share_relay = Some((stream_id, relay));
+                    }
+                }
+                UiMessage::CastFailed(e) => {
+                    app.is_starting_cast = false;
+                    app.push_toast(
+                        format!("Cast failed: {}", e),
+                        ToastLevel::Error,
+                        ToastLevel::Error.ttl(),
+                    );
+                }
+                UiMessage::PersonCreditsLoaded { name, items } => {
+                    app.person_name = name;
+                    app.person_credits = items;
+                    app.selected_credit_index = 0;
+                    app.is_fetching_person = false;
+                }
+                UiMessage::PersonError(e) => {
+                    app.is_fetching_person = false;
+                    app.search_error = Some(e);
+                    app.view = View::Search;
+                }
+                UiMessage::CollectionFound {
+                    search_id,
+                    collection,
+                } => {
+                    app.results_collection = Some((search_id, collection));
+                }
+                UiMessage::CollectionLoaded { name, items } => {
+                    app.collection_name = name;
+                    app.collection_items = items;
+                    app.selected_collection_index = 0;
+                    app.is_fetching_collection = false;
+                }
+                UiMessage::CollectionError(e) => {
+                    app.is_fetching_collection = false;
+                    app.push_toast(e, ToastLevel::Error, ToastLevel::Error.ttl());
+                    if app.view == View::Collection {
+                        app.view = View::Results;
+                    }
+                }
+                UiMessage::PeersLoaded(peers) => {
+                    app.selected_peer_index =
+                        app.selected_peer_index.min(peers.len().saturating_sub(1));
+                    app.peers = peers;
+                    app.is_fetching_peers = false;
+                    app.peers_error = None;
+                }
+                UiMessage::PeersError(e) => {
+                    app.is_fetching_peers = false;
+                    app.peers_error = Some(e);
+                }
+                UiMessage::RssMatch {
+                    rule_name,
+                    result,
+                    auto_stream,
+                } => {
+                    if config.notifications.enabled {
+                        notifications::notify("New release", &result.title, Urgency::Normal);
+                    }
+                    if auto_stream && !app.is_streaming {
+                        app.push_toast(
+                            format!("Streaming new release: {}", result.title),
+                            ToastLevel::Info,
+                            ToastLevel::Info.ttl(),
+                        );
+                        discard_prefetch(app, &mut prefetch_cancel, &tx);
+                        start_rss_stream(app, result, &tx, config, &mut streaming_cancel);
+                    } else {
+                        app.push_toast(
+                            format!("\"{}\" matched: {} (n to stream)", rule_name, result.title),
+                            ToastLevel::Info,
+                            ToastLevel::Warning.ttl(),
+                        );
+                        app.rss_queue.push_back(result);
+                    }
+                }
+                UiMessage::Notify { message, level } => {
+                    app.push_toast(message, level, level.ttl());
+                }
+                UiMessage::ConfigReloaded(reloaded) => {
+                    // Our own saves come back through the watcher too
+                    if Config::diff(config, &reloaded).is_empty() {
+                        continue;
+                    }
+                    if app.settings_editing || app.settings_dirty {
+                        app.push_toast(
+                            "Config changed on disk, keeping your unsaved settings",
+                            ToastLevel::Warning,
+                            ToastLevel::Warning.ttl(),
+                        );
+                        continue;
+                    }
+                    let errors = reloaded.validate();
+                    if !errors.is_empty() {
+                        let messages: Vec<String> =
+                            errors.iter().map(ToString::to_string).collect();
+                        app.push_toast(
+                            format!("Config not reloaded: {}", messages.join("; ")),
+                            ToastLevel::Error,
+                            ToastLevel::Error.ttl(),
+                        );
+                        continue;
+                    }
+                    info!("config reloaded from disk");
+                    *config = *reloaded;
+                    app.compact_mode = compact_mode(config, terminal.size()?.width);
+                    // Prowlarr may have moved, start over with a fresh indexer list
+                    app.search_service = SearchService::new(&config.prowlarr)
+                        .with_usenet(config.usenet.is_some())
+                        .shared();
+                    if app.settings_saved.is_some() {
+                        app.settings_saved = Some(config.clone());
+                    }
+                    app.push_toast("Config reloaded", ToastLevel::Info, ToastLevel::Info.ttl());
+                }
+                UiMessage::ConfigReloadFailed(error) => {
+                    app.push_toast(
+                        format!("Config not reloaded: {}", error),
+                        ToastLevel::Error,
+                        ToastLevel::Error.ttl(),
+                    );
+                }
+                UiMessage::CacheSize(size) => {
+                    app.cache_size = Some(size);
+                }
+                UiMessage::FreeSpace(free) => {
+                    app.free_space = free;
+                }
+                UiMessage::PlayersDetected(players) => {
+                    app.is_detecting_players = false;
+                    app.detected_players = Some(players);
+                    if app.view == View::Wizard
+                        && app.wizard_step == WizardStep::Player
+                        && !app.wizard_editing
+                    {
+                        // Rows moved, stay on the configured player
+                        app.wizard_field_index = app.player_option_index(&config.player.command);
+                    }
+                }
+                UiMessage::CacheCleared {
+                    removed,
+                    freed,
+                    size,
+                } => {
+                    app.is_clearing_cache = false;
+                    app.cache_size = Some(size);
+                    let message = if removed > 0 {
+                        format!("Removed {} session(s), freed {}", removed, format_gb(freed))
+                    } else {
+                        "Nothing to clear, the cache is in use".to_string()
+                    };
+                    app.push_toast(message, ToastLevel::Success, ToastLevel::Success.ttl());
+                }
+                UiMessage::AddProgress { phase, stream_id } => {
+                    if stream_id == app.stream_id {
+                        // Raced torrents report over each other, show the furthest along
+                        app.add_phase = app.add_phase.max(Some(phase));
+                    }
+                }
+                UiMessage::TorrentMetadata {
+                    torrent_info,
+                    session,
+                    stream_id,
+                } => {
+                    if stream_id != app.stream_id {
+                        // Finished after the user moved on to another stream
+                        info!(torrent = %torrent_info.name, "discarding metadata of a replaced stream");
+                        spawn_cleanup(app, session, &tx);
+                        continue;
+                    }
+                    app.racing_message = None; // Clear racing message
+                    if app.direct_stream_id == Some(stream_id) {
+                        let (title, year) = parse_torrent_title(&torrent_info.name);
+                        if !title.is_empty() {
+                            app.current_title = title;
+                        }
+                        app.current_year = year;
+                    }
+                    app.pending_torrent_id = Some(torrent_info.id);
+                    streaming_session = Some(session.clone());
+                    pending_torrent_info = Some(torrent_info.clone());
 
-                        // Notify extensions
-                        let (season, episode) = parse_episode_info(&file.name);
-                        ext_manager.broadcast(PlaybackEvent::Started(MediaInfo {
-                            title: app.current_title.clone(),
-                            file_name: file.name.clone(),
-                            total_bytes: file.size,
-                            tmdb_id: app.current_tmdb_id,
-                            year: app.current_year.map(|y| y as u32),
-                            media_type: app.current_media_type.clone(),
-                            poster_url: app.current_poster_url.clone(),
-                            season,
-                            episode,
-                        }));
-
-                        // Launch player task for single file
-                        let tx = tx.clone();
-                        let player_command = config.player.command.clone();
-                        let player_args = config.player.args.clone();
-                        let subtitles_enabled = config.subtitles.enabled;
-                        let preferred_language = config.subtitles.language.clone();
-                        let opensubtitles_key = config.subtitles.opensubtitles_api_key.clone();
-                        let tmdb_id = app.current_tmdb_id;
-                        let subtitle_files = torrent_info.subtitle_files.clone();
-                        let stream_url = file.stream_url.clone();
-                        let torrent_id = torrent_info.id;
-                        let cancel_token = streaming_cancel.clone().unwrap_or_default();
-
-                        tokio::spawn(async move {
-                            // Spawn progress polling task
-                            let progress_tx = tx.clone();
-                            let progress_session = session.clone();
-                            let progress_handle = tokio::spawn(async move {
-                                loop {
-                                    tokio::time::sleep(Duration::from_millis(500)).await;
-                                    if let Some(stats) =
-                                        progress_session.get_stats(torrent_id).await
-                                    {
-                                        let progress = DownloadProgress {
-                                            downloaded_bytes: stats.downloaded_bytes,
-                                            total_bytes: stats.total_bytes,
-                                            download_speed: stats.download_speed,
-                                            upload_speed: stats.upload_speed,
-                                            peers_connected: stats.peers_connected,
-                                            progress_percent: if stats.total_bytes > 0 {
-                                                (stats.downloaded_bytes as f64
-                                                    / stats.total_bytes as f64)
-                                                    * 100.0
-                                            } else {
-                                                0.0
-                                            },
-                                        };
-                                        if progress_tx
-                                            .send(UiMessage::ProgressUpdate(progress))
-                                            .await
-                                            .is_err()
-                                        {
-                                            break;
-                                        }
-                                    }
-                                }
-                            });
-
-                            // Find best subtitle
-                            let subtitle_url = if subtitles_enabled {
-                                let from_torrent = subtitle_files
-                                    .iter()
-                                    .find(|s| {
-                                        s.language
-                                            .as_ref()
-                                            .map(|l| l == &preferred_language)
-                                            .unwrap_or(false)
-                                    })
-                                    .or_else(|| subtitle_files.first())
-                                    .map(|s| s.stream_url.clone());
-
-                                if from_torrent.is_some() {
-                                    from_torrent
-                                } else if let (Some(api_key), Some(tmdb)) =
-                                    (&opensubtitles_key, tmdb_id)
-                                {
-                                    info!("no subtitles in torrent, trying OpenSubtitles");
-                                    let os_client = OpenSubtitlesClient::new(api_key);
-                                    match os_client.search_by_tmdb(tmdb, &preferred_language).await
-                                    {
-                                        Ok(subs) => subs.first().map(|s| s.download_url.clone()),
-                                        Err(e) => {
-                                            debug!(error = %e, "OpenSubtitles search failed");
-                                            None
-                                        }
-                                    }
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            };
-
-                            if cancel_token.is_cancelled() {
-                                progress_handle.abort();
-                                session.cleanup().await;
-                                let _ = tx.send(UiMessage::PlayerExited).await;
-                                return;
-                            }
-
-                            info!(player = %player_command, "launching player");
-                            match streaming::launch_player(
-                                &player_command,
-                                &player_args,
-                                &stream_url,
-                                subtitle_url.as_deref(),
-                            )
-                            .await
-                            {
-                                Ok(mut handle) => {
-                                    // Spawn position polling task if we have IPC
-                                    let position_handle = if let Some(ref socket_path) =
-                                        handle.ipc_socket
-                                    {
-                                        let socket = socket_path.clone();
-                                        let tx_pos = tx.clone();
-                                        Some(tokio::spawn(async move {
-                                            // Wait a bit for mpv to start
-                                            tokio::time::sleep(Duration::from_secs(2)).await;
-                                            loop {
-                                                if let Some((pos, dur)) =
-                                                    streaming::get_mpv_position(&socket).await
-                                                {
-                                                    let progress =
-                                                        streaming::calculate_progress(pos, dur);
-                                                    let _ = tx_pos
-                                                        .send(UiMessage::PlaybackProgress(progress))
-                                                        .await;
-                                                }
-                                                tokio::time::sleep(Duration::from_secs(5)).await;
-                                            }
-                                        }))
-                                    } else {
-                                        None
-                                    };
+                    if let Some(file_idx) = app.recovering_file.take() {
+                        // Same file and position as the session that was cut short,
+                        // by index if the name changed
+                        if !torrent_info
+                            .video_files
+                            .iter()
+                            .any(|f| f.name == app.current_file)
+                            && let Some(file) = torrent_info
+                                .video_files
+                                .iter()
+                                .find(|f| f.file_idx == file_idx)
+                        {
+                            app.current_file = file.name.clone();
+                        }
+                        let player = PlaybackController::new(
+                            config,
+                            session.clone(),
+                            streaming_cancel.clone().unwrap_or_default(),
+                            tx.clone(),
+                            app.progress.clone(),
+                            app.stream_id,
+                            app.config_location.pending_cleanup_path(),
+                        );
+                        if !resume_stream(app, &torrent_info, &player) {
+                            streaming_session = None;
+                            pending_torrent_info = None;
+                            spawn_cleanup(app, session, &tx);
+                        }
+                        continue;
+                    }
 
-                                    // Wait for either player to exit OR cancellation
-                                    tokio::select! {
-                                        _ = handle.child.wait() => {
-                                            info!("player exited normally");
-                                        }
-                                        _ = cancel_token.cancelled() => {
-                                            info!("cancellation requested, killing player");
-                                            let _ = handle.child.kill().await;
-                                        }
-                                    }
+                    if torrent_info.video_files.len() > 1 {
+                        // Multiple files - show selection UI
+                        info!(
+                            files = torrent_info.video_files.len(),
+                            "multiple video files, showing selection"
+                        );
+                        // Sort by episode number for season packs
+                        let mut sorted_files = torrent_info.video_files.clone();
+                        sort_episodes(&mut sorted_files);
+                        app.set_files(sorted_files, torrent_info.extra_files.clone());
+                        app.current_episode_index = 0;
+                        app.next_episode_ready = false;
+                        app.view = View::FileSelection;
+                        app.streaming_state = StreamingState::FetchingMetadata;
 
-                                    // Stop position polling
-                                    if let Some(h) = position_handle {
-                                        h.abort();
-                                    }
+                        if config.notifications.enabled && config.notifications.on_stream_ready {
+                            notifications::notify(
+                                "Torrent ready",
+                                &format!(
+                                    "{} files available - select one to play",
+                                    app.available_files.len()
+                                ),
+                                Urgency::Normal,
+                            );
+                        }
+                    } else if let Some(file) = torrent_info.video_files.first().cloned() {
+                        // Single file - proceed directly to streaming
+                        info!(file = %file.name, "single video file, starting stream");
+                        app.current_file = file.name.clone();
+                        app.streaming_state = StreamingState::Ready {
+                            stream_url: file.stream_url.clone(),
+                        };
+                        app.view = View::Streaming;
 
-                                    // Clean up IPC socket
-                                    if let Some(socket_path) = handle.ipc_socket {
-                                        let _ = std::fs::remove_file(socket_path);
-                                    }
-                                }
-                                Err(e) => {
-                                    error!(error = %e, "failed to launch player");
-                                    let _ = tx.send(UiMessage::StreamError(e.to_string())).await;
-                                    progress_handle.abort();
-                                    return;
-                                }
-                            }
+                        if config.notifications.enabled && config.notifications.on_stream_ready {
+                            notify_stream_ready(app);
+                        }
 
-                            progress_handle.abort();
-                            session.cleanup().await;
-                            let _ = tx.send(UiMessage::PlayerExited).await;
-                        });
+                        // Launch player task for single file
+                        PlaybackController::new(
+                            config,
+                            session,
+                            streaming_cancel.clone().unwrap_or_default(),
+                            tx.clone(),
+                            app.progress.clone(),
+                            app.stream_id,
+                            app.config_location.pending_cleanup_path(),
+                        )
+                        .play(&file, &torrent_info, app.media_context());
                     }
                 }
                 UiMessage::StreamReady {
                     file_name,
                     stream_url,
+                    stream_id,
                 } => {
+                    if stream_id != app.stream_id {
+                        continue;
+                    }
                     app.current_file = file_name.clone();
                     app.streaming_state = StreamingState::Ready { stream_url };
                     app.playback_progress = 0.0; // Reset for new playback
 
                     // Check if there's a resume point for this content
-                    let history_key = WatchHistory::make_key(app.current_tmdb_id, &file_name);
+                    let history_key = app.history_key();
                     if let Some(progress) = watch_history.has_resume_point(&history_key) {
                         app.show_resume_prompt = true;
                         app.resume_progress = progress;
                     }
 
+                    if config.notifications.enabled && config.notifications.on_stream_ready {
+                        notify_stream_ready(app);
+                    }
+
                     // Notify extensions
                     let (season, episode) = parse_episode_info(&file_name);
                     ext_manager.broadcast(PlaybackEvent::Started(MediaInfo {
@@ -846,45 +2045,404 @@ async fn run_app(
                         episode,
                     }));
                 }
-                UiMessage::StreamError(e) => {
-                    app.streaming_state = StreamingState::Error(e);
+                UiMessage::StreamError { error, stream_id } => {
+                    if stream_id != app.stream_id {
+                        warn!(%error, "ignoring error of a replaced stream");
+                        continue;
+                    }
+                    app.streaming_state = StreamingState::Error(error);
                     app.is_streaming = false;
+                    app.recovering_file = None;
+                }
+                UiMessage::StreamBackendLost { stream_id } => {
+                    // Before the player is up, cancelling for a restart would
+                    // clean up the files the restart picks up again
+                    if stream_id == app.stream_id
+                        && app.is_streaming
+                        && matches!(app.streaming_state, StreamingState::Playing { .. })
+                    {
+                        app.streaming_state = StreamingState::BackendLost;
+                        app.push_toast(
+                            "Stream stopped responding - press r to restart",
+                            ToastLevel::Error,
+                            ToastLevel::Error.ttl(),
+                        );
+                    }
+                }
+                UiMessage::StreamRestarted {
+                    torrent_info,
+                    session,
+                } => {
+                    if !app.is_streaming || streaming_cancel.is_none() {
+                        // Stopped while restarting
+                        spawn_cleanup(app, session, &tx);
+                        continue;
+                    }
+                    // Relaunch the player on the new session, where playback stalled
+                    let player = PlaybackController::new(
+                        config,
+                        session.clone(),
+                        streaming_cancel.clone().unwrap_or_default(),
+                        tx.clone(),
+                        app.progress.clone(),
+                        app.stream_id,
+                        app.config_location.pending_cleanup_path(),
+                    );
+                    if resume_stream(app, &torrent_info, &player) {
+                        streaming_session = Some(session);
+                        pending_torrent_info = Some(torrent_info);
+                    } else {
+                        spawn_cleanup(app, session, &tx);
+                    }
                 }
-                UiMessage::ProgressUpdate(progress) => {
+                UiMessage::ProgressUpdate {
+                    progress,
+                    stream_id,
+                    torrent,
+                } => {
+                    // Counted even for a stream that was just replaced
+                    app.record_transfer(
+                        torrent,
+                        progress.downloaded_bytes,
+                        progress.uploaded_bytes,
+                    );
+                    if stream_id != app.stream_id {
+                        continue;
+                    }
+                    app.push_download_speed(progress.download_speed);
                     app.download_progress = progress;
+
+                    if app.view == View::Streaming
+                        && !app.piece_map_loading
+                        && let (Some(session), Some(torrent_info)) =
+                            (streaming_session.as_ref(), pending_torrent_info.as_ref())
+                        && let Some(file_idx) = current_file_idx(app, torrent_info)
+                    {
+                        app.piece_map_loading = true;
+                        spawn_piece_map_fetch(
+                            session.clone(),
+                            torrent_info.id,
+                            file_idx,
+                            tx.clone(),
+                            stream_id,
+                        );
+                    }
+                }
+                UiMessage::PieceMapLoaded { map, stream_id } => {
+                    app.piece_map_loading = false;
+                    if stream_id == app.stream_id && map.is_some() {
+                        app.piece_map = map;
+                    }
+                }
+                UiMessage::Buffering { percent, stream_id } => {
+                    if stream_id != app.stream_id {
+                        continue;
+                    }
+                    app.buffering_percent = Some(percent);
+                }
+                UiMessage::SubtitlesMissing {
+                    languages,
+                    has_fallback,
+                    stream_id,
+                    reply,
+                } => {
+                    if stream_id != app.stream_id {
+                        continue;
+                    }
+                    app.subtitle_prompt = Some(SubtitlePrompt {
+                        stream_id,
+                        languages,
+                        has_fallback,
+                        language_input: None,
+                        reply,
+                    });
+                }
+                UiMessage::TorrentProbed {
+                    probe,
+                    stream_id,
+                    reply,
+                } => {
+                    if stream_id != app.stream_id {
+                        continue;
+                    }
+                    app.probe_prompt = Some(ProbePrompt {
+                        stream_id,
+                        probe,
+                        reply,
+                    });
+                }
+                UiMessage::PlayerLaunching {
+                    file_name,
+                    total_bytes,
+                    stream_id,
+                } => {
+                    if stream_id != app.stream_id {
+                        continue;
+                    }
+                    app.buffering_percent = None;
+                    if let Some(snapshot) = session_snapshot(
+                        app,
+                        streaming_session.as_deref(),
+                        pending_torrent_info.as_ref(),
+                    ) {
+                        session_recovery.save(&SessionSnapshot {
+                            progress_percent: 0.0,
+                            ..snapshot
+                        });
+                    }
+
+                    if config.streaming.verify_duration
+                        && let Some(tmdb_id) = app.current_tmdb_id
+                    {
+                        spawn_runtime_check(
+                            file_name.clone(),
+                            total_bytes,
+                            (tmdb_id, app.current_media_type.clone()),
+                            config.tmdb.as_ref().map(|t| t.apikey.clone()),
+                            tx.clone(),
+                        );
+                    }
+
+                    // Notify extensions
+                    let (season, episode) = parse_episode_info(&file_name);
+                    ext_manager.broadcast(PlaybackEvent::Started(MediaInfo {
+                        title: app.current_title.clone(),
+                        file_name,
+                        total_bytes,
+                        tmdb_id: app.current_tmdb_id,
+                        year: app.current_year.map(|y| y as u32),
+                        media_type: app.current_media_type.clone(),
+                        poster_url: app.current_poster_url.clone(),
+                        season,
+                        episode,
+                    }));
+                }
+                UiMessage::AudioTracksLoaded {
+                    socket,
+                    tracks,
+                    stream_id,
+                } => {
+                    // A player from a stream that has since been replaced
+                    if stream_id != app.stream_id {
+                        continue;
+                    }
+                    app.audio_tracks = tracks;
+                    // A track picked for this title before wins over the configured languages
+                    let remembered = app
+                        .current_tmdb_id
+                        .filter(|id| audio_preferences.get(*id).is_some());
+                    let preferred = match remembered {
+                        Some(tmdb_id) => audio_preferences.track_for(tmdb_id, &app.audio_tracks),
+                        None => mpv::preferred_audio_track(
+                            &app.audio_tracks,
+                            &config.player.audio_language,
+                        ),
+                    }
+                    .cloned();
+                    if let Some(track) = preferred {
+                        app.select_audio_track(track.id);
+                        spawn_audio_track_switch(socket.clone(), track, tx.clone());
+                    }
+                    if config.subtitles.hide_when_audio_matches {
+                        hide_redundant_subtitle(app, &socket, config);
+                    }
+                    app.player_ipc = Some(socket);
+                }
+                UiMessage::SubtitleChosen {
+                    subtitle,
+                    stream_id,
+                } => {
+                    if stream_id == app.stream_id {
+                        app.subtitle_status = match subtitle {
+                            Some(subtitle) => SubtitleStatus::Attached(subtitle),
+                            None => SubtitleStatus::None,
+                        };
+                    }
+                }
+                UiMessage::ChaptersLoaded { chapters, duration } => {
+                    app.chapters = chapters;
+                    app.media_duration = duration;
+                    app.update_current_chapter();
+                }
+                UiMessage::ChapterChanged(index) => {
+                    app.current_chapter = Some(index);
+                    if let Some(label) = app.current_chapter_label() {
+                        app.push_toast(label, ToastLevel::Info, ToastLevel::Info.ttl());
+                    }
+                }
+                UiMessage::PlaybackPaused(paused) => {
+                    let media = current_media_info(app);
+                    let position_percent = app.playback_progress;
+                    ext_manager.broadcast(if paused {
+                        PlaybackEvent::Paused {
+                            media,
+                            position_percent,
+                        }
+                    } else {
+                        PlaybackEvent::Resumed {
+                            media,
+                            position_percent,
+                        }
+                    });
                 }
                 UiMessage::PlaybackProgress(percent) => {
                     app.playback_progress = percent;
+                    app.update_current_chapter();
                     debug!(progress = percent, "playback position update");
+                    if app.is_streaming
+                        && let Some(snapshot) = session_snapshot(
+                            app,
+                            streaming_session.as_deref(),
+                            pending_torrent_info.as_ref(),
+                        )
+                    {
+                        session_recovery.save_throttled(&snapshot);
+                    }
+
+                    // Look for the next episode in the background, unless this torrent has it
+                    let pack_has_next = app.available_files.len() > 1 && app.has_next_episode();
+                    if percent >= PREFETCH_AT_PERCENT
+                        && prefetch_cancel.is_none()
+                        && app.is_streaming
+                        && !pack_has_next
+                        && let (Some((episode_index, episode)), Some(show)) =
+                            (app.following_tv_episode(), app.tv_details.as_ref())
+                    {
+                        let query = if app.absolute_numbering {
+                            episode.absolute_search_query(&show.name)
+                        } else {
+                            episode.search_query(&show.name)
+                        };
+                        let context = SearchContext {
+                            season: (!app.absolute_numbering).then_some(episode.season_number),
+                            episode: Some(episode.episode_number),
+                            ..app.search_context.clone()
+                        };
+                        let title = format!("{} - {}", show.name, episode.display_title());
+                        info!(query = %query, "prefetching next episode");
+
+                        prefetch_cancel = Some(spawn_episode_prefetch(
+                            query,
+                            context,
+                            title,
+                            episode_index,
+                            config,
+                            app,
+                            tx.clone(),
+                        ));
+                        app.is_prefetching = true;
+                    }
+                }
+                UiMessage::NextEpisodePrefetched(prefetched) => {
+                    app.is_prefetching = false;
+                    if prefetch_cancel
+                        .as_ref()
+                        .is_none_or(|cancel| cancel.is_cancelled())
+                    {
+                        // Discarded while racing
+                        spawn_cleanup(app, prefetched.session, &tx);
+                    } else {
+                        info!(title = %prefetched.title, "next episode ready");
+                        app.push_toast(
+                            format!("Next up: {}", prefetched.title),
+                            ToastLevel::Info,
+                            ToastLevel::Info.ttl(),
+                        );
+                        app.prefetched_episode = Some(prefetched);
+                    }
+                }
+                UiMessage::PrefetchFailed(e) => {
+                    app.is_prefetching = false;
+                    debug!(error = %e, "next episode prefetch failed");
+                }
+                UiMessage::SeedingStats { torrent, stats } => {
+                    app.record_transfer(torrent, stats.downloaded_bytes, stats.uploaded_bytes);
+                }
+                UiMessage::SeedingFinished { seed_id, name } => {
+                    seeding.retain(|s| s.id != seed_id);
+                    app.seeding_count = seeding.len();
+                    app.push_toast(
+                        format!("Finished seeding {}", name),
+                        ToastLevel::Success,
+                        ToastLevel::Success.ttl(),
+                    );
+                }
+                UiMessage::PlayerStarted { stream_id, cancel } => {
+                    if stream_id != app.stream_id {
+                        continue;
+                    }
+                    player_cancel = Some(cancel);
+                    if let StreamingState::Ready { stream_url } = &app.streaming_state {
+                        app.streaming_state = StreamingState::Playing {
+                            stream_url: stream_url.clone(),
+                        };
+                    }
+                }
+                UiMessage::PlayerExited if app.restarting_stream => {
+                    // The stalled player was closed for a restart, its replacement carries on
+                    app.restarting_stream = false;
+                    app.forget_player();
+                    let history_key = app.history_key();
+                    watch_history.update(
+                        &history_key,
+                        app.current_title.clone(),
+                        app.playback_progress,
+                    );
+                    watch_history.save();
+                }
+                UiMessage::PlayerExited if app.switching_player => {
+                    // The previous player was closed for the one picked from the player list
+                    app.switching_player = false;
+                    app.forget_player();
+                }
+                UiMessage::ExtraPlayerExited(player) => {
+                    if app.is_streaming {
+                        app.push_toast(
+                            format!("{} closed", player),
+                            ToastLevel::Info,
+                            ToastLevel::Info.ttl(),
+                        );
+                    }
                 }
                 UiMessage::PlayerExited => {
+                    app.forget_player();
+
                     // Use playback progress from mpv if available, otherwise fall back to download progress
                     let watched_percent = if app.playback_progress > 0.0 {
                         app.playback_progress
                     } else {
                         app.download_progress.progress_percent
                     };
-                    let (season, episode) = parse_episode_info(&app.current_file);
                     ext_manager.broadcast(PlaybackEvent::Stopped {
-                        media: MediaInfo {
-                            title: app.current_title.clone(),
-                            file_name: app.current_file.clone(),
-                            total_bytes: app.download_progress.total_bytes,
-                            tmdb_id: app.current_tmdb_id,
-                            year: app.current_year.map(|y| y as u32),
-                            media_type: app.current_media_type.clone(),
-                            poster_url: app.current_poster_url.clone(),
-                            season,
-                            episode,
-                        },
+                        media: current_media_info(app),
                         watched_percent,
                     });
 
+                    if config.notifications.enabled && config.notifications.on_player_exit {
+                        notifications::notify(
+                            "Playback stopped",
+                            &format!("{} ({:.0}% watched)", display_name(app), watched_percent),
+                            Urgency::Low,
+                        );
+                    }
+
+                    if let Some(command) = &config.player.post_play_command {
+                        hooks::spawn_post_play(
+                            command.clone(),
+                            PostPlay {
+                                title: app.current_title.clone(),
+                                file: app.current_file.clone(),
+                                progress_percent: watched_percent,
+                                tmdb_id: app.current_tmdb_id,
+                            },
+                        );
+                    }
+
                     // Save watch progress to history
-                    let history_key =
-                        WatchHistory::make_key(app.current_tmdb_id, &app.current_file);
-                    watch_history.update(history_key, app.current_title.clone(), watched_percent);
+                    let history_key = app.history_key();
+                    watch_history.update(&history_key, app.current_title.clone(), watched_percent);
                     watch_history.save();
+                    session_recovery.clear();
 
                     // Check if we should auto-play next episode
                     let has_next = app.has_next_episode();
@@ -900,193 +2458,88 @@ async fn run_app(
                                 stream_url: next_file.stream_url.clone(),
                             };
 
-                            // Notify extensions about new episode
-                            let (season, episode) = parse_episode_info(&next_file.name);
-                            ext_manager.broadcast(PlaybackEvent::Started(MediaInfo {
-                                title: app.current_title.clone(),
-                                file_name: next_file.name.clone(),
-                                total_bytes: next_file.size,
-                                tmdb_id: app.current_tmdb_id,
-                                year: app.current_year.map(|y| y as u32),
-                                media_type: app.current_media_type.clone(),
-                                poster_url: app.current_poster_url.clone(),
-                                season,
-                                episode,
-                            }));
-
                             // Pre-download the episode after this one
                             if let (Some(after_next), Some(session), Some(torrent_info)) = (
                                 app.next_episode(),
                                 streaming_session.as_ref(),
                                 pending_torrent_info.as_ref(),
                             ) {
-                                let after_next_idx = after_next.file_idx;
-                                let session_clone = session.clone();
-                                let torrent_id = torrent_info.id;
                                 info!(next_file = %after_next.name, "pre-downloading next episode");
-                                tokio::spawn(async move {
-                                    let _ = session_clone
-                                        .prioritize_file(torrent_id, after_next_idx)
-                                        .await;
-                                });
+                                spawn_prioritize_file(
+                                    session.clone(),
+                                    torrent_info.id,
+                                    after_next,
+                                    tx.clone(),
+                                );
                             }
 
                             // Launch player for next episode
                             if let (Some(session), Some(torrent_info)) =
                                 (streaming_session.clone(), pending_torrent_info.as_ref())
                             {
-                                let tx = tx.clone();
-                                let player_command = config.player.command.clone();
-                                let player_args = config.player.args.clone();
-                                let subtitles_enabled = config.subtitles.enabled;
-                                let preferred_language = config.subtitles.language.clone();
-                                let opensubtitles_key =
-                                    config.subtitles.opensubtitles_api_key.clone();
-                                let tmdb_id = app.current_tmdb_id;
-                                let subtitle_files = torrent_info.subtitle_files.clone();
-                                let stream_url = next_file.stream_url.clone();
-                                let torrent_id = torrent_info.id;
-                                let cancel_token = streaming_cancel.clone().unwrap_or_default();
-
-                                tokio::spawn(async move {
-                                    // Progress polling
-                                    let progress_tx = tx.clone();
-                                    let progress_session = session.clone();
-                                    let progress_handle = tokio::spawn(async move {
-                                        loop {
-                                            tokio::time::sleep(Duration::from_millis(500)).await;
-                                            if let Some(stats) =
-                                                progress_session.get_stats(torrent_id).await
-                                            {
-                                                let progress = DownloadProgress {
-                                                    downloaded_bytes: stats.downloaded_bytes,
-                                                    total_bytes: stats.total_bytes,
-                                                    download_speed: stats.download_speed,
-                                                    upload_speed: stats.upload_speed,
-                                                    peers_connected: stats.peers_connected,
-                                                    progress_percent: if stats.total_bytes > 0 {
-                                                        (stats.downloaded_bytes as f64
-                                                            / stats.total_bytes as f64)
-                                                            * 100.0
-                                                    } else {
-                                                        0.0
-                                                    },
-                                                };
-                                                if progress_tx
-                                                    .send(UiMessage::ProgressUpdate(progress))
-                                                    .await
-                                                    .is_err()
-                                                {
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                    });
-
-                                    // Find subtitle
-                                    let subtitle_url = if subtitles_enabled {
-                                        subtitle_files
-                                            .iter()
-                                            .find(|s| {
-                                                s.language.as_deref() == Some(&preferred_language)
-                                            })
-                                            .or_else(|| subtitle_files.first())
-                                            .map(|s| s.stream_url.clone())
-                                    } else {
-                                        None
-                                    };
-
-                                    // Launch player
-                                    match streaming::launch_player(
-                                        &player_command,
-                                        &player_args,
-                                        &stream_url,
-                                        subtitle_url.as_deref(),
-                                    )
-                                    .await
-                                    {
-                                        Ok(mut handle) => {
-                                            // Spawn position polling task if we have IPC
-                                            let position_handle = if let Some(ref socket_path) =
-                                                handle.ipc_socket
-                                            {
-                                                let socket = socket_path.clone();
-                                                let tx_pos = tx.clone();
-                                                Some(tokio::spawn(async move {
-                                                    tokio::time::sleep(Duration::from_secs(2))
-                                                        .await;
-                                                    loop {
-                                                        if let Some((pos, dur)) =
-                                                            streaming::get_mpv_position(&socket)
-                                                                .await
-                                                        {
-                                                            let progress =
-                                                                streaming::calculate_progress(
-                                                                    pos, dur,
-                                                                );
-                                                            let _ = tx_pos
-                                                                .send(UiMessage::PlaybackProgress(
-                                                                    progress,
-                                                                ))
-                                                                .await;
-                                                        }
-                                                        tokio::time::sleep(Duration::from_secs(5))
-                                                            .await;
-                                                    }
-                                                }))
-                                            } else {
-                                                None
-                                            };
-
-                                            tokio::select! {
-                                                _ = handle.child.wait() => {
-                                                    info!("player exited normally");
-                                                }
-                                                _ = cancel_token.cancelled() => {
-                                                    info!("cancellation requested, killing player");
-                                                    let _ = handle.child.kill().await;
-                                                }
-                                            }
-
-                                            if let Some(h) = position_handle {
-                                                h.abort();
-                                            }
-                                            if let Some(socket_path) = handle.ipc_socket {
-                                                let _ = std::fs::remove_file(socket_path);
-                                            }
-                                        }
-                                        Err(e) => {
-                                            error!(error = %e, "failed to launch player");
-                                        }
-                                    }
-
-                                    progress_handle.abort();
-                                    let _ = tx.send(UiMessage::PlayerExited).await;
-                                });
+                                PlaybackController::new(
+                                    config,
+                                    session,
+                                    streaming_cancel.clone().unwrap_or_default(),
+                                    tx.clone(),
+                                    app.progress.clone(),
+                                    app.stream_id,
+                                    app.config_location.pending_cleanup_path(),
+                                )
+                                .play(
+                                    &next_file,
+                                    torrent_info,
+                                    app.media_context(),
+                                );
                             }
                         }
                     } else if has_next && app.available_files.len() > 1 {
                         // Has next but auto-play disabled - go back to file selection
                         info!("playback ended, returning to file selection");
-                        app.selected_file_index = app.current_episode_index + 1;
+                        if let Some(next) = app.next_episode_index() {
+                            app.selected_file_index = next;
+                        }
                         app.view = View::FileSelection;
                         app.streaming_state = StreamingState::FetchingMetadata;
                     } else {
-                        // No next episode or single file - cleanup and go back
+                        // No next episode or single file - seed or cleanup and go back
                         if let Some(session) = streaming_session.take() {
-                            session.cleanup().await;
+                            let policy = config.streaming.seed_after_watch;
+                            match pending_torrent_info.as_ref() {
+                                // Usenet downloads have nobody to seed to
+                                Some(torrent_info)
+                                    if policy.is_enabled()
+                                        && !session.is_served(torrent_info.id) =>
+                                {
+                                    info!(torrent = %torrent_info.name, %policy, "seeding after playback");
+                                    next_seed_id += 1;
+                                    seeding.push(spawn_seeding(
+                                        next_seed_id,
+                                        session,
+                                        torrent_info,
+                                        policy,
+                                        app.config_location.pending_cleanup_path(),
+                                        tx.clone(),
+                                    ));
+                                    app.seeding_count = seeding.len();
+                                }
+                                _ => spawn_cleanup(app, session, &tx),
+                            }
                         }
                         pending_torrent_info = None;
                         app.available_files.clear();
                         app.current_file.clear();
                         app.current_title.clear();
                         app.racing_message = None;
-                        // Go back to Search if auto-race is enabled (user never saw Results)
-                        app.view = if config.streaming.auto_race > 0 {
-                            View::Discovery
+                        if app.prefetched_episode.is_some() {
+                            // Offer the prefetched episode instead of going back
+                            app.show_next_episode_prompt = true;
+                            app.view = View::Streaming;
                         } else {
-                            View::Results
-                        };
+                            // Too late to still be useful
+                            discard_prefetch(app, &mut prefetch_cancel, &tx);
+                            app.view = view_after_stream(app, config);
+                        }
                         app.streaming_state = StreamingState::Connecting;
                         app.is_streaming = false;
                         info!("streaming ended, ready for next");
@@ -1096,16 +2549,79 @@ async fn run_app(
         }
 
         // Handle input with timeout
-        if event::poll(Duration::from_millis(100))?
-            && let Event::Key(key) = event::read()?
-        {
+        if event::poll(Duration::from_millis(100))? {
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                // Clicking the status bar jumps back to the stream
+                Event::Mouse(mouse) => {
+                    let on_status_bar = mouse.kind == MouseEventKind::Down(MouseButton::Left)
+                        && ui::has_status_bar(&app.view)
+                        && mouse.row + 1 == terminal.size()?.height;
+                    if on_status_bar && app.is_streaming && !app.show_quit_prompt {
+                        app.view = View::Streaming;
+                    }
+                    continue;
+                }
+                Event::Paste(text) => {
+                    if !app.show_quit_prompt {
+                        handle_paste(app, &text, &tx, config);
+                    }
+                    continue;
+                }
+                // Layouts are worked out from the frame size on every draw,
+                // so resizing the buffers is all a redraw needs
+                Event::Resize(width, _) => {
+                    terminal.autoresize()?;
+                    app.compact_mode = compact_mode(config, width);
+                    // Widening past the threshold makes room for the still panel
+                    if app.view == View::TvEpisodes {
+                        spawn_episode_still_fetch(app, &tx, config);
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+
+            // Quit confirmation while seeding: y stops seeding and quits, anything else stays
+            if app.show_quit_prompt {
+                app.show_quit_prompt = false;
+                if key.code == KeyCode::Char('y') {
+                    stop_seeding(&mut seeding, &app.config_location.pending_cleanup_path()).await;
+                    app.seeding_count = 0;
+                    app.should_quit = true;
+                }
+                continue;
+            }
+
+            // Any key closes the compact mode help, '?' opens it
+            if app.show_help {
+                app.show_help = false;
+                continue;
+            }
+            if key.code == KeyCode::Char('?') && app.compact_mode && !app.is_typing() {
+                app.show_help = true;
+                continue;
+            }
+
             // Global quit
             if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
                 app.should_quit = true;
             }
 
+            // Back to the stream from any view showing the status bar
+            if key.code == KeyCode::Char('s')
+                && key.modifiers.contains(KeyModifiers::CONTROL)
+                && ui::has_status_bar(&app.view)
+            {
+                if app.is_streaming {
+                    app.view = View::Streaming;
+                }
+                continue;
+            }
+
             match app.view {
                 View::Wizard => {
+                    let step = app.wizard_step;
                     if app.wizard_editing {
                         // Text input mode
                         match key.code {
@@ -1114,10 +2630,22 @@ async fn run_app(
                                 app.wizard_edit_buffer.clear();
                             }
                             KeyCode::Enter => {
-                                // Save the field value
-                                apply_wizard_edit(app, config);
-                                app.wizard_editing = false;
-                                app.wizard_edit_buffer.clear();
+                                // Save the field value, stay in edit mode on error
+                                match apply_wizard_edit(app, config) {
+                                    Ok(()) => {
+                                        app.wizard_editing = false;
+                                        app.wizard_edit_buffer.clear();
+                                        if app.wizard_step == WizardStep::Storage {
+                                            // The temp dir may have moved
+                                            spawn_cache_size(app, config, &tx);
+                                        }
+                                    }
+                                    Err(e) => app.push_toast(
+                                        e,
+                                        ToastLevel::Error,
+                                        ToastLevel::Error.ttl(),
+                                    ),
+                                }
                             }
                             KeyCode::Backspace => {
                                 app.wizard_edit_buffer.pop();
@@ -1133,7 +2661,11 @@ async fn run_app(
                             KeyCode::Esc => {
                                 // Go back a step or quit wizard
                                 if app.wizard_step == WizardStep::Welcome {
-                                    app.should_quit = true;
+                                    if let Some(view) = app.wizard_return_to.take() {
+                                        app.view = view;
+                                    } else {
+                                        app.should_quit = true;
+                                    }
                                 } else {
                                     app.wizard_step = app.wizard_step.prev();
                                     app.wizard_field_index = 0;
@@ -1141,17 +2673,29 @@ async fn run_app(
                             }
                             KeyCode::Enter => {
                                 if app.wizard_step == WizardStep::Done {
-                                    // Finish wizard - save config and go to search
-                                    if let Err(e) = config.save() {
-                                        error!("Failed to save config: {}", e);
-                                    } else {
-                                        info!("Config saved from wizard");
+                                    // Finish wizard - save config and go to search,
+                                    // or back to wherever it was re-run from
+                                    let saved = save_config(app, config);
+                                    match app.wizard_return_to.take() {
+                                        Some(View::Settings) => {
+                                            app.view = View::Settings;
+                                            if saved {
+                                                app.settings_saved = Some(config.clone());
+                                                app.settings_dirty = false;
+                                            }
+                                        }
+                                        Some(view) => app.view = view,
+                                        None => app.view = View::Discovery,
                                     }
-                                    app.view = View::Discovery;
                                 } else if app.wizard_field_count() == 0 {
                                     // No fields (Welcome) - just advance
                                     app.wizard_step = app.wizard_step.next();
                                     app.wizard_field_index = 0;
+                                } else if let Some(player) = (app.wizard_step == WizardStep::Player)
+                                    .then(|| app.detected_players().get(app.wizard_field_index))
+                                    .flatten()
+                                {
+                                    use_detected_player(config, player);
                                 } else {
                                     // Start editing current field
                                     let current_value = get_wizard_field_value(app, config);
@@ -1177,13 +2721,72 @@ async fn run_app(
                             _ => {}
                         }
                     }
+                    if app.wizard_step != step {
+                        match app.wizard_step {
+                            WizardStep::Player => {
+                                spawn_player_detection(app, &tx);
+                                app.wizard_field_index =
+                                    app.player_option_index(&config.player.command);
+                            }
+                            WizardStep::Storage => spawn_cache_size(app, config, &tx),
+                            _ => {}
+                        }
+                    }
                 }
 
+                View::Discovery if app.recovery_prompt.is_some() => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => {
+                        if let Some(snapshot) = app.recovery_prompt.take() {
+                            recover_stream(app, snapshot, &tx, config, &mut streaming_cancel);
+                        }
+                    }
+                    KeyCode::Char('n') | KeyCode::Esc => {
+                        app.recovery_prompt = None;
+                        session_recovery.clear();
+                    }
+                    _ => {}
+                },
+
+                View::Discovery if app.is_filtering_discovery => match key.code {
+                    KeyCode::Esc => {
+                        app.clear_discovery_filter();
+                    }
+                    KeyCode::Enter => {
+                        app.is_filtering_discovery = false;
+                        if app.discovery_filter.is_empty() {
+                            // Closed without typing anything
+                        } else if let Some(item) = app.single_discovery_match().cloned() {
+                            app.clear_discovery_filter();
+                            open_discovery_item(app, item, &tx, config);
+                        } else if app.discovery_rows.is_empty() {
+                            // Not in discovery, search for it instead
+                            app.search_input = std::mem::take(&mut app.discovery_filter);
+                            app.clear_discovery_filter();
+                            app.view = View::Search;
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        app.discovery_filter.pop();
+                        app.apply_discovery_filter();
+                    }
+                    KeyCode::Char(c) => {
+                        app.discovery_filter.push(c);
+                        app.apply_discovery_filter();
+                    }
+                    _ => {}
+                },
+
                 View::Discovery => match key.code {
+                    KeyCode::Esc if !app.discovery_filter.is_empty() => {
+                        app.clear_discovery_filter();
+                    }
                     KeyCode::Char('q') | KeyCode::Esc => {
                         app.should_quit = true;
                     }
                     KeyCode::Char('/') => {
+                        app.is_filtering_discovery = true;
+                    }
+                    KeyCode::Char('f') => {
                         app.view = View::Search;
                         app.search_input.clear();
                     }
@@ -1191,8 +2794,23 @@ async fn run_app(
                         load_discovery_data(&tx, config);
                         app.is_loading_discovery = true;
                     }
+                    KeyCode::Char('p') if config.search.discovery_refresh_interval().is_some() => {
+                        app.discovery_refresh_paused = !app.discovery_refresh_paused;
+                        let message = if app.discovery_refresh_paused {
+                            "Discovery auto-refresh paused"
+                        } else {
+                            "Discovery auto-refresh resumed"
+                        };
+                        app.push_toast(message, ToastLevel::Info, ToastLevel::Info.ttl());
+                    }
                     KeyCode::Char('s') => {
-                        app.view = View::Settings;
+                        open_settings(app, config, &tx);
+                    }
+                    KeyCode::Char('e') => {
+                        app.view = View::HistoryExport;
+                        app.history_export_path = WatchHistory::default_export_path();
+                        app.history_export_count = watch_history.len();
+                        app.history_export_result = None;
                     }
                     KeyCode::Char('d') => {
                         app.view = View::Doctor;
@@ -1220,45 +2838,57 @@ async fn run_app(
                     }
                     KeyCode::Enter if !app.is_loading_discovery => {
                         if let Some(item) = app.selected_discovery_item().cloned() {
-                            // Set metadata
-                            app.current_title = item.title.clone();
-                            app.current_tmdb_id = Some(item.id);
-                            app.current_year = item.year;
-                            app.current_media_type = Some(item.media_type.clone());
-                            app.current_poster_url = item.poster_url.clone();
-
-                            // If TV show, go to season browser
-                            if item.media_type == "tv" {
-                                app.is_fetching_tv_details = true;
-                                spawn_tv_details_fetch(
-                                    item.id,
-                                    tx.clone(),
-                                    config.tmdb.as_ref().map(|t| t.apikey.clone()),
-                                );
-                            } else {
-                                // Movie - start torrent search
-                                let search_query = if let Some(year) = item.year {
-                                    format!("{} {}", item.title, year)
-                                } else {
-                                    item.title.clone()
-                                };
-
-                                app.search_id += 1;
-                                app.is_searching = true;
-                                app.search_input = search_query.clone();
-                                app.search_error = None;
+                            open_discovery_item(app, item, &tx, config);
+                        }
+                    }
+                    KeyCode::Char('n') if !app.is_streaming => {
+                        if let Some(result) = app.rss_queue.pop_front() {
+                            discard_prefetch(app, &mut prefetch_cancel, &tx);
+                            start_rss_stream(app, result, &tx, config, &mut streaming_cancel);
+                        }
+                    }
+                    _ => {}
+                },
 
-                                spawn_torrent_search(
-                                    search_query,
-                                    app.search_id,
-                                    tx.clone(),
-                                    config.prowlarr.url.clone(),
-                                    config.prowlarr.apikey.clone(),
-                                );
+                View::PersonFilmography => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => {
+                        app.view = View::Search;
+                    }
+                    KeyCode::Char('/') => {
+                        app.view = View::Search;
+                        app.search_input.clear();
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        app.select_previous_credit();
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        app.select_next_credit();
+                    }
+                    KeyCode::Enter if !app.is_fetching_person && !app.is_fetching_tv_details => {
+                        if let Some(item) = app.selected_credit().cloned() {
+                            open_discovery_item(app, item, &tx, config);
+                        }
+                    }
+                    _ => {}
+                },
 
-                                // Navigate to Results view
-                                app.view = View::Results;
-                            }
+                View::Collection => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => {
+                        app.view = View::Results;
+                    }
+                    KeyCode::Char('/') => {
+                        app.view = View::Search;
+                        app.search_input.clear();
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        app.select_previous_collection_item();
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        app.select_next_collection_item();
+                    }
+                    KeyCode::Enter if !app.is_fetching_collection && !app.is_searching => {
+                        if let Some(item) = app.selected_collection_item().cloned() {
+                            open_discovery_item(app, item, &tx, config);
                         }
                     }
                     _ => {}
@@ -1276,17 +2906,71 @@ async fn run_app(
                         let selected_tv = app
                             .suggestions
                             .get(app.selected_suggestion)
-                            .filter(|s| s.media_type == "tv")
+                            .filter(|s| s.is_series())
+                            .cloned();
+
+                        let person_query = parse_person_query(&app.search_input)
+                            .map(|(role, name)| (role, name.to_string()));
+                        let selected_person = app
+                            .suggestions
+                            .get(app.selected_suggestion)
+                            .filter(|s| s.media_type == "person")
                             .cloned();
 
-                        if let Some(suggestion) = selected_tv {
+                        if let Some(source) = streaming::direct_torrent_source(&app.search_input) {
+                            // Magnet or .torrent - nothing to search for
+                            if let Err(e) = streaming::check_torrent_source(&source) {
+                                app.search_error = Some(e.to_string());
+                            } else {
+                                app.search_input.clear();
+                                app.suggestions.clear();
+                                start_direct_stream(
+                                    app,
+                                    source,
+                                    &tx,
+                                    config,
+                                    &mut streaming_cancel,
+                                );
+                            }
+                        } else if let Some((role, name)) = person_query.or_else(|| {
+                            selected_person
+                                .as_ref()
+                                .map(|person| (PersonRole::Any, person.title.clone()))
+                        }) {
+                            // A person picked or an actor:/director: query - show their filmography
+                            let (person_id, name) = match selected_person {
+                                Some(person) => (Some(person.id), person.title),
+                                None => (None, name),
+                            };
+                            if name.is_empty() {
+                                app.search_error =
+                                    Some("Enter a name after actor: or director:".to_string());
+                            } else {
+                                app.person_name = name.clone();
+                                app.person_credits.clear();
+                                app.selected_credit_index = 0;
+                                app.is_fetching_person = true;
+                                app.search_error = None;
+                                app.suggestions.clear();
+                                app.view = View::PersonFilmography;
+
+                                info!(name = %name, "fetching person credits");
+                                spawn_person_credits_fetch(
+                                    person_id,
+                                    name,
+                                    role,
+                                    tx.clone(),
+                                    config.tmdb.as_ref().map(|t| t.apikey.clone()),
+                                );
+                            }
+                        } else if let Some(suggestion) = selected_tv {
                             // TV show selected - go to episode browser
                             let tv_id = suggestion.id;
                             let tv_title = suggestion.title.clone();
 
                             app.current_title = tv_title;
                             app.current_tmdb_id = Some(tv_id);
-                            app.current_media_type = Some("tv".to_string());
+                            app.current_media_type = Some(suggestion.media_type.clone());
                             app.is_fetching_tv_details = true;
                             app.suggestions.clear();
                             app.search_input.clear();
@@ -1304,32 +2988,42 @@ async fn run_app(
                             app.is_searching = true;
                             app.search_error = None;
                             app.tmdb_info = None;
-                            let query = app.search_input.clone();
+                            app.tmdb_info_guessed = false;
+                            let query =
+                                transform_search_query(app, config, app.search_input.clone());
+                            let context = SearchContext::from_query(&query);
                             let current_search_id = app.search_id;
                             let tx = tx.clone();
-                            let prowlarr_url = config.prowlarr.url.clone();
-                            let prowlarr_apikey = config.prowlarr.apikey.clone();
                             let tmdb_apikey = config.tmdb.as_ref().map(|t| t.apikey.clone());
 
                             // Spawn TMDB lookup task in parallel
                             let tmdb_tx = tx.clone();
                             let tmdb_query = query.clone();
+                            let tmdb_apikey =
+                                tmdb_apikey.filter(|_| app.media_mode != MediaMode::Audio);
                             tokio::spawn(async move {
                                 if let Some(client) = TmdbClient::new(tmdb_apikey.as_deref()) {
                                     debug!(query = %tmdb_query, "looking up TMDB info");
                                     if let Ok(results) = client.search_multi(&tmdb_query).await
                                         && let Some(first) = results.first()
                                     {
-                                        let info = TmdbMetadata {
-                                            id: Some(first.id),
-                                            title: first.display_title().to_string(),
-                                            year: first.year(),
-                                            overview: first.overview.clone(),
-                                            rating: first.vote_average,
-                                            media_type: first.media_type.clone(),
-                                            poster_url: first.poster_url("w500"),
-                                        };
+                                        let info = TmdbMetadata::from(first);
                                         let _ = tmdb_tx.send(UiMessage::TmdbInfo(info)).await;
+
+                                        let candidates: Vec<TmdbMetadata> =
+                                            tmdb::ambiguous_matches(&results)
+                                                .into_iter()
+                                                .map(TmdbMetadata::from)
+                                                .collect();
+                                        if !candidates.is_empty() {
+                                            let _ = tmdb_tx
+                                                .send(UiMessage::TmdbAmbiguous {
+                                                    search_id: current_search_id,
+                                                    query: tmdb_query,
+                                                    candidates,
+                                                })
+                                                .await;
+                                        }
                                     }
                                 }
                             });
@@ -1337,10 +3031,12 @@ async fn run_app(
                             // Spawn torrent search task
                             spawn_torrent_search(
                                 query,
+                                context,
                                 current_search_id,
+                                app.media_mode,
                                 tx.clone(),
-                                prowlarr_url,
-                                prowlarr_apikey,
+                                app.search_service.clone(),
+                                app.indexer_health.clone(),
                             );
                         }
                     }
@@ -1357,8 +3053,7 @@ async fn run_app(
                         });
                     }
                     KeyCode::Char('s') if app.search_input.is_empty() && !app.is_searching => {
-                        // Open settings view
-                        app.view = View::Settings;
+                        open_settings(app, config, &tx);
                         app.settings_section = SettingsSection::default();
                     }
                     KeyCode::Tab if !app.suggestions.is_empty() => {
@@ -1372,6 +3067,11 @@ async fn run_app(
                             app.suggestions.clear();
                         }
                     }
+                    KeyCode::Tab if !app.is_searching => {
+                        app.media_mode = app.media_mode.next();
+                        app.suggestions.clear();
+                        spawn_suggestion_fetch(app, &tx, config);
+                    }
                     KeyCode::Down if !app.suggestions.is_empty() => {
                         app.selected_suggestion =
                             (app.selected_suggestion + 1).min(app.suggestions.len() - 1);
@@ -1384,30 +3084,7 @@ async fn run_app(
                         app.suggestions.clear();
 
                         // Fetch suggestions if input is long enough
-                        if app.search_input.len() >= 3 {
-                            let tx = tx.clone();
-                            let query = app.search_input.clone();
-                            let tmdb_apikey = config.tmdb.as_ref().map(|t| t.apikey.clone());
-                            app.is_fetching_suggestions = true;
-
-                            tokio::spawn(async move {
-                                if let Some(client) = TmdbClient::new(tmdb_apikey.as_deref())
-                                    && let Ok(results) = client.search_multi(&query).await
-                                {
-                                    let suggestions: Vec<TmdbSuggestion> = results
-                                        .into_iter()
-                                        .take(5)
-                                        .map(|r| TmdbSuggestion {
-                                            id: r.id,
-                                            title: r.display_title().to_string(),
-                                            year: r.year(),
-                                            media_type: r.media_type.unwrap_or_default(),
-                                        })
-                                        .collect();
-                                    let _ = tx.send(UiMessage::Suggestions(suggestions)).await;
-                                }
-                            });
-                        }
+                        spawn_suggestion_fetch(app, &tx, config);
                     }
                     KeyCode::Backspace if !app.is_searching => {
                         app.search_input.pop();
@@ -1415,30 +3092,7 @@ async fn run_app(
                         app.selected_suggestion = 0;
 
                         // Fetch suggestions if input is still long enough
-                        if app.search_input.len() >= 3 {
-                            let tx = tx.clone();
-                            let query = app.search_input.clone();
-                            let tmdb_apikey = config.tmdb.as_ref().map(|t| t.apikey.clone());
-                            app.is_fetching_suggestions = true;
-
-                            tokio::spawn(async move {
-                                if let Some(client) = TmdbClient::new(tmdb_apikey.as_deref())
-                                    && let Ok(results) = client.search_multi(&query).await
-                                {
-                                    let suggestions: Vec<TmdbSuggestion> = results
-                                        .into_iter()
-                                        .take(5)
-                                        .map(|r| TmdbSuggestion {
-                                            id: r.id,
-                                            title: r.display_title().to_string(),
-                                            year: r.year(),
-                                            media_type: r.media_type.unwrap_or_default(),
-                                        })
-                                        .collect();
-                                    let _ = tx.send(UiMessage::Suggestions(suggestions)).await;
-                                }
-                            });
-                        }
+                        spawn_suggestion_fetch(app, &tx, config);
                     }
                     _ => {}
                 },
@@ -1494,16 +3148,74 @@ async fn run_app(
                     }
                     KeyCode::Up | KeyCode::Char('k') if !app.is_searching => {
                         app.select_previous_episode();
+                        spawn_episode_still_fetch(app, &tx, config);
                     }
                     KeyCode::Down | KeyCode::Char('j') if !app.is_searching => {
                         app.select_next_episode();
+                        spawn_episode_still_fetch(app, &tx, config);
+                    }
+                    KeyCode::Char('w') if !app.is_searching => {
+                        // Mark as watched (e.g. seen elsewhere), or unmark
+                        let episode = app.selected_tv_episode().cloned();
+                        let key = episode.as_ref().and_then(|ep| app.episode_history_key(ep));
+                        if let (Some(episode), Some(key)) = (episode, key) {
+                            if watch_history.is_finished(&key, FINISHED_PERCENT) {
+                                watch_history.clear(&key);
+                            } else {
+                                let show = app.tv_details.as_ref().map(|d| d.name.as_str());
+                                let title = format!(
+                                    "{} - {}",
+                                    show.unwrap_or_default(),
+                                    episode.display_title()
+                                );
+                                watch_history.update(&key, title, 100.0);
+                            }
+                            watch_history.save();
+                            refresh_episode_progress(app, &watch_history);
+                        }
+                    }
+                    KeyCode::Char('a') if !app.is_searching => {
+                        if app.select_next_unaired_episode(tmdb::today()) {
+                            spawn_episode_still_fetch(app, &tx, config);
+                        } else {
+                            app.push_toast(
+                                "No upcoming episodes this season".to_string(),
+                                ToastLevel::Info,
+                                ToastLevel::Info.ttl(),
+                            );
+                        }
+                    }
+                    KeyCode::Enter
+                        if !app.is_searching
+                            && !config.search.allow_unaired
+                            && app.selected_tv_episode().is_some_and(|ep| {
+                                matches!(
+                                    ep.air_status(tmdb::today()),
+                                    tmdb::AirStatus::Upcoming { .. }
+                                )
+                            }) =>
+                    {
+                        // Nothing to find yet
+                        if let Some(episode) = app.selected_tv_episode() {
+                            let message = format!(
+                                "{} hasn't aired yet ({}) - set search.allow_unaired to search anyway",
+                                episode.display_title(),
+                                episode.air_date.as_deref().unwrap_or_default()
+                            );
+                            app.push_toast(message, ToastLevel::Warning, ToastLevel::Warning.ttl());
+                        }
                     }
                     KeyCode::Enter if !app.is_searching => {
                         // Search for this episode
                         if let (Some(episode), Some(tv_details)) =
                             (app.selected_tv_episode().cloned(), app.tv_details.clone())
                         {
-                            let query = episode.search_query(&tv_details.name);
+                            let query = if app.absolute_numbering {
+                                episode.absolute_search_query(&tv_details.name)
+                            } else {
+                                episode.search_query(&tv_details.name)
+                            };
+                            let query = transform_search_query(app, config, query);
                             info!(query = %query, "searching for episode");
 
                             app.search_id += 1; // Increment to invalidate any in-flight searches
@@ -1515,66 +3227,62 @@ async fn run_app(
                                 .first_air_date
                                 .as_ref()
                                 .and_then(|d| d.split('-').next()?.parse().ok());
-                            app.current_media_type = Some("tv".to_string());
+                            if app.current_media_type.as_deref() != Some("anime") {
+                                app.current_media_type = Some("tv".to_string());
+                            }
 
-                            let current_search_id = app.search_id;
-                            let tx = tx.clone();
-                            let prowlarr_url = config.prowlarr.url.clone();
-                            let prowlarr_apikey = config.prowlarr.apikey.clone();
+                            let context = SearchContext {
+                                title: tv_details.name.clone(),
+                                year: app.current_year,
+                                season: (!app.absolute_numbering).then_some(episode.season_number),
+                                episode: Some(episode.episode_number),
+                                media_type: app.current_media_type.clone(),
+                                tmdb_id: Some(tv_details.id),
+                                imdb_id: None,
+                            };
+                            app.search_input = query.clone();
 
-                            tokio::spawn(async move {
-                                let prowlarr_config = crate::config::ProwlarrConfig {
-                                    url: prowlarr_url,
-                                    apikey: prowlarr_apikey,
-                                };
-                                let prowlarr = ProwlarrClient::new(&prowlarr_config);
-                                let torznab = TorznabClient::new();
-
-                                match prowlarr.get_usable_indexers().await {
-                                    Ok(indexers) => {
-                                        let mut all_results = Vec::new();
-                                        for indexer in &indexers {
-                                            if let Ok(results) = torznab
-                                                .search(
-                                                    &prowlarr_config.url,
-                                                    &prowlarr_config.apikey,
-                                                    indexer.id,
-                                                    &indexer.name,
-                                                    &query,
-                                                    Some(VIDEO_CATEGORIES),
-                                                )
-                                                .await
-                                            {
-                                                all_results.extend(results);
-                                            }
-                                        }
-                                        // Filter for streamable and sort by seeders
-                                        let mut streamable: Vec<_> = all_results
-                                            .into_iter()
-                                            .filter(|r| r.is_streamable())
-                                            .collect();
-                                        streamable.sort_by(|a, b| {
-                                            b.seeders.unwrap_or(0).cmp(&a.seeders.unwrap_or(0))
-                                        });
-                                        let _ = tx
-                                            .send(UiMessage::SearchComplete {
-                                                results: streamable,
-                                                search_id: current_search_id,
-                                            })
-                                            .await;
-                                    }
-                                    Err(e) => {
-                                        let _ =
-                                            tx.send(UiMessage::SearchError(e.to_string())).await;
-                                    }
-                                }
-                            });
+                            spawn_torrent_search(
+                                query,
+                                context,
+                                app.search_id,
+                                app.media_mode,
+                                tx.clone(),
+                                app.search_service.clone(),
+                                app.indexer_health.clone(),
+                            );
                         }
                     }
                     _ => {}
                 },
 
                 View::Results => match key.code {
+                    KeyCode::Up | KeyCode::Char('k') if app.current_tmdb_prompt().is_some() => {
+                        app.select_previous_tmdb_candidate();
+                    }
+                    KeyCode::Down | KeyCode::Char('j') if app.current_tmdb_prompt().is_some() => {
+                        app.select_next_tmdb_candidate();
+                    }
+                    KeyCode::Enter | KeyCode::Esc if app.current_tmdb_prompt().is_some() => {
+                        // Esc keeps the match already in use
+                        if let Some(prompt) = app.tmdb_prompt.take() {
+                            let choice = if key.code == KeyCode::Enter {
+                                prompt.candidates.get(app.selected_tmdb_candidate).cloned()
+                            } else {
+                                app.tmdb_info.clone()
+                            };
+                            if let Some(choice) = choice {
+                                if let Some(id) = choice.id {
+                                    app.tmdb_choices
+                                        .insert(App::tmdb_choice_key(&prompt.query), id);
+                                }
+                                if choice.id != app.tmdb_info.as_ref().and_then(|t| t.id) {
+                                    choose_tmdb_match(app, choice, &tx, config);
+                                }
+                            }
+                        }
+                    }
+                    _ if app.current_tmdb_prompt().is_some() => {}
                     KeyCode::Char('q') | KeyCode::Esc => {
                         app.view = View::Discovery;
                     }
@@ -1585,90 +3293,132 @@ async fn run_app(
                     KeyCode::Char('s') => {
                         app.cycle_sort();
                     }
+                    KeyCode::Char('m') => {
+                        app.cycle_media_filter();
+                    }
+                    KeyCode::Char('y') => {
+                        // For opening in another client
+                        let link = app
+                            .selected_result()
+                            .and_then(|r| r.magnet_url.clone().or_else(|| r.link.clone()));
+                        match link {
+                            Some(link) => copy_with_toast(app, &link),
+                            None => app.push_toast(
+                                "No magnet or torrent link",
+                                ToastLevel::Warning,
+                                ToastLevel::Warning.ttl(),
+                            ),
+                        }
+                    }
+                    KeyCode::Char('Y') if !app.results_query.is_empty() => {
+                        let query = app.results_query.clone();
+                        copy_with_toast(app, &query);
+                    }
+                    KeyCode::Char('f') if !app.hidden_results.is_empty() => {
+                        app.toggle_hidden_results();
+                    }
+                    KeyCode::Char('g') => {
+                        app.toggle_release_grouping();
+                    }
+                    KeyCode::Char('c') => {
+                        if let Some(collection) = app.current_collection().cloned() {
+                            app.view = View::Collection;
+                            app.is_fetching_collection = true;
+                            app.collection_name = collection.name;
+                            app.collection_items.clear();
+                            app.selected_collection_index = 0;
+                            spawn_collection_fetch(
+                                collection.id,
+                                tx.clone(),
+                                config.tmdb.as_ref().map(|t| t.apikey.clone()),
+                            );
+                        }
+                    }
                     KeyCode::Up | KeyCode::Char('k') => {
                         app.select_previous();
                     }
                     KeyCode::Down | KeyCode::Char('j') => {
                         app.select_next();
                     }
+                    KeyCode::Char(' ') if !app.is_streaming => {
+                        app.toggle_marked_result();
+                    }
+                    KeyCode::Char('R') if !app.is_streaming && !app.marked_results.is_empty() => {
+                        // Race exactly the picked torrents, all at once
+                        if let Some(cancel) = streaming_cancel.take() {
+                            cancel.cancel();
+                        }
+                        discard_prefetch(app, &mut prefetch_cancel, &tx);
+                        if let Some(session) = streaming_session.take() {
+                            spawn_cleanup(app, session, &tx);
+                        }
+                        pending_torrent_info = None;
+                        if app.tmdb_info_guessed {
+                            // Guessed from the highlighted result, not these
+                            app.tmdb_info = None;
+                            app.tmdb_info_guessed = false;
+                        }
+
+                        let urls = app.marked_urls();
+                        info!(count = urls.len(), "racing picked torrents");
+                        app.current_tmdb_id = app.tmdb_info.as_ref().and_then(|t| t.id);
+                        app.current_year = app.tmdb_info.as_ref().and_then(|t| t.year);
+                        app.current_media_type =
+                            app.tmdb_info.as_ref().and_then(|t| t.media_type.clone());
+                        app.current_poster_url =
+                            app.tmdb_info.as_ref().and_then(|t| t.poster_url.clone());
+                        let description = if urls.len() == 1 {
+                            "1 picked torrent".to_string()
+                        } else {
+                            format!("{} picked torrents", urls.len())
+                        };
+                        let concurrent = urls.len();
+                        start_race(
+                            app,
+                            urls,
+                            concurrent,
+                            description,
+                            &tx,
+                            config,
+                            &mut streaming_cancel,
+                        );
+                    }
+                    KeyCode::Enter if app.toggle_selected_group() => {}
                     KeyCode::Enter if !app.is_streaming => {
+                        discard_prefetch(app, &mut prefetch_cancel, &tx);
                         if let Some(result) = app.selected_result()
-                            && let Some(url) = result.get_torrent_url()
+                            && let Some(source) =
+                                StreamSource::for_result(result, config.streaming.probe_peers)
                         {
                             info!(title = %result.title, "starting stream");
+                            let title = result.title.clone();
+                            if app.tmdb_info.is_none()
+                                && app.media_mode != MediaMode::Audio
+                                && !app.enrichment_cache.contains_key(&title)
+                            {
+                                // Look it up now, the ids are filled in when it's back
+                                if app.enrichment_in_flight.insert(title.clone()) {
+                                    spawn_result_enrichment(
+                                        title.clone(),
+                                        tx.clone(),
+                                        config.tmdb.as_ref().map(|t| t.apikey.clone()),
+                                    );
+                                }
+                                app.enrichment_started = Some(title.clone());
+                            }
                             // Use TMDB title if available, otherwise torrent title
                             app.current_title = app
                                 .tmdb_info
                                 .as_ref()
                                 .map(|t| t.title.clone())
-                                .unwrap_or_else(|| result.title.clone());
+                                .unwrap_or(title);
                             app.current_tmdb_id = app.tmdb_info.as_ref().and_then(|t| t.id);
                             app.current_year = app.tmdb_info.as_ref().and_then(|t| t.year);
                             app.current_media_type =
                                 app.tmdb_info.as_ref().and_then(|t| t.media_type.clone());
                             app.current_poster_url =
                                 app.tmdb_info.as_ref().and_then(|t| t.poster_url.clone());
-                            app.view = View::Streaming;
-                            app.streaming_state = StreamingState::Connecting;
-                            app.download_progress = DownloadProgress::default();
-                            app.is_streaming = true;
-
-                            let tx = tx.clone();
-                            let temp_dir = config.storage.temp_dir();
-
-                            // Create cancellation token
-                            let cancel_token = CancellationToken::new();
-                            streaming_cancel = Some(cancel_token.clone());
-
-                            // Phase 1: Create session and add torrent
-                            tokio::spawn(async move {
-                                if cancel_token.is_cancelled() {
-                                    info!("streaming cancelled before start");
-                                    let _ = tx.send(UiMessage::PlayerExited).await;
-                                    return;
-                                }
-                                info!("creating streaming session");
-                                let session = match StreamingSession::new(temp_dir).await {
-                                    Ok(s) => {
-                                        info!("session created");
-                                        std::sync::Arc::new(s)
-                                    }
-                                    Err(e) => {
-                                        error!(error = %e, "failed to create session");
-                                        let _ =
-                                            tx.send(UiMessage::StreamError(e.to_string())).await;
-                                        return;
-                                    }
-                                };
-
-                                if cancel_token.is_cancelled() {
-                                    info!("streaming cancelled");
-                                    session.cleanup().await;
-                                    let _ = tx.send(UiMessage::PlayerExited).await;
-                                    return;
-                                }
-                                info!("adding torrent");
-                                let torrent_info = match session.add_torrent(&url).await {
-                                    Ok(info) => {
-                                        info!(files = info.video_files.len(), "torrent added");
-                                        info
-                                    }
-                                    Err(e) => {
-                                        error!(error = %e, "failed to add torrent");
-                                        let _ =
-                                            tx.send(UiMessage::StreamError(e.to_string())).await;
-                                        return;
-                                    }
-                                };
-
-                                // Send metadata to UI - it will decide whether to show file selection
-                                let _ = tx
-                                    .send(UiMessage::TorrentMetadata {
-                                        torrent_info,
-                                        session,
-                                    })
-                                    .await;
-                            });
+                            start_stream(app, source, &tx, config, &mut streaming_cancel);
                         }
                     }
                     _ => {}
@@ -1681,11 +3431,11 @@ async fn run_app(
                             cancel.cancel();
                         }
                         if let Some(session) = streaming_session.take() {
-                            session.cleanup().await;
+                            spawn_cleanup(app, session, &tx);
                         }
                         pending_torrent_info = None;
                         app.available_files.clear();
-                        app.view = View::Results;
+                        app.view = view_after_stream(app, config);
                         app.is_streaming = false;
                     }
                     KeyCode::Up | KeyCode::Char('k') => {
@@ -1694,6 +3444,17 @@ async fn run_app(
                     KeyCode::Down | KeyCode::Char('j') => {
                         app.select_next_file();
                     }
+                    KeyCode::Char('x') if !app.extra_files.is_empty() => {
+                        app.toggle_extra_files();
+                    }
+                    KeyCode::Char('S') => {
+                        app.toggle_shuffle();
+                        info!(shuffle = app.shuffle_episodes, "toggled shuffle");
+                    }
+                    KeyCode::Char('L') if app.shuffle_episodes => {
+                        app.loop_shuffle = !app.loop_shuffle;
+                        info!(loop_shuffle = app.loop_shuffle, "toggled shuffle loop");
+                    }
                     KeyCode::Enter => {
                         // User selected a file - launch player
                         if let (Some(file), Some(session), Some(torrent_info)) = (
@@ -1704,211 +3465,257 @@ async fn run_app(
                             info!(file = %file.name, "user selected file");
                             app.current_file = file.name.clone();
                             app.current_episode_index = app.selected_file_index;
+                            app.played_episode_indices.insert(app.current_episode_index);
                             app.streaming_state = StreamingState::Ready {
                                 stream_url: file.stream_url.clone(),
                             };
                             app.view = View::Streaming;
 
-                            // Notify extensions
-                            let (season, episode) = parse_episode_info(&file.name);
-                            ext_manager.broadcast(PlaybackEvent::Started(MediaInfo {
-                                title: app.current_title.clone(),
-                                file_name: file.name.clone(),
-                                total_bytes: file.size,
-                                tmdb_id: app.current_tmdb_id,
-                                year: app.current_year.map(|y| y as u32),
-                                media_type: app.current_media_type.clone(),
-                                poster_url: app.current_poster_url.clone(),
-                                season,
-                                episode,
-                            }));
-
                             // Pre-download next episode if available
                             if let Some(next_file) = app.next_episode() {
-                                let next_file_idx = next_file.file_idx;
-                                let session_clone = session.clone();
-                                let torrent_id_clone = torrent_info.id;
                                 info!(
                                     next_file = %next_file.name,
                                     "pre-downloading next episode"
                                 );
-                                tokio::spawn(async move {
-                                    let _ = session_clone
-                                        .prioritize_file(torrent_id_clone, next_file_idx)
-                                        .await;
-                                });
+                                spawn_prioritize_file(
+                                    session.clone(),
+                                    torrent_info.id,
+                                    next_file,
+                                    tx.clone(),
+                                );
                                 app.next_episode_ready = true;
                             }
 
                             // Launch player task
-                            let tx = tx.clone();
-                            let player_command = config.player.command.clone();
-                            let player_args = config.player.args.clone();
-                            let subtitles_enabled = config.subtitles.enabled;
-                            let preferred_language = config.subtitles.language.clone();
-                            let opensubtitles_key = config.subtitles.opensubtitles_api_key.clone();
-                            let tmdb_id = app.current_tmdb_id;
-                            let subtitle_files = torrent_info.subtitle_files.clone();
-                            let stream_url = file.stream_url.clone();
-                            let torrent_id = torrent_info.id;
-                            let cancel_token = streaming_cancel.clone().unwrap_or_default();
-
-                            tokio::spawn(async move {
-                                // Spawn progress polling task
-                                let progress_tx = tx.clone();
-                                let progress_session = session.clone();
-                                let progress_handle = tokio::spawn(async move {
-                                    loop {
-                                        tokio::time::sleep(Duration::from_millis(500)).await;
-                                        if let Some(stats) =
-                                            progress_session.get_stats(torrent_id).await
-                                        {
-                                            let progress = DownloadProgress {
-                                                downloaded_bytes: stats.downloaded_bytes,
-                                                total_bytes: stats.total_bytes,
-                                                download_speed: stats.download_speed,
-                                                upload_speed: stats.upload_speed,
-                                                peers_connected: stats.peers_connected,
-                                                progress_percent: if stats.total_bytes > 0 {
-                                                    (stats.downloaded_bytes as f64
-                                                        / stats.total_bytes as f64)
-                                                        * 100.0
-                                                } else {
-                                                    0.0
-                                                },
-                                            };
-                                            if progress_tx
-                                                .send(UiMessage::ProgressUpdate(progress))
-                                                .await
-                                                .is_err()
-                                            {
-                                                break;
-                                            }
-                                        }
-                                    }
-                                });
-
-                                // Find best subtitle
-                                let subtitle_url = if subtitles_enabled {
-                                    let from_torrent = subtitle_files
-                                        .iter()
-                                        .find(|s| {
-                                            s.language
-                                                .as_ref()
-                                                .map(|l| l == &preferred_language)
-                                                .unwrap_or(false)
-                                        })
-                                        .or_else(|| subtitle_files.first())
-                                        .map(|s| s.stream_url.clone());
-
-                                    if from_torrent.is_some() {
-                                        from_torrent
-                                    } else if let (Some(api_key), Some(tmdb)) =
-                                        (&opensubtitles_key, tmdb_id)
-                                    {
-                                        info!("no subtitles in torrent, trying OpenSubtitles");
-                                        let os_client = OpenSubtitlesClient::new(api_key);
-                                        match os_client
-                                            .search_by_tmdb(tmdb, &preferred_language)
-                                            .await
-                                        {
-                                            Ok(subs) => {
-                                                subs.first().map(|s| s.download_url.clone())
-                                            }
-                                            Err(e) => {
-                                                debug!(error = %e, "OpenSubtitles search failed");
-                                                None
-                                            }
-                                        }
-                                    } else {
-                                        None
-                                    }
-                                } else {
-                                    None
-                                };
-
-                                if cancel_token.is_cancelled() {
-                                    progress_handle.abort();
-                                    session.cleanup().await;
-                                    let _ = tx.send(UiMessage::PlayerExited).await;
-                                    return;
-                                }
-
-                                info!(player = %player_command, "launching player");
-                                match streaming::launch_player(
-                                    &player_command,
-                                    &player_args,
-                                    &stream_url,
-                                    subtitle_url.as_deref(),
-                                )
-                                .await
-                                {
-                                    Ok(mut handle) => {
-                                        // Spawn position polling task if we have IPC
-                                        let position_handle = if let Some(ref socket_path) =
-                                            handle.ipc_socket
-                                        {
-                                            let socket = socket_path.clone();
-                                            let tx_pos = tx.clone();
-                                            Some(tokio::spawn(async move {
-                                                tokio::time::sleep(Duration::from_secs(2)).await;
-                                                loop {
-                                                    if let Some((pos, dur)) =
-                                                        streaming::get_mpv_position(&socket).await
-                                                    {
-                                                        let progress =
-                                                            streaming::calculate_progress(pos, dur);
-                                                        let _ = tx_pos
-                                                            .send(UiMessage::PlaybackProgress(
-                                                                progress,
-                                                            ))
-                                                            .await;
-                                                    }
-                                                    tokio::time::sleep(Duration::from_secs(5))
-                                                        .await;
-                                                }
-                                            }))
-                                        } else {
-                                            None
-                                        };
-
-                                        // Wait for either player to exit OR cancellation
-                                        tokio::select! {
-                                            _ = handle.child.wait() => {
-                                                info!("player exited normally");
-                                            }
-                                            _ = cancel_token.cancelled() => {
-                                                info!("cancellation requested, killing player");
-                                                let _ = handle.child.kill().await;
-                                            }
-                                        }
-
-                                        if let Some(h) = position_handle {
-                                            h.abort();
-                                        }
-                                        if let Some(socket_path) = handle.ipc_socket {
-                                            let _ = std::fs::remove_file(socket_path);
-                                        }
-                                    }
-                                    Err(e) => {
-                                        error!(error = %e, "failed to launch player");
-                                        let _ =
-                                            tx.send(UiMessage::StreamError(e.to_string())).await;
-                                        progress_handle.abort();
-                                        return;
-                                    }
-                                }
-
-                                progress_handle.abort();
-                                session.cleanup().await;
-                                let _ = tx.send(UiMessage::PlayerExited).await;
-                            });
+                            PlaybackController::new(
+                                config,
+                                session,
+                                streaming_cancel.clone().unwrap_or_default(),
+                                tx.clone(),
+                                app.progress.clone(),
+                                app.stream_id,
+                                app.config_location.pending_cleanup_path(),
+                            )
+                            .play(
+                                &file,
+                                torrent_info,
+                                app.media_context(),
+                            );
                         }
                     }
                     _ => {}
                 },
 
                 View::Streaming => match key.code {
+                    KeyCode::Char(c)
+                        if app.is_picking_subtitle_language() && c.is_ascii_alphabetic() =>
+                    {
+                        if let Some(input) = app
+                            .subtitle_prompt
+                            .as_mut()
+                            .and_then(|prompt| prompt.language_input.as_mut())
+                        {
+                            input.push(c.to_ascii_lowercase());
+                        }
+                    }
+                    KeyCode::Backspace if app.is_picking_subtitle_language() => {
+                        if let Some(input) = app
+                            .subtitle_prompt
+                            .as_mut()
+                            .and_then(|prompt| prompt.language_input.as_mut())
+                        {
+                            input.pop();
+                        }
+                    }
+                    KeyCode::Esc if app.is_picking_subtitle_language() => {
+                        if let Some(prompt) = app.subtitle_prompt.as_mut() {
+                            prompt.language_input = None;
+                        }
+                    }
+                    KeyCode::Enter if app.is_picking_subtitle_language() => {
+                        let language = app
+                            .subtitle_prompt
+                            .as_ref()
+                            .and_then(|prompt| prompt.language_input.clone())
+                            .unwrap_or_default();
+                        if !language.is_empty()
+                            && let Some(prompt) = app.subtitle_prompt.take()
+                        {
+                            info!(%language, "looking for subtitles in another language");
+                            prompt.answer(SubtitleDecision::Language(language));
+                        }
+                    }
+                    _ if app.is_picking_subtitle_language() => {}
+                    KeyCode::Enter | KeyCode::Char('c')
+                        if app.current_subtitle_prompt().is_some() =>
+                    {
+                        if let Some(prompt) = app.subtitle_prompt.take() {
+                            info!("continuing without preferred subtitles");
+                            prompt.answer(SubtitleDecision::Continue);
+                        }
+                    }
+                    KeyCode::Char('l') if app.current_subtitle_prompt().is_some() => {
+                        if let Some(prompt) = app.subtitle_prompt.as_mut() {
+                            prompt.language_input = Some(String::new());
+                        }
+                    }
+                    // q/Esc stop the stream, which also drops the prompt
+                    code if app.current_subtitle_prompt().is_some()
+                        && !matches!(code, KeyCode::Char('q') | KeyCode::Esc) => {}
+                    KeyCode::Enter | KeyCode::Char('y') if app.current_probe_prompt().is_some() => {
+                        if let Some(prompt) = app.probe_prompt.take() {
+                            info!(peers = prompt.probe.peers, "streaming probed torrent");
+                            prompt.answer(true);
+                        }
+                    }
+                    // Backing out (q/Esc) stops the stream, which also drops the prompt
+                    code if app.current_probe_prompt().is_some()
+                        && !matches!(code, KeyCode::Char('q') | KeyCode::Esc) => {}
+                    KeyCode::Enter | KeyCode::Char('n') if app.show_next_episode_prompt => {
+                        app.show_next_episode_prompt = false;
+                        // Let the episode after this one be prefetched too
+                        prefetch_cancel = None;
+                        if let Some(next) = app.prefetched_episode.take() {
+                            info!(title = %next.title, "playing prefetched next episode");
+                            app.selected_episode_index = next.episode_index;
+                            app.current_title = next.title;
+                            app.search_context = next.context;
+                            app.playback_progress = 0.0;
+                            app.reset_download_progress();
+                            app.is_streaming = true;
+                            streaming_cancel = Some(CancellationToken::new());
+                            let stream_id = app.next_stream_id();
+                            local_messages.push(UiMessage::TorrentMetadata {
+                                torrent_info: next.torrent_info,
+                                session: next.session,
+                                stream_id,
+                            });
+                        }
+                    }
+                    KeyCode::Char('q') | KeyCode::Esc if app.show_next_episode_prompt => {
+                        discard_prefetch(app, &mut prefetch_cancel, &tx);
+                        app.view = view_after_stream(app, config);
+                    }
+                    KeyCode::Up | KeyCode::Char('k') if app.show_player_picker => {
+                        app.selected_player_index = app.selected_player_index.saturating_sub(1);
+                    }
+                    KeyCode::Down | KeyCode::Char('j')
+                        if app.show_player_picker
+                            && app.selected_player_index + 1 < app.player_choices.len() =>
+                    {
+                        app.selected_player_index += 1;
+                    }
+                    KeyCode::Enter if app.show_player_picker => {
+                        app.show_player_picker = false;
+                        let player = app.player_choices.get(app.selected_player_index).cloned();
+                        let source = streaming_session.clone().zip(pending_torrent_info.as_ref());
+                        if let (Some(player), Some((session, torrent_info))) = (player, source) {
+                            if !config.player.multi_player {
+                                // Close only the current player, its PlayerExited
+                                // is expected. The session and its files stay.
+                                app.switching_player = true;
+                                if let Some(cancel) = player_cancel.take() {
+                                    cancel.cancel();
+                                }
+                            }
+                            // Stopping the stream still closes the new player
+                            let cancel = streaming_cancel
+                                .as_ref()
+                                .map(CancellationToken::child_token)
+                                .unwrap_or_default();
+                            spawn_player_switch(
+                                app,
+                                config,
+                                player,
+                                session,
+                                torrent_info,
+                                cancel,
+                                tx.clone(),
+                            );
+                        }
+                    }
+                    KeyCode::Char('q') | KeyCode::Esc if app.show_player_picker => {
+                        app.show_player_picker = false;
+                    }
+                    _ if app.show_player_picker => {}
+                    // Any key closes the stream URL
+                    _ if app.shared_stream_url.is_some() => {
+                        app.shared_stream_url = None;
+                    }
+                    KeyCode::Char('a')
+                        if app.audio_tracks.len() > 1
+                            && !app.show_resume_prompt
+                            && !app.show_next_episode_prompt =>
+                    {
+                        let track = audio_preferences::next_track(&app.audio_tracks).cloned();
+                        if let (Some(socket), Some(track)) = (app.player_ipc.clone(), track) {
+                            app.select_audio_track(track.id);
+                            if let Some(tmdb_id) = app.current_tmdb_id {
+                                audio_preferences.set(tmdb_id, AudioChoice::from(&track));
+                                audio_preferences.save();
+                            }
+                            spawn_audio_track_switch(socket, track, tx.clone());
+                        }
+                    }
+                    KeyCode::Char(']') | KeyCode::Char('[')
+                        if app.chapters.len() > 1
+                            && !app.show_resume_prompt
+                            && !app.show_next_episode_prompt =>
+                    {
+                        let offset = if key.code == KeyCode::Char(']') {
+                            1
+                        } else {
+                            -1
+                        };
+                        if let Some(socket) = app.player_ipc.clone() {
+                            spawn_chapter_seek(socket, offset, app.chapters.len(), tx.clone());
+                        }
+                    }
+                    KeyCode::Char('o')
+                        if matches!(app.streaming_state, StreamingState::Playing { .. })
+                            && !app.show_resume_prompt
+                            && !app.show_next_episode_prompt =>
+                    {
+                        app.player_choices = streaming::detect_players(&config.player.command);
+                        app.selected_player_index = 0;
+                        app.show_player_picker = true;
+                    }
+                    KeyCode::Char('r')
+                        if app.streaming_state == StreamingState::BackendLost
+                            && !app.show_resume_prompt =>
+                    {
+                        let source = streaming_session.clone().zip(
+                            pending_torrent_info
+                                .as_ref()
+                                .and_then(|info| streaming_session.as_ref()?.source_url(info.id)),
+                        );
+                        if let Some((old_session, url)) = source {
+                            info!(progress = app.playback_progress, "restarting lost stream");
+                            // Close the stalled player, its PlayerExited is expected
+                            app.restarting_stream = true;
+                            if let Some(cancel) = streaming_cancel.take() {
+                                cancel.cancel();
+                            }
+                            streaming_cancel = Some(CancellationToken::new());
+                            streaming_session = None;
+                            app.streaming_state = StreamingState::Connecting;
+                            app.reset_download_progress();
+                            spawn_stream_restart(
+                                old_session,
+                                app.next_session_id(),
+                                url,
+                                config.streaming.rqbit_url.clone(),
+                                tx.clone(),
+                                app.stream_id,
+                            );
+                        } else {
+                            app.push_toast(
+                                "Nothing to restart, search again",
+                                ToastLevel::Error,
+                                ToastLevel::Error.ttl(),
+                            );
+                        }
+                    }
                     KeyCode::Char('r') if app.show_resume_prompt => {
                         // Resume from saved position
                         app.show_resume_prompt = false;
@@ -1923,8 +3730,7 @@ async fn run_app(
                     KeyCode::Char('s') if app.show_resume_prompt => {
                         // Start from beginning - clear the saved progress
                         app.show_resume_prompt = false;
-                        let history_key =
-                            WatchHistory::make_key(app.current_tmdb_id, &app.current_file);
+                        let history_key = app.history_key();
                         watch_history.clear(&history_key);
                         watch_history.save();
                         info!("user chose to start from beginning");
@@ -1935,23 +3741,70 @@ async fn run_app(
                             info!("user cancelled streaming");
                             cancel.cancel();
                         }
-                        // Clean up session if it exists
+                        app.recovering_file = None;
+                        // Clean up once the player lets go of the files
                         if let Some(session) = streaming_session.take() {
-                            session.cleanup().await;
+                            spawn_cleanup(app, session, &tx);
                         }
+                        discard_prefetch(app, &mut prefetch_cancel, &tx);
                         pending_torrent_info = None;
+                        app.subtitle_prompt = None;
+                        app.probe_prompt = None;
                         app.available_files.clear();
                         app.racing_message = None;
-                        // Go back to Search if auto-race is enabled (user never saw Results)
-                        // Otherwise go back to Results
-                        app.view = if config.streaming.auto_race > 0 {
-                            View::Discovery
-                        } else {
-                            View::Results
-                        };
+                        app.view = view_after_stream(app, config);
                         app.streaming_state = StreamingState::Connecting;
                         app.is_streaming = false;
                     }
+                    KeyCode::Char('C')
+                        if app.streaming_state.stream_url().is_some()
+                            && !app.show_resume_prompt
+                            && !app.show_next_episode_prompt =>
+                    {
+                        app.cast_devices.clear();
+                        app.selected_cast_index = 0;
+                        app.is_discovering_cast = true;
+                        app.view = View::CastDevices;
+                        spawn_cast_discovery(tx.clone());
+                    }
+                    KeyCode::Char('u')
+                        if !app.show_resume_prompt && !app.show_next_episode_prompt =>
+                    {
+                        if let Some(stream_url) =
+                            app.streaming_state.stream_url().map(str::to_string)
+                        {
+                            let shared = share_relay
+                                .as_ref()
+                                .filter(|(stream_id, _)| *stream_id == app.stream_id);
+                            match (config.streaming.lan_address(), shared) {
+                                (_, Some((_, relay))) => {
+                                    show_stream_url(app, relay.url().to_string())
+                                }
+                                (Some(bind), None) => spawn_stream_share(
+                                    app,
+                                    stream_url.clone(),
+                                    bind,
+                                    config.streaming.lan_token().map(str::to_string),
+                                    tx.clone(),
+                                ),
+                                // Not shared, only this machine can open it
+                                (None, None) => show_stream_url(app, stream_url.clone()),
+                            }
+                        }
+                    }
+                    KeyCode::Char('p') | KeyCode::Char('P') if !app.show_resume_prompt => {
+                        let torrent = streaming_session
+                            .clone()
+                            .zip(pending_torrent_info.as_ref().map(|info| info.id));
+                        if let Some((session, torrent_id)) = torrent {
+                            app.peers.clear();
+                            app.selected_peer_index = 0;
+                            app.peers_error = None;
+                            app.is_fetching_peers = true;
+                            app.view = View::PeerList;
+                            spawn_peer_fetch(session, torrent_id, geoip.clone(), tx.clone());
+                        }
+                    }
                     KeyCode::Char('n') if app.has_next_episode() && !app.show_resume_prompt => {
                         // Skip to next episode - cancel current player
                         if let Some(cancel) = streaming_cancel.take() {
@@ -1968,8 +3821,9 @@ async fn run_app(
                         app.view = View::Discovery;
                     }
                     KeyCode::Char('r') if !app.is_checking => {
-                        // Run checks
+                        // Run checks, and have the next search ask Prowlarr for its indexers
                         app.is_checking = true;
+                        app.search_service.refresh();
                         let tx = tx.clone();
                         let config_clone = config.clone();
                         tokio::spawn(async move {
@@ -1977,30 +3831,216 @@ async fn run_app(
                             let _ = tx.send(UiMessage::DoctorComplete(results)).await;
                         });
                     }
+                    KeyCode::Char('i') => {
+                        app.selected_indexer_index = 0;
+                        app.view = View::Indexers;
+                    }
+                    KeyCode::Char('b') => {
+                        write_diagnostics(app, config);
+                    }
+                    _ => {}
+                },
+
+                View::Indexers => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => {
+                        app.view = View::Doctor;
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        app.select_next_indexer();
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        app.select_previous_indexer();
+                    }
+                    KeyCode::Char('e') => {
+                        if let Some(name) = app.selected_indexer_name() {
+                            let mut health = app.indexer_health.lock().unwrap();
+                            if health.reenable(&name) {
+                                health.save();
+                                drop(health);
+                                info!(indexer = %name, "re-enabled indexer");
+                                app.push_toast(
+                                    format!("Re-enabled {}", name),
+                                    ToastLevel::Info,
+                                    ToastLevel::Info.ttl(),
+                                );
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+
+                View::PeerList => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => {
+                        app.view = View::Streaming;
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        app.select_next_peer();
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        app.select_previous_peer();
+                    }
+                    KeyCode::Char('r') if !app.is_fetching_peers => {
+                        let torrent = streaming_session
+                            .clone()
+                            .zip(pending_torrent_info.as_ref().map(|info| info.id));
+                        if let Some((session, torrent_id)) = torrent {
+                            app.is_fetching_peers = true;
+                            spawn_peer_fetch(session, torrent_id, geoip.clone(), tx.clone());
+                        }
+                    }
+                    _ => {}
+                },
+
+                View::CastDevices => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => {
+                        app.view = View::Streaming;
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        app.select_next_cast_device();
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        app.select_previous_cast_device();
+                    }
+                    KeyCode::Char('r') if !app.is_discovering_cast => {
+                        app.is_discovering_cast = true;
+                        spawn_cast_discovery(tx.clone());
+                    }
+                    KeyCode::Enter if !app.is_starting_cast => {
+                        let device = app.selected_cast_device().cloned();
+                        if let (Some(device), Some(stream_url)) =
+                            (device, app.streaming_state.stream_url())
+                        {
+                            app.is_starting_cast = true;
+                            spawn_cast(app, device, stream_url.to_string(), tx.clone());
+                        }
+                    }
+                    _ => {}
+                },
+
+                View::HistoryExport => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => {
+                        app.view = View::Discovery;
+                    }
+                    // Any key closes the result popup
+                    _ if app.history_export_result.is_some() => {
+                        app.view = View::Discovery;
+                    }
+                    KeyCode::Enter if watch_history.is_empty() => {
+                        app.history_export_result = Some(Err(
+                            "Nothing to export, the watch history is empty".to_string(),
+                        ));
+                    }
+                    KeyCode::Enter => {
+                        let path = app.history_export_path.clone();
+                        app.history_export_result = Some(match watch_history.export_csv(&path) {
+                            Ok(()) => {
+                                info!(path = %path.display(), "exported watch history");
+                                Ok(format!(
+                                    "Exported {} entries to {}",
+                                    app.history_export_count,
+                                    path.display()
+                                ))
+                            }
+                            Err(e) => {
+                                error!(error = %e, "failed to export watch history");
+                                Err(format!("Export failed: {}", e))
+                            }
+                        });
+                    }
                     _ => {}
                 },
 
                 View::Settings => {
-                    if app.settings_editing {
+                    if app.settings_diff.is_some() {
+                        // Unsaved changes prompt
+                        match key.code {
+                            KeyCode::Char('s') | KeyCode::Char('S') => {
+                                app.settings_diff = None;
+                                if save_config(app, config) {
+                                    app.settings_dirty = false;
+                                    close_settings(app);
+                                }
+                            }
+                            KeyCode::Char('d') | KeyCode::Char('D') => {
+                                if let Some(saved) = app.settings_saved.clone() {
+                                    *config = saved;
+                                    if let Err(e) = crate::http::configure(&config.network) {
+                                        warn!("Network settings not restored: {}", e);
+                                    }
+                                }
+                                info!("discarded settings changes");
+                                app.settings_diff = None;
+                                app.settings_dirty = false;
+                                close_settings(app);
+                            }
+                            KeyCode::Char('c') | KeyCode::Char('C') | KeyCode::Esc => {
+                                app.settings_diff = None;
+                            }
+                            _ => {}
+                        }
+                    } else if app.show_command_picker {
+                        // Detected players, then the custom command
+                        match key.code {
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                app.selected_command_index =
+                                    app.selected_command_index.saturating_sub(1);
+                            }
+                            KeyCode::Down | KeyCode::Char('j')
+                                if app.selected_command_index < app.detected_players().len() =>
+                            {
+                                app.selected_command_index += 1;
+                            }
+                            KeyCode::Enter => {
+                                app.show_command_picker = false;
+                                match app.detected_players().get(app.selected_command_index) {
+                                    Some(player) => {
+                                        use_detected_player(config, player);
+                                        settings_changed(app, config);
+                                    }
+                                    None => {
+                                        app.settings_edit_buffer = config.player.command.clone();
+                                        app.settings_editing = true;
+                                    }
+                                }
+                            }
+                            KeyCode::Char('q') | KeyCode::Esc => {
+                                app.show_command_picker = false;
+                            }
+                            _ => {}
+                        }
+                    } else if app.settings_editing {
                         // Editing mode - handle text input
                         match key.code {
                             KeyCode::Esc => {
                                 // Cancel edit
                                 app.settings_editing = false;
                                 app.settings_edit_buffer.clear();
+                                app.settings_error = None;
                             }
                             KeyCode::Enter => {
-                                // Save edit to config
-                                apply_settings_edit(app, config);
-                                app.settings_editing = false;
-                                app.settings_edit_buffer.clear();
-                                app.settings_dirty = true;
+                                // Validate and save edit to config, stay in edit mode on error
+                                let field = app.selected_settings_field();
+                                match field.apply(config, &app.settings_edit_buffer) {
+                                    Ok(()) => {
+                                        settings_changed(app, config);
+                                        app.settings_editing = false;
+                                        app.settings_edit_buffer.clear();
+                                        app.settings_error = None;
+                                        if app.settings_section == SettingsSection::Storage {
+                                            // The temp dir may have moved
+                                            spawn_cache_size(app, config, &tx);
+                                        }
+                                    }
+                                    Err(e) => app.settings_error = Some(e),
+                                }
                             }
                             KeyCode::Backspace => {
                                 app.settings_edit_buffer.pop();
+                                app.settings_error = None;
                             }
                             KeyCode::Char(c) => {
                                 app.settings_edit_buffer.push(c);
+                                app.settings_error = None;
                             }
                             _ => {}
                         }
@@ -2008,27 +4048,39 @@ async fn run_app(
                         // Navigation mode
                         match key.code {
                             KeyCode::Char('q') | KeyCode::Esc => {
-                                if app.settings_dirty {
-                                    // Save config before exiting
-                                    if let Err(e) = config.save() {
-                                        error!("Failed to save config: {}", e);
-                                    } else {
-                                        info!("Config saved");
-                                    }
+                                let diffs = match (&app.settings_saved, app.settings_dirty) {
+                                    (Some(saved), true) => Config::diff(saved, config),
+                                    _ => Vec::new(),
+                                };
+                                if diffs.is_empty() {
+                                    // Nothing changed, or changed back
                                     app.settings_dirty = false;
+                                    close_settings(app);
+                                } else {
+                                    app.settings_diff = Some(diffs);
                                 }
-                                app.view = View::Discovery;
-                                app.settings_field_index = 0;
                             }
                             KeyCode::Left | KeyCode::Char('h') => {
                                 // Switch sections
                                 app.settings_section = app.settings_section.prev();
                                 app.settings_field_index = 0;
+                                if app.settings_section == SettingsSection::Storage {
+                                    spawn_cache_size(app, config, &tx);
+                                }
                             }
                             KeyCode::Right | KeyCode::Char('l') => {
                                 // Switch sections
                                 app.settings_section = app.settings_section.next();
                                 app.settings_field_index = 0;
+                                if app.settings_section == SettingsSection::Storage {
+                                    spawn_cache_size(app, config, &tx);
+                                }
+                            }
+                            KeyCode::Char('C')
+                                if app.settings_section == SettingsSection::Storage
+                                    && !app.is_clearing_cache =>
+                            {
+                                spawn_cache_clear(app, config, &tx);
                             }
                             KeyCode::Up | KeyCode::Char('k') => {
                                 // Move between fields
@@ -2038,25 +4090,47 @@ async fn run_app(
                                 // Move between fields
                                 app.settings_next_field();
                             }
-                            KeyCode::Enter => {
-                                // Start editing current field
-                                let current_value = get_settings_field_value(app, config);
-                                app.settings_edit_buffer = current_value;
-                                app.settings_editing = true;
-                            }
-                            KeyCode::Char(' ') => {
-                                // Toggle boolean fields
-                                if toggle_settings_bool(app, config) {
-                                    app.settings_dirty = true;
+                            KeyCode::Enter | KeyCode::Char(' ') => {
+                                let field = app.selected_settings_field();
+                                if let Some(reason) = field.lock_reason(config) {
+                                    app.push_toast(
+                                        reason.to_string(),
+                                        ToastLevel::Warning,
+                                        ToastLevel::Warning.ttl(),
+                                    );
+                                } else if field.toggle(config) {
+                                    // Bools flip and enums cycle in place
+                                    settings_changed(app, config);
+                                } else if key.code == KeyCode::Enter
+                                    && std::ptr::eq(field, settings::command_field())
+                                    && !app.detected_players().is_empty()
+                                {
+                                    // Pick from the installed players
+                                    app.selected_command_index =
+                                        app.player_option_index(&config.player.command);
+                                    app.show_command_picker = true;
+                                } else if key.code == KeyCode::Enter {
+                                    // Start editing current field
+                                    app.settings_edit_buffer = field.value(config);
+                                    app.settings_editing = true;
                                 }
                             }
                             KeyCode::Char('s') => {
                                 // Save now
-                                if let Err(e) = config.save() {
-                                    error!("Failed to save config: {}", e);
-                                } else {
-                                    info!("Config saved");
-                                    app.settings_dirty = false;
+                                let saved = save_config(app, config);
+                                app.settings_dirty &= !saved;
+                            }
+                            KeyCode::Char('w') => {
+                                if let Some(step) = WizardStep::for_section(app.settings_section) {
+                                    // Re-run the wizard step for this section
+                                    app.wizard_step = step;
+                                    app.wizard_field_index = if step == WizardStep::Player {
+                                        app.player_option_index(&config.player.command)
+                                    } else {
+                                        0
+                                    };
+                                    app.wizard_return_to = Some(View::Settings);
+                                    app.view = View::Wizard;
                                 }
                             }
                             _ => {}
@@ -2067,10 +4141,33 @@ async fn run_app(
         }
 
         if app.should_quit {
-            // Cleanup before exit
+            if !seeding.is_empty() {
+                // Ask before dropping torrents that are still seeding
+                app.should_quit = false;
+                app.show_quit_prompt = true;
+                continue;
+            }
+            // Cleanup before exit, closing the player first
+            if let Some(cancel) = streaming_cancel.take() {
+                cancel.cancel();
+            }
             if let Some(session) = streaming_session.take() {
-                session.cleanup().await;
+                cleanup_report(
+                    session.cleanup().await,
+                    &app.config_location.pending_cleanup_path(),
+                );
             }
+            if let Some(prefetched) = app.prefetched_episode.take() {
+                cleanup_report(
+                    prefetched.session.cleanup().await,
+                    &app.config_location.pending_cleanup_path(),
+                );
+            }
+            discard_prefetch(app, &mut prefetch_cancel, &tx);
+            session_recovery.clear();
+            // Let discarded sessions finish cleaning up, without hanging on a stuck one
+            let cleanups = async { while app.cleanups.join_next().await.is_some() {} };
+            let _ = tokio::time::timeout(QUIT_CLEANUP_TIMEOUT, cleanups).await;
             break;
         }
     }
@@ -2078,150 +4175,1809 @@ async fn run_app(
     Ok(())
 }
 
-/// Get the current value of the selected settings field
-fn get_settings_field_value(app: &App, config: &Config) -> String {
-    match app.settings_section {
-        SettingsSection::Prowlarr => match app.settings_field_index {
-            0 => config.prowlarr.url.clone(),
-            1 => config.prowlarr.apikey.clone(),
-            _ => String::new(),
-        },
-        SettingsSection::Tmdb => match app.settings_field_index {
-            0 => config
-                .tmdb
-                .as_ref()
-                .map(|t| t.apikey.clone())
-                .unwrap_or_default(),
-            _ => String::new(),
-        },
-        SettingsSection::Player => match app.settings_field_index {
-            0 => config.player.command.clone(),
-            1 => config.player.args.join(" "),
-            _ => String::new(),
-        },
-        SettingsSection::Streaming => match app.settings_field_index {
-            0 => config.streaming.auto_race.to_string(),
-            _ => String::new(),
-        },
-        SettingsSection::Subtitles => match app.settings_field_index {
-            0 => config.subtitles.enabled.to_string(),
-            1 => config.subtitles.language.clone(),
-            2 => config
-                .subtitles
-                .opensubtitles_api_key
-                .clone()
-                .unwrap_or_default(),
-            _ => String::new(),
-        },
-        SettingsSection::Discord => match app.settings_field_index {
-            0 => config.extensions.discord.enabled.to_string(),
-            1 => config.extensions.discord.app_id.clone().unwrap_or_default(),
-            _ => String::new(),
-        },
-        SettingsSection::Trakt => match app.settings_field_index {
-            0 => config.extensions.trakt.enabled.to_string(),
-            1 => config
-                .extensions
-                .trakt
-                .client_id
-                .clone()
-                .unwrap_or_default(),
-            2 => config
-                .extensions
-                .trakt
-                .access_token
-                .clone()
-                .unwrap_or_default(),
-            _ => String::new(),
-        },
+/// Switch the search to another TMDB match, re-deriving validation and the smart scores
+fn choose_tmdb_match(
+    app: &mut App,
+    info: TmdbMetadata,
+    tx: &mpsc::Sender<UiMessage>,
+    config: &Config,
+) {
+    info!(title = %info.title, year = ?info.year, "using picked TMDB match");
+    app.results_collection = None;
+    if info.media_type.as_deref() == Some("movie")
+        && let Some(id) = info.id
+    {
+        spawn_collection_lookup(
+            id,
+            app.search_id,
+            tx.clone(),
+            config.tmdb.as_ref().map(|t| t.apikey.clone()),
+        );
     }
+    app.tmdb_info = Some(info);
+
+    let validation = search_validation(&app.search_context, app.tmdb_info.as_ref(), config);
+    let scorer = ResultScorer::new(
+        validation,
+        app.search_context.media_type.clone(),
+        config.filter.result_filter(app.media_mode).blocked_terms,
+    );
+    app.selected_index = 0;
+    app.rescore_results(|results| scorer.score_all(results));
 }
 
-/// Apply the edit buffer to the config field
-fn apply_settings_edit(app: &App, config: &mut Config) {
-    let value = app.settings_edit_buffer.trim().to_string();
-
-    match app.settings_section {
-        SettingsSection::Prowlarr => match app.settings_field_index {
-            0 => config.prowlarr.url = value,
-            1 => config.prowlarr.apikey = value,
-            _ => {}
-        },
-        SettingsSection::Tmdb => {
-            if app.settings_field_index == 0 {
-                if value.is_empty() {
-                    config.tmdb = None;
-                } else {
-                    config.tmdb = Some(crate::config::TmdbConfig { apikey: value });
-                }
-            }
-        }
-        SettingsSection::Player => match app.settings_field_index {
-            0 => config.player.command = value,
-            1 => {
-                config.player.args = if value.is_empty() {
-                    Vec::new()
-                } else {
-                    value.split_whitespace().map(String::from).collect()
-                };
-            }
-            _ => {}
-        },
-        SettingsSection::Streaming => {
-            if app.settings_field_index == 0
-                && let Ok(v) = value.parse::<u8>()
+/// Channel a session reports its add phases on, forwarded to the UI until
+/// the session is dropped
+fn add_progress_channel(
+    tx: &mpsc::Sender<UiMessage>,
+    stream_id: u64,
+) -> mpsc::UnboundedSender<AddPhase> {
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        while let Some(phase) = progress_rx.recv().await {
+            if tx
+                .send(UiMessage::AddProgress { phase, stream_id })
+                .await
+                .is_err()
             {
-                config.streaming.auto_race = v;
+                return;
             }
         }
-        SettingsSection::Subtitles => match app.settings_field_index {
-            0 => config.subtitles.enabled = value.to_lowercase() == "true",
-            1 => config.subtitles.language = value,
-            2 => {
-                config.subtitles.opensubtitles_api_key =
-                    if value.is_empty() { None } else { Some(value) };
-            }
-            _ => {}
-        },
-        SettingsSection::Discord => match app.settings_field_index {
-            0 => config.extensions.discord.enabled = value.to_lowercase() == "true",
-            1 => {
-                config.extensions.discord.app_id =
-                    if value.is_empty() { None } else { Some(value) };
-            }
-            _ => {}
-        },
-        SettingsSection::Trakt => match app.settings_field_index {
-            0 => config.extensions.trakt.enabled = value.to_lowercase() == "true",
-            1 => {
-                config.extensions.trakt.client_id =
-                    if value.is_empty() { None } else { Some(value) };
+    });
+    progress_tx
+}
+
+/// Race `urls`, `concurrent` at a time, and stream the first one that
+/// connects and matches the current search
+fn start_race(
+    app: &mut App,
+    urls: Vec<String>,
+    concurrent: usize,
+    race_description: String,
+    tx: &mpsc::Sender<UiMessage>,
+    config: &Config,
+    streaming_cancel: &mut Option<CancellationToken>,
+) {
+    // Clear previous streaming state
+    app.current_file.clear();
+    app.current_title.clear();
+    app.available_files.clear();
+    app.reset_download_progress();
+
+    app.is_streaming = true;
+    app.racing_message = Some(format!("Racing {}...", race_description));
+    app.view = View::Streaming;
+    app.streaming_state = StreamingState::Connecting;
+
+    let tx = tx.clone();
+    let storage_dir = config.storage.temp_dir();
+    let session_id = app.next_session_id();
+    let rqbit_url = config.streaming.rqbit_url.clone();
+    let media_mode = app.media_mode;
+    let stream_id = app.next_stream_id();
+    let cancel_token = CancellationToken::new();
+    *streaming_cancel = Some(cancel_token.clone());
+
+    let validation = search_validation(&app.search_context, app.tmdb_info.as_ref(), config);
+    info!(
+        keywords = ?validation.title_keywords,
+        year = ?validation.year,
+        season = ?validation.season,
+        episode = ?validation.episode,
+        quality = ?validation.quality_preference,
+        "validation criteria"
+    );
+    let validation = (!validation.is_empty()).then_some(validation);
+
+    let metadata_timeout = config.streaming.metadata_timeout();
+    let pending_cleanup = app.config_location.pending_cleanup_path();
+
+    tokio::spawn(async move {
+        let session =
+            match StreamingSession::start(rqbit_url.as_deref(), storage_dir, session_id).await {
+                Ok(s) => std::sync::Arc::new(
+                    s.with_media_mode(media_mode)
+                        .with_metadata_timeout(metadata_timeout)
+                        .with_add_progress(add_progress_channel(&tx, stream_id)),
+                ),
+                Err(e) => {
+                    let _ = tx
+                        .send(UiMessage::StreamError {
+                            error: e.to_string(),
+                            stream_id,
+                        })
+                        .await;
+                    return;
+                }
+            };
+
+        if cancel_token.is_cancelled() {
+            cleanup_session(&session, &pending_cleanup, &tx).await;
+            return;
+        }
+
+        match session
+            .race_torrents(urls, validation, concurrent, cancel_token.clone())
+            .await
+        {
+            Ok((_winner_idx, torrent_info)) => {
+                // The losers would keep downloading next to the stream
+                session.keep_only(torrent_info.id).await;
+                let _ = tx
+                    .send(UiMessage::TorrentMetadata {
+                        torrent_info,
+                        session,
+                        stream_id,
+                    })
+                    .await;
             }
-            2 => {
-                config.extensions.trakt.access_token =
-                    if value.is_empty() { None } else { Some(value) };
+            Err(e) => {
+                // Don't report error if cancelled
+                if !cancel_token.is_cancelled() {
+                    let _ = tx
+                        .send(UiMessage::StreamError {
+                            error: e.to_string(),
+                            stream_id,
+                        })
+                        .await;
+                }
+                cleanup_session(&session, &pending_cleanup, &tx).await;
             }
-            _ => {}
-        },
+        }
+    });
+}
+
+/// Criteria raced torrents are checked against, also used to score results.
+/// Free-text searches borrow the title (and movie year) from the TMDB match.
+fn search_validation(
+    context: &SearchContext,
+    tmdb_info: Option<&TmdbMetadata>,
+    config: &Config,
+) -> TorrentValidation {
+    let mut validation = TorrentValidation::from_context(context);
+
+    if context.media_type.is_none()
+        && let Some(tmdb) = tmdb_info
+    {
+        validation
+            .title_keywords
+            .extend(TorrentValidation::extract_keywords(&tmdb.title));
+        // A show's first-air year won't be in episode names
+        if validation.episode.is_none() && tmdb.media_type.as_deref() != Some("tv") {
+            validation.year = tmdb.year;
+        }
     }
+    // Deduplicate keywords
+    validation.title_keywords.sort();
+    validation.title_keywords.dedup();
+    validation.quality_preference = config.streaming.preferred_quality.clone();
+    validation
 }
 
-/// Toggle boolean fields with spacebar, returns true if a toggle happened
-fn toggle_settings_bool(app: &App, config: &mut Config) -> bool {
-    match app.settings_section {
-        SettingsSection::Subtitles if app.settings_field_index == 0 => {
-            config.subtitles.enabled = !config.subtitles.enabled;
-            true
+/// Playback percentage at which the next episode is looked up
+const PREFETCH_AT_PERCENT: f64 = 60.0;
+/// Torrents raced at once for a prefetch, kept low to leave bandwidth to playback
+const PREFETCH_CONCURRENCY: usize = 2;
+
+/// Search and race the next episode quietly, reporting back once it's connected.
+/// Returns the token that cancels the prefetch.
+fn spawn_episode_prefetch(
+    query: String,
+    context: SearchContext,
+    title: String,
+    episode_index: usize,
+    config: &Config,
+    app: &mut App,
+    tx: mpsc::Sender<UiMessage>,
+) -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let task_cancel = cancel.clone();
+    let search_service = app.search_service.clone();
+    let mode = app.media_mode;
+    let result_filter = config.filter.result_filter(mode);
+    let race_order = config.streaming.race_order();
+    let rqbit_url = config.streaming.rqbit_url.clone();
+    let storage_dir = config.storage.temp_dir();
+    let session_id = app.next_session_id();
+    let metadata_timeout = config.streaming.metadata_timeout();
+    let indexer_health = app.indexer_health.clone();
+    let pending_cleanup = app.config_location.pending_cleanup_path();
+    // Rewritten like a typed search, without touching its status line
+    let query = config.streaming.transform_query(&query);
+    let validation = search_validation(&context, None, config);
+    let scorer = ResultScorer::new(
+        validation.clone(),
+        context.media_type.clone(),
+        result_filter.blocked_terms.clone(),
+    );
+
+    tokio::spawn(async move {
+        let cancel = task_cancel;
+        let request = SearchRequest::new(query, context.clone());
+        let results =
+            match search_indexers(&request, mode, &search_service, &indexer_health, &tx).await {
+                Ok(results) => results,
+                Err(e) => {
+                    let _ = tx.send(UiMessage::PrefetchFailed(e)).await;
+                    return;
+                }
+            };
+        let (results, _) = result_filter.apply(results);
+        let scores = scorer.score_all(&results);
+        let urls: Vec<String> = race_order
+            .rank(&results, &scores)
+            .iter()
+            .filter_map(|r| r.get_torrent_url())
+            .collect();
+        if urls.is_empty() || cancel.is_cancelled() {
+            return;
         }
-        SettingsSection::Discord if app.settings_field_index == 0 => {
-            config.extensions.discord.enabled = !config.extensions.discord.enabled;
-            true
+
+        let session =
+            match StreamingSession::start(rqbit_url.as_deref(), storage_dir, session_id).await {
+                Ok(session) => std::sync::Arc::new(
+                    session
+                        .with_media_mode(mode)
+                        .with_metadata_timeout(metadata_timeout),
+                ),
+                Err(e) => {
+                    let _ = tx.send(UiMessage::PrefetchFailed(e.to_string())).await;
+                    return;
+                }
+            };
+
+        match session
+            .race_torrents(urls, Some(validation), PREFETCH_CONCURRENCY, cancel.clone())
+            .await
+        {
+            Ok((_, torrent_info)) => {
+                session.keep_only(torrent_info.id).await;
+                let prefetched = PrefetchedEpisode {
+                    episode_index,
+                    title,
+                    context,
+                    torrent_info,
+                    session,
+                };
+                let _ = tx.send(UiMessage::NextEpisodePrefetched(prefetched)).await;
+            }
+            Err(e) => {
+                cleanup_session(&session, &pending_cleanup, &tx).await;
+                if !cancel.is_cancelled() {
+                    let _ = tx.send(UiMessage::PrefetchFailed(e.to_string())).await;
+                }
+            }
         }
-        SettingsSection::Trakt if app.settings_field_index == 0 => {
-            config.extensions.trakt.enabled = !config.extensions.trakt.enabled;
+    });
+
+    cancel
+}
+
+/// Cancel a running prefetch and drop a prefetched next episode
+fn discard_prefetch(
+    app: &mut App,
+    prefetch_cancel: &mut Option<CancellationToken>,
+    tx: &mpsc::Sender<UiMessage>,
+) {
+    if let Some(cancel) = prefetch_cancel.take() {
+        cancel.cancel();
+    }
+    app.is_prefetching = false;
+    app.show_next_episode_prompt = false;
+    if let Some(prefetched) = app.prefetched_episode.take() {
+        spawn_cleanup(app, prefetched.session, tx);
+    }
+}
+
+/// A torrent kept seeding in the background after playback
+struct Seeding {
+    id: u64,
+    session: std::sync::Arc<StreamingSession>,
+    cancel: CancellationToken,
+}
+
+/// How long quitting waits for background cleanups
+const QUIT_CLEANUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a seeding torrent is checked against its target
+const SEED_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Seed `torrent_info` until `policy` is met, then remove it and report back.
+/// The other torrents of the session are removed first, race losers don't seed.
+fn spawn_seeding(
+    seed_id: u64,
+    session: std::sync::Arc<StreamingSession>,
+    torrent_info: &crate::streaming::TorrentInfo,
+    policy: SeedPolicy,
+    pending_cleanup: PathBuf,
+    tx: mpsc::Sender<UiMessage>,
+) -> Seeding {
+    let cancel = CancellationToken::new();
+    let seeding = Seeding {
+        id: seed_id,
+        session: session.clone(),
+        cancel: cancel.clone(),
+    };
+    let torrent_id = torrent_info.id;
+    let name = torrent_info.name.clone();
+
+    tokio::spawn(async move {
+        session.keep_only(torrent_id).await;
+        let started = Instant::now();
+        loop {
+            tokio::select! {
+                // Stopped on exit, whoever cancelled cleans up
+                _ = cancel.cancelled() => return,
+                _ = tokio::time::sleep(SEED_CHECK_INTERVAL) => {}
+            }
+            let stats = session.get_stats(torrent_id).await;
+            if let Some(stats) = stats.clone() {
+                let torrent = (session.session_id(), torrent_id);
+                let _ = tx.send(UiMessage::SeedingStats { torrent, stats }).await;
+            }
+            let stats = stats.unwrap_or_default();
+            if policy.is_met(started.elapsed(), &stats) {
+                info!(torrent = %name, uploaded = stats.uploaded_bytes, "seeding target reached");
+                break;
+            }
+        }
+        cleanup_session(&session, &pending_cleanup, &tx).await;
+        let _ = tx.send(UiMessage::SeedingFinished { seed_id, name }).await;
+    });
+
+    seeding
+}
+
+/// Stop all background seeding and remove the torrents
+async fn stop_seeding(seeding: &mut Vec<Seeding>, pending_path: &Path) {
+    for seed in seeding.drain(..) {
+        seed.cancel.cancel();
+        cleanup_report(seed.session.cleanup().await, pending_path);
+    }
+}
+
+/// Where to go once a stream ends: back to the results it came from, or to
+/// discovery when the user never saw any (auto-race, a pasted torrent)
+fn view_after_stream(app: &App, config: &Config) -> View {
+    if config.streaming.auto_race > 0
+        || app.direct_stream_id == Some(app.stream_id)
+        || app.results.is_empty()
+    {
+        View::Discovery
+    } else {
+        View::Results
+    }
+}
+
+/// Start the stream of a session that was cut short again, its metadata
+/// picks up the same file at the same position
+fn recover_stream(
+    app: &mut App,
+    snapshot: SessionSnapshot,
+    tx: &mpsc::Sender<UiMessage>,
+    config: &Config,
+    streaming_cancel: &mut Option<CancellationToken>,
+) {
+    info!(
+        title = %snapshot.title,
+        progress = snapshot.progress_percent,
+        "resuming interrupted stream"
+    );
+    app.current_title = snapshot.title;
+    app.current_tmdb_id = snapshot.tmdb_id;
+    app.current_year = snapshot.year;
+    app.current_media_type = snapshot.media_type;
+    app.current_poster_url = None;
+    app.search_context = SearchContext::default();
+    app.current_file = snapshot.file_name;
+    app.playback_progress = snapshot.progress_percent;
+    start_stream(
+        app,
+        StreamSource::torrent(snapshot.source_url),
+        tx,
+        config,
+        streaming_cancel,
+    );
+    app.recovering_file = Some(snapshot.file_idx);
+}
+
+/// What's playing, for picking it up after a crash. None for streams that
+/// can't be added again, like Usenet ones.
+fn session_snapshot(
+    app: &App,
+    session: Option<&StreamingSession>,
+    torrent_info: Option<&TorrentInfo>,
+) -> Option<SessionSnapshot> {
+    let torrent_info = torrent_info?;
+    let source_url = session?.source_url(torrent_info.id)?;
+    let file = torrent_info
+        .video_files
+        .iter()
+        .chain(&torrent_info.extra_files)
+        .find(|f| f.name == app.current_file)?;
+    Some(SessionSnapshot {
+        source_url,
+        file_name: file.name.clone(),
+        file_idx: file.file_idx,
+        progress_percent: app.playback_progress,
+        title: app.current_title.clone(),
+        tmdb_id: app.current_tmdb_id,
+        year: app.current_year,
+        media_type: app.current_media_type.clone(),
+        saved_at: 0,
+    })
+}
+
+/// Stream a pasted magnet or .torrent without searching. Until the metadata
+/// names it, the title is the magnet's display name or the file name.
+fn start_direct_stream(
+    app: &mut App,
+    source: String,
+    tx: &mpsc::Sender<UiMessage>,
+    config: &Config,
+    streaming_cancel: &mut Option<CancellationToken>,
+) {
+    info!("streaming pasted torrent");
+    app.current_title = direct_source_name(&source);
+    app.current_tmdb_id = None;
+    app.current_year = None;
+    app.current_media_type = None;
+    app.current_poster_url = None;
+    app.search_context = SearchContext::default();
+    start_stream(
+        app,
+        StreamSource::torrent(source),
+        tx,
+        config,
+        streaming_cancel,
+    );
+    app.direct_stream_id = Some(app.stream_id);
+}
+
+/// Placeholder title for a pasted torrent
+fn direct_source_name(source: &str) -> String {
+    let name = if source
+        .get(..7)
+        .is_some_and(|s| s.eq_ignore_ascii_case("magnet:"))
+    {
+        url::Url::parse(source).ok().and_then(|url| {
+            url.query_pairs()
+                .find(|(key, _)| key == "dn")
+                .map(|(_, name)| name.into_owned())
+        })
+    } else {
+        source
+            .rsplit(['/', '\\'])
+            .next()
+            .and_then(|file| file.split('?').next())
+            .map(|file| file.trim_end_matches(".torrent").to_string())
+    };
+    name.filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Pasted torrent".to_string())
+}
+
+/// What a stream is started from
+enum StreamSource {
+    /// Magnet, .torrent URL or path, probed for peers first when `probe` is set
+    Torrent { url: String, probe: bool },
+    /// NZB found on a Usenet indexer, fetched by the configured downloader
+    Nzb { url: String, name: String },
+}
+
+impl StreamSource {
+    fn torrent(url: String) -> Self {
+        StreamSource::Torrent { url, probe: false }
+    }
+
+    /// Where to stream a search result from, None if it links to nothing
+    fn for_result(result: &TorrentResult, probe: bool) -> Option<Self> {
+        match result.nzb_url() {
+            Some(url) => Some(StreamSource::Nzb {
+                url,
+                name: result.title.clone(),
+            }),
+            None => Some(StreamSource::Torrent {
+                url: result.get_torrent_url()?,
+                probe,
+            }),
+        }
+    }
+}
+
+/// Stream a single torrent, file selection follows if it has several videos.
+/// A torrent with `probe` set has its peers counted first and the user asked
+/// to go on.
+fn start_stream(
+    app: &mut App,
+    source: StreamSource,
+    tx: &mpsc::Sender<UiMessage>,
+    config: &Config,
+    streaming_cancel: &mut Option<CancellationToken>,
+) {
+    app.view = View::Streaming;
+    app.streaming_state = StreamingState::Connecting;
+    app.reset_download_progress();
+    app.is_streaming = true;
+    // Only a recovered stream picks up a snapshot's file, see `recover_stream`
+    app.recovering_file = None;
+
+    let tx = tx.clone();
+    let storage_dir = config.storage.temp_dir();
+    let session_id = app.next_session_id();
+    let rqbit_url = config.streaming.rqbit_url.clone();
+    let media_mode = app.media_mode;
+    let metadata_timeout = config.streaming.metadata_timeout();
+    let stream_id = app.next_stream_id();
+    let usenet = config.usenet.as_ref().map(UsenetClient::new);
+    let pending_cleanup = app.config_location.pending_cleanup_path();
+
+    // Create cancellation token
+    let cancel_token = CancellationToken::new();
+    *streaming_cancel = Some(cancel_token.clone());
+
+    // Phase 1: Create session and add torrent
+    tokio::spawn(async move {
+        if cancel_token.is_cancelled() {
+            info!("streaming cancelled before start");
+            let _ = tx.send(UiMessage::PlayerExited).await;
+            return;
+        }
+        info!("creating streaming session");
+        let session =
+            match StreamingSession::start(rqbit_url.as_deref(), storage_dir, session_id).await {
+                Ok(s) => {
+                    info!("session created");
+                    std::sync::Arc::new(
+                        s.with_media_mode(media_mode)
+                            .with_metadata_timeout(metadata_timeout)
+                            .with_add_progress(add_progress_channel(&tx, stream_id)),
+                    )
+                }
+                Err(e) => {
+                    error!(error = %e, "failed to create session");
+                    let _ = tx
+                        .send(UiMessage::StreamError {
+                            error: e.to_string(),
+                            stream_id,
+                        })
+                        .await;
+                    return;
+                }
+            };
+
+        if cancel_token.is_cancelled() {
+            info!("streaming cancelled");
+            cleanup_session(&session, &pending_cleanup, &tx).await;
+            let _ = tx.send(UiMessage::PlayerExited).await;
+            return;
+        }
+        let added = match source {
+            StreamSource::Torrent { url, probe: true } => {
+                info!("probing torrent");
+                probe_torrent(&session, &url, &tx, stream_id, &cancel_token).await
+            }
+            StreamSource::Torrent { url, probe: false } => {
+                info!("adding torrent");
+                session.add_torrent(&url).await.map(Some)
+            }
+            StreamSource::Nzb { url, name } => {
+                info!("sending nzb to usenet downloader");
+                match &usenet {
+                    Some(usenet) => match session.add_nzb(usenet, &url, &name, &cancel_token).await
+                    {
+                        Err(StreamError::Usenet(UsenetError::Cancelled)) => Ok(None),
+                        added => added.map(Some),
+                    },
+                    None => Err(StreamError::TorrentError(
+                        "no Usenet downloader configured".to_string(),
+                    )),
+                }
+            }
+        };
+        let torrent_info = match added {
+            Ok(Some(info)) => {
+                info!(files = info.video_files.len(), "torrent added");
+                info
+            }
+            Ok(None) => {
+                info!("backed out after probing torrent, or cancelled");
+                cleanup_session(&session, &pending_cleanup, &tx).await;
+                return;
+            }
+            Err(e) => {
+                error!(error = %e, "failed to add torrent");
+                let _ = tx
+                    .send(UiMessage::StreamError {
+                        error: e.to_string(),
+                        stream_id,
+                    })
+                    .await;
+                return;
+            }
+        };
+
+        // Send metadata to UI - it will decide whether to show file selection
+        let _ = tx
+            .send(UiMessage::TorrentMetadata {
+                torrent_info,
+                session,
+                stream_id,
+            })
+            .await;
+    });
+}
+
+/// OpenSubtitles REST client, if there's an API key for it
+fn opensubtitles_client(config: &Config) -> Option<OpenSubtitlesClient> {
+    let subtitles = &config.subtitles;
+    subtitles
+        .opensubtitles_api_key
+        .as_deref()
+        .map(|key| OpenSubtitlesClient::new(key, subtitles.opensubtitles_max_wait()))
+}
+
+/// Resolve the subtitle for a fresh launch. When none is found in the
+/// preferred languages the user is asked whether to continue anyway or try
+/// another language; stopping the stream drops the question.
+async fn choose_subtitle(
+    subtitle_files: &[SubtitleFile],
+    languages: &[String],
+    opensubtitles: Option<&OpenSubtitlesClient>,
+    media: &MediaContext,
+    video: (&str, u64),
+    tx: &mpsc::Sender<UiMessage>,
+    stream_id: u64,
+) -> Option<Subtitle> {
+    let mut languages = languages.to_vec();
+    loop {
+        let found =
+            resolve_subtitle(subtitle_files, &languages, opensubtitles, media, video, tx).await;
+        if found.is_some() || languages.is_empty() {
+            return found.or_else(|| fallback_subtitle(subtitle_files));
+        }
+
+        info!(?languages, "no subtitles in preferred languages, asking");
+        let (reply, decision) = oneshot::channel();
+        let _ = tx
+            .send(UiMessage::SubtitlesMissing {
+                languages: languages.clone(),
+                has_fallback: !subtitle_files.is_empty(),
+                stream_id,
+                reply,
+            })
+            .await;
+        match decision.await {
+            Ok(SubtitleDecision::Continue) => return fallback_subtitle(subtitle_files),
+            Ok(SubtitleDecision::Language(language)) => languages = vec![language],
+            // The stream was stopped
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Any subtitle shipped with the torrent, for when none is in a preferred language
+fn fallback_subtitle(subtitle_files: &[SubtitleFile]) -> Option<Subtitle> {
+    subtitle_files.first().map(|s| Subtitle {
+        url: s.stream_url.clone(),
+        source: SubtitleSource::Torrent,
+        language: s.language.clone(),
+    })
+}
+
+/// Pick a subtitle by walking the preferred languages in order, trying the
+/// torrent's own files before OpenSubtitles for each. OpenSubtitles results
+/// matching the video's moviehash win over TMDB matches since they're in sync.
+/// Once the REST API's quota is used up or it stays rate limited, searches
+/// go over XML-RPC, by hash or else by title.
+async fn resolve_subtitle(
+    subtitle_files: &[SubtitleFile],
+    languages: &[String],
+    opensubtitles: Option<&OpenSubtitlesClient>,
+    media: &MediaContext,
+    (video_url, video_size): (&str, u64),
+    tx: &mpsc::Sender<UiMessage>,
+) -> Option<Subtitle> {
+    let mut opensubtitles = opensubtitles;
+    let mut xmlrpc: Option<OpenSubtitlesXmlRpcClient> = None;
+    let tmdb_id = media.tmdb_id;
+    let mut moviehash = None;
+    let found = 'search: {
+        for language in languages {
+            if let Some(sub) = subtitle_files
+                .iter()
+                .find(|s| s.language.as_deref() == Some(language.as_str()))
+            {
+                break 'search Some(Subtitle {
+                    url: sub.stream_url.clone(),
+                    source: SubtitleSource::Torrent,
+                    language: Some(language.clone()),
+                });
+            }
+
+            if opensubtitles.is_none() && xmlrpc.is_none() {
+                continue;
+            }
+            // Only hashed once the torrent has nothing better to offer
+            if moviehash.is_none() {
+                moviehash = Some(opensubtitles::stream_moviehash(video_url, video_size).await);
+            }
+            let hash = moviehash.clone().flatten();
+            if hash.is_none() && tmdb_id.is_none() {
+                continue;
+            }
+
+            info!(language = %language, "no subtitles in torrent, trying OpenSubtitles");
+            let search = async {
+                let Some(client) = &opensubtitles else {
+                    return Err(OpenSubtitlesError::QuotaExceeded);
+                };
+                if let Some(hash) = &hash {
+                    match client.search_by_hash(hash, video_size, language).await {
+                        Ok(subs) => return Ok((subs, true)),
+                        Err(OpenSubtitlesError::NotFound) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                match tmdb_id {
+                    Some(tmdb) => client
+                        .search_by_tmdb(tmdb, language)
+                        .await
+                        .map(|s| (s, false)),
+                    None => Err(OpenSubtitlesError::NotFound),
+                }
+            };
+            let result = match search.await {
+                Err(e @ (OpenSubtitlesError::QuotaExceeded | OpenSubtitlesError::RateLimited)) => {
+                    if opensubtitles.take().is_some() {
+                        info!(reason = %e, "OpenSubtitles REST API unavailable, falling back to XML-RPC");
+                    }
+                    let client = xmlrpc.get_or_insert_with(OpenSubtitlesXmlRpcClient::new);
+                    match &hash {
+                        Some(hash) => client
+                            .search_subtitles(
+                                SubtitleQuery::Hash {
+                                    hash,
+                                    size: video_size,
+                                },
+                                language,
+                            )
+                            .await
+                            .map(|s| (s, true)),
+                        None if !media.title.is_empty() => client
+                            .search_subtitles(SubtitleQuery::Text(&media.title), language)
+                            .await
+                            .map(|s| (s, false)),
+                        None => Err(OpenSubtitlesError::NotFound),
+                    }
+                }
+                result => result,
+            };
+            match result {
+                Ok((subs, synced)) => {
+                    if let Some(sub) = subs.first() {
+                        let message = if synced {
+                            format!("Synced subtitles from OpenSubtitles ({})", language)
+                        } else {
+                            format!("Subtitles from OpenSubtitles ({})", language)
+                        };
+                        let _ = tx
+                            .send(UiMessage::Notify {
+                                message,
+                                level: ToastLevel::Info,
+                            })
+                            .await;
+                        break 'search Some(Subtitle {
+                            url: sub.download_url.clone(),
+                            source: SubtitleSource::OpenSubtitles,
+                            language: Some(language.clone()),
+                        });
+                    }
+                }
+                // Nothing in this language, the next one may have some
+                Err(OpenSubtitlesError::NotFound) => {}
+                // These fail the same for every language, don't repeat the search
+                Err(
+                    e @ (OpenSubtitlesError::Unauthorized
+                    | OpenSubtitlesError::RequestError(_)
+                    | OpenSubtitlesError::QuotaExceeded
+                    | OpenSubtitlesError::RateLimited),
+                ) => {
+                    debug!(error = %e, "OpenSubtitles unavailable");
+                    let _ = tx
+                        .send(UiMessage::Notify {
+                            message: format!("OpenSubtitles search failed: {}", e),
+                            level: ToastLevel::Warning,
+                        })
+                        .await;
+                    opensubtitles = None;
+                    if let Some(mut client) = xmlrpc.take() {
+                        client.logout().await;
+                    }
+                }
+                Err(e) => {
+                    debug!(error = %e, language = %language, "OpenSubtitles search failed");
+                    let _ = tx
+                        .send(UiMessage::Notify {
+                            message: format!("OpenSubtitles search failed: {}", e),
+                            level: ToastLevel::Warning,
+                        })
+                        .await;
+                }
+            }
+        }
+        None
+    };
+
+    if let Some(mut client) = xmlrpc {
+        client.logout().await;
+    }
+    found
+}
+
+/// Consecutive failed stats polls (every 500ms) before the stream backend counts as lost
+const BACKEND_LOST_AFTER: u32 = 10;
+
+/// Poll download stats for the UI. Reports `StreamBackendLost` and stops if
+/// the HTTP API stops answering, since the player would only stall from then on.
+fn spawn_progress_poll(
+    session: std::sync::Arc<StreamingSession>,
+    torrent_id: usize,
+    tx: mpsc::Sender<UiMessage>,
+    progress_tx: ProgressSender,
+    stream_id: u64,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut failures = 0;
+        loop {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            let Some(stats) = session.get_stats(torrent_id).await else {
+                failures += 1;
+                if failures >= BACKEND_LOST_AFTER {
+                    warn!(torrent_id, "stream backend stopped responding");
+                    let _ = tx.send(UiMessage::StreamBackendLost { stream_id }).await;
+                    break;
+                }
+                continue;
+            };
+            failures = 0;
+
+            let progress = DownloadProgress {
+                downloaded_bytes: stats.downloaded_bytes,
+                total_bytes: stats.total_bytes,
+                download_speed: stats.download_speed,
+                upload_speed: stats.upload_speed,
+                uploaded_bytes: stats.uploaded_bytes,
+                peers_connected: stats.peers_connected,
+                progress_percent: stats.progress_percent(),
+            };
+            if !progress_tx.download(DownloadUpdate {
+                progress,
+                stream_id,
+                torrent: (session.session_id(), torrent_id),
+            }) {
+                break;
+            }
+        }
+    })
+}
+
+/// Index in its torrent of the file that's streaming
+fn current_file_idx(app: &App, torrent_info: &TorrentInfo) -> Option<usize> {
+    torrent_info
+        .video_files
+        .iter()
+        .chain(&torrent_info.extra_files)
+        .find(|f| f.name == app.current_file)
+        .map(|f| f.file_idx)
+}
+
+/// Read which pieces of a file are downloaded, for the streaming view
+fn spawn_piece_map_fetch(
+    session: std::sync::Arc<StreamingSession>,
+    torrent_id: usize,
+    file_idx: usize,
+    tx: mpsc::Sender<UiMessage>,
+    stream_id: u64,
+) {
+    tokio::spawn(async move {
+        let map = session.get_piece_map(torrent_id, file_idx).await;
+        let _ = tx.send(UiMessage::PieceMapLoaded { map, stream_id }).await;
+    });
+}
+
+/// Play `app.current_file` of a torrent added again, from `app.playback_progress`.
+/// Stream URLs point at the new session's port, so the file is matched by
+/// name. False if the torrent has no video files.
+fn resume_stream(app: &mut App, torrent_info: &TorrentInfo, player: &PlaybackController) -> bool {
+    let mut files = torrent_info.video_files.clone();
+    sort_episodes(&mut files);
+    app.extra_files = torrent_info.extra_files.clone();
+    if app.show_extras {
+        files.extend(app.extra_files.iter().cloned());
+    }
+    let index = files
+        .iter()
+        .position(|f| f.name == app.current_file)
+        .unwrap_or(0);
+    let Some(file) = files.get(index).cloned() else {
+        app.streaming_state = StreamingState::Error("Torrent has no video files".into());
+        app.is_streaming = false;
+        return false;
+    };
+    if files.len() > 1 {
+        app.available_files = files;
+        app.current_episode_index = index;
+    }
+    app.current_file = file.name.clone();
+    app.streaming_state = StreamingState::Ready {
+        stream_url: file.stream_url.clone(),
+    };
+    player.resume(
+        &file,
+        torrent_info,
+        app.media_context(),
+        app.playback_progress,
+    );
+    true
+}
+
+/// Bring a stream back after its backend died: stop the old session, move its
+/// download directory over to a new session as `session_id` and add the
+/// torrent again, so the data downloaded so far is picked up instead of
+/// fetched again
+fn spawn_stream_restart(
+    old_session: std::sync::Arc<StreamingSession>,
+    session_id: u64,
+    url: String,
+    rqbit_url: Option<String>,
+    tx: mpsc::Sender<UiMessage>,
+    stream_id: u64,
+) {
+    tokio::spawn(async move {
+        old_session.shutdown().await;
+        let storage_dir = old_session.storage_dir().clone();
+        let old_dir = storage_dir.join(streaming::session_dir_name(old_session.session_id()));
+        let media_mode = old_session.media_mode();
+        let metadata_timeout = old_session.metadata_timeout();
+        drop(old_session);
+
+        if rqbit_url.is_none() {
+            let new_dir = storage_dir.join(streaming::session_dir_name(session_id));
+            if let Err(e) = tokio::fs::rename(&old_dir, &new_dir).await {
+                // Starts over, the old directory is left for the cleanup on next launch
+                warn!(dir = %old_dir.display(), error = %e, "failed to move session directory");
+            }
+        }
+
+        let result = async {
+            let session = StreamingSession::start(rqbit_url.as_deref(), storage_dir, session_id)
+                .await?
+                .with_media_mode(media_mode)
+                .with_metadata_timeout(metadata_timeout);
+            let torrent_info = session.add_torrent(&url).await?;
+            // A daemon may have kept it across the restart, it's still ours
+            session.claim(torrent_info.id);
+            Ok::<_, crate::streaming::StreamError>((std::sync::Arc::new(session), torrent_info))
+        }
+        .await;
+
+        match result {
+            Ok((session, torrent_info)) => {
+                info!(torrent = %torrent_info.name, "stream restarted");
+                let _ = tx
+                    .send(UiMessage::StreamRestarted {
+                        torrent_info,
+                        session,
+                    })
+                    .await;
+            }
+            Err(e) => {
+                error!(error = %e, "failed to restart stream");
+                let _ = tx
+                    .send(UiMessage::StreamError {
+                        error: e.to_string(),
+                        stream_id,
+                    })
+                    .await;
+            }
+        }
+    });
+}
+
+/// Hold off launching the player until `pre_roll_percent` of the torrent is
+/// downloaded, so playback doesn't start on an empty buffer. Returns early on cancel.
+async fn wait_for_pre_roll(
+    session: &StreamingSession,
+    torrent_id: usize,
+    pre_roll_percent: f64,
+    cancel: &CancellationToken,
+    tx: &mpsc::Sender<UiMessage>,
+    stream_id: u64,
+) {
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    loop {
+        let percent = session
+            .get_stats(torrent_id)
+            .await
+            .map(|stats| stats.progress_percent())
+            .unwrap_or_default();
+        if percent >= pre_roll_percent {
+            return;
+        }
+        let _ = tx.send(UiMessage::Buffering { percent, stream_id }).await;
+
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+    }
+}
+
+/// Compare the size of the file that's starting against its TMDB runtime,
+/// warning when it's too small to be the whole thing
+fn spawn_runtime_check(
+    file_name: String,
+    size: u64,
+    (tmdb_id, media_type): (u64, Option<String>),
+    tmdb_apikey: Option<String>,
+    tx: mpsc::Sender<UiMessage>,
+) {
+    tokio::spawn(async move {
+        let Some(client) = TmdbClient::new(tmdb_apikey.as_deref()) else {
+            return;
+        };
+        let runtime = match (media_type.as_deref(), parse_episode_info(&file_name)) {
+            (Some("movie"), _) => client
+                .get_movie_details(tmdb_id)
+                .await
+                .map(|details| details.runtime),
+            (Some("tv" | "anime"), (Some(season), Some(episode))) => client
+                .get_season_details(tmdb_id, season)
+                .await
+                .map(|details| {
+                    details
+                        .episodes
+                        .iter()
+                        .find(|e| e.episode_number == episode)
+                        .and_then(|e| e.runtime)
+                }),
+            _ => Ok(None),
+        };
+        let runtime = match runtime {
+            Ok(Some(runtime)) if runtime > 0 => runtime,
+            Ok(_) => return,
+            Err(e) => {
+                debug!(tmdb_id, error = %e, "runtime lookup failed");
+                return;
+            }
+        };
+        if let Err(e) = streaming::check_file_size(&file_name, size, runtime) {
+            warn!(file = %file_name, size, runtime, "suspicious file");
+            let _ = tx
+                .send(UiMessage::Notify {
+                    message: e.to_string(),
+                    level: ToastLevel::Warning,
+                })
+                .await;
+        }
+    });
+}
+
+/// What extensions get told about the file that's playing
+fn current_media_info(app: &App) -> MediaInfo {
+    let (season, episode) = parse_episode_info(&app.current_file);
+    MediaInfo {
+        title: app.current_title.clone(),
+        file_name: app.current_file.clone(),
+        total_bytes: app.download_progress.total_bytes,
+        tmdb_id: app.current_tmdb_id,
+        year: app.current_year.map(|y| y as u32),
+        media_type: app.current_media_type.clone(),
+        poster_url: app.current_poster_url.clone(),
+        season,
+        episode,
+    }
+}
+
+/// Report mpv's playback position every few seconds, and when it's paused or
+/// resumed, until aborted
+fn spawn_position_poll(
+    socket: crate::streaming::IpcEndpoint,
+    tx: mpsc::Sender<UiMessage>,
+    progress_tx: ProgressSender,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        // Wait a bit for mpv to start
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        let mut paused = false;
+        loop {
+            if let Some((pos, dur)) = mpv::get_mpv_position(&socket).await {
+                let progress = streaming::calculate_progress(pos, dur);
+                progress_tx.playback(progress);
+            }
+            if let Some(now_paused) = mpv::get_property(&socket, "pause")
+                .await
+                .and_then(|p| p.as_bool())
+                && now_paused != paused
+            {
+                paused = now_paused;
+                let _ = tx.send(UiMessage::PlaybackPaused(paused)).await;
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    })
+}
+
+/// Turn the subtitle off in mpv when the audio playing is already in its
+/// language, it can still be turned on from mpv
+fn hide_redundant_subtitle(app: &mut App, socket: &crate::streaming::IpcEndpoint, config: &Config) {
+    let SubtitleStatus::Attached(subtitle) = app.subtitle_status.clone() else {
+        return;
+    };
+    let Some(audio) = mpv::audio_matching_subtitles(
+        &app.audio_tracks,
+        subtitle.language.as_deref(),
+        &config.subtitles.languages(),
+    )
+    .map(|track| track.label()) else {
+        return;
+    };
+    info!(%audio, "audio matches the subtitle language, hiding subtitles");
+    let socket = socket.clone();
+    tokio::spawn(async move {
+        if !mpv::set_property(&socket, "sid", serde_json::json!("no")).await {
+            warn!("failed to turn off subtitles");
+        }
+    });
+    app.push_toast(
+        format!("Audio is {}, subtitles off (j in mpv turns them on)", audio),
+        ToastLevel::Info,
+        ToastLevel::Info.ttl(),
+    );
+    app.subtitle_status = SubtitleStatus::Hidden { subtitle, audio };
+}
+
+/// Report mpv's audio tracks and chapters once the file's tracks are known
+fn spawn_audio_track_check(
+    socket: crate::streaming::IpcEndpoint,
+    stream_id: u64,
+    tx: mpsc::Sender<UiMessage>,
+) {
+    const ATTEMPTS: usize = 30;
+
+    tokio::spawn(async move {
+        for _ in 0..ATTEMPTS {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            let Some(tracks) = mpv::get_audio_tracks(&socket).await else {
+                continue;
+            };
+            if tracks.is_empty() {
+                continue;
+            }
+
+            let chapters = mpv::get_mpv_chapters(&socket).await.unwrap_or_default();
+            let duration = mpv::get_property(&socket, "duration")
+                .await
+                .and_then(|d| d.as_f64());
+            if let (false, Some(duration)) = (chapters.is_empty(), duration) {
+                let _ = tx
+                    .send(UiMessage::ChaptersLoaded { chapters, duration })
+                    .await;
+            }
+            let _ = tx
+                .send(UiMessage::AudioTracksLoaded {
+                    socket,
+                    tracks,
+                    stream_id,
+                })
+                .await;
+            return;
+        }
+    });
+}
+
+/// Move mpv `offset` chapters from the one it's in, staying within the file
+fn spawn_chapter_seek(
+    socket: crate::streaming::IpcEndpoint,
+    offset: i64,
+    count: usize,
+    tx: mpsc::Sender<UiMessage>,
+) {
+    tokio::spawn(async move {
+        // -1 before the first chapter
+        let Some(current) = mpv::get_property(&socket, "chapter")
+            .await
+            .and_then(|c| c.as_i64())
+        else {
+            return;
+        };
+        let target = (current + offset).clamp(0, count as i64 - 1);
+        if target != current && mpv::set_property(&socket, "chapter", target.into()).await {
+            let _ = tx.send(UiMessage::ChapterChanged(target as usize)).await;
+        }
+    });
+}
+
+/// Switch mpv to an audio track, reporting back with a toast
+fn spawn_audio_track_switch(
+    socket: crate::streaming::IpcEndpoint,
+    track: AudioTrack,
+    tx: mpsc::Sender<UiMessage>,
+) {
+    tokio::spawn(async move {
+        let notice = if mpv::set_property(&socket, "aid", track.id.into()).await {
+            info!(track = %track.label(), "switched audio track");
+            UiMessage::Notify {
+                message: format!("Audio: switched to {}", track.label()),
+                level: ToastLevel::Info,
+            }
+        } else {
+            UiMessage::Notify {
+                message: format!("Audio: couldn't switch to {}", track.label()),
+                level: ToastLevel::Warning,
+            }
+        };
+        let _ = tx.send(notice).await;
+    });
+}
+
+/// Open the current stream in another player. Unless `multi_player` is set it
+/// replaces the main player and reports PlayerExited like it, otherwise it runs
+/// next to it until closed or the stream stops.
+fn spawn_player_switch(
+    app: &App,
+    config: &Config,
+    player: String,
+    session: std::sync::Arc<StreamingSession>,
+    torrent_info: &crate::streaming::TorrentInfo,
+    cancel: CancellationToken,
+    tx: mpsc::Sender<UiMessage>,
+) {
+    let Some(stream_url) = app.streaming_state.stream_url() else {
+        return;
+    };
+    let stream_url = stream_url.to_string();
+    let primary = !config.player.multi_player;
+    // Configured args are meant for the configured player
+    let player_args = if player == config.player.command {
+        config.player.args.clone()
+    } else {
+        Vec::new()
+    };
+    let player_language = config.subtitles.player_language();
+    let audio_language = config.player.audio_language.clone();
+    let subtitles_enabled = config.subtitles.enabled;
+    let subtitle_languages = config.subtitles.languages();
+    let opensubtitles = opensubtitles_client(config);
+    let media = app.media_context();
+    let subtitle_files = torrent_info.subtitle_files.clone();
+    let file_size = torrent_info
+        .video_files
+        .iter()
+        .find(|f| f.name == app.current_file)
+        .map_or(torrent_info.selected_file.size, |f| f.size);
+    let start_percent = Some(app.playback_progress);
+    let torrent_id = torrent_info.id;
+    let stream_id = app.stream_id;
+    let progress_tx = app.progress.clone();
+
+    tokio::spawn(async move {
+        let progress_handle = primary.then(|| {
+            spawn_progress_poll(
+                session.clone(),
+                torrent_id,
+                tx.clone(),
+                progress_tx.clone(),
+                stream_id,
+            )
+        });
+
+        let subtitle_url = if subtitles_enabled {
+            resolve_subtitle(
+                &subtitle_files,
+                &subtitle_languages,
+                opensubtitles.as_ref(),
+                &media,
+                (&stream_url, file_size),
+                &tx,
+            )
+            .await
+            .or_else(|| fallback_subtitle(&subtitle_files))
+            .map(|subtitle| subtitle.url)
+        } else {
+            None
+        };
+
+        info!(%player, primary, "opening stream in another player");
+        let result = if cancel.is_cancelled() {
+            None
+        } else {
+            Some(
+                streaming::launch_player(
+                    &player,
+                    &player_args,
+                    &stream_url,
+                    subtitle_url.as_deref(),
+                    player_language.as_deref(),
+                    &audio_language,
+                    start_percent,
+                )
+                .await,
+            )
+        };
+
+        match result {
+            Some(Ok(mut handle)) => {
+                if primary {
+                    let _ = tx
+                        .send(UiMessage::PlayerStarted {
+                            stream_id,
+                            cancel: cancel.clone(),
+                        })
+                        .await;
+                }
+                // Cleanup waits for this to be dropped
+                let _player = session.player_running();
+                let position_handle = match &handle.ipc_socket {
+                    Some(socket) if primary => {
+                        spawn_audio_track_check(socket.clone(), stream_id, tx.clone());
+                        Some(spawn_position_poll(socket.clone(), tx.clone(), progress_tx))
+                    }
+                    _ => None,
+                };
+
+                tokio::select! {
+                    _ = handle.child.wait() => {
+                        info!(%player, "player exited normally");
+                    }
+                    _ = cancel.cancelled() => {
+                        info!(%player, "cancellation requested, killing player");
+                        let _ = handle.child.kill().await;
+                    }
+                }
+
+                if let Some(h) = position_handle {
+                    h.abort();
+                }
+                if let Some(endpoint) = handle.ipc_socket {
+                    endpoint.cleanup();
+                }
+            }
+            Some(Err(e)) => {
+                error!(error = %e, "failed to launch player");
+                let _ = tx
+                    .send(UiMessage::Notify {
+                        message: e.to_string(),
+                        level: ToastLevel::Error,
+                    })
+                    .await;
+                if !primary {
+                    return;
+                }
+            }
+            None => {}
+        }
+
+        if let Some(h) = progress_handle {
+            h.abort();
+        }
+        let message = if primary {
+            // Cleanup (or seeding) is up to the PlayerExited handler
+            UiMessage::PlayerExited
+        } else {
+            UiMessage::ExtraPlayerExited(player)
+        };
+        let _ = tx.send(message).await;
+    });
+}
+
+/// Remove session directories left behind by crashed or killed runs once the
+/// cache is over its limit, and warn if it's still over `max_cache_gb` after
+fn spawn_stale_session_cleanup(
+    tx: &mpsc::Sender<UiMessage>,
+    config: &Config,
+    pending_path: PathBuf,
+) {
+    let tx = tx.clone();
+    let storage = config.storage.clone();
+    let temp_dir = storage.temp_dir();
+    let max_bytes = storage.max_cache_bytes();
+    let run_started = std::time::SystemTime::now();
+
+    tokio::spawn(async move {
+        // Left over from a cleanup that failed last time
+        let (deferred, deferred_freed) = PendingCleanup::retry(&pending_path).await;
+        let (removed, freed) =
+            streaming::cleanup_stale_sessions(&temp_dir, run_started, max_bytes).await;
+        let (removed, freed) = (removed + deferred, freed + deferred_freed);
+        if removed > 0 {
+            info!(removed, freed, "cleaned up stale sessions");
+            let _ = tx
+                .send(UiMessage::Notify {
+                    message: format!(
+                        "Removed {} stale session(s), freed {}",
+                        removed,
+                        format_gb(freed)
+                    ),
+                    level: ToastLevel::Info,
+                })
+                .await;
+        }
+
+        let size = streaming::cache_size(&temp_dir).await;
+        if storage.is_over_cache_limit(size) {
+            warn!(
+                size,
+                limit_gb = storage.max_cache_gb,
+                "cache over its limit"
+            );
+            let _ = tx
+                .send(UiMessage::Notify {
+                    message: format!(
+                        "Cache uses {}, over the limit - clear it in Settings > Storage",
+                        format_gb(size)
+                    ),
+                    level: ToastLevel::Warning,
+                })
+                .await;
+        }
+    });
+}
+
+/// Clean up a session in the background, toasting the space freed or that
+/// the files were left for the next launch
+fn spawn_cleanup(
+    app: &mut App,
+    session: std::sync::Arc<StreamingSession>,
+    tx: &mpsc::Sender<UiMessage>,
+) {
+    let pending_path = app.config_location.pending_cleanup_path();
+    let tx = tx.clone();
+    app.cleanups.spawn(async move {
+        cleanup_session(&session, &pending_path, &tx).await;
+    });
+}
+
+/// Clean up a session from a task, toasting the outcome like [`spawn_cleanup`]
+async fn cleanup_session(
+    session: &StreamingSession,
+    pending_path: &Path,
+    tx: &mpsc::Sender<UiMessage>,
+) {
+    let cleanup = session.cleanup().await;
+    if let Some((message, level)) = cleanup_report(cleanup, pending_path) {
+        let _ = tx.send(UiMessage::Notify { message, level }).await;
+    }
+}
+
+/// Remember a directory cleanup couldn't remove, returns the toast for the outcome
+fn cleanup_report(cleanup: Cleanup, pending_path: &Path) -> Option<(String, ToastLevel)> {
+    match cleanup {
+        Cleanup::Daemon | Cleanup::Freed(0) => None,
+        Cleanup::Freed(bytes) => {
+            info!(bytes, "cleaned up session");
+            Some((format!("Freed {}", format_gb(bytes)), ToastLevel::Info))
+        }
+        Cleanup::Deferred(dir) => {
+            PendingCleanup::defer(pending_path, &dir);
+            Some((
+                format!(
+                    "Couldn't remove {} yet, will retry on next launch",
+                    dir.display()
+                ),
+                ToastLevel::Warning,
+            ))
+        }
+    }
+}
+
+/// Look for installed players, once per run
+fn spawn_player_detection(app: &mut App, tx: &mpsc::Sender<UiMessage>) {
+    if app.detected_players.is_some() || app.is_detecting_players {
+        return;
+    }
+    app.is_detecting_players = true;
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let players = streaming::find_players().await;
+        info!(count = players.len(), "detected players");
+        let _ = tx.send(UiMessage::PlayersDetected(players)).await;
+    });
+}
+
+/// Make a detected player the configured one, with the args it's set up with
+fn use_detected_player(config: &mut Config, player: &DetectedPlayer) {
+    config.player.command = player.command.clone();
+    config.player.args = player
+        .default_args
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+}
+
+/// Measure the temp dir and the space left beside it for the Storage
+/// settings and wizard step
+fn spawn_cache_size(app: &mut App, config: &Config, tx: &mpsc::Sender<UiMessage>) {
+    app.cache_size = None;
+    app.free_space = None;
+    let tx = tx.clone();
+    let temp_dir = config.storage.temp_dir();
+    tokio::spawn(async move {
+        let free = streaming::free_space(&temp_dir).await;
+        let _ = tx.send(UiMessage::FreeSpace(free)).await;
+        let size = streaming::cache_size(&temp_dir).await;
+        let _ = tx.send(UiMessage::CacheSize(size)).await;
+    });
+}
+
+/// Remove every session directory in the temp dir that no running stream
+/// (here or in another instance) still uses
+fn spawn_cache_clear(app: &mut App, config: &Config, tx: &mpsc::Sender<UiMessage>) {
+    app.is_clearing_cache = true;
+    let tx = tx.clone();
+    let temp_dir = config.storage.temp_dir();
+    tokio::spawn(async move {
+        // No size limit, every leftover goes
+        let (removed, freed) =
+            streaming::cleanup_stale_sessions(&temp_dir, std::time::SystemTime::now(), 0).await;
+        info!(removed, freed, "cleared cache");
+        let size = streaming::cache_size(&temp_dir).await;
+        let free = streaming::free_space(&temp_dir).await;
+        let _ = tx.send(UiMessage::FreeSpace(free)).await;
+        let _ = tx
+            .send(UiMessage::CacheCleared {
+                removed,
+                freed,
+                size,
+            })
+            .await;
+    });
+}
+
+fn format_gb(bytes: u64) -> String {
+    format!("{:.1} GB", bytes as f64 / 1024.0 / 1024.0 / 1024.0)
+}
+
+/// Poll the feeds in `rss_rules` for new releases matching each rule
+fn spawn_rss_poll(tx: &mpsc::Sender<UiMessage>, config: &Config) {
+    let mut watchers: Vec<RssWatcher> = config
+        .rss_rules
+        .iter()
+        .cloned()
+        .filter_map(|rule| match RssWatcher::new(rule) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!(error = %e, "skipping rss rule with invalid pattern");
+                None
+            }
+        })
+        .collect();
+    if watchers.is_empty() {
+        return;
+    }
+
+    let tx = tx.clone();
+    let poll_interval = config.rss.poll_interval();
+
+    tokio::spawn(async move {
+        let client = crate::http::client();
+        let mut ticker = tokio::time::interval(poll_interval);
+
+        while !tx.is_closed() {
+            ticker.tick().await;
+
+            for watcher in &mut watchers {
+                let items = match rss::fetch_feed(&client, &watcher.rule.feed_url).await {
+                    Ok(items) => items,
+                    Err(e) => {
+                        warn!(feed = %watcher.rule.feed_url, error = %e, "rss poll failed");
+                        continue;
+                    }
+                };
+
+                for result in watcher.new_matches(items) {
+                    info!(rule = %watcher.rule.name(), title = %result.title, "rss match");
+                    let _ = tx
+                        .send(UiMessage::RssMatch {
+                            rule_name: watcher.rule.name().to_string(),
+                            result,
+                            auto_stream: watcher.rule.auto_stream,
+                        })
+                        .await;
+                }
+            }
+        }
+    });
+}
+
+/// Stream a release picked up by an RSS rule
+fn start_rss_stream(
+    app: &mut App,
+    result: TorrentResult,
+    tx: &mpsc::Sender<UiMessage>,
+    config: &Config,
+    streaming_cancel: &mut Option<CancellationToken>,
+) {
+    let Some(url) = result.get_torrent_url() else {
+        return;
+    };
+    info!(title = %result.title, "starting rss stream");
+    app.current_title = result.title;
+    app.current_tmdb_id = None;
+    app.current_year = None;
+    app.current_media_type = None;
+    app.current_poster_url = None;
+    start_stream(
+        app,
+        StreamSource::torrent(url),
+        tx,
+        config,
+        streaming_cancel,
+    );
+}
+
+/// Count the peers of a torrent and ask whether to stream it, adding it
+/// only then. None when the user backed out or the stream was cancelled.
+async fn probe_torrent(
+    session: &StreamingSession,
+    url: &str,
+    tx: &mpsc::Sender<UiMessage>,
+    stream_id: u64,
+    cancel: &CancellationToken,
+) -> Result<Option<TorrentInfo>, StreamError> {
+    let probe = session.probe(url, streaming::PROBE_BUDGET).await?;
+    let (reply, answer) = oneshot::channel();
+    let _ = tx
+        .send(UiMessage::TorrentProbed {
+            probe,
+            stream_id,
+            reply,
+        })
+        .await;
+    let stream = tokio::select! {
+        answer = answer => answer.unwrap_or(false),
+        _ = cancel.cancelled() => false,
+    };
+    if !stream {
+        return Ok(None);
+    }
+    session.add_torrent(url).await.map(Some)
+}
+
+/// Name of the current stream for user-facing messages
+fn display_name(app: &App) -> &str {
+    if app.current_title.is_empty() {
+        app.current_file
+            .rsplit('/')
+            .next()
+            .unwrap_or(&app.current_file)
+    } else {
+        &app.current_title
+    }
+}
+
+fn notify_stream_ready(app: &App) {
+    notifications::notify(
+        "Stream ready",
+        &format!("Stream ready for {}", display_name(app)),
+        Urgency::Normal,
+    );
+}
+
+/// Write a diagnostics bundle to the current directory for a bug report
+fn write_diagnostics(app: &mut App, config: &Config) {
+    let now = indexer_health::now_secs();
+    let log_lines = diagnostics::recent_log_lines(&app.log_path);
+    let report = {
+        let health = app.indexer_health.lock().unwrap();
+        diagnostics::build_report(
+            config,
+            &app.doctor_results,
+            health.last_search(),
+            &log_lines,
+            now,
+        )
+    };
+    let dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    match diagnostics::write_bundle(&dir, &report, now) {
+        Ok(path) => {
+            info!(path = %path.display(), "wrote diagnostics bundle");
+            app.push_toast(
+                format!("Diagnostics written to {}", path.display()),
+                ToastLevel::Success,
+                ToastLevel::Success.ttl(),
+            );
+        }
+        Err(e) => {
+            error!(error = %e, "failed to write diagnostics bundle");
+            app.push_toast(
+                format!("Failed to write diagnostics: {}", e),
+                ToastLevel::Error,
+                ToastLevel::Error.ttl(),
+            );
+        }
+    }
+}
+
+/// Save the config and report the outcome as a toast
+fn save_config(app: &mut App, config: &Config) -> bool {
+    match config.save(&app.config_location) {
+        Ok(()) => {
+            info!("Config saved");
+            // Prowlarr may have moved, start over with a fresh indexer list
+            app.search_service = SearchService::new(&config.prowlarr)
+                .with_usenet(config.usenet.is_some())
+                .shared();
+            if app.settings_saved.is_some() {
+                app.settings_saved = Some(config.clone());
+            }
+            app.push_toast(
+                "Config saved",
+                ToastLevel::Success,
+                ToastLevel::Success.ttl(),
+            );
             true
         }
-        _ => false,
+        Err(e) => {
+            error!("Failed to save config: {}", e);
+            app.push_toast(
+                format!("Failed to save config: {}", e),
+                ToastLevel::Error,
+                ToastLevel::Error.ttl(),
+            );
+            false
+        }
+    }
+}
+
+/// Pasted text goes into whichever text input has focus, in one piece
+fn handle_paste(app: &mut App, text: &str, tx: &mpsc::Sender<UiMessage>, config: &Config) {
+    let text: String = text.chars().filter(|c| !c.is_control()).collect();
+    match app.view {
+        View::Wizard if app.wizard_editing => app.wizard_edit_buffer.push_str(&text),
+        View::Settings if app.settings_editing => {
+            app.settings_edit_buffer.push_str(&text);
+            app.settings_error = None;
+        }
+        View::Discovery if app.is_filtering_discovery => {
+            app.discovery_filter.push_str(&text);
+            app.apply_discovery_filter();
+        }
+        View::Search if !app.is_searching => {
+            app.search_input.push_str(&text);
+            app.suggestions.clear();
+            app.selected_suggestion = 0;
+            spawn_suggestion_fetch(app, tx, config);
+        }
+        _ => {}
+    }
+}
+
+/// Show the settings view, remembering the config to diff changes against
+fn open_settings(app: &mut App, config: &Config, tx: &mpsc::Sender<UiMessage>) {
+    app.view = View::Settings;
+    app.settings_saved = Some(config.clone());
+    spawn_player_detection(app, tx);
+    if app.settings_section == SettingsSection::Storage {
+        spawn_cache_size(app, config, tx);
+    }
+}
+
+fn close_settings(app: &mut App) {
+    app.view = View::Discovery;
+    app.settings_field_index = 0;
+    app.settings_saved = None;
+}
+
+/// Mark settings as modified and apply the ones that take effect immediately
+fn settings_changed(app: &mut App, config: &Config) {
+    app.settings_dirty = true;
+    if app.settings_section == SettingsSection::Network
+        && let Err(e) = crate::http::configure(&config.network)
+    {
+        warn!("Network settings not applied: {}", e);
     }
 }
 
@@ -2241,8 +5997,26 @@ fn get_wizard_field_value(app: &App, config: &Config) -> String {
                 .unwrap_or_default(),
             _ => String::new(),
         },
-        WizardStep::Player => match app.wizard_field_index {
-            0 => config.player.command.clone(),
+        WizardStep::Player if app.wizard_field_index == app.detected_players().len() => {
+            config.player.command.clone()
+        }
+        WizardStep::Storage => match app.wizard_field_index {
+            0 => config.storage.temp_dir().display().to_string(),
+            _ => String::new(),
+        },
+        WizardStep::Integrations => match app.wizard_field_index {
+            0 => config
+                .extensions
+                .simkl
+                .client_id
+                .clone()
+                .unwrap_or_default(),
+            1 => config
+                .extensions
+                .simkl
+                .access_token
+                .clone()
+                .unwrap_or_default(),
             _ => String::new(),
         },
         _ => String::new(),
@@ -2250,29 +6024,38 @@ fn get_wizard_field_value(app: &App, config: &Config) -> String {
 }
 
 /// Apply the wizard edit buffer to the config field
-fn apply_wizard_edit(app: &App, config: &mut Config) {
+fn apply_wizard_edit(app: &App, config: &mut Config) -> Result<(), String> {
     let value = app.wizard_edit_buffer.trim().to_string();
 
     match app.wizard_step {
-        WizardStep::Prowlarr => match app.wizard_field_index {
-            0 => config.prowlarr.url = value,
-            1 => config.prowlarr.apikey = value,
-            _ => {}
-        },
-        WizardStep::Tmdb => {
-            if app.wizard_field_index == 0 {
-                if value.is_empty() {
-                    config.tmdb = None;
-                } else {
-                    config.tmdb = Some(crate::config::TmdbConfig { apikey: value });
-                }
+        // Checked like in the settings view
+        WizardStep::Prowlarr | WizardStep::Tmdb => {
+            let section = match app.wizard_step {
+                WizardStep::Prowlarr => SettingsSection::Prowlarr,
+                _ => SettingsSection::Tmdb,
+            };
+            if let Some(field) = settings::fields(section).get(app.wizard_field_index) {
+                return field.apply(config, &value);
             }
         }
-        WizardStep::Player => {
-            if app.wizard_field_index == 0 {
-                config.player.command = value;
+        WizardStep::Player if app.wizard_field_index == app.detected_players().len() => {
+            config.player.command = value;
+        }
+        WizardStep::Storage if app.wizard_field_index == 0 => {
+            return settings::temp_dir_field().apply(config, &value);
+        }
+        WizardStep::Integrations => {
+            let simkl = &mut config.extensions.simkl;
+            let value = (!value.is_empty()).then_some(value);
+            match app.wizard_field_index {
+                0 => simkl.client_id = value,
+                1 => simkl.access_token = value,
+                _ => {}
             }
+            // Turn Simkl on once both credentials are in
+            simkl.enabled = simkl.client_id.is_some() && simkl.access_token.is_some();
         }
         _ => {}
     }
+    Ok(())
 }
@@ -0,0 +1,464 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use super::progress::ProgressSender;
+use super::{
+    UiMessage, choose_subtitle, cleanup_session, fallback_subtitle, opensubtitles_client,
+    resolve_subtitle, spawn_audio_track_check, spawn_position_poll, spawn_progress_poll,
+    wait_for_pre_roll,
+};
+use crate::config::Config;
+use crate::opensubtitles::OpenSubtitlesClient;
+use crate::streaming::{self, PlayerHandle, StreamingSession, TorrentInfo, VideoFile};
+
+/// What's playing, beyond the file itself
+#[derive(Debug, Clone, Default)]
+pub struct MediaContext {
+    /// Looked up on OpenSubtitles when the torrent has no subtitles
+    pub tmdb_id: Option<u64>,
+    /// Searched for over XML-RPC, which can't look up TMDB ids
+    pub title: String,
+}
+
+/// Where the subtitle the player starts with came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleSource {
+    /// A subtitle file shipped in the torrent
+    Torrent,
+    OpenSubtitles,
+}
+
+impl SubtitleSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            SubtitleSource::Torrent => "torrent file",
+            SubtitleSource::OpenSubtitles => "OpenSubtitles",
+        }
+    }
+}
+
+/// Subtitle to start the player with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subtitle {
+    pub url: String,
+    pub source: SubtitleSource,
+    /// None for a torrent file whose name doesn't give its language away
+    pub language: Option<String>,
+}
+
+/// Plays files of a stream in the configured player and sees each one
+/// through: download progress, subtitles, the pre-roll buffer, mpv IPC polling
+/// and killing the player on cancel. Every run ends with `PlayerExited`
+/// (`StreamError` if the player didn't start), cleanup or seeding is up to
+/// its handler.
+#[derive(Clone)]
+pub struct PlaybackController {
+    session: Arc<StreamingSession>,
+    cancel: CancellationToken,
+    tx: mpsc::Sender<UiMessage>,
+    progress_tx: ProgressSender,
+    stream_id: u64,
+    /// Where a directory cleanup couldn't remove is recorded
+    pending_cleanup: PathBuf,
+    player_command: String,
+    player_args: Vec<String>,
+    player_language: Option<String>,
+    audio_language: Vec<String>,
+    subtitles_enabled: bool,
+    subtitle_languages: Vec<String>,
+    opensubtitles: Option<OpenSubtitlesClient>,
+    pre_roll_percent: f64,
+}
+
+/// One file to play
+struct Launch {
+    file: VideoFile,
+    torrent_id: usize,
+    subtitle_files: Vec<streaming::SubtitleFile>,
+    media: MediaContext,
+    /// Set when picking up where a stalled stream left off
+    resume_at: Option<f64>,
+}
+
+impl PlaybackController {
+    pub fn new(
+        config: &Config,
+        session: Arc<StreamingSession>,
+        cancel: CancellationToken,
+        tx: mpsc::Sender<UiMessage>,
+        progress_tx: ProgressSender,
+        stream_id: u64,
+        pending_cleanup: PathBuf,
+    ) -> Self {
+        Self {
+            session,
+            cancel,
+            tx,
+            progress_tx,
+            stream_id,
+            pending_cleanup,
+            player_command: config.player.command.clone(),
+            player_args: config.player.args.clone(),
+            player_language: config.subtitles.player_language(),
+            audio_language: config.player.audio_language.clone(),
+            subtitles_enabled: config.subtitles.enabled,
+            subtitle_languages: config.subtitles.languages(),
+            opensubtitles: opensubtitles_client(config),
+            pre_roll_percent: config.streaming.pre_roll_buffer_percent as f64,
+        }
+    }
+
+    /// Play `file` from the start, asking the user when no subtitles are
+    /// found in their languages
+    pub fn play(
+        &self,
+        file: &VideoFile,
+        torrent_info: &TorrentInfo,
+        media: MediaContext,
+    ) -> JoinHandle<()> {
+        self.spawn(file, torrent_info, media, None)
+    }
+
+    /// Play `file` again from `percent`, after the stream was restarted. Any
+    /// subtitle will do, the user already answered for this file.
+    pub fn resume(
+        &self,
+        file: &VideoFile,
+        torrent_info: &TorrentInfo,
+        media: MediaContext,
+        percent: f64,
+    ) -> JoinHandle<()> {
+        self.spawn(file, torrent_info, media, Some(percent))
+    }
+
+    fn spawn(
+        &self,
+        file: &VideoFile,
+        torrent_info: &TorrentInfo,
+        media: MediaContext,
+        resume_at: Option<f64>,
+    ) -> JoinHandle<()> {
+        let launch = Launch {
+            file: file.clone(),
+            torrent_id: torrent_info.id,
+            subtitle_files: torrent_info.subtitle_files.clone(),
+            media,
+            resume_at,
+        };
+        tokio::spawn(self.clone().run(launch))
+    }
+
+    async fn run(self, launch: Launch) {
+        let progress_handle = spawn_progress_poll(
+            self.session.clone(),
+            launch.torrent_id,
+            self.tx.clone(),
+            self.progress_tx.clone(),
+            self.stream_id,
+        );
+
+        let subtitle = self.subtitle(&launch).await;
+        let _ = self
+            .tx
+            .send(UiMessage::SubtitleChosen {
+                subtitle: subtitle.clone(),
+                stream_id: self.stream_id,
+            })
+            .await;
+
+        if launch.resume_at.is_none() {
+            wait_for_pre_roll(
+                &self.session,
+                launch.torrent_id,
+                self.pre_roll_percent,
+                &self.cancel,
+                &self.tx,
+                self.stream_id,
+            )
+            .await;
+        }
+        if self.cancel.is_cancelled() {
+            progress_handle.abort();
+            cleanup_session(&self.session, &self.pending_cleanup, &self.tx).await;
+            let _ = self.tx.send(UiMessage::PlayerExited).await;
+            return;
+        }
+        if launch.resume_at.is_none() {
+            let _ = self
+                .tx
+                .send(UiMessage::PlayerLaunching {
+                    file_name: launch.file.name.clone(),
+                    total_bytes: launch.file.size,
+                    stream_id: self.stream_id,
+                })
+                .await;
+        }
+
+        info!(player = %self.player_command, "launching player");
+        match streaming::launch_player(
+            &self.player_command,
+            &self.player_args,
+            &launch.file.stream_url,
+            subtitle.as_ref().map(|s| s.url.as_str()),
+            self.player_language.as_deref(),
+            &self.audio_language,
+            launch.resume_at,
+        )
+        .await
+        {
+            Ok(handle) => {
+                // Closing the player alone (to switch players) leaves the stream up
+                let player_cancel = self.cancel.child_token();
+                let _ = self
+                    .tx
+                    .send(UiMessage::PlayerStarted {
+                        stream_id: self.stream_id,
+                        cancel: player_cancel.clone(),
+                    })
+                    .await;
+                self.watch_player(handle, &player_cancel).await
+            }
+            Err(e) => {
+                error!(error = %e, "failed to launch player");
+                let _ = self
+                    .tx
+                    .send(UiMessage::StreamError {
+                        error: e.to_string(),
+                        stream_id: self.stream_id,
+                    })
+                    .await;
+                progress_handle.abort();
+                return;
+            }
+        }
+
+        // Cleanup (or seeding) is up to the PlayerExited handler
+        progress_handle.abort();
+        let _ = self.tx.send(UiMessage::PlayerExited).await;
+    }
+
+    /// Subtitle to start the player with, if any
+    async fn subtitle(&self, launch: &Launch) -> Option<Subtitle> {
+        if !self.subtitles_enabled {
+            return None;
+        }
+        let video = (launch.file.stream_url.as_str(), launch.file.size);
+        if launch.resume_at.is_some() {
+            resolve_subtitle(
+                &launch.subtitle_files,
+                &self.subtitle_languages,
+                self.opensubtitles.as_ref(),
+                &launch.media,
+                video,
+                &self.tx,
+            )
+            .await
+            .or_else(|| fallback_subtitle(&launch.subtitle_files))
+        } else {
+            choose_subtitle(
+                &launch.subtitle_files,
+                &self.subtitle_languages,
+                self.opensubtitles.as_ref(),
+                &launch.media,
+                video,
+                &self.tx,
+                self.stream_id,
+            )
+            .await
+        }
+    }
+
+    /// Poll the player over IPC until it exits, or kill it on cancel
+    async fn watch_player(&self, mut handle: PlayerHandle, cancel: &CancellationToken) {
+        // Cleanup waits for this to be dropped
+        let _player = self.session.player_running();
+        let position_handle = handle.ipc_socket.as_ref().map(|socket| {
+            spawn_audio_track_check(socket.clone(), self.stream_id, self.tx.clone());
+            spawn_position_poll(socket.clone(), self.tx.clone(), self.progress_tx.clone())
+        });
+
+        tokio::select! {
+            _ = handle.child.wait() => {
+                info!("player exited normally");
+            }
+            _ = cancel.cancelled() => {
+                info!("cancellation requested, killing player");
+                let _ = handle.child.kill().await;
+            }
+        }
+
+        if let Some(h) = position_handle {
+            h.abort();
+        }
+        if let Some(endpoint) = handle.ipc_socket {
+            endpoint.cleanup();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A daemon session with one torrent, which never gets any stats
+    async fn session() -> (MockServer, Arc<StreamingSession>) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "server": "rqbit",
+                "version": "8.0.0"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/torrents"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "torrents": [] })))
+            .mount(&server)
+            .await;
+        let session = StreamingSession::connect(&server.uri()).await.unwrap();
+        (server, Arc::new(session))
+    }
+
+    fn torrent(stream_url: &str) -> TorrentInfo {
+        let file = VideoFile {
+            name: "Severance.S02E01.1080p.mkv".to_string(),
+            file_idx: 0,
+            size: 1_500_000_000,
+            stream_url: stream_url.to_string(),
+        };
+        TorrentInfo {
+            id: 0,
+            name: "Severance.S02.1080p".to_string(),
+            video_files: vec![file.clone()],
+            extra_files: Vec::new(),
+            selected_file: file,
+            subtitle_files: Vec::new(),
+        }
+    }
+
+    /// Controller running `script` through sh as the player
+    fn controller(
+        session: Arc<StreamingSession>,
+        script: &str,
+    ) -> (
+        PlaybackController,
+        CancellationToken,
+        mpsc::Receiver<UiMessage>,
+    ) {
+        let mut config = Config::default();
+        config.player.command = "sh".to_string();
+        config.player.args = vec!["-c".to_string(), script.to_string()];
+        config.subtitles.enabled = false;
+        config.streaming.pre_roll_buffer_percent = 0.0;
+        let cancel = CancellationToken::new();
+        let (tx, rx) = mpsc::channel(64);
+        let controller = PlaybackController::new(
+            &config,
+            session,
+            cancel.clone(),
+            tx,
+            ProgressSender::new(),
+            7,
+            std::env::temp_dir().join("ferristream-test-pending-cleanup.toml"),
+        );
+        (controller, cancel, rx)
+    }
+
+    /// Messages about the player, leaving out download progress
+    fn player_messages(rx: &mut mpsc::Receiver<UiMessage>) -> Vec<String> {
+        let mut messages = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            match message {
+                UiMessage::PlayerLaunching {
+                    file_name,
+                    stream_id,
+                    ..
+                } => messages.push(format!("launching {} ({})", file_name, stream_id)),
+                UiMessage::PlayerExited => messages.push("exited".to_string()),
+                UiMessage::StreamError { stream_id, .. } => {
+                    messages.push(format!("error ({})", stream_id))
+                }
+                _ => {}
+            }
+        }
+        messages
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_play_until_player_exits() {
+        let (server, session) = session().await;
+        let torrent = torrent(&format!("{}/torrents/0/stream/0", server.uri()));
+        let (controller, _cancel, mut rx) = controller(session.clone(), "sleep 0.2");
+
+        let run = controller.play(&torrent.selected_file, &torrent, MediaContext::default());
+        tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            player_messages(&mut rx),
+            ["launching Severance.S02E01.1080p.mkv (7)", "exited"]
+        );
+
+        // Resuming doesn't announce a new file
+        let run = controller.resume(
+            &torrent.selected_file,
+            &torrent,
+            MediaContext::default(),
+            40.0,
+        );
+        tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(player_messages(&mut rx), ["exited"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_kills_player() {
+        let (server, session) = session().await;
+        let torrent = torrent(&format!("{}/torrents/0/stream/0", server.uri()));
+        let (controller, cancel, mut rx) = controller(session, "sleep 30");
+
+        let run = controller.play(&torrent.selected_file, &torrent, MediaContext::default());
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        cancel.cancel();
+        tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            player_messages(&mut rx),
+            ["launching Severance.S02E01.1080p.mkv (7)", "exited"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_player_that_wont_start() {
+        let (server, session) = session().await;
+        let torrent = torrent(&format!("{}/torrents/0/stream/0", server.uri()));
+        let (mut controller, _cancel, mut rx) = controller(session, "");
+        controller.player_command = "ferristream-no-such-player".to_string();
+
+        let run = controller.play(&torrent.selected_file, &torrent, MediaContext::default());
+        tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            player_messages(&mut rx),
+            ["launching Severance.S02E01.1080p.mkv (7)", "error (7)"]
+        );
+    }
+}
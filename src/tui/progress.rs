@@ -0,0 +1,195 @@
+use tokio::sync::watch;
+
+use super::UiMessage;
+use super::app::DownloadProgress;
+
+/// Download stats of a stream, sent as a `ProgressUpdate`
+#[derive(Debug, Clone)]
+pub struct DownloadUpdate {
+    pub progress: DownloadProgress,
+    pub stream_id: u64,
+    /// Streaming session and torrent the byte counters are for
+    pub torrent: (u64, usize),
+}
+
+/// Carries the updates background polls make several times a second. Unlike
+/// the `UiMessage` channel only the latest value is kept, so the polls never
+/// wait on a stalled UI loop and it doesn't catch up on a burst of stale ones.
+#[derive(Clone)]
+pub struct ProgressSender {
+    download: watch::Sender<Option<DownloadUpdate>>,
+    playback: watch::Sender<Option<f64>>,
+}
+
+/// Reads what a [`ProgressSender`] sent, once per frame
+pub struct ProgressReceiver {
+    download: watch::Receiver<Option<DownloadUpdate>>,
+    playback: watch::Receiver<Option<f64>>,
+}
+
+impl ProgressSender {
+    pub fn new() -> Self {
+        Self {
+            download: watch::Sender::new(None),
+            playback: watch::Sender::new(None),
+        }
+    }
+
+    /// Receiver of updates sent from now on
+    pub fn subscribe(&self) -> ProgressReceiver {
+        ProgressReceiver {
+            download: self.download.subscribe(),
+            playback: self.playback.subscribe(),
+        }
+    }
+
+    /// Replace the download stats, false once nobody reads them
+    pub fn download(&self, update: DownloadUpdate) -> bool {
+        self.download.send(Some(update)).is_ok()
+    }
+
+    /// Replace mpv's playback position (percent watched), false once nobody reads it
+    pub fn playback(&self, percent: f64) -> bool {
+        self.playback.send(Some(percent)).is_ok()
+    }
+}
+
+impl Default for ProgressSender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressReceiver {
+    /// Latest updates since the last call, as the messages they stand for
+    pub fn take(&mut self) -> impl Iterator<Item = UiMessage> + use<> {
+        let download = changed(&mut self.download).map(|update| UiMessage::ProgressUpdate {
+            progress: update.progress,
+            stream_id: update.stream_id,
+            torrent: update.torrent,
+        });
+        let playback = changed(&mut self.playback).map(UiMessage::PlaybackProgress);
+        download.into_iter().chain(playback)
+    }
+}
+
+fn changed<T: Clone>(rx: &mut watch::Receiver<Option<T>>) -> Option<T> {
+    if rx.has_changed().unwrap_or(false) {
+        rx.borrow_and_update().clone()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::spawn_progress_poll;
+    use super::*;
+    use crate::streaming::StreamingSession;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio::time::timeout;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn update(stream_id: u64) -> DownloadUpdate {
+        DownloadUpdate {
+            progress: DownloadProgress::default(),
+            stream_id,
+            torrent: (1, 0),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_progress_flood_keeps_events() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "server": "rqbit",
+                "version": "8.0.0"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/torrents"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "torrents": [] })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/torrents/0/stats/v1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(include_str!("../../tests/fixtures/rqbit/stats_v8.json")),
+            )
+            .mount(&server)
+            .await;
+        let session = Arc::new(StreamingSession::connect(&server.uri()).await.unwrap());
+
+        let (tx, mut rx) = mpsc::channel::<UiMessage>(4);
+        let progress = ProgressSender::new();
+        let mut progress_rx = progress.subscribe();
+
+        // The poll keeps reporting while the UI loop reads nothing
+        let poll = spawn_progress_poll(session, 0, tx.clone(), progress, 7);
+        tokio::time::sleep(Duration::from_millis(1700)).await;
+        let polls = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| request.url.path() == "/torrents/0/stats/v1")
+            .count();
+        assert!(polls >= 3, "only {polls} polls");
+
+        // None of it went into the channel, events get all of it to themselves
+        for chapter in 0..4 {
+            timeout(
+                Duration::from_millis(100),
+                tx.send(UiMessage::ChapterChanged(chapter)),
+            )
+            .await
+            .expect("event waited on progress updates")
+            .unwrap();
+        }
+        poll.abort();
+
+        let messages: Vec<_> = progress_rx
+            .take()
+            .chain(std::iter::from_fn(|| rx.try_recv().ok()))
+            .collect();
+        assert_eq!(messages.len(), 1 + 4);
+        assert!(matches!(
+            &messages[0],
+            UiMessage::ProgressUpdate {
+                progress,
+                stream_id: 7,
+                torrent: (_, 0),
+            } if progress.downloaded_bytes == 1_073_831_824
+        ));
+        for (chapter, message) in messages[1..].iter().enumerate() {
+            assert!(matches!(message, UiMessage::ChapterChanged(c) if *c == chapter));
+        }
+
+        // Nothing new since
+        assert_eq!(progress_rx.take().count(), 0);
+    }
+
+    #[test]
+    fn test_progress_unread() {
+        let progress = ProgressSender::new();
+        assert!(!progress.playback(10.0));
+        let mut progress_rx = progress.subscribe();
+        // Sent before subscribing
+        assert_eq!(progress_rx.take().count(), 0);
+        assert!(progress.playback(20.0));
+        assert!(matches!(
+            progress_rx.take().collect::<Vec<_>>()[..],
+            [UiMessage::PlaybackProgress(p)] if p == 20.0
+        ));
+        drop(progress_rx);
+        assert!(!progress.download(update(1)));
+    }
+}
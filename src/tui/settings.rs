@@ -0,0 +1,778 @@
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use super::app::SettingsSection;
+use crate::config::{Config, TmdbConfig};
+use crate::redact::mask_secret;
+use crate::streaming::SeedPolicy;
+
+/// How a settings field is edited and displayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldKind {
+    Text,
+    /// Text that is masked when displayed
+    Secret,
+    /// Toggled with Space/Enter instead of being edited
+    Bool,
+    Number {
+        min: u64,
+        max: u64,
+    },
+    /// Cycled through with Space/Enter, the first option means "not set"
+    Enum {
+        options: &'static [&'static str],
+    },
+}
+
+/// One editable field in the settings view
+pub struct SettingsField {
+    pub label: &'static str,
+    pub kind: FieldKind,
+    /// Shown instead of an empty value
+    pub placeholder: &'static str,
+    /// Shown dimmed after the value
+    pub hint: &'static str,
+    get: fn(&Config) -> String,
+    set: fn(&mut Config, String),
+    check: fn(&str) -> Result<(), String>,
+    /// Shown in place of the hint when the value is allowed but looks wrong
+    warn: fn(&Config) -> Option<String>,
+    /// Why the field can't be changed here, if it can't
+    locked: fn(&Config) -> Option<&'static str>,
+}
+
+impl SettingsField {
+    const fn new(
+        label: &'static str,
+        kind: FieldKind,
+        get: fn(&Config) -> String,
+        set: fn(&mut Config, String),
+    ) -> Self {
+        Self {
+            label,
+            kind,
+            placeholder: "(not set)",
+            hint: "",
+            get,
+            set,
+            check: |_| Ok(()),
+            warn: |_| None,
+            locked: |_| None,
+        }
+    }
+
+    const fn placeholder(mut self, placeholder: &'static str) -> Self {
+        self.placeholder = placeholder;
+        self
+    }
+
+    const fn hint(mut self, hint: &'static str) -> Self {
+        self.hint = hint;
+        self
+    }
+
+    const fn check(mut self, check: fn(&str) -> Result<(), String>) -> Self {
+        self.check = check;
+        self
+    }
+
+    const fn warn(mut self, warn: fn(&Config) -> Option<String>) -> Self {
+        self.warn = warn;
+        self
+    }
+
+    const fn locked(mut self, locked: fn(&Config) -> Option<&'static str>) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    /// Why the field is read-only in the settings view, if it is
+    pub fn lock_reason(&self, config: &Config) -> Option<&'static str> {
+        (self.locked)(config)
+    }
+
+    /// What looks off about the current value, if anything
+    pub fn warning(&self, config: &Config) -> Option<String> {
+        (self.warn)(config)
+    }
+
+    /// Raw value, used to start editing
+    pub fn value(&self, config: &Config) -> String {
+        (self.get)(config)
+    }
+
+    /// Value as shown in the settings list
+    pub fn display(&self, config: &Config) -> String {
+        let value = self.value(config);
+        match self.kind {
+            FieldKind::Bool if value == "true" => "Yes".to_string(),
+            FieldKind::Bool => "No".to_string(),
+            _ if value.is_empty() => self.placeholder.to_string(),
+            FieldKind::Secret => mask_secret(&value),
+            _ => value,
+        }
+    }
+
+    /// Validate the edit buffer and write it to the config
+    pub fn apply(&self, config: &mut Config, input: &str) -> Result<(), String> {
+        if let Some(reason) = self.lock_reason(config) {
+            return Err(reason.to_string());
+        }
+        let mut value = input.trim().to_string();
+        match self.kind {
+            FieldKind::Number { min, max } => match value.parse::<u64>() {
+                Ok(n) if (min..=max).contains(&n) => {}
+                _ => return Err(format!("Enter a number from {} to {}", min, max)),
+            },
+            FieldKind::Bool => {
+                value = value.to_lowercase();
+                if value != "true" && value != "false" {
+                    return Err("Enter true or false".to_string());
+                }
+            }
+            FieldKind::Enum { options } => {
+                if !options.contains(&value.as_str()) {
+                    return Err(format!("Choose one of: {}", options.join(", ")));
+                }
+            }
+            FieldKind::Text | FieldKind::Secret => {}
+        }
+        if !value.is_empty() {
+            (self.check)(&value)?;
+        }
+        (self.set)(config, value);
+        Ok(())
+    }
+
+    /// Flip a bool or move an enum to its next option, returns true if changed
+    pub fn toggle(&self, config: &mut Config) -> bool {
+        if self.lock_reason(config).is_some() {
+            return false;
+        }
+        let next = match self.kind {
+            FieldKind::Bool => (self.value(config) != "true").to_string(),
+            FieldKind::Enum { options } => {
+                let current = self.value(config);
+                let idx = options.iter().position(|o| *o == current).unwrap_or(0);
+                options[(idx + 1) % options.len()].to_string()
+            }
+            _ => return false,
+        };
+        (self.set)(config, next);
+        true
+    }
+}
+
+fn some_if_not_empty(value: String) -> Option<String> {
+    if value.is_empty() { None } else { Some(value) }
+}
+
+/// Split "fr, en" or "fr en" into lowercase language codes
+fn language_list(value: &str) -> Vec<String> {
+    value
+        .split(|ch: char| ch == ',' || ch.is_whitespace())
+        .filter(|l| !l.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn check_http_url(value: &str) -> Result<(), String> {
+    if value.starts_with("http://") || value.starts_with("https://") {
+        Ok(())
+    } else {
+        Err("URL must start with http:// or https://".to_string())
+    }
+}
+
+/// Keys in an unexpected format are still saved, the service may accept them
+fn api_key_warning(config: &Config, field: &str) -> Option<String> {
+    config
+        .warnings()
+        .into_iter()
+        .find(|warning| warning.field == field)
+        .map(|warning| format!("API key {}", warning.message))
+}
+
+fn check_proxy_url(value: &str) -> Result<(), String> {
+    reqwest::Proxy::all(value)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid proxy URL: {}", e))
+}
+
+fn check_ip_address(value: &str) -> Result<(), String> {
+    value
+        .parse::<IpAddr>()
+        .map(|_| ())
+        .map_err(|_| "Enter an IP address like 192.168.1.10".to_string())
+}
+
+fn check_lan_token(value: &str) -> Result<(), String> {
+    if value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(())
+    } else {
+        Err("Use only letters, digits, - and _".to_string())
+    }
+}
+
+fn check_seed_policy(value: &str) -> Result<(), String> {
+    value.parse::<SeedPolicy>().map(|_| ())
+}
+
+fn check_temp_dir(value: &str) -> Result<(), String> {
+    crate::streaming::check_writable(Path::new(value))
+        .map_err(|e| format!("Can't write to {}: {}", value, e))
+}
+
+const QUALITY_OPTIONS: &[&str] = &["any", "480p", "720p", "1080p", "2160p"];
+
+static PROWLARR_FIELDS: [SettingsField; 2] = [
+    SettingsField::new(
+        "URL",
+        FieldKind::Text,
+        |c| c.prowlarr.url.clone(),
+        |c, v| c.prowlarr.url = v,
+    )
+    .check(check_http_url),
+    SettingsField::new(
+        "API Key",
+        FieldKind::Secret,
+        |c| c.prowlarr.apikey.clone(),
+        |c, v| c.prowlarr.apikey = v,
+    )
+    .warn(|c| api_key_warning(c, "prowlarr.apikey")),
+];
+
+static TMDB_FIELDS: [SettingsField; 1] = [SettingsField::new(
+    "API Key",
+    FieldKind::Secret,
+    |c| {
+        c.tmdb
+            .as_ref()
+            .map(|t| t.apikey.clone())
+            .unwrap_or_default()
+    },
+    |c, v| c.tmdb = some_if_not_empty(v).map(|apikey| TmdbConfig { apikey }),
+)
+.warn(|c| api_key_warning(c, "tmdb.apikey"))];
+
+static PLAYER_FIELDS: [SettingsField; 5] = [
+    SettingsField::new(
+        "Command",
+        FieldKind::Text,
+        |c| c.player.command.clone(),
+        |c, v| c.player.command = v,
+    ),
+    SettingsField::new(
+        "Args",
+        FieldKind::Text,
+        |c| c.player.args.join(" "),
+        |c, v| c.player.args = v.split_whitespace().map(String::from).collect(),
+    )
+    .placeholder("(none)"),
+    SettingsField::new(
+        "Audio Language",
+        FieldKind::Text,
+        |c| c.player.audio_language.join(","),
+        |c, v| c.player.audio_language = language_list(&v),
+    )
+    .placeholder("(player default)")
+    .hint("e.g. ja,en"),
+    SettingsField::new(
+        "Multi Player",
+        FieldKind::Bool,
+        |c| c.player.multi_player.to_string(),
+        |c, v| c.player.multi_player = v == "true",
+    )
+    .hint("keep the current player open when switching"),
+    SettingsField::new(
+        "Post-play Command",
+        FieldKind::Text,
+        |c| c.player.post_play_command.clone().unwrap_or_default(),
+        |c, v| c.player.post_play_command = some_if_not_empty(v),
+    )
+    .placeholder("(none)")
+    .hint("shell command run after each file"),
+];
+
+static STREAMING_FIELDS: [SettingsField; 7] = [
+    SettingsField::new(
+        "Auto Race",
+        FieldKind::Number { min: 0, max: 50 },
+        |c| c.streaming.auto_race.to_string(),
+        |c, v| c.streaming.auto_race = v.parse().unwrap_or_default(),
+    )
+    .hint("0 = disabled"),
+    SettingsField::new(
+        "Min Seeders",
+        FieldKind::Number { min: 0, max: 1000 },
+        |c| c.streaming.min_seeders.to_string(),
+        |c, v| c.streaming.min_seeders = v.parse().unwrap_or_default(),
+    ),
+    SettingsField::new(
+        "Preferred Quality",
+        FieldKind::Enum {
+            options: QUALITY_OPTIONS,
+        },
+        |c| {
+            c.streaming
+                .preferred_quality
+                .clone()
+                .unwrap_or_else(|| QUALITY_OPTIONS[0].to_string())
+        },
+        |c, v| c.streaming.preferred_quality = Some(v).filter(|q| q != QUALITY_OPTIONS[0]),
+    ),
+    SettingsField::new(
+        "Seed After Watch",
+        FieldKind::Text,
+        |c| c.streaming.seed_after_watch.to_string(),
+        |c, v| c.streaming.seed_after_watch = v.parse().unwrap_or_default(),
+    )
+    .placeholder("off")
+    .hint("off, minutes(N) or ratio(X)")
+    .check(check_seed_policy),
+    SettingsField::new(
+        "Check Peers First",
+        FieldKind::Bool,
+        |c| c.streaming.probe_peers.to_string(),
+        |c, v| c.streaming.probe_peers = v == "true",
+    ),
+    SettingsField::new(
+        "Share on LAN",
+        FieldKind::Text,
+        |c| c.streaming.bind_lan.clone().unwrap_or_default(),
+        |c, v| c.streaming.bind_lan = some_if_not_empty(v),
+    )
+    .placeholder("(localhost only)")
+    .hint("0.0.0.0 for every interface")
+    .check(check_ip_address),
+    SettingsField::new(
+        "Share Token",
+        FieldKind::Secret,
+        |c| c.streaming.lan_token.clone().unwrap_or_default(),
+        |c, v| c.streaming.lan_token = some_if_not_empty(v),
+    )
+    .placeholder("(none)")
+    .check(check_lan_token),
+];
+
+/// Comma separated list of words, as typed in the settings view
+fn term_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+static FILTERS_FIELDS: [SettingsField; 5] = [
+    SettingsField::new(
+        "Safe Mode",
+        FieldKind::Bool,
+        |c| c.filter.safe_mode.to_string(),
+        |c, v| c.filter.safe_mode = v == "true",
+    )
+    .hint("drops adult releases")
+    .locked(|c| {
+        c.filter
+            .safe_mode_locked
+            .then_some("Locked by filter.safe_mode_locked in the config file")
+    }),
+    SettingsField::new(
+        "Blocked Terms",
+        FieldKind::Text,
+        |c| c.filter.blocked_terms.join(", "),
+        |c, v| c.filter.blocked_terms = term_list(&v),
+    )
+    .placeholder("(none)")
+    .hint("comma separated"),
+    SettingsField::new(
+        "Min Size (MB)",
+        FieldKind::Number {
+            min: 0,
+            max: 1_000_000,
+        },
+        |c| c.filter.min_size_mb.to_string(),
+        |c, v| c.filter.min_size_mb = v.parse().unwrap_or_default(),
+    )
+    .hint("0 = no limit"),
+    SettingsField::new(
+        "Max Size (GB)",
+        FieldKind::Number {
+            min: 0,
+            max: 10_000,
+        },
+        |c| c.filter.max_size_gb.to_string(),
+        |c, v| c.filter.max_size_gb = v.parse().unwrap_or_default(),
+    )
+    .hint("0 = no limit"),
+    SettingsField::new(
+        "Require Streamable",
+        FieldKind::Bool,
+        |c| c.filter.require_streamable.to_string(),
+        |c, v| c.filter.require_streamable = v == "true",
+    )
+    .hint("hide results without a link"),
+];
+
+static NETWORK_FIELDS: [SettingsField; 7] = [
+    SettingsField::new(
+        "HTTP Proxy",
+        FieldKind::Text,
+        |c| c.network.http_proxy.clone().unwrap_or_default(),
+        |c, v| c.network.http_proxy = some_if_not_empty(v),
+    )
+    .placeholder("(none)")
+    .check(check_proxy_url),
+    SettingsField::new(
+        "SOCKS5 Proxy",
+        FieldKind::Text,
+        |c| c.network.socks5_proxy.clone().unwrap_or_default(),
+        |c, v| c.network.socks5_proxy = some_if_not_empty(v),
+    )
+    .placeholder("(none)")
+    .check(check_proxy_url),
+    SettingsField::new(
+        "Bind Address",
+        FieldKind::Text,
+        |c| c.network.bind_address.clone().unwrap_or_default(),
+        |c, v| c.network.bind_address = some_if_not_empty(v),
+    )
+    .placeholder("(any)")
+    .check(check_ip_address),
+    SettingsField::new(
+        "Timeout",
+        FieldKind::Number { min: 0, max: 600 },
+        |c| c.network.timeout_secs.to_string(),
+        |c, v| c.network.timeout_secs = v.parse().unwrap_or_default(),
+    )
+    .hint("seconds, 0 = none"),
+    SettingsField::new(
+        "Max Redirects",
+        FieldKind::Number { min: 0, max: 20 },
+        |c| c.network.max_redirects.to_string(),
+        |c, v| c.network.max_redirects = v.parse().unwrap_or_default(),
+    ),
+    SettingsField::new(
+        "Max Attempts",
+        FieldKind::Number { min: 1, max: 10 },
+        |c| c.network.max_attempts.to_string(),
+        |c, v| c.network.max_attempts = v.parse().unwrap_or(1),
+    )
+    .hint("retries timeouts and 5xx"),
+    SettingsField::new(
+        "User Agent",
+        FieldKind::Text,
+        |c| c.network.user_agent.clone().unwrap_or_default(),
+        |c, v| c.network.user_agent = some_if_not_empty(v),
+    )
+    .placeholder("(default)"),
+];
+
+static STORAGE_FIELDS: [SettingsField; 2] = [
+    SettingsField::new(
+        "Temp Dir",
+        FieldKind::Text,
+        |c| {
+            c.storage
+                .temp_dir
+                .as_ref()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default()
+        },
+        |c, v| c.storage.temp_dir = some_if_not_empty(v).map(PathBuf::from),
+    )
+    .placeholder("(system temp)")
+    .check(check_temp_dir),
+    SettingsField::new(
+        "Max Cache",
+        FieldKind::Number {
+            min: 0,
+            max: 10_000,
+        },
+        |c| c.storage.max_cache_gb.to_string(),
+        |c, v| {
+            if let Ok(gb) = v.parse() {
+                c.storage.max_cache_gb = gb;
+            }
+        },
+    )
+    .hint("GB, crash leftovers above this go on startup"),
+];
+
+static SUBTITLES_FIELDS: [SettingsField; 5] = [
+    SettingsField::new(
+        "Enabled",
+        FieldKind::Bool,
+        |c| c.subtitles.enabled.to_string(),
+        |c, v| c.subtitles.enabled = v == "true",
+    ),
+    SettingsField::new(
+        "Language",
+        FieldKind::Text,
+        |c| c.subtitles.language.clone(),
+        |c, v| c.subtitles.language = v,
+    ),
+    SettingsField::new(
+        "Fallback Order",
+        FieldKind::Text,
+        |c| c.subtitles.subtitle_languages.join(","),
+        |c, v| c.subtitles.subtitle_languages = language_list(&v),
+    )
+    .placeholder("(language only)")
+    .hint("e.g. fr,en"),
+    SettingsField::new(
+        "OpenSubtitles Key",
+        FieldKind::Secret,
+        |c| {
+            c.subtitles
+                .opensubtitles_api_key
+                .clone()
+                .unwrap_or_default()
+        },
+        |c, v| c.subtitles.opensubtitles_api_key = some_if_not_empty(v),
+    ),
+    SettingsField::new(
+        "Off If Audio Matches",
+        FieldKind::Bool,
+        |c| c.subtitles.hide_when_audio_matches.to_string(),
+        |c, v| c.subtitles.hide_when_audio_matches = v == "true",
+    ),
+];
+
+static DISCORD_FIELDS: [SettingsField; 2] = [
+    SettingsField::new(
+        "Enabled",
+        FieldKind::Bool,
+        |c| c.extensions.discord.enabled.to_string(),
+        |c, v| c.extensions.discord.enabled = v == "true",
+    ),
+    SettingsField::new(
+        "App ID",
+        FieldKind::Text,
+        |c| c.extensions.discord.app_id.clone().unwrap_or_default(),
+        |c, v| c.extensions.discord.app_id = some_if_not_empty(v),
+    )
+    .placeholder("(using default)"),
+];
+
+static TRAKT_FIELDS: [SettingsField; 5] = [
+    SettingsField::new(
+        "Enabled",
+        FieldKind::Bool,
+        |c| c.extensions.trakt.enabled.to_string(),
+        |c, v| c.extensions.trakt.enabled = v == "true",
+    ),
+    SettingsField::new(
+        "Client ID",
+        FieldKind::Secret,
+        |c| c.extensions.trakt.client_id.clone().unwrap_or_default(),
+        |c, v| c.extensions.trakt.client_id = some_if_not_empty(v),
+    ),
+    SettingsField::new(
+        "Access Token",
+        FieldKind::Secret,
+        |c| c.extensions.trakt.access_token.clone().unwrap_or_default(),
+        |c, v| c.extensions.trakt.access_token = some_if_not_empty(v),
+    ),
+    SettingsField::new(
+        "Client Secret",
+        FieldKind::Secret,
+        |c| c.extensions.trakt.client_secret.clone().unwrap_or_default(),
+        |c, v| c.extensions.trakt.client_secret = some_if_not_empty(v),
+    ),
+    SettingsField::new(
+        "Refresh Token",
+        FieldKind::Secret,
+        |c| c.extensions.trakt.refresh_token.clone().unwrap_or_default(),
+        |c, v| c.extensions.trakt.refresh_token = some_if_not_empty(v),
+    ),
+];
+
+static SIMKL_FIELDS: [SettingsField; 3] = [
+    SettingsField::new(
+        "Enabled",
+        FieldKind::Bool,
+        |c| c.extensions.simkl.enabled.to_string(),
+        |c, v| c.extensions.simkl.enabled = v == "true",
+    ),
+    SettingsField::new(
+        "Client ID",
+        FieldKind::Secret,
+        |c| c.extensions.simkl.client_id.clone().unwrap_or_default(),
+        |c, v| c.extensions.simkl.client_id = some_if_not_empty(v),
+    ),
+    SettingsField::new(
+        "Access Token",
+        FieldKind::Secret,
+        |c| c.extensions.simkl.access_token.clone().unwrap_or_default(),
+        |c, v| c.extensions.simkl.access_token = some_if_not_empty(v),
+    ),
+];
+
+/// Editable fields of a settings section, in display order
+/// Player Command field, picked from the installed players
+pub fn command_field() -> &'static SettingsField {
+    &PLAYER_FIELDS[0]
+}
+
+/// Temp Dir field, also edited by the wizard's Storage step
+pub fn temp_dir_field() -> &'static SettingsField {
+    &STORAGE_FIELDS[0]
+}
+
+pub fn fields(section: SettingsSection) -> &'static [SettingsField] {
+    match section {
+        SettingsSection::Prowlarr => &PROWLARR_FIELDS,
+        SettingsSection::Tmdb => &TMDB_FIELDS,
+        SettingsSection::Player => &PLAYER_FIELDS,
+        SettingsSection::Streaming => &STREAMING_FIELDS,
+        SettingsSection::Filters => &FILTERS_FIELDS,
+        SettingsSection::Network => &NETWORK_FIELDS,
+        SettingsSection::Storage => &STORAGE_FIELDS,
+        SettingsSection::Subtitles => &SUBTITLES_FIELDS,
+        SettingsSection::Discord => &DISCORD_FIELDS,
+        SettingsSection::Trakt => &TRAKT_FIELDS,
+        SettingsSection::Simkl => &SIMKL_FIELDS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(section: SettingsSection, label: &str) -> &'static SettingsField {
+        fields(section).iter().find(|f| f.label == label).unwrap()
+    }
+
+    #[test]
+    fn test_number_field_range() {
+        let mut config = Config::default();
+        let auto_race = field(SettingsSection::Streaming, "Auto Race");
+
+        assert!(auto_race.apply(&mut config, " 5 ").is_ok());
+        assert_eq!(config.streaming.auto_race, 5);
+
+        assert!(auto_race.apply(&mut config, "51").is_err());
+        assert!(auto_race.apply(&mut config, "five").is_err());
+        assert_eq!(config.streaming.auto_race, 5);
+    }
+
+    #[test]
+    fn test_enum_field_cycles() {
+        let mut config = Config::default();
+        let quality = field(SettingsSection::Streaming, "Preferred Quality");
+
+        assert_eq!(quality.value(&config), "any");
+        assert!(quality.toggle(&mut config));
+        assert_eq!(config.streaming.preferred_quality.as_deref(), Some("480p"));
+
+        for _ in 1..QUALITY_OPTIONS.len() {
+            quality.toggle(&mut config);
+        }
+        assert_eq!(config.streaming.preferred_quality, None);
+    }
+
+    #[test]
+    fn test_text_field_checks() {
+        let mut config = Config::default();
+
+        let url = field(SettingsSection::Prowlarr, "URL");
+        assert!(url.apply(&mut config, "localhost:9696").is_err());
+        assert!(url.apply(&mut config, "http://localhost:9696").is_ok());
+
+        // Odd-looking keys are saved with a warning
+        let apikey = field(SettingsSection::Prowlarr, "API Key");
+        assert!(apikey.apply(&mut config, "abc123").is_ok());
+        assert_eq!(
+            apikey.warning(&config).as_deref(),
+            Some("API key should be 32 characters, got 6")
+        );
+        assert!(apikey.apply(&mut config, &"g".repeat(32)).is_ok());
+        assert!(apikey.warning(&config).is_some());
+        assert!(apikey.apply(&mut config, &"a1".repeat(16)).is_ok());
+        assert_eq!(apikey.warning(&config), None);
+        let tmdb = field(SettingsSection::Tmdb, "API Key");
+        assert!(tmdb.apply(&mut config, "abc123").is_ok());
+        assert!(tmdb.warning(&config).is_some());
+        assert!(tmdb.apply(&mut config, "").is_ok());
+        assert!(config.tmdb.is_none());
+        assert!(config.validate().is_empty());
+
+        let bind = field(SettingsSection::Network, "Bind Address");
+        assert!(bind.apply(&mut config, "not-an-ip").is_err());
+        assert!(bind.apply(&mut config, "").is_ok());
+        assert_eq!(config.network.bind_address, None);
+    }
+
+    #[test]
+    fn test_bool_field_toggles() {
+        let mut config = Config::default();
+        config.subtitles.enabled = false;
+        let enabled = field(SettingsSection::Subtitles, "Enabled");
+
+        assert_eq!(enabled.display(&config), "No");
+        assert!(enabled.toggle(&mut config));
+        assert!(config.subtitles.enabled);
+        assert_eq!(enabled.display(&config), "Yes");
+    }
+
+    #[test]
+    fn test_storage_fields() {
+        let mut config = Config::default();
+        let temp_dir = field(SettingsSection::Storage, "Temp Dir");
+        let max_cache = field(SettingsSection::Storage, "Max Cache");
+
+        assert_eq!(temp_dir.display(&config), "(system temp)");
+        let dir = std::env::temp_dir().join(format!(
+            "ferristream_settings_test_{}/cache",
+            std::process::id()
+        ));
+        assert!(
+            temp_dir
+                .apply(&mut config, &dir.display().to_string())
+                .is_ok()
+        );
+        assert_eq!(config.storage.temp_dir(), dir);
+        assert!(dir.is_dir());
+
+        // Not creatable, the old value stays
+        let blocked = dir.parent().unwrap().join("file");
+        std::fs::write(&blocked, "x").unwrap();
+        assert!(
+            temp_dir
+                .apply(&mut config, &blocked.join("cache").display().to_string())
+                .is_err()
+        );
+        assert_eq!(config.storage.temp_dir(), dir);
+        std::fs::remove_dir_all(dir.parent().unwrap()).ok();
+
+        assert_eq!(max_cache.display(&config), "10");
+        assert!(max_cache.apply(&mut config, "2").is_ok());
+        assert_eq!(config.storage.max_cache_gb, 2.0);
+        assert!(config.storage.is_over_cache_limit(3 * 1024 * 1024 * 1024));
+        assert!(!config.storage.is_over_cache_limit(1024));
+    }
+
+    #[test]
+    fn test_locked_safe_mode() {
+        let mut config = Config::default();
+        let safe_mode = field(SettingsSection::Filters, "Safe Mode");
+        assert!(safe_mode.toggle(&mut config));
+        assert!(config.filter.safe_mode);
+
+        config.filter.safe_mode_locked = true;
+        assert!(safe_mode.lock_reason(&config).is_some());
+        assert!(!safe_mode.toggle(&mut config));
+        assert!(safe_mode.apply(&mut config, "false").is_err());
+        assert!(config.filter.safe_mode);
+
+        // Other filters stay editable
+        let terms = field(SettingsSection::Filters, "Blocked Terms");
+        assert!(terms.apply(&mut config, "cam, , telesync").is_ok());
+        assert_eq!(config.filter.blocked_terms, ["cam", "telesync"]);
+    }
+}
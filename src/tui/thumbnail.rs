@@ -0,0 +1,257 @@
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+
+/// Largest size a thumbnail is kept at, plenty for a side panel
+const MAX_WIDTH: u32 = 160;
+const MAX_HEIGHT: u32 = 90;
+
+/// How bright a backdrop is drawn, dark enough for text on top to stay readable
+const BACKDROP_BRIGHTNESS: f32 = 0.35;
+
+/// Small RGB image drawn with half blocks, two pixels stacked in each cell
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Thumbnail {
+    /// Decode a JPEG or PNG and shrink it, None if it can't be read
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let image = image::load_from_memory(bytes).ok()?;
+        let image = image.thumbnail(MAX_WIDTH, MAX_HEIGHT).to_rgb8();
+        Some(Self {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.pixels().map(|p| p.0).collect(),
+        })
+    }
+
+    /// Rows of half blocks filling at most `columns` x `rows` cells, keeping
+    /// the aspect ratio (a cell is about twice as tall as it is wide)
+    pub fn lines(&self, columns: u16, rows: u16) -> Vec<Line<'static>> {
+        if self.width == 0 || self.height == 0 || columns == 0 || rows == 0 {
+            return Vec::new();
+        }
+        let max_width = columns as u32;
+        let max_height = rows as u32 * 2;
+        let (width, height) = if max_width * self.height <= max_height * self.width {
+            (max_width, (max_width * self.height / self.width).max(1))
+        } else {
+            ((max_height * self.width / self.height).max(1), max_height)
+        };
+
+        let pixel = |x: u32, y: u32| {
+            let src_x = (x * self.width / width).min(self.width - 1);
+            let src_y = (y * self.height / height).min(self.height - 1);
+            let [r, g, b] = self.pixels[(src_y * self.width + src_x) as usize];
+            Color::Rgb(r, g, b)
+        };
+
+        (0..height.div_ceil(2))
+            .map(|row| {
+                let spans = (0..width)
+                    .map(|x| {
+                        let top = pixel(x, row * 2);
+                        let style = if row * 2 + 1 < height {
+                            Style::default().fg(top).bg(pixel(x, row * 2 + 1))
+                        } else {
+                            Style::default().fg(top)
+                        };
+                        Span::styled("▀", style)
+                    })
+                    .collect::<Vec<_>>();
+                Line::from(spans)
+            })
+            .collect()
+    }
+
+    /// Copy softened with a box blur `radius` pixels each way, so it stays a
+    /// background to text drawn over it
+    pub fn blurred(&self, radius: u32) -> Self {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let radius = radius as isize;
+        let pass = |pixels: &[[u8; 3]], horizontal: bool| -> Vec<[u8; 3]> {
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    let mut sum = [0u32; 3];
+                    let mut count = 0;
+                    for offset in -radius..=radius {
+                        let (sx, sy) = if horizontal {
+                            (x as isize + offset, y as isize)
+                        } else {
+                            (x as isize, y as isize + offset)
+                        };
+                        if sx < 0 || sy < 0 || sx >= width as isize || sy >= height as isize {
+                            continue;
+                        }
+                        let pixel = pixels[sy as usize * width + sx as usize];
+                        for (total, channel) in sum.iter_mut().zip(pixel) {
+                            *total += channel as u32;
+                        }
+                        count += 1;
+                    }
+                    sum.map(|total| (total / count.max(1)) as u8)
+                })
+                .collect()
+        };
+        let pixels = pass(&pass(&self.pixels, true), false);
+        Self {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+
+    /// Rows of half blocks covering all of `columns` x `rows`, cropping the
+    /// image to fill them and darkened to sit behind text. `progress` from 0.0
+    /// to 1.0 fades in from `from`, or from black without one.
+    pub fn backdrop_lines(
+        &self,
+        from: Option<&Thumbnail>,
+        columns: u16,
+        rows: u16,
+        progress: f32,
+    ) -> Vec<Line<'static>> {
+        if self.width == 0 || self.height == 0 || columns == 0 || rows == 0 {
+            return Vec::new();
+        }
+        let (width, height) = (columns as u32, rows as u32 * 2);
+        let progress = progress.clamp(0.0, 1.0);
+        let pixel = |x: u32, y: u32| {
+            let to = self.cover_pixel(x, y, width, height);
+            let from = from
+                .filter(|from| from.width > 0 && from.height > 0)
+                .map_or([0; 3], |from| from.cover_pixel(x, y, width, height));
+            let channel = |i: usize| {
+                let mixed = from[i] as f32 * (1.0 - progress) + to[i] as f32 * progress;
+                (mixed * BACKDROP_BRIGHTNESS) as u8
+            };
+            Color::Rgb(channel(0), channel(1), channel(2))
+        };
+
+        (0..rows as u32)
+            .map(|row| {
+                let spans = (0..width)
+                    .map(|x| {
+                        let style = Style::default()
+                            .fg(pixel(x, row * 2))
+                            .bg(pixel(x, row * 2 + 1));
+                        Span::styled("▀", style)
+                    })
+                    .collect::<Vec<_>>();
+                Line::from(spans)
+            })
+            .collect()
+    }
+
+    /// Pixel at (`x`, `y`) of the image scaled to cover `width` x `height`,
+    /// centered with the overflow cropped
+    fn cover_pixel(&self, x: u32, y: u32, width: u32, height: u32) -> [u8; 3] {
+        let scale = (width as f32 / self.width as f32).max(height as f32 / self.height as f32);
+        let crop_x = (self.width as f32 - width as f32 / scale) / 2.0;
+        let crop_y = (self.height as f32 - height as f32 / scale) / 2.0;
+        let src_x = ((x as f32 + 0.5) / scale + crop_x) as u32;
+        let src_y = ((y as f32 + 0.5) / scale + crop_y) as u32;
+        self.pixels[(src_y.min(self.height - 1) * self.width + src_x.min(self.width - 1)) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbImage::from_fn(width, height, |x, _| {
+            if x < width / 2 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn test_decode_shrinks() {
+        let thumbnail = Thumbnail::decode(&png(1920, 1080)).unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (160, 90));
+        assert!(Thumbnail::decode(b"not an image").is_none());
+    }
+
+    #[test]
+    fn test_lines_keep_aspect() {
+        let thumbnail = Thumbnail::decode(&png(1920, 1080)).unwrap();
+
+        // Width bound: 40 columns of 16:9 is 22 pixels, 11 rows
+        let lines = thumbnail.lines(40, 30);
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0].spans.len(), 40);
+        assert_eq!(lines[0].spans[0].style.fg, Some(Color::Rgb(255, 0, 0)));
+        assert_eq!(lines[0].spans[39].style.fg, Some(Color::Rgb(0, 0, 255)));
+
+        // Height bound: 5 rows are 10 pixels, 17 columns
+        let lines = thumbnail.lines(40, 5);
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0].spans.len(), 17);
+
+        assert!(thumbnail.lines(0, 5).is_empty());
+    }
+
+    #[test]
+    fn test_blurred_softens_edges() {
+        let pixels = (0..16 * 9)
+            .map(|i| if i % 16 < 8 { [255, 0, 0] } else { [0, 0, 255] })
+            .collect();
+        let thumbnail = Thumbnail {
+            width: 16,
+            height: 9,
+            pixels,
+        };
+        let blurred = thumbnail.blurred(2);
+
+        // Far from the edge between the halves nothing changes
+        assert_eq!(blurred.pixels[0], [255, 0, 0]);
+        assert_eq!(blurred.pixels[15], [0, 0, 255]);
+        // Next to it the colors mix
+        let [r, _, b] = blurred.pixels[7];
+        assert!(
+            r > 0 && r < 255 && b > 0 && b < 255,
+            "{:?}",
+            blurred.pixels[7]
+        );
+    }
+
+    #[test]
+    fn test_backdrop_lines_cover_and_fade() {
+        let thumbnail = Thumbnail::decode(&png(1920, 1080)).unwrap();
+
+        // Fills the whole area even though it's far wider than 16:9
+        let lines = thumbnail.backdrop_lines(None, 100, 10, 1.0);
+        assert_eq!(lines.len(), 10);
+        assert!(lines.iter().all(|line| line.spans.len() == 100));
+        let red = (255.0 * BACKDROP_BRIGHTNESS) as u8;
+        assert_eq!(lines[0].spans[0].style.fg, Some(Color::Rgb(red, 0, 0)));
+        assert_eq!(lines[9].spans[99].style.bg, Some(Color::Rgb(0, 0, red)));
+
+        // Halfway from black
+        let lines = thumbnail.backdrop_lines(None, 100, 10, 0.5);
+        assert_eq!(
+            lines[0].spans[0].style.fg,
+            Some(Color::Rgb((127.5 * BACKDROP_BRIGHTNESS) as u8, 0, 0))
+        );
+
+        // Starting from another image shows that one
+        let green = Thumbnail {
+            width: 1,
+            height: 1,
+            pixels: vec![[0, 255, 0]],
+        };
+        let lines = thumbnail.backdrop_lines(Some(&green), 100, 10, 0.0);
+        assert_eq!(lines[0].spans[0].style.fg, Some(Color::Rgb(0, red, 0)));
+    }
+}
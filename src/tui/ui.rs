@@ -1,22 +1,47 @@
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Gauge, List, ListItem, Paragraph},
+    widgets::{
+        Block, Borders, Cell, Gauge, LineGauge, List, ListItem, Paragraph, Row, Table, TableState,
+        Tabs,
+    },
 };
 use unicode_truncate::UnicodeTruncateStr;
 
 use crate::doctor::CheckStatus;
+use crate::history::FINISHED_PERCENT;
+use crate::indexer_health;
+use crate::recovery::SessionSnapshot;
+use crate::redact::mask_secret;
+use crate::streaming::{self, DetectedPlayer, MediaMode};
+use crate::tmdb::{self, AirStatus};
 
-use crate::config::Config;
+use crate::config::{Config, ConfigDiff, NOT_SET};
 
-use super::app::{App, SettingsSection, StreamingState, View, WizardStep};
+use super::app::{
+    App, Artwork, MediaFilter, SettingsSection, StreamingState, ToastLevel, View, WizardStep,
+};
+use super::settings::{self, FieldKind};
 
 // Discovery UI constants
 const DISCOVERY_ITEM_WIDTH: u16 = 30;
 const MIN_ROW_HEIGHT: u16 = 3;
 
+/// Terminal width above which the episode list gets a still panel
+pub const EPISODE_STILL_MIN_WIDTH: u16 = 120;
+
+/// Terminal width above which Discovery draws a backdrop behind the rows
+pub const BACKDROP_MIN_WIDTH: u16 = 200;
+
+/// Terminal height below which the streaming view drops its boxes, the
+/// boxed layout needs 24 rows
+const COMPACT_STREAMING_HEIGHT: u16 = 27;
+
+/// Terminal width below which the settings sections move to a tab bar
+const SETTINGS_SIDEBAR_MIN_WIDTH: u16 = 80;
+
 pub fn draw(frame: &mut Frame, app: &App, config: Option<&Config>) {
     match app.view {
         View::Wizard => {
@@ -24,20 +49,325 @@ pub fn draw(frame: &mut Frame, app: &App, config: Option<&Config>) {
                 draw_wizard(frame, app, cfg);
             }
         }
-        View::Discovery => draw_discovery(frame, app),
+        View::Discovery => draw_discovery(
+            frame,
+            app,
+            config.is_some_and(|c| c.search.discovery_refresh_interval().is_some()),
+        ),
+        View::PersonFilmography => draw_person_filmography(frame, app),
+        View::Collection => draw_collection(frame, app),
         View::Search => draw_search(frame, app),
         View::Results => draw_results(frame, app),
         View::TvSeasons => draw_tv_seasons(frame, app),
         View::TvEpisodes => draw_tv_episodes(frame, app),
         View::FileSelection => draw_file_selection(frame, app),
         View::Streaming => draw_streaming(frame, app),
+        View::PeerList => draw_peer_list(frame, app),
+        View::CastDevices => draw_cast_devices(frame, app),
         View::Doctor => draw_doctor(frame, app),
+        View::Indexers => draw_indexers(frame, app),
+        View::HistoryExport => draw_history_export(frame, app),
         View::Settings => {
             if let Some(cfg) = config {
                 draw_settings(frame, app, cfg);
             }
         }
     }
+
+    if app.view == View::Discovery
+        && let Some(snapshot) = &app.recovery_prompt
+    {
+        draw_recovery_prompt(frame, snapshot);
+    }
+    if app.show_quit_prompt {
+        draw_quit_prompt(frame, app);
+    }
+    draw_toasts(frame, app);
+}
+
+/// Terminal width below which compact mode turns on at startup
+pub const COMPACT_MODE_WIDTH: u16 = 80;
+
+/// Result list width below which the size column is left out
+const RESULTS_SIZE_MIN_WIDTH: u16 = 80;
+
+/// Help line of a view, its last span holding the keys. Compact mode shows a
+/// "[?]: help" hint in place of the keys, and lists them in a popup while
+/// the help is open. Text fields keep the full line, '?' is typed there.
+fn draw_help<'a>(frame: &mut Frame, app: &App, area: Rect, help: impl Into<Line<'a>>) {
+    let mut help: Line = help.into();
+    let style = Style::default().fg(Color::DarkGray);
+    if !app.compact_mode || app.is_typing() {
+        frame.render_widget(Paragraph::new(help).style(style), area);
+        return;
+    }
+    let keys = help
+        .spans
+        .pop()
+        .map(|span| span.content.into_owned())
+        .unwrap_or_default();
+    help.spans.push(Span::raw("[?]: help"));
+    frame.render_widget(Paragraph::new(help).style(style), area);
+    if app.show_help {
+        draw_help_popup(frame, &keys);
+    }
+}
+
+/// The keys of a help line, one per row
+fn draw_help_popup(frame: &mut Frame, keys: &str) {
+    let lines: Vec<Line> = keys
+        .split(" | ")
+        .map(|key| Line::from(format!(" {} ", key)))
+        .collect();
+    let area = frame.area();
+    let width = (lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 2).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup_area = Rect::new(
+        area.width.saturating_sub(width) / 2,
+        area.height.saturating_sub(height) / 2,
+        width,
+        height,
+    );
+    frame.render_widget(ratatui::widgets::Clear, popup_area);
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan))
+            .title("Help"),
+    );
+    frame.render_widget(popup, popup_area);
+}
+
+/// Offer to pick up a stream the last session didn't stop cleanly
+fn draw_recovery_prompt(frame: &mut Frame, snapshot: &SessionSnapshot) {
+    let area = frame.area();
+    let popup_width = 60.min(area.width.saturating_sub(4));
+    let popup_height = 8;
+    let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+    let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+    frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+    let text = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            format!(
+                "Resume {} at {:.0}%?",
+                snapshot.title, snapshot.progress_percent
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(
+            snapshot.file_name.clone(),
+            Style::default().fg(Color::DarkGray),
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Resume  |  "),
+            Span::styled("n", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Discard"),
+        ]),
+    ];
+
+    let popup = Paragraph::new(text)
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title("Interrupted stream"),
+        );
+    frame.render_widget(popup, popup_area);
+}
+
+/// Confirmation before quitting drops torrents that are still seeding
+fn draw_quit_prompt(frame: &mut Frame, app: &App) {
+    let area = frame.area();
+    let popup_width = 50.min(area.width.saturating_sub(4));
+    let popup_height = 7;
+    let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+    let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+    frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+    let text = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            format!(
+                "Stop seeding {} and quit?",
+                seeding_label(app.seeding_count)
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Quit  |  "),
+            Span::styled("any key", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Keep seeding"),
+        ]),
+    ];
+
+    let popup = Paragraph::new(text)
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title("Quit"),
+        );
+    frame.render_widget(popup, popup_area);
+}
+
+fn seeding_label(count: usize) -> String {
+    if count == 1 {
+        "1 torrent".to_string()
+    } else {
+        format!("{} torrents", count)
+    }
+}
+
+/// "UL: 1.2 GB (ratio 0.43)" over every torrent this run
+fn upload_summary(app: &App) -> String {
+    match app.session_upload() {
+        (uploaded, Some(ratio)) => format!("UL: {} (ratio {:.2})", format_bytes(uploaded), ratio),
+        (uploaded, None) => format!("UL: {}", format_bytes(uploaded)),
+    }
+}
+
+/// Views with the background activity bar on their bottom line
+pub fn has_status_bar(view: &View) -> bool {
+    matches!(view, View::Discovery | View::Search | View::Results)
+}
+
+/// Reserve the bottom line of `area` for the status bar
+fn split_status_bar(area: Rect) -> (Rect, Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(area);
+    (chunks[0], chunks[1])
+}
+
+/// "Searching: <sent> (from: <typed>)" when query_transforms rewrote the query
+fn transformed_query_status(app: &App) -> Option<String> {
+    app.transformed_query
+        .as_ref()
+        .map(|(sent, typed)| format!("Searching: {} (from: {})", sent, typed))
+}
+
+/// One line summarizing what's going on in the background, empty when idle
+fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
+    let separator = || Span::styled(" · ", Style::default().fg(Color::DarkGray));
+    let mut spans = Vec::new();
+
+    if app.is_streaming {
+        let title = if app.current_title.is_empty() {
+            "connecting..."
+        } else {
+            app.current_title.as_str()
+        };
+        spans.push(Span::styled(
+            format!(
+                "⬇ {}/s streaming {}",
+                format_bytes(app.download_progress.download_speed),
+                title
+            ),
+            Style::default().fg(Color::Green),
+        ));
+    }
+    if let Some(device) = &app.casting_to {
+        spans.push(Span::styled(
+            format!("casting to {}", device),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    if app.is_prefetching {
+        spans.push(Span::raw("finding next episode"));
+    }
+    if app.seeding_count > 0 {
+        spans.push(Span::styled(
+            format!("↑ seeding {}  {}", app.seeding_count, upload_summary(app)),
+            Style::default().fg(Color::Green),
+        ));
+    }
+    if !app.rss_queue.is_empty() {
+        spans.push(Span::raw(format!(
+            "{} queued from RSS",
+            app.rss_queue.len()
+        )));
+    }
+    if app.is_searching {
+        spans.push(Span::raw(
+            transformed_query_status(app).unwrap_or_else(|| "searching…".to_string()),
+        ));
+    }
+    if spans.is_empty() {
+        return;
+    }
+
+    let mut line: Vec<Span> = Vec::new();
+    for span in spans {
+        if !line.is_empty() {
+            line.push(separator());
+        }
+        line.push(span);
+    }
+    if app.is_streaming {
+        line.push(Span::styled(
+            "   ctrl+s: back to stream",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    frame.render_widget(Paragraph::new(Line::from(line)), area);
+}
+
+/// Stack active toasts in the bottom-right corner, newest at the bottom
+fn draw_toasts(frame: &mut Frame, app: &App) {
+    const TOAST_WIDTH: u16 = 44;
+    const TOAST_HEIGHT: u16 = 3;
+
+    let area = frame.area();
+    let width = TOAST_WIDTH.min(area.width.saturating_sub(2));
+    let now = std::time::Instant::now();
+    let mut bottom = area.height.saturating_sub(1);
+
+    for toast in app.toasts.iter().rev().filter(|t| t.expires_at > now) {
+        if bottom < TOAST_HEIGHT || width < 6 {
+            break;
+        }
+        let toast_area = ratatui::layout::Rect::new(
+            area.width.saturating_sub(width + 1),
+            bottom - TOAST_HEIGHT,
+            width,
+            TOAST_HEIGHT,
+        );
+        bottom -= TOAST_HEIGHT;
+
+        let (icon, color) = match toast.level {
+            ToastLevel::Info => ("ℹ", Color::Cyan),
+            ToastLevel::Success => ("✓", Color::Green),
+            ToastLevel::Warning => ("!", Color::Yellow),
+            ToastLevel::Error => ("✗", Color::Red),
+        };
+        let max_len = width.saturating_sub(6) as usize;
+        let (message, _) = toast.message.unicode_truncate(max_len);
+
+        frame.render_widget(ratatui::widgets::Clear, toast_area);
+        let paragraph = Paragraph::new(Line::from(vec![
+            Span::styled(format!("{} ", icon), Style::default().fg(color)),
+            Span::raw(message),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(color)),
+        );
+        frame.render_widget(paragraph, toast_area);
+    }
 }
 
 fn draw_wizard(frame: &mut Frame, app: &App, config: &Config) {
@@ -72,6 +402,8 @@ fn draw_wizard(frame: &mut Frame, app: &App, config: &Config) {
             WizardStep::Prowlarr => "Prowlarr Setup",
             WizardStep::Tmdb => "TMDB (Optional)",
             WizardStep::Player => "Player",
+            WizardStep::Storage => "Storage",
+            WizardStep::Integrations => "Integrations (Optional)",
             WizardStep::Done => "Ready!",
         }
     );
@@ -80,9 +412,9 @@ fn draw_wizard(frame: &mut Frame, app: &App, config: &Config) {
         .block(Block::default().borders(Borders::BOTTOM));
     frame.render_widget(progress_widget, chunks[1]);
 
-    // Content based on step
-    let content_lines: Vec<Line> = match app.wizard_step {
-        WizardStep::Welcome => vec![
+    // Content based on step, with the line to keep in view
+    let (content_lines, focus): (Vec<Line>, usize) = match app.wizard_step {
+        WizardStep::Welcome => with_last_focused(vec![
             Line::from(""),
             Line::from(Span::styled(
                 "Let's get you set up!",
@@ -93,18 +425,20 @@ fn draw_wizard(frame: &mut Frame, app: &App, config: &Config) {
             Line::from("  - Prowlarr connection (required)"),
             Line::from("  - TMDB for metadata (optional)"),
             Line::from("  - Video player settings"),
+            Line::from("  - Where downloads are kept while streaming"),
+            Line::from("  - Watch tracking with Simkl (optional)"),
             Line::from(""),
             Line::from(Span::styled(
                 "Press Enter to continue...",
                 Style::default().fg(Color::DarkGray),
             )),
-        ],
+        ]),
         WizardStep::Prowlarr => {
             let fields = [
                 ("URL", config.prowlarr.url.clone(), 0),
                 ("API Key", mask_secret(&config.prowlarr.apikey), 1),
             ];
-            build_wizard_fields(app, &fields)
+            (build_wizard_fields(app, &fields), app.wizard_field_index)
         }
         WizardStep::Tmdb => {
             let api_key = config
@@ -125,27 +459,106 @@ fn draw_wizard(frame: &mut Frame, app: &App, config: &Config) {
                 )),
                 Line::from(""),
             ];
+            let focus = lines.len() + app.wizard_field_index;
             lines.extend(build_wizard_fields(app, &fields));
-            lines
+            (lines, focus)
         }
         WizardStep::Player => {
-            let fields = [("Command", config.player.command.clone(), 0)];
+            let players = app.detected_players();
+            let mut fields: Vec<(&str, String, usize)> = players
+                .iter()
+                .enumerate()
+                .map(|(i, player)| (player.name, detected_player_info(player, config), i))
+                .collect();
+            let custom = if app.player_option_index(&config.player.command) == players.len() {
+                config.player.command.clone()
+            } else {
+                "(type a command)".to_string()
+            };
+            fields.push(("Custom", custom, players.len()));
+            let status = if app.is_detecting_players {
+                "Looking for installed players..."
+            } else if players.is_empty() {
+                "No known player found - enter the command for yours."
+            } else {
+                "Pick an installed player, or enter another command."
+            };
             let mut lines = vec![
                 Line::from(""),
                 Line::from(Span::styled(
                     "Which video player do you use?",
                     Style::default().fg(Color::DarkGray),
                 )),
+                Line::from(Span::styled(status, Style::default().fg(Color::DarkGray))),
+                Line::from(""),
+            ];
+            let focus = lines.len() + app.wizard_field_index;
+            lines.extend(build_wizard_fields(app, &fields));
+            (lines, focus)
+        }
+        WizardStep::Storage => {
+            let fields = [(
+                "Temp Dir",
+                config.storage.temp_dir().display().to_string(),
+                0,
+            )];
+            let mut lines = vec![
+                Line::from(""),
+                Line::from(Span::styled(
+                    "Streams download here first, often several GB each.",
+                    Style::default().fg(Color::DarkGray),
+                )),
+                Line::from(Span::styled(
+                    "Pick a drive with room to spare.",
+                    Style::default().fg(Color::DarkGray),
+                )),
+                Line::from(""),
+            ];
+            let focus = lines.len() + app.wizard_field_index;
+            lines.extend(build_wizard_fields(app, &fields));
+            lines.push(Line::from(""));
+            lines.push(free_space_line(app));
+            (lines, focus)
+        }
+        WizardStep::Integrations => {
+            let simkl = &config.extensions.simkl;
+            let fields = [
+                (
+                    "Simkl Client ID",
+                    simkl
+                        .client_id
+                        .as_deref()
+                        .map(mask_secret)
+                        .unwrap_or_else(|| "(not set - optional)".to_string()),
+                    0,
+                ),
+                (
+                    "Simkl Access Token",
+                    simkl
+                        .access_token
+                        .as_deref()
+                        .map(mask_secret)
+                        .unwrap_or_else(|| "(not set - optional)".to_string()),
+                    1,
+                ),
+            ];
+            let mut lines = vec![
+                Line::from(""),
+                Line::from(Span::styled(
+                    "Simkl marks what you watch as watched on simkl.com.",
+                    Style::default().fg(Color::DarkGray),
+                )),
                 Line::from(Span::styled(
-                    "Common options: mpv, vlc, iina",
+                    "Discord and Trakt can be set up later in settings - press Tab to skip.",
                     Style::default().fg(Color::DarkGray),
                 )),
                 Line::from(""),
             ];
+            let focus = lines.len() + app.wizard_field_index;
             lines.extend(build_wizard_fields(app, &fields));
-            lines
+            (lines, focus)
         }
-        WizardStep::Done => vec![
+        WizardStep::Done => with_last_focused(vec![
             Line::from(""),
             Line::from(Span::styled(
                 "All set!",
@@ -163,19 +576,26 @@ fn draw_wizard(frame: &mut Frame, app: &App, config: &Config) {
                 "Press Enter to start using ferristream!",
                 Style::default().fg(Color::Cyan),
             )),
-        ],
+        ]),
     };
 
-    let content =
-        Paragraph::new(content_lines).block(Block::default().borders(Borders::ALL).title(
-            match app.wizard_step {
-                WizardStep::Welcome => "Setup Wizard",
-                WizardStep::Prowlarr => "Prowlarr",
-                WizardStep::Tmdb => "TMDB",
-                WizardStep::Player => "Player",
-                WizardStep::Done => "Complete",
-            },
-        ));
+    // Scroll instead of cutting off fields on short terminals
+    let visible = chunks[2].height.saturating_sub(2) as usize;
+    let content = Paragraph::new(content_lines)
+        .scroll((scroll_to_show(focus, visible), 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(match app.wizard_step {
+                    WizardStep::Welcome => "Setup Wizard",
+                    WizardStep::Prowlarr => "Prowlarr",
+                    WizardStep::Tmdb => "TMDB",
+                    WizardStep::Player => "Player",
+                    WizardStep::Storage => "Storage",
+                    WizardStep::Integrations => "Integrations",
+                    WizardStep::Done => "Complete",
+                }),
+        );
     frame.render_widget(content, chunks[2]);
 
     // Help
@@ -183,13 +603,44 @@ fn draw_wizard(frame: &mut Frame, app: &App, config: &Config) {
         "Enter: save | Esc: cancel"
     } else {
         match app.wizard_step {
+            WizardStep::Welcome if app.wizard_return_to.is_some() => {
+                "Enter: continue | Esc: back to settings"
+            }
             WizardStep::Welcome => "Enter: continue | Esc: quit",
+            WizardStep::Done if app.wizard_return_to.is_some() => {
+                "Enter: save & back to settings | Esc: back"
+            }
             WizardStep::Done => "Enter: finish | Esc: back",
+            WizardStep::Player if app.wizard_field_index < app.detected_players().len() => {
+                "↑/↓: players | Enter: use | Tab: next step | Esc: back"
+            }
             _ => "Enter: edit | Tab: next step | Esc: back",
         }
     };
-    let help = Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray));
-    frame.render_widget(help, chunks[3]);
+    draw_help(frame, app, chunks[3], help_text);
+}
+
+/// Version and location of an installed player, marked when it's the configured one
+fn detected_player_info(player: &DetectedPlayer, config: &Config) -> String {
+    let mut info = match &player.version {
+        Some(version) => format!("{}  {}", version, player.path.display()),
+        None => player.path.display().to_string(),
+    };
+    if player.command == config.player.command {
+        info.push_str("  (in use)");
+    }
+    info
+}
+
+/// Lines ending in a call to action, which is what to keep in view
+fn with_last_focused(lines: Vec<Line<'static>>) -> (Vec<Line<'static>>, usize) {
+    let focus = lines.len().saturating_sub(1);
+    (lines, focus)
+}
+
+/// Scroll offset that keeps line `focus` inside `visible` rows
+fn scroll_to_show(focus: usize, visible: usize) -> u16 {
+    (focus + 1).saturating_sub(visible.max(1)) as u16
 }
 
 fn build_wizard_fields(app: &App, fields: &[(&str, String, usize)]) -> Vec<Line<'static>> {
@@ -225,7 +676,9 @@ fn build_wizard_fields(app: &App, fields: &[(&str, String, usize)]) -> Vec<Line<
         .collect()
 }
 
-fn draw_discovery(frame: &mut Frame, app: &App) {
+fn draw_discovery(frame: &mut Frame, app: &App, auto_refresh: bool) {
+    let (area, status_area) = split_status_bar(frame.area());
+    draw_status_bar(frame, app, status_area);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(2)
@@ -234,20 +687,38 @@ fn draw_discovery(frame: &mut Frame, app: &App) {
             Constraint::Min(0),    // Content
             Constraint::Length(2), // Help
         ])
-        .split(frame.area());
-
-    // Title
-    let title = Paragraph::new("ferristream - Discovery")
-        .style(
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        )
-        .block(Block::default());
+        .split(area);
+
+    // Title, with new RSS matches waiting
+    let mut title_spans = vec![Span::styled(
+        "ferristream - Discovery",
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    )];
+    if !app.rss_queue.is_empty() {
+        title_spans.push(Span::styled(
+            format!("  ● {} new from RSS", app.rss_queue.len()),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if app.is_loading_discovery && !app.discovery_rows.is_empty() {
+        title_spans.push(Span::styled(
+            "  refreshing...",
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if auto_refresh && app.discovery_refresh_paused {
+        title_spans.push(Span::styled(
+            "  auto-refresh paused",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    let title = Paragraph::new(Line::from(title_spans)).block(Block::default());
     frame.render_widget(title, chunks[0]);
 
-    // Content
-    if app.is_loading_discovery {
+    // Content, old rows stay up while a refresh loads
+    if app.is_loading_discovery && app.discovery_rows.is_empty() {
         let loading = Paragraph::new("Loading discovery data...")
             .style(Style::default().fg(Color::Yellow))
             .block(Block::default().borders(Borders::ALL));
@@ -257,12 +728,33 @@ fn draw_discovery(frame: &mut Frame, app: &App) {
             .style(Style::default().fg(Color::Red))
             .block(Block::default().borders(Borders::ALL).title("Error"));
         frame.render_widget(error, chunks[1]);
+    } else if app.discovery_rows.is_empty() && !app.discovery_filter.is_empty() {
+        let empty = Paragraph::new(format!(
+            "Nothing matches \"{}\". Press Enter to search for it.",
+            app.discovery_filter
+        ))
+        .style(Style::default().fg(Color::DarkGray))
+        .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(empty, chunks[1]);
     } else if app.discovery_rows.is_empty() {
         let empty = Paragraph::new("No discovery data. Press 'r' to refresh.")
             .style(Style::default().fg(Color::DarkGray))
             .block(Block::default().borders(Borders::ALL));
         frame.render_widget(empty, chunks[1]);
     } else {
+        // Backdrop first, the rows are drawn over it
+        if let Some(backdrop) = &app.backdrop
+            && frame.area().width > BACKDROP_MIN_WIDTH
+        {
+            let lines = backdrop.image.backdrop_lines(
+                backdrop.previous.as_ref(),
+                chunks[1].width,
+                chunks[1].height,
+                backdrop.progress(),
+            );
+            frame.render_widget(Paragraph::new(lines), chunks[1]);
+        }
+
         // Render rows with horizontal item browsing
         let row_count = app.discovery_rows.len();
         let available_height = chunks[1].height;
@@ -327,7 +819,13 @@ fn draw_discovery(frame: &mut Frame, app: &App) {
                     .map(|r| format!(" ★{:.1}", r))
                     .unwrap_or_default();
 
-                let text = format!(" {}{}{}{} ", media_icon, item.title, year_str, rating_str);
+                // Up front so truncating a long title doesn't hide it
+                let badge = if item.has_4k { "4K " } else { "" };
+
+                let text = format!(
+                    " {}{}{}{}{} ",
+                    media_icon, badge, item.title, year_str, rating_str
+                );
                 let (text, _) = text.unicode_truncate(DISCOVERY_ITEM_WIDTH as usize);
                 spans.push(Span::styled(
                     format!("{:<width$}", text, width = DISCOVERY_ITEM_WIDTH as usize),
@@ -354,14 +852,59 @@ fn draw_discovery(frame: &mut Frame, app: &App) {
         }
     }
 
+    // Filter bar, like a Vim search
+    if app.is_filtering_discovery {
+        let filter = Paragraph::new(Line::from(vec![
+            Span::styled("/", Style::default().fg(Color::Yellow)),
+            Span::raw(app.discovery_filter.as_str()),
+            Span::styled("▏", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                "  Enter: done | Esc: clear",
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]));
+        frame.render_widget(filter, chunks[2]);
+        return;
+    }
+
     // Help
     let help_text = if app.is_loading_discovery {
-        "Loading..."
+        "Loading...".to_string()
+    } else if !app.discovery_filter.is_empty() {
+        format!(
+            "Filter: \"{}\" | ↑/↓: rows | ←/→: items | Enter: select | /: edit filter | f: search | Esc: clear filter",
+            app.discovery_filter
+        )
+    } else {
+        "↑/↓: rows | ←/→: items | Enter: select | /: filter | f: search | r: refresh | s: settings | d: doctor | e: export history | q: quit".to_string()
+    };
+    let help_text = if auto_refresh && !app.is_loading_discovery {
+        let toggle = if app.discovery_refresh_paused {
+            "p: resume auto-refresh"
+        } else {
+            "p: pause auto-refresh"
+        };
+        format!("{} | {}", toggle, help_text)
+    } else {
+        help_text
+    };
+    let help_text = if app.rss_queue.is_empty() || app.is_loading_discovery {
+        help_text
     } else {
-        "↑/↓: rows | ←/→: items | Enter: select | /: search | r: refresh | s: settings | d: doctor | q: quit"
+        format!("n: stream RSS match | {}", help_text)
     };
-    let help = Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray));
-    frame.render_widget(help, chunks[2]);
+    draw_help(
+        frame,
+        app,
+        chunks[2],
+        Line::from(vec![
+            Span::styled(
+                format!("[{}] ", app.config_location.profile_name()),
+                Style::default().fg(Color::Magenta),
+            ),
+            Span::raw(help_text),
+        ]),
+    );
 }
 
 fn draw_search(frame: &mut Frame, app: &App) {
@@ -372,6 +915,8 @@ fn draw_search(frame: &mut Frame, app: &App) {
         0
     };
 
+    let (area, status_area) = split_status_bar(frame.area());
+    draw_status_bar(frame, app, status_area);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(2)
@@ -382,7 +927,7 @@ fn draw_search(frame: &mut Frame, app: &App) {
             Constraint::Length(3),                 // Status/help
             Constraint::Min(0),                    // Empty space
         ])
-        .split(frame.area());
+        .split(area);
 
     // Title
     let title = Paragraph::new("ferristream")
@@ -401,13 +946,14 @@ fn draw_search(frame: &mut Frame, app: &App) {
         Style::default().fg(Color::White)
     };
 
+    let title = match app.media_mode {
+        MediaMode::Video => "Search (Movies & TV)",
+        MediaMode::Audio => "Search (Music & Audiobooks)",
+        MediaMode::All => "Search (All)",
+    };
     let input = Paragraph::new(app.search_input.as_str())
         .style(input_style)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Search (Movies & TV)"),
-        );
+        .block(Block::default().borders(Borders::ALL).title(title));
     frame.render_widget(input, chunks[1]);
 
     // Show cursor in search input
@@ -428,7 +974,8 @@ fn draw_search(frame: &mut Frame, app: &App) {
                 let year_str = s.year.map(|y| format!(" ({})", y)).unwrap_or_default();
                 let media_icon = match s.media_type.as_str() {
                     "movie" => "🎬",
-                    "tv" => "📺",
+                    "tv" | "anime" => "📺",
+                    "person" => "👤",
                     _ => "•",
                 };
 
@@ -452,24 +999,31 @@ fn draw_search(frame: &mut Frame, app: &App) {
 
     // Status/error text
     let status = if app.is_searching {
-        Paragraph::new("Searching...").style(Style::default().fg(Color::Yellow))
+        Paragraph::new(transformed_query_status(app).unwrap_or_else(|| "Searching...".to_string()))
+            .style(Style::default().fg(Color::Yellow))
     } else if let Some(ref err) = app.search_error {
         Paragraph::new(err.as_str()).style(Style::default().fg(Color::Red))
+    } else if streaming::direct_torrent_source(&app.search_input).is_some() {
+        Paragraph::new("Enter: stream this torrent without searching")
+            .style(Style::default().fg(Color::Cyan))
     } else if has_suggestions {
-        // Check if selected suggestion is a TV show
-        let selected_is_tv = app
+        // Check if selected suggestion is a TV show or person
+        let selected_type = app
             .suggestions
             .get(app.selected_suggestion)
-            .is_some_and(|s| s.media_type == "tv");
-        let help_text = if selected_is_tv {
-            "↑/↓: select | Tab: accept | Enter: browse episodes"
-        } else {
-            "↑/↓: select | Tab: accept | Enter: search"
+            .map(|s| s.media_type.as_str());
+        let help_text = match selected_type {
+            Some("tv") | Some("anime") => "↑/↓: select | Tab: accept | Enter: browse episodes",
+            Some("person") => "↑/↓: select | Enter: show filmography",
+            _ => "↑/↓: select | Tab: accept | Enter: search",
         };
         Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray))
     } else {
-        Paragraph::new("Enter: search | s: settings | d: doctor | Esc: quit")
-            .style(Style::default().fg(Color::DarkGray))
+        Paragraph::new(format!(
+            "Enter: search | Tab: {} | actor:/director: <name> | s: settings | d: doctor | Esc: quit",
+            app.media_mode.next().label().to_lowercase()
+        ))
+        .style(Style::default().fg(Color::DarkGray))
     };
     frame.render_widget(status, chunks[3]);
 }
@@ -477,6 +1031,8 @@ fn draw_search(frame: &mut Frame, app: &App) {
 fn draw_results(frame: &mut Frame, app: &App) {
     // Adjust layout based on whether we have TMDB info
     let has_tmdb = app.tmdb_info.is_some();
+    let (area, status_area) = split_status_bar(frame.area());
+    draw_status_bar(frame, app, status_area);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
@@ -493,7 +1049,7 @@ fn draw_results(frame: &mut Frame, app: &App) {
                 Constraint::Length(2), // Help
             ]
         })
-        .split(frame.area());
+        .split(area);
 
     // Title / TMDB info
     if let Some(ref tmdb) = app.tmdb_info {
@@ -504,7 +1060,10 @@ fn draw_results(frame: &mut Frame, app: &App) {
             .unwrap_or_default();
         let media_str = tmdb.media_type.as_deref().unwrap_or("");
 
-        let header = format!("{}{} [{}]{}", tmdb.title, year_str, media_str, rating_str);
+        let mut header = format!("{}{} [{}]{}", tmdb.title, year_str, media_str, rating_str);
+        if app.tmdb_info_guessed {
+            header.push_str("  (guessed from this release)");
+        }
 
         let title = Paragraph::new(header)
             .style(
@@ -521,12 +1080,131 @@ fn draw_results(frame: &mut Frame, app: &App) {
     }
 
     // Results list
-    let items: Vec<ListItem> = app
-        .results
-        .iter()
-        .enumerate()
-        .map(|(i, r)| {
-            let style = if i == app.selected_index {
+    let selected_style = Style::default()
+        .fg(Color::Black)
+        .bg(Color::Cyan)
+        .add_modifier(Modifier::BOLD);
+    let show_size = chunks[1].width >= RESULTS_SIZE_MIN_WIDTH;
+    let items: Vec<ListItem> = if app.group_releases {
+        app.result_rows()
+            .into_iter()
+            .filter_map(|(group, variant)| {
+                let release = app.release_groups.get(group)?;
+                let idx = release.members[variant.unwrap_or(0)];
+                let line = match variant {
+                    Some(_) => result_line(app, idx, show_size, "    ", None),
+                    None => {
+                        let others = release.members.len() - 1;
+                        let (toggle, badge) = match others {
+                            0 => ("  ", None),
+                            _ if app.expanded_group == Some(group) => ("▾ ", Some(others)),
+                            _ => ("▸ ", Some(others)),
+                        };
+                        result_line(app, idx, show_size, toggle, badge)
+                    }
+                };
+                let selected = (group, variant) == (app.selected_group, app.selected_variant);
+                Some(ListItem::new(line).style(if selected {
+                    selected_style
+                } else {
+                    Style::default()
+                }))
+            })
+            .collect()
+    } else {
+        (0..app.results.len())
+            .map(|i| {
+                let style = if i == app.selected_index {
+                    selected_style
+                } else {
+                    Style::default()
+                };
+                ListItem::new(result_line(app, i, show_size, "", None)).style(style)
+            })
+            .collect()
+    };
+
+    let sort_label = if app.compact_mode {
+        app.sort_order.short_label()
+    } else {
+        app.sort_order.label()
+    };
+    let list_title = if app.result_media_filter == MediaFilter::All {
+        format!("Results [{}]", sort_label)
+    } else {
+        format!(
+            "Results [{} | Filter: {}]",
+            sort_label,
+            app.result_media_filter.label()
+        )
+    };
+    let list_title = if app.group_releases {
+        format!("{} ({} releases)", list_title, app.release_groups.len())
+    } else {
+        list_title
+    };
+    let list_title = if app.hidden_results.is_empty() {
+        list_title
+    } else if app.show_hidden {
+        format!(
+            "{} (including {} filtered)",
+            list_title,
+            app.hidden_results.len()
+        )
+    } else {
+        format!("{} ({} filtered)", list_title, app.hidden_results.len())
+    };
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(list_title))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD));
+
+    frame.render_widget(list, chunks[1]);
+
+    // Help
+    let mut help_text = if app.hidden_results.is_empty() {
+        "↑/↓: navigate | Enter: stream | s: sort | m: movies/TV | y/Y: copy link/query | /: new search | q: quit"
+            .to_string()
+    } else {
+        format!(
+            "↑/↓: navigate | Enter: stream | s: sort | m: movies/TV | f: {} filtered ({}) | y/Y: copy link/query | /: new search | q: quit",
+            if app.show_hidden { "hide" } else { "show" },
+            app.hidden_results.len()
+        )
+    };
+    help_text = if app.group_releases {
+        help_text.replace("Enter: stream", "Enter: stream/expand | g: ungroup")
+    } else {
+        help_text.replace("Enter: stream", "Enter: stream | g: group releases")
+    };
+    if let Some(collection) = app.current_collection() {
+        help_text = format!("c: browse {} | {}", collection.name, help_text);
+    }
+    help_text = if app.marked_results.is_empty() {
+        format!("Space: mark | {}", help_text)
+    } else {
+        format!(
+            "Space: mark | R: race marked ({}) | {}",
+            app.marked_results.len(),
+            help_text
+        )
+    };
+    draw_help(frame, app, chunks[2], help_text);
+
+    // Which TMDB match did the search mean
+    if let Some(prompt) = app.current_tmdb_prompt() {
+        let area = frame.area();
+        let popup_width = 60.min(area.width.saturating_sub(4));
+        let popup_height = (prompt.candidates.len() as u16 + 6).min(area.height);
+        let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+        let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+        let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+        frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+        let max_len = popup_width.saturating_sub(20) as usize;
+        let mut text = vec![Line::from("")];
+        for (idx, candidate) in prompt.candidates.iter().enumerate() {
+            let style = if idx == app.selected_tmdb_candidate {
                 Style::default()
                     .fg(Color::Black)
                     .bg(Color::Cyan)
@@ -534,42 +1212,95 @@ fn draw_results(frame: &mut Frame, app: &App) {
             } else {
                 Style::default()
             };
-
-            let seeders = r.seeders.unwrap_or(0);
-            let seeder_color = if seeders >= 50 {
-                Color::Green
-            } else if seeders >= 10 {
-                Color::Yellow
-            } else {
-                Color::Red
+            let (title, _) = candidate.title.unicode_truncate(max_len);
+            let year = candidate
+                .year
+                .map(|y| y.to_string())
+                .unwrap_or_else(|| "----".to_string());
+            let media_type = match candidate.media_type.as_deref() {
+                Some("tv") => "TV",
+                _ => "Movie",
             };
+            text.push(Line::from(Span::styled(
+                format!(" {} ({}) - {} ", title, year, media_type),
+                style,
+            )));
+        }
+        text.push(Line::from(""));
+        text.push(Line::from(vec![
+            Span::styled("Enter", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Use this  |  "),
+            Span::styled("Esc", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Keep current"),
+        ]));
+
+        let popup = Paragraph::new(text)
+            .alignment(ratatui::layout::Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Yellow))
+                    .title(format!("Which \"{}\"?", prompt.query)),
+            );
+        frame.render_widget(popup, popup_area);
+    }
+}
 
-            let line = Line::from(vec![
-                Span::styled(
-                    format!("S:{:<4}", seeders),
-                    Style::default().fg(seeder_color),
-                ),
-                Span::raw(" | "),
-                Span::styled(r.size_human(), Style::default().fg(Color::DarkGray)),
-                Span::raw(" | "),
-                Span::raw(&r.title),
-            ]);
+/// One result in the list, `badge` counts the other variants of a collapsed
+/// release
+fn result_line<'a>(
+    app: &'a App,
+    i: usize,
+    show_size: bool,
+    indent: &'a str,
+    badge: Option<usize>,
+) -> Line<'a> {
+    let r = &app.results[i];
+    let seeders = r.seeders.unwrap_or(0);
+    let seeder_color = if seeders >= 50 {
+        Color::Green
+    } else if seeders >= 10 {
+        Color::Yellow
+    } else {
+        Color::Red
+    };
 
-            ListItem::new(line).style(style)
-        })
-        .collect();
+    let score = app.result_scores.get(i).copied().unwrap_or(0);
+    let score_color = if score >= 70 {
+        Color::Green
+    } else if score >= 40 {
+        Color::Yellow
+    } else {
+        Color::DarkGray
+    };
 
-    let list_title = format!("Results [{}]", app.sort_order.label());
-    let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title(list_title))
-        .highlight_style(Style::default().add_modifier(Modifier::BOLD));
-
-    frame.render_widget(list, chunks[1]);
-
-    // Help
-    let help = Paragraph::new("↑/↓: navigate | Enter: stream | s: sort | /: new search | q: quit")
-        .style(Style::default().fg(Color::DarkGray));
-    frame.render_widget(help, chunks[2]);
+    let marker = if app.is_marked(r) { "● " } else { "  " };
+    let mut spans = vec![
+        Span::styled(marker, Style::default().fg(Color::Magenta)),
+        Span::styled(format!("{:>3}", score), Style::default().fg(score_color)),
+        Span::raw(" "),
+        Span::styled(
+            format!("S:{:<4}", seeders),
+            Style::default().fg(seeder_color),
+        ),
+        Span::raw(" | "),
+    ];
+    if show_size {
+        spans.push(Span::styled(
+            r.size_human(),
+            Style::default().fg(Color::DarkGray),
+        ));
+        spans.push(Span::raw(" | "));
+    }
+    spans.push(Span::raw(indent));
+    spans.push(Span::raw(&r.title));
+    if let Some(count) = badge {
+        spans.push(Span::styled(
+            format!(" [+{}]", count),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    Line::from(spans)
 }
 
 fn draw_file_selection(frame: &mut Frame, app: &App) {
@@ -623,7 +1354,28 @@ fn draw_file_selection(frame: &mut Frame, app: &App) {
         })
         .collect();
 
-    let list_title = format!("Files [{}]", app.available_files.len());
+    let mut list_title = if app.extra_files.is_empty() {
+        format!("Files [{}]", app.available_files.len())
+    } else if app.show_extras {
+        format!(
+            "Files [{}] (including {} extras)",
+            app.available_files.len(),
+            app.extra_files.len()
+        )
+    } else {
+        format!(
+            "Files [{}] ({} extras hidden)",
+            app.available_files.len(),
+            app.extra_files.len()
+        )
+    };
+    if app.shuffle_episodes {
+        list_title.push_str(if app.loop_shuffle {
+            " [Shuffle, loop]"
+        } else {
+            " [Shuffle]"
+        });
+    }
     let list = List::new(items)
         .block(Block::default().borders(Borders::ALL).title(list_title))
         .highlight_style(Style::default().add_modifier(Modifier::BOLD));
@@ -631,141 +1383,30 @@ fn draw_file_selection(frame: &mut Frame, app: &App) {
     frame.render_widget(list, chunks[1]);
 
     // Help
-    let help = Paragraph::new("↑/↓: navigate | Enter: play | Esc: cancel")
-        .style(Style::default().fg(Color::DarkGray));
-    frame.render_widget(help, chunks[2]);
-}
-
-fn draw_streaming(frame: &mut Frame, app: &App) {
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(2)
-        .constraints([
-            Constraint::Length(3), // Title
-            Constraint::Length(3), // Status
-            Constraint::Length(3), // Progress bar
-            Constraint::Length(3), // Stats
-            Constraint::Length(3), // File info
-            Constraint::Min(0),    // Empty
-            Constraint::Length(2), // Help
-        ])
-        .split(frame.area());
-
-    // Title
-    let title = Paragraph::new(&*app.current_title)
-        .style(
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        )
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Now Streaming"),
-        );
-    frame.render_widget(title, chunks[0]);
-
-    // Status - show racing message if active
-    let (status_text, status_color) = if let Some(ref racing_msg) = app.racing_message {
-        (racing_msg.as_str(), Color::Magenta)
-    } else {
-        match &app.streaming_state {
-            StreamingState::Connecting => ("Connecting...", Color::Yellow),
-            StreamingState::FetchingMetadata => ("Fetching metadata...", Color::Yellow),
-            StreamingState::Ready { .. } => ("Playing", Color::Green),
-            StreamingState::Playing => ("Playing", Color::Green),
-            StreamingState::Error(e) => (e.as_str(), Color::Red),
-        }
+    let shuffle = match (app.shuffle_episodes, app.loop_shuffle) {
+        (true, true) => "S: shuffle off | L: loop off",
+        (true, false) => "S: shuffle off | L: loop",
+        (false, _) => "S: shuffle",
     };
-
-    let status = Paragraph::new(status_text)
-        .style(Style::default().fg(status_color))
-        .block(Block::default().borders(Borders::ALL).title("Status"));
-    frame.render_widget(status, chunks[1]);
-
-    // Progress bars - show both download and playback progress if available
-    let download = &app.download_progress;
-
-    // Show playback progress if we have it from mpv, otherwise download progress
-    let (gauge_title, gauge_percent, gauge_label) = if app.playback_progress > 0.0 {
-        (
-            "Playback Progress",
-            app.playback_progress,
-            format!("{:.1}% watched", app.playback_progress),
-        )
+    let help_text = if app.extra_files.is_empty() {
+        format!("↑/↓: navigate | Enter: play | {} | Esc: cancel", shuffle)
     } else {
-        (
-            "Download Progress",
-            download.progress_percent,
-            format!(
-                "{:.1}% ({} / {})",
-                download.progress_percent,
-                format_bytes(download.downloaded_bytes),
-                format_bytes(download.total_bytes)
-            ),
+        format!(
+            "↑/↓: navigate | Enter: play | x: {} extras ({}) | {} | Esc: cancel",
+            if app.show_extras { "hide" } else { "show" },
+            app.extra_files.len(),
+            shuffle
         )
     };
+    draw_help(frame, app, chunks[2], help_text);
+}
 
-    let gauge = Gauge::default()
-        .block(Block::default().borders(Borders::ALL).title(gauge_title))
-        .gauge_style(Style::default().fg(Color::Cyan).bg(Color::DarkGray))
-        .percent((gauge_percent.min(100.0)) as u16)
-        .label(gauge_label);
-    frame.render_widget(gauge, chunks[2]);
-
-    // Stats line - show download stats
-    let stats_text = format!(
-        "↓ {}/s  ↑ {}/s  Peers: {}  DL: {:.0}%",
-        format_bytes(download.download_speed),
-        format_bytes(download.upload_speed),
-        download.peers_connected,
-        download.progress_percent
-    );
-    let stats = Paragraph::new(stats_text)
-        .style(Style::default().fg(Color::White))
-        .block(Block::default().borders(Borders::ALL).title("Stats"));
-    frame.render_widget(stats, chunks[3]);
-
-    // File info with episode tracking
-    if !app.current_file.is_empty() {
-        let episode_info = if app.available_files.len() > 1 {
-            format!(
-                "{} [{}/{}]",
-                app.current_file,
-                app.current_episode_index + 1,
-                app.available_files.len()
-            )
-        } else {
-            app.current_file.clone()
-        };
-
-        let mut file_spans = vec![Span::raw(episode_info)];
-
-        // Show next episode indicator if available
-        if let Some(next) = app.next_episode() {
-            let next_name = next.name.rsplit('/').next().unwrap_or(&next.name);
-            file_spans.push(Span::styled(
-                format!("  → Next: {}", next_name),
-                Style::default().fg(Color::DarkGray),
-            ));
-        }
-
-        let file_info = Paragraph::new(Line::from(file_spans))
-            .style(Style::default().fg(Color::White))
-            .block(Block::default().borders(Borders::ALL).title("File"));
-        frame.render_widget(file_info, chunks[4]);
-    }
-
-    // Help
-    let help_text = if app.show_resume_prompt {
-        "r: resume | s: start over"
-    } else if app.has_next_episode() {
-        "q: stop & return | n: skip to next episode"
+fn draw_streaming(frame: &mut Frame, app: &App) {
+    if frame.area().height < COMPACT_STREAMING_HEIGHT {
+        draw_streaming_compact(frame, app);
     } else {
-        "q: stop & return to results"
-    };
-    let help = Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray));
-    frame.render_widget(help, chunks[6]);
+        draw_streaming_full(frame, app);
+    }
 
     // Resume prompt overlay
     if app.show_resume_prompt {
@@ -805,28 +1446,687 @@ fn draw_streaming(frame: &mut Frame, app: &App) {
             );
         frame.render_widget(popup, popup_area);
     }
+
+    // Prefetched next episode overlay
+    if let Some(next) = app
+        .prefetched_episode
+        .as_ref()
+        .filter(|_| app.show_next_episode_prompt)
+    {
+        let area = frame.area();
+        let popup_width = 60.min(area.width.saturating_sub(4));
+        let popup_height = 7;
+        let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+        let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+        let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+        frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+        let max_len = popup_width.saturating_sub(4) as usize;
+        let (title, _) = next.title.unicode_truncate(max_len);
+        let text = vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                title.to_string(),
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Enter", Style::default().fg(Color::Cyan)),
+                Span::raw(" - Play  |  "),
+                Span::styled("q", Style::default().fg(Color::Cyan)),
+                Span::raw(" - Back"),
+            ]),
+        ];
+
+        let popup = Paragraph::new(text)
+            .alignment(ratatui::layout::Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Green))
+                    .title("Play Next Episode"),
+            );
+        frame.render_widget(popup, popup_area);
+    }
+
+    // Missing subtitles overlay
+    if let Some(prompt) = app.current_subtitle_prompt() {
+        let area = frame.area();
+        let popup_width = 60.min(area.width.saturating_sub(4));
+        let popup_height = 7;
+        let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+        let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+        let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+        frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+        let languages: Vec<String> = prompt
+            .languages
+            .iter()
+            .map(|l| format!("'{}'", l))
+            .collect();
+        let keys = match &prompt.language_input {
+            Some(input) => vec![
+                Span::raw("Language: "),
+                Span::styled(
+                    format!("{}_", input),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            ],
+            None => {
+                let continue_label = if prompt.has_fallback {
+                    " - Use torrent's  |  "
+                } else {
+                    " - Continue without  |  "
+                };
+                vec![
+                    Span::styled("Enter", Style::default().fg(Color::Cyan)),
+                    Span::raw(continue_label),
+                    Span::styled("l", Style::default().fg(Color::Cyan)),
+                    Span::raw(" - Other language  |  "),
+                    Span::styled("q", Style::default().fg(Color::Cyan)),
+                    Span::raw(" - Cancel"),
+                ]
+            }
+        };
+        let text = vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                format!("No subtitles found for {}", languages.join(", ")),
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(keys),
+        ];
+
+        let popup = Paragraph::new(text)
+            .alignment(ratatui::layout::Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Yellow))
+                    .title("Subtitles"),
+            );
+        frame.render_widget(popup, popup_area);
+    }
+
+    // Peer count overlay
+    if let Some(prompt) = app.current_probe_prompt() {
+        let area = frame.area();
+        let popup_width = 50.min(area.width.saturating_sub(4));
+        let popup_height = 7;
+        let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+        let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+        let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+        frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+        let (found, color) = match prompt.probe.peers {
+            0 if prompt.probe.metadata_pending => {
+                ("No peers yet, metadata pending".to_string(), Color::Yellow)
+            }
+            0 => ("Found no peers".to_string(), Color::Red),
+            1 => ("Found 1 peer".to_string(), Color::Yellow),
+            n => (format!("Found {} peers", n), Color::Green),
+        };
+        let text = vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                found,
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Enter", Style::default().fg(Color::Cyan)),
+                Span::raw(" - Stream  |  "),
+                Span::styled("q", Style::default().fg(Color::Cyan)),
+                Span::raw(" - Back"),
+            ]),
+        ];
+
+        let popup = Paragraph::new(text)
+            .alignment(ratatui::layout::Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(color))
+                    .title("Torrent Health"),
+            );
+        frame.render_widget(popup, popup_area);
+    }
+
+    // Stream URL overlay
+    if let Some(url) = &app.shared_stream_url {
+        let area = frame.area();
+        let popup_width = (url.len() as u16 + 4)
+            .max(40)
+            .min(area.width.saturating_sub(4));
+        let popup_height = 7;
+        let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+        let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+        let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+        frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+        let text = vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                url.clone(),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(Span::styled(
+                "Copied if your terminal allows it  |  any key to close",
+                Style::default().fg(Color::DarkGray),
+            )),
+        ];
+
+        let popup = Paragraph::new(text)
+            .alignment(ratatui::layout::Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Cyan))
+                    .title("Stream URL"),
+            );
+        frame.render_widget(popup, popup_area);
+    }
+
+    // Player picker overlay
+    if app.show_player_picker {
+        let area = frame.area();
+        let popup_width = 40.min(area.width.saturating_sub(4));
+        let popup_height = (app.player_choices.len() as u16 + 2).min(area.height);
+        let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+        let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+        let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+        frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+        let lines: Vec<Line> = app
+            .player_choices
+            .iter()
+            .enumerate()
+            .map(|(i, player)| {
+                if i == app.selected_player_index {
+                    Line::from(Span::styled(
+                        format!("> {}", player),
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    ))
+                } else {
+                    Line::from(format!("  {}", player))
+                }
+            })
+            .collect();
+
+        let popup = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Cyan))
+                .title("Open In"),
+        );
+        frame.render_widget(popup, popup_area);
+    }
 }
 
-fn draw_doctor(frame: &mut Frame, app: &App) {
+/// Boxed layout with the speed graph, for terminals tall enough to hold it
+fn draw_streaming_full(frame: &mut Frame, app: &App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(2)
         .constraints([
             Constraint::Length(3), // Title
-            Constraint::Min(0),    // Check results
+            Constraint::Length(3), // Status
+            Constraint::Length(3), // Progress bar
+            Constraint::Length(3), // Piece map
+            Constraint::Length(3), // Speed graph
+            Constraint::Length(3), // Stats
+            Constraint::Length(3), // File info
+            Constraint::Min(0),    // Empty
             Constraint::Length(2), // Help
         ])
         .split(frame.area());
 
     // Title
-    let title = Paragraph::new("Service Health Check")
+    let title = Paragraph::new(&*app.current_title)
         .style(
             Style::default()
                 .fg(Color::Cyan)
                 .add_modifier(Modifier::BOLD),
         )
-        .block(Block::default());
-    frame.render_widget(title, chunks[0]);
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Now Streaming"),
+        );
+    frame.render_widget(title, chunks[0]);
+
+    let (status_text, status_color) = streaming_status(app);
+    let status = Paragraph::new(status_text)
+        .style(Style::default().fg(status_color))
+        .block(Block::default().borders(Borders::ALL).title("Status"));
+    frame.render_widget(status, chunks[1]);
+
+    let (gauge_title, gauge_percent, gauge_label) = streaming_progress(app);
+    let gauge = Gauge::default()
+        .block(Block::default().borders(Borders::ALL).title(gauge_title))
+        .gauge_style(Style::default().fg(Color::Cyan).bg(Color::DarkGray))
+        .percent((gauge_percent.min(100.0)) as u16)
+        .label(gauge_label);
+    frame.render_widget(gauge, chunks[2]);
+
+    let pieces = Paragraph::new(piece_strip(app, chunks[3].width.saturating_sub(2)))
+        .block(Block::default().borders(Borders::ALL).title("Pieces"));
+    frame.render_widget(pieces, chunks[3]);
+
+    // Download speed over the last minute
+    let peak = app
+        .download_speed_history
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(0);
+    let graph = Paragraph::new(sparkline(app.download_speed_history.iter().copied(), peak))
+        .style(Style::default().fg(Color::Cyan))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Download Speed (peak {}/s)", format_bytes(peak))),
+        );
+    frame.render_widget(graph, chunks[4]);
+
+    let stats = Paragraph::new(streaming_stats(app))
+        .style(Style::default().fg(Color::White))
+        .block(Block::default().borders(Borders::ALL).title("Stats"));
+    frame.render_widget(stats, chunks[5]);
+
+    if let Some(file_line) = streaming_file_line(app) {
+        let file_info = Paragraph::new(file_line)
+            .style(Style::default().fg(Color::White))
+            .block(Block::default().borders(Borders::ALL).title("File"));
+        frame.render_widget(file_info, chunks[6]);
+    }
+
+    draw_help(frame, app, chunks[8], streaming_help(app));
+}
+
+/// The file's downloaded pieces in `width` cells, with the playback position
+/// marked. Empty until the first piece map arrives.
+fn piece_strip(app: &App, width: u16) -> Line<'static> {
+    let (Some(map), width @ 1..) = (&app.piece_map, width as usize) else {
+        return Line::default();
+    };
+    let position = (app.playback_progress > 0.0)
+        .then(|| ((app.playback_progress / 100.0 * width as f64) as usize).min(width - 1));
+
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut run = String::new();
+    let mut run_style = Style::default();
+    for (i, share) in map.buckets(width).into_iter().enumerate() {
+        let (cell, style) = if position == Some(i) {
+            ('┃', Style::default().fg(Color::Yellow))
+        } else if share >= 1.0 {
+            ('█', Style::default().fg(Color::Cyan))
+        } else if share > 0.0 {
+            ('▒', Style::default().fg(Color::Cyan))
+        } else {
+            ('░', Style::default().fg(Color::DarkGray))
+        };
+        if style != run_style && !run.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut run), run_style));
+        }
+        run_style = style;
+        run.push(cell);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, run_style));
+    }
+    Line::from(spans)
+}
+
+/// One line per item and no boxes, status and progress share a line
+fn draw_streaming_compact(frame: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .horizontal_margin(1)
+        .constraints([
+            Constraint::Length(1), // Title
+            Constraint::Length(1), // Status + progress
+            Constraint::Length(1), // Piece map
+            Constraint::Length(1), // Stats
+            Constraint::Length(1), // File info
+            Constraint::Min(0),    // Empty
+            Constraint::Length(1), // Help
+        ])
+        .split(frame.area());
+
+    let title = Paragraph::new(&*app.current_title).style(
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    );
+    frame.render_widget(title, chunks[0]);
+
+    let (status_text, status_color) = streaming_status(app);
+    let (_, gauge_percent, gauge_label) = streaming_progress(app);
+    let gauge = LineGauge::default()
+        .label(Line::from(vec![
+            Span::styled(status_text, Style::default().fg(status_color)),
+            Span::raw(format!("  {}", gauge_label)),
+        ]))
+        .filled_style(Style::default().fg(Color::Cyan))
+        .unfilled_style(Style::default().fg(Color::DarkGray))
+        .ratio(gauge_percent.clamp(0.0, 100.0) / 100.0);
+    frame.render_widget(gauge, chunks[1]);
+
+    frame.render_widget(Paragraph::new(piece_strip(app, chunks[2].width)), chunks[2]);
+
+    let stats = Paragraph::new(streaming_stats(app)).style(Style::default().fg(Color::White));
+    frame.render_widget(stats, chunks[3]);
+
+    if let Some(file_line) = streaming_file_line(app) {
+        frame.render_widget(Paragraph::new(file_line), chunks[4]);
+    }
+
+    draw_help(frame, app, chunks[6], streaming_help(app));
+}
+
+/// Status line text and color, the racing message wins when racing
+fn streaming_status(app: &App) -> (String, Color) {
+    let (status_text, status_color) = if let Some(ref racing_msg) = app.racing_message {
+        match app.add_phase {
+            Some(phase) => (format!("{}  |  {}", racing_msg, phase), Color::Magenta),
+            None => (racing_msg.clone(), Color::Magenta),
+        }
+    } else if let Some(phase) = app.add_phase.filter(|_| {
+        matches!(
+            app.streaming_state,
+            StreamingState::Connecting | StreamingState::FetchingMetadata
+        )
+    }) {
+        (format!("{}...", phase), Color::Yellow)
+    } else if let Some(percent) = app
+        .buffering_percent
+        .filter(|_| matches!(app.streaming_state, StreamingState::Ready { .. }))
+    {
+        (format!("Buffering {:.1}%...", percent), Color::Yellow)
+    } else {
+        let (text, color) = match &app.streaming_state {
+            StreamingState::Connecting => ("Connecting...", Color::Yellow),
+            StreamingState::FetchingMetadata => ("Fetching metadata...", Color::Yellow),
+            StreamingState::Ready { .. } | StreamingState::Playing { .. } => {
+                ("Playing", Color::Green)
+            }
+            StreamingState::BackendLost => ("Stream stopped responding", Color::Red),
+            StreamingState::Error(e) => (e.as_str(), Color::Red),
+        };
+        (text.to_string(), color)
+    };
+
+    let (status_text, status_color) = match &app.casting_to {
+        Some(device) if status_color == Color::Green => {
+            (format!("Casting to {}", device), Color::Cyan)
+        }
+        _ => (status_text, status_color),
+    };
+
+    match app.current_chapter_label() {
+        Some(chapter) if matches!(status_color, Color::Green | Color::Cyan) => {
+            (format!("{}  |  {}", status_text, chapter), status_color)
+        }
+        _ => (status_text, status_color),
+    }
+}
+
+/// Playback progress from mpv if we have it, otherwise download progress
+fn streaming_progress(app: &App) -> (&'static str, f64, String) {
+    let download = &app.download_progress;
+    if app.playback_progress > 0.0 {
+        (
+            "Playback Progress",
+            app.playback_progress,
+            format!("{:.1}% watched", app.playback_progress),
+        )
+    } else {
+        (
+            "Download Progress",
+            download.progress_percent,
+            format!(
+                "{:.1}% ({} / {})",
+                download.progress_percent,
+                format_bytes(download.downloaded_bytes),
+                format_bytes(download.total_bytes)
+            ),
+        )
+    }
+}
+
+/// Download stats and the audio track mpv is playing
+fn streaming_stats(app: &App) -> String {
+    let download = &app.download_progress;
+    if app.compact_mode {
+        let mut stats_text = format!(
+            "↓{}/s ↑{}/s P:{} {:.0}% UL:{}",
+            format_bytes(download.download_speed),
+            format_bytes(download.upload_speed),
+            download.peers_connected,
+            download.progress_percent,
+            format_bytes(app.session_upload().0)
+        );
+        if let Some(track) = app.current_audio_track() {
+            stats_text.push_str(&format!(" A:{}", track.label()));
+        }
+        return stats_text;
+    }
+    let mut stats_text = format!(
+        "↓ {}/s  ↑ {}/s  Peers: {}  DL: {:.0}%",
+        format_bytes(download.download_speed),
+        format_bytes(download.upload_speed),
+        download.peers_connected,
+        download.progress_percent
+    );
+    stats_text.push_str(&format!("  {}", upload_summary(app)));
+    if let Some(track) = app.current_audio_track() {
+        stats_text.push_str(&format!(
+            "  Audio: {} [{}/{}]",
+            track.label(),
+            app.audio_tracks
+                .iter()
+                .position(|t| t.selected)
+                .unwrap_or(0)
+                + 1,
+            app.audio_tracks.len()
+        ));
+    }
+    stats_text
+}
+
+/// Current file with episode tracking, None before a file is picked
+fn streaming_file_line(app: &App) -> Option<Line<'static>> {
+    if app.current_file.is_empty() {
+        return None;
+    }
+    let episode_info = if app.available_files.len() > 1 {
+        format!(
+            "{} [{}/{}]",
+            app.current_file,
+            app.current_episode_index + 1,
+            app.available_files.len()
+        )
+    } else {
+        app.current_file.clone()
+    };
+
+    let mut file_spans = vec![Span::raw(episode_info)];
+    if app.shuffle_episodes && app.available_files.len() > 1 {
+        file_spans.push(Span::styled(
+            if app.loop_shuffle {
+                " [Shuffle, loop]"
+            } else {
+                " [Shuffle]"
+            },
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+
+    // Show next episode indicator if available
+    if let Some(next) = app.next_episode() {
+        let next_name = next.name.rsplit('/').next().unwrap_or(&next.name);
+        file_spans.push(Span::styled(
+            format!("  → Next: {}", next_name),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    if let Some(subtitles) = app.subtitle_status.label() {
+        file_spans.push(Span::styled(
+            format!("  {}", subtitles),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    Some(Line::from(file_spans))
+}
+
+fn streaming_help(app: &App) -> &'static str {
+    if app.is_picking_subtitle_language() {
+        "Enter: search | Esc: back"
+    } else if app.current_subtitle_prompt().is_some() {
+        "Enter: continue | l: another language | q: cancel"
+    } else if app.current_probe_prompt().is_some() {
+        "Enter: stream | q: back"
+    } else if app.show_player_picker {
+        "↑/↓: select | Enter: open | q: back"
+    } else if app.shared_stream_url.is_some() {
+        "any key: close"
+    } else if app.show_next_episode_prompt {
+        "Enter: play next | q: back"
+    } else if app.streaming_state == StreamingState::BackendLost && !app.show_resume_prompt {
+        "r: restart stream | q: stop & return"
+    } else if app.show_resume_prompt {
+        "r: resume | s: start over"
+    } else if app.has_next_episode() {
+        "q: stop & return | n: skip to next episode | p: peers | a: audio | [/]: chapters | o: other player | C: cast | u: URL"
+    } else {
+        "q: stop & return to results | p: peers | a: audio | [/]: chapters | o: other player | C: cast | u: URL"
+    }
+}
+
+/// One block character per sample, scaled so the peak fills the row
+fn sparkline(samples: impl IntoIterator<Item = u64>, peak: u64) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    samples
+        .into_iter()
+        .map(|sample| {
+            let level = (sample * (BARS.len() as u64 - 1))
+                .checked_div(peak)
+                .unwrap_or(0) as usize;
+            BARS[level]
+        })
+        .collect()
+}
+
+fn draw_history_export(frame: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Min(0),    // Details
+            Constraint::Length(2), // Help
+        ])
+        .split(frame.area());
+
+    let title = Paragraph::new("Export Watch History")
+        .style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .block(Block::default());
+    frame.render_widget(title, chunks[0]);
+
+    let details = vec![
+        Line::from(format!(
+            "{} entries will be written as CSV (title, tmdb_id, watched_percent, last_watched) to:",
+            app.history_export_count
+        )),
+        Line::from(""),
+        Line::from(Span::styled(
+            app.history_export_path.display().to_string(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+    ];
+    let paragraph = Paragraph::new(details)
+        .wrap(ratatui::widgets::Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL));
+    frame.render_widget(paragraph, chunks[1]);
+
+    draw_help(frame, app, chunks[2], "Enter: export | q/Esc: back");
+
+    // Result popup
+    if let Some(result) = &app.history_export_result {
+        let (message, color, popup_title) = match result {
+            Ok(msg) => (msg, Color::Green, "Export Complete"),
+            Err(msg) => (msg, Color::Red, "Export Failed"),
+        };
+
+        let area = frame.area();
+        let popup_width = 60.min(area.width.saturating_sub(4));
+        let popup_height = 7;
+        let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+        let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+        let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+        frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+        let popup = Paragraph::new(vec![
+            Line::from(""),
+            Line::from(message.as_str()),
+            Line::from(""),
+            Line::from(Span::styled(
+                "Press any key to continue",
+                Style::default().fg(Color::DarkGray),
+            )),
+        ])
+        .alignment(ratatui::layout::Alignment::Center)
+        .wrap(ratatui::widgets::Wrap { trim: true })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(color))
+                .title(popup_title),
+        );
+        frame.render_widget(popup, popup_area);
+    }
+}
+
+fn draw_doctor(frame: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Min(0),    // Check results
+            Constraint::Length(2), // Help
+        ])
+        .split(frame.area());
+
+    // Title
+    let title = Paragraph::new("Service Health Check")
+        .style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .block(Block::default());
+    frame.render_widget(title, chunks[0]);
 
     // Results
     if app.is_checking {
@@ -848,27 +2148,466 @@ fn draw_doctor(frame: &mut Frame, app: &App) {
                     CheckStatus::Error => ("✗", Color::Red),
                 };
 
-                let line = Line::from(vec![
-                    Span::styled(format!("{} ", icon), Style::default().fg(color)),
-                    Span::styled(
-                        format!("{:<10}", r.name),
-                        Style::default().add_modifier(Modifier::BOLD),
-                    ),
-                    Span::raw(&r.message),
-                ]);
+                let line = Line::from(vec![
+                    Span::styled(format!("{} ", icon), Style::default().fg(color)),
+                    Span::styled(
+                        format!("{:<10}", r.name),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(&r.message),
+                ]);
+
+                ListItem::new(line)
+            })
+            .collect();
+
+        let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Results"));
+        frame.render_widget(list, chunks[1]);
+    }
+
+    // Help
+    draw_help(
+        frame,
+        app,
+        chunks[2],
+        "r: run checks | i: indexers | b: diagnostics bundle | q/Esc: back to search",
+    );
+}
+
+fn draw_indexers(frame: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Min(0),    // Indexer stats
+            Constraint::Length(2), // Help
+        ])
+        .split(frame.area());
+
+    let title = Paragraph::new("Indexer Health")
+        .style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .block(Block::default());
+    frame.render_widget(title, chunks[0]);
+
+    let health = app.indexer_health.lock().unwrap();
+    let now = indexer_health::now_secs();
+    let rows: Vec<Row> = health
+        .iter()
+        .map(|(name, stats)| {
+            let (status, color) = match stats.demoted_for(now) {
+                Some(left) => (
+                    format!("demoted, {}m left", left.as_secs().div_ceil(60)),
+                    Color::Red,
+                ),
+                None if stats.consecutive_failures > 0 => (
+                    format!("{} failing", stats.consecutive_failures),
+                    Color::Yellow,
+                ),
+                None => ("ok".to_string(), Color::Green),
+            };
+            let latency = stats
+                .average_latency()
+                .map(|d| format!("{}ms", d.as_millis()))
+                .unwrap_or_else(|| "-".to_string());
+            let success = stats
+                .success_rate()
+                .map(|rate| format!("{:.0}%", rate * 100.0))
+                .unwrap_or_else(|| "-".to_string());
+            let results = stats
+                .average_results()
+                .map(|r| format!("{:.1}", r))
+                .unwrap_or_else(|| "-".to_string());
+            let peak = stats.recent_results.iter().max().copied().unwrap_or(0);
+            let history = sparkline(stats.recent_results.iter().map(|&n| n as u64), peak as u64);
+
+            Row::new(vec![
+                Cell::from(name.clone()).style(Style::default().add_modifier(Modifier::BOLD)),
+                Cell::from(stats.searches().to_string()),
+                Cell::from(success),
+                Cell::from(latency),
+                Cell::from(if stats.last_latency_ms > 0 {
+                    format!("{}ms", stats.last_latency_ms)
+                } else {
+                    "-".to_string()
+                }),
+                Cell::from(results),
+                Cell::from(history).style(Style::default().fg(Color::Cyan)),
+                Cell::from(status).style(Style::default().fg(color)),
+            ])
+        })
+        .collect();
+
+    if rows.is_empty() {
+        let empty = Paragraph::new("No searches recorded yet")
+            .style(Style::default().fg(Color::DarkGray))
+            .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(empty, chunks[1]);
+    } else {
+        let header = Row::new(vec![
+            "Indexer", "Searches", "Success", "Avg", "Last", "Results", "History", "Status",
+        ])
+        .style(Style::default().fg(Color::DarkGray));
+        let count = rows.len();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Min(16),
+                Constraint::Length(8),
+                Constraint::Length(7),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(7),
+                Constraint::Length(10),
+                Constraint::Length(18),
+            ],
+        )
+        .header(header)
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("{} indexers", count)),
+        );
+        let mut state = TableState::default().with_selected(Some(app.selected_indexer_index));
+        frame.render_stateful_widget(table, chunks[1], &mut state);
+    }
+
+    draw_help(
+        frame,
+        app,
+        chunks[2],
+        "e: re-enable | ↑/↓: navigate | q: back to doctor",
+    );
+}
+
+fn draw_peer_list(frame: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Min(0),    // Peers
+            Constraint::Length(2), // Help
+        ])
+        .split(frame.area());
+
+    let title = Paragraph::new(format!("Peers - {}", app.current_title))
+        .style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .block(Block::default());
+    frame.render_widget(title, chunks[0]);
+
+    if let Some(error) = &app.peers_error {
+        let error = Paragraph::new(format!("Failed to load peers: {}", error))
+            .style(Style::default().fg(Color::Red))
+            .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(error, chunks[1]);
+    } else if app.peers.is_empty() {
+        let text = if app.is_fetching_peers {
+            "Loading peers..."
+        } else {
+            "No peers yet"
+        };
+        let empty = Paragraph::new(text)
+            .style(Style::default().fg(Color::DarkGray))
+            .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(empty, chunks[1]);
+    } else {
+        // Keep the selected peer in view
+        let visible = chunks[1].height.saturating_sub(2) as usize;
+        let offset = (app.selected_peer_index + 1).saturating_sub(visible);
+        let live = app.peers.iter().filter(|p| p.state == "live").count();
+
+        let items: Vec<ListItem> = app
+            .peers
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .map(|(idx, peer)| {
+                let style = if idx == app.selected_peer_index {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let state_color = match peer.state.as_str() {
+                    "live" => Color::Green,
+                    "connecting" | "queued" => Color::Yellow,
+                    _ => Color::DarkGray,
+                };
+
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{:<3}", peer.flag.as_deref().unwrap_or("  "))),
+                    Span::styled(format!("{:<22}", peer.masked_ip), style),
+                    Span::raw(format!(" {:>5.1}%  ", peer.share)),
+                    Span::styled(peer.state.clone(), Style::default().fg(state_color)),
+                ]))
+            })
+            .collect();
+
+        let list = List::new(items).block(Block::default().borders(Borders::ALL).title(format!(
+            "{} peers, {} connected",
+            app.peers.len(),
+            live
+        )));
+        frame.render_widget(list, chunks[1]);
+    }
+
+    draw_help(
+        frame,
+        app,
+        chunks[2],
+        "r: refresh | ↑/↓: navigate | q: back to stream",
+    );
+}
+
+fn draw_cast_devices(frame: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Min(0),    // Devices
+            Constraint::Length(2), // Help
+        ])
+        .split(frame.area());
+
+    let title = Paragraph::new(format!("Cast - {}", app.current_title))
+        .style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .block(Block::default());
+    frame.render_widget(title, chunks[0]);
+
+    if app.cast_devices.is_empty() {
+        let text = if app.is_discovering_cast {
+            "Looking for Chromecasts and DLNA TVs..."
+        } else {
+            "No devices found on the local network"
+        };
+        let empty = Paragraph::new(text)
+            .style(Style::default().fg(Color::DarkGray))
+            .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(empty, chunks[1]);
+    } else {
+        let items: Vec<ListItem> = app
+            .cast_devices
+            .iter()
+            .enumerate()
+            .map(|(idx, device)| {
+                let style = if idx == app.selected_cast_index {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let casting = app.casting_to.as_deref() == Some(device.name.as_str());
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{:<32}", device.name), style),
+                    Span::styled(
+                        format!(" {:<11}", device.kind()),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(
+                        format!("{:<16}", device.host),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(
+                        if casting { "casting" } else { "" },
+                        Style::default().fg(Color::Green),
+                    ),
+                ]))
+            })
+            .collect();
+
+        let title = if app.is_discovering_cast {
+            "Devices (searching...)".to_string()
+        } else {
+            format!("{} devices", app.cast_devices.len())
+        };
+        let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(list, chunks[1]);
+    }
+
+    let help_text = if app.is_starting_cast {
+        "Starting playback on the device..."
+    } else {
+        "Enter: cast | r: search again | ↑/↓: navigate | q: back to stream"
+    };
+    draw_help(frame, app, chunks[2], help_text);
+}
+
+fn draw_person_filmography(frame: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Min(0),    // Credit list
+            Constraint::Length(2), // Help
+        ])
+        .split(frame.area());
+
+    // Title with person name
+    let title = Paragraph::new(format!("👤 {} - Filmography", app.person_name))
+        .style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .block(Block::default());
+    frame.render_widget(title, chunks[0]);
+
+    // Credit list
+    if app.is_fetching_person {
+        let loading =
+            Paragraph::new("Loading filmography...").style(Style::default().fg(Color::Yellow));
+        frame.render_widget(loading, chunks[1]);
+    } else if app.is_fetching_tv_details {
+        let loading =
+            Paragraph::new("Loading seasons...").style(Style::default().fg(Color::Yellow));
+        frame.render_widget(loading, chunks[1]);
+    } else if app.person_credits.is_empty() {
+        let empty = Paragraph::new("No movies or shows found")
+            .style(Style::default().fg(Color::DarkGray))
+            .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(empty, chunks[1]);
+    } else {
+        let items: Vec<ListItem> = app
+            .person_credits
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| {
+                let style = if idx == app.selected_credit_index {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+
+                let media_icon = if item.media_type == "movie" {
+                    "🎬"
+                } else {
+                    "📺"
+                };
+                let year = item.year.map(|y| format!(" ({})", y)).unwrap_or_default();
+                let rating = item
+                    .rating
+                    .filter(|r| *r > 0.0)
+                    .map(|r| format!(" ★ {:.1}", r))
+                    .unwrap_or_default();
+
+                ListItem::new(format!("{} {}{}{}", media_icon, item.title, year, rating))
+                    .style(style)
+            })
+            .collect();
+
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("{} titles", app.person_credits.len())),
+        );
+        frame.render_widget(list, chunks[1]);
+    }
+
+    // Help
+    draw_help(
+        frame,
+        app,
+        chunks[2],
+        "Enter: select | ↑/↓: navigate | /: search | q: back to search",
+    );
+}
+
+fn draw_collection(frame: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Min(0),    // Movie list
+            Constraint::Length(2), // Help
+        ])
+        .split(frame.area());
+
+    let title = Paragraph::new(format!("🎞 {}", app.collection_name))
+        .style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .block(Block::default());
+    frame.render_widget(title, chunks[0]);
+
+    if app.is_fetching_collection {
+        let loading =
+            Paragraph::new("Loading collection...").style(Style::default().fg(Color::Yellow));
+        frame.render_widget(loading, chunks[1]);
+    } else if app.collection_items.is_empty() {
+        let empty = Paragraph::new("No movies in this collection")
+            .style(Style::default().fg(Color::DarkGray))
+            .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(empty, chunks[1]);
+    } else {
+        let items: Vec<ListItem> = app
+            .collection_items
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| {
+                let style = if idx == app.selected_collection_index {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+
+                let year = item
+                    .year
+                    .map(|y| format!(" ({})", y))
+                    .unwrap_or_else(|| " (TBA)".to_string());
+                let rating = item
+                    .rating
+                    .filter(|r| *r > 0.0)
+                    .map(|r| format!(" ★ {:.1}", r))
+                    .unwrap_or_default();
 
-                ListItem::new(line)
+                ListItem::new(format!("{:>2}. {}{}{}", idx + 1, item.title, year, rating))
+                    .style(style)
             })
             .collect();
 
-        let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Results"));
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("{} movies", app.collection_items.len())),
+        );
         frame.render_widget(list, chunks[1]);
     }
 
-    // Help
-    let help = Paragraph::new("r: run checks | q/Esc: back to search")
-        .style(Style::default().fg(Color::DarkGray));
-    frame.render_widget(help, chunks[2]);
+    draw_help(
+        frame,
+        app,
+        chunks[2],
+        "Enter: search torrents | ↑/↓: navigate | /: search | q: back",
+    );
 }
 
 fn draw_tv_seasons(frame: &mut Frame, app: &App) {
@@ -932,9 +2671,12 @@ fn draw_tv_seasons(frame: &mut Frame, app: &App) {
     }
 
     // Help
-    let help = Paragraph::new("Enter: view episodes | ↑/↓: navigate | q: back to search")
-        .style(Style::default().fg(Color::DarkGray));
-    frame.render_widget(help, chunks[2]);
+    draw_help(
+        frame,
+        app,
+        chunks[2],
+        "Enter: view episodes | ↑/↓: navigate | q: back to search",
+    );
 }
 
 fn draw_tv_episodes(frame: &mut Frame, app: &App) {
@@ -968,10 +2710,13 @@ fn draw_tv_episodes(frame: &mut Frame, app: &App) {
             Paragraph::new("Loading episodes...").style(Style::default().fg(Color::Yellow));
         frame.render_widget(loading, chunks[1]);
     } else if app.is_searching {
-        let loading =
-            Paragraph::new("Searching for episode...").style(Style::default().fg(Color::Yellow));
+        let loading = Paragraph::new(
+            transformed_query_status(app).unwrap_or_else(|| "Searching for episode...".to_string()),
+        )
+        .style(Style::default().fg(Color::Yellow));
         frame.render_widget(loading, chunks[1]);
     } else {
+        let today = tmdb::today();
         let items: Vec<ListItem> = app
             .tv_episodes
             .iter()
@@ -987,178 +2732,172 @@ fn draw_tv_episodes(frame: &mut Frame, app: &App) {
                 };
 
                 let runtime = ep.runtime.map(|r| format!(" ({}m)", r)).unwrap_or_default();
+                let watched = app.episode_watched_percent(ep);
+                let marker = match watched {
+                    Some(p) if p >= FINISHED_PERCENT => "  ✓ ".to_string(),
+                    Some(p) => format!("{:>3.0}%", p),
+                    None => "    ".to_string(),
+                };
+                // Dim episodes watched to the end
+                let style = if idx != app.selected_episode_index
+                    && watched.is_some_and(|p| p >= FINISHED_PERCENT)
+                {
+                    Style::default().fg(Color::DarkGray)
+                } else {
+                    style
+                };
 
-                let text = format!("{}{}", ep.display_title(), runtime);
-                ListItem::new(text).style(style)
+                let text = format!("{} {}{}", marker, ep.display_title(), runtime);
+                let (air, style) = match ep.air_status(today) {
+                    AirStatus::Aired => (ep.air_date.clone().unwrap_or_default(), style),
+                    AirStatus::Upcoming { days } => {
+                        let when = if days == 1 {
+                            "airs tomorrow".to_string()
+                        } else {
+                            format!("airs in {} days", days)
+                        };
+                        let style = if idx == app.selected_episode_index {
+                            style
+                        } else {
+                            Style::default().fg(Color::DarkGray)
+                        };
+                        (when, style)
+                    }
+                    AirStatus::Unknown => ("air date TBA".to_string(), style),
+                };
+                let line = Line::from(vec![
+                    Span::raw(text),
+                    Span::styled(format!("  {}", air), Style::default().fg(Color::DarkGray)),
+                ]);
+                ListItem::new(line).style(style)
             })
             .collect();
 
+        let list_area = if frame.area().width > EPISODE_STILL_MIN_WIDTH {
+            let panes = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Percentage(40)])
+                .split(chunks[1]);
+            draw_episode_still(frame, app, panes[1]);
+            panes[0]
+        } else {
+            chunks[1]
+        };
+
         let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Episodes"));
-        frame.render_widget(list, chunks[1]);
+        frame.render_widget(list, list_area);
     }
 
     // Help
-    let help = Paragraph::new("Enter: search & stream | ↑/↓: navigate | q: back to seasons")
-        .style(Style::default().fg(Color::DarkGray));
-    frame.render_widget(help, chunks[2]);
+    draw_help(
+        frame,
+        app,
+        chunks[2],
+        "Enter: search & stream | ↑/↓: navigate | w: toggle watched | a: next unaired | q: back to seasons",
+    );
 }
 
-fn draw_settings(frame: &mut Frame, app: &App, config: &Config) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .margin(1)
-        .constraints([
-            Constraint::Length(20), // Section list
-            Constraint::Min(0),     // Section content
-        ])
-        .split(frame.area());
+/// Still of the selected episode, drawn with half blocks
+fn draw_episode_still(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title("Still");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let still = app
+        .selected_tv_episode()
+        .and_then(|ep| app.episode_stills.get(&ep.id));
+    let content = match still {
+        Some(Artwork::Loaded(thumbnail)) => {
+            Paragraph::new(thumbnail.lines(inner.width, inner.height))
+        }
+        Some(Artwork::Missing) => {
+            Paragraph::new("No still available").style(Style::default().fg(Color::DarkGray))
+        }
+        Some(Artwork::Loading) | None => {
+            Paragraph::new("Loading still...").style(Style::default().fg(Color::Yellow))
+        }
+    };
+    frame.render_widget(content.alignment(ratatui::layout::Alignment::Center), inner);
+}
 
-    // Section list (left panel)
-    let section_items: Vec<ListItem> = SettingsSection::ALL
+fn draw_settings(frame: &mut Frame, app: &App, config: &Config) {
+    let selected = SettingsSection::ALL
         .iter()
-        .map(|s| {
-            let style = if *s == app.settings_section {
+        .position(|s| *s == app.settings_section)
+        .unwrap_or(0);
+
+    // Narrow terminals get a tab bar on top instead of a sidebar
+    let content_area = if frame.area().width < SETTINGS_SIDEBAR_MIN_WIDTH {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([
+                Constraint::Length(3), // Section tabs
+                Constraint::Min(0),    // Section content
+            ])
+            .split(frame.area());
+
+        let labels: Vec<&str> = SettingsSection::ALL.iter().map(|s| s.label()).collect();
+        let first = first_visible_tab(&labels, selected, chunks[0].width.saturating_sub(2));
+        let tabs = Tabs::new(labels[first..].to_vec())
+            .select(selected - first)
+            .highlight_style(
                 Style::default()
                     .fg(Color::Black)
                     .bg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD)
-            } else {
-                Style::default()
-            };
-            ListItem::new(s.label()).style(style)
-        })
-        .collect();
+                    .add_modifier(Modifier::BOLD),
+            )
+            .block(Block::default().borders(Borders::ALL).title("Settings"));
+        frame.render_widget(tabs, chunks[0]);
+        chunks[1]
+    } else {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .margin(1)
+            .constraints([
+                Constraint::Length(20), // Section list
+                Constraint::Min(0),     // Section content
+            ])
+            .split(frame.area());
+
+        // Section list (left panel)
+        let section_items: Vec<ListItem> = SettingsSection::ALL
+            .iter()
+            .map(|s| {
+                let style = if *s == app.settings_section {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                ListItem::new(s.label()).style(style)
+            })
+            .collect();
 
-    let section_list =
-        List::new(section_items).block(Block::default().borders(Borders::ALL).title("Settings"));
-    frame.render_widget(section_list, chunks[0]);
+        let section_list = List::new(section_items)
+            .block(Block::default().borders(Borders::ALL).title("Settings"));
+        frame.render_widget(section_list, chunks[0]);
+        chunks[1]
+    };
 
-    // Content panel (right side)
+    // Content panel
     let content_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Min(0),    // Content
             Constraint::Length(2), // Help
         ])
-        .split(chunks[1]);
-
-    // Build fields with selection highlighting
-    let fields: Vec<(&str, String, bool)> = match app.settings_section {
-        SettingsSection::Prowlarr => vec![
-            ("URL", config.prowlarr.url.clone(), false),
-            ("API Key", mask_secret(&config.prowlarr.apikey), true),
-        ],
-        SettingsSection::Tmdb => vec![(
-            "API Key",
-            config
-                .tmdb
-                .as_ref()
-                .map(|t| mask_secret(&t.apikey))
-                .unwrap_or_else(|| "(not set)".to_string()),
-            true,
-        )],
-        SettingsSection::Player => vec![
-            ("Command", config.player.command.clone(), false),
-            (
-                "Args",
-                if config.player.args.is_empty() {
-                    "(none)".to_string()
-                } else {
-                    config.player.args.join(" ")
-                },
-                false,
-            ),
-        ],
-        SettingsSection::Streaming => vec![(
-            "Auto Race",
-            format!("{} (0 = disabled)", config.streaming.auto_race),
-            false,
-        )],
-        SettingsSection::Subtitles => vec![
-            (
-                "Enabled",
-                if config.subtitles.enabled {
-                    "Yes".to_string()
-                } else {
-                    "No".to_string()
-                },
-                false,
-            ),
-            ("Language", config.subtitles.language.clone(), false),
-            (
-                "OpenSubtitles Key",
-                config
-                    .subtitles
-                    .opensubtitles_api_key
-                    .as_ref()
-                    .map(|k| mask_secret(k))
-                    .unwrap_or_else(|| "(not set)".to_string()),
-                true,
-            ),
-        ],
-        SettingsSection::Discord => vec![
-            (
-                "Enabled",
-                if config.extensions.discord.enabled {
-                    "Yes".to_string()
-                } else {
-                    "No".to_string()
-                },
-                false,
-            ),
-            (
-                "App ID",
-                config
-                    .extensions
-                    .discord
-                    .app_id
-                    .clone()
-                    .unwrap_or_else(|| "(using default)".to_string()),
-                false,
-            ),
-        ],
-        SettingsSection::Trakt => vec![
-            (
-                "Enabled",
-                if config.extensions.trakt.enabled {
-                    "Yes".to_string()
-                } else {
-                    "No".to_string()
-                },
-                false,
-            ),
-            (
-                "Client ID",
-                config
-                    .extensions
-                    .trakt
-                    .client_id
-                    .as_ref()
-                    .map(|k| mask_secret(k))
-                    .unwrap_or_else(|| "(not set)".to_string()),
-                true,
-            ),
-            (
-                "Access Token",
-                config
-                    .extensions
-                    .trakt
-                    .access_token
-                    .as_ref()
-                    .map(|k| mask_secret(k))
-                    .unwrap_or_else(|| "(not set)".to_string()),
-                true,
-            ),
-        ],
-    };
+        .split(content_area);
 
     // Build lines with selection highlighting
-    let lines: Vec<Line> = fields
+    let mut lines: Vec<Line> = settings::fields(app.settings_section)
         .iter()
         .enumerate()
-        .map(|(idx, (label, value, _is_secret))| {
+        .map(|(idx, field)| {
             let is_selected = idx == app.settings_field_index;
-            let is_bool = *label == "Enabled";
+            let value = field.display(config);
 
             // In edit mode, show the edit buffer for the selected field
             let display_value = if is_selected && app.settings_editing {
@@ -1174,7 +2913,7 @@ fn draw_settings(frame: &mut Frame, app: &App, config: &Config) {
                 } else {
                     Style::default().fg(Color::Cyan)
                 }
-            } else if is_bool {
+            } else if field.kind == FieldKind::Bool {
                 if value == "Yes" {
                     Style::default().fg(Color::Green)
                 } else {
@@ -1186,14 +2925,66 @@ fn draw_settings(frame: &mut Frame, app: &App, config: &Config) {
 
             let prefix = if is_selected { "▸ " } else { "  " };
 
-            Line::from(vec![
+            let mut spans = vec![
                 Span::raw(prefix),
-                Span::styled(format!("{}: ", label), label_style),
+                Span::styled(format!("{}: ", field.label), label_style),
                 Span::styled(display_value, value_style),
-            ])
+            ];
+            if field.lock_reason(config).is_some() {
+                spans.push(Span::styled(
+                    "  (locked in config file)",
+                    Style::default().fg(Color::DarkGray),
+                ));
+            } else if let Some(warning) = field.warning(config) {
+                spans.push(Span::styled(
+                    format!("  ({})", warning),
+                    Style::default().fg(Color::Yellow),
+                ));
+            } else if !field.hint.is_empty() {
+                spans.push(Span::styled(
+                    format!("  ({})", field.hint),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            Line::from(spans)
         })
         .collect();
 
+    if app.settings_section == SettingsSection::Storage {
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::styled(
+                "Cache Size: ",
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            cache_size_span(app, config),
+        ]));
+        lines.push(Line::styled(
+            format!("  in {}", config.storage.temp_dir().display()),
+            Style::default().fg(Color::DarkGray),
+        ));
+        lines.push(free_space_line(app));
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![
+            Span::raw("  ["),
+            Span::styled("C", Style::default().fg(Color::Cyan)),
+            Span::raw("]lear cache"),
+            Span::styled(
+                "  (removes downloads no running stream uses)",
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]));
+    }
+
+    if let Some(error) = &app.settings_error {
+        lines.push(Line::from(""));
+        lines.push(Line::styled(
+            format!("  {}", error),
+            Style::default().fg(Color::Red),
+        ));
+    }
+
     let title = if app.settings_dirty {
         format!("{} [modified]", app.settings_section.label())
     } else {
@@ -1207,20 +2998,204 @@ fn draw_settings(frame: &mut Frame, app: &App, config: &Config) {
     // Help text
     let help_text = if app.settings_editing {
         "Enter: save | Esc: cancel"
+    } else if app.show_command_picker {
+        "↑/↓: players | Enter: use | Esc: cancel"
+    } else if app.settings_section == SettingsSection::Storage {
+        "←/→: sections | ↑/↓: fields | Enter: edit | C: clear cache | w: re-run wizard | s: save | q: back"
+    } else if WizardStep::for_section(app.settings_section).is_some() {
+        "←/→: sections | ↑/↓: fields | Enter: edit/toggle | w: re-run wizard | s: save | q: back"
+    } else {
+        "←/→: sections | ↑/↓: fields | Enter: edit/toggle | s: save | q: back"
+    };
+    draw_help(frame, app, content_chunks[1], help_text);
+
+    if app.show_command_picker {
+        draw_command_picker(frame, app, config);
+    }
+    if let Some(diffs) = &app.settings_diff {
+        draw_settings_diff(frame, diffs);
+    }
+}
+
+/// Installed players to set as the player command, then a custom one
+fn draw_command_picker(frame: &mut Frame, app: &App, config: &Config) {
+    let players = app.detected_players();
+    let area = frame.area();
+    let popup_width = 70.min(area.width.saturating_sub(4));
+    let popup_height = (players.len() as u16 + 3).min(area.height);
+    let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+    let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+    frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+    let max_len = popup_width.saturating_sub(4) as usize;
+    let options = players
+        .iter()
+        .map(|player| format!("{}  {}", player.name, detected_player_info(player, config)))
+        .chain(std::iter::once("Custom command...".to_string()));
+    let lines: Vec<Line> = options
+        .enumerate()
+        .map(|(i, option)| {
+            let (option, _) = option.unicode_truncate(max_len);
+            if i == app.selected_command_index {
+                Line::from(Span::styled(
+                    format!("> {}", option),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ))
+            } else {
+                Line::from(format!("  {}", option))
+            }
+        })
+        .collect();
+
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan))
+            .title("Player"),
+    );
+    frame.render_widget(popup, popup_area);
+}
+
+/// First tab to draw so the selected one fits in `width` columns. Each tab
+/// takes its label plus a space either side and a divider.
+fn first_visible_tab(labels: &[&str], selected: usize, width: u16) -> usize {
+    let tab_width = |label: &&str| label.chars().count() + 3;
+    let mut first = 0;
+    while first < selected
+        && labels[first..=selected]
+            .iter()
+            .map(tab_width)
+            .sum::<usize>()
+            > width as usize
+    {
+        first += 1;
+    }
+    first
+}
+
+/// Temp dir usage, red when over `max_cache_gb`
+fn cache_size_span(app: &App, config: &Config) -> Span<'static> {
+    if app.is_clearing_cache {
+        return Span::styled("clearing…", Style::default().fg(Color::Yellow));
+    }
+    let Some(size) = app.cache_size else {
+        return Span::styled("measuring…", Style::default().fg(Color::DarkGray));
+    };
+    let style = if config.storage.is_over_cache_limit(size) {
+        Style::default().fg(Color::Red)
     } else {
-        "←/→: sections | ↑/↓: fields | Enter: edit | Space: toggle | s: save | q: back"
+        Style::default()
     };
-    let help = Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray));
-    frame.render_widget(help, content_chunks[1]);
+    Span::styled(
+        format!(
+            "{} of {} GB",
+            format_bytes(size),
+            config.storage.max_cache_gb
+        ),
+        style,
+    )
 }
 
-/// Mask a secret string, showing only first/last 2 chars
-fn mask_secret(s: &str) -> String {
-    if s.len() <= 6 {
-        "*".repeat(s.len())
+/// Space left beside the temp dir, with a warning when it's low
+fn free_space_line(app: &App) -> Line<'static> {
+    let label = Span::styled(
+        "  Free Space: ",
+        Style::default().add_modifier(Modifier::BOLD),
+    );
+    // Measured just before the cache size
+    let free = match (app.free_space, app.cache_size) {
+        (Some(free), _) => free,
+        (None, None) => {
+            return Line::from(vec![
+                label,
+                Span::styled("measuring…", Style::default().fg(Color::DarkGray)),
+            ]);
+        }
+        (None, Some(_)) => {
+            return Line::from(vec![
+                label,
+                Span::styled("unknown", Style::default().fg(Color::DarkGray)),
+            ]);
+        }
+    };
+    if free < crate::streaming::LOW_FREE_SPACE {
+        Line::from(vec![
+            label,
+            Span::styled(format_bytes(free), Style::default().fg(Color::Red)),
+            Span::styled(
+                "  (low - a stream may not fit)",
+                Style::default().fg(Color::Yellow),
+            ),
+        ])
     } else {
-        format!("{}...{}", &s[..2], &s[s.len() - 2..])
+        Line::from(vec![label, Span::raw(format_bytes(free))])
+    }
+}
+
+/// Unsaved changes, asked about when leaving settings
+fn draw_settings_diff(frame: &mut Frame, diffs: &[ConfigDiff]) {
+    const MAX_LISTED: usize = 10;
+
+    let area = frame.area();
+    let popup_width = 70.min(area.width.saturating_sub(4));
+    let listed = diffs.len().min(MAX_LISTED);
+    let more = diffs.len() > MAX_LISTED;
+    let popup_height = ((listed + usize::from(more)) as u16 + 6).min(area.height);
+    let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+    let popup_area = ratatui::layout::Rect::new(popup_x, popup_y, popup_width, popup_height);
+    frame.render_widget(ratatui::widgets::Clear, popup_area);
+
+    let max_len = popup_width.saturating_sub(4) as usize;
+    let mut text = vec![Line::from("")];
+    for diff in &diffs[..listed] {
+        // Keep "(not set)" readable, only real values are masked
+        let show = |value: &str| {
+            if diff.is_secret() && value != NOT_SET {
+                mask_secret(value)
+            } else {
+                value.to_string()
+            }
+        };
+        let line = format!(
+            "{}: {} -> {}",
+            diff.path(),
+            show(&diff.old_value),
+            show(&diff.new_value)
+        );
+        let (line, _) = line.unicode_truncate(max_len);
+        text.push(Line::from(line.to_string()));
     }
+    if more {
+        text.push(Line::styled(
+            format!("...and {} more", diffs.len() - MAX_LISTED),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    text.push(Line::from(""));
+    text.push(Line::from(vec![
+        Span::styled("S", Style::default().fg(Color::Cyan)),
+        Span::raw(" - Save  |  "),
+        Span::styled("D", Style::default().fg(Color::Cyan)),
+        Span::raw(" - Discard  |  "),
+        Span::styled("C", Style::default().fg(Color::Cyan)),
+        Span::raw(" - Cancel"),
+    ]));
+
+    let popup = Paragraph::new(text)
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title("Unsaved changes"),
+        );
+    frame.render_widget(popup, popup_area);
 }
 
 fn format_bytes(bytes: u64) -> String {
@@ -1238,3 +3213,571 @@ fn format_bytes(bytes: u64) -> String {
         format!("{} B", bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::torznab::TorrentResult;
+    use crate::tui::app::SortOrder;
+    use ratatui::{Terminal, backend::TestBackend};
+    use std::path::PathBuf;
+
+    /// Draw `app` into a `width` x `height` buffer, one string per row
+    fn render(app: &App, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        let config = Config::default();
+        terminal.draw(|f| draw(f, app, Some(&config))).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    fn row_of(rows: &[String], needle: &str) -> Option<usize> {
+        rows.iter().position(|row| row.contains(needle))
+    }
+
+    #[test]
+    fn test_streaming_layout_by_height() {
+        let mut app = App::new();
+        app.view = View::Streaming;
+        app.current_title = "Severance S02E01".to_string();
+        app.streaming_state = StreamingState::Playing {
+            stream_url: "http://127.0.0.1:3030/torrents/0/stream/1".to_string(),
+        };
+        app.playback_progress = 42.0;
+
+        // Tall enough for the boxes
+        let rows = render(&app, 100, 30);
+        assert_eq!(row_of(&rows, "Now Streaming"), Some(2));
+        assert!(row_of(&rows, "Download Speed").is_some());
+        assert_eq!(row_of(&rows, "Severance S02E01"), Some(3));
+
+        // Short: one line each, status and progress together
+        let rows = render(&app, 100, 12);
+        assert_eq!(row_of(&rows, "Now Streaming"), None);
+        assert_eq!(row_of(&rows, "Severance S02E01"), Some(0));
+        assert_eq!(row_of(&rows, "Playing  42.0% watched"), Some(1));
+        assert_eq!(row_of(&rows, "q: stop & return"), Some(11));
+
+        // Tiny terminals still draw without panicking
+        render(&app, 20, 3);
+    }
+
+    #[test]
+    fn test_stream_url_overlay() {
+        let mut app = App::new();
+        app.view = View::Streaming;
+        app.streaming_state = StreamingState::Playing {
+            stream_url: "http://127.0.0.1:3030/torrents/0/stream/1".to_string(),
+        };
+        app.shared_stream_url =
+            Some("http://192.168.1.20:41234/s3cret/torrents/0/stream/1".to_string());
+
+        let rows = render(&app, 100, 30);
+        let url = row_of(
+            &rows,
+            "http://192.168.1.20:41234/s3cret/torrents/0/stream/1",
+        );
+        assert!(url.is_some_and(|row| row > 0));
+        assert!(row_of(&rows, "any key: close").is_some());
+    }
+
+    #[test]
+    fn test_piece_strip() {
+        let mut app = App::new();
+        assert_eq!(piece_strip(&app, 8).to_string(), "");
+
+        // The start and a stretch in the middle are downloaded
+        let mut pieces = vec![false; 16];
+        pieces[..4].fill(true);
+        pieces[9..12].fill(true);
+        app.piece_map = Some(streaming::PieceMap { pieces });
+        assert_eq!(piece_strip(&app, 8).to_string(), "██░░▒█░░");
+
+        app.playback_progress = 30.0;
+        assert_eq!(piece_strip(&app, 8).to_string(), "██┃░▒█░░");
+        app.playback_progress = 100.0;
+        assert_eq!(piece_strip(&app, 8).to_string(), "██░░▒█░┃");
+        assert_eq!(piece_strip(&app, 0).to_string(), "");
+    }
+
+    #[test]
+    fn test_discovery_4k_badge() {
+        use super::super::app::{DiscoveryItem, DiscoveryRow};
+
+        let item = |id, title: &str, has_4k| DiscoveryItem {
+            id,
+            title: title.to_string(),
+            year: Some(2024),
+            media_type: "movie".to_string(),
+            poster_url: None,
+            backdrop_url: None,
+            overview: None,
+            rating: None,
+            has_4k,
+        };
+        let mut app = App::new();
+        app.view = View::Discovery;
+        app.set_discovery_rows(vec![DiscoveryRow {
+            title: "4K & HDR".to_string(),
+            items: vec![
+                item(693134, "Dune: Part Two", true),
+                item(1, "Home Video", false),
+            ],
+        }]);
+
+        let rows = render(&app, 100, 20);
+        assert!(row_of(&rows, "4K Dune: Part Two (2024)").is_some());
+        assert!(row_of(&rows, "Home Video (2024)").is_some());
+        assert!(row_of(&rows, "4K Home Video").is_none());
+    }
+
+    #[test]
+    fn test_discovery_backdrop_needs_wide_terminal() {
+        use super::super::app::{BackdropFade, DiscoveryItem, DiscoveryRow};
+        use super::super::thumbnail::Thumbnail;
+
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(32, 18, image::Rgb([200, 120, 40]))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let mut app = App::new();
+        app.view = View::Discovery;
+        app.set_discovery_rows(vec![DiscoveryRow {
+            title: "Trending".to_string(),
+            items: vec![DiscoveryItem {
+                id: 693134,
+                title: "Dune: Part Two".to_string(),
+                year: Some(2024),
+                media_type: "movie".to_string(),
+                poster_url: None,
+                backdrop_url: Some("https://image.tmdb.org/t/p/w780/dune.jpg".to_string()),
+                overview: None,
+                rating: None,
+                has_4k: false,
+            }],
+        }]);
+        app.backdrop = Some(BackdropFade {
+            url: "https://image.tmdb.org/t/p/w780/dune.jpg".to_string(),
+            image: Thumbnail::decode(png.get_ref()).unwrap(),
+            previous: None,
+            started: std::time::Instant::now(),
+        });
+
+        // Behind the rows, which stay readable
+        let rows = render(&app, 220, 30);
+        assert!(row_of(&rows, "Dune: Part Two (2024)").is_some());
+        assert!(rows.iter().any(|row| row.contains("▀▀▀")));
+
+        let rows = render(&app, 120, 30);
+        assert!(rows.iter().all(|row| !row.contains('▀')));
+    }
+
+    #[test]
+    fn test_recovery_prompt_on_discovery() {
+        let mut app = App::new();
+        app.view = View::Discovery;
+        app.recovery_prompt = Some(SessionSnapshot {
+            source_url: "magnet:?xt=urn:btih:abc".to_string(),
+            file_name: "Severance.S02E03.1080p.mkv".to_string(),
+            file_idx: 2,
+            progress_percent: 42.4,
+            title: "Severance".to_string(),
+            tmdb_id: Some(95396),
+            year: Some(2022),
+            media_type: Some("tv".to_string()),
+            saved_at: 0,
+        });
+
+        let rows = render(&app, 100, 30);
+        assert!(row_of(&rows, "Resume Severance at 42%?").is_some());
+        assert!(row_of(&rows, "Severance.S02E03.1080p.mkv").is_some());
+
+        // Only offered on Discovery
+        app.view = View::Search;
+        let rows = render(&app, 100, 30);
+        assert!(row_of(&rows, "Resume Severance").is_none());
+    }
+
+    #[test]
+    fn test_search_status_shows_transformed_query() {
+        let mut app = App::new();
+        app.view = View::Search;
+        app.search_input = "Office".to_string();
+        app.is_searching = true;
+
+        let rows = render(&app, 100, 30);
+        assert!(row_of(&rows, "Searching...").is_some());
+
+        app.transformed_query = Some(("The Office US 2005".to_string(), "Office".to_string()));
+        let rows = render(&app, 100, 30);
+        assert!(row_of(&rows, "Searching: The Office US 2005 (from: Office)").is_some());
+    }
+
+    #[test]
+    fn test_grouped_results() {
+        let torrent = |title: &str, seeders: u32| TorrentResult {
+            title: title.to_string(),
+            link: None,
+            magnet_url: Some(format!("magnet:?xt=urn:btih:{}", title)),
+            infohash: None,
+            size: None,
+            seeders: Some(seeders),
+            leechers: None,
+            indexer: "test".to_string(),
+            categories: Vec::new(),
+            usenet: false,
+        };
+        let mut app = App::new();
+        app.view = View::Results;
+        app.sort_order = SortOrder::SeedersDesc;
+        app.set_results(
+            vec![
+                (torrent("Heat.1995.720p.BluRay", 10), 50),
+                (torrent("Heat.1995.1080p.BluRay", 80), 50),
+                (torrent("Heat.1995.1080p.WEB-DL", 40), 50),
+            ],
+            Vec::new(),
+        );
+        app.toggle_release_grouping();
+
+        let rows = render(&app, 120, 30);
+        assert!(row_of(&rows, "▸ Heat.1995.1080p.BluRay [+1]").is_some());
+        assert!(row_of(&rows, "Heat.1995.720p.BluRay").is_none());
+        assert!(row_of(&rows, "(2 releases)").is_some());
+
+        app.toggle_selected_group();
+        let rows = render(&app, 120, 30);
+        let header = row_of(&rows, "▾ Heat.1995.1080p.BluRay").unwrap();
+        assert_eq!(row_of(&rows, "    Heat.1995.720p.BluRay"), Some(header + 2));
+    }
+
+    #[test]
+    fn test_compact_mode_help() {
+        let mut app = App::new();
+        app.view = View::Doctor;
+        let rows = render(&app, 120, 30);
+        assert!(row_of(&rows, "r: run checks | i: indexers").is_some());
+
+        app.compact_mode = true;
+        let rows = render(&app, 70, 30);
+        assert!(row_of(&rows, "[?]: help").is_some());
+        assert!(row_of(&rows, "r: run checks").is_none());
+
+        app.show_help = true;
+        let rows = render(&app, 70, 30);
+        let first = row_of(&rows, " r: run checks ").unwrap();
+        assert_eq!(row_of(&rows, " i: indexers "), Some(first + 1));
+
+        // Keys typed into a field are text, the full help stays
+        app.show_help = false;
+        app.view = View::Search;
+        let rows = render(&app, 70, 30);
+        assert!(row_of(&rows, "[?]: help").is_none());
+    }
+
+    #[test]
+    fn test_results_size_column_by_width() {
+        let mut app = App::new();
+        app.view = View::Results;
+        app.set_results(
+            vec![(
+                TorrentResult {
+                    title: "Heat.1995.1080p.BluRay".to_string(),
+                    link: None,
+                    magnet_url: Some("magnet:?xt=urn:btih:heat".to_string()),
+                    infohash: None,
+                    size: Some(8_000_000_000),
+                    seeders: Some(80),
+                    leechers: None,
+                    indexer: "test".to_string(),
+                    categories: Vec::new(),
+                    usenet: false,
+                },
+                50,
+            )],
+            Vec::new(),
+        );
+        let rows = render(&app, 120, 30);
+        let row = row_of(&rows, "Heat.1995.1080p.BluRay").unwrap();
+        assert!(rows[row].contains("7.45 GB | Heat"), "{}", rows[row]);
+
+        let rows = render(&app, 70, 30);
+        let row = row_of(&rows, "Heat.1995.1080p.BluRay").unwrap();
+        assert!(rows[row].contains("S:80   | Heat"), "{}", rows[row]);
+    }
+
+    #[test]
+    fn test_shuffle_badge() {
+        let mut app = App::new();
+        app.view = View::Streaming;
+        app.is_streaming = true;
+        app.available_files = ["S01E01.mkv", "S01E02.mkv"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| crate::streaming::VideoFile {
+                name: name.to_string(),
+                file_idx: i,
+                size: 0,
+                stream_url: String::new(),
+            })
+            .collect();
+        app.current_file = "S01E01.mkv".to_string();
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "[Shuffle]").is_none());
+
+        app.toggle_shuffle();
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "S01E01.mkv [1/2] [Shuffle]").is_some());
+
+        app.view = View::FileSelection;
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "Files [2] [Shuffle]").is_some());
+        assert!(row_of(&rows, "S: shuffle off | L: loop").is_some());
+
+        app.loop_shuffle = true;
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "Files [2] [Shuffle, loop]").is_some());
+    }
+
+    #[test]
+    fn test_subtitle_source_in_file_block() {
+        use crate::tui::app::SubtitleStatus;
+        use crate::tui::playback::{Subtitle, SubtitleSource};
+
+        let mut app = App::new();
+        app.view = View::Streaming;
+        app.is_streaming = true;
+        app.current_file = "Severance.S02E03.1080p.mkv".to_string();
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "Subs:").is_none());
+
+        let subtitle = Subtitle {
+            url: "http://localhost/sub.srt".to_string(),
+            source: SubtitleSource::OpenSubtitles,
+            language: Some("en".to_string()),
+        };
+        app.subtitle_status = SubtitleStatus::Attached(subtitle.clone());
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "Subs: OpenSubtitles (en)").is_some());
+
+        app.subtitle_status = SubtitleStatus::Hidden {
+            subtitle,
+            audio: "eng".to_string(),
+        };
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "Subs: OpenSubtitles (en), off since audio is eng").is_some());
+
+        app.subtitle_status = SubtitleStatus::None;
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "Subs: none").is_some());
+    }
+
+    #[test]
+    fn test_probe_prompt() {
+        use crate::streaming::Probe;
+        use crate::tui::app::ProbePrompt;
+
+        let mut app = App::new();
+        app.view = View::Streaming;
+        app.is_streaming = true;
+        let stream_id = app.next_stream_id();
+        let (reply, _answer) = tokio::sync::oneshot::channel();
+        app.probe_prompt = Some(ProbePrompt {
+            stream_id,
+            probe: Probe {
+                peers: 22,
+                metadata_pending: false,
+            },
+            reply,
+        });
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "Found 22 peers").is_some());
+        assert!(row_of(&rows, "Enter: stream | q: back").is_some());
+
+        // Out of time before a single peer turned up
+        if let Some(prompt) = &mut app.probe_prompt {
+            prompt.probe = Probe {
+                peers: 0,
+                metadata_pending: true,
+            };
+        }
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "No peers yet, metadata pending").is_some());
+
+        // Left over from a stream the user moved on from
+        app.next_stream_id();
+        let rows = render(&app, 120, 40);
+        assert!(row_of(&rows, "No peers yet").is_none());
+    }
+
+    #[test]
+    fn test_settings_layout_by_width() {
+        let mut app = App::new();
+        app.view = View::Settings;
+        app.settings_section = SettingsSection::Simkl;
+
+        // Wide: sections down the left, every one listed
+        let rows = render(&app, 120, 30);
+        assert!(rows[2].starts_with(" │Prowlarr"));
+        assert!(rows.iter().any(|row| row.starts_with(" │Simkl")));
+
+        // Narrow: a tab bar scrolled so the selected section shows
+        let rows = render(&app, 60, 30);
+        assert_eq!(row_of(&rows, "Simkl"), Some(2));
+        assert!(!rows[2].contains("Prowlarr"));
+        assert!(row_of(&rows, "Client ID").is_some_and(|row| row > 3));
+    }
+
+    #[test]
+    fn test_settings_diff_in_short_terminal() {
+        let mut app = App::new();
+        app.view = View::Settings;
+        app.settings_diff = Some(
+            (0..12)
+                .map(|i| crate::config::ConfigDiff {
+                    section: "rss_rules".to_string(),
+                    field: format!("[{}].title_pattern", i),
+                    old_value: "Severance".to_string(),
+                    new_value: "Silo".to_string(),
+                })
+                .collect(),
+        );
+
+        let rows = render(&app, 100, 40);
+        assert!(row_of(&rows, "...and 2 more").is_some());
+
+        // The popup is cut to the terminal instead of drawn past it
+        let rows = render(&app, 100, 10);
+        assert!(row_of(&rows, "rss_rules[0].title_pattern").is_some());
+    }
+
+    #[test]
+    fn test_wizard_scrolls_to_focused_field() {
+        let mut app = App::new();
+        app.view = View::Wizard;
+        app.wizard_step = WizardStep::Integrations;
+        app.wizard_field_index = 1;
+
+        let rows = render(&app, 80, 40);
+        assert!(row_of(&rows, "Simkl marks what you watch").is_some());
+        assert!(row_of(&rows, "Simkl Access Token").is_some());
+
+        // Too short for the whole step, the focused field stays in view
+        let rows = render(&app, 80, 16);
+        assert!(row_of(&rows, "Simkl marks what you watch").is_none());
+        assert!(row_of(&rows, "▸ Simkl Access Token").is_some());
+    }
+
+    #[test]
+    fn test_wizard_rerun_from_settings() {
+        let mut app = App::new();
+        app.view = View::Settings;
+        app.settings_section = SettingsSection::Trakt;
+        let rows = render(&app, 140, 30);
+        assert!(row_of(&rows, "w: re-run wizard").is_some());
+        app.settings_section = SettingsSection::Streaming;
+        let rows = render(&app, 140, 30);
+        assert!(row_of(&rows, "w: re-run wizard").is_none());
+
+        app.view = View::Wizard;
+        app.wizard_step = WizardStep::Done;
+        app.wizard_return_to = Some(View::Settings);
+        let rows = render(&app, 80, 30);
+        assert!(row_of(&rows, "Enter: save & back to settings").is_some());
+    }
+
+    #[test]
+    fn test_detected_players() {
+        let mut app = App::new();
+        let config = Config::default();
+        app.view = View::Wizard;
+        app.wizard_step = WizardStep::Player;
+        app.is_detecting_players = true;
+        let rows = render(&app, 100, 30);
+        assert!(row_of(&rows, "Looking for installed players").is_some());
+        assert!(row_of(&rows, "Custom: mpv").is_some());
+
+        app.is_detecting_players = false;
+        app.detected_players = Some(vec![
+            DetectedPlayer {
+                name: "mpv",
+                command: "mpv".to_string(),
+                path: PathBuf::from("/usr/bin/mpv"),
+                version: Some("mpv 0.38.0".to_string()),
+                default_args: &[],
+            },
+            DetectedPlayer {
+                name: "vlc",
+                command: "vlc".to_string(),
+                path: PathBuf::from("/usr/bin/vlc"),
+                version: None,
+                default_args: &["--play-and-exit"],
+            },
+        ]);
+        assert_eq!(app.wizard_field_count(), 3);
+        assert_eq!(app.player_option_index(&config.player.command), 0);
+        assert_eq!(app.player_option_index("/opt/celluloid"), 2);
+        let rows = render(&app, 100, 30);
+        assert!(row_of(&rows, "mpv: mpv 0.38.0  /usr/bin/mpv  (in use)").is_some());
+        assert!(row_of(&rows, "vlc: /usr/bin/vlc").is_some());
+        assert!(row_of(&rows, "Custom: (type a command)").is_some());
+        assert!(row_of(&rows, "Enter: use").is_some());
+
+        // The same list in the Player settings
+        app.view = View::Settings;
+        app.settings_section = SettingsSection::Player;
+        app.show_command_picker = true;
+        app.selected_command_index = 2;
+        let rows = render(&app, 100, 30);
+        assert!(row_of(&rows, "vlc  /usr/bin/vlc").is_some());
+        assert!(row_of(&rows, "> Custom command...").is_some());
+    }
+
+    #[test]
+    fn test_storage_free_space() {
+        let mut app = App::new();
+        app.view = View::Wizard;
+        app.wizard_step = WizardStep::Storage;
+        assert_eq!(WizardStep::Player.next(), WizardStep::Storage);
+        assert_eq!(WizardStep::Storage.prev(), WizardStep::Player);
+        assert_eq!(app.wizard_field_count(), 1);
+
+        let rows = render(&app, 100, 30);
+        assert!(row_of(&rows, "Step 5 of 7 - Storage").is_some());
+        assert!(row_of(&rows, "Free Space: measuring…").is_some());
+
+        app.cache_size = Some(0);
+        app.free_space = Some(2 * 1024 * 1024 * 1024);
+        let rows = render(&app, 100, 30);
+        assert!(row_of(&rows, "Free Space: 2.00 GB").is_some());
+        assert!(row_of(&rows, "low - a stream may not fit").is_some());
+
+        // Same readout in Settings
+        app.view = View::Settings;
+        app.settings_section = SettingsSection::Storage;
+        app.free_space = Some(500 * 1024 * 1024 * 1024);
+        let rows = render(&app, 140, 30);
+        assert!(row_of(&rows, "Free Space: 500.00 GB").is_some());
+        assert!(row_of(&rows, "low - a stream may not fit").is_none());
+        assert!(row_of(&rows, "w: re-run wizard").is_some());
+    }
+
+    #[test]
+    fn test_first_visible_tab() {
+        let labels = ["One", "Two", "Three"];
+        assert_eq!(first_visible_tab(&labels, 0, 10), 0);
+        // All three need 6 + 6 + 8 columns
+        assert_eq!(first_visible_tab(&labels, 2, 20), 0);
+        assert_eq!(first_visible_tab(&labels, 2, 19), 1);
+        assert_eq!(first_visible_tab(&labels, 2, 1), 2);
+        assert_eq!(scroll_to_show(3, 10), 0);
+        assert_eq!(scroll_to_show(12, 10), 3);
+        assert_eq!(scroll_to_show(5, 0), 5);
+    }
+}
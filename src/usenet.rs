@@ -0,0 +1,571 @@
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use crate::config::{UsenetBackend, UsenetConfig};
+use crate::streaming::is_video_file;
+
+#[derive(Error, Debug)]
+pub enum UsenetError {
+    #[error("request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("download failed: {0}")]
+    DownloadFailed(String),
+    #[error("no video files in {}", .0.display())]
+    NoVideoFiles(PathBuf),
+    #[error("cancelled")]
+    Cancelled,
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// State of a submitted NZB
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStatus {
+    /// Queued or downloading, with how much is done
+    Downloading {
+        percent: f64,
+    },
+    /// Downloaded and unpacked into this directory
+    Completed(PathBuf),
+    Failed(String),
+}
+
+/// Submits NZBs to NZBGet or SABnzbd and follows them until they're downloaded
+pub struct UsenetClient {
+    client: Client,
+    backend: UsenetBackend,
+    base_url: String,
+    apikey: String,
+}
+
+impl UsenetClient {
+    pub fn new(config: &UsenetConfig) -> Self {
+        Self {
+            client: crate::http::client(),
+            backend: config.backend,
+            base_url: config.url.trim_end_matches('/').to_string(),
+            apikey: config.apikey.clone(),
+        }
+    }
+
+    /// Hand an NZB URL to the downloader, returning its job id
+    pub async fn submit(&self, nzb_url: &str, name: &str) -> Result<String, UsenetError> {
+        info!(backend = ?self.backend, name, "submitting nzb");
+        match self.backend {
+            UsenetBackend::Nzbget => {
+                // append(NZBFilename, Content, Category, Priority, AddToTop, AddPaused,
+                //        DupeKey, DupeScore, DupeMode, PPParameters)
+                let params = json!([
+                    format!("{}.nzb", name),
+                    nzb_url,
+                    "",
+                    0,
+                    true,
+                    false,
+                    "",
+                    0,
+                    "FORCE",
+                    []
+                ]);
+                let id: i64 = self.nzbget_call("append", params).await?;
+                if id <= 0 {
+                    return Err(UsenetError::InvalidResponse(
+                        "NZBGet refused the nzb".to_string(),
+                    ));
+                }
+                Ok(id.to_string())
+            }
+            UsenetBackend::Sabnzbd => {
+                #[derive(Deserialize)]
+                struct AddResponse {
+                    status: bool,
+                    #[serde(default)]
+                    nzo_ids: Vec<String>,
+                    error: Option<String>,
+                }
+
+                let response: AddResponse = self
+                    .sabnzbd_call(&[("mode", "addurl"), ("name", nzb_url), ("nzbname", name)])
+                    .await?;
+                match response.nzo_ids.into_iter().next() {
+                    Some(id) if response.status => Ok(id),
+                    _ => Err(UsenetError::InvalidResponse(
+                        response
+                            .error
+                            .unwrap_or_else(|| "SABnzbd refused the nzb".to_string()),
+                    )),
+                }
+            }
+        }
+    }
+
+    /// Where a submitted job is at
+    pub async fn status(&self, job_id: &str) -> Result<JobStatus, UsenetError> {
+        match self.backend {
+            UsenetBackend::Nzbget => self.nzbget_status(job_id).await,
+            UsenetBackend::Sabnzbd => self.sabnzbd_status(job_id).await,
+        }
+    }
+
+    /// Poll a job until it's downloaded, returning the largest video file in it.
+    /// `on_progress` gets the percentage downloaded at every poll.
+    pub async fn wait_for_completion(
+        &self,
+        job_id: &str,
+        poll_interval: Duration,
+        cancel: &CancellationToken,
+        on_progress: impl Fn(f64),
+    ) -> Result<PathBuf, UsenetError> {
+        loop {
+            match self.status(job_id).await? {
+                JobStatus::Completed(dir) => return largest_video_file(&dir),
+                JobStatus::Failed(reason) => return Err(UsenetError::DownloadFailed(reason)),
+                JobStatus::Downloading { percent } => {
+                    debug!(job_id, percent, "usenet download in progress");
+                    on_progress(percent);
+                }
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => return Err(UsenetError::Cancelled),
+                _ = tokio::time::sleep(poll_interval) => {}
+            }
+        }
+    }
+
+    async fn nzbget_status(&self, job_id: &str) -> Result<JobStatus, UsenetError> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct Group {
+            #[serde(rename = "NZBID")]
+            nzb_id: i64,
+            #[serde(default, rename = "FileSizeMB")]
+            file_size_mb: f64,
+            #[serde(default, rename = "RemainingSizeMB")]
+            remaining_size_mb: f64,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct HistoryItem {
+            #[serde(rename = "NZBID")]
+            nzb_id: i64,
+            status: String,
+            #[serde(default)]
+            dest_dir: String,
+        }
+
+        let id: i64 = job_id
+            .parse()
+            .map_err(|_| UsenetError::InvalidResponse(format!("bad NZBGet id {}", job_id)))?;
+
+        let groups: Vec<Group> = self.nzbget_call("listgroups", json!([0])).await?;
+        if let Some(group) = groups.iter().find(|g| g.nzb_id == id) {
+            let done = group.file_size_mb - group.remaining_size_mb;
+            let percent = if group.file_size_mb > 0.0 {
+                done / group.file_size_mb * 100.0
+            } else {
+                0.0
+            };
+            return Ok(JobStatus::Downloading { percent });
+        }
+
+        let history: Vec<HistoryItem> = self.nzbget_call("history", json!([false])).await?;
+        match history.into_iter().find(|h| h.nzb_id == id) {
+            // e.g. "SUCCESS/ALL", "SUCCESS/UNPACK"
+            Some(item) if item.status.starts_with("SUCCESS") => {
+                Ok(JobStatus::Completed(PathBuf::from(item.dest_dir)))
+            }
+            Some(item) => Ok(JobStatus::Failed(item.status)),
+            None => Err(UsenetError::InvalidResponse(format!(
+                "NZBGet has no job {}",
+                job_id
+            ))),
+        }
+    }
+
+    async fn sabnzbd_status(&self, job_id: &str) -> Result<JobStatus, UsenetError> {
+        #[derive(Deserialize)]
+        struct Queue {
+            queue: Slots<QueueSlot>,
+        }
+
+        #[derive(Deserialize)]
+        struct History {
+            history: Slots<HistorySlot>,
+        }
+
+        #[derive(Deserialize)]
+        struct Slots<T> {
+            #[serde(default = "Vec::new")]
+            slots: Vec<T>,
+        }
+
+        #[derive(Deserialize)]
+        struct QueueSlot {
+            nzo_id: String,
+            #[serde(default)]
+            percentage: String,
+        }
+
+        #[derive(Deserialize)]
+        struct HistorySlot {
+            nzo_id: String,
+            status: String,
+            #[serde(default)]
+            storage: Option<String>,
+            #[serde(default)]
+            fail_message: String,
+        }
+
+        let queue: Queue = self.sabnzbd_call(&[("mode", "queue")]).await?;
+        if let Some(slot) = queue.queue.slots.iter().find(|s| s.nzo_id == job_id) {
+            let percent = slot.percentage.parse().unwrap_or(0.0);
+            return Ok(JobStatus::Downloading { percent });
+        }
+
+        let history: History = self.sabnzbd_call(&[("mode", "history")]).await?;
+        match history
+            .history
+            .slots
+            .into_iter()
+            .find(|s| s.nzo_id == job_id)
+        {
+            Some(slot) if slot.status == "Completed" => match slot.storage {
+                Some(storage) => Ok(JobStatus::Completed(PathBuf::from(storage))),
+                None => Err(UsenetError::InvalidResponse(
+                    "SABnzbd didn't report where the download is".to_string(),
+                )),
+            },
+            Some(slot) if slot.status == "Failed" => Ok(JobStatus::Failed(slot.fail_message)),
+            // Verifying, extracting, ...
+            Some(_) => Ok(JobStatus::Downloading { percent: 100.0 }),
+            None => Err(UsenetError::InvalidResponse(format!(
+                "SABnzbd has no job {}",
+                job_id
+            ))),
+        }
+    }
+
+    async fn nzbget_call<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, UsenetError> {
+        #[derive(Deserialize)]
+        struct RpcResponse<T> {
+            result: Option<T>,
+            error: Option<serde_json::Value>,
+        }
+
+        let (username, password) = self.apikey.split_once(':').unwrap_or((&self.apikey, ""));
+        let response = self
+            .client
+            .post(format!("{}/jsonrpc", self.base_url))
+            .basic_auth(username, Some(password))
+            .json(&json!({ "method": method, "params": params }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(UsenetError::InvalidResponse(format!(
+                "status: {}",
+                response.status()
+            )));
+        }
+
+        let body: RpcResponse<T> = response.json().await?;
+        match (body.result, body.error) {
+            (Some(result), None) => Ok(result),
+            (_, error) => Err(UsenetError::InvalidResponse(format!(
+                "{} failed: {}",
+                method,
+                error.unwrap_or_default()
+            ))),
+        }
+    }
+
+    async fn sabnzbd_call<T: for<'de> Deserialize<'de>>(
+        &self,
+        params: &[(&str, &str)],
+    ) -> Result<T, UsenetError> {
+        let response = self
+            .client
+            .get(format!("{}/api", self.base_url))
+            .query(params)
+            .query(&[("output", "json"), ("apikey", self.apikey.as_str())])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(UsenetError::InvalidResponse(format!(
+                "status: {}",
+                response.status()
+            )));
+        }
+
+        Ok(response.json().await?)
+    }
+}
+
+/// The largest video file in a finished download (the download itself if it's a file)
+pub fn largest_video_file(path: &Path) -> Result<PathBuf, UsenetError> {
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+
+    let mut largest: Option<(u64, PathBuf)> = None;
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)?.flatten() {
+            let entry_path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                dirs.push(entry_path);
+            } else if is_video_file(&entry.file_name().to_string_lossy())
+                && largest
+                    .as_ref()
+                    .is_none_or(|(size, _)| metadata.len() > *size)
+            {
+                largest = Some((metadata.len(), entry_path));
+            }
+        }
+    }
+
+    largest
+        .map(|(_, path)| path)
+        .ok_or_else(|| UsenetError::NoVideoFiles(path.to_path_buf()))
+}
+
+/// Serve a downloaded file over local HTTP with range requests, so players
+/// get a stream URL just like the ones librqbit hands out.
+/// The server runs until the returned handle is aborted.
+pub async fn serve_file(path: PathBuf) -> Result<(String, JoinHandle<()>), UsenetError> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "stream".to_string());
+    let url = format!("http://{}/{}", addr, urlencoding::encode(&name));
+
+    let handle = tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            let path = path.clone();
+            tokio::spawn(async move {
+                if let Err(e) = serve_request(socket, &path).await {
+                    debug!(error = %e, "file server request failed");
+                }
+            });
+        }
+    });
+    Ok((url, handle))
+}
+
+async fn serve_request(mut socket: TcpStream, path: &Path) -> std::io::Result<()> {
+    // Request line and headers, players send small requests
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = socket.read(&mut buf).await?;
+        if n == 0 || head.len() > 16 * 1024 {
+            return Ok(());
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let is_head = head.starts_with("HEAD ");
+
+    let mut file = tokio::fs::File::open(path).await?;
+    let len = file.metadata().await?.len();
+    let range = head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("range").then(|| value.trim())
+        })
+        .and_then(|value| parse_range(value, len));
+
+    let (status, start, end) = match range {
+        Some((start, end)) => ("206 Partial Content", start, end),
+        None if len == 0 => ("200 OK", 0, 0),
+        None => ("200 OK", 0, len - 1),
+    };
+    let body_len = if len == 0 { 0 } else { end - start + 1 };
+
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/octet-stream\r\nAccept-Ranges: bytes\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status, body_len
+    );
+    if range.is_some() {
+        response.push_str(&format!(
+            "Content-Range: bytes {}-{}/{}\r\n",
+            start, end, len
+        ));
+    }
+    response.push_str("\r\n");
+    socket.write_all(response.as_bytes()).await?;
+
+    if !is_head && body_len > 0 {
+        file.seek(std::io::SeekFrom::Start(start)).await?;
+        tokio::io::copy(&mut file.take(body_len), &mut socket).await?;
+    }
+    socket.shutdown().await
+}
+
+/// Parse a single "bytes=start-end" range against a file of `len` bytes
+fn parse_range(value: &str, len: u64) -> Option<(u64, u64)> {
+    let (start, end) = value.strip_prefix("bytes=")?.split_once('-')?;
+    let last = len.checked_sub(1)?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        // Suffix range: the last N bytes
+        ("", suffix) => (len.saturating_sub(suffix.parse().ok()?), last),
+        (start, "") => (start.parse().ok()?, last),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(last)),
+    };
+    (start <= end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn client(server: &MockServer, backend: UsenetBackend, apikey: &str) -> UsenetClient {
+        UsenetClient::new(&UsenetConfig {
+            backend,
+            url: server.uri(),
+            apikey: apikey.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_sabnzbd_submit_and_complete() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .and(query_param("mode", "addurl"))
+            .and(query_param("apikey", "key"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"status": true, "nzo_ids": ["SABnzbd_nzo_1"]})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .and(query_param("mode", "queue"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"queue": {"slots": []}})))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .and(query_param("mode", "history"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "history": {"slots": [
+                    {"nzo_id": "SABnzbd_nzo_1", "status": "Completed", "storage": "/downloads/Movie"}
+                ]}
+            })))
+            .mount(&server)
+            .await;
+
+        let client = client(&server, UsenetBackend::Sabnzbd, "key");
+        let id = client
+            .submit("https://indexer/get/1.nzb", "Movie")
+            .await
+            .unwrap();
+        assert_eq!(id, "SABnzbd_nzo_1");
+        assert_eq!(
+            client.status(&id).await.unwrap(),
+            JobStatus::Completed(PathBuf::from("/downloads/Movie"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_nzbget_progress_and_failure() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/jsonrpc"))
+            .and(body_partial_json(json!({"method": "listgroups"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": [{"NZBID": 7, "FileSizeMB": 1000.0, "RemainingSizeMB": 250.0}]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/jsonrpc"))
+            .and(body_partial_json(json!({"method": "history"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": [{"NZBID": 8, "Status": "FAILURE/PAR", "DestDir": ""}]
+            })))
+            .mount(&server)
+            .await;
+
+        let client = client(&server, UsenetBackend::Nzbget, "user:pass");
+        assert_eq!(
+            client.status("7").await.unwrap(),
+            JobStatus::Downloading { percent: 75.0 }
+        );
+        assert_eq!(
+            client.status("8").await.unwrap(),
+            JobStatus::Failed("FAILURE/PAR".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=900-", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=500-5000", 1000), Some((500, 999)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+    }
+
+    #[tokio::test]
+    async fn test_serve_file_ranges() {
+        let dir = std::env::temp_dir().join(format!("ferristream-usenet-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("Sample")).unwrap();
+        std::fs::write(dir.join("Movie.mkv"), b"0123456789").unwrap();
+        std::fs::write(dir.join("Sample/sample.mkv"), b"01").unwrap();
+        std::fs::write(dir.join("Movie.nfo"), b"012345678901234567890").unwrap();
+
+        let file = largest_video_file(&dir).unwrap();
+        assert!(file.ends_with("Movie.mkv"));
+
+        let (url, handle) = serve_file(file).await.unwrap();
+        let client = reqwest::Client::new();
+        let full = client.get(&url).send().await.unwrap();
+        assert_eq!(full.status(), 200);
+        assert_eq!(full.bytes().await.unwrap().as_ref(), b"0123456789");
+
+        let partial = client
+            .get(&url)
+            .header("Range", "bytes=2-4")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(partial.status(), 206);
+        assert_eq!(
+            partial.headers()["content-range"].to_str().unwrap(),
+            "bytes 2-4/10"
+        );
+        assert_eq!(partial.bytes().await.unwrap().as_ref(), b"234");
+
+        handle.abort();
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,105 @@
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use ferristream::anidb::{AnimeClient, MalClient};
+
+#[tokio::test]
+async fn test_mal_search_anime() {
+    let mock_server = MockServer::start().await;
+
+    let response_body = r#"{
+        "data": [
+            {
+                "mal_id": 20,
+                "title": "Naruto",
+                "episodes": 220,
+                "year": 2002,
+                "aired": {"from": "2002-10-03T00:00:00+00:00"}
+            },
+            {
+                "mal_id": 1735,
+                "title": "Naruto: Shippuuden",
+                "episodes": 500,
+                "year": null,
+                "aired": {"from": "2007-02-15T00:00:00+00:00"}
+            }
+        ]
+    }"#;
+
+    Mock::given(method("GET"))
+        .and(path("/anime"))
+        .and(query_param("q", "naruto"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(response_body))
+        .mount(&mock_server)
+        .await;
+
+    let client = MalClient::with_base_url(&mock_server.uri());
+
+    let results = client.search_anime("naruto").await.unwrap();
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].id, 20);
+    assert_eq!(results[0].year, Some(2002));
+    assert_eq!(results[0].episodes, Some(220));
+    // Year falls back to the airing start date
+    assert_eq!(results[1].year, Some(2007));
+}
+
+#[tokio::test]
+async fn test_mal_find_episodes_across_pages() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/anime"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            r#"{"data": [
+                {"mal_id": 20, "title": "Naruto", "episodes": 220, "year": 2002},
+                {"mal_id": 1735, "title": "Naruto: Shippuuden", "episodes": 500, "year": 2007}
+            ]}"#,
+        ))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/anime/1735/episodes"))
+        .and(query_param("page", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            r#"{
+                "pagination": {"last_visible_page": 2, "has_next_page": true},
+                "data": [
+                    {"mal_id": 1, "title": "Homecoming", "aired": "2007-02-15T00:00:00+00:00", "score": 4.5},
+                    {"mal_id": 2, "title": "The Akatsuki Makes Its Move", "aired": null, "score": null}
+                ]
+            }"#,
+        ))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/anime/1735/episodes"))
+        .and(query_param("page", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            r#"{
+                "pagination": {"last_visible_page": 2, "has_next_page": false},
+                "data": [{"mal_id": 101, "title": null, "aired": null, "score": null}]
+            }"#,
+        ))
+        .mount(&mock_server)
+        .await;
+
+    let client = AnimeClient::Mal(MalClient::with_base_url(&mock_server.uri()));
+
+    // The year picks Shippuuden over the first search result
+    let episodes = client.find_episodes("Naruto", Some(2007)).await.unwrap();
+
+    assert_eq!(episodes.len(), 3);
+    assert_eq!(episodes[0].episode_number, 1);
+    assert_eq!(episodes[0].air_date.as_deref(), Some("2007-02-15"));
+    assert_eq!(episodes[0].vote_average, Some(4.5));
+    assert_eq!(episodes[2].episode_number, 101);
+    assert_eq!(episodes[2].name, "Episode 101");
+    assert_eq!(
+        episodes[2].absolute_search_query("Naruto Shippuden"),
+        "Naruto Shippuden 101"
+    );
+}
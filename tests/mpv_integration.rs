@@ -0,0 +1,50 @@
+//! Talks to a real mpv over JSON IPC. Needs mpv on PATH:
+//! `cargo test --features mpv-integration --test mpv_integration`
+#![cfg(feature = "mpv-integration")]
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use ferristream::mpv;
+use ferristream::streaming::IpcEndpoint;
+use serde_json::json;
+
+#[tokio::test]
+async fn test_ipc_with_real_mpv() {
+    if which::which("mpv").is_err() {
+        eprintln!("mpv not found, skipping");
+        return;
+    }
+
+    let endpoint = IpcEndpoint::new_for_player();
+    let mut child = tokio::process::Command::new("mpv")
+        .args(["--idle=yes", "--no-config", "--really-quiet"])
+        .arg(endpoint.server_arg())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to start mpv");
+
+    // The socket/pipe shows up once mpv has started
+    let mut version = None;
+    for _ in 0..50 {
+        version = mpv::get_property(&endpoint, "mpv-version").await;
+        if version.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let volume_set = mpv::set_property(&endpoint, "volume", json!(50)).await;
+    let volume = mpv::get_property(&endpoint, "volume").await;
+
+    let _ = child.kill().await;
+    endpoint.cleanup();
+
+    let version = version.expect("mpv never answered over IPC");
+    assert!(version.as_str().unwrap_or_default().starts_with("mpv"));
+    assert!(volume_set);
+    assert_eq!(volume.and_then(|v| v.as_f64()), Some(50.0));
+    // Nothing is playing in idle mode
+    assert!(mpv::get_mpv_position(&endpoint).await.is_none());
+}
@@ -0,0 +1,462 @@
+use serde_json::json;
+use wiremock::matchers::{body_bytes, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use ferristream::streaming::{
+    AddPhase, Cleanup, Probe, RemoteRqbitBackend, StreamError, StreamingSession, TorrentValidation,
+    parse_stats,
+};
+
+const MAGNET: &str = "magnet:?xt=urn:btih:4344503b7e797ebf31582327a5baae35b11bda01";
+
+/// Mock an rqbit daemon that already manages torrent 0 and hands out `added_id`
+async fn mock_daemon(added_id: u64) -> MockServer {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "server": "rqbit",
+            "version": "8.0.0"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/torrents"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "torrents": [{"id": 0, "info_hash": "a1b2c3d4e5f60718293a4b5c6d7e8f9012345678"}]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/torrents"))
+        .and(query_param("overwrite", "true"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": added_id })))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path(format!("/torrents/{}", added_id)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "name": "Blade.Runner.2049.2017.1080p.BluRay.x264-SPARKS",
+            "files": [
+                {"name": "Blade.Runner.2049.2017.1080p.BluRay.x264-SPARKS.mkv", "length": 12_000_000_000u64},
+                {"name": "Blade.Runner.2049.2017.1080p.BluRay.x264-SPARKS.en.srt", "length": 90_000},
+                {"name": "sample.mkv", "length": 50_000_000}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    mock_server
+}
+
+#[tokio::test]
+async fn test_daemon_version() {
+    let mock_server = mock_daemon(1).await;
+
+    let version = RemoteRqbitBackend::new(&format!("{}/", mock_server.uri()))
+        .version()
+        .await
+        .unwrap();
+
+    assert_eq!(version, "8.0.0");
+}
+
+#[tokio::test]
+async fn test_daemon_not_rqbit() {
+    let mock_server = MockServer::start().await;
+
+    let result = StreamingSession::connect(&mock_server.uri()).await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_daemon_add_and_cleanup_own_torrent() {
+    let mock_server = mock_daemon(1).await;
+
+    Mock::given(method("POST"))
+        .and(path("/torrents/1/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/torrents/0/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let session = StreamingSession::connect(&mock_server.uri()).await.unwrap();
+    let info = session.add_torrent(MAGNET).await.unwrap();
+
+    assert_eq!(info.id, 1);
+    assert_eq!(info.video_files.len(), 1);
+    assert_eq!(info.extra_files[0].name, "sample.mkv");
+    assert_eq!(
+        info.selected_file.name,
+        "Blade.Runner.2049.2017.1080p.BluRay.x264-SPARKS.mkv"
+    );
+    assert_eq!(
+        info.selected_file.stream_url,
+        format!("{}/torrents/1/stream/0", mock_server.uri())
+    );
+    assert_eq!(info.subtitle_files.len(), 1);
+
+    assert_eq!(session.cleanup().await, Cleanup::Daemon);
+}
+
+#[tokio::test]
+async fn test_daemon_cleanup_keeps_existing_torrent() {
+    // The daemon already had this torrent, so it isn't ours to remove
+    let mock_server = mock_daemon(0).await;
+
+    Mock::given(method("POST"))
+        .and(path("/torrents/0/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let session = StreamingSession::connect(&mock_server.uri()).await.unwrap();
+    let info = session.add_torrent(MAGNET).await.unwrap();
+    assert_eq!(info.id, 0);
+
+    assert_eq!(session.cleanup().await, Cleanup::Daemon);
+}
+
+#[tokio::test]
+async fn test_daemon_cleanup_claimed_torrent() {
+    // Ours from before a restart, the daemon kept it
+    let mock_server = mock_daemon(0).await;
+
+    Mock::given(method("POST"))
+        .and(path("/torrents/0/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let session = StreamingSession::connect(&mock_server.uri()).await.unwrap();
+    let info = session.add_torrent(MAGNET).await.unwrap();
+    session.claim(info.id);
+
+    assert_eq!(session.cleanup().await, Cleanup::Daemon);
+}
+
+#[tokio::test]
+async fn test_daemon_race_removes_superseded_torrents() {
+    const SD_MAGNET: &str = "magnet:?xt=urn:btih:5555503b7e797ebf31582327a5baae35b11bda01";
+    let mock_server = mock_daemon(1).await;
+
+    Mock::given(method("POST"))
+        .and(path("/torrents"))
+        .and(body_bytes(SD_MAGNET.as_bytes()))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": 2 })))
+        .with_priority(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/torrents/2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "name": "Blade.Runner.2049.2017.480p.DVDRip",
+            "files": [{"name": "Blade.Runner.2049.2017.480p.DVDRip.mkv", "length": 700_000_000}]
+        })))
+        .mount(&mock_server)
+        .await;
+    // Neither has the preferred quality, the 480p one loses to the 1080p one
+    // whichever connects first
+    Mock::given(method("POST"))
+        .and(path("/torrents/2/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/torrents/1/delete"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let session = StreamingSession::connect(&mock_server.uri()).await.unwrap();
+    let validation = TorrentValidation {
+        quality_preference: Some("2160p".to_string()),
+        ..TorrentValidation::new(vec!["blade".to_string()], Some(2017))
+    };
+    let (idx, info) = session
+        .race_torrents(
+            vec![SD_MAGNET.to_string(), MAGNET.to_string()],
+            Some(validation),
+            2,
+            tokio_util::sync::CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!((idx, info.id), (1, 1));
+}
+
+#[tokio::test]
+async fn test_daemon_add_reports_progress() {
+    let mock_server = mock_daemon(1).await;
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let session = StreamingSession::connect(&mock_server.uri())
+        .await
+        .unwrap()
+        .with_add_progress(progress_tx);
+    session.add_torrent(MAGNET).await.unwrap();
+
+    // The daemon's DHT isn't ours to count peers on
+    let mut phases = Vec::new();
+    while let Ok(phase) = progress_rx.try_recv() {
+        phases.push(phase);
+    }
+    assert_eq!(
+        phases,
+        vec![AddPhase::ResolvingMagnet, AddPhase::Initializing]
+    );
+
+    assert_eq!(session.cleanup().await, Cleanup::Daemon);
+}
+
+#[tokio::test]
+async fn test_daemon_metadata_timeout() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "server": "rqbit",
+            "version": "8.0.0"
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/torrents"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "torrents": [] })))
+        .mount(&mock_server)
+        .await;
+    // Resolving the magnet takes longer than we're willing to wait
+    Mock::given(method("POST"))
+        .and(path("/torrents"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "id": 1 }))
+                .set_delay(std::time::Duration::from_secs(10)),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let session = StreamingSession::connect(&mock_server.uri())
+        .await
+        .unwrap()
+        .with_metadata_timeout(std::time::Duration::from_secs(1));
+    let started = std::time::Instant::now();
+    let result = session.add_torrent(MAGNET).await;
+
+    assert!(matches!(result, Err(StreamError::MetadataTimeout)));
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_daemon_add_local_torrent_file() {
+    let mock_server = mock_daemon(1).await;
+
+    // The file's bytes are posted as they are
+    let torrent = b"d8:announce27:udp://tracker.example:13374:infod4:name4:teste".to_vec();
+    Mock::given(method("POST"))
+        .and(path("/torrents"))
+        .and(body_bytes(torrent.clone()))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": 1 })))
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let file =
+        std::env::temp_dir().join(format!("ferristream-local-{}.torrent", std::process::id()));
+    std::fs::write(&file, &torrent).unwrap();
+
+    let session = StreamingSession::connect(&mock_server.uri()).await.unwrap();
+    let info = session.add_torrent(&file.to_string_lossy()).await.unwrap();
+    assert_eq!(info.id, 1);
+    assert_eq!(info.name, "Blade.Runner.2049.2017.1080p.BluRay.x264-SPARKS");
+
+    // A missing file is an error, not a magnet
+    std::fs::remove_file(&file).unwrap();
+    assert!(session.add_torrent(&file.to_string_lossy()).await.is_err());
+
+    assert_eq!(session.cleanup().await, Cleanup::Daemon);
+}
+
+#[test]
+fn test_parse_stats_v8() {
+    let stats = parse_stats(include_str!("fixtures/rqbit/stats_v8.json")).unwrap();
+
+    assert_eq!(stats.downloaded_bytes, 1_073_831_824);
+    assert_eq!(stats.uploaded_bytes, 461_708_083);
+    assert_eq!(stats.total_bytes, 12_000_090_000);
+    // Speeds are reported in MiB/s
+    assert_eq!(stats.download_speed, 4_718_592);
+    assert_eq!(stats.upload_speed, 262_144);
+    assert_eq!(stats.peers_connected, 27);
+}
+
+#[test]
+fn test_parse_stats_v5() {
+    // No top-level uploaded_bytes, the snapshot has it
+    let stats = parse_stats(include_str!("fixtures/rqbit/stats_v5.json")).unwrap();
+
+    assert_eq!(stats.downloaded_bytes, 524_288_000);
+    assert_eq!(stats.uploaded_bytes, 104_857_600);
+    assert_eq!(stats.total_bytes, 2_147_483_648);
+    assert_eq!(stats.upload_speed, 524_288);
+    assert_eq!(stats.peers_connected, 8);
+}
+
+#[test]
+fn test_parse_stats_without_live() {
+    let stats = parse_stats(include_str!("fixtures/rqbit/stats_v8_paused.json")).unwrap();
+
+    assert_eq!(stats.uploaded_bytes, 3_000_000_000);
+    assert_eq!(stats.progress_percent(), 100.0);
+    assert_eq!((stats.download_speed, stats.peers_connected), (0, 0));
+
+    assert!(parse_stats(r#"{"state": "live"}"#).is_none());
+    assert!(parse_stats("not json").is_none());
+}
+
+#[tokio::test]
+async fn test_daemon_stats() {
+    let mock_server = mock_daemon(1).await;
+    Mock::given(method("GET"))
+        .and(path("/torrents/1/stats/v1"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string(include_str!("fixtures/rqbit/stats_v8.json")),
+        )
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/torrents/2/stats/v1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "unknown": true })))
+        .mount(&mock_server)
+        .await;
+
+    let session = StreamingSession::connect(&mock_server.uri()).await.unwrap();
+
+    let stats = session.get_stats(1).await.unwrap();
+    assert_eq!(stats.uploaded_bytes, 461_708_083);
+
+    // An answer in an unknown layout still means the daemon is alive
+    let stats = session.get_stats(2).await.unwrap();
+    assert_eq!(stats.uploaded_bytes, 0);
+
+    assert!(session.get_stats(3).await.is_none());
+}
+
+#[tokio::test]
+async fn test_daemon_probe() {
+    let mock_server = mock_daemon(1).await;
+    Mock::given(method("POST"))
+        .and(path("/torrents"))
+        .and(query_param("list_only", "true"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": null,
+            "seen_peers": ["10.0.0.1:6881", "10.0.0.2:6881", "10.0.0.3:51413"]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let session = StreamingSession::connect(&mock_server.uri()).await.unwrap();
+    let probe = session
+        .probe(MAGNET, std::time::Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(
+        probe,
+        Probe {
+            peers: 3,
+            metadata_pending: false
+        }
+    );
+
+    // Nothing was added, so there's nothing to remove
+    let requests = mock_server.received_requests().await.unwrap();
+    assert!(
+        !requests
+            .iter()
+            .any(|r| r.url.query_pairs().any(|(key, _)| key == "overwrite"))
+    );
+}
+
+#[tokio::test]
+async fn test_daemon_probe_metadata_pending() {
+    let mock_server = mock_daemon(1).await;
+    Mock::given(method("POST"))
+        .and(path("/torrents"))
+        .and(query_param("list_only", "true"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "id": null, "seen_peers": [] }))
+                .set_delay(std::time::Duration::from_secs(5)),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let session = StreamingSession::connect(&mock_server.uri()).await.unwrap();
+    let probe = session
+        .probe(MAGNET, std::time::Duration::from_millis(200))
+        .await
+        .unwrap();
+    assert_eq!(
+        probe,
+        Probe {
+            peers: 0,
+            metadata_pending: true
+        }
+    );
+}
+
+/// `/haves` as librqbit prints it, with `have` pieces downloaded out of `bits`
+fn haves_body(have: usize, bits: usize) -> String {
+    let list: Vec<&str> = (0..bits)
+        .map(|i| if i < have { "1" } else { "0" })
+        .collect();
+    format!(
+        "BitSlice<u8, bitvec::order::Msb0> {{ addr: 0x7f3a2c00a010, head: 000, bits: {} }} [{}]",
+        bits,
+        list.join(", ")
+    )
+}
+
+#[tokio::test]
+async fn test_daemon_piece_map() {
+    let mock_server = mock_daemon(1).await;
+    // 16 MiB pieces: 719 of them, padded to 720 bits
+    Mock::given(method("GET"))
+        .and(path("/torrents/1/haves"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(haves_body(100, 720)))
+        .mount(&mock_server)
+        .await;
+
+    let session = StreamingSession::connect(&mock_server.uri()).await.unwrap();
+
+    let movie = session.get_piece_map(1, 0).await.unwrap();
+    assert_eq!(movie.pieces.len(), 716);
+    assert_eq!(movie.pieces.iter().filter(|&&p| p).count(), 100);
+
+    // The sample at the end starts inside the movie's last piece
+    let sample = session.get_piece_map(1, 2).await.unwrap();
+    assert_eq!(sample.pieces.len(), 4);
+    assert!(sample.pieces.iter().all(|&p| !p));
+
+    assert!(session.get_piece_map(1, 5).await.is_none());
+    assert!(session.get_piece_map(2, 0).await.is_none());
+}
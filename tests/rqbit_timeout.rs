@@ -0,0 +1,62 @@
+//! Runs on its own: the HTTP timeout it sets is global to the test binary
+
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use ferristream::config::NetworkConfig;
+use ferristream::streaming::StreamingSession;
+
+const MAGNET: &str = "magnet:?xt=urn:btih:4344503b7e797ebf31582327a5baae35b11bda01";
+
+#[tokio::test]
+async fn test_daemon_add_outlasts_request_timeout() {
+    ferristream::http::configure(&NetworkConfig {
+        timeout_secs: 1,
+        ..Default::default()
+    })
+    .unwrap();
+
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "server": "rqbit",
+            "version": "8.0.0"
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/torrents"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "torrents": [] })))
+        .mount(&mock_server)
+        .await;
+    // Resolving the magnet takes longer than any other request may
+    Mock::given(method("POST"))
+        .and(path("/torrents"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "id": 1 }))
+                .set_delay(std::time::Duration::from_secs(3)),
+        )
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/torrents/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "name": "Blade.Runner.2049.2017.1080p.BluRay.x264-SPARKS",
+            "files": [
+                {"name": "Blade.Runner.2049.2017.1080p.BluRay.x264-SPARKS.mkv", "length": 12_000_000_000u64}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let session = StreamingSession::connect(&mock_server.uri())
+        .await
+        .unwrap()
+        .with_metadata_timeout(std::time::Duration::from_secs(10));
+    let info = session.add_torrent(MAGNET).await.unwrap();
+
+    assert_eq!(info.id, 1);
+}
@@ -0,0 +1,440 @@
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use ferristream::streaming::SearchContext;
+use ferristream::torznab::{
+    IndexerCaps, SearchRequest, TorrentResult, TorznabClient, TorznabError, parse_caps_xml,
+};
+
+/// Serve a recorded indexer response from tests/fixtures/torznab at /1/api
+async fn serve_fixture(mock_server: &MockServer, body: &str, status: u16) {
+    Mock::given(method("GET"))
+        .and(path("/1/api"))
+        .and(query_param("t", "search"))
+        .and(query_param("apikey", "test-key"))
+        .respond_with(
+            ResponseTemplate::new(status).set_body_raw(body.to_owned(), "application/rss+xml"),
+        )
+        .mount(mock_server)
+        .await;
+}
+
+async fn search_fixture(body: &str, status: u16) -> Result<Vec<TorrentResult>, TorznabError> {
+    let mock_server = MockServer::start().await;
+    serve_fixture(&mock_server, body, status).await;
+
+    TorznabClient::new()
+        .search(
+            &mock_server.uri(),
+            "test-key",
+            1,
+            "Indexer",
+            &SearchRequest::new("query", SearchContext::default()),
+            Some(&[2000, 5000]),
+        )
+        .await
+}
+
+#[tokio::test]
+async fn test_search_prowlarr_feed() {
+    let results = search_fixture(include_str!("fixtures/torznab/prowlarr_movies.xml"), 200)
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 2);
+
+    let first = &results[0];
+    assert_eq!(
+        first.title,
+        "Blade.Runner.2049.2017.1080p.BluRay.x264-SPARKS"
+    );
+    assert_eq!(first.size, Some(12_884_901_888));
+    assert_eq!(first.seeders, Some(512));
+    // Prowlarr only reports peers, leechers are derived from it
+    assert_eq!(first.leechers, Some(28));
+    assert_eq!(
+        first.infohash.as_deref(),
+        Some("4344503b7e797ebf31582327a5baae35b11bda01")
+    );
+    assert_eq!(
+        first.magnet_url.as_deref(),
+        Some(
+            "magnet:?xt=urn:btih:4344503b7e797ebf31582327a5baae35b11bda01&dn=Blade.Runner.2049.2017.1080p.BluRay.x264-SPARKS&tr=udp%3a%2f%2ftracker.example.org%3a1337%2fannounce"
+        )
+    );
+    assert_eq!(
+        first.link.as_deref(),
+        Some("http://localhost:9696/1/download?apikey=abc&link=eyJhbGciOi&file=Blade.Runner")
+    );
+    assert_eq!(first.categories, vec![2000, 2040, 100042]);
+    assert_eq!(first.indexer, "Indexer");
+
+    let second = &results[1];
+    assert_eq!(
+        second.title,
+        "Blade Runner 2049 (2017) [2160p] [4K] [HDR] & Extras"
+    );
+    assert_eq!(second.size, Some(64_424_509_440));
+    assert_eq!(second.seeders, Some(87));
+    assert_eq!(second.leechers, Some(12));
+    assert!(second.magnet_url.is_none());
+}
+
+#[tokio::test]
+async fn test_search_jackett_feed() {
+    let results = search_fixture(include_str!("fixtures/torznab/jackett_tv.xml"), 200)
+        .await
+        .unwrap();
+
+    // The item without a title is dropped
+    assert_eq!(results.len(), 2);
+
+    let first = &results[0];
+    assert_eq!(first.title, "Breaking Bad S01E01 720p HDTV x264-CTU");
+    assert_eq!(first.size, Some(734_003_200));
+    assert_eq!(first.seeders, Some(35));
+    assert_eq!(first.leechers, Some(2));
+    assert_eq!(
+        first.infohash.as_deref(),
+        Some("a1b2c3d4e5f60718293a4b5c6d7e8f9012345678")
+    );
+    assert!(first.magnet_url.is_none());
+    assert_eq!(first.categories, vec![5040]);
+
+    let second = &results[1];
+    assert_eq!(
+        second.title,
+        "Breaking.Bad.S01E02.Cat's.in.the.Bag.720p.HDTV"
+    );
+    assert_eq!(second.size, Some(700_000_000));
+    assert_eq!(second.seeders, Some(0));
+    assert_eq!(second.leechers, None);
+}
+
+#[tokio::test]
+async fn test_search_empty_channel() {
+    let results = search_fixture(include_str!("fixtures/torznab/empty_channel.xml"), 200)
+        .await
+        .unwrap();
+
+    assert!(results.is_empty());
+}
+
+#[tokio::test]
+async fn test_search_indexer_error() {
+    let err = search_fixture(include_str!("fixtures/torznab/error_api_key.xml"), 200)
+        .await
+        .unwrap_err();
+
+    match err {
+        TorznabError::InvalidResponse(msg) => {
+            assert!(msg.contains("100"), "unexpected message: {msg}");
+            assert!(msg.contains("Invalid API Key"), "unexpected message: {msg}");
+        }
+        other => panic!("expected InvalidResponse, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_search_truncated_feed() {
+    let result = search_fixture(include_str!("fixtures/torznab/truncated.xml"), 200).await;
+
+    assert!(
+        result.is_err(),
+        "truncated feed should not parse: {result:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_search_html_error_page() {
+    let result = search_fixture(include_str!("fixtures/torznab/html_error.html"), 200).await;
+
+    assert!(
+        matches!(result, Err(TorznabError::InvalidResponse(_))),
+        "HTML page should be rejected: {result:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_search_http_error_status() {
+    let result = search_fixture(include_str!("fixtures/torznab/html_error.html"), 502).await;
+
+    match result {
+        Err(TorznabError::InvalidResponse(msg)) => assert!(msg.contains("502")),
+        other => panic!("expected status error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_search_sends_categories() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/7/api"))
+        .and(query_param("q", "blade runner"))
+        .and(query_param("cat", "2000,5000"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            include_str!("fixtures/torznab/empty_channel.xml"),
+            "application/rss+xml",
+        ))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let results = TorznabClient::new()
+        .search(
+            &mock_server.uri(),
+            "test-key",
+            7,
+            "Indexer",
+            &SearchRequest::new("blade runner", SearchContext::default()),
+            Some(&[2000, 5000]),
+        )
+        .await
+        .unwrap();
+
+    assert!(results.is_empty());
+}
+
+/// Serve caps for indexer 1
+async fn serve_caps(mock_server: &MockServer, body: &str, status: u16) {
+    Mock::given(method("GET"))
+        .and(path("/1/api"))
+        .and(query_param("t", "caps"))
+        .respond_with(
+            ResponseTemplate::new(status).set_body_raw(body.to_owned(), "application/xml"),
+        )
+        .expect(1)
+        .mount(mock_server)
+        .await;
+}
+
+fn episode_request() -> SearchRequest {
+    SearchRequest::new(
+        "Severance S02E03",
+        SearchContext {
+            title: "Severance".to_string(),
+            season: Some(2),
+            episode: Some(3),
+            media_type: Some("tv".to_string()),
+            tmdb_id: Some(95396),
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn test_parse_caps_prowlarr() {
+    let caps = parse_caps_xml(include_str!("fixtures/torznab/caps_prowlarr.xml")).unwrap();
+
+    assert_eq!(caps.search.as_deref(), Some(&["q".to_string()][..]));
+    let tv = caps.tv_search.unwrap();
+    for param in ["q", "season", "ep", "tmdbid"] {
+        assert!(tv.iter().any(|p| p == param), "missing {param}");
+    }
+    assert!(caps.movie_search.unwrap().contains(&"tmdbid".to_string()));
+}
+
+#[test]
+fn test_parse_caps_variants() {
+    let caps = parse_caps_xml(include_str!("fixtures/torznab/caps_jackett.xml")).unwrap();
+    assert_eq!(
+        caps.tv_search,
+        Some(vec![
+            "q".to_string(),
+            "season".to_string(),
+            "ep".to_string(),
+            "tvdbid".to_string()
+        ])
+    );
+
+    // Unavailable searches are None, missing supportedParams means only q
+    let caps = parse_caps_xml(include_str!("fixtures/torznab/caps_structured_only.xml")).unwrap();
+    assert_eq!(
+        caps,
+        IndexerCaps {
+            search: None,
+            tv_search: Some(vec!["q".to_string()]),
+            movie_search: None,
+        }
+    );
+
+    assert!(parse_caps_xml(include_str!("fixtures/torznab/error_api_key.xml")).is_err());
+    assert!(parse_caps_xml(include_str!("fixtures/torznab/prowlarr_movies.xml")).is_err());
+    assert!(parse_caps_xml(include_str!("fixtures/torznab/html_error.html")).is_err());
+}
+
+#[tokio::test]
+async fn test_search_uses_tvsearch_from_caps() {
+    let mock_server = MockServer::start().await;
+    serve_caps(
+        &mock_server,
+        include_str!("fixtures/torznab/caps_prowlarr.xml"),
+        200,
+    )
+    .await;
+
+    Mock::given(method("GET"))
+        .and(path("/1/api"))
+        .and(query_param("t", "tvsearch"))
+        .and(query_param("tmdbid", "95396"))
+        .and(query_param("season", "2"))
+        .and(query_param("ep", "3"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            include_str!("fixtures/torznab/jackett_tv.xml"),
+            "application/rss+xml",
+        ))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    // Caps are fetched once and reused
+    let client = TorznabClient::new();
+    for _ in 0..2 {
+        let results = client
+            .search(
+                &mock_server.uri(),
+                "test-key",
+                1,
+                "Indexer",
+                &episode_request(),
+                Some(&[5000]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+    }
+}
+
+#[tokio::test]
+async fn test_search_falls_back_to_text_without_caps() {
+    let mock_server = MockServer::start().await;
+    serve_caps(
+        &mock_server,
+        include_str!("fixtures/torznab/html_error.html"),
+        200,
+    )
+    .await;
+
+    Mock::given(method("GET"))
+        .and(path("/1/api"))
+        .and(query_param("t", "search"))
+        .and(query_param("q", "Severance S02E03"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            include_str!("fixtures/torznab/jackett_tv.xml"),
+            "application/rss+xml",
+        ))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let results = TorznabClient::new()
+        .search(
+            &mock_server.uri(),
+            "test-key",
+            1,
+            "Indexer",
+            &episode_request(),
+            Some(&[5000]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 2);
+}
+
+#[tokio::test]
+async fn test_search_recovers_from_transient_errors() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/1/api"))
+        .and(query_param("t", "search"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+    serve_fixture(
+        &mock_server,
+        include_str!("fixtures/torznab/jackett_tv.xml"),
+        200,
+    )
+    .await;
+
+    let results = TorznabClient::new()
+        .search(
+            &mock_server.uri(),
+            "test-key",
+            1,
+            "Indexer",
+            &SearchRequest::new("breaking bad", SearchContext::default()),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 2);
+}
+
+#[tokio::test]
+async fn test_search_movie_by_imdb_id() {
+    let mock_server = MockServer::start().await;
+    serve_caps(
+        &mock_server,
+        include_str!("fixtures/torznab/caps_jackett.xml"),
+        200,
+    )
+    .await;
+
+    Mock::given(method("GET"))
+        .and(path("/1/api"))
+        .and(query_param("t", "movie"))
+        .and(query_param("imdbid", "1160419"))
+        .and(query_param("cat", "2000"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            include_str!("fixtures/torznab/prowlarr_movies.xml"),
+            "application/rss+xml",
+        ))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let client = TorznabClient::new();
+    let direct = client
+        .search_by_imdb_id(
+            &mock_server.uri(),
+            "test-key",
+            1,
+            "Indexer",
+            "tt1160419",
+            Some(&[2000]),
+        )
+        .await
+        .unwrap();
+    assert!(!direct.is_empty());
+    assert_eq!(direct[0].indexer, "Indexer");
+
+    // The caps only take an IMDB id for movies, so that's what gets searched
+    let request = SearchRequest::new(
+        "Dune 2021",
+        SearchContext {
+            title: "Dune".to_string(),
+            year: Some(2021),
+            media_type: Some("movie".to_string()),
+            tmdb_id: Some(438631),
+            imdb_id: Some("tt1160419".to_string()),
+            ..Default::default()
+        },
+    );
+    let planned = client
+        .search(
+            &mock_server.uri(),
+            "test-key",
+            1,
+            "Indexer",
+            &request,
+            Some(&[2000]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(planned.len(), direct.len());
+}
@@ -0,0 +1,143 @@
+use serde_json::json;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use ferristream::config::{UsenetBackend, UsenetConfig};
+use ferristream::streaming::{AddPhase, Cleanup, StreamingSession};
+use ferristream::usenet::UsenetClient;
+use tokio_util::sync::CancellationToken;
+
+const NZB_URL: &str = "http://prowlarr/2/download?file=Blade.Runner.2049.nzb";
+
+/// Mock an rqbit daemon with no torrents, the session streams through
+async fn mock_daemon() -> MockServer {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "server": "rqbit",
+            "version": "8.0.0"
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/torrents"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "torrents": [] })))
+        .mount(&mock_server)
+        .await;
+    mock_server
+}
+
+/// Mock SABnzbd: the job is queued at 40% once, then finished into `storage`
+async fn mock_sabnzbd(storage: &std::path::Path) -> MockServer {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api"))
+        .and(query_param("mode", "addurl"))
+        .and(query_param("name", NZB_URL))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"status": true, "nzo_ids": ["SABnzbd_nzo_1"]})),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api"))
+        .and(query_param("mode", "queue"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "queue": {"slots": [{"nzo_id": "SABnzbd_nzo_1", "percentage": "40"}]}
+        })))
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api"))
+        .and(query_param("mode", "queue"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"queue": {"slots": []}})))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api"))
+        .and(query_param("mode", "history"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "history": {"slots": [{
+                "nzo_id": "SABnzbd_nzo_1",
+                "status": "Completed",
+                "storage": storage.to_string_lossy()
+            }]}
+        })))
+        .mount(&mock_server)
+        .await;
+    mock_server
+}
+
+#[tokio::test]
+async fn test_nzb_streams_once_downloaded() {
+    let storage =
+        std::env::temp_dir().join(format!("ferristream-usenet-stream-{}", std::process::id()));
+    std::fs::create_dir_all(&storage).unwrap();
+    std::fs::write(storage.join("Blade.Runner.2049.mkv"), b"0123456789").unwrap();
+    std::fs::write(storage.join("Blade.Runner.2049.nfo"), b"release notes").unwrap();
+
+    let daemon = mock_daemon().await;
+    let sabnzbd = mock_sabnzbd(&storage).await;
+    let usenet = UsenetClient::new(&UsenetConfig {
+        backend: UsenetBackend::Sabnzbd,
+        url: sabnzbd.uri(),
+        apikey: "key".to_string(),
+    });
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+    let session = StreamingSession::connect(&daemon.uri())
+        .await
+        .unwrap()
+        .with_add_progress(progress_tx);
+
+    let info = session
+        .add_nzb(
+            &usenet,
+            NZB_URL,
+            "Blade.Runner.2049",
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        progress_rx.try_recv().unwrap(),
+        AddPhase::Downloading { percent: 40 }
+    );
+    assert_eq!(info.name, "Blade.Runner.2049");
+    assert_eq!(info.video_files.len(), 1);
+    assert_eq!(info.selected_file.name, "Blade.Runner.2049.mkv");
+    assert!(session.is_served(info.id));
+
+    // Players get a stream URL that serves the file with range requests
+    let client = reqwest::Client::new();
+    let partial = client
+        .get(&info.selected_file.stream_url)
+        .header("Range", "bytes=2-4")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(partial.status(), 206);
+    assert_eq!(partial.bytes().await.unwrap().as_ref(), b"234");
+
+    // Nothing left to download, so playback starts right away
+    let stats = session.get_stats(info.id).await.unwrap();
+    assert_eq!(stats.progress_percent(), 100.0);
+
+    assert_eq!(session.cleanup().await, Cleanup::Daemon);
+    tokio::task::yield_now().await;
+    assert!(
+        client
+            .get(&info.selected_file.stream_url)
+            .send()
+            .await
+            .is_err()
+    );
+    // The download belongs to the downloader
+    assert!(storage.join("Blade.Runner.2049.mkv").exists());
+
+    std::fs::remove_dir_all(&storage).ok();
+}